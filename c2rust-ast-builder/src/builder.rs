@@ -340,6 +340,11 @@ impl Builder {
         Builder { abi: abi, ..self }
     }
 
+    pub fn generic_over(mut self, param: GenericParam) -> Self {
+        self.generics.params.push(param);
+        self
+    }
+
     pub fn span<S: Make<Span>>(self, span: S) -> Self {
         let span = span.make(&self);
         Builder { span: span, ..self }
@@ -795,6 +800,22 @@ impl Builder {
         })
     }
 
+    /// Build a half-open range expression, e.g., `lo..hi`
+    pub fn range_expr<E1, E2>(self, lo: Option<E1>, hi: Option<E2>) -> P<Expr>
+    where
+        E1: Make<P<Expr>>,
+        E2: Make<P<Expr>>,
+    {
+        let lo = lo.map(|e| e.make(&self));
+        let hi = hi.map(|e| e.make(&self));
+        P(Expr {
+            id: self.id,
+            kind: ExprKind::Range(lo, hi, RangeLimits::HalfOpen),
+            span: self.span,
+            attrs: self.attrs.into(),
+        })
+    }
+
     pub fn path_expr<Pa>(self, path: Pa) -> P<Expr>
     where
         Pa: Make<Path>,
@@ -1717,6 +1738,34 @@ impl Builder {
         )
     }
 
+    pub fn impl_trait_item<Pa, T>(self, trait_path: Pa, ty: T, items: Vec<ImplItem>) -> P<Item>
+    where
+        Pa: Make<Path>,
+        T: Make<P<Ty>>,
+    {
+        let trait_path = trait_path.make(&self);
+        let ty = ty.make(&self);
+        Self::item(
+            Ident::invalid(),
+            self.attrs,
+            self.vis,
+            self.span,
+            self.id,
+            ItemKind::Impl(
+                self.unsafety,
+                ImplPolarity::Positive,
+                Defaultness::Final,
+                self.generics,
+                Some(TraitRef {
+                    path: trait_path,
+                    ref_id: self.id,
+                }),
+                ty,
+                items,
+            ),
+        )
+    }
+
     pub fn extern_crate_item<I>(self, name: I, rename: Option<I>) -> P<Item>
     where
         I: Make<Ident>,
@@ -1866,6 +1915,34 @@ impl Builder {
         }
     }
 
+    pub fn method_impl_item<I, D, B>(self, name: I, decl: D, block: B) -> ImplItem
+    where
+        I: Make<Ident>,
+        D: Make<P<FnDecl>>,
+        B: Make<P<Block>>,
+    {
+        let name = name.make(&self);
+        let decl = decl.make(&self);
+        let block = block.make(&self);
+        let header = FnHeader {
+            unsafety: self.unsafety,
+            asyncness: dummy_spanned(IsAsync::NotAsync),
+            constness: dummy_spanned(self.constness),
+            abi: self.abi,
+        };
+        let kind = ImplItemKind::Method(MethodSig { header, decl }, block);
+        Self::impl_item_(
+            name,
+            self.attrs,
+            self.vis,
+            Defaultness::Final,
+            self.generics,
+            self.span,
+            self.id,
+            kind,
+        )
+    }
+
     pub fn mac_impl_item<M>(self, mac: M) -> ImplItem
     where
         M: Make<Mac>,
@@ -68,6 +68,19 @@ void cbor_encode_string_array(CborEncoder *encoder,
     cbor_encoder_close_container(encoder, &array);
 }
 
+// Encode the attribute names of a type declaration into an array, each
+// followed by its argument if the translator uses it
+void encode_type_decl_attrs(CborEncoder *array, const Decl *D) {
+    for (auto attr : D->attrs()) {
+        cbor_encode_text_stringz(array, attr->getSpelling());
+
+        if (auto *da = dyn_cast<DeprecatedAttr>(attr)) {
+            // Empty if there is no message
+            cbor_encode_string(array, da->getMessage().str());
+        }
+    }
+}
+
 std::string make_realpath(std::string const &path) {
     if (auto abs_path = realpath(path.c_str(), nullptr)) {
         auto result = std::string(abs_path);
@@ -78,6 +91,41 @@ std::string make_realpath(std::string const &path) {
         abort();
     }
 }
+
+// When a header is compiled as C++, only the declarations with C language
+// linkage (i.e., inside `extern "C"`) can be used from Rust without name
+// mangling. C-compatible types are kept as well, since the C declarations
+// may refer to them.
+bool isExportedTopLevelDecl(const Decl *D) {
+    if (!D->getASTContext().getLangOpts().CPlusPlus)
+        return true;
+
+    if (auto LSD = dyn_cast<LinkageSpecDecl>(D))
+        return LSD->getLanguage() == LinkageSpecDecl::lang_c;
+    if (auto FD = dyn_cast<FunctionDecl>(D))
+        return FD->isExternC();
+    if (auto VD = dyn_cast<VarDecl>(D))
+        return VD->isExternC();
+    if (auto RD = dyn_cast<CXXRecordDecl>(D))
+        return !RD->hasDefinition() || RD->isCLike();
+    if (auto ED = dyn_cast<EnumDecl>(D))
+        return !ED->isScoped();
+    return isa<TypedefDecl>(D) || isa<EmptyDecl>(D);
+}
+
+// Collect the top-level declarations of a translation unit, looking through
+// `extern "C"` blocks
+void collectTopLevelDecls(const DeclContext *DC, std::vector<Decl *> &decls) {
+    for (auto d : DC->decls()) {
+        if (!isExportedTopLevelDecl(d))
+            continue;
+
+        if (auto LSD = dyn_cast<LinkageSpecDecl>(d))
+            collectTopLevelDecls(LSD, decls);
+        else
+            decls.push_back(d);
+    }
+}
 } // namespace
 
 class TranslateASTVisitor;
@@ -318,9 +366,36 @@ class TypeEncoder final : public TypeVisitor<TypeEncoder> {
         }
         // clang-format on
 
+        if (tag == TagTypeUnknown) {
+            // Name the type, so the translator can report which one it
+            // doesn't support
+            std::string name = T->getName(Context->getPrintingPolicy()).str();
+            encodeType(T, tag, [name](CborEncoder *local) {
+                cbor_encode_string(local, name);
+            });
+            return;
+        }
+
         encodeType(T, tag);
     }
 
+#if CLANG_VERSION_MAJOR >= 14
+    void VisitBitIntType(const BitIntType *T) {
+        encodeType(T, TagBitIntType, [T](CborEncoder *local) {
+            cbor_encode_boolean(local, T->isUnsigned());
+            cbor_encode_uint(local, T->getNumBits());
+        });
+    }
+#elif CLANG_VERSION_MAJOR >= 11
+    // `_BitInt(N)` was spelled `_ExtInt(N)` before clang 14
+    void VisitExtIntType(const ExtIntType *T) {
+        encodeType(T, TagBitIntType, [T](CborEncoder *local) {
+            cbor_encode_boolean(local, T->isUnsigned());
+            cbor_encode_uint(local, T->getNumBits());
+        });
+    }
+#endif // CLANG_VERSION_MAJOR
+
     // Clang represents function declarations with parameters as
     // `FunctionProtoType` instances whereas functions w/o parameters are
     // handled as `FunctionNoPrototype` instances. Note: we could handle both
@@ -444,6 +519,11 @@ class TranslateASTVisitor final
 
         /// Expressions that we have seen this macro expand to
         SmallPtrSet<Expr*, 10> Expressions;
+
+        /// For function-like macros, the subexpressions of each expansion
+        /// that the macro arguments expand to, along with the index of the
+        /// corresponding macro parameter
+        std::unordered_map<Expr*, std::vector<std::pair<unsigned, Expr*>>> Args;
     };
 
     ASTContext *Context;
@@ -461,6 +541,15 @@ class TranslateASTVisitor final
     std::unordered_set<unsigned> macroCallSites;
     SmallVector<MacroInfo*, 1> curMacroExpansionStack;
 
+    // Label operands of `asm goto` statements, which are exported as part of
+    // the statement instead of as expressions.
+    std::unordered_set<AddrLabelExpr*> asmGotoLabels;
+
+    // Raw encodings of the locations of the unsupported decimal floating-point
+    // types already reported, as several declarations can share one type
+    // specifier.
+    std::unordered_set<unsigned> reportedDecimalTypes;
+
     // Returns true when a new entry is added to exportedTags
     bool markForExport(void *ptr, ASTEntryTag tag) {
         return exportedTags.emplace(ptr, tag).second;
@@ -638,6 +727,147 @@ class TranslateASTVisitor final
         return true;
     }
 
+    // Check if we can translate the function-like macro `mac` to a function,
+    // i.e., it doesn't stringize or paste its arguments and isn't variadic.
+    static bool isTranslatableFunctionMacro(const MacroInfo *mac) {
+        if (mac->isVariadic())
+            return false;
+        for (auto I = mac->tokens_begin(), E = mac->tokens_end(); I != E; ++I) {
+            if (I->isOneOf(tok::hash, tok::hashhash, tok::hashat))
+                return false;
+        }
+        return true;
+    }
+
+    // Get the file locations of the first and last tokens of each argument
+    // of the function-like macro call starting at `CallBegin`.
+    bool getMacroCallArgRanges(SourceLocation CallBegin,
+                               std::vector<SourceRange> &ArgRanges) const {
+        auto &Mgr = Context->getSourceManager();
+        if (!CallBegin.isFileID())
+            return false;
+
+        auto Decomposed = Mgr.getDecomposedLoc(CallBegin);
+        bool Invalid = false;
+        StringRef Buffer = Mgr.getBufferData(Decomposed.first, &Invalid);
+        if (Invalid)
+            return false;
+        Lexer Lex(Mgr.getLocForStartOfFile(Decomposed.first),
+                  Context->getLangOpts(), Buffer.begin(),
+                  Buffer.data() + Decomposed.second, Buffer.end());
+
+        // Skip the macro name and the opening parenthesis
+        Token Tok;
+        Lex.LexFromRawLexer(Tok);
+        Lex.LexFromRawLexer(Tok);
+        if (!Tok.is(tok::l_paren))
+            return false;
+
+        unsigned Depth = 0;
+        SourceRange Arg;
+        while (true) {
+            Lex.LexFromRawLexer(Tok);
+            if (Tok.is(tok::eof))
+                return false;
+            if (Depth == 0 && Tok.isOneOf(tok::comma, tok::r_paren)) {
+                ArgRanges.push_back(Arg);
+                Arg = SourceRange();
+                if (Tok.is(tok::r_paren))
+                    return true;
+                continue;
+            }
+            if (Tok.is(tok::l_paren))
+                Depth++;
+            else if (Tok.is(tok::r_paren))
+                Depth--;
+            if (Arg.getBegin().isInvalid())
+                Arg.setBegin(Tok.getLocation());
+            Arg.setEnd(Tok.getLocation());
+        }
+    }
+
+    // Get the index of the macro call argument that `E` is the complete
+    // expansion of, or -1 if there is none.
+    int getMacroArgIndex(Expr *E, const std::vector<SourceRange> &ArgRanges) const {
+        auto &Mgr = Context->getSourceManager();
+        auto Begin = E->getSourceRange().getBegin();
+        auto End = E->getSourceRange().getEnd();
+        if (!Mgr.isMacroArgExpansion(Begin) || !Mgr.isMacroArgExpansion(End))
+            return -1;
+
+        // Map the locations back to the call site, where the arguments may
+        // themselves contain macro calls
+        while (Mgr.isMacroArgExpansion(Begin))
+            Begin = Mgr.getImmediateSpellingLoc(Begin);
+        while (Mgr.isMacroArgExpansion(End))
+            End = Mgr.getImmediateSpellingLoc(End);
+        Begin = Mgr.getExpansionLoc(Begin);
+#if CLANG_VERSION_MAJOR < 7
+        End = Mgr.getExpansionRange(End).second;
+#else // CLANG_VERSION_MAJOR >= 7
+        End = Mgr.getExpansionRange(End).getEnd();
+#endif
+        for (unsigned i = 0; i < ArgRanges.size(); i++) {
+            if (ArgRanges[i].getBegin() == Begin && ArgRanges[i].getEnd() == End)
+                return i;
+        }
+        return -1;
+    }
+
+    void findMacroArgs(Stmt *S, const std::vector<SourceRange> &ArgRanges,
+                       std::vector<std::pair<unsigned, Expr*>> &Args) const {
+        for (auto Child : S->children()) {
+            if (!Child)
+                continue;
+            if (auto E = dyn_cast<Expr>(Child)) {
+                int Index = getMacroArgIndex(E, ArgRanges);
+                if (Index >= 0) {
+                    Args.push_back(std::make_pair(unsigned(Index), E));
+                    continue;
+                }
+            }
+            findMacroArgs(Child, ArgRanges, Args);
+        }
+    }
+
+    // Find the subexpressions of `E` that the arguments of the call to the
+    // function-like macro `mac` at `CallBegin` expand to. Fails unless every
+    // occurrence of a parameter in the macro body expands to a complete
+    // expression.
+    bool getMacroArgs(MacroInfo *mac, SourceLocation CallBegin, Expr *E,
+                      std::vector<std::pair<unsigned, Expr*>> &Args) const {
+        std::vector<SourceRange> ArgRanges;
+        if (!getMacroCallArgRanges(CallBegin, ArgRanges))
+            return false;
+        // A call without arguments still has a single empty argument
+        if (mac->getNumParams() == 0 && ArgRanges.size() == 1 &&
+            ArgRanges[0].getBegin().isInvalid())
+            ArgRanges.clear();
+        if (ArgRanges.size() != mac->getNumParams())
+            return false;
+        for (auto &Arg : ArgRanges) {
+            if (Arg.getBegin().isInvalid())
+                return false;
+        }
+
+        std::vector<unsigned> Occurrences(mac->getNumParams(), 0);
+        for (auto I = mac->tokens_begin(), End = mac->tokens_end(); I != End; ++I) {
+            auto II = I->getIdentifierInfo();
+            int Index = II ? mac->getParameterNum(II) : -1;
+            if (Index >= 0)
+                Occurrences[Index]++;
+        }
+
+        findMacroArgs(E, ArgRanges, Args);
+        for (auto &Arg : Args)
+            Occurrences[Arg.first]--;
+        for (auto Count : Occurrences) {
+            if (Count != 0)
+                return false;
+        }
+        return true;
+    }
+
     static bool isScalarAsmType(QualType ty) {
         ty = ty.getCanonicalType();
         switch (ty->getTypeClass()) {
@@ -669,6 +899,16 @@ class TranslateASTVisitor final
     // Override the default behavior of the RecursiveASTVisitor
     bool shouldVisitImplicitCode() const { return true; }
 
+    // Skip the top-level C++ declarations we can't export, e.g., namespaces
+    // and functions with mangled names, along with everything inside them
+    bool TraverseDecl(Decl *D) {
+        if (D && !isa<TranslationUnitDecl>(D) &&
+            D->getDeclContext()->getRedeclContext()->isTranslationUnit() &&
+            !isExportedTopLevelDecl(D))
+            return true;
+        return RecursiveASTVisitor<TranslateASTVisitor>::TraverseDecl(D);
+    }
+
     // Return the filenames as a vector. Indices correspond to file IDs.
     const std::vector<std::pair<string, SourceLocation>> &getFiles() {
         // Iterate file include locations until fix point
@@ -708,8 +948,39 @@ class TranslateASTVisitor final
 
             auto range = SourceRange(Mac->getDefinitionLoc(), Mac->getDefinitionEndLoc());
             encode_entry_raw(Mac, tag, range, QualType(), false,
-                             false, false, childIds, [Name](CborEncoder *local) {
+                             false, false, childIds, [Name, Mac, &Info, &childIds](CborEncoder *local) {
                                  cbor_encode_string(local, Name.str());
+                                 if (!Mac->isFunctionLike())
+                                     return;
+
+                                 CborEncoder arrayEncoder;
+                                 cbor_encoder_create_array(local, &arrayEncoder,
+                                                           Mac->getNumParams());
+                                 for (auto Param : Mac->params()) {
+                                     cbor_encode_string(&arrayEncoder,
+                                                        Param->getName().str());
+                                 }
+                                 cbor_encoder_close_container(local, &arrayEncoder);
+
+                                 // Arguments of each expansion, in the same
+                                 // order as the children
+                                 cbor_encoder_create_array(local, &arrayEncoder,
+                                                           childIds.size());
+                                 for (auto Child : childIds) {
+                                     auto &Args = Info.Args[static_cast<Expr *>(Child)];
+                                     CborEncoder argsEncoder;
+                                     cbor_encoder_create_array(&arrayEncoder, &argsEncoder,
+                                                               Args.size());
+                                     for (auto &Arg : Args) {
+                                         CborEncoder argEncoder;
+                                         cbor_encoder_create_array(&argsEncoder, &argEncoder, 2);
+                                         cbor_encode_uint(&argEncoder, Arg.first);
+                                         cbor_encode_uint(&argEncoder, uintptr_t(Arg.second));
+                                         cbor_encoder_close_container(&argsEncoder, &argEncoder);
+                                     }
+                                     cbor_encoder_close_container(&arrayEncoder, &argsEncoder);
+                                 }
+                                 cbor_encoder_close_container(local, &arrayEncoder);
                              });
 
         }
@@ -955,22 +1226,33 @@ class TranslateASTVisitor final
     }
 
     // Encode ASM statements using the following encoding:
-    // Child IDs: inputs expressions, output expressions
+    // Child IDs: inputs expressions, output expressions, `asm goto` label
+    //            statements
     // Extras:
     //   Boolean true if volatile, false otherwise
     //   Assembly program fragment string
     //   List of input constraints
     //   List of output constraints
     //   List of clobbers
+    //   Boolean true if the target can translate `asm goto`, false otherwise
     //
     // The number of input and output expressions in the child id list will
-    // match the length of the corresponding constraint arrays.
+    // match the length of the corresponding constraint arrays. Any remaining
+    // children are the labels that an `asm goto` can jump to, in operand order.
     bool VisitGCCAsmStmt(GCCAsmStmt *E) {
 
         std::vector<void *> childIds;
         copy(E->begin_inputs(), E->end_inputs(), std::back_inserter(childIds));
         copy(E->begin_outputs(), E->end_outputs(),
              std::back_inserter(childIds));
+#if CLANG_VERSION_MAJOR >= 9
+        if (E->isAsmGoto()) {
+            for (AddrLabelExpr *L : E->labels()) {
+                childIds.push_back(L->getLabel()->getStmt());
+                asmGotoLabels.insert(L);
+            }
+        }
+#endif // CLANG_VERSION_MAJOR
 
         encode_entry(E, TagAsmStmt, childIds, [E, this](CborEncoder *local) {
             cbor_encode_boolean(local, E->isVolatile());
@@ -1019,6 +1301,12 @@ class TranslateASTVisitor final
             cbor_encode_string_array(local, ArrayRef<std::string>(inputs));
             cbor_encode_string_array(local, ArrayRef<std::string>(outputs));
             cbor_encode_string_array(local, ArrayRef<std::string>(clobbers));
+
+            // We lower `asm goto` to x86 code that records the label it
+            // jumps to
+            auto arch = this->Context->getTargetInfo().getTriple().getArch();
+            cbor_encode_boolean(local, arch == llvm::Triple::x86 ||
+                                           arch == llvm::Triple::x86_64);
         });
         return true;
     }
@@ -1059,10 +1347,9 @@ class TranslateASTVisitor final
     bool VisitExpr(Expr *E) {
         curMacroExpansionStack.clear();
 
-        // We only translate constant macro objects to Rust consts, so this
-        // expression must be constant.
-        if (!E->isConstantInitializer(*Context, false))
-            return true;
+        // We only translate constant macro objects to Rust consts, but
+        // function-like macros may expand to any expression.
+        bool isConstant = E->isConstantInitializer(*Context, false);
 
         auto &Mgr = Context->getSourceManager();
         auto Range = E->getSourceRange();
@@ -1109,8 +1396,16 @@ class TranslateASTVisitor final
             Begin = ExpansionBegin;
             End = ExpansionEnd;
 
-            if (mac->isObjectLike() && VisitMacro(name, Begin, mac, E)) {
-                curMacroExpansionStack.push_back(mac);
+            if (mac->isObjectLike()) {
+                if (isConstant && VisitMacro(name, Begin, mac, E))
+                    curMacroExpansionStack.push_back(mac);
+            } else if (isTranslatableFunctionMacro(mac)) {
+                std::vector<std::pair<unsigned, Expr*>> Args;
+                if (getMacroArgs(mac, Begin, E, Args) &&
+                    VisitMacro(name, Begin, mac, E)) {
+                    macros[mac].Args[E] = std::move(Args);
+                    curMacroExpansionStack.push_back(mac);
+                }
             }
         }
         return true;
@@ -1124,7 +1419,15 @@ class TranslateASTVisitor final
     }
 
     bool VisitGenericSelectionExpr(GenericSelectionExpr *E) {
-        printWarning("Encountered unsupported generic selection expression", E);
+        if (E->isResultDependent()) {
+            printWarning("Encountered result-dependent generic selection expression", E);
+            return true;
+        }
+
+        // Clang has already picked the association matching the controlling
+        // expression, so we only need to export the selected expression
+        std::vector<void *> childIds{E->getResultExpr()};
+        encode_entry(E, TagGenericSelectionExpr, childIds);
         return true;
     }
 
@@ -1184,6 +1487,16 @@ class TranslateASTVisitor final
                     abort();
                 }
                 cbor_encode_uint(extras, qt);
+
+                // Encode the alignment Clang computed so that the translator
+                // does not have to rely on Rust agreeing with it
+                APSInt value;
+                if (E->getKind() != UETT_SizeOf &&
+                    E->isIntegerConstantExpr(value, *this->Context)) {
+                    cbor_encode_uint(extras, value.getZExtValue());
+                } else {
+                    cbor_encode_null(extras);
+                }
             });
         typeEncoder.VisitQualType(t);
         return true;
@@ -1202,9 +1515,24 @@ class TranslateASTVisitor final
         bool is_constant =
             E->isIntegerConstantExpr(value, *this->Context);
 
+        // A constant offset of a direct member of a struct, as in
+        // offsetof(struct S, field), is also encoded with its struct type
+        // and field, so that it can be computed from the Rust struct
+        bool is_struct_member = is_constant && E->getNumComponents() == 1 &&
+            E->getComponent(0).getKind() == OffsetOfNode::Field &&
+            E->getComponent(0).getField()->getParent()->isStruct();
+
         encode_entry(
-            E, TagOffsetOfExpr, childIds, [this, E, value, is_constant](CborEncoder *extras) {
-                if (is_constant) {
+            E, TagOffsetOfExpr, childIds,
+            [this, E, value, is_constant, is_struct_member](CborEncoder *extras) {
+                if (is_struct_member) {
+                    auto ty = E->getTypeSourceInfo()->getType();
+                    auto field = E->getComponent(0).getField()->getCanonicalDecl();
+
+                    cbor_encode_uint(extras, value.getZExtValue());
+                    cbor_encode_uint(extras, typeEncoder.encodeQualType(ty));
+                    cbor_encode_uint(extras, uintptr_t(field));
+                } else if (is_constant) {
                     cbor_encode_uint(extras, value.getZExtValue());
                 } else {
                     // It's possible to get a non ICE in a field array like so:
@@ -1236,7 +1564,7 @@ class TranslateASTVisitor final
 
         // If this is the only use of the struct type, we need to ensure that it
         // gets visited.
-        if (!is_constant) {
+        if (!is_constant || is_struct_member) {
             auto ty = E->getTypeSourceInfo()->getType();
             typeEncoder.VisitQualType(ty);
         }
@@ -1598,6 +1926,8 @@ class TranslateASTVisitor final
     }
 
     bool VisitAddrLabelExpr(AddrLabelExpr *E) {
+        if (asmGotoLabels.count(E))
+            return true;
         printWarning("Cannot translate GNU address of label expression", E);
         return true;
     }
@@ -1612,6 +1942,58 @@ class TranslateASTVisitor final
         return true;
     }
 
+    bool VisitTypeTraitExpr(TypeTraitExpr *E) {
+        if (E->getTrait() != BTT_TypeCompatible) {
+            printWarning("Cannot translate type trait expression", E);
+            return true;
+        }
+
+        // `__builtin_types_compatible_p` is a constant, so export clang's
+        // answer along with the two types being compared
+        auto lhs = typeEncoder.encodeQualType(E->getArg(0)->getType());
+        auto rhs = typeEncoder.encodeQualType(E->getArg(1)->getType());
+        std::vector<void *> childIds;
+        encode_entry(E, TagTypesCompatibleExpr, childIds,
+                     [E, lhs, rhs](CborEncoder *extras) {
+                         cbor_encode_uint(extras, lhs);
+                         cbor_encode_uint(extras, rhs);
+                         cbor_encode_boolean(extras, E->getValue());
+                     });
+        return true;
+    }
+
+    bool VisitBlockExpr(BlockExpr *E) {
+        auto BD = E->getBlockDecl();
+
+        // The parameters, then the body, then the captured variables
+        std::vector<void *> childIds;
+        for (auto x : BD->parameters()) {
+            auto cd = x->getCanonicalDecl();
+            childIds.push_back(cd);
+            TraverseDecl(cd);
+        }
+        childIds.push_back(BD->getBody());
+        for (auto &C : BD->captures()) {
+            auto cd = C.getVariable()->getCanonicalDecl();
+            childIds.push_back(cd);
+        }
+
+        encode_entry(E, TagBlockExpr, childIds,
+                     [BD](CborEncoder *array) {
+                         cbor_encode_uint(array, BD->getNumParams());
+
+                         // Whether each captured variable is a `__block`
+                         // variable, which is captured by reference
+                         CborEncoder byrefs;
+                         cbor_encoder_create_array(array, &byrefs,
+                                                   BD->getNumCaptures());
+                         for (auto &C : BD->captures())
+                             cbor_encode_boolean(&byrefs, C.isByRef());
+                         cbor_encoder_close_container(array, &byrefs);
+                     });
+        return true;
+    }
+
     bool VisitGNUNullExpr(GNUNullExpr *E) {
         printWarning("Encountered unsupported GNU extension: null expression", E);
         return true;
@@ -1622,6 +2004,26 @@ class TranslateASTVisitor final
     // Declarations
     //
 
+    // Clang doesn't support the decimal floating-point types `_Decimal32`,
+    // `_Decimal64` and `_Decimal128`. It rejects them, and recovers by giving
+    // the declarations using them the type `int`, so point out the type that
+    // can't be translated.
+    bool VisitDecl(Decl *D) {
+        if (!D->isInvalidDecl())
+            return true;
+
+        SourceLocation TypeLoc;
+        auto name = findDecimalType(D, TypeLoc);
+        if (!name.empty() &&
+            reportedDecimalTypes.insert(TypeLoc.getRawEncoding()).second) {
+            auto DiagBuilder =
+                getDiagBuilder(TypeLoc, DiagnosticsEngine::Error);
+            DiagBuilder.AddString("unsupported decimal floating-point type `" +
+                                  name + "`");
+        }
+        return true;
+    }
+
     // Some function declarations are also function definitions.
     // This method handles both types of declarations.
     bool VisitFunctionDecl(FunctionDecl *FD) {
@@ -1701,6 +2103,9 @@ class TranslateASTVisitor final
                         } else if (auto *va = dyn_cast<VisibilityAttr>(attr)) {
                             const char *vis = VisibilityAttr::ConvertVisibilityTypeToStr(va->getVisibility());
                             cbor_encode_text_stringz(&attr_info, vis);
+                        } else if (auto *da = dyn_cast<DeprecatedAttr>(attr)) {
+                            // Empty if there is no message
+                            cbor_encode_string(&attr_info, da->getMessage().str());
                         }
                     }
                 }
@@ -1770,7 +2175,7 @@ class TranslateASTVisitor final
 
         encode_entry(
             VD, TagVarDecl, def->getLocation(), childIds, T,
-            [VD, is_defn, def, is_externally_visible](CborEncoder *array) {
+            [this, VD, is_defn, def, is_externally_visible](CborEncoder *array) {
                 auto name = VD->getNameAsString();
                 cbor_encode_string(array, name);
 
@@ -1805,6 +2210,22 @@ class TranslateASTVisitor final
                         } else if (auto *aa = dyn_cast<AliasAttr>(attr)) {
                             cbor_encode_text_stringz(
                                 &attr_info, aa->getAliasee().str().c_str());
+                        } else if (auto *va = dyn_cast<VisibilityAttr>(attr)) {
+                            const char *vis = VisibilityAttr::ConvertVisibilityTypeToStr(va->getVisibility());
+                            cbor_encode_text_stringz(&attr_info, vis);
+                        } else if (auto *aa = dyn_cast<AlignedAttr>(attr)) {
+                            // Alignment in bytes
+                            auto align = aa->getAlignment(*this->Context) / 8;
+                            cbor_encode_text_stringz(
+                                &attr_info, std::to_string(align).c_str());
+                        } else if (auto *da = dyn_cast<DeprecatedAttr>(attr)) {
+                            // Empty if there is no message
+                            cbor_encode_string(&attr_info, da->getMessage().str());
+                        } else if (auto *ca = dyn_cast<CleanupAttr>(attr)) {
+                            // Name of the function to call at scope exit
+                            cbor_encode_string(
+                                &attr_info,
+                                ca->getFunctionDecl()->getNameAsString());
                         }
                     }
                 }
@@ -1835,12 +2256,16 @@ class TranslateASTVisitor final
         auto def = D->getDefinition();
         auto recordAlignment = 0;
         auto byteSize = 0;
+        unsigned fieldAlignment = 0;
 
         auto loc = D->getLocation();
         std::vector<void *> childIds;
         if (def) {
             for (auto x : def->fields()) {
                 childIds.push_back(x->getCanonicalDecl());
+                // Over-aligned members (`_Alignas`) raise the alignment of
+                // the whole record, which we express on the record itself
+                fieldAlignment = std::max(fieldAlignment, x->getMaxAlignment());
             }
             // Since the RecordDecl D isn't the complete definition,
             // the actual location should be given. This should handle opaque
@@ -1857,7 +2282,7 @@ class TranslateASTVisitor final
 
         encode_entry(
             D, tag, loc, childIds, QualType(),
-            [D, def, recordAlignment, byteSize](CborEncoder *local) {
+            [D, def, recordAlignment, byteSize, fieldAlignment](CborEncoder *local) {
                 // 1. Encode name or null
                 auto name = D->getNameAsString();
                 if (name.empty()) {
@@ -1869,17 +2294,15 @@ class TranslateASTVisitor final
                 // 2. Boolean true when definition present
                 cbor_encode_boolean(local, !!def);
 
-                // 3. Attributes stored as an array of attribute names
+                // 3. Attributes stored as an array of attribute names and
+                // relevant info
                 CborEncoder attrs;
-                size_t attrs_n = D->hasAttrs() ? D->getAttrs().size() : 0;
-                cbor_encoder_create_array(local, &attrs, attrs_n);
-                for (auto a : D->attrs()) {
-                    cbor_encode_text_stringz(&attrs, a->getSpelling());
-                }
+                cbor_encoder_create_array(local, &attrs, CborIndefiniteLength);
+                encode_type_decl_attrs(&attrs, D);
                 cbor_encoder_close_container(local, &attrs);
 
                 // 4. Encode manually specified alignment
-                auto align = D->getMaxAlignment();
+                auto align = std::max(D->getMaxAlignment(), fieldAlignment);
                 if (align == 0) {
                     cbor_encode_null(local);
                 } else {
@@ -1887,7 +2310,12 @@ class TranslateASTVisitor final
                 }
 
                 // 5. Encode pragma pack(n)
-                if (auto const mfaa = D->getAttr<MaxFieldAlignmentAttr>()) {
+                // Clang attaches the packing in effect on the `#pragma pack`
+                // stack to the definition, which may come after the
+                // canonical (forward) declaration and sit inside a
+                // different push/pop region.
+                auto const packDecl = def ? def : D;
+                if (auto const mfaa = packDecl->getAttr<MaxFieldAlignmentAttr>()) {
                     cbor_encode_uint(local, mfaa->getAlignment() / 8);
                 } else {
                     cbor_encode_null(local);
@@ -1926,6 +2354,12 @@ class TranslateASTVisitor final
                          } else {
                              cbor_encode_string(local, name);
                          }
+
+                         CborEncoder attrs;
+                         cbor_encoder_create_array(local, &attrs,
+                                                   CborIndefiniteLength);
+                         encode_type_decl_attrs(&attrs, D);
+                         cbor_encoder_close_container(local, &attrs);
                      });
 
         return true;
@@ -2018,6 +2452,12 @@ class TranslateASTVisitor final
                          cbor_encode_string(array, name);
 
                          cbor_encode_boolean(array, D->isImplicit());
+
+                         CborEncoder attrs;
+                         cbor_encoder_create_array(array, &attrs,
+                                                   CborIndefiniteLength);
+                         encode_type_decl_attrs(&attrs, D);
+                         cbor_encoder_close_container(array, &attrs);
                      });
 
         typeEncoder.VisitQualType(typeForDecl);
@@ -2151,6 +2591,35 @@ class TranslateASTVisitor final
         return false;
     }
 
+    /// Find a decimal floating-point type among the tokens of `D` that precede
+    /// its name, and return its spelling and location.
+    std::string findDecimalType(Decl *D, SourceLocation &TypeLoc) {
+        auto &Mgr = Context->getSourceManager();
+        auto &LangOpts = Context->getLangOpts();
+#if CLANG_VERSION_MAJOR < 8
+        auto Loc = Mgr.getSpellingLoc(D->getLocStart());
+#else
+        auto Loc = Mgr.getSpellingLoc(D->getBeginLoc());
+#endif // CLANG_VERSION_MAJOR
+        auto End = Mgr.getSpellingLoc(D->getLocation());
+        Token Tok;
+        while (Loc.isValid() && Mgr.isBeforeInTranslationUnit(Loc, End)) {
+            if (Lexer::getRawToken(Loc, Tok, Mgr, LangOpts, true) ||
+                Tok.is(tok::eof))
+                break;
+            if (Tok.is(tok::raw_identifier)) {
+                auto name = Tok.getRawIdentifier();
+                if (name == "_Decimal32" || name == "_Decimal64" ||
+                    name == "_Decimal128") {
+                    TypeLoc = Tok.getLocation();
+                    return name.str();
+                }
+            }
+            Loc = Tok.getLocation().getLocWithOffset(Tok.getLength());
+        }
+        return "";
+    }
+
     DiagnosticBuilder getDiagBuilder(SourceLocation Loc,
                                      DiagnosticsEngine::Level Lvl) {
         auto &DiagEngine = Context->getDiagnostics();
@@ -2283,7 +2752,9 @@ class TranslateConsumer : public clang::ASTConsumer {
 
             // 2. Track all of the top-level declarations
             cbor_encoder_create_array(&outer, &array, CborIndefiniteLength);
-            for (auto d : translation_unit->decls()) {
+            std::vector<Decl *> top_level_decls;
+            collectTopLevelDecls(translation_unit, top_level_decls);
+            for (auto d : top_level_decls) {
                 if(!d->isCanonicalDecl() && isa<VarDecl>(d)) {
                     auto canonical_decl = d->getCanonicalDecl();
                     auto var_decl = cast<VarDecl>(canonical_decl);
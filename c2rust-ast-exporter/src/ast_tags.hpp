@@ -80,9 +80,16 @@ enum ASTEntryTag {
     // GNU extensions
     TagStmtExpr,
     TagChooseExpr,
+    TagTypesCompatibleExpr,
 
     TagAtomicExpr,
 
+    // C11 _Generic
+    TagGenericSelectionExpr,
+
+    // Apple blocks
+    TagBlockExpr,
+
     TagIntegerLiteral = 300,
     TagStringLiteral,
     TagCharacterLiteral,
@@ -139,6 +146,7 @@ enum TypeTag {
     TagBlockPointer,
     TagComplexType,
     TagHalf,
+    TagBitIntType,
 };
 
 enum StringTypeTag {
@@ -12,10 +12,14 @@ use std::slice;
 
 pub mod clang_ast;
 
-pub fn get_clang_major_version() -> Option<u32> {
+/// Get the version of clang that the exporter was built against, e.g., `7.0.1`.
+pub fn get_clang_version() -> &'static str {
     let s = unsafe { CStr::from_ptr(clang_version()) };
-    s.to_str()
-        .unwrap()
+    s.to_str().unwrap()
+}
+
+pub fn get_clang_major_version() -> Option<u32> {
+    get_clang_version()
         .split('.')
         .next()
         .unwrap()
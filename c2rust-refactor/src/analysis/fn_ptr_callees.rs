@@ -0,0 +1,151 @@
+//! Crude computation of the functions that may be called through a function pointer.
+//!
+//! Every function whose address is taken is recorded as a candidate callee of the function
+//! pointer type it is converted to, and of each struct field it is stored into.  A call through a
+//! pointer of that type, or loaded from that field, may call any of the candidates.  No attempt is
+//! made to track where pointers flow after that, so the candidate sets are only an
+//! approximation: a pointer converted to another type through a cast is lost, for example.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use rustc::hir::def_id::{DefId, LOCAL_CRATE};
+use rustc::mir::tcx::PlaceTy;
+use rustc::mir::*;
+use rustc::ty::{Ty, TyCtxt, TyKind};
+use rustc_target::abi::VariantIdx;
+
+/// The candidate callees of calls through function pointers.
+#[derive(Debug, Default)]
+pub struct FnPtrCallees<'tcx> {
+    by_ty: HashMap<Ty<'tcx>, Vec<DefId>>,
+    by_field: HashMap<DefId, Vec<DefId>>,
+}
+
+impl<'tcx> FnPtrCallees<'tcx> {
+    /// The functions converted to function pointers of type `ty`.
+    pub fn for_ty(&self, tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> &[DefId] {
+        self.by_ty
+            .get(&tcx.erase_regions(&ty))
+            .map_or(&[], |fns| &fns[..])
+    }
+
+    /// The functions stored into the struct field `field`.
+    pub fn for_field(&self, field: DefId) -> &[DefId] {
+        self.by_field.get(&field).map_or(&[], |fns| &fns[..])
+    }
+}
+
+/// Collect the candidate callees of function pointers from the MIR of every function and static
+/// in the crate.
+pub fn collect<'tcx>(tcx: TyCtxt<'tcx>) -> FnPtrCallees<'tcx> {
+    let mut callees = FnPtrCallees::default();
+
+    for &def_id in tcx.mir_keys(LOCAL_CRATE).iter() {
+        let mir = tcx.optimized_mir(def_id);
+
+        // The functions a pointer held in each local may point to.
+        let mut local_fns: HashMap<Local, Vec<DefId>> = HashMap::new();
+
+        for bb in mir.basic_blocks() {
+            for stmt in &bb.statements {
+                let (place, rv) = match stmt.kind {
+                    StatementKind::Assign(box (ref place, ref rv)) => (place, rv),
+                    _ => continue,
+                };
+
+                let fns = match *rv {
+                    Rvalue::Use(ref op) => operand_fns(op, &local_fns),
+                    Rvalue::Cast(_, ref op, ty) => {
+                        let fns = operand_fns(op, &local_fns);
+                        if let TyKind::FnPtr(_) = ty.kind {
+                            add_fns(&mut callees.by_ty, tcx.erase_regions(&ty), &fns);
+                        }
+                        fns
+                    }
+                    Rvalue::Aggregate(ref kind, ref ops) => {
+                        if let AggregateKind::Adt(adt, variant, _, _, union_field) = **kind {
+                            let fields = &adt.variants[variant].fields;
+                            for (i, op) in ops.iter().enumerate() {
+                                let field = fields[union_field.unwrap_or(i)].did;
+                                let fns = operand_fns(op, &local_fns);
+                                add_fns(&mut callees.by_field, field, &fns);
+                            }
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if fns.is_empty() {
+                    continue;
+                }
+
+                if let Some(field) = place_field(tcx, mir, place) {
+                    add_fns(&mut callees.by_field, field, &fns);
+                } else if let PlaceBase::Local(l) = place.base {
+                    if place.projection.is_empty() {
+                        add_fns(&mut local_fns, l, &fns);
+                    }
+                }
+            }
+        }
+    }
+
+    callees
+}
+
+fn add_fns<K: Hash + Eq>(map: &mut HashMap<K, Vec<DefId>>, key: K, fns: &[DefId]) {
+    if fns.is_empty() {
+        return;
+    }
+    let entry = map.entry(key).or_insert_with(Vec::new);
+    for &f in fns {
+        if !entry.contains(&f) {
+            entry.push(f);
+        }
+    }
+}
+
+/// The functions `op` may evaluate to a pointer to, or to the item of.
+fn operand_fns(op: &Operand, local_fns: &HashMap<Local, Vec<DefId>>) -> Vec<DefId> {
+    match *op {
+        Operand::Constant(ref c) => match c.literal.ty.kind {
+            TyKind::FnDef(def_id, _) => vec![def_id],
+            _ => vec![],
+        },
+        Operand::Copy(ref place) | Operand::Move(ref place) => match place.base {
+            PlaceBase::Local(l) if place.projection.is_empty() => {
+                local_fns.get(&l).cloned().unwrap_or_default()
+            }
+            _ => vec![],
+        },
+    }
+}
+
+/// If `place` is a struct field, return the `DefId` of the field.
+pub fn place_field<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    mir: &Body<'tcx>,
+    place: &Place<'tcx>,
+) -> Option<DefId> {
+    let (last, parent) = place.projection.split_last()?;
+    let f = match *last {
+        ProjectionElem::Field(f, _) => f,
+        _ => return None,
+    };
+
+    let base_ty = match place.base {
+        PlaceBase::Local(l) => mir.local_decls[l].ty,
+        PlaceBase::Static(ref s) => s.ty,
+    };
+    let parent_ty = parent
+        .iter()
+        .fold(PlaceTy::from_ty(base_ty), |ty, elem| ty.projection_ty(tcx, elem));
+    match parent_ty.ty.kind {
+        TyKind::Adt(adt, _) => {
+            let variant = parent_ty.variant_index.unwrap_or(VariantIdx::from_usize(0));
+            Some(adt.variants[variant].fields[f.index()].did)
+        }
+        _ => None,
+    }
+}
@@ -1,5 +1,7 @@
 //! Intraprocedural step of the analysis.
 
+use std::collections::HashMap;
+
 use log::Level;
 use rustc::hir::def_id::DefId;
 use rustc::mir::*;
@@ -8,6 +10,7 @@ use rustc_index::vec::IndexVec;
 use rustc_target::abi::VariantIdx;
 use syntax::source_map::{DUMMY_SP, Spanned};
 
+use crate::analysis::fn_ptr_callees::{self, FnPtrCallees};
 use crate::analysis::labeled_ty::{LabeledTy, LabeledTyCtxt};
 
 use super::constraint::{ConstraintSet, Perm};
@@ -80,6 +83,11 @@ pub struct IntraCtxt<'c, 'lty, 'a: 'lty, 'tcx: 'a> {
     /// `Label::FnDef` above), and we do that by adding a new entry to `insts`.
     insts: Vec<Instantiation>,
     next_inst_var: u32,
+
+    /// Candidate callees of calls through function pointers, collected from the whole crate.
+    callees: &'c FnPtrCallees<'tcx>,
+    /// The struct field each local holding a function pointer was loaded from, if any.
+    fn_ptr_fields: HashMap<Local, DefId>,
 }
 
 impl<'c, 'lty, 'a: 'lty, 'tcx: 'a> IntraCtxt<'c, 'lty, 'a, 'tcx> {
@@ -87,6 +95,7 @@ impl<'c, 'lty, 'a: 'lty, 'tcx: 'a> IntraCtxt<'c, 'lty, 'a, 'tcx> {
         cx: &'c mut Ctxt<'lty, 'tcx>,
         def_id: DefId,
         mir: &'a Body<'tcx>,
+        callees: &'c FnPtrCallees<'tcx>,
     ) -> IntraCtxt<'c, 'lty, 'a, 'tcx> {
         let ilcx = LabeledTyCtxt::new(cx.arena);
         IntraCtxt {
@@ -104,6 +113,9 @@ impl<'c, 'lty, 'a: 'lty, 'tcx: 'a> IntraCtxt<'c, 'lty, 'a, 'tcx> {
 
             insts: Vec::new(),
             next_inst_var: 0,
+
+            callees,
+            fn_ptr_fields: HashMap::new(),
         }
     }
 
@@ -584,6 +596,7 @@ impl<'c, 'lty, 'a: 'lty, 'tcx: 'a> IntraCtxt<'c, 'lty, 'a, 'tcx> {
             self.enter_stmt(idx);
             match s.kind {
                 StatementKind::Assign(box(ref lv, ref rv)) => {
+                    self.record_fn_ptr_field(lv, rv);
                     let (lv_ty, lv_perm) = self.place_lty(lv);
                     let (rv_ty, rv_perm) = self.rvalue_lty(rv);
                     self.propagate(lv_ty, rv_ty, rv_perm);
@@ -638,26 +651,95 @@ impl<'c, 'lty, 'a: 'lty, 'tcx: 'a> IntraCtxt<'c, 'lty, 'a, 'tcx> {
                 ..
             } => {
                 debug!("    call {:?}", func);
-                let (func_ty, _func_perm) = self.operand_lty(func);
-                debug!("fty = {:?}", func_ty);
-                let sig = self.ty_fn_sig(func_ty);
-
-                // Note that `sig.inputs` may be shorter than `args`, if `func` is varargs.
-                for (&sig_ty, arg) in sig.inputs.iter().zip(args.iter()) {
-                    let (arg_ty, arg_perm) = self.operand_lty(arg);
-                    self.propagate(sig_ty, arg_ty, arg_perm);
-                    debug!("    (arg): {:?}", sig_ty);
-                    debug!("    ^-- {:?}: {:?}", arg, arg_ty);
+                let callees = self.fn_ptr_callees(func);
+                if callees.is_empty() {
+                    let (func_ty, _func_perm) = self.operand_lty(func);
+                    debug!("fty = {:?}", func_ty);
+                    let sig = self.ty_fn_sig(func_ty);
+                    self.handle_call(sig, args, destination);
+                } else {
+                    // Instead of the permissions in the function pointer type, which are unknown
+                    // if it was loaded from a static location, use those of every function it
+                    // might point to.  The arguments get the join of the permissions the
+                    // candidates require.
+                    for callee in callees {
+                        debug!("    candidate callee {:?}", callee);
+                        let ty = self.cx.tcx.type_of(callee);
+                        let func_ty = self.local_ty(ty);
+                        let sig = self.ty_fn_sig(func_ty);
+                        self.handle_call(sig, args, destination);
+                    }
                 }
-                if let Some((ref dest, _)) = *destination {
-                    let sig_ty = sig.output;
-                    let (dest_ty, dest_perm) = self.place_lty(dest);
-                    self.propagate(dest_ty, sig_ty, Perm::move_());
-                    self.propagate_perm(Perm::write(), dest_perm);
-                    debug!("    {:?}: {:?}", dest, dest_ty);
-                    debug!("    ^-- (return): {:?}", sig_ty);
+            }
+        }
+    }
+
+    fn handle_call(
+        &mut self,
+        sig: IFnSig<'lty, 'tcx>,
+        args: &[Operand<'tcx>],
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+    ) {
+        // Note that `sig.inputs` may be shorter than `args`, if `func` is varargs.
+        for (&sig_ty, arg) in sig.inputs.iter().zip(args.iter()) {
+            let (arg_ty, arg_perm) = self.operand_lty(arg);
+            self.propagate(sig_ty, arg_ty, arg_perm);
+            debug!("    (arg): {:?}", sig_ty);
+            debug!("    ^-- {:?}: {:?}", arg, arg_ty);
+        }
+        if let Some((ref dest, _)) = *destination {
+            let sig_ty = sig.output;
+            let (dest_ty, dest_perm) = self.place_lty(dest);
+            self.propagate(dest_ty, sig_ty, Perm::move_());
+            self.propagate_perm(Perm::write(), dest_perm);
+            debug!("    {:?}: {:?}", dest, dest_ty);
+            debug!("    ^-- (return): {:?}", sig_ty);
+        }
+    }
+
+    /// Remember the struct field a function pointer copied into a local came from.
+    fn record_fn_ptr_field(&mut self, lv: &Place<'tcx>, rv: &Rvalue<'tcx>) {
+        let local = match lv.base {
+            PlaceBase::Local(l) if lv.projection.is_empty() => l,
+            _ => return,
+        };
+        let field = match *rv {
+            Rvalue::Use(Operand::Copy(ref src)) | Rvalue::Use(Operand::Move(ref src)) => {
+                fn_ptr_callees::place_field(self.cx.tcx, self.mir, src)
+            }
+            _ => None,
+        };
+        match field {
+            Some(field) => self.fn_ptr_fields.insert(local, field),
+            None => self.fn_ptr_fields.remove(&local),
+        };
+    }
+
+    /// The functions a call through `func` may call, if it is a function pointer whose possible
+    /// targets are known.  Functions stored into the struct field the pointer was loaded from are
+    /// preferred over all functions converted to the pointer's type.
+    fn fn_ptr_callees(&self, func: &Operand<'tcx>) -> Vec<DefId> {
+        let ty = func.ty(self.mir, self.cx.tcx);
+        match ty.kind {
+            TyKind::FnPtr(_) => {}
+            _ => return vec![],
+        }
+
+        let field = match *func {
+            Operand::Copy(ref place) | Operand::Move(ref place) => match place.base {
+                PlaceBase::Local(l) if place.projection.is_empty() => {
+                    self.fn_ptr_fields.get(&l).cloned()
                 }
+                _ => fn_ptr_callees::place_field(self.cx.tcx, self.mir, place),
+            },
+            Operand::Constant(_) => None,
+        };
+        if let Some(field) = field {
+            let fns = self.callees.for_field(field);
+            if !fns.is_empty() {
+                return fns.to_vec();
             }
         }
+        self.callees.for_ty(self.cx.tcx, ty).to_vec()
     }
 }
@@ -28,6 +28,7 @@ use rustc_index::vec::{Idx, IndexVec};
 use syntax::ast::IntTy;
 use syntax::source_map::Span;
 
+use crate::analysis::fn_ptr_callees;
 use crate::analysis::labeled_ty::{LabeledTy, LabeledTyCtxt};
 use crate::command::CommandState;
 use crate::context::HirMap;
@@ -177,6 +178,8 @@ fn analyze_intra<'a, 'tcx, 'lty>(
     hir_map: &HirMap<'a, 'tcx>,
     tcx: TyCtxt<'tcx>,
 ) {
+    let callees = fn_ptr_callees::collect(tcx);
+
     for &def_id in tcx.mir_keys(LOCAL_CRATE).iter() {
         // We currently don't process `static` bodies, even though they do have MIR.
         if !is_fn(hir_map, def_id) {
@@ -185,7 +188,7 @@ fn analyze_intra<'a, 'tcx, 'lty>(
 
         let mir = tcx.optimized_mir(def_id);
 
-        let mut local_cx = IntraCtxt::new(cx, def_id, mir);
+        let mut local_cx = IntraCtxt::new(cx, def_id, mir, &callees);
         local_cx.init();
 
         for (bbid, bb) in mir.basic_blocks().iter_enumerated() {
@@ -20,7 +20,7 @@ pub struct Comment {
     pub pos: BytePos,
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct CommentMap(HashMap<NodeId, Vec<Comment>>);
 
 impl CommentMap {
@@ -12,6 +12,7 @@ mod get_node_id;
 mod get_span;
 mod list_node_ids;
 mod output_exprs;
+mod par_visit;
 mod remove_paren;
 mod seq_edit;
 mod visit;
@@ -27,6 +28,7 @@ pub use self::get_node_id::{GetNodeId, MaybeGetNodeId};
 pub use self::get_span::GetSpan;
 pub use self::list_node_ids::ListNodeIds;
 pub use self::output_exprs::fold_output_exprs;
+pub use self::par_visit::{par_collect_nodes, par_map_items, ParVisitNodes};
 pub use self::remove_paren::remove_paren;
 pub use self::seq_edit::{fold_blocks, fold_modules};
 pub use self::visit::Visit;
@@ -0,0 +1,74 @@
+//! Read-only traversals that visit the top-level items of a crate in parallel.
+//!
+//! The items are distributed with `rustc_data_structures::sync::par_iter`, which uses rayon when
+//! the compiler we link against is built with `parallel_compiler`, and is an ordinary sequential
+//! iterator otherwise, since the AST and the interner are not thread-safe in that configuration.
+//! The pinned nightly toolchain is not built with `parallel_compiler`, so for now these
+//! traversals are sequential, and only split the work so it can run in parallel once the
+//! compiler supports it.
+//!
+//! Each item is visited on its own and the results are combined in item order, so the result
+//! doesn't depend on how the items were scheduled.  Passes that mutate the AST should keep using
+//! `MutVisitNodes`.
+use rustc_data_structures::sync::{par_iter, ParallelIterator, Send, Sync};
+use syntax::ast::*;
+
+use crate::ast_manip::VisitNode;
+
+/// Call `callback` on each top-level item of `krate`, possibly in parallel, and return the
+/// results in item order.
+pub fn par_map_items<R, F>(krate: &Crate, callback: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(&Item) -> R + Sync + Send,
+{
+    par_iter(&krate.module.items).map(|i| callback(i)).collect()
+}
+
+/// Trait for AST node types that can be visited by a parallel read-only traversal.
+pub trait ParVisitNodes: VisitNode + Sized {
+    /// Visit the nodes of this type in `krate` in preorder, with a fresh accumulator for each
+    /// top-level item.  The accumulators are then merged into the first one with `reduce`, in
+    /// item order.
+    fn visit<A, F, R>(krate: &Crate, callback: F, reduce: R) -> A
+    where
+        A: Default + Send,
+        F: Fn(&mut A, &Self) + Sync + Send,
+        R: FnMut(&mut A, A);
+}
+
+impl<N: VisitNode> ParVisitNodes for N {
+    fn visit<A, F, R>(krate: &Crate, callback: F, mut reduce: R) -> A
+    where
+        A: Default + Send,
+        F: Fn(&mut A, &Self) + Sync + Send,
+        R: FnMut(&mut A, A),
+    {
+        let results = par_map_items(krate, |i| {
+            let mut acc = A::default();
+            N::visit_nodes(i, |node| callback(&mut acc, node));
+            acc
+        });
+
+        let mut acc = A::default();
+        for result in results {
+            reduce(&mut acc, result);
+        }
+        acc
+    }
+}
+
+/// Collect the results of `callback` for the nodes of its argument type in `krate`, in the
+/// order of a sequential preorder traversal, visiting the top-level items in parallel.
+pub fn par_collect_nodes<N, T, F>(krate: &Crate, callback: F) -> Vec<T>
+where
+    N: VisitNode,
+    T: Send,
+    F: Fn(&N) -> Option<T> + Sync + Send,
+{
+    N::visit(
+        krate,
+        |acc: &mut Vec<T>, node| acc.extend(callback(node)),
+        |acc, result| acc.extend(result),
+    )
+}
@@ -1,10 +1,11 @@
 //! Miscellaneous utility functions.
+use c2rust_ast_builder::mk;
 use rustc::hir::def::{self, Namespace, Res};
 use smallvec::SmallVec;
 use syntax::ast::*;
 use syntax::ptr::P;
 use syntax::source_map::{SourceMap, Span, DUMMY_SP};
-use syntax::symbol::{kw, Symbol};
+use syntax::symbol::{kw, sym, Symbol};
 use syntax::tokenstream::TokenStream;
 
 use super::AstEquiv;
@@ -150,6 +151,32 @@ pub fn extend_span_attrs(mut s: Span, attrs: &[Attribute]) -> Span {
     s
 }
 
+/// Remove `traits` from the `#[derive]` attributes in `attrs`, dropping the attributes that no
+/// longer derive anything.
+pub fn remove_derives(attrs: &mut Vec<Attribute>, traits: &[&str]) {
+    let old_attrs = attrs.drain(..).collect::<Vec<_>>();
+    for attr in old_attrs {
+        let items = match attr.meta_item_list() {
+            Some(items) if attr.check_name(sym::derive) => items,
+            _ => {
+                attrs.push(attr);
+                continue;
+            }
+        };
+        let kept = items
+            .iter()
+            .filter_map(|item| item.ident())
+            .map(|ident| ident.to_string())
+            .filter(|name| !traits.contains(&&name[..]))
+            .collect::<Vec<_>>();
+        if kept.len() == items.len() {
+            attrs.push(attr);
+        } else if !kept.is_empty() {
+            attrs.extend(mk().call_attr("derive", kept).into_attrs());
+        }
+    }
+}
+
 /// Get the name of a macro invocation.
 pub fn macro_name(mac: &Mac) -> Name {
     let p = &mac.path;
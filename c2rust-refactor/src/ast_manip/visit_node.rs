@@ -100,6 +100,14 @@ gen_visit_node_impl! {
     walk = visit::walk_item(self, i);
 }
 
+gen_visit_node_impl! {
+    node = Pat;
+    visitor = PatNodeVisitor;
+    visitor_post = PatNodeVisitorPost;
+    fn visit_pat(&mut self, p: &'ast Pat);
+    walk = visit::walk_pat(self, p);
+}
+
 gen_visit_node_impl! {
     node = Path;
     visitor = PathNodeVisitor;
@@ -108,6 +116,14 @@ gen_visit_node_impl! {
     walk = visit::walk_path(self, p);
 }
 
+gen_visit_node_impl! {
+    node = Ty;
+    visitor = TyNodeVisitor;
+    visitor_post = TyNodeVisitorPost;
+    fn visit_ty(&mut self, t: &'ast Ty);
+    walk = visit::walk_ty(self, t);
+}
+
 gen_visit_node_impl! {
     node = Block;
     visitor = BlockNodeVisitor;
@@ -1,3 +1,4 @@
+use std::cmp;
 use std::collections::HashMap;
 use syntax::ast::*;
 use syntax::attr::HasAttrs;
@@ -10,8 +11,11 @@ use smallvec::SmallVec;
 
 use crate::ast_manip::{GetNodeId, MutVisit, Visit};
 
+/// The `#[cfg_attr]`s of a node, along with their positions in its list of attributes.
+pub type CfgAttrs = Vec<(usize, Attribute)>;
+
 struct CollectCfgAttrs {
-    node_attrs: HashMap<NodeId, Vec<Attribute>>,
+    node_attrs: HashMap<NodeId, CfgAttrs>,
 }
 
 impl CollectCfgAttrs {
@@ -19,8 +23,9 @@ impl CollectCfgAttrs {
         let attrs = x
             .attrs()
             .iter()
-            .filter(|attr| attr.check_name(sym::cfg_attr))
-            .cloned()
+            .enumerate()
+            .filter(|&(_, attr)| attr.check_name(sym::cfg_attr))
+            .map(|(i, attr)| (i, attr.clone()))
             .collect::<Vec<_>>();
         if !attrs.is_empty() {
             self.node_attrs.insert(x.get_node_id(), attrs);
@@ -55,7 +60,7 @@ collect_cfg_attrs! {
     // TODO: extend this list with the remaining node types
 }
 
-pub fn collect_cfg_attrs(krate: &Crate) -> HashMap<NodeId, Vec<Attribute>> {
+pub fn collect_cfg_attrs(krate: &Crate) -> HashMap<NodeId, CfgAttrs> {
     let mut v = CollectCfgAttrs {
         node_attrs: HashMap::new(),
     };
@@ -64,7 +69,7 @@ pub fn collect_cfg_attrs(krate: &Crate) -> HashMap<NodeId, Vec<Attribute>> {
 }
 
 struct RestoreCfgAttrs {
-    node_attrs: HashMap<NodeId, Vec<Attribute>>,
+    node_attrs: HashMap<NodeId, CfgAttrs>,
 }
 
 impl RestoreCfgAttrs {
@@ -74,7 +79,7 @@ impl RestoreCfgAttrs {
                 "RESTORE ATTRS {:?} onto {:?}",
                 cfg_attrs
                     .iter()
-                    .map(|a| ::syntax::print::pprust::attribute_to_string(a))
+                    .map(|(_, a)| ::syntax::print::pprust::attribute_to_string(a))
                     .collect::<Vec<_>>(),
                 x.attrs()
                     .iter()
@@ -83,9 +88,12 @@ impl RestoreCfgAttrs {
             );
             x.visit_attrs(|attrs| {
                 // Drop attrs that were produced by evaluation of one of the `#[cfg_attr]`s.
-                attrs.retain(|a| !cfg_attrs.iter().any(|ca| ca.span.contains(a.span)));
-                // Now put the #[cfg_attr]s themselves back in.
-                attrs.extend(cfg_attrs.iter().cloned());
+                attrs.retain(|a| !cfg_attrs.iter().any(|(_, ca)| ca.span.contains(a.span)));
+                // Now put the #[cfg_attr]s themselves back in, where they were originally.
+                for (i, ca) in cfg_attrs {
+                    let i = cmp::min(*i, attrs.len());
+                    attrs.insert(i, ca.clone());
+                }
             });
             info!(
                 "  attrs changed to {:?}",
@@ -136,7 +144,7 @@ impl MutVisitor for RestoreCfgAttrs {
     // TODO: extend this impl with the remaining node types
 }
 
-pub fn restore_cfg_attrs(krate: &mut Crate, node_attrs: HashMap<NodeId, Vec<Attribute>>) {
+pub fn restore_cfg_attrs(krate: &mut Crate, node_attrs: HashMap<NodeId, CfgAttrs>) {
     let mut f = RestoreCfgAttrs { node_attrs };
     krate.visit(&mut f)
 }
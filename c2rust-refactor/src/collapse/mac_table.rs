@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::mem;
 
 use rustc_target::spec::abi::Abi;
 use syntax::ast::*;
@@ -10,6 +11,7 @@ use syntax::ThinVec;
 
 use std::rc::Rc;
 use syntax::attr;
+use syntax::feature_gate::is_builtin_attr;
 use syntax::ptr::P;
 use syntax::source_map::Spanned;
 use syntax::visit::Visitor;
@@ -146,9 +148,12 @@ pub struct InvocId(pub u32);
 pub enum InvocKind<'ast> {
     Mac(&'ast Mac),
     ItemAttr(&'ast Item),
-    /// This is the generated item part of a `#[derive]`'s output.  The `InvocId` points to the
-    /// originating `ItemAttr`.
+    /// This is the generated item part of a `#[derive]`'s output, or of another attribute that
+    /// expands to extra items.  The `InvocId` points to the originating `ItemAttr`.
     Derive(InvocId),
+    /// A node generated by a compiler plugin or procedural macro that doesn't correspond to any
+    /// node of the unexpanded AST.
+    Generated,
 }
 
 struct MacInfoRaw<'ast> {
@@ -279,6 +284,18 @@ impl<'a> Ctxt<'a> {
         self.record_macro_with_id(invoc_id, expanded);
         self.record_node_id_match(old_id, expanded.id());
     }
+
+    /// Record a macro-generated node that doesn't belong to any invocation, so collapsing can
+    /// drop it.  Only nodes that appear in sequences can be dropped; others are left alone.
+    fn record_generated(&mut self, expanded: MacNodeRef<'a>) {
+        match expanded {
+            MacNodeRef::Expr(_) | MacNodeRef::Pat(_) | MacNodeRef::Ty(_) => return,
+            _ => {}
+        }
+        let invoc_id = self.record_invoc(InvocKind::Generated);
+        trace!("new {:?} for generated node {:?}", invoc_id, expanded.id());
+        self.record_macro_with_id(invoc_id, expanded);
+    }
 }
 
 fn is_macro_generated(sp: Span) -> bool {
@@ -302,6 +319,7 @@ where
             );
 
             let mut empty = true;
+            let mut seen_orig = false;
             while j < new_seq.len() {
                 let new = &new_seq[j];
 
@@ -318,14 +336,16 @@ where
                 trace!("  collect {:?} at {:?}", new.get_node_id(), new.get_span());
 
                 // The node came from `invoc`, so consume and record the node.
-                if let Some(child_invoc) = get_child_invoc(invoc, invoc_id, new.as_mac_node_ref()) {
+                let new_ref = new.as_mac_node_ref();
+                if let Some(child_invoc) = get_child_invoc(invoc, invoc_id, new_ref, seen_orig) {
                     let child_invoc_id = cx.record_invoc(child_invoc);
-                    cx.record_macro_with_id(child_invoc_id, new.as_mac_node_ref());
+                    cx.record_macro_with_id(child_invoc_id, new_ref);
                 } else {
-                    cx.record_macro_with_id(invoc_id, new.as_mac_node_ref());
+                    cx.record_macro_with_id(invoc_id, new_ref);
 
                     // Recurse into children so they get added to the node map.
                     if let InvocKind::ItemAttr(_) = invoc {
+                        seen_orig = true;
                         CollectMacros::collect_macros(old, new, cx);
                     }
                 }
@@ -333,10 +353,11 @@ where
                 j += 1;
             }
         } else {
-            // For now, any time we see a node with a macro-generated span that wasn't eaten up by
-            // the macro handling above, we assume it was created by a compiler plugin (such as
-            // prelude injection or `#[derive]`), and ignore it.
+            // Any time we see a node with a macro-generated span that wasn't eaten up by the
+            // macro handling above, we assume it was created by a compiler plugin or procedural
+            // macro, and record it so collapsing removes it again.
             while j < new_seq.len() && is_macro_generated(new_seq[j].get_span()) {
+                cx.record_generated(new_seq[j].as_mac_node_ref());
                 j += 1;
             }
             if j < new_seq.len() {
@@ -346,21 +367,43 @@ where
         }
     }
 
+    // Generated nodes can also follow the last node of the sequence.
+    while j < new_seq.len() && is_macro_generated(new_seq[j].get_span()) {
+        cx.record_generated(new_seq[j].as_mac_node_ref());
+        j += 1;
+    }
+
     assert!(
         j == new_seq.len(),
         "impossible: too many items in expanded sequence"
     );
 }
 
+/// Check whether `new`, produced by expanding the attributes of `orig`, is `orig` itself rather
+/// than an extra item generated alongside it.
+fn is_annotated_item(orig: &Item, new: &Item) -> bool {
+    orig.ident.name == new.ident.name
+        && mem::discriminant(&orig.kind) == mem::discriminant(&new.kind)
+}
+
+/// Get the invocation kind of a node produced by `invoc`.  `seen_orig` is set once the annotated
+/// item of an `ItemAttr` has been found among its outputs.
 fn get_child_invoc<'a>(
     invoc: InvocKind<'a>,
     id: InvocId,
     new: MacNodeRef<'a>,
+    seen_orig: bool,
 ) -> Option<InvocKind<'a>> {
     match invoc {
-        InvocKind::ItemAttr(..) => {
+        InvocKind::ItemAttr(orig) => {
             if let MacNodeRef::Item(i) = new {
-                if attr::contains_name(&i.attrs, Symbol::intern("automatically_derived")) {
+                // Not all derives mark their output `#[automatically_derived]` (serde, for
+                // example, wraps its impls in a `const`), and attribute macros can emit extra
+                // items of their own, so anything but the annotated item counts as generated.
+                if attr::contains_name(&i.attrs, Symbol::intern("automatically_derived"))
+                    || seen_orig
+                    || !is_annotated_item(orig, i)
+                {
                     return Some(InvocKind::Derive(id));
                 }
             }
@@ -486,9 +529,13 @@ impl MaybeInvoc for Item {
         match self.kind {
             ItemKind::Mac(ref mac) => Some(InvocKind::Mac(mac)),
             _ => {
+                // Besides the built-in attributes that change the item, any attribute the
+                // compiler doesn't know could be an attribute macro.
                 if attr::contains_name(&self.attrs, Symbol::intern("derive"))
                     || attr::contains_name(&self.attrs, Symbol::intern("cfg"))
+                    || attr::contains_name(&self.attrs, Symbol::intern("cfg_attr"))
                     || attr::contains_name(&self.attrs, Symbol::intern("test"))
+                    || self.attrs.iter().any(|a| !is_builtin_attr(a))
                 {
                     Some(InvocKind::ItemAttr(self))
                 } else {
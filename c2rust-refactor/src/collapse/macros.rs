@@ -8,11 +8,12 @@
 use c2rust_ast_builder::mk;
 use rustc_data_structures::sync::Lrc;
 use smallvec::SmallVec;
+use std::cmp;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use syntax::ast::*;
-use syntax::attr;
 use syntax::mut_visit::{self, MutVisitor};
 use syntax::parse::token::{Nonterminal, Token, TokenKind};
+use syntax::print::pprust;
 use syntax::ptr::P;
 use syntax::source_map::{BytePos, Span};
 use syntax::tokenstream::{self, TokenStream, TokenTree};
@@ -176,6 +177,9 @@ impl<'a> MutVisitor for CollapseMacros<'a> {
                     trace!("collapse (duplicate): {:?} -> /**/", s);
                     return smallvec![];
                 }
+            } else if let InvocKind::Generated = info.invoc {
+                trace!("collapse (generated): {:?} -> /**/", s);
+                return smallvec![];
             } else {
                 warn!("bad macro kind for stmt: {:?}", info.invoc);
             }
@@ -210,14 +214,27 @@ impl<'a> MutVisitor for CollapseMacros<'a> {
                 }
                 InvocKind::ItemAttr(orig_i) => {
                     trace!("ItemAttr: return original: {:?}", i);
-                    let i = i.map(|i| restore_attrs(i, orig_i));
+                    let expanded = info
+                        .expanded
+                        .as_item()
+                        .unwrap_or_else(|| panic!(
+                            "replaced {:?} with {:?} which is a different type?",
+                            i,
+                            info.expanded,
+                        ));
+                    let i = i.map(|i| restore_attrs(i, orig_i, expanded));
                     self.record_matched_ids(i.id, i.id);
-                    return smallvec![i];
+                    // Collapse any macros inside the item as usual.
+                    return mut_visit::noop_flat_map_item(i, self);
                 }
                 InvocKind::Derive(_parent_invoc_id) => {
                     trace!("ItemAttr: drop (generated): {:?} -> /**/", i);
                     return smallvec![];
                 }
+                InvocKind::Generated => {
+                    trace!("collapse (generated): {:?} -> /**/", i);
+                    return smallvec![];
+                }
             }
         }
         mut_visit::noop_flat_map_item(i, self)
@@ -249,6 +266,9 @@ impl<'a> MutVisitor for CollapseMacros<'a> {
                     trace!("collapse (duplicate): {:?} -> /**/", ii);
                     return smallvec![];
                 }
+            } else if let InvocKind::Generated = info.invoc {
+                trace!("collapse (generated): {:?} -> /**/", ii);
+                return smallvec![];
             } else {
                 warn!("bad macro kind for impl item: {:?}", info.invoc);
             }
@@ -282,6 +302,9 @@ impl<'a> MutVisitor for CollapseMacros<'a> {
                     trace!("collapse (duplicate): {:?} -> /**/", ti);
                     return smallvec![];
                 }
+            } else if let InvocKind::Generated = info.invoc {
+                trace!("collapse (generated): {:?} -> /**/", ti);
+                return smallvec![];
             } else {
                 warn!("bad macro kind for trait item: {:?}", info.invoc);
             }
@@ -315,8 +338,11 @@ impl<'a> MutVisitor for CollapseMacros<'a> {
                     trace!("collapse (duplicate): {:?} -> /**/", fi);
                     return smallvec![];
                 }
+            } else if let InvocKind::Generated = info.invoc {
+                trace!("collapse (generated): {:?} -> /**/", fi);
+                return smallvec![];
             } else {
-                warn!("bad macro kind for trait item: {:?}", info.invoc);
+                warn!("bad macro kind for foreign item: {:?}", info.invoc);
             }
         }
         mut_visit::noop_flat_map_foreign_item(fi, self)
@@ -327,33 +353,33 @@ impl<'a> MutVisitor for CollapseMacros<'a> {
     }
 }
 
-/// Undo changes to attributes that occurred during macro expansion.  `old` is unexpanded and `new`
-/// is transformed/collapsed.  Since additional attributes could have been added/removed by the
-/// transform, we can't just copy `old.attrs`.  Instead, we look through `old.attrs` for attributes
-/// with known effects (such as `#[cfg]`, which removes itself when the condition is met) and tries
-/// to reverse those specific effects on `new.attrs`.
-fn restore_attrs(mut new: Item, old: &Item) -> Item {
-    // If the original item had a `#[derive]` attr, transfer it to the new one.
-    // TODO: handle multiple instances of `#[derive]`
-    // TODO: try to keep attrs in the same order
-    if let Some(attr) = attr::find_by_name(&old.attrs, sym::derive) {
-        if !attr::contains_name(&new.attrs, sym::derive) {
-            new.attrs.push(attr.clone());
-        }
-    }
-
-    if let Some(attr) = attr::find_by_name(&old.attrs, sym::cfg) {
-        if !attr::contains_name(&new.attrs, sym::cfg) {
-            new.attrs.push(attr.clone());
-        }
-    }
-
+/// Undo changes to attributes that occurred during macro expansion.  `old` is unexpanded,
+/// `expanded` is its expansion, and `new` is transformed/collapsed.  Since additional attributes
+/// could have been added/removed by the transform, we can't just copy `old.attrs`.  Instead, we
+/// put back the attributes that expansion consumed (such as `#[derive]`, `#[cfg]`, which removes
+/// itself when the condition is met, and attribute macros) at their original positions, unless
+/// `new` already has them.  `#[cfg_attr]`s are restored separately, in `collapse::cfg_attr`.
+fn restore_attrs(mut new: Item, old: &Item, expanded: &Item) -> Item {
     new.attrs.retain(|attr| {
         // TODO: don't erase user-written #[structural_match] attrs
         // (It can be written explicitly, but is also inserted by #[derive(Eq)].)
         !attr.check_name(sym::structural_match)
     });
 
+    let attr_str = |attr: &Attribute| pprust::attribute_to_string(attr);
+    let has_attr = |attrs: &[Attribute], attr: &str| attrs.iter().any(|a| attr_str(a) == attr);
+    for (i, attr) in old.attrs.iter().enumerate() {
+        if attr.check_name(sym::cfg_attr) {
+            continue;
+        }
+        let s = attr_str(attr);
+        if has_attr(&expanded.attrs[..], &s) || has_attr(&new.attrs[..], &s) {
+            continue;
+        }
+        let i = cmp::min(i, new.attrs.len());
+        new.attrs.insert(i, attr.clone());
+    }
+
     new
 }
 
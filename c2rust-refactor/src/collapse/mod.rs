@@ -27,7 +27,7 @@ mod macros;
 mod node_map;
 mod nt_match;
 
-pub use self::cfg_attr::{collect_cfg_attrs, restore_cfg_attrs};
+pub use self::cfg_attr::{collect_cfg_attrs, restore_cfg_attrs, CfgAttrs};
 pub use self::deleted::{collect_deleted_nodes, restore_deleted_nodes};
 pub use self::mac_table::{collect_macro_invocations, MacInfo, MacTable};
 pub use self::macros::collapse_macros;
@@ -39,7 +39,7 @@ use deleted::DeletedNode;
 
 pub struct CollapseInfo<'ast> {
     mac_table: MacTable<'ast>,
-    cfg_attr_info: HashMap<NodeId, Vec<Attribute>>,
+    cfg_attr_info: HashMap<NodeId, CfgAttrs>,
     deleted_info: Vec<DeletedNode<'ast>>,
 }
 
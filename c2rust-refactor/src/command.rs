@@ -238,11 +238,18 @@ impl RefactorState {
     /// matches the text on disk) as the basis for rewriting.
     #[cfg_attr(feature = "profile", flame)]
     pub fn save_crate(&mut self) {
+        let file_io = self.file_io.clone();
+        self.save_crate_to(&*file_io);
+    }
+
+    /// Like `save_crate`, but write the rewritten files and marks through `file_io` instead of
+    /// the configured output.
+    pub fn save_crate_to(&self, file_io: &dyn FileIO) {
         let old = &self.orig_krate;
         let new = &self.cs.krate();
         let node_id_map = self.node_map.clone().into_inner();
 
-        self.file_io
+        file_io
             .save_marks(
                 new,
                 self.session().source_map(),
@@ -257,7 +264,36 @@ impl RefactorState {
         });
         // Note that `rewrite_files_with` does not read any files from disk - it uses the
         // `SourceMap` to get files' original source text.
-        files::rewrite_files_with(self.source_map(), &rw, &*self.file_io).unwrap();
+        files::rewrite_files_with(self.source_map(), &rw, file_io).unwrap();
+    }
+
+    /// Take a snapshot of the crate and everything needed to rewrite it, to go back to later
+    /// with `restore`.  Unlike `save_crate` followed by `load_crate`, this doesn't touch the disk
+    /// or reparse the crate, and it keeps the marks.
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            krate: self.cs.krate().clone(),
+            marks: self.cs.marks().clone(),
+            parsed_nodes: self.cs.parsed_nodes.borrow().clone(),
+            node_map: self.node_map.clone(),
+            comment_map: self.comment_map.clone(),
+            commands: self.commands.clone(),
+        }
+    }
+
+    /// Return to the state captured by `snapshot`.
+    pub fn restore(&mut self, snapshot: StateSnapshot) {
+        // The compiler phase stays as it is, since it describes the state of the compiler
+        // queries rather than of the crate.  The node ID counter also keeps counting, so that
+        // nodes created after the snapshot never share IDs with the restored ones.
+        *self.cs.krate.get_mut() = snapshot.krate;
+        *self.cs.marks.get_mut() = snapshot.marks;
+        *self.cs.parsed_nodes.get_mut() = snapshot.parsed_nodes;
+        self.cs.new_parsed_node_ids.get_mut().clear();
+        self.cs.new_comments.get_mut().clear();
+        self.node_map = snapshot.node_map;
+        self.comment_map = snapshot.comment_map;
+        self.commands = snapshot.commands;
     }
 
     #[cfg_attr(feature = "profile", flame)]
@@ -488,6 +524,27 @@ pub enum TypeckLoopResult {
     Finished,
 }
 
+/// The state of a `RefactorState` at some point, see `RefactorState::snapshot`.
+#[derive(Clone)]
+pub struct StateSnapshot {
+    krate: Crate,
+    marks: HashSet<(NodeId, Symbol)>,
+    parsed_nodes: ParsedNodes,
+    node_map: NodeMap,
+    comment_map: CommentMap,
+    commands: Vec<String>,
+}
+
+impl StateSnapshot {
+    pub fn krate(&self) -> &Crate {
+        &self.krate
+    }
+
+    pub fn marks(&self) -> &HashSet<(NodeId, Symbol)> {
+        &self.marks
+    }
+}
+
 /// Mutable state that can be modified by a "driver" command.  This is normally paired with a
 /// `RefactorCtxt`, which contains immutable analysis results from the original input `Crate`.
 pub struct CommandState {
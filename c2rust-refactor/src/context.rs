@@ -152,6 +152,25 @@ impl<'a, 'tcx> RefactorCtxt<'a, 'tcx> {
         }
     }
 
+    /// Check whether any adjustments, such as reborrows or unsizing coercions, were applied to
+    /// an expression.
+    pub fn has_adjustments(&self, id: NodeId) -> bool {
+        let hir_id = match self.hir_map().opt_node_to_hir_id(id) {
+            Some(x) => x,
+            None => return false,
+        };
+        let parent_node = self.hir_map().get_parent_item(hir_id);
+        let parent = match self.hir_map().opt_local_def_id(parent_node) {
+            Some(x) => x,
+            None => return false,
+        };
+        if !self.ty_ctxt().has_typeck_tables(parent) {
+            return false;
+        }
+        let tables = self.ty_ctxt().typeck_tables_of(parent);
+        tables.adjustments().get(hir_id).map_or(false, |adjs| !adjs.is_empty())
+    }
+
     pub fn def_type(&self, id: DefId) -> Ty<'tcx> {
         self.ty_ctxt().type_of(id)
     }
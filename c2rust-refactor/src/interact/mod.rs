@@ -8,6 +8,7 @@ mod vim8_backend;
 mod worker;
 
 pub use self::main_thread::interact_command;
+pub(crate) use self::main_thread::collect_spans;
 
 #[derive(Clone, Debug)]
 pub enum ToServer {
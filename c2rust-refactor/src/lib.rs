@@ -41,6 +41,7 @@ extern crate c2rust_ast_builder;
 extern crate syntax;
 extern crate syntax_ext;
 extern crate syntax_pos;
+extern crate toml;
 
 #[cfg(feature = "profile")]
 extern crate flame;
@@ -82,8 +83,10 @@ pub mod plugin;
 
 pub mod mark_adjust;
 pub mod print_spans;
+pub mod show_marks;
 pub mod select;
 pub mod transform;
+pub mod verify;
 
 mod context;
 mod scripting;
@@ -165,12 +168,21 @@ struct RustcArgs {
     cwd: Option<PathBuf>,
 }
 
+/// A shell command to check the crate with after every refactoring command, see `verify`.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub cmd: String,
+    /// On failure, find the nodes marked for the failing command that cause it
+    pub bisect: bool,
+}
+
 pub struct Options {
     pub rewrite_modes: Vec<file_io::OutputMode>,
     pub commands: Vec<Command>,
     pub rustc_args: RustcArgSource,
     pub cursors: Vec<Cursor>,
     pub marks: Vec<Mark>,
+    pub checkpoint: Option<Checkpoint>,
 
     pub plugins: Vec<String>,
     pub plugin_dirs: Vec<String>,
@@ -442,10 +454,12 @@ fn main_impl(opts: Options) -> interface::Result<()> {
         mark_adjust::register_commands(&mut cmd_reg);
         pick_node::register_commands(&mut cmd_reg);
         print_spans::register_commands(&mut cmd_reg);
+        show_marks::register_commands(&mut cmd_reg);
         select::register_commands(&mut cmd_reg);
         analysis::register_commands(&mut cmd_reg);
         reflect::register_commands(&mut cmd_reg);
         command::register_commands(&mut cmd_reg);
+        verify::register_commands(&mut cmd_reg);
 
         plugin::load_plugins(&opts.plugin_dirs, &opts.plugins, &mut cmd_reg);
 
@@ -464,6 +478,11 @@ fn main_impl(opts: Options) -> interface::Result<()> {
         } else {
             let file_io = Arc::new(file_io::RealFileIO::new(opts.rewrite_modes.clone()));
             driver::run_refactoring(config, cmd_reg, file_io, marks, |mut state| {
+                if let Some(ref checkpoint) = opts.checkpoint {
+                    verify::run_checkpointed(&mut state, &opts.commands, checkpoint);
+                    return;
+                }
+
                 for cmd in opts.commands.clone() {
                     if &cmd.name == "interact" {
                         panic!("`interact` must be the only command");
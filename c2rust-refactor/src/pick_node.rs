@@ -2,16 +2,18 @@
 //!
 //! This is used in various parts of the frontend to set marks at specific locations.
 use rustc::session::Session;
+use rustc_data_structures::sync::Lrc;
+use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
 use syntax::ast::*;
 use syntax::ext::hygiene::SyntaxContext;
-use syntax::source_map::{BytePos, Span};
+use syntax::source_map::{BytePos, SourceFile, Span};
 use syntax::visit::{self, FnKind, Visitor};
 use syntax_pos::FileName;
 
 use crate::ast_manip::Visit;
-use crate::command::{DriverCommand, Registry};
+use crate::command::{CommandState, DriverCommand, Registry};
 use crate::driver::Phase;
 use crate::RefactorCtxt;
 
@@ -26,6 +28,50 @@ struct PickVisitor {
     node_info: Option<NodeInfo>,
     kind: NodeKind,
     target: Span,
+    /// Only pick nodes whose span is exactly `target`
+    exact: bool,
+    /// Call site of the outermost macro expansion found to contain `target`
+    macro_callsite: Option<Span>,
+    /// All visited nodes of the selected kind that aren't part of a macro expansion
+    candidates: Vec<NodeInfo>,
+}
+
+impl PickVisitor {
+    fn new(kind: NodeKind, target: Span, exact: bool) -> PickVisitor {
+        PickVisitor {
+            node_info: None,
+            kind,
+            target,
+            exact,
+            macro_callsite: None,
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Pick the node if it's the first one of kind `kind` to match the target.  Since the visit
+    /// methods recurse first, this is the deepest matching node.
+    fn pick(&mut self, kind: NodeKind, id: NodeId, span: Span) {
+        if span.ctxt() != SyntaxContext::root() {
+            let callsite = span.source_callsite();
+            if callsite.contains(self.target) {
+                self.macro_callsite = Some(callsite);
+            }
+        } else if self.kind.contains(kind) {
+            self.candidates.push(NodeInfo { id, span });
+        }
+
+        if self.node_info.is_none() && self.kind.contains(kind) && self.matches(span) {
+            self.node_info = Some(NodeInfo { id, span });
+        }
+    }
+
+    fn matches(&self, span: Span) -> bool {
+        if self.exact {
+            span.lo() == self.target.lo() && span.hi() == self.target.hi()
+        } else {
+            span.contains(self.target)
+        }
+    }
 }
 
 impl<'a> Visitor<'a> for PickVisitor {
@@ -33,20 +79,12 @@ impl<'a> Visitor<'a> for PickVisitor {
         // Recurse first, so that the deepest node gets visited first.  This way we get
         // the function and not its containing module, for example.
         visit::walk_item(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::Item)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::Item, x.id, x.span);
 
         // Special case for modules.  If the cursor lies within the inner span of a mod item
         // (meaning inside the included file), then we mark the mod item itself.  This is because
         // `Mod` nodes don't have their own IDs.
-        if self.node_info.is_none() {
+        if self.node_info.is_none() && !self.exact {
             if let ItemKind::Mod(ref m) = x.kind {
                 if m.inner.contains(self.target) {
                     self.node_info = Some(NodeInfo {
@@ -60,93 +98,37 @@ impl<'a> Visitor<'a> for PickVisitor {
 
     fn visit_trait_item(&mut self, x: &'a TraitItem) {
         visit::walk_trait_item(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::TraitItem)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::TraitItem, x.id, x.span);
     }
 
     fn visit_impl_item(&mut self, x: &'a ImplItem) {
         visit::walk_impl_item(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::ImplItem)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::ImplItem, x.id, x.span);
     }
 
     fn visit_foreign_item(&mut self, x: &'a ForeignItem) {
         visit::walk_foreign_item(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::ForeignItem)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::ForeignItem, x.id, x.span);
     }
 
     fn visit_stmt(&mut self, x: &'a Stmt) {
         visit::walk_stmt(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::Stmt)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::Stmt, x.id, x.span);
     }
 
     fn visit_expr(&mut self, x: &'a Expr) {
         visit::walk_expr(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::Expr)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::Expr, x.id, x.span);
     }
 
     fn visit_pat(&mut self, x: &'a Pat) {
         visit::walk_pat(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::Pat)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::Pat, x.id, x.span);
     }
 
     fn visit_ty(&mut self, x: &'a Ty) {
         visit::walk_ty(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::Ty)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::Ty, x.id, x.span);
     }
 
     // There's no `visit_arg`, unfortunately, so we have to do this instead.
@@ -171,15 +153,7 @@ impl<'a> Visitor<'a> for PickVisitor {
 
     fn visit_struct_field(&mut self, x: &'a StructField) {
         visit::walk_struct_field(self, x);
-        if self.node_info.is_none()
-            && self.kind.contains(NodeKind::Field)
-            && x.span.contains(self.target)
-        {
-            self.node_info = Some(NodeInfo {
-                id: x.id,
-                span: x.span,
-            });
-        }
+        self.pick(NodeKind::Field, x.id, x.span);
     }
 
     fn visit_mac(&mut self, mac: &'a Mac) {
@@ -271,11 +245,7 @@ impl FromStr for NodeKind {
 /// Select an AST node by its `BytePos` in the `SourceMap`.  Only nodes of the specified `kind` will
 /// be selected.
 pub fn pick_node(krate: &Crate, kind: NodeKind, pos: BytePos) -> Option<NodeInfo> {
-    let mut v = PickVisitor {
-        node_info: None,
-        kind,
-        target: Span::new(pos, pos, SyntaxContext::root()),
-    };
+    let mut v = PickVisitor::new(kind, Span::new(pos, pos, SyntaxContext::root()), false);
     krate.visit(&mut v);
 
     // If the cursor falls inside the crate's module, then mark the crate itself.
@@ -291,6 +261,61 @@ pub fn pick_node(krate: &Crate, kind: NodeKind, pos: BytePos) -> Option<NodeInfo
     v.node_info
 }
 
+/// The result of selecting a node by byte range.
+pub enum RangePick {
+    Found(NodeInfo),
+    /// The range lies inside the macro invocation with this span.
+    InMacro(Span),
+    /// No node matched.  Holds the nodes of the requested kind that are closest to the range.
+    NotFound(Vec<NodeInfo>),
+}
+
+/// Select the smallest AST node of the specified `kind` whose span contains `target`, or whose
+/// span is exactly `target` if `exact` is set.
+pub fn pick_node_in_range(krate: &Crate, kind: NodeKind, target: Span, exact: bool) -> RangePick {
+    let mut v = PickVisitor::new(kind, target, exact);
+    krate.visit(&mut v);
+
+    if let Some(callsite) = v.macro_callsite {
+        return RangePick::InMacro(callsite);
+    }
+    if let Some(node_info) = v.node_info {
+        return RangePick::Found(node_info);
+    }
+
+    let distance = |span: Span| {
+        let lo = (i64::from(span.lo().0) - i64::from(target.lo().0)).abs();
+        let hi = (i64::from(span.hi().0) - i64::from(target.hi().0)).abs();
+        lo + hi
+    };
+    let mut candidates = v.candidates;
+    candidates.sort_by_key(|c| distance(c.span));
+    candidates.truncate(3);
+    RangePick::NotFound(candidates)
+}
+
+/// Find a file of the crate by its path.  Files loaded through `mod` declarations are named
+/// relative to the directory of the crate root, so paths that don't match any file name
+/// exactly are compared to the file names after canonicalizing both.
+fn find_source_file(session: &Session, file: &str) -> Lrc<SourceFile> {
+    let source_map = session.source_map();
+    if let Some(sf) = source_map.get_source_file(&FileName::Real(PathBuf::from(file))) {
+        return sf;
+    }
+
+    let canonical = fs::canonicalize(file).ok();
+    let found = canonical.as_ref().and_then(|canonical| {
+        source_map.files().iter().find(|sf| match sf.name {
+            FileName::Real(ref path) => fs::canonicalize(path).ok().as_ref() == Some(canonical),
+            _ => false,
+        }).cloned()
+    });
+    match found {
+        Some(sf) => sf,
+        None => panic!("target position lies in nonexistent file {:?}", file),
+    }
+}
+
 /// Select an AST node by its file, line, and column numbers.
 pub fn pick_node_at_loc(
     krate: &Crate,
@@ -300,15 +325,7 @@ pub fn pick_node_at_loc(
     line: u32,
     col: u32,
 ) -> Option<NodeInfo> {
-    let fm = match session
-        .source_map()
-        .get_source_file(&FileName::Real(PathBuf::from(file)))
-    {
-        Some(x) => x,
-        None => {
-            panic!("target position lies in nonexistent file {:?}", file);
-        }
-    };
+    let fm = find_source_file(session, file);
 
     if line == 0 || line as usize - 1 >= fm.lines.len() {
         panic!("line {} is outside the bounds of {}", line, file);
@@ -370,6 +387,81 @@ pub fn pick_node_command(krate: &Crate, cx: &RefactorCtxt, args: &[String]) {
     }
 }
 
+/// # `mark_span` Command
+///
+/// Usage: `mark_span FILE START END LABEL [KIND] [exact]`
+///
+/// Marks: `LABEL`
+///
+/// Find the smallest node of kind `KIND` (default: `any`) whose span contains the byte range
+/// `START..END` of `FILE`, and mark it with `LABEL`.  With `exact`, the node's span must be
+/// exactly that range.  `FILE` can be any file of the crate, including those loaded through
+/// `mod` declarations.
+///
+/// This is meant for scripts generated by external tools, which know byte ranges rather than
+/// `select` queries.  Ranges inside a macro invocation are rejected, since the nodes there come
+/// from the macro's expansion.  If no node matches, the error lists the spans of the nodes of
+/// kind `KIND` closest to the range.
+pub fn mark_span_command(st: &CommandState, cx: &RefactorCtxt, args: &[String]) {
+    if args.len() < 4 {
+        panic!("usage: mark_span FILE START END LABEL [KIND] [exact]");
+    }
+    let file = &args[0];
+    let start = u32::from_str(&args[1])
+        .unwrap_or_else(|_| panic!("bad start byte {:?}", args[1]));
+    let end = u32::from_str(&args[2])
+        .unwrap_or_else(|_| panic!("bad end byte {:?}", args[2]));
+    let label = &args[3];
+
+    let mut kind = NodeKind::Any;
+    let mut exact = false;
+    for arg in &args[4..] {
+        if arg == "exact" {
+            exact = true;
+        } else {
+            kind = NodeKind::from_str(arg)
+                .unwrap_or_else(|_| panic!("unknown node kind {:?}", arg));
+        }
+    }
+
+    let source_map = cx.session().source_map();
+    let sf = find_source_file(cx.session(), file);
+    let len = sf.end_pos.0 - sf.start_pos.0;
+    if start > end || end > len {
+        panic!("byte range {}..{} is outside the bounds of {} ({} bytes)", start, end, file, len);
+    }
+    let target = Span::new(
+        sf.start_pos + BytePos(start),
+        sf.start_pos + BytePos(end),
+        SyntaxContext::root(),
+    );
+
+    match pick_node_in_range(&st.krate(), kind, target, exact) {
+        RangePick::Found(node_info) => {
+            info!("marking {:?} at {}", node_info.id, source_map.span_to_string(node_info.span));
+            st.add_mark(node_info.id, label.as_str());
+        }
+        RangePick::InMacro(callsite) => panic!(
+            "byte range {}..{} of {} lies inside the macro invocation at {}; \
+             nodes from macro expansions can't be marked",
+            start, end, file, source_map.span_to_string(callsite),
+        ),
+        RangePick::NotFound(candidates) => {
+            let nearest = candidates
+                .iter()
+                .map(|c| source_map.span_to_string(c.span))
+                .collect::<Vec<_>>();
+            panic!(
+                "no {} node {} byte range {}..{} of {}; nearest candidates: [{}]",
+                kind.as_str(),
+                if exact { "matches" } else { "contains" },
+                start, end, file,
+                nearest.join(", "),
+            );
+        }
+    }
+}
+
 pub fn register_commands(reg: &mut Registry) {
     reg.register("pick_node", |args| {
         let args = args.to_owned();
@@ -377,4 +469,11 @@ pub fn register_commands(reg: &mut Registry) {
             pick_node_command(&st.krate(), &cx, &args);
         }))
     });
+
+    reg.register("mark_span", |args| {
+        let args = args.to_owned();
+        Box::new(DriverCommand::new(Phase::Phase2, move |st, cx| {
+            mark_span_command(st, &cx, &args);
+        }))
+    });
 }
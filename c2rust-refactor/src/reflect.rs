@@ -13,16 +13,45 @@ use syntax::ptr::P;
 use syntax::source_map::DUMMY_SP;
 use syntax::symbol::kw;
 
-use crate::ast_manip::MutVisitNodes;
+use crate::ast_manip::{visit_nodes, MutVisitNodes};
 use crate::command::{DriverCommand, Registry};
 use crate::context::RefactorCtxt;
 use crate::driver::Phase;
 
 /// Build an AST representing a `ty::Ty`.
+///
+/// `!` is only stable as the return type of a function, so it is reflected as `_` here.  Use
+/// `reflect_tcx_ret_ty` for function return types.
 pub fn reflect_tcx_ty<'a, 'gcx, 'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> P<Ty> {
     reflect_tcx_ty_inner(tcx, ty, false)
 }
 
+/// Build an AST representing a `ty::Ty`, or return `None` if some part of it can't be named (a
+/// closure type, for example) and would be reflected as `_`, which isn't allowed in item
+/// signatures.
+pub fn try_reflect_tcx_ty<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> Option<P<Ty>> {
+    let ty = reflect_tcx_ty(tcx, ty);
+    let mut nameable = true;
+    visit_nodes(&*ty, |t: &Ty| {
+        if let TyKind::Infer = t.kind {
+            nameable = false;
+        }
+    });
+    if nameable {
+        Some(ty)
+    } else {
+        None
+    }
+}
+
+/// Build an AST representing the return type of a function, which may be `!`.
+pub fn reflect_tcx_ret_ty<'a, 'gcx, 'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> P<Ty> {
+    match ty.kind {
+        ty::TyKind::Never => mk().never_ty(),
+        _ => reflect_tcx_ty(tcx, ty),
+    }
+}
+
 fn reflect_tcx_ty_inner<'a, 'gcx, 'tcx>(
     tcx: TyCtxt<'tcx>,
     ty: ty::Ty<'tcx>,
@@ -60,12 +89,34 @@ fn reflect_tcx_ty_inner<'a, 'gcx, 'tcx>(
             .ptr_ty(reflect_tcx_ty(tcx, mty.ty)),
         Ref(_, ty, m) => mk().set_mutbl(m).ref_ty(reflect_tcx_ty(tcx, ty)),
         FnDef(_, _) => mk().infer_ty(), // unsupported (type cannot be named)
-        FnPtr(_) => mk().infer_ty(),    // TODO (fn(...) -> ...)
+        FnPtr(sig) => {
+            let sig = sig.skip_binder();
+            let mut inputs = sig
+                .inputs()
+                .iter()
+                .map(|&ty| mk().arg(reflect_tcx_ty(tcx, ty), mk().wild_pat()))
+                .collect::<Vec<_>>();
+            if sig.c_variadic {
+                inputs.push(mk().arg(mk().cvar_args_ty(), mk().wild_pat()));
+            }
+            let output = if sig.output().is_unit() {
+                FunctionRetTy::Default(DUMMY_SP)
+            } else {
+                FunctionRetTy::Ty(reflect_tcx_ret_ty(tcx, sig.output()))
+            };
+            let unsafety = match sig.unsafety {
+                hir::Unsafety::Unsafe => "unsafe",
+                hir::Unsafety::Normal => "normal",
+            };
+            mk().unsafety(unsafety)
+                .abi(sig.abi)
+                .barefn_ty(mk().fn_decl(inputs, output))
+        }
         Dynamic(_, _) => mk().infer_ty(), // TODO (dyn Trait)
         Closure(_, _) => mk().infer_ty(), // unsupported (type cannot be named)
         Generator(_, _, _) => mk().infer_ty(), // unsupported (type cannot be named)
         GeneratorWitness(_) => mk().infer_ty(), // unsupported (type cannot be named)
-        Never => mk().never_ty(),
+        Never => mk().infer_ty(), // only stable as a return type, see `reflect_tcx_ret_ty`
         Tuple(tys) => mk().tuple_ty(tys.types().map(|ty| reflect_tcx_ty(tcx, &ty)).collect()),
         Projection(..) => mk().infer_ty(),             // TODO
         UnnormalizedProjection(..) => mk().infer_ty(), // TODO
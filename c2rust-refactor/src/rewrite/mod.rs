@@ -181,6 +181,10 @@ pub struct RewriteCtxt<'s> {
     /// looking for recycled text to splice in, it checks `old_nodes` for a node whose ID is
     /// `node_id_map[new_node.id]`.
     node_id_map: HashMap<NodeId, NodeId>,
+
+    /// Spans of macro-generated code where rewriting was needed but gave up, because there is no
+    /// source text to rewrite.  These get reported once rewriting is done.
+    unrewritable: Vec<Span>,
 }
 
 impl<'s> RewriteCtxt<'s> {
@@ -199,6 +203,7 @@ impl<'s> RewriteCtxt<'s> {
             fresh_start: DUMMY_SP,
             expr_prec: ExprPrec::Normal(parser::PREC_RESET),
             node_id_map,
+            unrewritable: Vec::new(),
         }
     }
 
@@ -230,6 +235,28 @@ impl<'s> RewriteCtxt<'s> {
         mem::replace(&mut self.expr_prec, prec)
     }
 
+    /// Record that the text of `span`, which comes from a macro expansion, couldn't be rewritten.
+    pub fn record_unrewritable(&mut self, span: Span) {
+        self.unrewritable.push(span);
+    }
+
+    /// Warn about the macro invocations whose expansions had changes we couldn't rewrite.
+    fn report_unrewritable(&self) {
+        let mut callsites = self
+            .unrewritable
+            .iter()
+            .map(|sp| sp.source_callsite())
+            .collect::<Vec<_>>();
+        callsites.sort_by_key(|sp| (sp.lo(), sp.hi()));
+        callsites.dedup();
+        for sp in callsites {
+            self.sess.span_warn(
+                sp,
+                "can't rewrite code generated by this macro; changes to its expansion were dropped",
+            );
+        }
+    }
+
     fn new_to_old_id<Id: MappableId>(&self, id: Id) -> Id {
         id.map_id(self)
     }
@@ -321,5 +348,6 @@ where
     let mut rcx = RewriteCtxt::new(sess, map, comment_map, node_id_map);
     let ok = Rewrite::rewrite(old, new, rcx.enter(&mut rw));
     assert!(ok, "rewriting did not complete");
+    rcx.report_unrewritable();
     rw
 }
@@ -585,7 +585,7 @@ where
     true
 }
 
-pub fn rewrite<T>(old: &T, new: &T, rcx: RewriteCtxtRef) -> bool
+pub fn rewrite<T>(old: &T, new: &T, mut rcx: RewriteCtxtRef) -> bool
 where
     T: PrintParse + RecoverChildren + Splice + Debug + MaybeGetNodeId,
 {
@@ -596,8 +596,13 @@ where
         // part of the rewrite when this happens, because rewriting inside the RHS of a
         // macro_rules! macro would be very difficult, and for procedural macros it's just
         // impossible.  But we still report success (`return true`) because we don't want to force
-        // replacement of the macro with its expansion.
-        warn!("can't splice in fresh text for a non-rewritable node");
+        // replacement of the macro with its expansion.  Spans from macro expansions are reported
+        // to the user once rewriting is done.
+        if old.splice_span().from_expansion() {
+            rcx.record_unrewritable(old.splice_span());
+        } else {
+            warn!("can't splice in fresh text for a non-rewritable node");
+        }
         return true;
     }
     new.rewrite_at(old.splice_span(), rcx)
@@ -1,29 +1,60 @@
 //! Visitors for implementing `ChildMatch`, `DescMatch`, and `Filter`, which need to walk the AST
 //! and inspect the currently selected nodes.
 
+use rustc_data_structures::sync::{Send, Sync};
 use std::collections::HashSet;
 use syntax::ast::*;
 use syntax::source_map::Span;
 use syntax::visit::{self, FnKind, Visitor};
 
+use crate::ast_manip::par_map_items;
 use crate::command::CommandState;
 use crate::select::filter::{self, AnyNode};
 use crate::select::Filter;
 use crate::RefactorCtxt;
 
-struct ChildMatchVisitor<'a, 'tcx: 'a> {
-    st: &'a CommandState,
-    cx: &'a RefactorCtxt<'a, 'tcx>,
-    old: HashSet<NodeId>,
+/// A visitor that collects the nodes it selects.
+trait SelectVisitor: for<'ast> Visitor<'ast> {
+    fn into_selected(self) -> HashSet<NodeId>;
+}
+
+/// Walk `krate` with the visitors built by `new_visitor`, which start in the state of the crate
+/// root, and collect the nodes they select.  With `parallel`, each top-level item gets its own
+/// visitor, and the items are visited with `par_map_items`, which is only actually parallel
+/// under `parallel_compiler`; the selection is the same either way.
+fn run_visitors<V, F>(krate: &Crate, parallel: bool, new_visitor: F) -> HashSet<NodeId>
+where
+    V: SelectVisitor,
+    F: Fn() -> V + Sync + Send,
+{
+    if !parallel {
+        let mut v = new_visitor();
+        visit::walk_crate(&mut v, krate);
+        return v.into_selected();
+    }
+
+    let mut selected = HashSet::new();
+    for new in par_map_items(krate, |i| {
+        let mut v = new_visitor();
+        v.visit_item(i);
+        v.into_selected()
+    }) {
+        selected.extend(new);
+    }
+    selected
+}
+
+struct ChildMatchVisitor<'a, M> {
+    matches: &'a M,
+    old: &'a HashSet<NodeId>,
     new: HashSet<NodeId>,
     /// Are we at a child of a node that was selected in the `old` set?
     in_old: bool,
-    filt: &'a Filter,
 }
 
-impl<'ast, 'a, 'tcx> ChildMatchVisitor<'a, 'tcx> {
+impl<'ast, 'a, M: Fn(AnyNode) -> bool> ChildMatchVisitor<'a, M> {
     fn matches(&self, node: AnyNode) -> bool {
-        filter::matches_filter(self.st, self.cx, node, self.filt)
+        (self.matches)(node)
     }
 
     fn maybe_enter_old<F: FnOnce(&mut Self)>(&mut self, id: NodeId, func: F) {
@@ -50,7 +81,13 @@ impl<'ast, 'a, 'tcx> ChildMatchVisitor<'a, 'tcx> {
     }
 }
 
-impl<'ast, 'a, 'tcx> Visitor<'ast> for ChildMatchVisitor<'a, 'tcx> {
+impl<'a, M: Fn(AnyNode) -> bool> SelectVisitor for ChildMatchVisitor<'a, M> {
+    fn into_selected(self) -> HashSet<NodeId> {
+        self.new
+    }
+}
+
+impl<'ast, 'a, M: Fn(AnyNode) -> bool> Visitor<'ast> for ChildMatchVisitor<'a, M> {
     fn visit_item(&mut self, x: &'ast Item) {
         if self.in_old && self.matches(AnyNode::Item(x)) {
             self.new.insert(x.id);
@@ -143,32 +180,39 @@ pub fn matching_children(
     sel: HashSet<NodeId>,
     filt: &Filter,
 ) -> HashSet<NodeId> {
+    children_matching(krate, &sel, true, |node| filter::matches_filter(st, cx, node, filt))
+}
+
+/// Find the nodes that match `matches` and are direct children of nodes in `sel`.
+pub fn children_matching<M>(
+    krate: &Crate,
+    sel: &HashSet<NodeId>,
+    parallel: bool,
+    matches: M,
+) -> HashSet<NodeId>
+where
+    M: Fn(AnyNode) -> bool + Sync + Send,
+{
     let in_old = sel.contains(&CRATE_NODE_ID);
-    let mut v = ChildMatchVisitor {
-        st,
-        cx,
+    run_visitors(krate, parallel, || ChildMatchVisitor {
+        matches: &matches,
         old: sel,
         new: HashSet::new(),
         in_old,
-        filt,
-    };
-    visit::walk_crate(&mut v, krate);
-    v.new
+    })
 }
 
-struct DescMatchVisitor<'a, 'tcx: 'a> {
-    st: &'a CommandState,
-    cx: &'a RefactorCtxt<'a, 'tcx>,
-    old: HashSet<NodeId>,
+struct DescMatchVisitor<'a, M> {
+    matches: &'a M,
+    old: &'a HashSet<NodeId>,
     new: HashSet<NodeId>,
     /// Are we at a descendant of a node that was selected in the `old` set?
     in_old: bool,
-    filt: &'a Filter,
 }
 
-impl<'ast, 'a, 'tcx> DescMatchVisitor<'a, 'tcx> {
+impl<'ast, 'a, M: Fn(AnyNode) -> bool> DescMatchVisitor<'a, M> {
     fn matches(&self, node: AnyNode) -> bool {
-        filter::matches_filter(self.st, self.cx, node, self.filt)
+        (self.matches)(node)
     }
 
     fn maybe_enter_old<F: FnOnce(&mut Self)>(&mut self, id: NodeId, func: F) {
@@ -200,7 +244,13 @@ impl<'ast, 'a, 'tcx> DescMatchVisitor<'a, 'tcx> {
     }
 }
 
-impl<'ast, 'a, 'tcx> Visitor<'ast> for DescMatchVisitor<'a, 'tcx> {
+impl<'a, M: Fn(AnyNode) -> bool> SelectVisitor for DescMatchVisitor<'a, M> {
+    fn into_selected(self) -> HashSet<NodeId> {
+        self.new
+    }
+}
+
+impl<'ast, 'a, M: Fn(AnyNode) -> bool> Visitor<'ast> for DescMatchVisitor<'a, M> {
     fn visit_item(&mut self, x: &'ast Item) {
         if self.in_old && self.matches(AnyNode::Item(x)) {
             self.new.insert(x.id);
@@ -292,30 +342,37 @@ pub fn matching_descendants(
     sel: HashSet<NodeId>,
     filt: &Filter,
 ) -> HashSet<NodeId> {
+    descendants_matching(krate, &sel, true, |node| filter::matches_filter(st, cx, node, filt))
+}
+
+/// Find the nodes that match `matches` and are descendants of nodes in `sel`.
+pub fn descendants_matching<M>(
+    krate: &Crate,
+    sel: &HashSet<NodeId>,
+    parallel: bool,
+    matches: M,
+) -> HashSet<NodeId>
+where
+    M: Fn(AnyNode) -> bool + Sync + Send,
+{
     let in_old = sel.contains(&CRATE_NODE_ID);
-    let mut v = DescMatchVisitor {
-        st,
-        cx,
+    run_visitors(krate, parallel, || DescMatchVisitor {
+        matches: &matches,
         old: sel,
         new: HashSet::new(),
         in_old,
-        filt,
-    };
-    visit::walk_crate(&mut v, krate);
-    v.new
+    })
 }
 
-struct FilterVisitor<'a, 'tcx: 'a> {
-    st: &'a CommandState,
-    cx: &'a RefactorCtxt<'a, 'tcx>,
-    old: HashSet<NodeId>,
+struct FilterVisitor<'a, M> {
+    matches: &'a M,
+    old: &'a HashSet<NodeId>,
     new: HashSet<NodeId>,
-    filt: &'a Filter,
 }
 
-impl<'ast, 'a, 'tcx> FilterVisitor<'a, 'tcx> {
+impl<'ast, 'a, M: Fn(AnyNode) -> bool> FilterVisitor<'a, M> {
     fn matches(&self, node: AnyNode) -> bool {
-        filter::matches_filter(self.st, self.cx, node, self.filt)
+        (self.matches)(node)
     }
 
     fn walk_args(&mut self, x: &'ast [Param]) {
@@ -327,7 +384,13 @@ impl<'ast, 'a, 'tcx> FilterVisitor<'a, 'tcx> {
     }
 }
 
-impl<'ast, 'a, 'tcx> Visitor<'ast> for FilterVisitor<'a, 'tcx> {
+impl<'a, M: Fn(AnyNode) -> bool> SelectVisitor for FilterVisitor<'a, M> {
+    fn into_selected(self) -> HashSet<NodeId> {
+        self.new
+    }
+}
+
+impl<'ast, 'a, M: Fn(AnyNode) -> bool> Visitor<'ast> for FilterVisitor<'a, M> {
     fn visit_item(&mut self, x: &'ast Item) {
         if self.old.contains(&x.id) && self.matches(AnyNode::Item(x)) {
             self.new.insert(x.id);
@@ -421,13 +484,25 @@ pub fn filter(
     sel: HashSet<NodeId>,
     filt: &Filter,
 ) -> HashSet<NodeId> {
-    let mut v = FilterVisitor {
-        st,
-        cx,
+    filter_matching(krate, &sel, true, |node| filter::matches_filter(st, cx, node, filt))
+}
+
+/// Find the nodes in `sel` that match `matches`.
+pub fn filter_matching<M>(
+    krate: &Crate,
+    sel: &HashSet<NodeId>,
+    parallel: bool,
+    matches: M,
+) -> HashSet<NodeId>
+where
+    M: Fn(AnyNode) -> bool + Sync + Send,
+{
+    run_visitors(krate, parallel, || FilterVisitor {
+        matches: &matches,
         old: sel,
         new: HashSet::new(),
-        filt,
-    };
-    visit::walk_crate(&mut v, krate);
-    v.new
+    })
 }
+
+#[cfg(test)]
+mod tests;
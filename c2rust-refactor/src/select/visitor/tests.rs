@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+use syntax::ast::*;
+use syntax::parse::{self, ParseSess};
+use syntax::source_map::{FileName, FilePathMapping};
+
+use super::{children_matching, descendants_matching, filter_matching};
+use crate::ast_manip::number_nodes::number_nodes;
+use crate::ast_manip::{par_collect_nodes, visit_nodes};
+use crate::select::filter::AnyNode;
+
+/// Parse a crate of 2,000 top-level items: modules, structs, functions and statics.
+fn generated_crate(sess: &ParseSess) -> Crate {
+    let mut src = String::new();
+    for i in 0..500 {
+        src += &format!("pub mod m{0} {{ pub fn inner{0}(x: i32) -> i32 {{ x * {0} }} }}\n", i);
+        src += &format!("struct S{0} {{ a: i32, b: *mut u8 }}\n", i);
+        src += &format!(
+            "pub fn f{0}(s: &S{0}) -> i32 {{ let y = s.a + {0}; \
+             if y > 10 {{ y }} else {{ m{0}::inner{0}(y) }} }}\n",
+            i
+        );
+        src += &format!("static mut G{0}: i32 = {0} + 1;\n", i);
+    }
+    let name = FileName::Custom("generated".to_owned());
+    let mut krate = parse::parse_crate_from_source_str(name, src, sess).unwrap();
+    number_nodes(&mut krate);
+    assert_eq!(krate.module.items.len(), 2000);
+    krate
+}
+
+fn is_fn(node: AnyNode) -> bool {
+    match node {
+        AnyNode::Item(i) => match i.kind {
+            ItemKind::Fn(..) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn name_ends_in_7(node: AnyNode) -> bool {
+    match node {
+        AnyNode::Item(i) => i.ident.as_str().ends_with('7'),
+        _ => false,
+    }
+}
+
+fn is_binary(node: AnyNode) -> bool {
+    match node {
+        AnyNode::Expr(e) => match e.kind {
+            ExprKind::Binary(..) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[test]
+fn test_parallel_select() {
+    syntax::with_default_globals(|| {
+        let sess = ParseSess::new(FilePathMapping::empty());
+        let krate = generated_crate(&sess);
+        let root = Some(CRATE_NODE_ID).into_iter().collect::<HashSet<_>>();
+
+        // `crate; desc(fn); filter(name(".*7")); child(any);`, evaluated both ways at each step
+        let fns = descendants_matching(&krate, &root, false, is_fn);
+        assert_eq!(fns, descendants_matching(&krate, &root, true, is_fn));
+        assert_eq!(fns.len(), 1000);
+
+        let sevens = filter_matching(&krate, &fns, false, name_ends_in_7);
+        assert_eq!(sevens, filter_matching(&krate, &fns, true, name_ends_in_7));
+        assert_eq!(sevens.len(), 100);
+
+        let children = children_matching(&krate, &sevens, false, |_| true);
+        assert_eq!(children, children_matching(&krate, &sevens, true, |_| true));
+        assert!(!children.is_empty());
+
+        // Collected nodes come out in the order of a sequential traversal
+        let mut binaries = vec![];
+        visit_nodes(&krate, |e: &Expr| {
+            if let ExprKind::Binary(..) = e.kind {
+                binaries.push(e.id);
+            }
+        });
+        let par_binaries = par_collect_nodes(&krate, |e: &Expr| match e.kind {
+            ExprKind::Binary(..) => Some(e.id),
+            _ => None,
+        });
+        assert_eq!(binaries, par_binaries);
+    });
+}
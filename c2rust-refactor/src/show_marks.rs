@@ -0,0 +1,469 @@
+//! `show_marks` command, which renders the source of the crate with the marked nodes
+//! highlighted.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use syntax::ast::*;
+use syntax::source_map::{SourceMap, Span};
+use syntax::symbol::Symbol;
+use syntax::visit::{self, FnKind, Visitor};
+
+use crate::command::{CommandState, DriverCommand, Registry};
+use crate::driver::Phase;
+
+/// Output format of `show_marks`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Format {
+    Ansi,
+    Html,
+}
+
+/// A marked node, located in the source of one file.
+#[derive(Clone, Debug)]
+struct Region {
+    lo: usize,
+    hi: usize,
+    label: Symbol,
+    kind: &'static str,
+    id: NodeId,
+}
+
+/// Collects the spans of all marked nodes.
+struct MarkCollector {
+    marks: HashMap<NodeId, Vec<Symbol>>,
+    found: Vec<(Span, &'static str, NodeId, Symbol)>,
+}
+
+impl MarkCollector {
+    fn collect(&mut self, kind: &'static str, id: NodeId, span: Span) {
+        if let Some(labels) = self.marks.get(&id) {
+            for &label in labels {
+                self.found.push((span, kind, id, label));
+            }
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for MarkCollector {
+    fn visit_item(&mut self, x: &'ast Item) {
+        self.collect("item", x.id, x.span);
+        visit::walk_item(self, x);
+    }
+
+    fn visit_impl_item(&mut self, x: &'ast ImplItem) {
+        self.collect("impl item", x.id, x.span);
+        visit::walk_impl_item(self, x);
+    }
+
+    fn visit_trait_item(&mut self, x: &'ast TraitItem) {
+        self.collect("trait item", x.id, x.span);
+        visit::walk_trait_item(self, x);
+    }
+
+    fn visit_foreign_item(&mut self, x: &'ast ForeignItem) {
+        self.collect("foreign item", x.id, x.span);
+        visit::walk_foreign_item(self, x);
+    }
+
+    fn visit_stmt(&mut self, x: &'ast Stmt) {
+        self.collect("stmt", x.id, x.span);
+        visit::walk_stmt(self, x);
+    }
+
+    fn visit_expr(&mut self, x: &'ast Expr) {
+        self.collect("expr", x.id, x.span);
+        visit::walk_expr(self, x);
+    }
+
+    fn visit_pat(&mut self, x: &'ast Pat) {
+        self.collect("pat", x.id, x.span);
+        visit::walk_pat(self, x);
+    }
+
+    fn visit_ty(&mut self, x: &'ast Ty) {
+        self.collect("ty", x.id, x.span);
+        visit::walk_ty(self, x);
+    }
+
+    fn visit_fn(&mut self, kind: FnKind<'ast>, fd: &'ast FnDecl, span: Span, _id: NodeId) {
+        for arg in &fd.inputs {
+            self.collect("arg", arg.id, arg.pat.span.to(arg.ty.span));
+        }
+        visit::walk_fn(self, kind, fd, span);
+    }
+
+    fn visit_struct_field(&mut self, x: &'ast StructField) {
+        self.collect("field", x.id, x.span);
+        visit::walk_struct_field(self, x);
+    }
+
+    fn visit_mac(&mut self, x: &'ast Mac) {
+        visit::walk_mac(self, x);
+    }
+}
+
+/// A marked source file: its name, its text, and the marked regions in it.
+struct MarkedFile {
+    name: String,
+    src: String,
+    regions: Vec<Region>,
+}
+
+/// Find the marked nodes of the crate, grouped by source file in order of their names. Spans
+/// inside macro expansions are mapped to the macro invocation.
+fn collect_marked_files(st: &CommandState, sm: &SourceMap) -> Vec<MarkedFile> {
+    let mut marks = HashMap::new();
+    for &(id, label) in st.marks().iter() {
+        marks.entry(id).or_insert_with(Vec::new).push(label);
+    }
+
+    let mut v = MarkCollector {
+        marks,
+        found: Vec::new(),
+    };
+    let krate = st.krate();
+    visit::walk_crate(&mut v, &krate);
+    v.collect("crate", CRATE_NODE_ID, krate.span);
+
+    let mut files: BTreeMap<String, MarkedFile> = BTreeMap::new();
+    for (span, kind, id, label) in v.found {
+        let span = span.source_callsite();
+        if span.is_dummy() {
+            continue;
+        }
+        let lo = sm.lookup_byte_offset(span.lo());
+        let src = match lo.sf.src {
+            Some(ref src) => src,
+            None => continue,
+        };
+        let hi = span.hi().0.saturating_sub(lo.sf.start_pos.0) as usize;
+        let lo_pos = lo.pos.0 as usize;
+        if hi < lo_pos || hi > src.len() {
+            continue;
+        }
+
+        let name = lo.sf.name.to_string();
+        files
+            .entry(name.clone())
+            .or_insert_with(|| MarkedFile {
+                name,
+                src: (**src).clone(),
+                regions: Vec::new(),
+            })
+            .regions
+            .push(Region {
+                lo: lo_pos,
+                hi,
+                label,
+                kind,
+                id,
+            });
+    }
+    files.into_iter().map(|(_, file)| file).collect()
+}
+
+/// A piece of the rendering of a marked file.
+#[derive(Debug, PartialEq, Eq)]
+enum Piece<'a> {
+    Text(&'a str),
+    /// Start of the region with the given index
+    Open(usize),
+    /// End of the region with the given index
+    Close(usize),
+}
+
+/// Split `src` into text and region boundaries. Regions nest properly: outer regions open
+/// first, and a region that overlaps the end of an enclosing one is cut off where the
+/// enclosing region ends.
+fn split_regions<'a>(src: &'a str, regions: &[Region]) -> Vec<Piece<'a>> {
+    let mut order = (0..regions.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let (ra, rb) = (&regions[a], &regions[b]);
+        let (label_a, label_b) = (ra.label.as_str(), rb.label.as_str());
+        ra.lo
+            .cmp(&rb.lo)
+            .then(rb.hi.cmp(&ra.hi))
+            .then_with(|| (*label_a).cmp(&*label_b))
+    });
+
+    let mut pieces = Vec::new();
+    let mut pos = 0;
+    // Open regions with the position where each one closes
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    for idx in order {
+        let region = &regions[idx];
+        close_regions(src, region.lo, &mut pieces, &mut stack, &mut pos);
+        if region.lo > pos {
+            pieces.push(Piece::Text(&src[pos..region.lo]));
+            pos = region.lo;
+        }
+        let hi = stack
+            .last()
+            .map_or(region.hi, |&(outer_hi, _)| region.hi.min(outer_hi));
+        pieces.push(Piece::Open(idx));
+        stack.push((hi, idx));
+    }
+    close_regions(src, src.len(), &mut pieces, &mut stack, &mut pos);
+    if pos < src.len() {
+        pieces.push(Piece::Text(&src[pos..]));
+    }
+    pieces
+}
+
+/// Close the open regions on `stack` that end at or before `until`, innermost first.
+fn close_regions<'a>(
+    src: &'a str,
+    until: usize,
+    pieces: &mut Vec<Piece<'a>>,
+    stack: &mut Vec<(usize, usize)>,
+    pos: &mut usize,
+) {
+    while let Some(&(hi, idx)) = stack.last() {
+        if hi > until {
+            break;
+        }
+        if hi > *pos {
+            pieces.push(Piece::Text(&src[*pos..hi]));
+            *pos = hi;
+        }
+        pieces.push(Piece::Close(idx));
+        stack.pop();
+    }
+}
+
+/// Number of marks, and the files containing them, of each label, in order of labels.
+fn summarize(files: &[MarkedFile]) -> BTreeMap<String, (usize, Vec<&str>)> {
+    let mut summary: BTreeMap<String, (usize, Vec<&str>)> = BTreeMap::new();
+    for file in files {
+        for region in &file.regions {
+            let entry = summary
+                .entry(region.label.as_str().to_string())
+                .or_insert((0, Vec::new()));
+            entry.0 += 1;
+            if entry.1.last() != Some(&&file.name[..]) {
+                entry.1.push(&file.name);
+            }
+        }
+    }
+    summary
+}
+
+const ANSI_COLORS: &[u8] = &[31, 32, 33, 34, 35, 36];
+
+fn render_ansi(files: &[MarkedFile]) -> String {
+    let summary = summarize(files);
+    let colors = summary
+        .keys()
+        .enumerate()
+        .map(|(i, label)| (label.clone(), ANSI_COLORS[i % ANSI_COLORS.len()]))
+        .collect::<HashMap<_, _>>();
+
+    let mut out = String::new();
+    writeln!(out, "Marks:").unwrap();
+    for (label, (count, file_names)) in &summary {
+        writeln!(
+            out,
+            "  \x1b[1;{}m{}\x1b[0m: {} in {}",
+            colors[label],
+            label,
+            count,
+            file_names.join(", ")
+        )
+        .unwrap();
+    }
+
+    for file in files {
+        writeln!(out, "\n\x1b[1m==== {} ====\x1b[0m", file.name).unwrap();
+        let mut open: Vec<u8> = Vec::new();
+        for piece in split_regions(&file.src, &file.regions) {
+            match piece {
+                Piece::Text(text) => out.push_str(text),
+                Piece::Open(idx) => {
+                    let region = &file.regions[idx];
+                    let color = colors[&*region.label.as_str()];
+                    write!(out, "\x1b[1;{}m[{}:\x1b[22m", color, region.label).unwrap();
+                    open.push(color);
+                }
+                Piece::Close(_) => {
+                    let color = open.pop().unwrap();
+                    write!(out, "\x1b[1;{}m]\x1b[0m", color).unwrap();
+                    // Restore the color of the enclosing region
+                    if let Some(&outer) = open.last() {
+                        write!(out, "\x1b[{}m", outer).unwrap();
+                    }
+                }
+            }
+        }
+        if !file.src.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+const HTML_COLORS: &[&str] = &[
+    "#ffd6d6", "#d6f5d6", "#fff3c4", "#d6e4ff", "#f0d6ff", "#c9f2f2",
+];
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_html(files: &[MarkedFile]) -> String {
+    let summary = summarize(files);
+    let label_classes = summary
+        .keys()
+        .enumerate()
+        .map(|(i, label)| (label.clone(), i))
+        .collect::<HashMap<_, _>>();
+    let file_anchors = files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| (&file.name[..], i))
+        .collect::<HashMap<_, _>>();
+
+    let mut out = String::new();
+    out.push_str(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Marks</title>\n\
+         <style>\n\
+         body { font-family: sans-serif; }\n\
+         pre { font-family: monospace; line-height: 1.6; }\n\
+         .mark { border: 1px solid #888; border-radius: 3px; padding: 1px; }\n\
+         .mark-label { font-size: 70%; font-weight: bold; vertical-align: super; }\n",
+    );
+    for i in 0..summary.len() {
+        writeln!(
+            out,
+            ".label-{} {{ background: {}; }}",
+            i,
+            HTML_COLORS[i % HTML_COLORS.len()]
+        )
+        .unwrap();
+    }
+    out.push_str("</style>\n</head>\n<body>\n<h1>Marks</h1>\n");
+
+    out.push_str(
+        "<table class=\"summary\">\n<tr><th>Label</th><th>Count</th><th>Files</th></tr>\n",
+    );
+    for (label, (count, file_names)) in &summary {
+        let links = file_names
+            .iter()
+            .map(|name| {
+                format!(
+                    "<a href=\"#file-{}\">{}</a>",
+                    file_anchors[name],
+                    escape_html(name)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            out,
+            "<tr><td><span class=\"mark label-{}\">{}</span></td><td>{}</td><td>{}</td></tr>",
+            label_classes[label],
+            escape_html(label),
+            count,
+            links
+        )
+        .unwrap();
+    }
+    out.push_str("</table>\n");
+
+    for (i, file) in files.iter().enumerate() {
+        writeln!(
+            out,
+            "<h2 id=\"file-{}\">{}</h2>\n<pre>",
+            i,
+            escape_html(&file.name)
+        )
+        .unwrap();
+        for piece in split_regions(&file.src, &file.regions) {
+            match piece {
+                Piece::Text(text) => out.push_str(&escape_html(text)),
+                Piece::Open(idx) => {
+                    let region = &file.regions[idx];
+                    let label = region.label.as_str();
+                    write!(
+                        out,
+                        "<span class=\"mark label-{}\" title=\"{} ({} {})\">\
+                         <span class=\"mark-label\">{}</span>",
+                        label_classes[&*label],
+                        escape_html(&label),
+                        region.kind,
+                        region.id.as_usize(),
+                        escape_html(&label)
+                    )
+                    .unwrap();
+                }
+                Piece::Close(_) => out.push_str("</span>"),
+            }
+        }
+        out.push_str("</pre>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// # `show_marks` Command
+///
+/// Usage: `show_marks [--format ansi|html] [--out PATH]`
+///
+/// Marks: reads all
+///
+/// Render every source file containing marked nodes, with each marked node
+/// highlighted in the color of its label, and preceded by a summary of the
+/// number of marks of each label and the files they are in. Marks on nodes
+/// inside macro expansions are shown on the macro invocation. Files without
+/// marks are left out.
+///
+/// With `--format ansi`, the default, marked nodes are wrapped in colored
+/// `[label: ...]` brackets for viewing in a terminal. With `--format html`,
+/// the output is a single self-contained HTML page. The output is printed,
+/// or written to `PATH` if `--out` is given.
+fn register_show_marks(reg: &mut Registry) {
+    reg.register("show_marks", |args| {
+        let mut format = Format::Ansi;
+        let mut out = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--format" => {
+                    format = match args.next().map(|s| &s[..]) {
+                        Some("ansi") => Format::Ansi,
+                        Some("html") => Format::Html,
+                        other => panic!("show_marks: unknown format {:?}", other),
+                    }
+                }
+                "--out" => out = Some(args.next().expect("show_marks: --out needs a path").clone()),
+                _ => panic!("show_marks: unexpected argument {:?}", arg),
+            }
+        }
+
+        Box::new(DriverCommand::new(Phase::Phase2, move |st, cx| {
+            let files = collect_marked_files(st, cx.session().source_map());
+            let rendered = match format {
+                Format::Ansi => render_ansi(&files),
+                Format::Html => render_html(&files),
+            };
+            match out {
+                Some(ref path) => fs::write(path, rendered)
+                    .unwrap_or_else(|e| panic!("show_marks: failed to write {}: {}", path, e)),
+                None => print!("{}", rendered),
+            }
+        }))
+    });
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    register_show_marks(reg);
+}
@@ -0,0 +1,584 @@
+use std::collections::{HashMap, HashSet};
+use rustc::hir::{self, HirId};
+use rustc::ty::{self, TyKind};
+use syntax::ast::*;
+use syntax::ptr::P;
+use syntax::symbol::Symbol;
+use syntax::visit::{self, Visitor};
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{FlatMapNodes, MutVisitNodes, visit_nodes};
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::reflect::reflect_tcx_ty;
+use crate::transform::util::{foreign_fn_name, is_local, is_null, stmt_expr, strip_casts};
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `libc_alloc_to_global` Command
+///
+/// Usage: `libc_alloc_to_global`
+///
+/// Replace the libc allocator calls on local pointers with the global allocator of
+/// `std::alloc`, which needs the `Layout` of each allocation to free it again:
+///
+///  * `malloc(n * size_of::<T>())` becomes `alloc(layout)`, and `calloc(n, size_of::<T>())`
+///    becomes `alloc_zeroed(layout)`, where `layout` is stored in a new `<ptr>_layout` local
+///    next to the pointer,
+///  * `realloc(p, n * size_of::<T>())` becomes `alloc` of the new layout when `p` is null and
+///    `realloc` from the stored layout otherwise, and updates the stored layout, and
+///  * `free(p)` becomes `dealloc(p, <ptr>_layout)` if `p` is not null.
+///
+/// Non-literal element counts are rounded up to one, since the global allocator does not
+/// support zero-sized allocations where `malloc(0)` may return a unique pointer.
+///
+/// The pointer must be a local of type `*mut T`, and every allocation assigned to it must
+/// have a size of a whole number of `T`s, so that its layout is known.  Pointers that are
+/// assigned anything other than a fresh allocation or null, or that are used other than by
+/// dereferencing, offsetting, comparing or passing them to libc string and memory functions,
+/// might be freed with a different layout and are left alone with an error.
+///
+/// Example:
+///
+/// ```ignore
+///     let mut buf: *mut libc::c_int =
+///         malloc((n as libc::c_ulong).wrapping_mul(::std::mem::size_of::<libc::c_int>()
+///                                                  as libc::c_ulong)) as *mut libc::c_int;
+///     *buf.offset(0) = 1;
+///     free(buf as *mut libc::c_void);
+/// ```
+///
+/// After running `libc_alloc_to_global`:
+///
+/// ```ignore
+///     let buf_layout = ::std::alloc::Layout::from_size_align(
+///         (n as usize).max(1) * ::std::mem::size_of::<libc::c_int>(),
+///         ::std::mem::align_of::<libc::c_int>(),
+///     ).unwrap();
+///     let mut buf: *mut libc::c_int = ::std::alloc::alloc(buf_layout) as *mut libc::c_int;
+///     *buf.offset(0) = 1;
+///     if !buf.is_null() {
+///         ::std::alloc::dealloc(buf as *mut u8, buf_layout)
+///     }
+/// ```
+pub struct LibcAllocToGlobal;
+
+impl Transform for LibcAllocToGlobal {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |b: &mut P<Block>| {
+            let mut i = 0;
+            while i < b.stmts.len() {
+                let ptr = match ptr_local(cx, &b.stmts[i]) {
+                    Some(ptr) => ptr,
+                    None => {
+                        i += 1;
+                        continue;
+                    }
+                };
+
+                match AllocPlan::new(cx, &ptr, &b.stmts[i..]) {
+                    Ok(Some(plan)) => {
+                        plan.rewrite(b, i, &ptr);
+                        // Skip the new layout local as well
+                        i += 2;
+                    }
+                    Ok(None) => i += 1,
+                    Err(reason) => {
+                        eprintln!("libc_alloc_to_global: {}: {}", ptr.name, reason);
+                        i += 1;
+                    }
+                }
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+struct PtrInfo<'tcx> {
+    hir_id: HirId,
+    name: Ident,
+    elem: ty::Ty<'tcx>,
+    elem_ty: P<Ty>,
+}
+
+impl<'tcx> PtrInfo<'tcx> {
+    fn layout_name(&self) -> Ident {
+        Ident::from_str(&format!("{}_layout", self.name))
+    }
+
+    fn ptr_ty(&self) -> P<Ty> {
+        mk().mutbl().ptr_ty(self.elem_ty.clone())
+    }
+}
+
+/// If `stmt` declares a `*mut T` local, get its info.
+fn ptr_local<'tcx>(cx: &RefactorCtxt<'_, 'tcx>, stmt: &Stmt) -> Option<PtrInfo<'tcx>> {
+    let l = match_or!([stmt.kind] StmtKind::Local(ref l) => l; return None);
+    let name = match_or!([l.pat.kind] PatKind::Ident(BindingMode::ByValue(_), name, None) => name;
+                         return None);
+    let ty = cx.node_type(l.pat.id);
+    let elem = match ty.kind {
+        TyKind::RawPtr(mt) if mt.mutbl == hir::Mutability::MutMutable => mt.ty,
+        _ => return None,
+    };
+    let elem_ty = match l.ty.as_ref().map(|ty| &ty.kind) {
+        Some(syntax::ast::TyKind::Ptr(mt)) => mt.ty.clone(),
+        _ => reflect_tcx_ty(cx.ty_ctxt(), elem),
+    };
+
+    Some(PtrInfo {
+        hir_id: cx.hir_map().node_to_hir_id(l.pat.id),
+        name,
+        elem,
+        elem_ty,
+    })
+}
+
+/// If `e` is a call to the libc `malloc`, `calloc` or `realloc`, get its name and arguments.
+fn libc_alloc<'a>(cx: &RefactorCtxt, e: &'a Expr) -> Option<(Symbol, &'a [P<Expr>])> {
+    let (func, args) = match_or!([strip_casts(e).kind] ExprKind::Call(ref func, ref args)
+                                 => (func, args); return None);
+    let name = foreign_fn_name(cx, func)?;
+    match (&*name.as_str(), args.len()) {
+        ("malloc", 1) | ("calloc", 2) | ("realloc", 2) => Some((name, args)),
+        _ => None,
+    }
+}
+
+/// If `stmt` is `free(p)`, get `p`.
+fn libc_free<'a>(cx: &RefactorCtxt, stmt: &'a Stmt) -> Option<&'a Expr> {
+    let e = stmt_expr(stmt)?;
+    let (func, args) = match_or!([e.kind] ExprKind::Call(ref func, ref args) => (func, args);
+                                 return None);
+    match foreign_fn_name(cx, func) {
+        Some(name) if name.as_str() == "free" && args.len() == 1 => Some(&args[0]),
+        _ => None,
+    }
+}
+
+/// libc functions that may read and write through a pointer argument, but never free it or
+/// keep it after returning.
+const NON_OWNING_FNS: &[&str] = &[
+    "memset", "memcpy", "memmove", "memcmp", "memchr",
+    "strlen", "strcpy", "strncpy", "strcat", "strncat", "strcmp", "strncmp", "strchr",
+    "strrchr", "strstr", "sprintf", "snprintf", "printf", "puts", "fputs", "fwrite", "fread",
+    "fgets", "qsort",
+];
+
+/// Check whether `e` is `size_of::<T>()` for the element type `T` of the pointer.
+fn is_size_of(cx: &RefactorCtxt, ptr: &PtrInfo, e: &Expr) -> bool {
+    let func = match strip_casts(e).kind {
+        ExprKind::Call(ref func, ref args) if args.is_empty() => func,
+        _ => return false,
+    };
+    match cx.opt_node_type(func.id).map(|ty| &ty.kind) {
+        Some(&TyKind::FnDef(def_id, substs)) => {
+            cx.ty_ctxt().item_name(def_id).as_str() == "size_of" &&
+                substs.types().next() == Some(ptr.elem)
+        }
+        _ => false,
+    }
+}
+
+/// The number of elements of the pointer's type in the allocation size `size`, which must be
+/// `size_of::<T>()` or a multiple of it.
+fn element_count<'a>(cx: &RefactorCtxt,
+                     ptr: &PtrInfo,
+                     size: &'a Expr) -> Option<Option<&'a Expr>> {
+    if is_size_of(cx, ptr, size) {
+        return Some(None);
+    }
+    let (a, b) = match strip_casts(size).kind {
+        ExprKind::Binary(op, ref a, ref b) if op.node == BinOpKind::Mul => (a, b),
+        ExprKind::MethodCall(ref seg, ref args) if seg.ident.as_str() == "wrapping_mul" &&
+                args.len() == 2 => (&args[0], &args[1]),
+        _ => return None,
+    };
+    if is_size_of(cx, ptr, b) {
+        Some(Some(a))
+    } else if is_size_of(cx, ptr, a) {
+        Some(Some(b))
+    } else {
+        None
+    }
+}
+
+/// Build the `Layout` of `count` elements of the pointer's type, or of a single one.
+fn layout_expr(ptr: &PtrInfo, count: Option<&Expr>) -> P<Expr> {
+    let std_fn = |module: &str, name: &str| {
+        mk().call_expr(
+            mk().path_expr(vec![
+                mk().path_segment(""),
+                mk().path_segment("std"),
+                mk().path_segment(module),
+                mk().path_segment_with_args(
+                    name,
+                    mk().angle_bracketed_args(vec![ptr.elem_ty.clone()]),
+                ),
+            ]),
+            Vec::<P<Expr>>::new(),
+        )
+    };
+
+    let count = match count {
+        Some(count) => count,
+        None => {
+            return mk().call_expr(
+                mk().path_expr(vec![
+                    mk().path_segment(""),
+                    mk().path_segment("std"),
+                    mk().path_segment("alloc"),
+                    mk().path_segment("Layout"),
+                    mk().path_segment_with_args(
+                        "new",
+                        mk().angle_bracketed_args(vec![ptr.elem_ty.clone()]),
+                    ),
+                ]),
+                Vec::<P<Expr>>::new(),
+            );
+        }
+    };
+
+    let count = strip_casts(count);
+    let count = match count.kind {
+        ExprKind::Lit(Lit { kind: LitKind::Int(n, _), .. }) => {
+            mk().lit_expr(mk().int_lit(n.max(1), LitIntType::Unsuffixed))
+        }
+        _ => {
+            let count = match count.kind {
+                ExprKind::Path(..) | ExprKind::Field(..) | ExprKind::Call(..) |
+                ExprKind::MethodCall(..) => P(count.clone()),
+                _ => mk().paren_expr(count.clone()),
+            };
+            mk().method_call_expr(
+                mk().paren_expr(mk().cast_expr(count, mk().path_ty(vec!["usize"]))),
+                "max",
+                vec![mk().lit_expr(mk().int_lit(1, LitIntType::Unsuffixed))],
+            )
+        }
+    };
+
+    mk().method_call_expr(
+        mk().call_expr(
+            mk().path_expr(vec!["", "std", "alloc", "Layout", "from_size_align"]),
+            vec![
+                mk().binary_expr(BinOpKind::Mul, count, std_fn("mem", "size_of")),
+                std_fn("mem", "align_of"),
+            ],
+        ),
+        "unwrap",
+        Vec::<P<Expr>>::new(),
+    )
+}
+
+/// The `Layout` of an allocation assigned to the pointer: `malloc(size) as *mut T` or
+/// `calloc(n, size) as *mut T`, and whether it's zeroed.
+fn alloc_layout(cx: &RefactorCtxt,
+                ptr: &PtrInfo,
+                name: Symbol,
+                args: &[P<Expr>]) -> Result<(P<Expr>, bool), &'static str> {
+    let unknown = "the size of an allocation is not a multiple of the pointer's element size";
+    if name.as_str() == "calloc" {
+        let count = if is_size_of(cx, ptr, &args[1]) {
+            &args[0]
+        } else if is_size_of(cx, ptr, &args[0]) {
+            &args[1]
+        } else {
+            return Err(unknown);
+        };
+        Ok((layout_expr(ptr, Some(&**count)), true))
+    } else {
+        let size = args.last().unwrap();
+        let count = element_count(cx, ptr, size).ok_or(unknown)?;
+        Ok((layout_expr(ptr, count), false))
+    }
+}
+
+/// How each allocation, reallocation and free of a pointer gets rewritten.
+#[derive(Default)]
+struct AllocPlan {
+    /// The layout the pointer is initialized with, and whether it's zeroed
+    init: Option<(P<Expr>, bool)>,
+    /// Statements assigning a fresh allocation to the pointer, with its layout and whether it's
+    /// zeroed
+    allocs: HashMap<NodeId, (P<Expr>, bool)>,
+    /// Statements reallocating the pointer, with the new layout
+    reallocs: HashMap<NodeId, P<Expr>>,
+    /// Statements freeing the pointer
+    frees: HashSet<NodeId>,
+}
+
+impl AllocPlan {
+    /// Check the uses of the pointer declared by `stmts[0]`.  Returns `None` if it is never
+    /// allocated with the libc allocator, and an error if it is but its layout can't be tracked
+    /// up to the matching frees.
+    fn new(cx: &RefactorCtxt,
+           ptr: &PtrInfo,
+           stmts: &[Stmt]) -> Result<Option<AllocPlan>, &'static str> {
+        let mut plan = AllocPlan::default();
+
+        let l = expect!([stmts[0].kind] StmtKind::Local(ref l) => l);
+        let mut checker = UseChecker {
+            cx,
+            ptr,
+            plan: &mut plan,
+            error: None,
+        };
+        match l.init {
+            Some(ref init) => match libc_alloc(cx, init) {
+                Some((name, args)) if name.as_str() != "realloc" => {
+                    checker.plan.init = Some(alloc_layout(cx, ptr, name, args)?);
+                    for arg in args {
+                        checker.visit_expr(arg);
+                    }
+                }
+                _ if is_null(init) => {}
+                _ => checker.fail("it is initialized with a pointer that wasn't allocated here"),
+            },
+            None => return Ok(None),
+        }
+        for stmt in &stmts[1..] {
+            checker.visit_stmt(stmt);
+        }
+        let error = checker.error;
+
+        let allocated = plan.init.is_some() || !plan.allocs.is_empty() ||
+            !plan.reallocs.is_empty();
+        if !allocated {
+            return Ok(None);
+        }
+        if let Some(reason) = error {
+            return Err(reason);
+        }
+
+        // The layout local must not shadow or be shadowed by another variable
+        let layout_name = ptr.layout_name();
+        let mut collides = false;
+        for stmt in stmts {
+            visit_nodes(stmt, |p: &Pat| {
+                if let PatKind::Ident(_, ident, _) = p.kind {
+                    collides |= ident.name == layout_name.name;
+                }
+            });
+            visit_nodes(stmt, |e: &Expr| {
+                if let ExprKind::Path(None, ref path) = e.kind {
+                    collides |= path.segments.len() == 1 &&
+                        path.segments[0].ident.name == layout_name.name;
+                }
+            });
+        }
+        if collides {
+            return Err("its layout local would collide with an existing name");
+        }
+
+        Ok(Some(plan))
+    }
+
+    fn rewrite(&self, b: &mut P<Block>, decl: usize, ptr: &PtrInfo) {
+        let layout_name = ptr.layout_name();
+        let layout = || mk().ident_expr(layout_name);
+        let alloc = |arg: P<Expr>, zeroed: bool| {
+            let name = if zeroed { "alloc_zeroed" } else { "alloc" };
+            mk().call_expr(mk().path_expr(vec!["", "std", "alloc", name]), vec![arg])
+        };
+        let ptr_expr = || mk().ident_expr(ptr.name);
+        let byte_ptr = || {
+            mk().cast_expr(ptr_expr(), mk().mutbl().ptr_ty(mk().path_ty(vec!["u8"])))
+        };
+
+        FlatMapNodes::visit(b, |s: Stmt| {
+            if let Some((new_layout, zeroed)) = self.allocs.get(&s.id) {
+                smallvec![
+                    mk().semi_stmt(mk().assign_expr(layout(), new_layout.clone())),
+                    mk().semi_stmt(mk().assign_expr(
+                        ptr_expr(),
+                        mk().cast_expr(alloc(layout(), *zeroed), ptr.ptr_ty()),
+                    )),
+                ]
+            } else if let Some(new_layout) = self.reallocs.get(&s.id) {
+                let new_name = mk().ident_expr("new_layout");
+                let realloc = mk().call_expr(
+                    mk().path_expr(vec!["", "std", "alloc", "realloc"]),
+                    vec![
+                        byte_ptr(),
+                        layout(),
+                        mk().method_call_expr(new_name.clone(), "size", Vec::<P<Expr>>::new()),
+                    ],
+                );
+                let new_ptr = mk().ifte_expr(
+                    mk().method_call_expr(ptr_expr(), "is_null", Vec::<P<Expr>>::new()),
+                    mk().block(vec![mk().expr_stmt(alloc(new_name.clone(), false))]),
+                    Some(mk().block_expr(mk().block(vec![mk().expr_stmt(realloc)]))),
+                );
+                smallvec![mk().expr_stmt(mk().block_expr(mk().block(vec![
+                    mk().local_stmt(P(mk().local(
+                        mk().ident_pat("new_layout"),
+                        None as Option<P<Ty>>,
+                        Some(new_layout.clone()),
+                    ))),
+                    mk().semi_stmt(mk().assign_expr(
+                        ptr_expr(),
+                        mk().cast_expr(mk().paren_expr(new_ptr), ptr.ptr_ty()),
+                    )),
+                    mk().semi_stmt(mk().assign_expr(layout(), new_name)),
+                ])))]
+            } else if self.frees.contains(&s.id) {
+                let dealloc = mk().call_expr(
+                    mk().path_expr(vec!["", "std", "alloc", "dealloc"]),
+                    vec![byte_ptr(), layout()],
+                );
+                smallvec![mk().expr_stmt(mk().ifte_expr(
+                    mk().unary_expr(
+                        "!",
+                        mk().method_call_expr(ptr_expr(), "is_null", Vec::<P<Expr>>::new()),
+                    ),
+                    mk().block(vec![mk().expr_stmt(dealloc)]),
+                    None::<P<Expr>>,
+                ))]
+            } else {
+                smallvec![s]
+            }
+        });
+
+        let reassigned = !self.allocs.is_empty() || !self.reallocs.is_empty();
+        let pat = if reassigned {
+            mk().mutbl().ident_pat(layout_name)
+        } else {
+            mk().ident_pat(layout_name)
+        };
+        let init = match self.init {
+            Some((ref init, _)) => init.clone(),
+            // Never read before the first allocation assigns the real layout
+            None => layout_expr(ptr, None),
+        };
+        let layout_decl = mk().local_stmt(P(mk().local(pat, None as Option<P<Ty>>, Some(init))));
+        b.stmts.insert(decl, layout_decl);
+
+        if let Some((_, zeroed)) = self.init {
+            let l = expect!([b.stmts[decl + 1].kind] StmtKind::Local(ref mut l) => l);
+            l.init = Some(mk().cast_expr(alloc(layout(), zeroed), ptr.ptr_ty()));
+        }
+    }
+}
+
+/// Checks that a pointer is only ever assigned fresh allocations or null, and never escapes
+/// to code that could free it, and records the statements to rewrite.
+struct UseChecker<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    ptr: &'a PtrInfo<'tcx>,
+    plan: &'a mut AllocPlan,
+    error: Option<&'static str>,
+}
+
+impl<'a, 'tcx> UseChecker<'a, 'tcx> {
+    fn fail(&mut self, reason: &'static str) {
+        if self.error.is_none() {
+            self.error = Some(reason);
+        }
+    }
+
+    fn is_ptr(&self, e: &Expr) -> bool {
+        is_local(self.cx, self.ptr.hir_id, e)
+    }
+}
+
+impl<'a, 'tcx, 'ast> Visitor<'ast> for UseChecker<'a, 'tcx> {
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        let cx = self.cx;
+        if let Some(arg) = libc_free(cx, s) {
+            if self.is_ptr(arg) {
+                self.plan.frees.insert(s.id);
+                return;
+            }
+        }
+
+        let (lhs, rhs) = match stmt_expr(s).map(|e| &e.kind) {
+            Some(ExprKind::Assign(lhs, rhs)) => (lhs, rhs),
+            _ => return visit::walk_stmt(self, s),
+        };
+        if !self.is_ptr(lhs) {
+            return visit::walk_stmt(self, s);
+        }
+
+        match libc_alloc(cx, rhs) {
+            Some((name, args)) if name.as_str() == "realloc" => {
+                if !self.is_ptr(&args[0]) {
+                    self.fail("it is reallocated from a different pointer");
+                }
+                match element_count(cx, self.ptr, &args[1]) {
+                    Some(count) => {
+                        self.plan.reallocs.insert(s.id, layout_expr(self.ptr, count));
+                    }
+                    None => self.fail("the size of an allocation is not a multiple of the \
+                                       pointer's element size"),
+                }
+                self.visit_expr(&args[1]);
+            }
+            Some((name, args)) => {
+                match alloc_layout(cx, self.ptr, name, args) {
+                    Ok(layout) => {
+                        self.plan.allocs.insert(s.id, layout);
+                    }
+                    Err(reason) => self.fail(reason),
+                }
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+            }
+            None if is_null(rhs) => {}
+            None => self.fail("it is assigned a pointer that wasn't allocated here"),
+        }
+    }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        let cx = self.cx;
+        if self.is_ptr(e) {
+            self.fail("it is used other than by dereferencing, offsetting or comparing it, so \
+                       it may be freed elsewhere");
+            return;
+        }
+
+        match e.kind {
+            ExprKind::Assign(ref lhs, _) | ExprKind::AssignOp(_, ref lhs, _)
+                    if self.is_ptr(lhs) => {
+                self.fail("it is assigned inside an expression");
+            }
+            ExprKind::Unary(UnOp::Deref, ref inner) if self.is_ptr(inner) => {}
+            ExprKind::MethodCall(_, ref args) if self.is_ptr(&args[0]) => {
+                for arg in &args[1..] {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprKind::Binary(op, ref a, ref b) if op.node.is_comparison() => {
+                for &side in &[a, b] {
+                    if !self.is_ptr(side) {
+                        self.visit_expr(side);
+                    }
+                }
+            }
+            ExprKind::Call(ref func, ref args) if foreign_fn_name(cx, func)
+                    .map_or(false, |name| NON_OWNING_FNS.contains(&&*name.as_str())) => {
+                for arg in args {
+                    if !self.is_ptr(arg) {
+                        self.visit_expr(arg);
+                    }
+                }
+            }
+            _ => visit::walk_expr(self, e),
+        }
+    }
+
+    fn visit_mac(&mut self, mac: &'ast Mac) {
+        visit::walk_mac(self, mac);
+    }
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("libc_alloc_to_global", |_args| mk(LibcAllocToGlobal));
+}
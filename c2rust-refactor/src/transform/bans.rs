@@ -0,0 +1,333 @@
+use std::fs;
+use std::process;
+use indexmap::IndexMap;
+use json::{self, JsonValue};
+use regex::Regex;
+use rustc::hir::def_id::DefId;
+use rustc::ty;
+use syntax::ast::*;
+use syntax::visit::{self, Visitor};
+
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `ban_api` Command
+///
+/// Usage: `ban_api CONFIG [--fail-over N] [--json PATH]`
+///
+/// Find the uses of the APIs banned by the rules in the TOML file `CONFIG`, to track how many
+/// uses of `malloc`, `memcpy`, `transmute`, `static mut`s or raw pointers are left in a
+/// translated crate.  Each `[[rule]]` table has a `name` and any of:
+///
+///  * `paths`, the paths of banned functions, statics and types, like `libc::malloc`,
+///  * `patterns`, regular expressions matching such paths, like `"::mem(cpy|move)$"`,
+///  * `kinds`, banned kinds of nodes: `static_mut` (uses of `static mut`s), `raw_deref`
+///    (dereferences of raw pointers) and `unsafe_block`, and
+///  * `budget`, the number of uses the rule allows.
+///
+/// Paths and method calls are matched by the definitions they resolve to, not by how they are
+/// written, so `use` renames don't hide a use.  Definitions are named by their absolute paths,
+/// starting with `crate::` in the crate itself and with `std::intrinsics::transmute`, not the
+/// `std::mem` reexport, for `transmute`.  Foreign functions and statics declared in the crate are
+/// also named `libc::NAME`, so that `libc::malloc` covers the `malloc` declarations of translated
+/// modules.
+///
+/// Every use is marked with the name of the rule it breaks, and the number of uses of each rule
+/// in each module is printed, and written to `PATH` as JSON with `--json`.  With `--fail-over`,
+/// the command exits with an error, without saving the crate, when any rule has more uses than
+/// its `budget`, or than `N` if it has none.
+///
+/// Example:
+///
+/// ```ignore
+///     [[rule]]
+///     name = "alloc"
+///     paths = ["libc::malloc", "libc::free"]
+///     budget = 10
+///
+///     [[rule]]
+///     name = "raw_deref"
+///     kinds = ["raw_deref"]
+/// ```
+pub struct BanApi {
+    rules: Vec<Rule>,
+    fail_over: Option<usize>,
+    json: Option<String>,
+}
+
+/// A kind of node banned by a rule, regardless of the definitions it uses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum NodeKind {
+    StaticMut,
+    RawDeref,
+    UnsafeBlock,
+}
+
+impl NodeKind {
+    fn from_str(s: &str) -> Option<NodeKind> {
+        match s {
+            "static_mut" => Some(NodeKind::StaticMut),
+            "raw_deref" => Some(NodeKind::RawDeref),
+            "unsafe_block" => Some(NodeKind::UnsafeBlock),
+            _ => None,
+        }
+    }
+}
+
+struct Rule {
+    name: String,
+    paths: Vec<String>,
+    patterns: Vec<Regex>,
+    kinds: Vec<NodeKind>,
+    budget: Option<usize>,
+}
+
+impl Rule {
+    fn bans_path(&self, path: &str) -> bool {
+        self.paths.iter().any(|p| p == path) || self.patterns.iter().any(|re| re.is_match(path))
+    }
+}
+
+fn strings(table: &toml::value::Table, key: &str) -> Result<Vec<String>, String> {
+    let values = match table.get(key) {
+        Some(v) => v.as_array().ok_or_else(|| format!("`{}` is not an array", key))?,
+        None => return Ok(Vec::new()),
+    };
+    values.iter()
+        .map(|v| v.as_str()
+             .map(|s| s.to_owned())
+             .ok_or_else(|| format!("`{}` has an element that is not a string", key)))
+        .collect()
+}
+
+fn parse_rule(table: &toml::value::Table) -> Result<Rule, String> {
+    let name = table.get("name").and_then(|v| v.as_str())
+        .ok_or_else(|| "a rule has no `name`".to_owned())?
+        .to_owned();
+    let rule_err = |e: String| format!("rule `{}`: {}", name, e);
+
+    let patterns: Vec<Regex> = strings(table, "patterns").map_err(rule_err)?
+        .iter()
+        .map(|p| Regex::new(p).map_err(|e| rule_err(e.to_string())))
+        .collect::<Result<_, _>>()?;
+    let kinds: Vec<NodeKind> = strings(table, "kinds").map_err(rule_err)?
+        .iter()
+        .map(|k| NodeKind::from_str(k)
+             .ok_or_else(|| rule_err(format!("unknown node kind `{}`", k))))
+        .collect::<Result<_, _>>()?;
+    let budget = match table.get("budget") {
+        Some(v) => match v.as_integer() {
+            Some(n) if n >= 0 => Some(n as usize),
+            _ => return Err(rule_err("`budget` is not a count".to_owned())),
+        },
+        None => None,
+    };
+
+    Ok(Rule {
+        paths: strings(table, "paths").map_err(rule_err)?,
+        patterns,
+        kinds,
+        budget,
+        name,
+    })
+}
+
+fn load_rules(path: &str) -> Result<Vec<Rule>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let config = text.parse::<toml::Value>().map_err(|e| e.to_string())?;
+    let tables = config.get("rule").and_then(|v| v.as_array())
+        .ok_or_else(|| "no `[[rule]]` tables".to_owned())?;
+    tables.iter()
+        .map(|t| t.as_table().ok_or_else(|| "`rule` is not a table".to_owned())
+             .and_then(parse_rule))
+        .collect()
+}
+
+/// Name `did` the way rules spell paths.  Foreign items of the crate also go by `libc::NAME`.
+fn def_names(cx: &RefactorCtxt, did: DefId) -> Vec<String> {
+    let tcx = cx.ty_ctxt();
+    let path = tcx.def_path_str(did);
+    let mut names = vec![if did.is_local() { format!("crate::{}", path) } else { path }];
+    if did.is_local() && tcx.is_foreign_item(did) {
+        names.push(format!("libc::{}", tcx.item_name(did)));
+    }
+    names
+}
+
+/// Collects the uses of banned APIs, with the module each one is in.
+struct BanVisitor<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    rules: &'a [Rule],
+    module: Vec<String>,
+    /// The `NodeId` and module of each use, and the index of the rule it breaks.
+    hits: Vec<(NodeId, String, usize)>,
+}
+
+impl<'a, 'tcx> BanVisitor<'a, 'tcx> {
+    fn record(&mut self, id: NodeId, banned: impl Fn(&Rule) -> bool) {
+        let module = self.module.join("::");
+        for (i, rule) in self.rules.iter().enumerate() {
+            if banned(rule) {
+                self.hits.push((id, module.clone(), i));
+            }
+        }
+    }
+
+    fn record_def(&mut self, id: NodeId, did: DefId) {
+        let names = def_names(self.cx, did);
+        let static_mut = self.cx.ty_ctxt().is_mutable_static(did);
+        self.record(id, |rule| {
+            names.iter().any(|n| rule.bans_path(n)) ||
+                (static_mut && rule.kinds.contains(&NodeKind::StaticMut))
+        });
+    }
+
+    fn record_kind(&mut self, id: NodeId, kind: NodeKind) {
+        self.record(id, |rule| rule.kinds.contains(&kind));
+    }
+}
+
+impl<'a, 'tcx, 'ast> Visitor<'ast> for BanVisitor<'a, 'tcx> {
+    fn visit_item(&mut self, i: &'ast Item) {
+        if let ItemKind::Mod(_) = i.kind {
+            self.module.push(i.ident.to_string());
+            visit::walk_item(self, i);
+            self.module.pop();
+        } else {
+            visit::walk_item(self, i);
+        }
+    }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        let cx = self.cx;
+        match e.kind {
+            ExprKind::Path(..) => {
+                if let Some(did) = cx.try_resolve_expr(e) {
+                    self.record_def(e.id, did);
+                }
+            }
+            ExprKind::MethodCall(..) => {
+                if let Some(did) = cx.opt_callee(e) {
+                    self.record_def(e.id, did);
+                }
+            }
+            ExprKind::Unary(UnOp::Deref, ref inner) => {
+                if let Some(&ty::TyKind::RawPtr(_)) = cx.opt_node_type(inner.id).map(|t| &t.kind) {
+                    self.record_kind(e.id, NodeKind::RawDeref);
+                }
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, e);
+    }
+
+    fn visit_ty(&mut self, t: &'ast Ty) {
+        if let TyKind::Path(..) = t.kind {
+            if let Some(did) = self.cx.try_resolve_ty(t) {
+                self.record_def(t.id, did);
+            }
+        }
+        visit::walk_ty(self, t);
+    }
+
+    fn visit_block(&mut self, b: &'ast Block) {
+        if let BlockCheckMode::Unsafe(UnsafeSource::UserProvided) = b.rules {
+            self.record_kind(b.id, NodeKind::UnsafeBlock);
+        }
+        visit::walk_block(self, b);
+    }
+
+    fn visit_mac(&mut self, _mac: &'ast Mac) {}
+}
+
+impl Transform for BanApi {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        let mut v = BanVisitor {
+            cx,
+            rules: &self.rules,
+            module: vec!["crate".to_owned()],
+            hits: Vec::new(),
+        };
+        visit::walk_crate(&mut v, krate);
+
+        let mut counts = self.rules.iter()
+            .map(|_| IndexMap::<String, usize>::new())
+            .collect::<Vec<_>>();
+        for (id, module, i) in v.hits {
+            st.add_mark(id, &self.rules[i].name);
+            *counts[i].entry(module).or_insert(0) += 1;
+        }
+
+        let mut js = JsonValue::new_object();
+        let mut over = 0;
+        for (rule, modules) in self.rules.iter().zip(&counts) {
+            let total = modules.values().sum::<usize>();
+            let budget = rule.budget.or(self.fail_over);
+            match budget {
+                Some(b) if total > b => {
+                    over += 1;
+                    eprintln!("ban_api: {}: total {} (budget {}, exceeded)", rule.name, total, b);
+                }
+                Some(b) => eprintln!("ban_api: {}: total {} (budget {})", rule.name, total, b),
+                None => eprintln!("ban_api: {}: total {}", rule.name, total),
+            }
+            let mut modules_js = JsonValue::new_object();
+            for (module, &count) in modules {
+                eprintln!("    {}: {}", module, count);
+                modules_js[module.as_str()] = count.into();
+            }
+
+            let mut rule_js = JsonValue::new_object();
+            rule_js["total"] = total.into();
+            rule_js["budget"] = budget.map_or(JsonValue::Null, |b| b.into());
+            rule_js["modules"] = modules_js;
+            js[rule.name.as_str()] = rule_js;
+        }
+
+        if let Some(ref path) = self.json {
+            fs::write(path, json::stringify_pretty(js, 2))
+                .unwrap_or_else(|e| panic!("ban_api: failed to write {}: {}", path, e));
+        }
+
+        if self.fail_over.is_some() && over > 0 {
+            eprintln!("ban_api: {} of {} rules over budget", over, self.rules.len());
+            process::exit(1);
+        }
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+
+    fn cleanup_casts(&self) -> bool {
+        false
+    }
+}
+
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("ban_api", |args| {
+        let config = args.get(0).expect("ban_api: expected a config file");
+        let rules = load_rules(config)
+            .unwrap_or_else(|e| panic!("ban_api: {}: {}", config, e));
+        let mut fail_over = None;
+        let mut json = None;
+        let mut args = args[1..].iter();
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--fail-over" => {
+                    fail_over = Some(args.next().and_then(|n| n.parse().ok())
+                                     .expect("ban_api: --fail-over needs a count"));
+                }
+                "--json" => json = Some(args.next().expect("ban_api: --json needs a path").clone()),
+                _ => panic!("ban_api: unexpected argument {:?}", arg),
+            }
+        }
+        mk(BanApi { rules, fail_over, json })
+    });
+}
@@ -0,0 +1,336 @@
+use std::collections::HashSet;
+use rustc::hir::def_id::DefId;
+use syntax::ast::*;
+use syntax::ptr::P;
+use syntax::symbol::Symbol;
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{MutVisitNodes, visit_nodes};
+use crate::command::{CommandState, Registry};
+use crate::driver::{Phase, parse_ty};
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `widen_bitfield` Command
+///
+/// Usage: `widen_bitfield STRUCT FIELD TY`
+///
+/// Change the type of the bitfield `FIELD` of `STRUCT`, a `#[derive(BitfieldStruct)]` struct
+/// emitted by the transpiler, to the integer type `TY`, and widen the bitfield to all of the
+/// bits of `TY`.  The accessors `FIELD()` and `set_FIELD()` are derived from the
+/// `#[bitfield(name = "FIELD", ty = "...", bits = "...")]` attribute of the field storing the
+/// bitfield, so the layout of that storage is re-derived:
+///
+///  * the bitfields stored after `FIELD` move up by the number of bits it gained,
+///  * the storage array grows to fit them, along with the `[0; N]` initializers of the storage
+///    array in literals of `STRUCT`, and
+///  * the getter and setter are regenerated by the derive from the updated attributes, with the
+///    sign extension of the new type.
+///
+/// The values going in and out of the accessors are cast so that the code using them keeps
+/// seeing the old type: setters are passed `v as TY`, and getters are cast back to the old type,
+/// except where their value is cast anyway and the old type doesn't need sign extending. This
+/// keeps the behavior of the program the same for values that fit in the old bitfield.
+///
+/// Example:
+///
+/// ```ignore
+///     #[derive(BitfieldStruct, Clone, Copy)]
+///     pub struct flags {
+///         #[bitfield(name = "mode", ty = "i8", bits = "0..=2")]
+///         #[bitfield(name = "level", ty = "u8", bits = "3..=6")]
+///         pub mode_level: [u8; 1],
+///     }
+///
+///     f.set_mode(m);
+///     let x = f.mode() as i32;
+/// ```
+///
+/// After running `widen_bitfield flags mode u8`:
+///
+/// ```ignore
+///     #[derive(BitfieldStruct, Clone, Copy)]
+///     pub struct flags {
+///         #[bitfield(name = "mode", ty = "u8", bits = "0..=7")]
+///         #[bitfield(name = "level", ty = "u8", bits = "8..=11")]
+///         pub mode_level: [u8; 2],
+///     }
+///
+///     f.set_mode(m as u8);
+///     let x = f.mode() as i8 as i32;
+/// ```
+pub struct WidenBitfield {
+    struct_name: String,
+    field_name: String,
+    new_ty: String,
+}
+
+/// A `#[bitfield(name = "...", ty = "...", bits = "LO..=HI")]` attribute
+#[derive(Clone, Debug, PartialEq)]
+struct Bitfield {
+    name: String,
+    ty: String,
+    lo: u128,
+    hi: u128,
+}
+
+/// The storage field of the widened bitfield, and how it changed
+struct Widened {
+    struct_id: DefId,
+    storage: Ident,
+    old: Bitfield,
+    old_bytes: u128,
+    new_bytes: u128,
+}
+
+impl Transform for WidenBitfield {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        let (new_width, new_signed) = int_type_info(&self.new_ty).unwrap_or_else(|| {
+            panic!("widen_bitfield: `{}` is not an integer type", self.new_ty)
+        });
+
+        // Re-derive the layout of the storage holding the bitfield
+        let mut widened = None;
+        MutVisitNodes::visit(krate, |i: &mut P<Item>| {
+            if i.ident.as_str() != &*self.struct_name || widened.is_some() {
+                return;
+            }
+            let struct_id = cx.node_def_id(i.id);
+            let fields = match i.kind {
+                ItemKind::Struct(VariantData::Struct(ref mut fields, _), _) => fields,
+                _ => return,
+            };
+            for field in fields {
+                if let Some((old, old_bytes, new_bytes)) = self.widen_storage(field, new_width) {
+                    widened = Some(Widened {
+                        struct_id,
+                        storage: field.ident.expect("bitfield storage has no name"),
+                        old,
+                        old_bytes,
+                        new_bytes,
+                    });
+                    return;
+                }
+            }
+        });
+        let widened = widened.unwrap_or_else(|| {
+            panic!("widen_bitfield: found no bitfield `{}` in a struct `{}`",
+                   self.field_name, self.struct_name)
+        });
+        let (_, old_signed) = int_type_info(&widened.old.ty).unwrap_or_else(|| {
+            panic!("widen_bitfield: `{}` is not an integer type", widened.old.ty)
+        });
+
+        let getter = self.field_name.clone();
+        let setter = format!("set_{}", self.field_name);
+        let is_getter = |e: &Expr| is_accessor(cx, e, widened.struct_id, &getter);
+        let is_setter = |e: &Expr| is_accessor(cx, e, widened.struct_id, &setter);
+
+        // A getter whose value is cast anyway only needs casting back to the old type first
+        // to sign extend it
+        let mut cast_getters = HashSet::new();
+        if !old_signed || new_signed {
+            visit_nodes(krate, |e: &Expr| {
+                if let ExprKind::Cast(ref inner, _) = e.kind {
+                    if is_getter(&**inner) {
+                        cast_getters.insert(inner.id);
+                    }
+                }
+            });
+        }
+
+        let old_ty = parse_ty(cx.session(), &widened.old.ty);
+        let new_ty = parse_ty(cx.session(), &self.new_ty);
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            if is_getter(&**e) && !cast_getters.contains(&e.id) {
+                *e = mk().cast_expr(e.clone(), old_ty.clone());
+                return;
+            }
+
+            if is_setter(&**e) {
+                if let ExprKind::MethodCall(_, ref mut args) = e.kind {
+                    if let Some(val) = args.get_mut(1) {
+                        *val = mk().cast_expr(val.clone(), new_ty.clone());
+                    }
+                }
+                return;
+            }
+
+            if widened.new_bytes == widened.old_bytes {
+                return;
+            }
+            let struct_id = cx.opt_node_type(e.id)
+                .and_then(|ty| ty.ty_adt_def())
+                .map(|adt| adt.did);
+            if let ExprKind::Struct(_, ref mut fields, _) = e.kind {
+                if struct_id != Some(widened.struct_id) {
+                    return;
+                }
+                for field in fields {
+                    if field.ident != widened.storage {
+                        continue;
+                    }
+                    if let ExprKind::Repeat(_, ref mut count) = field.expr.kind {
+                        count.value = byte_count(widened.new_bytes);
+                    }
+                }
+            }
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+impl WidenBitfield {
+    /// If `field` stores the bitfield we are widening, update its `#[bitfield]` attributes and
+    /// type for the new layout, and return the old attribute of the bitfield along with the old
+    /// and new sizes of the storage in bytes.
+    fn widen_storage(
+        &self,
+        field: &mut StructField,
+        new_width: u128,
+    ) -> Option<(Bitfield, u128, u128)> {
+        let old_bitfields: Vec<Bitfield> =
+            field.attrs.iter().filter_map(parse_bitfield_attr).collect();
+        let old = old_bitfields.iter().find(|bf| bf.name == self.field_name)?.clone();
+        let old_bytes = storage_bytes(&field.ty)?;
+
+        let old_width = old.hi - old.lo + 1;
+        if new_width < old_width {
+            panic!("widen_bitfield: `{}` has {} bits, more than `{}`",
+                   old.name, old_width, self.new_ty);
+        }
+        let growth = new_width - old_width;
+
+        let mut bitfields = old_bitfields.clone();
+        for bf in &mut bitfields {
+            if bf.name == old.name {
+                bf.ty = self.new_ty.clone();
+                bf.hi += growth;
+            } else if bf.lo > old.hi {
+                bf.lo += growth;
+                bf.hi += growth;
+            }
+        }
+        let used_bytes = bitfields.iter().map(|bf| bf.hi / 8 + 1).max().unwrap_or(0);
+        let new_bytes = used_bytes.max(old_bytes);
+
+        for attr in &mut field.attrs {
+            let bf = match parse_bitfield_attr(attr) {
+                Some(bf) => bf,
+                None => continue,
+            };
+            let new_bf = bitfields.iter().find(|new_bf| new_bf.name == bf.name)
+                .expect("bitfield disappeared");
+            if *new_bf != bf {
+                *attr = bitfield_attr(new_bf);
+            }
+        }
+        if new_bytes != old_bytes {
+            field.ty = mk().array_ty(mk().ident_ty("u8"), byte_count(new_bytes));
+        }
+
+        Some((old, old_bytes, new_bytes))
+    }
+}
+
+fn parse_bitfield_attr(attr: &Attribute) -> Option<Bitfield> {
+    if !attr.check_name(Symbol::intern("bitfield")) {
+        return None;
+    }
+
+    let (mut name, mut ty, mut bits) = (None, None, None);
+    for item in attr.meta_item_list()? {
+        let value = item.value_str()?.to_string();
+        match &*item.name_or_empty().as_str() {
+            "name" => name = Some(value),
+            "ty" => ty = Some(value),
+            "bits" => bits = Some(value),
+            _ => {}
+        }
+    }
+
+    let bits = bits?;
+    let mut range = bits.splitn(2, "..=");
+    let lo = range.next()?.trim().parse().ok()?;
+    let hi = range.next()?.trim().parse().ok()?;
+    Some(Bitfield { name: name?, ty: ty?, lo, hi })
+}
+
+fn bitfield_attr(bf: &Bitfield) -> Attribute {
+    let arg = |key: &str, value: &str| {
+        let value = MetaItemKind::NameValue(mk().str_lit(value));
+        NestedMetaItem::MetaItem(mk().meta_item(vec![key], value))
+    };
+    let args = vec![
+        arg("name", &bf.name),
+        arg("ty", &bf.ty),
+        arg("bits", &format!("{}..={}", bf.lo, bf.hi)),
+    ];
+    let meta = mk().meta_item(vec!["bitfield"], MetaItemKind::List(args));
+    mk().meta_item_attr(AttrStyle::Outer, meta).into_attrs().pop().unwrap()
+}
+
+/// Get the size of a `[u8; N]` bitfield storage array.
+fn storage_bytes(ty: &Ty) -> Option<u128> {
+    match ty.kind {
+        TyKind::Array(_, ref len) => match len.value.kind {
+            ExprKind::Lit(ref lit) => match lit.kind {
+                LitKind::Int(n, _) => Some(n),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn byte_count(bytes: u128) -> P<Expr> {
+    mk().lit_expr(mk().int_lit(bytes, LitIntType::Unsuffixed))
+}
+
+/// Check if `e` calls the accessor method `name` derived for the struct `struct_id`.
+fn is_accessor(cx: &RefactorCtxt, e: &Expr, struct_id: DefId, name: &str) -> bool {
+    match e.kind {
+        ExprKind::MethodCall(ref seg, _) if seg.ident.as_str() == name => {}
+        _ => return false,
+    }
+    let tcx = cx.ty_ctxt();
+    cx.opt_callee(e)
+        .and_then(|def_id| tcx.impl_of_method(def_id))
+        .and_then(|impl_id| tcx.type_of(impl_id).ty_adt_def())
+        .map_or(false, |adt| adt.did == struct_id)
+}
+
+/// Get the width and signedness of the integer types that bitfields can have, which the
+/// transpiler spells with the `libc` names.
+fn int_type_info(ty: &str) -> Option<(u128, bool)> {
+    let name = ty.rsplit("::").next()?;
+    Some(match name {
+        "u8" | "c_uchar" => (8, false),
+        "i8" | "c_schar" | "c_char" => (8, true),
+        "u16" | "c_ushort" => (16, false),
+        "i16" | "c_short" => (16, true),
+        "u32" | "c_uint" => (32, false),
+        "i32" | "c_int" => (32, true),
+        "u64" | "c_ulong" | "c_ulonglong" => (64, false),
+        "i64" | "c_long" | "c_longlong" => (64, true),
+        "u128" => (128, false),
+        "i128" => (128, true),
+        _ => return None,
+    })
+}
+
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("widen_bitfield", |args| mk(WidenBitfield {
+        struct_name: args[0].clone(),
+        field_name: args[1].clone(),
+        new_ty: args[2].clone(),
+    }));
+}
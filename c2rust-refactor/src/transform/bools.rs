@@ -0,0 +1,258 @@
+use std::collections::HashSet;
+use syntax::ast::*;
+use syntax::ptr::P;
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::MutVisitNodes;
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `simplify_bool` Command
+///
+/// Usage: `simplify_bool`
+///
+/// Simplify the boolean logic produced by the translator:
+///
+///  * `e as T != 0` becomes `e`, and `e as T == 0` becomes `!e`, when `e` is a `bool`
+///  * `(if c { 1 } else { 0 }) != 0` becomes `c`, and `== 0` becomes `!c`
+///  * `!!e` becomes `e`, `!(a == b)` becomes `a != b`, and `!(a < b)` becomes `a >= b` for
+///    integer operands
+///  * `!a && !b` becomes `!(a || b)`, and `!(!a && !b)` becomes `a || b` (and likewise with
+///    the operators swapped)
+///
+/// Only expressions that typecheck as `bool` are rewritten, so integer expressions that
+/// merely look boolean are left alone.  Operands are never reordered or dropped, so
+/// short-circuit evaluation and side effects are preserved.
+pub struct SimplifyBool;
+
+impl Transform for SimplifyBool {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        let mut rewritten = HashSet::new();
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            // Parentheses around an expression we simplified are usually no longer needed.  The
+            // rewriter adds them back where precedence requires it.
+            let unparen = match e.kind {
+                ExprKind::Paren(ref inner) if rewritten.contains(&inner.id) => Some(inner.clone()),
+                _ => None,
+            };
+            if let Some(inner) = unparen {
+                *e = inner;
+                return;
+            }
+
+            if let Some(new_e) = simplify(cx, e) {
+                rewritten.insert(new_e.id);
+                *e = new_e;
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+fn simplify(cx: &RefactorCtxt, e: &P<Expr>) -> Option<P<Expr>> {
+    match e.kind {
+        ExprKind::Binary(op, ref lhs, ref rhs) => match op.node {
+            BinOpKind::Eq | BinOpKind::Ne => {
+                // `X != 0` or `0 != X`, where `X` is a `bool` converted to an integer
+                let other = if is_zero(rhs) {
+                    lhs
+                } else if is_zero(lhs) {
+                    rhs
+                } else {
+                    return None;
+                };
+                let b = bool_source(cx, other)?;
+                if op.node == BinOpKind::Ne {
+                    Some(b)
+                } else {
+                    Some(negate(cx, e, &b))
+                }
+            }
+
+            BinOpKind::And | BinOpKind::Or => {
+                // `!a && !b` => `!(a || b)`
+                let a = strip_not(cx, lhs)?;
+                let b = strip_not(cx, rhs)?;
+                let op = if op.node == BinOpKind::And { BinOpKind::Or } else { BinOpKind::And };
+                Some(mk().id(e.id).span(e.span).unary_expr(
+                    UnOp::Not,
+                    mk().binary_expr(op, a, b),
+                ))
+            }
+
+            _ => None,
+        },
+
+        ExprKind::Unary(UnOp::Not, ref inner) => {
+            if !is_bool(cx, inner) {
+                return None;
+            }
+            let inner = strip_parens(inner);
+            match inner.kind {
+                // `!!a` => `a`
+                ExprKind::Unary(UnOp::Not, ref a) => Some(strip_parens(a).clone()),
+
+                // `!(!a && !b)` => `a || b`
+                ExprKind::Binary(op, ref lhs, ref rhs)
+                    if op.node == BinOpKind::And || op.node == BinOpKind::Or =>
+                {
+                    let a = strip_not(cx, lhs)?;
+                    let b = strip_not(cx, rhs)?;
+                    let op = if op.node == BinOpKind::And { BinOpKind::Or } else { BinOpKind::And };
+                    Some(mk().id(e.id).span(e.span).binary_expr(op, a, b))
+                }
+
+                // `!(a == b)` => `a != b`
+                _ => flip_comparison(cx, e, inner),
+            }
+        }
+
+        _ => None,
+    }
+}
+
+/// If `e` is an integer expression whose value is a `bool` converted to `0` or `1`, return
+/// that `bool` expression.
+fn bool_source(cx: &RefactorCtxt, e: &Expr) -> Option<P<Expr>> {
+    match e.kind {
+        ExprKind::Paren(ref inner) => bool_source(cx, inner),
+
+        // `b as T`
+        ExprKind::Cast(ref inner, _) if is_bool(cx, inner) => Some(strip_parens(inner).clone()),
+
+        // `b as T1 as T2`: integer-to-integer casts preserve `0` and `1`
+        ExprKind::Cast(ref inner, _) if is_integral(cx, e) => bool_source(cx, inner),
+
+        // `if c { 1 } else { 0 }`
+        ExprKind::If(ref cond, ref then_block, Some(ref else_expr)) => {
+            let then_val = block_int_value(then_block)?;
+            let else_val = match else_expr.kind {
+                ExprKind::Block(ref block, None) => block_int_value(block)?,
+                _ => return None,
+            };
+            match (then_val, else_val) {
+                (1, 0) => Some(cond.clone()),
+                (0, 1) => Some(negate(cx, cond, cond)),
+                _ => None,
+            }
+        }
+
+        _ => None,
+    }
+}
+
+/// Build the negation of the `bool` expression `b`, giving the result the id and span of
+/// `orig`.
+fn negate(cx: &RefactorCtxt, orig: &Expr, b: &P<Expr>) -> P<Expr> {
+    let b = strip_parens(b);
+    match b.kind {
+        ExprKind::Unary(UnOp::Not, ref inner) => return strip_parens(inner).clone(),
+        _ => {}
+    }
+    if let Some(flipped) = flip_comparison(cx, orig, b) {
+        return flipped;
+    }
+    mk().id(orig.id).span(orig.span).unary_expr(UnOp::Not, b.clone())
+}
+
+/// Negate the comparison `cmp` by flipping its operator.  The ordered comparisons can only
+/// be flipped for integers, since for floats `!(a < b)` is not `a >= b` when either is NaN.
+fn flip_comparison(cx: &RefactorCtxt, orig: &Expr, cmp: &Expr) -> Option<P<Expr>> {
+    let (op, lhs, rhs) = match cmp.kind {
+        ExprKind::Binary(op, ref lhs, ref rhs) => (op.node, lhs, rhs),
+        _ => return None,
+    };
+    let flipped = match op {
+        BinOpKind::Eq => BinOpKind::Ne,
+        BinOpKind::Ne => BinOpKind::Eq,
+        BinOpKind::Lt | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge
+            if is_integral(cx, lhs) && is_integral(cx, rhs) =>
+        {
+            match op {
+                BinOpKind::Lt => BinOpKind::Ge,
+                BinOpKind::Le => BinOpKind::Gt,
+                BinOpKind::Gt => BinOpKind::Le,
+                _ => BinOpKind::Lt,
+            }
+        }
+        _ => return None,
+    };
+    Some(mk().id(orig.id).span(orig.span).binary_expr(flipped, lhs.clone(), rhs.clone()))
+}
+
+/// If `e` is `!a` for a `bool` `a`, return `a`.
+fn strip_not(cx: &RefactorCtxt, e: &P<Expr>) -> Option<P<Expr>> {
+    match strip_parens(e).kind {
+        ExprKind::Unary(UnOp::Not, ref a) if is_bool(cx, a) => Some(strip_parens(a).clone()),
+        _ => None,
+    }
+}
+
+fn strip_parens(e: &P<Expr>) -> &P<Expr> {
+    match e.kind {
+        ExprKind::Paren(ref inner) => strip_parens(inner),
+        _ => e,
+    }
+}
+
+fn is_bool(cx: &RefactorCtxt, e: &Expr) -> bool {
+    match e.kind {
+        ExprKind::Paren(ref inner) => is_bool(cx, inner),
+        ExprKind::Lit(ref lit) => match lit.kind {
+            LitKind::Bool(_) => true,
+            _ => false,
+        },
+        ExprKind::Binary(op, _, _) => match op.node {
+            BinOpKind::And | BinOpKind::Or | BinOpKind::Eq | BinOpKind::Ne | BinOpKind::Lt
+            | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge => true,
+            _ => cx.opt_node_type(e.id).map_or(false, |ty| ty.is_bool()),
+        },
+        ExprKind::Unary(UnOp::Not, ref inner) => is_bool(cx, inner),
+        // Nodes we built ourselves have no type information
+        _ if e.id == DUMMY_NODE_ID => false,
+        _ => cx.opt_node_type(e.id).map_or(false, |ty| ty.is_bool()),
+    }
+}
+
+fn is_integral(cx: &RefactorCtxt, e: &Expr) -> bool {
+    e.id != DUMMY_NODE_ID && cx.opt_node_type(e.id).map_or(false, |ty| ty.is_integral())
+}
+
+fn is_zero(e: &Expr) -> bool {
+    int_value(e) == Some(0)
+}
+
+fn int_value(e: &Expr) -> Option<u128> {
+    match e.kind {
+        ExprKind::Paren(ref inner) => int_value(inner),
+        ExprKind::Lit(ref lit) => match lit.kind {
+            LitKind::Int(i, _) => Some(i),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Get the value of a block that consists only of an integer literal, e.g. `{ 1 }`.
+fn block_int_value(block: &Block) -> Option<u128> {
+    match block.stmts.as_slice() {
+        [stmt] => match stmt.kind {
+            StmtKind::Expr(ref e) => int_value(e),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("simplify_bool", |_| mk(SimplifyBool));
+}
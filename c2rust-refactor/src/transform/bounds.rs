@@ -0,0 +1,853 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use rustc::hir::{self, HirId};
+use rustc::hir::def::Res;
+use rustc::hir::def_id::DefId;
+use rustc::ty::{self, TyKind};
+use syntax::ast::*;
+use syntax::print::pprust;
+use syntax::ptr::P;
+use syntax::symbol::Symbol;
+use syntax::visit::{self, Visitor};
+use syntax_pos::BytePos;
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{visit_nodes, AstEquiv, FlatMapNodes, MutVisitNodes};
+use crate::ast_manip::lr_expr::{self, fold_exprs_with_context};
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::transform::Transform;
+use crate::transform::items::struct_field_def_id;
+use crate::transform::util::{path_name, strip_casts};
+use crate::RefactorCtxt;
+
+
+/// # `hoist_bounds_checks` Command
+///
+/// Usage: `hoist_bounds_checks [unchecked]`
+///
+/// Check the bounds of the slices and arrays indexed by the counter of a counting loop once,
+/// before the loop, instead of on every iteration.  Translated C loops such as
+/// `i = 0; while i < n { dst[i as usize] = src[i as usize]; i += 1 }` index their slices only
+/// with counters in `start .. n`, so asserting that `n` is at most the length of each slice
+/// before the loop covers every index.
+///
+/// This is a purely syntactic match on the shape of the loop.  It doesn't use `restrict`
+/// qualifiers or any other information recorded about the C source, which the transpiler
+/// doesn't keep, and doesn't need to: each slice is checked on its own, whether or not it
+/// overlaps the others, and a slice that is assigned or borrowed mutably in the loop is left
+/// alone.
+///
+/// By default, the indexing in the loop is left as it is, so a slice that is too short panics
+/// before the loop, but whether the checks in the loop are elided is up to the optimizer, which
+/// often keeps them.  With `unchecked`, the indexing is replaced with `get_unchecked` and
+/// `get_unchecked_mut`, in an `unsafe` block unless the function is already an `unsafe fn`, so
+/// the loop has no checks of its own.
+///
+/// A loop is only rewritten if its counter is assigned a non-negative integer literal right
+/// before it, its condition is `i < n` for a variable or literal `n`, its body ends with
+/// incrementing the counter by one and doesn't otherwise change the counter, `n`, or the
+/// slices, and it can't be left with `break`, `continue`, `return` or `?`.  Only slices that
+/// are indexed on every iteration, not in a branch of an `if` or `match`, a nested loop or a
+/// closure, get their checks hoisted, since a loop that would index out of bounds now panics
+/// before its first iteration.
+///
+/// Example:
+///
+/// ```ignore
+///     i = 0;
+///     while i < n {
+///         dst[i as usize] = src[i as usize];
+///         i += 1
+///     }
+/// ```
+///
+/// After running `hoist_bounds_checks unchecked` in an `unsafe fn`:
+///
+/// ```ignore
+///     i = 0;
+///     assert!(n <= 0 || n as usize <= dst.len());
+///     assert!(n <= 0 || n as usize <= src.len());
+///     while i < n {
+///         *dst.get_unchecked_mut(i as usize) = *src.get_unchecked(i as usize);
+///         i += 1
+///     }
+/// ```
+pub struct HoistBoundsChecks {
+    unchecked: bool,
+}
+
+impl Transform for HoistBoundsChecks {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        FlatMapNodes::visit(krate, |mut i: P<Item>| {
+            if let ItemKind::Fn(_, ref header, _, ref mut block) = i.kind {
+                let in_unsafe_fn = header.unsafety == Unsafety::Unsafe;
+                MutVisitNodes::visit(block, |b: &mut P<Block>| {
+                    self.hoist_in_block(st, cx, b, in_unsafe_fn)
+                });
+            }
+            smallvec![i]
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+impl HoistBoundsChecks {
+    fn hoist_in_block(&self, st: &CommandState, cx: &RefactorCtxt, b: &mut P<Block>,
+                      in_unsafe_fn: bool) {
+        let mut k = 1;
+        while k < b.stmts.len() {
+            let lp = match counting_loop(cx, &b.stmts[k - 1], &b.stmts[k]) {
+                Some(lp) => lp,
+                None => {
+                    k += 1;
+                    continue;
+                }
+            };
+
+            if self.unchecked {
+                let body = expect!([b.stmts[k].kind]
+                    StmtKind::Expr(ref mut e), StmtKind::Semi(ref mut e) => match e.kind {
+                        ExprKind::While(_, ref mut body, _) => body,
+                        _ => unreachable!(),
+                    });
+                fold_exprs_with_context(body, |e, context| {
+                    let (base, idx) = match e.kind {
+                        ExprKind::Index(ref base, ref idx) => (base, idx),
+                        _ => return,
+                    };
+                    match path_name(base) {
+                        Some(name) if lp.slices.contains(&name) && is_counter(idx, lp.counter) => {}
+                        _ => return,
+                    }
+                    let method = match context {
+                        lr_expr::Context::LvalueMut => "get_unchecked_mut",
+                        _ => "get_unchecked",
+                    };
+                    let mut elem = mk().method_call_expr(base.clone(), method, vec![idx.clone()]);
+                    if !in_unsafe_fn {
+                        elem = mk().block_expr(mk().unsafe_().block(vec![mk().expr_stmt(elem)]));
+                    }
+                    *e = mk().unary_expr(UnOp::Deref, elem);
+                });
+            }
+
+            let checks = lp.checks();
+            let n = checks.len();
+            for (j, check) in checks.into_iter().enumerate() {
+                let check = st.parse_expr(cx, &format!("assert!({})", check));
+                b.stmts.insert(k + j, mk().semi_stmt(check));
+            }
+            k += n + 1;
+        }
+    }
+}
+
+/// A counting loop `i = start; while i < end { ...; i += 1 }` whose indexing of `slices` by
+/// `i` can be checked before the loop.
+struct CountingLoop {
+    counter: Symbol,
+    start: u128,
+    end: P<Expr>,
+    /// Whether `end` is signed, so it may be below `start`
+    signed: bool,
+    /// Whether `end` is already a `usize`
+    usize: bool,
+    slices: Vec<Symbol>,
+}
+
+impl CountingLoop {
+    /// Build the conditions asserted before the loop.
+    fn checks(&self) -> Vec<String> {
+        let mut end = pprust::expr_to_string(&self.end);
+        if let ExprKind::Cast(..) = self.end.kind {
+            end = format!("({})", end);
+        }
+        let len = if self.usize { end.clone() } else { format!("{} as usize", end) };
+        self.slices.iter().map(|slice| {
+            if self.start == 0 && !self.signed {
+                format!("{} <= {}.len()", len, slice)
+            } else {
+                format!("{} <= {} || {} <= {}.len()", end, self.start, len, slice)
+            }
+        }).collect()
+    }
+}
+
+/// Match `init` and `stmt` against a counting loop that only indexes slices in a way that can
+/// be checked before the loop.
+fn counting_loop(cx: &RefactorCtxt, init: &Stmt, stmt: &Stmt) -> Option<CountingLoop> {
+    let (counter, start) = match init.kind {
+        StmtKind::Semi(ref e) => match e.kind {
+            ExprKind::Assign(ref lhs, ref rhs) => (path_name(lhs)?, int_lit(rhs)?),
+            _ => return None,
+        },
+        StmtKind::Local(ref l) => match l.pat.kind {
+            PatKind::Ident(_, ident, None) => (ident.name, int_lit(l.init.as_ref()?)?),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let (cond, body) = match stmt.kind {
+        StmtKind::Expr(ref e) | StmtKind::Semi(ref e) => match e.kind {
+            ExprKind::While(ref cond, ref body, _) => (cond, body),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let end = match cond.kind {
+        ExprKind::Binary(op, ref lhs, ref rhs)
+            if op.node == BinOpKind::Lt && path_name(lhs) == Some(counter) => rhs,
+        _ => return None,
+    };
+    let end_name = match strip_casts(end).kind {
+        ExprKind::Path(..) => Some(path_name(strip_casts(end))?),
+        ExprKind::Lit(_) => None,
+        _ => return None,
+    };
+    let (signed, usize) = match cx.opt_node_type(end.id)?.kind {
+        TyKind::Int(_) => (true, false),
+        TyKind::Uint(UintTy::Usize) => (false, true),
+        TyKind::Uint(_) => (false, false),
+        _ => return None,
+    };
+
+    let (last, rest) = body.stmts.split_last()?;
+    if !is_increment(last, counter) {
+        return None;
+    }
+    let mut v = LoopBody {
+        counter,
+        conditional: 0,
+        exits: false,
+        indexed: Vec::new(),
+        changed: HashSet::new(),
+    };
+    for s in rest {
+        v.visit_stmt(s);
+    }
+    if v.exits || v.changed.contains(&counter) ||
+       end_name.map_or(false, |name| v.changed.contains(&name)) {
+        return None;
+    }
+
+    let mut slices = Vec::new();
+    for &(name, id, _) in &v.indexed {
+        if slices.contains(&name) || v.changed.contains(&name) ||
+           v.indexed.iter().any(|&(other, _, conditional)| other == name && conditional) ||
+           !cx.opt_node_type(id).map_or(false, is_indexable) {
+            continue;
+        }
+        slices.push(name);
+    }
+    if slices.is_empty() {
+        return None;
+    }
+
+    Some(CountingLoop {
+        counter,
+        start,
+        end: end.clone(),
+        signed,
+        usize,
+        slices,
+    })
+}
+
+/// Collects what the body of a counting loop does with its counter and slices.
+struct LoopBody {
+    counter: Symbol,
+    /// The number of conditionally evaluated expressions the visitor is in
+    conditional: usize,
+    /// Whether the body may leave the loop
+    exits: bool,
+    /// The slices indexed by the counter, the ID of the slice expression, and whether it's
+    /// indexed conditionally
+    indexed: Vec<(Symbol, NodeId, bool)>,
+    /// The names that are assigned, borrowed mutably, or bound in the body
+    changed: HashSet<Symbol>,
+}
+
+impl<'ast> Visitor<'ast> for LoopBody {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        match e.kind {
+            ExprKind::Break(..) | ExprKind::Continue(_) | ExprKind::Ret(_) |
+            ExprKind::Try(_) | ExprKind::Yield(_) => self.exits = true,
+            ExprKind::Assign(ref lhs, _) |
+            ExprKind::AssignOp(_, ref lhs, _) |
+            ExprKind::AddrOf(Mutability::Mutable, ref lhs) => {
+                if let Some(name) = path_name(lhs) {
+                    self.changed.insert(name);
+                }
+            }
+            ExprKind::Index(ref base, ref idx) if is_counter(idx, self.counter) => {
+                if let Some(name) = path_name(base) {
+                    self.indexed.push((name, base.id, self.conditional > 0));
+                }
+            }
+            _ => {}
+        }
+
+        // The condition of an `if` and the scrutinee of a `match` are always evaluated, but
+        // their branches aren't
+        match e.kind {
+            ExprKind::If(ref cond, ref then, ref els) => {
+                self.visit_expr(cond);
+                self.conditional += 1;
+                self.visit_block(then);
+                if let Some(ref els) = *els {
+                    self.visit_expr(els);
+                }
+                self.conditional -= 1;
+            }
+            ExprKind::Match(ref scrutinee, ref arms) => {
+                self.visit_expr(scrutinee);
+                self.conditional += 1;
+                for arm in arms {
+                    self.visit_arm(arm);
+                }
+                self.conditional -= 1;
+            }
+            ExprKind::While(..) | ExprKind::Loop(..) | ExprKind::ForLoop(..) |
+            ExprKind::Closure(..) => {
+                self.conditional += 1;
+                visit::walk_expr(self, e);
+                self.conditional -= 1;
+            }
+            ExprKind::Binary(op, ref lhs, ref rhs)
+                    if op.node == BinOpKind::And || op.node == BinOpKind::Or => {
+                self.visit_expr(lhs);
+                self.conditional += 1;
+                self.visit_expr(rhs);
+                self.conditional -= 1;
+            }
+            _ => visit::walk_expr(self, e),
+        }
+    }
+
+    fn visit_pat(&mut self, p: &'ast Pat) {
+        if let PatKind::Ident(_, ident, _) = p.kind {
+            self.changed.insert(ident.name);
+        }
+        visit::walk_pat(self, p);
+    }
+
+    // Nested items can't see the loop's locals
+    fn visit_item(&mut self, _i: &'ast Item) {}
+
+    fn visit_mac(&mut self, _mac: &'ast Mac) {}
+}
+
+/// Check if `s` increments `counter` by one, with `i += 1`, `i = i + 1` or
+/// `i = i.wrapping_add(1)`.
+fn is_increment(s: &Stmt, counter: Symbol) -> bool {
+    let e = match s.kind {
+        StmtKind::Expr(ref e) | StmtKind::Semi(ref e) => e,
+        _ => return false,
+    };
+    match e.kind {
+        ExprKind::AssignOp(op, ref lhs, ref rhs) => {
+            op.node == BinOpKind::Add && path_name(lhs) == Some(counter) && int_lit(rhs) == Some(1)
+        }
+        ExprKind::Assign(ref lhs, ref rhs) if path_name(lhs) == Some(counter) => match rhs.kind {
+            ExprKind::Binary(op, ref a, ref b) => {
+                op.node == BinOpKind::Add && path_name(a) == Some(counter) && int_lit(b) == Some(1)
+            }
+            ExprKind::MethodCall(ref seg, ref args) => {
+                seg.ident.name.as_str() == "wrapping_add" && args.len() == 2 &&
+                    path_name(&args[0]) == Some(counter) && int_lit(&args[1]) == Some(1)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Check if `idx` is `counter`, possibly cast to `usize`.
+fn is_counter(idx: &Expr, counter: Symbol) -> bool {
+    path_name(strip_casts(idx)) == Some(counter)
+}
+
+/// Check if `ty` is a slice or array, or a reference to one.
+fn is_indexable(ty: ty::Ty) -> bool {
+    match ty.kind {
+        TyKind::Ref(_, ty, _) => is_indexable(ty),
+        TyKind::Slice(_) | TyKind::Array(..) => true,
+        _ => false,
+    }
+}
+
+/// Get the value of an integer literal, possibly cast.
+fn int_lit(e: &Expr) -> Option<u128> {
+    match strip_casts(e).kind {
+        ExprKind::Lit(ref lit) => match lit.kind {
+            LitKind::Int(i, _) => Some(i),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+
+/// # `chunk_matrix_rows` Command
+///
+/// Usage: `chunk_matrix_rows`
+///
+/// Iterate over the rows of row-major matrices stored in slices with `chunks_exact`, instead of
+/// indexing them with `a[i * COLS + j]`.  In a loop nest such as
+/// `for i in 0..rows { for j in 0..COLS { ... a[i * COLS + j] ... } }`, each slice indexed
+/// this way is split into rows of `COLS` elements that are indexed by `j` alone, which is more
+/// idiomatic, and lets the compiler drop the bounds checks of the inner loop.  The slices are
+/// first cut down to `rows * COLS` elements, so a slice that is too short still panics, but
+/// before the loop starts instead of at the first access out of bounds.
+///
+/// `COLS` must be a non-zero integer literal, or a `const` defined as one, and both loops must
+/// count up from 0 in `usize` counters.  A slice is only rewritten if it is a local and every
+/// use of it in the outer loop is indexed exactly with `i * COLS + j` inside the inner loop.
+/// Any other use, including indices like `j + i * COLS` or `i * COLS + j + 1`, leaves the loop
+/// unchanged.
+///
+/// Example:
+///
+/// ```ignore
+///     for i in 0..rows {
+///         for j in 0..N {
+///             m[i * N + j] *= s;
+///         }
+///     }
+/// ```
+///
+/// After running `chunk_matrix_rows`:
+///
+/// ```ignore
+///     for m_row in m[..rows * N].chunks_exact_mut(N) {
+///         for j in 0..N {
+///             m_row[j] *= s;
+///         }
+///     }
+/// ```
+pub struct ChunkMatrixRows;
+
+impl Transform for ChunkMatrixRows {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            if let Some((pat, iter, body)) = chunk_rows(cx, e) {
+                if let ExprKind::ForLoop(ref mut old_pat, ref mut old_iter, ref mut old_body, _) =
+                        e.kind {
+                    *old_pat = pat;
+                    *old_iter = iter;
+                    *old_body = body;
+                }
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// A slice of a matrix nest that is indexed by rows.
+struct MatrixSlice {
+    hir_id: HirId,
+    name: Symbol,
+    row: Symbol,
+    mutable: bool,
+    /// The start of its first use, to keep the slices in source order
+    first_use: BytePos,
+}
+
+/// Match `e` against a loop nest over the rows and columns of matrices, and build the pattern,
+/// iterator and body of the outer loop iterating over their rows instead.
+fn chunk_rows(cx: &RefactorCtxt, e: &Expr) -> Option<(P<Pat>, P<Expr>, P<Block>)> {
+    let (pat, iter, body) = match e.kind {
+        ExprKind::ForLoop(ref pat, ref iter, ref body, _) => (pat, iter, body),
+        _ => return None,
+    };
+    let (i, rows) = counting_range(cx, pat, iter)?;
+
+    // The inner loop, over the columns
+    let mut inner = None;
+    for s in &body.stmts {
+        let e = match s.kind {
+            StmtKind::Expr(ref e) | StmtKind::Semi(ref e) => e,
+            _ => continue,
+        };
+        if let ExprKind::ForLoop(ref pat, ref iter, _, _) = e.kind {
+            if let Some((j, cols)) = counting_range(cx, pat, iter) {
+                if inner.is_some() {
+                    return None;
+                }
+                inner = Some((j, const_len(cx, cols)?));
+            }
+        }
+    }
+    let (j, cols) = inner?;
+
+    let mut taken = HashSet::new();
+    visit_nodes(&**body, |e: &Expr| taken.extend(path_name(e)));
+    visit_nodes(&**body, |p: &Pat| {
+        if let PatKind::Ident(_, ident, _) = p.kind {
+            taken.insert(ident.name);
+        }
+    });
+
+    let mut slices: Vec<MatrixSlice> = Vec::new();
+    let mut cols_expr = None;
+    let mut new_body = body.clone();
+    fold_exprs_with_context(&mut new_body, |e, context| {
+        let (base, idx) = match e.kind {
+            ExprKind::Index(ref base, ref idx) => (base, idx),
+            _ => return,
+        };
+        let hir_id = match_or!([local_of(cx, base)] Some(x) => x; return);
+        if !cx.opt_node_type(base.id).map_or(false, is_indexable) {
+            return;
+        }
+        // `i * cols + j`
+        let (row_idx, col_idx) = match idx.kind {
+            ExprKind::Binary(op, ref lhs, ref rhs) if op.node == BinOpKind::Add => (lhs, rhs),
+            _ => return,
+        };
+        let (row_idx, cols_idx) = match row_idx.kind {
+            ExprKind::Binary(op, ref lhs, ref rhs) if op.node == BinOpKind::Mul => (lhs, rhs),
+            _ => return,
+        };
+        if local_of(cx, row_idx) != Some(i) || local_of(cx, col_idx) != Some(j) ||
+           const_len(cx, cols_idx) != Some(cols) {
+            return;
+        }
+        cols_expr = Some(cols_idx.clone());
+
+        let k = match slices.iter().position(|s| s.hir_id == hir_id) {
+            Some(k) => k,
+            None => {
+                let name = path_name(base).unwrap();
+                let mut row = Symbol::intern(&format!("{}_row", name));
+                let mut suffix = 1;
+                while taken.contains(&row) {
+                    row = Symbol::intern(&format!("{}_row_{}", name, suffix));
+                    suffix += 1;
+                }
+                taken.insert(row);
+                slices.push(MatrixSlice {
+                    hir_id,
+                    name,
+                    row,
+                    mutable: false,
+                    first_use: base.span.lo(),
+                });
+                slices.len() - 1
+            }
+        };
+        slices[k].first_use = slices[k].first_use.min(base.span.lo());
+        if context == lr_expr::Context::LvalueMut {
+            slices[k].mutable = true;
+        }
+        *e = mk().index_expr(mk().ident_expr(slices[k].row), col_idx.clone());
+    });
+    if slices.is_empty() {
+        return None;
+    }
+    slices.sort_by_key(|s| s.first_use);
+
+    // Each slice must now only be used through its rows.
+    let mut other_use = false;
+    let mut uses_i = false;
+    visit_nodes(&*new_body, |e: &Expr| {
+        match local_of(cx, e) {
+            Some(id) if slices.iter().any(|s| s.hir_id == id) => other_use = true,
+            Some(id) if id == i => uses_i = true,
+            _ => {}
+        }
+    });
+    if other_use {
+        return None;
+    }
+
+    // `a[..rows * cols].chunks_exact(cols)`, zipped together for each slice
+    let cols_expr = cols_expr.unwrap();
+    let len = mk().binary_expr(BinOpKind::Mul, rows.clone(), cols_expr.clone());
+    let mut rows_iter = None;
+    let mut rows_pat = None;
+    for slice in &slices {
+        let method = if slice.mutable { "chunks_exact_mut" } else { "chunks_exact" };
+        let chunks = mk().method_call_expr(
+            mk().index_expr(
+                mk().ident_expr(slice.name),
+                mk().range_expr(None as Option<P<Expr>>, Some(len.clone())),
+            ),
+            method,
+            vec![cols_expr.clone()],
+        );
+        let row_pat = mk().ident_pat(slice.row);
+        rows_iter = Some(match rows_iter {
+            None => chunks,
+            Some(it) => mk().method_call_expr(it, "zip", vec![chunks]),
+        });
+        rows_pat = Some(match rows_pat {
+            None => row_pat,
+            Some(p) => mk().tuple_pat(vec![p, row_pat]),
+        });
+    }
+    let (mut new_iter, mut new_pat) = (rows_iter.unwrap(), rows_pat.unwrap());
+    if uses_i {
+        new_iter = mk().method_call_expr(new_iter, "enumerate", Vec::<P<Expr>>::new());
+        new_pat = mk().tuple_pat(vec![pat.clone(), new_pat]);
+    }
+    Some((new_pat, new_iter, new_body))
+}
+
+/// Match a loop `for i in 0..end` counting in a `usize`, and return the local `i` and `end`.
+fn counting_range<'a>(cx: &RefactorCtxt, pat: &Pat, iter: &'a Expr) -> Option<(HirId, &'a P<Expr>)> {
+    match pat.kind {
+        PatKind::Ident(BindingMode::ByValue(Mutability::Immutable), _, None) => {}
+        _ => return None,
+    }
+    let end = match iter.kind {
+        ExprKind::Range(Some(ref start), Some(ref end), RangeLimits::HalfOpen)
+            if int_lit(start) == Some(0) => end,
+        _ => return None,
+    };
+    match cx.opt_node_type(end.id)?.kind {
+        TyKind::Uint(UintTy::Usize) => {}
+        _ => return None,
+    }
+    Some((cx.hir_map().node_to_hir_id(pat.id), end))
+}
+
+/// Get the value of `e` if it's a non-zero integer literal, possibly cast, or a `const` defined
+/// as one.
+fn const_len(cx: &RefactorCtxt, e: &Expr) -> Option<u128> {
+    let value = match strip_casts(e).kind {
+        ExprKind::Lit(_) => int_lit(e)?,
+        ExprKind::Path(..) => {
+            let def_id = cx.try_resolve_expr(strip_casts(e))?;
+            let body_id = match cx.hir_map().get_if_local(def_id)? {
+                hir::Node::Item(item) => match item.kind {
+                    hir::ItemKind::Const(_, body_id) => body_id,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            let mut value = &cx.hir_map().krate().body(body_id).value;
+            while let hir::ExprKind::Cast(ref inner, _) = value.kind {
+                value = &**inner;
+            }
+            match value.kind {
+                hir::ExprKind::Lit(ref lit) => match lit.node {
+                    LitKind::Int(i, _) => i,
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    if value > 0 {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Get the local `e` refers to, if it's a single identifier naming one.
+fn local_of(cx: &RefactorCtxt, e: &Expr) -> Option<HirId> {
+    path_name(e)?;
+    match cx.try_resolve_expr_hir(e)? {
+        Res::Local(hir_id) => Some(hir_id),
+        _ => None,
+    }
+}
+
+
+/// # `modular_index` Command
+///
+/// Usage: `modular_index`
+///
+/// Replace the wrapping advances of ring buffer indices, `i = (i + k) % n`, with calls to a
+/// helper that says what they do: `i = advance_index_u32(i, k, n)`.  Unsigned indices in
+/// translated code are advanced with `i = i.wrapping_add(k).wrapping_rem(n)` instead, which
+/// becomes a call to `wrapping_advance_index_u32`.  One helper is added for each form and
+/// integer type, in each module that uses it, and computes exactly the same expression as the
+/// advance it replaces, overflow and division by zero included.  In particular, the remainder
+/// is never replaced with a mask, so indices wrap the same way whether or not `n` is a power of
+/// two.
+///
+/// An index is only rewritten if it is a local or a struct field, and everywhere in the crate
+/// it is used for indexing, possibly cast, it indexes the same local or struct field.  Indices
+/// that are never used for indexing, or that index several collections, are left alone.
+///
+/// Example:
+///
+/// ```ignore
+///     (*q).items[(*q).tail as usize] = x;
+///     (*q).tail = (*q).tail.wrapping_add(1).wrapping_rem(16);
+/// ```
+///
+/// After running `modular_index`:
+///
+/// ```ignore
+///     (*q).items[(*q).tail as usize] = x;
+///     (*q).tail = wrapping_advance_index_u32((*q).tail, 1, 16);
+///
+/// // ...
+///
+/// fn wrapping_advance_index_u32(i: u32, k: u32, n: u32) -> u32 {
+///     i.wrapping_add(k).wrapping_rem(n)
+/// }
+/// ```
+pub struct ModularIndex;
+
+/// A local or struct field holding an index or a collection.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum IndexVar {
+    Local(HirId),
+    Field(DefId),
+}
+
+impl Transform for ModularIndex {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        // The collection indexed by each index, or `None` if there are several.
+        let mut collections: HashMap<IndexVar, Option<IndexVar>> = HashMap::new();
+        visit_nodes(&*krate, |e: &Expr| {
+            let (base, idx) = match e.kind {
+                ExprKind::Index(ref base, ref idx) => (base, idx),
+                _ => return,
+            };
+            let var = match_or!([index_var(cx, strip_casts(idx))] Some(x) => x; return);
+            let collection = index_var(cx, base);
+            let entry = collections.entry(var).or_insert(collection);
+            if *entry != collection {
+                *entry = None;
+            }
+        });
+
+        MutVisitNodes::visit(krate, |m: &mut Mod| {
+            // The helpers used in this module, by name
+            let mut helpers = BTreeMap::new();
+            for item in &mut m.items {
+                if let ItemKind::Mod(_) = item.kind {
+                    continue;
+                }
+                MutVisitNodes::visit(item, |e: &mut P<Expr>| {
+                    let (i, k, n, wrapping) = match_or!([index_advance(e)] Some(x) => x; return);
+                    let var = match_or!([index_var(cx, i)] Some(x) => x; return);
+                    if collections.get(&var).map_or(true, |c| c.is_none()) {
+                        return;
+                    }
+                    let ty = match cx.opt_node_type(i.id) {
+                        Some(ty) if is_int(ty) => ty.to_string(),
+                        _ => return,
+                    };
+
+                    let (name, body) = if wrapping {
+                        (
+                            format!("wrapping_advance_index_{}", ty),
+                            "i.wrapping_add(k).wrapping_rem(n)",
+                        )
+                    } else {
+                        (format!("advance_index_{}", ty), "(i + k) % n")
+                    };
+                    let call = mk().call_expr(
+                        mk().ident_expr(&name),
+                        vec![i.clone(), k.clone(), n.clone()],
+                    );
+                    let src = format!(
+                        "fn {name}(i: {ty}, k: {ty}, n: {ty}) -> {ty} {{ {body} }}",
+                        name = name,
+                        ty = ty,
+                        body = body,
+                    );
+                    *e = mk().assign_expr(i.clone(), call);
+                    helpers.insert(name, src);
+                });
+            }
+
+            for (name, src) in helpers {
+                // Left by an earlier run
+                if m.items.iter().any(|i| i.ident.as_str() == &*name) {
+                    continue;
+                }
+                m.items.extend(st.parse_items(cx, &src));
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// Match an index advance, `i = (i + k) % n` or `i = i.wrapping_add(k).wrapping_rem(n)`, and
+/// return `i`, `k`, `n`, and whether it is the wrapping form.
+fn index_advance(e: &Expr) -> Option<(&P<Expr>, &P<Expr>, &P<Expr>, bool)> {
+    let (lhs, rhs) = match e.kind {
+        ExprKind::Assign(ref lhs, ref rhs) => (lhs, rhs),
+        _ => return None,
+    };
+    let (i, k, n, wrapping) = match strip_parens(rhs).kind {
+        ExprKind::Binary(op, ref sum, ref n) if op.node == BinOpKind::Rem => {
+            match strip_parens(sum).kind {
+                ExprKind::Binary(op, ref i, ref k) if op.node == BinOpKind::Add => (i, k, n, false),
+                _ => return None,
+            }
+        }
+        ExprKind::MethodCall(ref seg, ref args)
+            if seg.ident.as_str() == "wrapping_rem" && args.len() == 2 =>
+        {
+            match strip_parens(&args[0]).kind {
+                ExprKind::MethodCall(ref seg, ref sum_args)
+                    if seg.ident.as_str() == "wrapping_add" && sum_args.len() == 2 =>
+                {
+                    (&sum_args[0], &sum_args[1], &args[1], true)
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    if !strip_parens(i).ast_equiv(strip_parens(lhs)) {
+        return None;
+    }
+    Some((lhs, k, n, wrapping))
+}
+
+/// Get the local or struct field `e` refers to.
+fn index_var(cx: &RefactorCtxt, e: &Expr) -> Option<IndexVar> {
+    match strip_parens(e).kind {
+        ExprKind::Field(ref base, ident) => {
+            let ty = cx.opt_node_type(strip_parens(base).id)?;
+            struct_field_def_id(ty, ident).map(IndexVar::Field)
+        }
+        _ => local_of(cx, strip_parens(e)).map(IndexVar::Local),
+    }
+}
+
+fn is_int(ty: ty::Ty) -> bool {
+    match ty.kind {
+        TyKind::Int(_) | TyKind::Uint(_) => true,
+        _ => false,
+    }
+}
+
+fn strip_parens(e: &Expr) -> &Expr {
+    match e.kind {
+        ExprKind::Paren(ref inner) => strip_parens(inner),
+        _ => e,
+    }
+}
+
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("hoist_bounds_checks", |args| mk(HoistBoundsChecks {
+        unchecked: args.get(0).map_or(false, |arg| arg == "unchecked"),
+    }));
+    reg.register("chunk_matrix_rows", |_args| mk(ChunkMatrixRows));
+    reg.register("modular_index", |_args| mk(ModularIndex));
+}
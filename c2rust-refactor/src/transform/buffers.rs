@@ -0,0 +1,521 @@
+use std::collections::{HashMap, HashSet};
+use rustc::hir::HirId;
+use rustc::ty::TyKind;
+use syntax::ast::*;
+use syntax::ptr::P;
+use syntax::visit::{self, Visitor};
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{FlatMapNodes, MutVisitNodes, visit_nodes};
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::reflect::reflect_tcx_ty;
+use crate::transform::util::{is_local, stmt_expr, strip_casts};
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `buffer_to_string` Command
+///
+/// Usage: `buffer_to_string`
+///
+/// Replace local byte buffers that are filled in order through a counter, as in C's
+/// `char buf[N]; int pos = 0; buf[pos++] = c;`, with a `Vec` of the same element type:
+///
+///  * the buffer becomes `Vec::with_capacity(N)` and the counter is removed,
+///  * each `buf[pos++] = c` (or `buf[pos] = c; pos++`) becomes `buf.push(c)`,
+///  * the terminating `buf[pos] = 0` becomes `buf.push(0)`, so the contents stay
+///    NUL-terminated for pointers passed on to C afterwards,
+///  * reads of the counter, such as the bounds check against `N`, become `buf.len()`, minus
+///    one for the terminator once it has been pushed, and
+///  * `strlen(buf.as_ptr())` after the terminator becomes `buf.len() - 1`.
+///
+/// The buffer and its counter must be declared in the same block, the buffer initialized to
+/// zeroes and the counter to zero.  Buffers whose address is taken, that are used other than by
+/// indexing, or whose pointer is passed to C before the terminator is written are left alone,
+/// as are buffers whose counter is modified other than by the pushes.
+///
+/// Example:
+///
+/// ```ignore
+///     let mut buf: [libc::c_char; 16] = [0; 16];
+///     let mut len: libc::c_int = 0;
+///     while n > 0 && len < 15 {
+///         let fresh0 = len;
+///         len = len + 1;
+///         buf[fresh0 as usize] = ('0' as i32 + n % 10) as libc::c_char;
+///         n /= 10
+///     }
+///     buf[len as usize] = 0 as libc::c_char;
+///     puts(buf.as_ptr());
+/// ```
+///
+/// After running `buffer_to_string`:
+///
+/// ```ignore
+///     let mut buf: Vec<libc::c_char> = Vec::with_capacity(16);
+///     while n > 0 && (buf.len() as libc::c_int) < 15 {
+///         buf.push(('0' as i32 + n % 10) as libc::c_char);
+///         n /= 10
+///     }
+///     buf.push(0 as libc::c_char);
+///     puts(buf.as_ptr());
+/// ```
+pub struct BufferToString;
+
+impl Transform for BufferToString {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |b: &mut P<Block>| {
+            let mut i = 0;
+            while i < b.stmts.len() {
+                let buf = match buffer_local(cx, &b.stmts[i]) {
+                    Some(buf) => buf,
+                    None => {
+                        i += 1;
+                        continue;
+                    }
+                };
+
+                let mut error = None;
+                let mut plan = None;
+                for (p, stmt) in b.stmts.iter().enumerate() {
+                    let counter = match counter_local(cx, stmt) {
+                        Some(counter) if p != i => counter,
+                        _ => continue,
+                    };
+                    match BufferPlan::new(cx, &buf, &counter, &b.stmts, i, p) {
+                        Ok(Some(found)) => {
+                            plan = Some((found, counter, p));
+                            break;
+                        }
+                        Ok(None) => {}
+                        Err(reason) => error = Some(reason),
+                    }
+                }
+
+                let (plan, counter, p) = match plan {
+                    Some(x) => x,
+                    None => {
+                        if let Some(reason) = error {
+                            eprintln!("buffer_to_string: {}: {}", buf.name, reason);
+                        }
+                        i += 1;
+                        continue;
+                    }
+                };
+
+                b.stmts[i] = mk().local_stmt(P(mk().local(
+                    mk().mutbl().ident_pat(buf.name),
+                    Some(mk().path_ty(vec![mk().path_segment_with_args(
+                        "Vec",
+                        mk().angle_bracketed_args(vec![buf.elem_ty.clone()]),
+                    )])),
+                    Some(mk().call_expr(
+                        mk().path_expr(vec!["Vec", "with_capacity"]),
+                        vec![buf.len.clone()],
+                    )),
+                )));
+                b.stmts.remove(p);
+                plan.rewrite(b, &buf, &counter);
+                i = if p < i { i } else { i + 1 };
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+struct BufferInfo {
+    hir_id: HirId,
+    name: Ident,
+    elem_ty: P<Ty>,
+    len: P<Expr>,
+}
+
+struct CounterInfo {
+    hir_id: HirId,
+    ty: P<Ty>,
+}
+
+/// If `stmt` declares a zero-initialized array of bytes, get its info.
+fn buffer_local(cx: &RefactorCtxt, stmt: &Stmt) -> Option<BufferInfo> {
+    let l = match_or!([stmt.kind] StmtKind::Local(ref l) => l; return None);
+    let name = match_or!([l.pat.kind] PatKind::Ident(BindingMode::ByValue(_), name, None) => name;
+                         return None);
+    let init = l.init.as_ref()?;
+    match init.kind {
+        ExprKind::Repeat(ref e, _) if is_zero(e) => {}
+        _ => return None,
+    }
+
+    let ty = cx.node_type(l.pat.id);
+    let elem = match_or!([ty.kind] TyKind::Array(elem, _) => elem; return None);
+    match elem.kind {
+        TyKind::Int(IntTy::I8) | TyKind::Uint(UintTy::U8) => {}
+        _ => return None,
+    }
+
+    let (elem_ty, len) = match l.ty.as_ref().map(|ty| &ty.kind) {
+        Some(syntax::ast::TyKind::Array(elem_ty, len)) => (elem_ty.clone(), len.value.clone()),
+        _ => {
+            let ast_ty = reflect_tcx_ty(cx.ty_ctxt(), ty);
+            match ast_ty.kind {
+                syntax::ast::TyKind::Array(ref elem_ty, ref len) => {
+                    (elem_ty.clone(), len.value.clone())
+                }
+                _ => return None,
+            }
+        }
+    };
+
+    Some(BufferInfo {
+        hir_id: cx.hir_map().node_to_hir_id(l.pat.id),
+        name,
+        elem_ty,
+        len,
+    })
+}
+
+/// If `stmt` declares a zero-initialized integer, get its info.
+fn counter_local(cx: &RefactorCtxt, stmt: &Stmt) -> Option<CounterInfo> {
+    let l = match_or!([stmt.kind] StmtKind::Local(ref l) => l; return None);
+    if !matches!([l.pat.kind] PatKind::Ident(BindingMode::ByValue(_), _, None)) {
+        return None;
+    }
+    if !l.init.as_ref().map_or(false, |e| is_zero(e)) {
+        return None;
+    }
+
+    let ty = cx.node_type(l.pat.id);
+    match ty.kind {
+        TyKind::Int(_) | TyKind::Uint(_) => {}
+        _ => return None,
+    }
+
+    Some(CounterInfo {
+        hir_id: cx.hir_map().node_to_hir_id(l.pat.id),
+        ty: l.ty.clone().unwrap_or_else(|| reflect_tcx_ty(cx.ty_ctxt(), ty)),
+    })
+}
+
+fn is_zero(e: &Expr) -> bool {
+    match strip_casts(e).kind {
+        ExprKind::Lit(ref lit) => match lit.kind {
+            LitKind::Int(0, _) | LitKind::Char('\0') => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// If `stmt` is `buf[idx] = val`, get `idx` and `val`.
+fn buffer_write<'a>(cx: &RefactorCtxt,
+                    buf: HirId,
+                    stmt: &'a Stmt) -> Option<(&'a Expr, &'a P<Expr>)> {
+    let e = stmt_expr(stmt)?;
+    let (lhs, val) = match_or!([e.kind] ExprKind::Assign(ref lhs, ref val) => (lhs, val);
+                               return None);
+    let (base, idx) = match_or!([lhs.kind] ExprKind::Index(ref base, ref idx) => (base, idx);
+                                return None);
+    if is_local(cx, buf, base) {
+        Some((&**idx, val))
+    } else {
+        None
+    }
+}
+
+/// Check whether `stmt` increments the counter by one.
+fn is_increment(cx: &RefactorCtxt, counter: HirId, stmt: &Stmt) -> bool {
+    let e = match_or!([stmt_expr(stmt)] Some(e) => e; return false);
+    let is_one = |e: &Expr| match strip_casts(e).kind {
+        ExprKind::Lit(ref lit) => matches!([lit.kind] LitKind::Int(1, _)),
+        _ => false,
+    };
+    match e.kind {
+        ExprKind::AssignOp(op, ref lhs, ref rhs) => {
+            op.node == BinOpKind::Add && is_local(cx, counter, lhs) && is_one(rhs)
+        }
+        ExprKind::Assign(ref lhs, ref rhs) if is_local(cx, counter, lhs) => match rhs.kind {
+            ExprKind::Binary(op, ref a, ref b) => {
+                op.node == BinOpKind::Add && is_local(cx, counter, a) && is_one(b)
+            }
+            ExprKind::MethodCall(ref seg, ref args) => {
+                seg.ident.as_str() == "wrapping_add" &&
+                    is_local(cx, counter, &args[0]) && is_one(&args[1])
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// How each use of a buffer and its counter gets rewritten.
+#[derive(Default)]
+struct BufferPlan {
+    /// Statements writing to the buffer that become pushes, and whether the value is evaluated
+    /// after the counter was incremented
+    pushes: HashMap<NodeId, bool>,
+    /// Statements that save and increment the counter for a push
+    removed: HashSet<NodeId>,
+    /// Reads of the counter, and whether they come after the terminator
+    len_reads: HashMap<NodeId, bool>,
+    /// `strlen` calls on the terminated buffer, and their types
+    strlen_reads: HashMap<NodeId, P<Ty>>,
+    /// The statement writing the terminating NUL
+    terminator: Option<NodeId>,
+}
+
+impl BufferPlan {
+    /// Check the uses of the buffer declared by `stmts[decl]` with the counter declared by
+    /// `stmts[counter_decl]`.  Returns `None` if the buffer is never pushed to, and an error if
+    /// it is pushed to but used in some way that `Vec` can't replicate.
+    fn new(cx: &RefactorCtxt,
+           buf: &BufferInfo,
+           counter: &CounterInfo,
+           stmts: &[Stmt],
+           decl: usize,
+           counter_decl: usize) -> Result<Option<BufferPlan>, &'static str> {
+        let mut plan = BufferPlan::default();
+
+        // The counter must not be used before the buffer exists
+        for stmt in &stmts[counter_decl + 1..decl.max(counter_decl + 1)] {
+            let mut used = false;
+            visit_nodes(stmt, |e: &Expr| used |= is_local(cx, counter.hir_id, e));
+            if used {
+                return Ok(None);
+            }
+        }
+
+        let rest = &stmts[decl + 1..];
+        plan.find_pushes(cx, buf, counter, rest);
+        for stmt in rest {
+            visit_nodes(stmt, |b: &Block| plan.find_pushes(cx, buf, counter, &b.stmts));
+        }
+        if plan.pushes.is_empty() {
+            return Ok(None);
+        }
+
+        // The terminating NUL, written at the top level after the buffer is built
+        let terminator = rest.iter().position(|stmt| {
+            !plan.pushes.contains_key(&stmt.id) &&
+                buffer_write(cx, buf.hir_id, stmt).map_or(false, |(idx, val)| {
+                    is_local(cx, counter.hir_id, idx) && is_zero(val)
+                })
+        });
+        if let Some(t) = terminator {
+            plan.pushes.insert(rest[t].id, false);
+            plan.terminator = Some(rest[t].id);
+        }
+
+        let mut checker = UseChecker {
+            cx,
+            buf: buf.hir_id,
+            counter: counter.hir_id,
+            plan: &mut plan,
+            after_end: false,
+            value_after_increment: false,
+            error: None,
+        };
+        for (idx, stmt) in stmts.iter().enumerate().skip(decl + 1) {
+            if idx == counter_decl {
+                continue;
+            }
+            checker.after_end = terminator.map_or(false, |t| idx - (decl + 1) > t);
+            checker.visit_stmt(stmt);
+        }
+        if let Some(reason) = checker.error {
+            return Err(reason);
+        }
+
+        Ok(Some(plan))
+    }
+
+    /// Find the pushes to the buffer in a list of statements: `let fresh = pos; pos = pos + 1;
+    /// buf[fresh] = val;` as the translator emits for `buf[pos++] = val`, or `buf[pos] = val;
+    /// pos = pos + 1;`.
+    fn find_pushes(&mut self, cx: &RefactorCtxt, buf: &BufferInfo, counter: &CounterInfo,
+                   stmts: &[Stmt]) {
+        for (j, stmt) in stmts.iter().enumerate() {
+            if let StmtKind::Local(ref l) = stmt.kind {
+                let saves_counter = l.init.as_ref()
+                    .map_or(false, |init| is_local(cx, counter.hir_id, init));
+                if saves_counter && j + 2 < stmts.len() &&
+                   is_increment(cx, counter.hir_id, &stmts[j + 1]) {
+                    let fresh = cx.hir_map().node_to_hir_id(l.pat.id);
+                    let write = buffer_write(cx, buf.hir_id, &stmts[j + 2]);
+                    if write.map_or(false, |(idx, _)| is_local(cx, fresh, idx)) {
+                        self.removed.insert(stmt.id);
+                        self.removed.insert(stmts[j + 1].id);
+                        self.pushes.insert(stmts[j + 2].id, true);
+                    }
+                }
+            }
+
+            if let Some((idx, _)) = buffer_write(cx, buf.hir_id, stmt) {
+                if is_local(cx, counter.hir_id, idx) && j + 1 < stmts.len() &&
+                   is_increment(cx, counter.hir_id, &stmts[j + 1]) {
+                    self.removed.insert(stmts[j + 1].id);
+                    self.pushes.insert(stmt.id, false);
+                }
+            }
+        }
+    }
+
+    fn rewrite(&self, b: &mut P<Block>, buf: &BufferInfo, counter: &CounterInfo) {
+        let len = || mk().method_call_expr(mk().ident_expr(buf.name), "len",
+                                           Vec::<P<Expr>>::new());
+        let len_minus_one = || mk().paren_expr(mk().binary_expr(
+            BinOpKind::Sub, len(), mk().lit_expr(mk().int_lit(1, LitIntType::Unsuffixed))));
+
+        MutVisitNodes::visit(b, |e: &mut P<Expr>| {
+            if let Some(&after_end) = self.len_reads.get(&e.id) {
+                let len = if after_end { len_minus_one() } else { len() };
+                *e = mk().paren_expr(mk().cast_expr(len, counter.ty.clone()));
+            } else if let Some(ty) = self.strlen_reads.get(&e.id) {
+                *e = mk().paren_expr(mk().cast_expr(len_minus_one(), ty.clone()));
+            }
+        });
+
+        FlatMapNodes::visit(b, |s: Stmt| {
+            if self.removed.contains(&s.id) {
+                return smallvec![];
+            }
+            if !self.pushes.contains_key(&s.id) {
+                return smallvec![s];
+            }
+            let e = stmt_expr(&s).unwrap();
+            let val = expect!([e.kind] ExprKind::Assign(_, ref val) => val.clone());
+            smallvec![mk().semi_stmt(
+                mk().method_call_expr(mk().ident_expr(buf.name), "push", vec![val]))]
+        });
+    }
+}
+
+/// Checks that a buffer and its counter are only used in ways a `Vec` supports, and records
+/// the counter reads to replace.
+struct UseChecker<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    buf: HirId,
+    counter: HirId,
+    plan: &'a mut BufferPlan,
+    after_end: bool,
+    value_after_increment: bool,
+    error: Option<&'static str>,
+}
+
+impl<'a, 'tcx> UseChecker<'a, 'tcx> {
+    /// Check whether `e` is `buf.as_ptr()` or `buf.as_mut_ptr()`.
+    fn is_buffer_ptr(&self, e: &Expr) -> bool {
+        match strip_casts(e).kind {
+            ExprKind::MethodCall(ref seg, ref args) => {
+                (seg.ident.as_str() == "as_ptr" || seg.ident.as_str() == "as_mut_ptr") &&
+                    is_local(self.cx, self.buf, &args[0])
+            }
+            _ => false,
+        }
+    }
+
+    /// If `e` is `buf[idx]`, get `idx`.
+    fn buffer_index<'b>(&self, e: &'b Expr) -> Option<&'b Expr> {
+        match strip_casts(e).kind {
+            ExprKind::Index(ref base, ref idx) if is_local(self.cx, self.buf, base) => {
+                Some(&**idx)
+            }
+            _ => None,
+        }
+    }
+
+    fn fail(&mut self, reason: &'static str) {
+        if self.error.is_none() {
+            self.error = Some(reason);
+        }
+    }
+}
+
+fn is_strlen(func: &Expr) -> bool {
+    match func.kind {
+        ExprKind::Path(None, ref path) => {
+            path.segments.last().map_or(false, |seg| seg.ident.as_str() == "strlen")
+        }
+        _ => false,
+    }
+}
+
+impl<'a, 'tcx, 'ast> Visitor<'ast> for UseChecker<'a, 'tcx> {
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        if self.plan.removed.contains(&s.id) {
+            return;
+        }
+        if let Some(&after_increment) = self.plan.pushes.get(&s.id) {
+            if self.after_end && self.plan.terminator != Some(s.id) {
+                self.fail("the buffer is pushed to after it is terminated");
+            }
+            // Only the pushed value is left after the rewrite
+            let val = expect!([stmt_expr(s).map(|e| &e.kind)]
+                              Some(ExprKind::Assign(_, val)) => val);
+            self.value_after_increment = after_increment;
+            self.visit_expr(val);
+            self.value_after_increment = false;
+            return;
+        }
+        visit::walk_stmt(self, s);
+    }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        let cx = self.cx;
+        if is_local(cx, self.counter, e) {
+            if self.value_after_increment {
+                self.fail("the counter is read in a pushed value");
+            }
+            self.plan.len_reads.insert(strip_casts(e).id, self.after_end);
+            return;
+        }
+        if is_local(cx, self.buf, e) {
+            self.fail("the buffer is used other than by indexing");
+            return;
+        }
+
+        match e.kind {
+            ExprKind::Assign(ref lhs, _) | ExprKind::AssignOp(_, ref lhs, _)
+                    if is_local(cx, self.counter, lhs) => {
+                self.fail("the counter is modified outside of pushes");
+            }
+            ExprKind::Assign(ref lhs, _) if self.buffer_index(lhs)
+                    .map_or(false, |idx| is_local(cx, self.counter, idx)) => {
+                self.fail("the buffer is written past its end");
+            }
+            ExprKind::AddrOf(_, ref inner) if self.buffer_index(inner).is_some() => {
+                self.fail("the address of an element is taken");
+            }
+            ExprKind::Index(ref base, ref idx) if is_local(cx, self.buf, base) => {
+                self.visit_expr(idx);
+            }
+            ExprKind::MethodCall(_, ref args) if self.is_buffer_ptr(e) => {
+                if !self.after_end {
+                    self.fail("the buffer is passed on before it is terminated");
+                }
+                for arg in &args[1..] {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprKind::Call(ref func, ref args) if self.after_end && args.len() == 1 &&
+                    self.is_buffer_ptr(&args[0]) && is_strlen(func) => {
+                let ty = reflect_tcx_ty(cx.ty_ctxt(), cx.node_type(e.id));
+                self.plan.strlen_reads.insert(e.id, ty);
+            }
+            _ => visit::walk_expr(self, e),
+        }
+    }
+
+    fn visit_mac(&mut self, mac: &'ast Mac) {
+        visit::walk_mac(self, mac);
+    }
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("buffer_to_string", |_args| mk(BufferToString));
+}
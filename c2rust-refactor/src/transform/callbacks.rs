@@ -0,0 +1,606 @@
+use std::collections::{HashMap, HashSet};
+use rustc::hir::def_id::DefId;
+use rustc::ty::{self, TyCtxt};
+use syntax::ast::*;
+use syntax::print::pprust;
+use syntax::ptr::P;
+use syntax::source_map::DUMMY_SP;
+use syntax::visit::{self, Visitor};
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{AstEquiv, FlatMapNodes, MutVisitNodes, Visit, visit_nodes};
+use crate::ast_manip::util::remove_derives;
+use crate::command::{CommandState, Registry};
+use crate::driver::{Phase, parse_ty};
+use crate::reflect;
+use crate::transform::util::{stmt_expr, strip_casts};
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `callback_to_closure` Command
+///
+/// Usage: `callback_to_closure`
+///
+/// Marks: `target`
+///
+/// Convert a callback stored in a struct as a function pointer and the `void *` data passed
+/// back to it into a boxed closure.  Mark the two fields `target`: the function pointer field
+/// becomes a `Box<dyn FnMut(...)>` taking the callback's other arguments (in an `Option` if the
+/// pointer was one), and the data field is removed.
+///
+///  * Calls of the callback with the data, `(*p).cb.expect(...)((*p).data, x)`, call the closure
+///    instead: `(*p).cb.as_mut().expect(...)(x)`.
+///  * Registrations, which store a function pointer and the data in the same block, store a
+///    closure calling the function pointer with the data.  The registration functions keep their
+///    signatures, so C callers can still register an `extern "C"` function with its data: the
+///    closure is the thunk between the two.
+///  * Stores of the data alone, such as resetting it along with the callback, are removed.
+///
+/// The struct loses its `Copy` and `Clone` derives.  It is only converted if foreign functions
+/// don't use it and the two fields are used in no other way.
+///
+/// Example:
+///
+/// ```ignore
+///     pub struct emitter {
+///         pub on_event: Option<unsafe extern "C" fn(*mut libc::c_void, libc::c_int)>,
+///         pub user_data: *mut libc::c_void,
+///     }
+///     pub unsafe extern "C" fn emitter_on(mut e: *mut emitter,
+///                                         mut cb: Option<unsafe extern "C" fn(*mut libc::c_void,
+///                                                                             libc::c_int)>,
+///                                         mut data: *mut libc::c_void) {
+///         (*e).on_event = cb;
+///         (*e).user_data = data;
+///     }
+///     pub unsafe extern "C" fn emitter_emit(mut e: *mut emitter, mut event: libc::c_int) {
+///         (*e).on_event.expect("non-null function pointer")((*e).user_data, event);
+///     }
+/// ```
+///
+/// After running `callback_to_closure` with both fields of `emitter` marked:
+///
+/// ```ignore
+///     pub struct emitter {
+///         pub on_event: Option<Box<dyn FnMut(libc::c_int)>>,
+///     }
+///     pub unsafe extern "C" fn emitter_on(mut e: *mut emitter,
+///                                         mut cb: Option<unsafe extern "C" fn(*mut libc::c_void,
+///                                                                             libc::c_int)>,
+///                                         mut data: *mut libc::c_void) {
+///         (*e).on_event = {
+///             let user_data = data;
+///             cb.map(|f| {
+///                 Box::new(move |arg0: libc::c_int| unsafe { f(user_data, arg0) })
+///                     as Box<dyn FnMut(libc::c_int)>
+///             })
+///         };
+///     }
+///     pub unsafe extern "C" fn emitter_emit(mut e: *mut emitter, mut event: libc::c_int) {
+///         (*e).on_event.as_mut().expect("non-null function pointer")(event);
+///     }
+/// ```
+pub struct CallbackToClosure;
+
+impl Transform for CallbackToClosure {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        let mut callbacks = vec![];
+        visit_nodes(krate, |i: &Item| {
+            if let ItemKind::Struct(VariantData::Struct(ref fields, _), _) = i.kind {
+                let marked = fields
+                    .iter()
+                    .filter(|f| st.marked(f.id, "target"))
+                    .collect::<Vec<_>>();
+                if marked.is_empty() {
+                    return;
+                }
+                match Callback::new(cx, krate, i, &marked) {
+                    Ok(callback) => callbacks.push(callback),
+                    Err(reason) => eprintln!("callback_to_closure: {}: {}", i.ident, reason),
+                }
+            }
+        });
+
+        for callback in callbacks {
+            match CallbackPlan::new(cx, krate, &callback) {
+                Ok(plan) => plan.rewrite(cx, krate, &callback),
+                Err(reason) => eprintln!("callback_to_closure: {}: {}", callback.name, reason),
+            }
+        }
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// A struct field holding a function pointer, and the field holding the data passed to it.
+struct Callback {
+    def_id: DefId,
+    name: Ident,
+    func: Ident,
+    data: Ident,
+    /// Whether the function pointer is an `Option`
+    optional: bool,
+    /// The position of the data among the arguments of the function
+    data_index: usize,
+    /// The types of the other arguments, which the closure takes
+    arg_tys: Vec<P<Ty>>,
+    /// `Box<dyn FnMut(...)>`
+    closure_ty: P<Ty>,
+}
+
+impl Callback {
+    fn new(cx: &RefactorCtxt,
+           krate: &Crate,
+           i: &Item,
+           marked: &[&StructField]) -> Result<Callback, String> {
+        let tcx = cx.ty_ctxt();
+        if marked.len() != 2 {
+            return Err(format!("expected 2 marked fields, but found {}", marked.len()));
+        }
+        let field_ty = |f: &StructField| tcx.type_of(cx.node_def_id(f.id));
+        let (func, data, sig, optional) = match (fn_ptr_sig(tcx, field_ty(marked[0])),
+                                                 fn_ptr_sig(tcx, field_ty(marked[1]))) {
+            (Some((sig, optional)), None) => (marked[0], marked[1], sig, optional),
+            (None, Some((sig, optional))) => (marked[1], marked[0], sig, optional),
+            _ => return Err("expected exactly one of the marked fields to be a function \
+                             pointer".to_owned()),
+        };
+        let (func_name, data_name) = match (func.ident, data.ident) {
+            (Some(func_name), Some(data_name)) => (func_name, data_name),
+            _ => return Err("the marked fields must be named".to_owned()),
+        };
+
+        let data_ty = field_ty(data);
+        if !matches!([data_ty.kind] ty::TyKind::RawPtr(..)) {
+            return Err(format!("the data field `{}` is not a pointer", data_name));
+        }
+        let sig = sig.skip_binder();
+        if sig.c_variadic {
+            return Err(format!("`{}` is variadic", func_name));
+        }
+        let data_index = sig.inputs().iter().position(|&ty| ty == data_ty).ok_or_else(|| {
+            format!("`{}` takes no argument of the type of `{}`", func_name, data_name)
+        })?;
+
+        // Spell the types as the field type does, if we can find the `fn` type in it
+        let decl = fn_ptr_decl(cx, krate, &func.ty)
+            .filter(|decl| decl.inputs.len() == sig.inputs().len());
+        let mut arg_tys = match decl {
+            Some(ref decl) => decl.inputs.iter().map(|arg| arg.ty.clone()).collect::<Vec<_>>(),
+            None => sig.inputs().iter().map(|&ty| reflect::reflect_tcx_ty(tcx, ty)).collect(),
+        };
+        arg_tys.remove(data_index);
+        let ret = if sig.output().is_unit() {
+            String::new()
+        } else {
+            let ty = match decl.as_ref().map(|decl| &decl.output) {
+                Some(FunctionRetTy::Ty(ty)) => ty.clone(),
+                _ => reflect::reflect_tcx_ret_ty(tcx, sig.output()),
+            };
+            format!(" -> {}", pprust::ty_to_string(&ty))
+        };
+        let args = arg_tys.iter().map(|ty| pprust::ty_to_string(ty)).collect::<Vec<_>>();
+        let closure_ty = parse_ty(cx.session(),
+                                  &format!("Box<dyn FnMut({}){}>", args.join(", "), ret));
+
+        Ok(Callback {
+            def_id: cx.node_def_id(i.id),
+            name: i.ident,
+            func: func_name,
+            data: data_name,
+            optional,
+            data_index,
+            arg_tys,
+            closure_ty,
+        })
+    }
+
+    /// The new type of the function pointer field.
+    fn field_ty(&self) -> P<Ty> {
+        if self.optional {
+            mk().path_ty(vec![mk().path_segment_with_args(
+                "Option",
+                mk().angle_bracketed_args(vec![self.closure_ty.clone()]),
+            )])
+        } else {
+            self.closure_ty.clone()
+        }
+    }
+
+    /// Build the value to store for the function pointer `func` registered with `data`: a boxed
+    /// closure calling `func` with `data` and its own arguments.
+    fn boxed(&self, func: P<Expr>, data: P<Expr>) -> P<Expr> {
+        let names = (0..self.arg_tys.len()).map(|i| format!("arg{}", i)).collect::<Vec<_>>();
+        let params = self.arg_tys.iter().zip(&names)
+            .map(|(ty, name)| mk().arg(ty, mk().ident_pat(name)))
+            .collect();
+        let mut args = names.iter().map(|name| mk().ident_expr(name)).collect::<Vec<_>>();
+        args.insert(self.data_index, mk().ident_expr("user_data"));
+        let call = mk().call_expr(mk().ident_expr("f"), args);
+        let closure = mk().closure_expr(
+            CaptureBy::Value,
+            Movability::Movable,
+            mk().fn_decl(params, FunctionRetTy::Default(DUMMY_SP)),
+            mk().block_expr(mk().unsafe_().block(vec![mk().expr_stmt(call)])),
+        );
+        let boxed = mk().cast_expr(
+            mk().call_expr(mk().path_expr(vec!["Box", "new"]), vec![closure]),
+            &self.closure_ty,
+        );
+
+        let mut stmts = vec![
+            mk().local_stmt(P(mk().local(mk().ident_pat("user_data"), None as Option<P<Ty>>,
+                                         Some(data)))),
+        ];
+        let value = if self.optional {
+            let wrap = mk().closure_expr(
+                CaptureBy::Ref,
+                Movability::Movable,
+                mk().fn_decl(vec![mk().arg(mk().infer_ty(), mk().ident_pat("f"))],
+                             FunctionRetTy::Default(DUMMY_SP)),
+                boxed,
+            );
+            mk().method_call_expr(func, "map", vec![wrap])
+        } else {
+            stmts.push(mk().local_stmt(P(mk().local(mk().ident_pat("f"), None as Option<P<Ty>>,
+                                                    Some(func)))));
+            boxed
+        };
+        stmts.push(mk().expr_stmt(value));
+        mk().block_expr(mk().block(stmts))
+    }
+}
+
+/// Get the signature of a function pointer type, which may be wrapped in an `Option`, and
+/// whether it is.
+fn fn_ptr_sig<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> Option<(ty::PolyFnSig<'tcx>, bool)> {
+    match ty.kind {
+        ty::TyKind::FnPtr(sig) => Some((sig, false)),
+        ty::TyKind::Adt(def, substs) if tcx.item_name(def.did).as_str() == "Option" => {
+            match substs.type_at(0).kind {
+                ty::TyKind::FnPtr(sig) => Some((sig, true)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Find the `fn` type that `ty` spells out, looking through `Option` and type aliases of the
+/// crate.
+fn fn_ptr_decl(cx: &RefactorCtxt, krate: &Crate, ty: &Ty) -> Option<P<FnDecl>> {
+    match ty.kind {
+        TyKind::BareFn(ref f) => Some(f.decl.clone()),
+        TyKind::Path(None, ref path) => {
+            let seg = path.segments.last()?;
+            if seg.ident.as_str() == "Option" {
+                let args = match_or!([seg.args.as_ref().map(|args| &**args)]
+                                     Some(GenericArgs::AngleBracketed(args)) => args;
+                                     return None);
+                return match args.args.first() {
+                    Some(GenericArg::Type(ty)) => fn_ptr_decl(cx, krate, ty),
+                    _ => None,
+                };
+            }
+
+            let def_id = cx.try_resolve_ty(ty)?;
+            let mut alias = None;
+            visit_nodes(krate, |i: &Item| {
+                if let ItemKind::TyAlias(ref ty, _) = i.kind {
+                    if cx.hir_map().opt_local_def_id_from_node_id(i.id) == Some(def_id) {
+                        alias = Some(ty.clone());
+                    }
+                }
+            });
+            fn_ptr_decl(cx, krate, &alias?)
+        }
+        _ => None,
+    }
+}
+
+fn strip_parens(mut e: &Expr) -> &Expr {
+    while let ExprKind::Paren(ref inner) = e.kind {
+        e = inner;
+    }
+    e
+}
+
+fn is_none(e: &Expr) -> bool {
+    match strip_parens(e).kind {
+        ExprKind::Path(None, ref path) => {
+            path.segments.last().map_or(false, |seg| seg.ident.as_str() == "None")
+        }
+        _ => false,
+    }
+}
+
+/// A statement `base.field = value;` storing one of the two fields.
+struct Store<'a> {
+    stmt: NodeId,
+    lhs: NodeId,
+    base: &'a Expr,
+    value: &'a P<Expr>,
+}
+
+/// The rewrites converting one callback.
+#[derive(Default)]
+struct CallbackPlan {
+    /// Calls of the function pointer, which lose the data argument
+    calls: HashSet<NodeId>,
+    /// The function pointer fields those calls go through
+    callees: HashSet<NodeId>,
+    /// Assignments of function pointers, with the data registered along with them
+    registrations: HashMap<NodeId, P<Expr>>,
+    /// Struct literals initializing the fields
+    literals: HashSet<NodeId>,
+    /// Statements storing the data
+    removed: HashSet<NodeId>,
+}
+
+impl CallbackPlan {
+    fn new(cx: &RefactorCtxt, krate: &Crate, callback: &Callback) -> Result<CallbackPlan, String> {
+        let mut foreign = false;
+        visit_nodes(krate, |fi: &ForeignItem| {
+            visit_nodes(fi, |t: &Ty| {
+                if cx.try_resolve_ty(t) == Some(callback.def_id) {
+                    foreign = true;
+                }
+            });
+        });
+        if foreign {
+            return Err("it is used by foreign functions".to_owned());
+        }
+
+        let mut plan = CallbackPlan::default();
+        let mut v = UseChecker {
+            cx,
+            callback,
+            plan: &mut plan,
+            allowed: HashSet::new(),
+            error: None,
+        };
+        krate.visit(&mut v);
+        match v.error {
+            Some(error) => Err(error),
+            None => Ok(plan),
+        }
+    }
+
+    fn rewrite(self, cx: &RefactorCtxt, krate: &mut Crate, callback: &Callback) {
+        FlatMapNodes::visit(krate, |mut i: P<Item>| {
+            if cx.hir_map().opt_local_def_id_from_node_id(i.id) != Some(callback.def_id) {
+                return smallvec![i];
+            }
+            if let ItemKind::Struct(VariantData::Struct(ref mut fields, _), _) = i.kind {
+                fields.retain(|f| f.ident != Some(callback.data));
+                for f in fields.iter_mut() {
+                    if f.ident == Some(callback.func) {
+                        f.ty = callback.field_ty();
+                    }
+                }
+            }
+            // Closures are neither `Copy` nor `Clone`
+            remove_derives(&mut i.attrs, &["Copy", "Clone"]);
+            smallvec![i]
+        });
+
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            if self.callees.contains(&e.id) {
+                *e = if callback.optional {
+                    mk().method_call_expr(e.clone(), "as_mut", Vec::<P<Expr>>::new())
+                } else {
+                    mk().paren_expr(e.clone())
+                };
+                return;
+            }
+
+            let id = e.id;
+            match e.kind {
+                ExprKind::Call(_, ref mut args) if self.calls.contains(&id) => {
+                    args.remove(callback.data_index);
+                }
+                ExprKind::Assign(_, ref mut rhs) => {
+                    if let Some(data) = self.registrations.get(&id) {
+                        *rhs = callback.boxed(rhs.clone(), data.clone());
+                    }
+                }
+                ExprKind::Struct(_, ref mut fields, _) if self.literals.contains(&id) => {
+                    let data = fields.iter()
+                        .find(|f| f.ident == callback.data)
+                        .map(|f| f.expr.clone());
+                    fields.retain(|f| f.ident != callback.data);
+                    for f in fields.iter_mut() {
+                        if f.ident == callback.func && !is_none(&f.expr) {
+                            let data = data.clone().expect("registered without data");
+                            f.expr = callback.boxed(f.expr.clone(), data);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        });
+
+        FlatMapNodes::visit(krate, |s: Stmt| {
+            if self.removed.contains(&s.id) {
+                smallvec![]
+            } else {
+                smallvec![s]
+            }
+        });
+    }
+}
+
+/// Check that the two fields are only used in ways we can rewrite, collecting the rewrites into
+/// the plan.
+struct UseChecker<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    callback: &'a Callback,
+    plan: &'a mut CallbackPlan,
+    /// Uses of the fields in a position we can rewrite
+    allowed: HashSet<NodeId>,
+    error: Option<String>,
+}
+
+impl<'a, 'tcx> UseChecker<'a, 'tcx> {
+    fn fail(&mut self, msg: String) {
+        if self.error.is_none() {
+            self.error = Some(msg);
+        }
+    }
+
+    /// Check whether `e` is the target struct, or a reference to it.
+    fn is_target(&self, e: &Expr) -> bool {
+        let mut ty = match self.cx.opt_node_type(strip_parens(e).id) {
+            Some(ty) => ty,
+            None => return false,
+        };
+        while let ty::TyKind::Ref(_, inner, _) = ty.kind {
+            ty = inner;
+        }
+        match ty.kind {
+            ty::TyKind::Adt(def, _) => def.did == self.callback.def_id,
+            _ => false,
+        }
+    }
+
+    /// If `e` is `base.field` on the target struct, get `base`.
+    fn field_base<'e>(&self, e: &'e Expr, field: Ident) -> Option<&'e Expr> {
+        match strip_parens(e).kind {
+            ExprKind::Field(ref base, ident) if ident == field && self.is_target(base) => {
+                Some(base)
+            }
+            _ => None,
+        }
+    }
+
+    fn store<'s>(&self, stmt: &'s Stmt, field: Ident) -> Option<Store<'s>> {
+        let (lhs, value) = match_or!([stmt_expr(stmt)?.kind]
+                                     ExprKind::Assign(ref lhs, ref value) => (lhs, value);
+                                     return None);
+        let base = self.field_base(lhs, field)?;
+        Some(Store { stmt: stmt.id, lhs: strip_parens(lhs).id, base, value })
+    }
+
+    /// If `callee` calls the function pointer field, get the field expression.
+    fn callee_field<'e>(&self, callee: &'e Expr) -> Option<&'e Expr> {
+        let callee = strip_parens(callee);
+        let field = if self.callback.optional {
+            match callee.kind {
+                ExprKind::MethodCall(ref seg, ref args)
+                    if seg.ident.as_str() == "expect" || seg.ident.as_str() == "unwrap" =>
+                {
+                    strip_parens(&args[0])
+                }
+                _ => return None,
+            }
+        } else {
+            callee
+        };
+        self.field_base(field, self.callback.func)?;
+        Some(field)
+    }
+}
+
+impl<'a, 'tcx, 'ast> Visitor<'ast> for UseChecker<'a, 'tcx> {
+    fn visit_block(&mut self, b: &'ast Block) {
+        let (func, data) = (self.callback.func, self.callback.data);
+        let data_stores = b.stmts.iter().filter_map(|s| self.store(s, data)).collect::<Vec<_>>();
+        let mut used_data = HashSet::new();
+        for s in &b.stmts {
+            let store = match self.store(s, func) {
+                Some(store) => store,
+                None => continue,
+            };
+            self.allowed.insert(store.lhs);
+            if is_none(store.value) {
+                continue;
+            }
+            match data_stores.iter().find(|d| d.base.ast_equiv(store.base)) {
+                Some(data_store) => {
+                    used_data.insert(data_store.stmt);
+                    self.plan.registrations.insert(stmt_expr(s).unwrap().id,
+                                                   data_store.value.clone());
+                }
+                None => self.fail(format!("`{}` is stored without its data: `{}`",
+                                          func, pprust::stmt_to_string(s))),
+            }
+        }
+        for d in &data_stores {
+            // The data is dropped, so it may only be evaluated for its value
+            let value = strip_casts(d.value);
+            let pure = match value.kind {
+                ExprKind::Path(..) | ExprKind::Lit(..) => true,
+                ExprKind::AddrOf(_, ref place) => matches!([strip_casts(place).kind]
+                                                           ExprKind::Path(..)),
+                _ => false,
+            };
+            if !pure && !used_data.contains(&d.stmt) {
+                self.fail(format!("`{}` is stored without a callback: `{}`",
+                                  data, pprust::expr_to_string(d.value)));
+            }
+            self.allowed.insert(d.lhs);
+            self.plan.removed.insert(d.stmt);
+        }
+
+        visit::walk_block(self, b);
+    }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        let callback = self.callback;
+        match e.kind {
+            ExprKind::Call(ref callee, ref args) => {
+                if let Some(field) = self.callee_field(callee) {
+                    let data = args.get(callback.data_index).map(|arg| strip_casts(arg));
+                    match data.filter(|arg| self.field_base(arg, callback.data).is_some()) {
+                        Some(arg) => {
+                            self.allowed.insert(field.id);
+                            self.allowed.insert(arg.id);
+                            self.plan.callees.insert(field.id);
+                            self.plan.calls.insert(e.id);
+                        }
+                        None => self.fail(format!("`{}` is called without its data: `{}`",
+                                                  callback.func, pprust::expr_to_string(e))),
+                    }
+                }
+            }
+            ExprKind::MethodCall(ref seg, ref args)
+                if seg.ident.as_str() == "is_some" || seg.ident.as_str() == "is_none" =>
+            {
+                if self.field_base(&args[0], callback.func).is_some() {
+                    self.allowed.insert(strip_parens(&args[0]).id);
+                }
+            }
+            ExprKind::Struct(_, ref fields, _) if self.is_target(e) => {
+                let func = fields.iter().find(|f| f.ident == callback.func);
+                let has_data = fields.iter().any(|f| f.ident == callback.data);
+                if func.map_or(false, |f| !is_none(&f.expr)) && !has_data {
+                    self.fail(format!("`{}` is initialized without its data: `{}`",
+                                      callback.func, pprust::expr_to_string(e)));
+                }
+                self.plan.literals.insert(e.id);
+            }
+            _ => {}
+        }
+
+        let is_field = self.field_base(e, callback.func).is_some()
+            || self.field_base(e, callback.data).is_some();
+        if is_field && !matches!([e.kind] ExprKind::Paren(..)) && !self.allowed.contains(&e.id) {
+            self.fail(format!("unsupported use of a field: `{}`", pprust::expr_to_string(e)));
+        }
+
+        visit::walk_expr(self, e);
+    }
+
+    fn visit_mac(&mut self, mac: &'ast Mac) {
+        visit::walk_mac(self, mac);
+    }
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("callback_to_closure", |_args| mk(CallbackToClosure));
+}
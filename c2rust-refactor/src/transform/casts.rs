@@ -3,7 +3,8 @@ use syntax::ast::*;
 use syntax::parse::token;
 use syntax::ptr::P;
 
-use crate::command::{CommandState, Registry};
+use crate::ast_manip::MutVisitNodes;
+use crate::command::{CommandState, Registry, RefactorState, TypeckLoopResult};
 use crate::driver::Phase;
 use crate::matcher::{mut_visit_match_with, replace_expr, MatchCtxt};
 use crate::transform::Transform;
@@ -127,6 +128,74 @@ impl Transform for RemoveRedundantCasts {
     fn min_phase(&self) -> Phase {
         Phase::Phase3
     }
+
+    fn cleanup_casts(&self) -> bool {
+        // Casts to the same type are already removed here
+        false
+    }
+}
+
+/// Remove the casts to the type their operand already has from the crate, repeating until no
+/// more are found.  This is the cleanup `TransformCommand` runs after each phase 3 transform.
+pub fn remove_cast_to_self_type(state: &mut RefactorState) {
+    state.run_typeck_loop(|krate, _st, cx| {
+        let mut removed = false;
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            let operand = match e.kind {
+                ExprKind::Cast(ref operand, _) if is_cast_to_self_type(e, operand, cx) => {
+                    operand.clone()
+                }
+                _ => return,
+            };
+            debug!("removing cast to self type: {:?}", e);
+            *e = operand;
+            removed = true;
+        });
+        if removed {
+            TypeckLoopResult::Iterate
+        } else {
+            TypeckLoopResult::Finished
+        }
+    }).expect("Failed to remove casts to self type");
+}
+
+/// Check if the cast `e` of `operand` can be removed because it has the same type as
+/// `operand`, after resolving type aliases.
+fn is_cast_to_self_type(e: &Expr, operand: &Expr, cx: &RefactorCtxt) -> bool {
+    // Leave casts inside macro expansions alone, since we can't rewrite them
+    if e.span.from_expansion() {
+        return false;
+    }
+
+    // Unsuffixed literals take their type from the cast, see `RemoveRedundantCasts`
+    let lit = match operand.kind {
+        ExprKind::Unary(UnOp::Neg, ref expr) => &expr.kind,
+        ref kind => kind,
+    };
+    if let ExprKind::Lit(ref lit) = *lit {
+        if lit.kind.is_unsuffixed() {
+            return false;
+        }
+    }
+
+    // A cast can also coerce its operand, for example reborrowing a `&mut T` or unsizing
+    // `&[T; N]` to `&[T]`.  The operand alone would not be coerced in every context, so such
+    // casts are kept even if the types match.
+    if cx.has_adjustments(operand.id) {
+        return false;
+    }
+
+    let tcx = cx.ty_ctxt();
+    let (operand_ty, cast_ty) = match (cx.opt_node_type(operand.id), cx.opt_node_type(e.id)) {
+        (Some(operand_ty), Some(cast_ty)) => (operand_ty, cast_ty),
+        _ => return false,
+    };
+    if let TyKind::Error = operand_ty.kind {
+        return false;
+    }
+    let operand_ty = tcx.normalize_erasing_regions(ParamEnv::empty(), operand_ty);
+    let cast_ty = tcx.normalize_erasing_regions(ParamEnv::empty(), cast_ty);
+    operand_ty == cast_ty
 }
 
 enum DoubleCastAction {
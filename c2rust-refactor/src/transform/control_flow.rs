@@ -1,7 +1,16 @@
-use syntax::ast::{Crate, Expr, ExprKind, Lit, LitKind, Stmt, StmtKind};
+use std::collections::HashSet;
+use rustc::hir::def::{DefKind, Res};
+use rustc::ty::{self, TyKind};
+use syntax::ast::{BinOpKind, Block, BlockCheckMode, Crate, Expr, ExprKind, Label, Lit, LitIntType};
+use syntax::ast::{LitKind, Mac, Pat, Stmt, StmtKind, UnOp};
+use syntax::print::pprust;
 use syntax::ptr::P;
+use syntax::visit::{self, Visitor};
 
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{AstEquiv, MutVisitNodes, visit_nodes};
 use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
 use crate::matcher::{MatchCtxt, Subst, replace_expr, mut_visit_match_with, find_first};
 use crate::transform::Transform;
 use crate::RefactorCtxt;
@@ -166,10 +175,410 @@ impl Transform for RemoveUnusedLabels {
 }
 
 
+/// # `destructure_loops` Command
+///
+/// Usage: `destructure_loops`
+///
+/// Removes `loop`s whose body only ever runs once.  The relooper can leave behind
+/// `loop { ...; break; }` around code with early exits, even when the C code had no `goto`.  A
+/// loop is removed if every path through its body ends in a `break` out of the loop and nothing
+/// in the body continues it.  The `break`s are turned into the control flow they stand for: a
+/// `break` at the end of the body is dropped, and an `if` that breaks out of the loop in one
+/// branch gets the rest of the body as its other branch.  For example,
+/// `'a: loop { if c { f(); break 'a; } g(); break; }` becomes `{ if c { f(); } else { g(); } }`.
+///
+/// Loops that break with a value, or that break out of the loop in any other position, are left
+/// alone.
+pub struct DestructureLoops;
+
+impl Transform for DestructureLoops {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, _cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            let block = match e.kind {
+                ExprKind::Loop(ref body, label) => match destructure_stmts(&body.stmts, label) {
+                    Some(stmts) => mk().span(body.span).block(stmts),
+                    None => return,
+                },
+                _ => return,
+            };
+            e.kind = ExprKind::Block(block, None);
+        })
+    }
+}
+
+/// Rewrite `stmts` from the body of the loop labeled `label` so they no longer exit the loop.
+/// Returns `None` unless every path through `stmts` exits the loop with a `break`, and each exit
+/// can be removed without duplicating code.
+fn destructure_stmts(stmts: &[Stmt], label: Option<Label>) -> Option<Vec<Stmt>> {
+    let mut result = vec![];
+    for (i, stmt) in stmts.iter().enumerate() {
+        if !exits_loop(|v| v.visit_stmt(stmt), label) {
+            result.push(stmt.clone());
+            continue;
+        }
+
+        let e = match stmt.kind {
+            StmtKind::Expr(ref e) | StmtKind::Semi(ref e) => e,
+            _ => return None,
+        };
+        match e.kind {
+            // Anything after the `break` is unreachable
+            ExprKind::Break(_, None) if breaks_loop(e, label) => return Some(result),
+
+            ExprKind::Block(ref block, None) => {
+                let stmts = destructure_stmts(&block.stmts, label)?;
+                let block = mk().span(block.span).block(stmts);
+                result.push(mk().span(stmt.span).expr_stmt(mk().block_expr(block)));
+                return Some(result);
+            }
+
+            ExprKind::If(ref cond, ref then, ref els) => {
+                let else_stmts = match *els {
+                    None => vec![],
+                    Some(ref els) => match els.kind {
+                        ExprKind::Block(ref block, None) => block.stmts.clone(),
+                        ExprKind::If(..) => vec![mk().expr_stmt(els.clone())],
+                        _ => return None,
+                    },
+                };
+
+                // The rest of the body can only be moved into one of the branches
+                let rest = &stmts[i + 1..];
+                let (then_stmts, else_stmts) = match (
+                    destructure_stmts(&then.stmts, label),
+                    destructure_stmts(&else_stmts, label),
+                ) {
+                    (Some(then_stmts), Some(else_stmts)) => (then_stmts, else_stmts),
+                    (Some(then_stmts), None) => {
+                        let else_stmts = [&else_stmts[..], rest].concat();
+                        (then_stmts, destructure_stmts(&else_stmts, label)?)
+                    }
+                    (None, Some(else_stmts)) => {
+                        let then_stmts = [&then.stmts[..], rest].concat();
+                        (destructure_stmts(&then_stmts, label)?, else_stmts)
+                    }
+                    (None, None) => return None,
+                };
+
+                let els = match &else_stmts[..] {
+                    [] => None,
+                    [Stmt { kind: StmtKind::Expr(e), .. }] if is_if(e) => Some(e.clone()),
+                    _ => Some(mk().block_expr(mk().block(else_stmts))),
+                };
+                let if_expr = mk().ifte_expr(
+                    cond.clone(),
+                    mk().span(then.span).block(then_stmts),
+                    els,
+                );
+                result.push(mk().span(stmt.span).expr_stmt(if_expr));
+                return Some(result);
+            }
+
+            _ => return None,
+        }
+    }
+
+    // Control falls through to the next iteration
+    None
+}
+
+fn is_if(e: &Expr) -> bool {
+    match e.kind {
+        ExprKind::If(..) => true,
+        _ => false,
+    }
+}
+
+/// Check if `e`, which is not nested in another loop, is a `break` out of the loop labeled
+/// `label`.
+fn breaks_loop(e: &Expr, label: Option<Label>) -> bool {
+    match e.kind {
+        ExprKind::Break(target, _) => targets_loop(target, label, 0),
+        _ => false,
+    }
+}
+
+fn targets_loop(target: Option<Label>, label: Option<Label>, depth: usize) -> bool {
+    match (target, label) {
+        (None, _) => depth == 0,
+        (Some(target), Some(label)) => target.ident.name == label.ident.name,
+        (Some(_), None) => false,
+    }
+}
+
+/// Check if the AST visited by `walk` contains a `break` or `continue` for the loop labeled
+/// `label`.
+fn exits_loop<F>(walk: F, label: Option<Label>) -> bool
+where
+    F: FnOnce(&mut LoopExitFinder),
+{
+    let mut finder = LoopExitFinder {
+        label,
+        depth: 0,
+        found: false,
+    };
+    walk(&mut finder);
+    finder.found
+}
+
+struct LoopExitFinder {
+    label: Option<Label>,
+    /// Number of loops and closures we are inside of, which unlabeled `break`s and `continue`s
+    /// refer to instead
+    depth: usize,
+    found: bool,
+}
+
+impl<'ast> Visitor<'ast> for LoopExitFinder {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        match e.kind {
+            ExprKind::Break(target, _) | ExprKind::Continue(target) => {
+                if targets_loop(target, self.label, self.depth) {
+                    self.found = true;
+                }
+            }
+            ExprKind::Loop(..)
+            | ExprKind::While(..)
+            | ExprKind::ForLoop(..)
+            | ExprKind::Closure(..) => {
+                self.depth += 1;
+                visit::walk_expr(self, e);
+                self.depth -= 1;
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, e);
+    }
+
+    fn visit_mac(&mut self, _mac: &'ast Mac) {}
+}
+
+
+/// # `ternary_chain_to_match` Command
+///
+/// Usage: `ternary_chain_to_match`
+///
+/// Replaces chains of `if`/`else` expressions that compare the same value against a different
+/// constant in each condition with a `match`.  Nested ternaries like
+/// `op == 0 ? a : op == 1 ? b : c` translate to `if op == 0 { a } else if op == 1 { b } else
+/// { c }`, which becomes `match op { 0 => a, 1 => b, _ => c, }`.
+///
+/// A chain is only rewritten if it has at least two conditions and ends in an `else`, and every
+/// condition is `scrutinee == constant` or `constant == scrutinee` for the same integer, `char`
+/// or `bool` scrutinee without side effects.  Constants are literals, literals cast to the
+/// scrutinee type that fit in it, and paths to `const` items.
+pub struct TernaryChainToMatch;
+
+impl Transform for TernaryChainToMatch {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        // Only rewrite whole chains, not the `else if`s in the middle of one
+        let mut else_ifs = HashSet::new();
+        visit_nodes(krate, |e: &Expr| {
+            if let ExprKind::If(_, _, Some(ref els)) = e.kind {
+                if let Some(next) = else_if(els) {
+                    else_ifs.insert(next.id);
+                }
+            }
+        });
+
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            if else_ifs.contains(&e.id) {
+                return;
+            }
+            if let Some(kind) = chain_to_match(cx, e) {
+                e.kind = kind;
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// Build a `match` from the chain of `if`s starting at `e`.
+fn chain_to_match(cx: &RefactorCtxt, e: &Expr) -> Option<ExprKind> {
+    let mut scrutinee: Option<&Expr> = None;
+    let mut arms = vec![];
+    // The printed patterns, since a constant compared twice would make an unreachable arm
+    let mut seen = HashSet::new();
+    let mut cur = e;
+    loop {
+        let (cond, then, els) = match cur.kind {
+            ExprKind::If(ref cond, ref then, Some(ref els)) => (cond, then, els),
+            _ => return None,
+        };
+        let (lhs, rhs) = match strip_parens(cond).kind {
+            ExprKind::Binary(op, ref lhs, ref rhs) if op.node == BinOpKind::Eq => {
+                (strip_parens(lhs), strip_parens(rhs))
+            }
+            _ => return None,
+        };
+        let same_scrutinee = |e: &Expr| scrutinee.map_or(true, |s| s.ast_equiv(e));
+        let (scrut, pat) = match const_pattern(cx, rhs) {
+            Some(pat) if same_scrutinee(lhs) => (lhs, pat),
+            _ => match const_pattern(cx, lhs) {
+                Some(pat) if same_scrutinee(rhs) => (rhs, pat),
+                _ => return None,
+            },
+        };
+        if scrutinee.is_none() {
+            if !is_pure(scrut) || !is_matchable(cx, scrut) {
+                return None;
+            }
+            scrutinee = Some(scrut);
+        }
+        if !seen.insert(pprust::pat_to_string(&pat)) {
+            return None;
+        }
+        arms.push(mk().arm(pat, None, block_value(then)));
+
+        match else_if(els) {
+            Some(next) => cur = next,
+            None => break,
+        }
+    }
+    if arms.len() < 2 {
+        return None;
+    }
+
+    let els = match cur.kind {
+        ExprKind::If(_, _, Some(ref els)) => els,
+        _ => unreachable!(),
+    };
+    let default = match els.kind {
+        ExprKind::Block(ref block, None) if block.rules == BlockCheckMode::Default => {
+            block_value(block)
+        }
+        _ => els.clone(),
+    };
+    arms.push(mk().arm(mk().wild_pat(), None, default));
+    Some(ExprKind::Match(P(scrutinee?.clone()), arms))
+}
+
+/// Get the `if` in the `else` branch `els`, if it's an `else if`.
+fn else_if(els: &Expr) -> Option<&Expr> {
+    match els.kind {
+        ExprKind::If(..) => Some(els),
+        ExprKind::Block(ref block, None) if block.rules == BlockCheckMode::Default => {
+            match block.stmts[..] {
+                [Stmt { kind: StmtKind::Expr(ref e), .. }] if is_if(e) => Some(e),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Get the expression for a match arm evaluating `block`, which is just its value if it has
+/// no statements.
+fn block_value(block: &P<Block>) -> P<Expr> {
+    match block.stmts[..] {
+        [Stmt { kind: StmtKind::Expr(ref e), .. }] => e.clone(),
+        _ => mk().block_expr(block.clone()),
+    }
+}
+
+/// Build the pattern for `e` if it's a constant that can be matched against.
+fn const_pattern(cx: &RefactorCtxt, e: &Expr) -> Option<P<Pat>> {
+    match e.kind {
+        ExprKind::Lit(ref lit) => match lit.kind {
+            LitKind::Int(..) | LitKind::Char(_) | LitKind::Byte(_) | LitKind::Bool(_) => {
+                Some(mk().lit_pat(P(e.clone())))
+            }
+            _ => None,
+        },
+        // Patterns can't have parentheses, so `-(1)` is matched as `-1`
+        ExprKind::Unary(UnOp::Neg, ref inner) => match strip_parens(inner).kind {
+            ExprKind::Lit(ref lit) if int_value(inner).is_some() => {
+                Some(mk().lit_pat(mk().unary_expr(UnOp::Neg, mk().lit_expr(lit.clone()))))
+            }
+            _ => None,
+        },
+        // `1 as libc::c_int`, which is matched as `1` if the cast doesn't change the value
+        ExprKind::Cast(ref inner, _) => {
+            let value = int_value(inner)?;
+            if !int_fits(cx, value, cx.opt_node_type(e.id)?) {
+                return None;
+            }
+            let lit = mk().lit_expr(mk().int_lit(value.wrapping_abs() as u128,
+                                                 LitIntType::Unsuffixed));
+            let lit = if value < 0 { mk().unary_expr(UnOp::Neg, lit) } else { lit };
+            Some(mk().lit_pat(lit))
+        }
+        ExprKind::Path(ref qself, ref path) => match cx.try_resolve_expr_hir(e) {
+            Some(Res::Def(DefKind::Const, _)) | Some(Res::Def(DefKind::AssocConst, _)) => {
+                Some(mk().qpath_pat(qself.clone(), path.clone()))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Get the value of an integer literal, possibly negated.
+fn int_value(e: &Expr) -> Option<i128> {
+    match e.kind {
+        ExprKind::Paren(ref inner) => int_value(inner),
+        ExprKind::Lit(ref lit) => match lit.kind {
+            LitKind::Int(i, _) if i <= i128::max_value() as u128 => Some(i as i128),
+            _ => None,
+        },
+        ExprKind::Unary(UnOp::Neg, ref inner) => int_value(inner).map(|i| -i),
+        _ => None,
+    }
+}
+
+/// Check if `value` is in the range of the integer type `ty`.
+fn int_fits(cx: &RefactorCtxt, value: i128, ty: ty::Ty) -> bool {
+    let ptr_bits = cx.ty_ctxt().data_layout.pointer_size.bits() as usize;
+    match ty.kind {
+        TyKind::Int(ity) => {
+            let bits = ity.bit_width().unwrap_or(ptr_bits);
+            bits >= 128 || (value >= -(1 << (bits - 1)) && value < 1 << (bits - 1))
+        }
+        TyKind::Uint(uty) => {
+            let bits = uty.bit_width().unwrap_or(ptr_bits);
+            value >= 0 && (bits >= 128 || value < 1 << bits)
+        }
+        _ => false,
+    }
+}
+
+/// Check if `e` can be evaluated once instead of in each condition.
+fn is_pure(e: &Expr) -> bool {
+    match e.kind {
+        ExprKind::Path(..) | ExprKind::Lit(..) => true,
+        ExprKind::Paren(ref e)
+        | ExprKind::Field(ref e, _)
+        | ExprKind::Cast(ref e, _)
+        | ExprKind::Unary(_, ref e) => is_pure(e),
+        ExprKind::Index(ref base, ref index) => is_pure(base) && is_pure(index),
+        _ => false,
+    }
+}
+
+/// Check if `e` has a type whose `==` is the same as matching against constants.
+fn is_matchable(cx: &RefactorCtxt, e: &Expr) -> bool {
+    cx.opt_node_type(e.id)
+        .map_or(false, |ty| ty.is_integral() || ty.is_char() || ty.is_bool())
+}
+
+fn strip_parens(e: &Expr) -> &Expr {
+    match e.kind {
+        ExprKind::Paren(ref inner) => strip_parens(inner),
+        _ => e,
+    }
+}
+
+
 pub fn register_commands(reg: &mut Registry) {
     use super::mk;
 
     reg.register("reconstruct_while", |_args| mk(ReconstructWhile));
     reg.register("reconstruct_for_range", |_args| mk(ReconstructForRange));
     reg.register("remove_unused_labels", |_args| mk(RemoveUnusedLabels));
+    reg.register("destructure_loops", |_args| mk(DestructureLoops));
+    reg.register("ternary_chain_to_match", |_args| mk(TernaryChainToMatch));
 }
@@ -0,0 +1,471 @@
+use rustc::hir::def_id::DefId;
+use syntax::ast::*;
+use syntax::print::pprust;
+use syntax::ptr::P;
+use syntax::source_map::DUMMY_SP;
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{AstEquiv, fold_modules, visit_nodes};
+use crate::command::{CommandState, Registry};
+use crate::driver::{Phase, parse_items, parse_pat};
+use crate::transform::util::strip_casts;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `tag_dispatch_to_trait` Command
+///
+/// Usage: `tag_dispatch_to_trait TRAIT VARIANT=TAG...`
+///
+/// Marks: `target`
+///
+/// Replace the `match`es on the tag of a C-style tagged union, the struct field marked `target`,
+/// with dynamic dispatch through a new trait `TRAIT`.  Each `VARIANT=TAG` argument introduces a
+/// unit struct `VARIANT` implementing `TRAIT` for the tag value `TAG`, written as a pattern the
+/// way the `match`es spell it, like `0` or `CIRCLE`.
+///
+/// Every function in the module of the struct whose body starts with a `match` on the tag of
+/// one of its arguments, like `match (*s).kind as libc::c_uint { ... }`, is an operation, which
+/// becomes a method of `TRAIT` with the arguments of the function plus `&self`:
+///
+///  * the `_` arm, followed by the statements after the `match`, is the provided method of the
+///    trait, used by the variants without an arm and by all other tag values,
+///  * each other arm, followed by the statements after the `match` unless it `return`s, is the
+///    method of the impls of the variants its patterns name, and
+///  * the body of the function looks up the trait object for the tag with the function named
+///    after the trait, `trait_name(tag)`, and calls the method on it.
+///
+/// The new items are added after the struct.  Functions whose `match` has guards, patterns
+/// naming no variant, or no `_` arm are reported and left alone, as are generic functions and
+/// those with arguments other than plain identifiers.
+///
+/// Example:
+///
+/// ```ignore
+///     pub struct shape {
+///         pub kind: libc::c_uint,     // Marked `target`
+///         pub a: libc::c_double,
+///     }
+///     pub unsafe fn area(mut s: *const shape) -> libc::c_double {
+///         match (*s).kind {
+///             0 => return (*s).a * (*s).a,
+///             _ => {}
+///         }
+///         return 0.0;
+///     }
+/// ```
+///
+/// After running `tag_dispatch_to_trait ShapeOps Square=0`:
+///
+/// ```ignore
+///     pub struct shape { ... }
+///     pub trait ShapeOps {
+///         unsafe fn area(&self, mut s: *const shape) -> libc::c_double {
+///             return 0.0;
+///         }
+///     }
+///     pub struct Square;
+///     impl ShapeOps for Square {
+///         unsafe fn area(&self, mut s: *const shape) -> libc::c_double {
+///             return (*s).a * (*s).a
+///         }
+///     }
+///     pub struct ShapeOpsDefault;
+///     impl ShapeOps for ShapeOpsDefault {}
+///     pub fn shape_ops(tag: libc::c_uint) -> &'static dyn ShapeOps {
+///         match tag {
+///             0 => &Square,
+///             _ => &ShapeOpsDefault,
+///         }
+///     }
+///     pub unsafe fn area(mut s: *const shape) -> libc::c_double {
+///         shape_ops((*s).kind).area(s)
+///     }
+/// ```
+pub struct TagDispatchToTrait {
+    trait_name: String,
+    variants: Vec<(String, String)>,
+}
+
+/// The tag field marked `target`.
+struct Tag {
+    struct_id: DefId,
+    struct_item: NodeId,
+    field: Ident,
+    ty: P<Ty>,
+}
+
+/// A function dispatching on the tag, and the bodies its method gets.
+struct Operation {
+    name: Ident,
+    decl: P<FnDecl>,
+    unsafety: Unsafety,
+    /// The method body of each variant with an arm, by index into the variants
+    arms: Vec<Option<P<Block>>>,
+    /// The provided method body, from the `_` arm
+    default: P<Block>,
+}
+
+impl Transform for TagDispatchToTrait {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        let mut tag = None;
+        visit_nodes(krate, |i: &Item| {
+            if let ItemKind::Struct(VariantData::Struct(ref fields, _), _) = i.kind {
+                for f in fields {
+                    if st.marked(f.id, "target") && tag.is_none() {
+                        tag = Some(Tag {
+                            struct_id: cx.node_def_id(i.id),
+                            struct_item: i.id,
+                            field: f.ident.expect("tag field has no name"),
+                            ty: f.ty.clone(),
+                        });
+                    }
+                }
+            }
+        });
+        let tag = tag.expect("tag_dispatch_to_trait: no struct field is marked `target`");
+
+        let tag_pats = self.variants.iter()
+            .map(|(_, tag)| parse_pat(cx.session(), tag))
+            .collect::<Vec<_>>();
+        let lookup = snake_case(&self.trait_name);
+
+        fold_modules(krate, |curs| {
+            let start = curs.mark();
+            curs.advance_until(|i| i.id == tag.struct_item);
+            if curs.eof() {
+                return;
+            }
+            curs.advance();
+            let after_struct = curs.mark();
+            curs.seek(start);
+
+            let mut ops = vec![];
+            while !curs.eof() {
+                let op = match operation(cx, &tag, &tag_pats, curs.next()) {
+                    Some(Ok(op)) => op,
+                    Some(Err(reason)) => {
+                        eprintln!("tag_dispatch_to_trait: {}: {}", curs.next().ident, reason);
+                        curs.advance();
+                        continue;
+                    }
+                    None => {
+                        curs.advance();
+                        continue;
+                    }
+                };
+                curs.replace(|i| i.map(|mut i| {
+                    if let ItemKind::Fn(ref decl, _, _, ref mut block) = i.kind {
+                        *block = dispatch_body(&lookup, decl, &op.name, &*block);
+                    }
+                    i
+                }));
+                curs.advance();
+                ops.push(op);
+            }
+            if ops.is_empty() {
+                return;
+            }
+
+            curs.seek(after_struct);
+            curs.insert_multi(self.build_items(cx, &tag, &tag_pats, &lookup, ops));
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+impl TagDispatchToTrait {
+    /// Build the trait, the variant structs and impls, and the lookup function.
+    fn build_items(
+        &self,
+        cx: &RefactorCtxt,
+        tag: &Tag,
+        tag_pats: &[P<Pat>],
+        lookup: &str,
+        ops: Vec<Operation>,
+    ) -> Vec<P<Item>> {
+        let trait_name = &self.trait_name;
+        let default_name = format!("{}Default", trait_name);
+        let mut items = parse_items(cx.session(), &format!("pub trait {} {{}}", trait_name));
+        items[0] = items[0].clone().map(|mut i| {
+            if let ItemKind::Trait(_, _, _, _, ref mut trait_items) = i.kind {
+                for op in &ops {
+                    let sig = method_sig(op);
+                    trait_items.push(TraitItem {
+                        id: DUMMY_NODE_ID,
+                        ident: op.name,
+                        attrs: vec![],
+                        generics: Generics::default(),
+                        kind: TraitItemKind::Method(sig, Some(op.default.clone())),
+                        span: DUMMY_SP,
+                        tokens: None,
+                    });
+                }
+            }
+            i
+        });
+
+        let impl_names = self.variants.iter().map(|(name, _)| name).chain(Some(&default_name));
+        for (idx, name) in impl_names.enumerate() {
+            items.extend(parse_items(cx.session(), &format!("pub struct {};", name)));
+            let methods = ops.iter().filter_map(|op| {
+                let block = op.arms.get(idx)?.clone()?;
+                let sig = method_sig(op);
+                Some(mk().unsafety(sig.header.unsafety).method_impl_item(op.name, sig.decl, block))
+            }).collect();
+            items.push(mk().impl_trait_item(vec![trait_name], mk().ident_ty(name), methods));
+        }
+
+        let mut arms = self.variants.iter().zip(tag_pats)
+            .map(|((name, _), pat)| format!("{} => &{},", pprust::pat_to_string(pat), name))
+            .collect::<Vec<_>>();
+        arms.push(format!("_ => &{},", default_name));
+        items.extend(parse_items(cx.session(), &format!(
+            "pub fn {}(tag: {}) -> &'static dyn {} {{ match tag {{ {} }} }}",
+            lookup,
+            pprust::ty_to_string(&tag.ty),
+            trait_name,
+            arms.join(" "),
+        )));
+        items
+    }
+}
+
+/// If `i` is a function whose body starts with a `match` on the tag of one of its arguments,
+/// split the `match` into the method bodies of an operation, or explain why it can't be.
+fn operation(
+    cx: &RefactorCtxt,
+    tag: &Tag,
+    tag_pats: &[P<Pat>],
+    i: &Item,
+) -> Option<Result<Operation, String>> {
+    let (decl, header, generics, block) = match i.kind {
+        ItemKind::Fn(ref decl, ref header, ref generics, ref block) => {
+            (decl, header, generics, block)
+        }
+        _ => return None,
+    };
+    let (first, rest) = block.stmts.split_first()?;
+    let (scrutinee, arms) = match first.kind {
+        StmtKind::Expr(ref e) | StmtKind::Semi(ref e) => match e.kind {
+            ExprKind::Match(ref scrutinee, ref arms) => (scrutinee, arms),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if !is_tag(cx, tag, scrutinee) {
+        return None;
+    }
+
+    if !generics.params.is_empty() {
+        return Some(Err("generic functions are not supported".to_owned()));
+    }
+    if decl.inputs.iter().any(|arg| param_ident(arg).is_none()) {
+        return Some(Err("arguments must be plain identifiers".to_owned()));
+    }
+
+    // A `match` that is the whole body gives the value of the function, and other `match`es
+    // are followed by the rest of the body
+    let is_tail = rest.is_empty() && match first.kind {
+        StmtKind::Expr(_) => true,
+        _ => false,
+    };
+    let method_body = |body: &P<Expr>| {
+        if is_tail {
+            expr_block(body)
+        } else {
+            followed_by(expr_block(body), rest)
+        }
+    };
+
+    let mut op_arms = vec![None; tag_pats.len()];
+    let mut default = None;
+    for arm in arms {
+        if arm.guard.is_some() {
+            return Some(Err("`match` arms with guards are not supported".to_owned()));
+        }
+        let pats = match arm.pat.kind {
+            PatKind::Or(ref pats) => pats.iter().collect::<Vec<_>>(),
+            _ => vec![&arm.pat],
+        };
+        for pat in pats {
+            if let PatKind::Wild = pat.kind {
+                default.get_or_insert_with(|| method_body(&arm.body));
+                continue;
+            }
+            let idx = match tag_pats.iter().position(|tag_pat| tag_pat.ast_equiv(pat)) {
+                Some(idx) => idx,
+                None => return Some(Err(format!(
+                    "pattern `{}` names no variant", pprust::pat_to_string(pat)))),
+            };
+            if op_arms[idx].is_none() {
+                op_arms[idx] = Some(method_body(&arm.body));
+            }
+        }
+    }
+    let default = match default {
+        Some(default) => default,
+        None => return Some(Err("the `match` has no `_` arm".to_owned())),
+    };
+
+    Some(Ok(Operation {
+        name: i.ident,
+        decl: decl.clone(),
+        unsafety: header.unsafety,
+        arms: op_arms,
+        default,
+    }))
+}
+
+/// Check if `e`, without casts, reads the tag field of a struct or a reference to one.
+fn is_tag(cx: &RefactorCtxt, tag: &Tag, e: &Expr) -> bool {
+    let (base, field) = match strip_casts(e).kind {
+        ExprKind::Field(ref base, field) => (base, field),
+        _ => return false,
+    };
+    if field.name != tag.field.name {
+        return false;
+    }
+    // Parentheses have no type of their own
+    let mut base = &**base;
+    while let ExprKind::Paren(ref inner) = base.kind {
+        base = inner;
+    }
+    let mut ty = match cx.opt_node_type(base.id) {
+        Some(ty) => ty,
+        None => return false,
+    };
+    while let Some(inner) = ty.builtin_deref(false) {
+        ty = inner.ty;
+    }
+    ty.ty_adt_def().map_or(false, |adt| adt.did == tag.struct_id)
+}
+
+/// The body of an operation after the conversion: `lookup(tag).method(args...)`.
+fn dispatch_body(lookup: &str, decl: &FnDecl, name: &Ident, block: &Block) -> P<Block> {
+    let scrutinee = match block.stmts[0].kind {
+        StmtKind::Expr(ref e) | StmtKind::Semi(ref e) => match e.kind {
+            ExprKind::Match(ref scrutinee, _) => strip_casts(scrutinee).clone(),
+            _ => unreachable!("operation doesn't start with a `match`"),
+        },
+        _ => unreachable!("operation doesn't start with a `match`"),
+    };
+    let args = decl.inputs.iter()
+        .map(|arg| mk().ident_expr(param_ident(arg).unwrap()))
+        .collect();
+    let table = mk().call_expr(mk().ident_expr(lookup), vec![P(scrutinee)]);
+    let call = mk().method_call_expr(table, name, args);
+    mk().block(vec![mk().expr_stmt(call)])
+}
+
+/// The signature of the method of an operation, which takes `&self` first.
+fn method_sig(op: &Operation) -> MethodSig {
+    let mut inputs = vec![mk().self_arg(SelfKind::Region(None, Mutability::Immutable))];
+    inputs.extend(op.decl.inputs.iter().cloned());
+    MethodSig {
+        header: FnHeader {
+            unsafety: op.unsafety,
+            ..FnHeader::default()
+        },
+        decl: P(FnDecl {
+            inputs,
+            output: op.decl.output.clone(),
+        }),
+    }
+}
+
+fn param_ident(arg: &Param) -> Option<Ident> {
+    match arg.pat.kind {
+        PatKind::Ident(_, ident, None) => Some(ident),
+        _ => None,
+    }
+}
+
+/// Turn the body of a `match` arm into a block.
+fn expr_block(e: &P<Expr>) -> P<Block> {
+    match e.kind {
+        ExprKind::Block(ref b, None) if b.rules == BlockCheckMode::Default => b.clone(),
+        _ => mk().block(vec![mk().expr_stmt(e.clone())]),
+    }
+}
+
+/// Append the statements following a `match` to the block of one of its arms, unless the arm
+/// returns.
+fn followed_by(block: P<Block>, rest: &[Stmt]) -> P<Block> {
+    let returns = match block.stmts.last().map(|s| &s.kind) {
+        Some(StmtKind::Expr(e)) | Some(StmtKind::Semi(e)) => match e.kind {
+            ExprKind::Ret(_) => true,
+            _ => false,
+        },
+        _ => false,
+    };
+    if returns || rest.is_empty() {
+        return block;
+    }
+
+    // Keep the locals of the arm from shadowing the names used after the `match`
+    let declares_locals = block.stmts.iter().any(|s| match s.kind {
+        StmtKind::Local(_) => true,
+        _ => false,
+    });
+    let mut stmts = if declares_locals {
+        vec![mk().expr_stmt(mk().block_expr(block))]
+    } else {
+        block.into_inner().stmts
+    };
+    if let Some(last) = stmts.last_mut() {
+        if let StmtKind::Expr(ref e) = last.kind {
+            if !is_block_like(e) {
+                last.kind = StmtKind::Semi(e.clone());
+            }
+        }
+    }
+    stmts.extend(rest.iter().cloned());
+    mk().block(stmts)
+}
+
+/// Check if `e` can be a statement without a semicolon.
+fn is_block_like(e: &Expr) -> bool {
+    match e.kind {
+        ExprKind::If(..) | ExprKind::Match(..) | ExprKind::Block(..) | ExprKind::While(..) |
+        ExprKind::Loop(..) | ExprKind::ForLoop(..) | ExprKind::TryBlock(..) => true,
+        _ => false,
+    }
+}
+
+/// `ShapeOps` -> `shape_ops`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("tag_dispatch_to_trait", |args| mk(TagDispatchToTrait {
+        trait_name: args[0].clone(),
+        variants: args[1..].iter().map(|arg| {
+            let mut parts = arg.splitn(2, '=');
+            let name = parts.next().unwrap().to_owned();
+            let tag = parts.next()
+                .unwrap_or_else(|| panic!("tag_dispatch_to_trait: expected VARIANT=TAG, not `{}`",
+                                          arg))
+                .to_owned();
+            (name, tag)
+        }).collect(),
+    }));
+}
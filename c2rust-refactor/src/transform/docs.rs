@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use regex::Regex;
+use smallvec::SmallVec;
+use syntax::attr;
+use syntax::ast::*;
+use syntax::mut_visit::{self, MutVisitor};
+use syntax::ptr::P;
+use syntax::source_map::DUMMY_SP;
+use syntax::symbol::{sym, Symbol};
+
+use crate::ast_manip::MutVisit;
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `doc_from_c_comments` Command
+///
+/// Usage: `doc_from_c_comments [--c-src-root=]DIR`
+///
+/// Copy the documentation of translated C functions into Rust doc comments.  The C definition
+/// of each function is found using either a leading `// file.c:LINE` breadcrumb comment, or a
+/// `#[src_loc = "LINE:COL"]` attribute inside a module carrying a
+/// `#[header_src = "file.c:LINE"]` attribute.  Relative C paths are resolved against `DIR`.
+///
+/// The doc-style comment (`/** ... */` or a run of `///` lines) right before the C definition
+/// becomes a `///` comment on the Rust function.  Paragraph breaks are preserved, and Doxygen
+/// tags such as `@param` and `@return` are turned into a Markdown list.  Functions that already
+/// have doc comments are left alone, so running the command again has no further effect.
+///
+/// A warning is printed for each function whose C file can't be read or whose location is
+/// outside of that file.
+pub struct DocFromCComments {
+    c_src_root: PathBuf,
+}
+
+impl Transform for DocFromCComments {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        struct DocFolder<'a, 'b, 'tcx> {
+            cx: &'a RefactorCtxt<'b, 'tcx>,
+            c_src_root: &'a Path,
+            breadcrumb: Regex,
+            /// C files named by the `header_src` attributes of the enclosing modules
+            header_files: Vec<String>,
+            sources: HashMap<PathBuf, Option<Vec<String>>>,
+        }
+
+        impl<'a, 'b, 'tcx> DocFolder<'a, 'b, 'tcx> {
+            /// Find the C file and line that `i` was translated from.
+            fn c_location(&self, i: &Item) -> Option<(String, usize)> {
+                if let Some((file, line)) = self.breadcrumb_location(i) {
+                    return Some((file, line));
+                }
+
+                let src_loc = i.attrs.iter().find(|attr| is_src_loc_attr(attr))?;
+                let line = src_loc.value_str()?.as_str().split(':').next()?.parse().ok()?;
+                match self.header_files.last() {
+                    Some(file) => Some((file.clone(), line)),
+                    None => {
+                        warn!("{}: no C file is known for its src_loc attribute", i.ident);
+                        None
+                    }
+                }
+            }
+
+            /// Parse a `// file.c:LINE` comment on the line before `i` and its attributes.
+            fn breadcrumb_location(&self, i: &Item) -> Option<(String, usize)> {
+                let lo = i.attrs.iter()
+                    .map(|attr| attr.span.lo())
+                    .chain(Some(i.span.lo()))
+                    .min()?;
+                let loc = self.cx.session().source_map().lookup_char_pos(lo);
+                // `loc.line` is 1-based, so this is the index of the line before the item
+                let prev_line = loc.line.checked_sub(2)?;
+                let text = loc.file.get_line(prev_line)?;
+                let caps = self.breadcrumb.captures(&text)?;
+                Some((caps[1].to_owned(), caps[2].parse().ok()?))
+            }
+
+            fn c_source(&mut self, file: &str) -> Option<&Vec<String>> {
+                let path = resolve_c_path(self.c_src_root, file);
+                self.sources.entry(path.clone())
+                    .or_insert_with(|| {
+                        fs::read_to_string(&path).ok()
+                            .map(|src| src.lines().map(|l| l.to_owned()).collect())
+                    })
+                    .as_ref()
+            }
+
+            fn add_docs(&mut self, i: &mut Item) {
+                if i.attrs.iter().any(|attr| attr.is_sugared_doc || attr.check_name(sym::doc)) {
+                    return;
+                }
+                let (file, line) = match self.c_location(i) {
+                    Some(x) => x,
+                    None => return,
+                };
+                let ident = i.ident;
+                let lines = match self.c_source(&file) {
+                    Some(lines) => lines,
+                    None => {
+                        warn!("{}: could not read C file {}", ident, file);
+                        return;
+                    }
+                };
+                if line == 0 || line > lines.len() {
+                    warn!("{}: line {} is outside of C file {}", ident, line, file);
+                    return;
+                }
+
+                let docs = match extract_doc_comment(lines, line) {
+                    Some(comment) => format_doc(&comment),
+                    None => return,
+                };
+                if docs.is_empty() {
+                    return;
+                }
+
+                let mut attrs = docs.into_iter().map(|line| {
+                    let text = if line.is_empty() {
+                        "///".to_owned()
+                    } else {
+                        format!("/// {}", line)
+                    };
+                    attr::mk_sugared_doc_attr(Symbol::intern(&text), DUMMY_SP)
+                }).collect::<Vec<_>>();
+                attrs.append(&mut i.attrs);
+                i.attrs = attrs;
+            }
+        }
+
+        impl<'a, 'b, 'tcx> MutVisitor for DocFolder<'a, 'b, 'tcx> {
+            fn flat_map_item(&mut self, mut i: P<Item>) -> SmallVec<[P<Item>; 1]> {
+                let header_file = match i.kind {
+                    ItemKind::Mod(_) => header_src_file(&i.attrs),
+                    _ => None,
+                };
+                let in_header = header_file.is_some();
+                if let Some(file) = header_file {
+                    self.header_files.push(file);
+                }
+
+                if let ItemKind::Fn(..) = i.kind {
+                    self.add_docs(&mut i);
+                }
+                let result = mut_visit::noop_flat_map_item(i, self);
+
+                if in_header {
+                    self.header_files.pop();
+                }
+                result
+            }
+        }
+
+        krate.visit(&mut DocFolder {
+            cx,
+            c_src_root: &self.c_src_root,
+            breadcrumb: Regex::new(r"^\s*//\s*(\S+\.[ch]):(\d+)\s*$").unwrap(),
+            header_files: Vec::new(),
+            sources: HashMap::new(),
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase1
+    }
+}
+
+fn is_src_loc_attr(attr: &Attribute) -> bool {
+    match &attr.path.to_string() as &str {
+        "src_loc" | "c2rust::src_loc" => true,
+        _ => false,
+    }
+}
+
+/// Get the file name from a `#[header_src = "file:LINE"]` attribute.
+fn header_src_file(attrs: &[Attribute]) -> Option<String> {
+    let value = attr::first_attr_value_str_by_name(attrs, Symbol::intern("header_src"))?;
+    let value = value.as_str();
+    let file = match value.rfind(':') {
+        Some(idx) => &value[..idx],
+        None => &*value,
+    };
+    Some(file.to_owned())
+}
+
+fn resolve_c_path(root: &Path, file: &str) -> PathBuf {
+    let path = Path::new(file);
+    if path.is_absolute() {
+        if path.exists() {
+            return path.to_owned();
+        }
+        // The file was translated somewhere else; look for it by name under the root instead
+        if let Some(name) = path.file_name() {
+            return root.join(name);
+        }
+    }
+    root.join(path)
+}
+
+/// Get the text of the doc comment just before line `decl_line` (1-based) of `lines`, with the
+/// comment markers removed.
+fn extract_doc_comment(lines: &[String], decl_line: usize) -> Option<Vec<String>> {
+    // Index of the line after the comment, skipping blank lines
+    let mut end = decl_line - 1;
+    while end > 0 && lines[end - 1].trim().is_empty() {
+        end -= 1;
+    }
+    let last = end.checked_sub(1)?;
+
+    if lines[last].trim_end().ends_with("*/") {
+        let mut first = last;
+        while !lines[first].contains("/*") {
+            first = first.checked_sub(1)?;
+        }
+        let start = lines[first].find("/*").unwrap();
+        let opener = &lines[first][start..];
+        if !(opener.starts_with("/**") || opener.starts_with("/*!")) || opener.starts_with("/**/") {
+            return None;
+        }
+
+        let mut text = Vec::new();
+        for (idx, line) in lines[first..=last].iter().enumerate() {
+            let mut line = line.as_str();
+            if idx == last - first {
+                line = line.trim_end();
+                line = line[..line.len() - 2].trim_end_matches('*');
+            }
+            if idx == 0 {
+                line = line.get(start + 3..).unwrap_or("");
+            } else {
+                line = line.trim_start().trim_start_matches('*');
+            }
+            text.push(strip_comment_space(line));
+        }
+        Some(text)
+    } else {
+        let is_doc_line = |l: &str| {
+            let l = l.trim_start();
+            (l.starts_with("///") && !l.starts_with("////")) || l.starts_with("//!")
+        };
+        let mut first = end;
+        while first > 0 && is_doc_line(&lines[first - 1]) {
+            first -= 1;
+        }
+        if first == end {
+            return None;
+        }
+        Some(lines[first..end].iter()
+            .map(|l| strip_comment_space(&l.trim_start()[3..]))
+            .collect())
+    }
+}
+
+/// Remove the space that usually follows a comment marker.
+fn strip_comment_space(line: &str) -> String {
+    let line = if line.starts_with(' ') { &line[1..] } else { line };
+    line.trim_end().to_owned()
+}
+
+/// Format the text of a C doc comment as Markdown.  Doxygen tags become list items.
+fn format_doc(comment: &[String]) -> Vec<String> {
+    let tag_re = Regex::new(r"^[@\\]([A-Za-z]+)(\[[^\]]*\])?\s*(.*)$").unwrap();
+
+    let mut docs: Vec<String> = Vec::new();
+    let mut in_list = false;
+    for line in comment {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            in_list = false;
+            if docs.last().map_or(false, |l| !l.is_empty()) {
+                docs.push(String::new());
+            }
+            continue;
+        }
+
+        if let Some(caps) = tag_re.captures(trimmed) {
+            let tag = caps[1].to_lowercase();
+            let rest = caps[3].trim();
+            let item = match tag.as_str() {
+                "brief" | "short" => {
+                    in_list = false;
+                    docs.push(rest.to_owned());
+                    continue;
+                }
+                "param" | "tparam" => {
+                    let mut parts = rest.splitn(2, char::is_whitespace);
+                    let name = parts.next().unwrap_or("");
+                    let desc = parts.next().unwrap_or("").trim();
+                    format!("* `{}`: {}", name, desc)
+                }
+                "retval" => {
+                    let mut parts = rest.splitn(2, char::is_whitespace);
+                    let value = parts.next().unwrap_or("");
+                    let desc = parts.next().unwrap_or("").trim();
+                    format!("* Returns `{}`: {}", value, desc)
+                }
+                "return" | "returns" | "result" => format!("* Returns: {}", rest),
+                _ => {
+                    let mut chars = tag.chars();
+                    let first = chars.next().unwrap().to_uppercase().collect::<String>();
+                    format!("* {}{}: {}", first, chars.as_str(), rest)
+                }
+            };
+            if !in_list && docs.last().map_or(false, |l| !l.is_empty()) {
+                // Markdown needs a blank line between a paragraph and a list
+                docs.push(String::new());
+            }
+            in_list = true;
+            docs.push(item.trim_end().to_owned());
+        } else if in_list {
+            // Continuation of the last list item
+            let last = docs.last_mut().unwrap();
+            last.push(' ');
+            last.push_str(trimmed);
+        } else {
+            docs.push(line.trim_end().to_owned());
+        }
+    }
+
+    while docs.last().map_or(false, |l| l.is_empty()) {
+        docs.pop();
+    }
+    docs
+}
+
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("doc_from_c_comments", |args| {
+        let root = args.get(0).map_or(".", |s| s as &str);
+        let root = if root.starts_with("--c-src-root=") {
+            &root["--c-src-root=".len()..]
+        } else {
+            root
+        };
+        mk(DocFromCComments {
+            c_src_root: PathBuf::from(root),
+        })
+    });
+}
@@ -1,25 +1,23 @@
 use std::collections::HashMap;
 use rustc::hir::def::DefKind;
 use rustc::hir::def_id::{DefId};
-use rustc::ty::{Instance, TyCtxt, TyKind, Ty};
+use rustc::ty::{Instance, TyCtxt};
 use syntax::ast::*;
-use syntax::ptr::P;
 
-use c2rust_ast_builder::mk;
 use crate::ast_manip::{MutVisitNodes, visit_nodes};
 use crate::command::{CommandState, Registry};
 use crate::driver::{Phase};
 use crate::path_edit::fold_resolved_paths_with_id;
-use crate::reflect;
 use crate::resolve;
 use crate::transform::Transform;
+use crate::transform::linkage::{check_abi, fix_abi_uses};
 use crate::RefactorCtxt;
 
 
 
 /// # `canonicalize_externs` Command
 /// 
-/// Usage: `canonicalize_externs MOD_PATH`
+/// Usage: `canonicalize_externs MOD_PATH [--force]`
 /// 
 /// Marks: `target`
 /// 
@@ -32,8 +30,14 @@ use crate::RefactorCtxt;
 /// with uses of the matching foreign item in `MOD_PATH`.  If a replacement item
 /// has a different type than the original, it also inserts the necessary casts at
 /// each use of the item.
+/// 
+/// Replacements are checked for ABI compatibility the same way as in `link_funcs`:
+/// castable differences get casts, suspicious ones are only replaced (using
+/// `transmute`) with `--force`, and incompatible ones are never replaced.  A report
+/// is logged for each symbol whose types differ.
 pub struct CanonicalizeExterns {
     path: String,
+    force: bool,
 }
 
 fn is_foreign_symbol(tcx: TyCtxt, did: DefId) -> bool {
@@ -93,69 +97,22 @@ impl Transform for CanonicalizeExterns {
         });
 
 
-        // Consult the types of the old and new externs to figure out where we'll need to add casts
-
-        /// In `ty_replace_map`, which types need replacing?
-        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
-        enum TyLoc {
-            /// The type of a function argument
-            Arg(usize),
-            /// The return type of a function
-            Ret,
-            /// The type of the whole item
-            Whole,
-        }
-        let mut ty_replace_map: HashMap<(DefId, TyLoc), (Ty, Ty)> = HashMap::new();
-
-        for (&old_did, &new_did) in &replace_map {
-            let old_ty = cx.def_type(old_did);
-            let new_ty = cx.def_type(new_did);
-
-            if !matches!([old_ty.kind] TyKind::FnDef(..)) {
-                // Non-fn items are easy to handle.
-                if old_ty != new_ty {
-                    ty_replace_map.insert((old_did, TyLoc::Whole), (old_ty, new_ty));
-                }
-                continue;
-            }
-
-            // This is a fn replacement.  Look up sigs and compare arg and return types.
-            let old_sig = tcx.fn_sig(old_did);
-            let new_sig = tcx.fn_sig(new_did);
-
-            macro_rules! bail {
-                ($msg:expr) => {{
-                    warn!(concat!("canonicalize_externs: {:?} -> {:?}: ", $msg, " - skipping"),
-                          old_did, new_did);
-                    continue;
-                }};
-            }
-
-            let (old_sig, new_sig) = match (old_sig.no_bound_vars(),
-                                            new_sig.no_bound_vars()) {
-                (Some(x), Some(y)) => (x, y),
-                _ => bail!("old or new sig had late-bound regions"),
-            };
-
-            if old_sig.inputs().len() != new_sig.inputs().len() {
-                bail!("old and new sig differ in arg count");
-            }
-
-            if old_sig.c_variadic != new_sig.c_variadic {
-                bail!("old and new sig differ in variadicness");
-            }
-
-            for (i, (&old_ty, &new_ty)) in old_sig.inputs().iter()
-                                                  .zip(new_sig.inputs().iter()).enumerate() {
-                if old_ty != new_ty {
-                    ty_replace_map.insert((old_did, TyLoc::Arg(i)), (old_ty, new_ty));
-                }
-            }
-
-            let old_ty = old_sig.output();
-            let new_ty = new_sig.output();
-            if old_ty != new_ty {
-                ty_replace_map.insert((old_did, TyLoc::Ret), (old_ty, new_ty));
+        // Consult the types of the old and new externs to figure out which ones are compatible,
+        // and where we'll need to add casts
+
+        let mut replacements = replace_map.iter().map(|(&old_did, &new_did)| {
+            let inst = Instance::new(old_did, tcx.intern_substs(&[]));
+            (tcx.symbol_name(inst).name.as_symbol(), old_did, new_did)
+        }).collect::<Vec<_>>();
+        replacements.sort_by_key(|&(sym, _, _)| sym.to_string());
+        let mut reports = HashMap::new();
+        for (sym, old_did, new_did) in replacements {
+            let report = check_abi(tcx, old_did, new_did);
+            report.log(tcx, "canonicalize_externs", sym, self.force);
+            if report.allows_linking(self.force) {
+                reports.insert(old_did, report);
+            } else {
+                replace_map.remove(&old_did);
             }
         }
 
@@ -165,12 +122,6 @@ impl Transform for CanonicalizeExterns {
             info!("   WITH {:?} ({:?})", v, cx.def_type(v));
         }
 
-        let mut stuff = ty_replace_map.iter().collect::<Vec<_>>();
-        stuff.sort_by_key(|&(&a, _)| a);
-        for (&(did, loc), &(old, new)) in stuff {
-            info!("TYPE CHANGE: {:?}  @{:?}:  {:?} -> {:?}", did, loc, old, new);
-        }
-
 
         // Replace uses of old externs with new ones
 
@@ -188,48 +139,8 @@ impl Transform for CanonicalizeExterns {
 
         // Add casts to rewritten calls and exprs
 
-        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
-            if let Some(&old_did) = path_ids.get(&e.id) {
-                // This whole expr was a reference to the old extern `old_did`.  See if we need a
-                // cast around the whole thing.  (This should only be true for statics.)
-                if let Some(&(old_ty, _new_ty)) = ty_replace_map.get(&(old_did, TyLoc::Whole)) {
-                    // The rewritten expr has type `new_ty`, but its context expects `old_ty`.
-                    *e = mk().cast_expr(e.clone(), reflect::reflect_tcx_ty(tcx, old_ty));
-                }
-            }
-
-            // TODO: handle assignments to replaced extern statics
-
-            let callee_old_did = match e.kind {
-                ExprKind::Call(ref f, _) => path_ids.get(&f.id),
-                _ => None,
-            };
-            if let Some(&old_did) = callee_old_did {
-                // This expr is a call to a rewritten extern fn.  Add casts around args and around
-                // the whole expression, as directed by `ty_replace_map`.
-                let arg_count = expect!([e.kind] ExprKind::Call(_, ref a) => a.len());
-                info!("rewriting call - e = {:?}", e);
-
-                for i in 0 .. arg_count {
-                    let k = (old_did, TyLoc::Arg(i));
-                    if let Some(&(_old_ty, new_ty)) = ty_replace_map.get(&k) {
-                        expect!([e.kind] ExprKind::Call(_, ref mut args) => {
-                            // The new fn requires `new_ty`, where the old one needed `old_ty`.
-                            let ty_ast = reflect::reflect_tcx_ty(tcx, new_ty);
-                            let new_arg = mk().cast_expr(&args[i], ty_ast);
-                            args[i] = new_arg;
-                        });
-                        info!("  arg {} - rewrote e = {:?}", i, e);
-                    }
-                }
-
-                if let Some(&(old_ty, _new_ty)) = ty_replace_map.get(&(old_did, TyLoc::Ret)) {
-                    // The new fn returns `new_ty`, where the old context requires `old_ty`.
-                    *e = mk().cast_expr(e.clone(), reflect::reflect_tcx_ty(tcx, old_ty));
-                    info!("  return - rewrote e = {:?}", e);
-                }
-            }
-        });
+        // TODO: handle assignments to replaced extern statics
+        fix_abi_uses(krate, tcx, &path_ids, &reports);
 
 
         // Remove the old externs
@@ -252,5 +163,6 @@ pub fn register_commands(reg: &mut Registry) {
     use super::mk;
     reg.register("canonicalize_externs", |args| mk(CanonicalizeExterns {
         path: args[0].clone(),
+        force: args[1..].iter().any(|a| a == "--force"),
     }));
 }
@@ -1,21 +1,28 @@
 use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use rustc::hir::{self, HirId};
+use rustc::hir::def::Res;
 use rustc::hir::def_id::DefId;
-use rustc::ty::TyKind;
+use rustc::ty::{self, TyKind};
 use rustc_target::spec::abi::Abi;
 use syntax::ast;
 use syntax::ast::*;
 use syntax::attr;
 use syntax::mut_visit::{self, MutVisitor};
+use syntax::print::pprust;
 use syntax::ptr::P;
-use syntax_pos::sym;
+use syntax::symbol::{kw, Symbol};
+use syntax::visit::{self, Visitor};
+use syntax_pos::{sym, Span, DUMMY_SP};
 use smallvec::SmallVec;
 
 use c2rust_ast_builder::{mk, IntoSymbol};
-use crate::ast_manip::{FlatMapNodes, MutVisitNodes, fold_modules, visit_nodes, MutVisit};
+use crate::ast_manip::{FlatMapNodes, MutVisitNodes, fold_modules, visit_nodes, MutVisit, Visit};
 use crate::command::{CommandState, Registry};
 use crate::driver::{Phase, parse_expr};
 use crate::matcher::{BindingType, MatchCtxt, Subst, mut_visit_match_with};
 use crate::path_edit::{fold_resolved_paths, fold_resolved_paths_with_id};
+use crate::reflect;
 use crate::transform::Transform;
 use crate::util::Lone;
 use crate::RefactorCtxt;
@@ -803,6 +810,1205 @@ impl Transform for Abstract {
 }
 
 
+/// # `inline_function` Command
+///
+/// Usage: `inline_function`
+///
+/// Marks: `target`
+///
+/// Inline each function marked `target` at its call sites throughout the crate.  The arguments
+/// are evaluated once each, in order, into fresh `let` bindings for the parameters, followed by
+/// the statements of the function body.  Parameters are renamed where their original names
+/// would capture a name used by a later argument or by the body, and paths in the body that the
+/// caller's locals could shadow (or that are relative to a different module) are made absolute.
+/// Once every use of a function has been inlined, the function is deleted, unless it is exported
+/// (`#[no_mangle]` or `#[export_name]`).
+///
+/// Functions that can `return` early (other than through a final `return` statement) or use `?`
+/// are not inlined, nor are functions with type parameters or non-identifier parameter patterns.
+/// Recursive calls and uses of a function other than as a callee, such as taking it as a
+/// function pointer, are left in place, and keep the function alive.  Every skipped function
+/// and call site is reported on stderr.
+///
+/// Example:
+///
+/// ```ignore
+///     unsafe fn get_flags(p: *mut foo) -> c_int {
+///         (*p).flags
+///     }
+///
+///     unsafe fn f(q: *mut foo) -> c_int {
+///         let flags = get_flags(q);
+///         flags + 1
+///     }
+/// ```
+///
+/// After running `inline_function` with `get_flags` marked `target`:
+///
+/// ```ignore
+///     unsafe fn f(q: *mut foo) -> c_int {
+///         let flags = {
+///             let p: *mut foo = q;
+///             (*p).flags
+///         };
+///         flags + 1
+///     }
+/// ```
+pub struct InlineFunction;
+
+/// A function marked for inlining.
+struct InlineTarget {
+    id: NodeId,
+    ident: Ident,
+    params: Vec<InlineParam>,
+    /// The function body, with any final `return` turned into a trailing expression.
+    body: P<Block>,
+    /// The module containing the function.
+    module: HirId,
+    exported: bool,
+}
+
+struct InlineParam {
+    ident: Ident,
+    hir_id: HirId,
+    mutbl: Mutability,
+    ty: P<Ty>,
+}
+
+impl Transform for InlineFunction {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        // (1) Collect the marked functions, skipping any we can't inline.
+
+        let mut targets = HashMap::new();
+        visit_nodes(&*krate, |i: &Item| {
+            if !st.marked(i.id, "target") {
+                return;
+            }
+            match inline_target(i, cx) {
+                Ok(Some(target)) => {
+                    targets.insert(cx.node_def_id(i.id), target);
+                }
+                Ok(None) => {}
+                Err(reason) => eprintln!("inline_function: {}: {}", i.ident, reason),
+            }
+        });
+        if targets.is_empty() {
+            return;
+        }
+
+
+        // (2) Find uses of the functions other than calls.  These can't be inlined, so the
+        // function has to stay.
+
+        let source_map = cx.session().source_map();
+        let mut callees = HashSet::new();
+        visit_nodes(&*krate, |e: &Expr| {
+            if let ExprKind::Call(ref callee, _) = e.kind {
+                callees.insert(callee.id);
+            }
+        });
+        let mut kept = HashSet::new();
+        visit_nodes(&*krate, |e: &Expr| {
+            if !matches!([e.kind] ExprKind::Path(..)) || callees.contains(&e.id) {
+                return;
+            }
+            let def_id = match_or!([cx.try_resolve_expr(e)] Some(x) => x; return);
+            if let Some(target) = targets.get(&def_id) {
+                eprintln!("inline_function: {}: used as a value at {}",
+                          target.ident, source_map.span_to_string(e.span));
+                kept.insert(def_id);
+            }
+        });
+
+
+        // (3) Replace the calls with the function bodies.
+
+        let mut folder = InlineFolder {
+            cx,
+            targets: &targets,
+            kept: &mut kept,
+            callers: Vec::new(),
+            module: None,
+            stmt_starts: HashSet::new(),
+            substitute_locals: false,
+        };
+        krate.visit(&mut folder);
+
+
+        // (4) Delete the functions that are no longer used.
+
+        let mut removed = HashSet::new();
+        for (def_id, target) in &targets {
+            if target.exported {
+                eprintln!("inline_function: {}: kept because it is exported", target.ident);
+            } else if !kept.contains(def_id) {
+                removed.insert(target.id);
+            }
+        }
+        FlatMapNodes::visit(krate, |i: P<Item>| {
+            if removed.contains(&i.id) {
+                smallvec![]
+            } else {
+                smallvec![i]
+            }
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// # `inline_single_use` Command
+///
+/// Usage: `inline_single_use`
+///
+/// Inline every function that is called from exactly one place in the crate at that call site,
+/// and delete it.  This cleans up the small helpers that translation creates for C macros and
+/// `static inline` functions.  Arguments are bound to fresh `let`s in order, as with
+/// `inline_function`, except for plain reads of an immutable local of a scalar type, which are
+/// used in place of the parameter.
+///
+/// Functions whose address is taken, recursive functions (including mutual recursion through
+/// other single-use functions), and exported functions (`#[no_mangle]`, `#[export_name]`, or
+/// `pub` in a library crate) are left alone, as are functions `inline_function` can't inline,
+/// which are reported on stderr.
+///
+/// Example:
+///
+/// ```ignore
+///     unsafe extern "C" fn is_full(b: *mut buf, extra: c_int) -> c_int {
+///         ((*b).len + extra >= (*b).cap) as c_int
+///     }
+///
+///     unsafe fn push(b: *mut buf, c: u8) {
+///         if is_full(b, 1) != 0 {
+///             grow(b);
+///         }
+///         // ...
+///     }
+/// ```
+///
+/// After running `inline_single_use`:
+///
+/// ```ignore
+///     unsafe fn push(b: *mut buf, c: u8) {
+///         if {
+///             let extra: c_int = 1;
+///             ((*b).len + extra >= (*b).cap) as c_int
+///         } != 0 {
+///             grow(b);
+///         }
+///         // ...
+///     }
+/// ```
+pub struct InlineSingleUse;
+
+impl Transform for InlineSingleUse {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        // (1) Find the callers of each function, and the functions used other than as a
+        // callee.
+
+        let mut callees = HashSet::new();
+        visit_nodes(&*krate, |e: &Expr| {
+            if let ExprKind::Call(ref callee, _) = e.kind {
+                callees.insert(callee.id);
+            }
+        });
+        let mut callers = HashMap::<DefId, Vec<DefId>>::new();
+        let mut address_taken = HashSet::new();
+        visit_nodes(&*krate, |e: &Expr| {
+            if !matches!([e.kind] ExprKind::Path(..)) {
+                return;
+            }
+            let def_id = match_or!([cx.try_resolve_expr(e)] Some(x) => x; return);
+            if callees.contains(&e.id) {
+                let hir_id = cx.hir_map().node_to_hir_id(e.id);
+                let caller = cx.hir_map().local_def_id(cx.hir_map().get_parent_item(hir_id));
+                callers.entry(def_id).or_insert_with(Vec::new).push(caller);
+            } else {
+                address_taken.insert(def_id);
+            }
+        });
+
+
+        // (2) Collect the functions with a single call site.
+
+        let library = !cx.is_executable();
+        let mut caller_of = HashMap::new();
+        let mut targets = HashMap::new();
+        visit_nodes(&*krate, |i: &Item| {
+            if !matches!([i.kind] ItemKind::Fn(..)) {
+                return;
+            }
+            let def_id = cx.node_def_id(i.id);
+            let caller = match callers.get(&def_id).map(|c| &c[..]) {
+                Some(&[caller]) if caller != def_id => caller,
+                _ => return,
+            };
+            let exported = attr::contains_name(&i.attrs, sym::no_mangle) ||
+                attr::contains_name(&i.attrs, sym::export_name) ||
+                (library && matches!([i.vis.node] VisibilityKind::Public));
+            if exported || address_taken.contains(&def_id) {
+                return;
+            }
+            match inline_target(i, cx) {
+                Ok(Some(target)) => {
+                    caller_of.insert(def_id, caller);
+                    targets.insert(def_id, target);
+                }
+                Ok(None) => {}
+                Err(reason) => eprintln!("inline_single_use: {}: {}", i.ident, reason),
+            }
+        });
+
+        // Each target has one caller, so a recursive target is one whose chain of callers
+        // leads back to it.
+        let recursive = targets.keys().filter(|&&def_id| {
+            let mut cur = caller_of[&def_id];
+            for _ in 0..caller_of.len() {
+                if cur == def_id {
+                    return true;
+                }
+                cur = match_or!([caller_of.get(&cur)] Some(&x) => x; return false);
+            }
+            false
+        }).cloned().collect::<Vec<_>>();
+        for def_id in recursive {
+            targets.remove(&def_id);
+        }
+        if targets.is_empty() {
+            return;
+        }
+
+
+        // (3) Replace the calls with the function bodies, and delete the functions.
+
+        let mut kept = HashSet::new();
+        let mut folder = InlineFolder {
+            cx,
+            targets: &targets,
+            kept: &mut kept,
+            callers: Vec::new(),
+            module: None,
+            stmt_starts: HashSet::new(),
+            substitute_locals: true,
+        };
+        krate.visit(&mut folder);
+
+        let removed = targets.iter()
+            .filter(|(def_id, _)| !kept.contains(def_id))
+            .map(|(_, target)| target.id)
+            .collect::<HashSet<_>>();
+        FlatMapNodes::visit(krate, |i: P<Item>| {
+            if removed.contains(&i.id) {
+                smallvec![]
+            } else {
+                smallvec![i]
+            }
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// Check whether the function `i` can be inlined, and collect the information needed to do so.
+/// Returns `Ok(None)` if `i` is not a function at all.
+fn inline_target(i: &Item, cx: &RefactorCtxt) -> Result<Option<InlineTarget>, String> {
+    let (decl, generics, block) = match i.kind {
+        ItemKind::Fn(ref decl, _, ref generics, ref block) => (decl, generics, block),
+        _ => return Ok(None),
+    };
+
+    let has_ty_params = generics.params.iter().any(|p| match p.kind {
+        GenericParamKind::Lifetime => false,
+        _ => true,
+    });
+    if has_ty_params {
+        return Err("functions with type parameters are not supported".to_owned());
+    }
+    if decl.c_variadic() {
+        return Err("variadic functions are not supported".to_owned());
+    }
+
+    let mut params = Vec::with_capacity(decl.inputs.len());
+    for arg in &decl.inputs {
+        match arg.pat.kind {
+            PatKind::Ident(BindingMode::ByValue(mutbl), ident, None) => params.push(InlineParam {
+                ident,
+                hir_id: cx.hir_map().node_to_hir_id(arg.pat.id),
+                mutbl,
+                ty: arg.ty.clone(),
+            }),
+            _ => return Err(format!(
+                "parameter pattern `{}` is not a plain identifier",
+                pprust::pat_to_string(&arg.pat))),
+        }
+    }
+
+    // A final `return` is the same as a trailing expression.
+    let mut body = block.clone();
+    let tail = body.stmts.last().and_then(|s| match s.kind {
+        StmtKind::Expr(ref e) | StmtKind::Semi(ref e) => match e.kind {
+            ExprKind::Ret(ref val) => Some(val.clone()),
+            _ => None,
+        },
+        _ => None,
+    });
+    if let Some(val) = tail {
+        body.stmts.pop();
+        if let Some(val) = val {
+            body.stmts.push(mk().expr_stmt(val));
+        }
+    }
+    if returns_early(&body) {
+        return Err("the body returns early".to_owned());
+    }
+
+    Ok(Some(InlineTarget {
+        id: i.id,
+        ident: i.ident,
+        params,
+        body,
+        module: cx.hir_map().get_module_parent_node(cx.hir_map().node_to_hir_id(i.id)),
+        exported: attr::contains_name(&i.attrs, sym::no_mangle) ||
+            attr::contains_name(&i.attrs, sym::export_name),
+    }))
+}
+
+/// Check whether `body` contains a `return` or `?` that leaves the enclosing function.
+fn returns_early(body: &Block) -> bool {
+    struct ReturnVisitor {
+        found: bool,
+    }
+
+    impl<'ast> Visitor<'ast> for ReturnVisitor {
+        fn visit_expr(&mut self, e: &'ast Expr) {
+            match e.kind {
+                ExprKind::Ret(_) | ExprKind::Try(_) => self.found = true,
+                // These have their own `return` target.
+                ExprKind::Closure(..) | ExprKind::Async(..) => {}
+                _ => visit::walk_expr(self, e),
+            }
+        }
+
+        fn visit_item(&mut self, _i: &'ast Item) {}
+
+        fn visit_mac(&mut self, mac: &'ast Mac) {
+            visit::walk_mac(self, mac);
+        }
+    }
+
+    let mut v = ReturnVisitor { found: false };
+    visit::walk_block(&mut v, body);
+    v.found
+}
+
+/// Collect the names bound by patterns in `target`.
+fn binding_names<T: Visit>(target: &T) -> HashSet<Symbol> {
+    let mut names = HashSet::new();
+    visit_nodes(target, |p: &Pat| {
+        if let PatKind::Ident(_, ident, _) = p.kind {
+            names.insert(ident.name);
+        }
+    });
+    names
+}
+
+/// Collect the names that a new binding inside `target` could capture: names bound by patterns,
+/// and single-segment paths in expressions, except those that resolve to one of `params`.
+fn capturable_names<T: Visit>(
+    target: &T,
+    params: &HashSet<HirId>,
+    cx: &RefactorCtxt,
+) -> HashSet<Symbol> {
+    let mut names = binding_names(target);
+    visit_nodes(target, |e: &Expr| {
+        let path = match_or!([e.kind] ExprKind::Path(None, ref path) => path; return);
+        if path.segments.len() != 1 {
+            return;
+        }
+        let is_param = cx.try_resolve_expr_to_hid(e).map_or(false, |id| params.contains(&id));
+        if !is_param {
+            names.insert(path.segments[0].ident.name);
+        }
+    });
+    names
+}
+
+/// The first operand of `e`, if `e` is an expression whose source text starts with that
+/// operand.
+fn leftmost_operand(e: &Expr) -> Option<&P<Expr>> {
+    match e.kind {
+        ExprKind::Binary(_, ref lhs, _) |
+        ExprKind::Assign(ref lhs, _) |
+        ExprKind::AssignOp(_, ref lhs, _) |
+        ExprKind::Cast(ref lhs, _) |
+        ExprKind::Type(ref lhs, _) |
+        ExprKind::Field(ref lhs, _) |
+        ExprKind::Index(ref lhs, _) |
+        ExprKind::Call(ref lhs, _) |
+        ExprKind::Try(ref lhs) |
+        ExprKind::Range(Some(ref lhs), _, _) => Some(lhs),
+        ExprKind::MethodCall(_, ref args) => args.first(),
+        _ => None,
+    }
+}
+
+struct InlineFolder<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    targets: &'a HashMap<DefId, InlineTarget>,
+    /// Functions with uses that couldn't be inlined
+    kept: &'a mut HashSet<DefId>,
+    /// The enclosing functions, innermost last, along with the names bound in each.  This
+    /// includes functions whose bodies are being inlined.
+    callers: Vec<(DefId, HashSet<Symbol>)>,
+    /// The module of the call site the body being inlined will end up in, if any.
+    module: Option<HirId>,
+    /// Expressions at the start of an expression statement, but not the whole statement.  A
+    /// block in this position would end the statement, so it needs parentheses.
+    stmt_starts: HashSet<NodeId>,
+    /// Whether to use arguments that are plain reads of the caller's immutable locals in place
+    /// of their parameters, instead of binding them to new locals.
+    substitute_locals: bool,
+}
+
+impl<'a, 'tcx> InlineFolder<'a, 'tcx> {
+    /// Build the inlined version of a call to `target` with arguments `args`.
+    fn inline_call(&mut self, def_id: DefId, args: &[P<Expr>], module: HirId) -> P<Expr> {
+        let cx = self.cx;
+        let targets = self.targets;
+        let target = &targets[&def_id];
+
+        // Pick names for the parameters that won't capture anything.
+        let param_ids = target.params.iter().map(|p| p.hir_id).collect::<HashSet<_>>();
+        let mut taken = capturable_names(&*target.body, &param_ids, cx);
+        let mut renames = HashMap::new();
+
+        // Use the caller's locals directly for the parameters they're passed as, where allowed.
+        let mut substituted = HashSet::new();
+        if self.substitute_locals && self.module.is_none() {
+            let body_names = binding_names(&*target.body);
+            for (param, arg) in target.params.iter().zip(args) {
+                let name = match_or!([self.substitutable_local(param, arg)] Some(x) => x; continue);
+                if body_names.contains(&name) {
+                    continue;
+                }
+                taken.insert(name);
+                substituted.insert(param.hir_id);
+                renames.insert(param.hir_id, name.as_str().to_string());
+            }
+        }
+
+        let mut lets = Vec::with_capacity(args.len());
+        for (idx, (param, arg)) in target.params.iter().zip(args).enumerate() {
+            if substituted.contains(&param.hir_id) {
+                continue;
+            }
+            let mut later_names = HashSet::new();
+            for later in &args[idx + 1..] {
+                later_names.extend(capturable_names(&**later, &HashSet::new(), cx));
+            }
+            let base = param.ident.name.as_str().to_string();
+            let mut name = base.clone();
+            let mut suffix = 1;
+            while taken.contains(&name.clone().into_symbol()) ||
+                  later_names.contains(&name.clone().into_symbol()) {
+                name = format!("{}_{}", base, suffix);
+                suffix += 1;
+            }
+            taken.insert(name.clone().into_symbol());
+            if name != base {
+                renames.insert(param.hir_id, name.clone());
+            }
+
+            let mut ty = param.ty.clone();
+            self.fix_item_paths(&mut ty, target.module, module);
+            let pat = mk().set_mutbl(param.mutbl).ident_pat(name);
+            lets.push(mk().local_stmt(P(mk().local(pat, Some(ty), Some(arg.clone())))));
+        }
+
+        // Rename the parameters in the body, and fix up any paths that would no longer refer to
+        // the same item.
+        let mut body = target.body.clone();
+        fold_resolved_paths(&mut body, cx, |qself, path, res| {
+            if let Res::Local(hir_id) = *res {
+                if let Some(name) = renames.get(&hir_id) {
+                    return (None, mk().path(vec![name.as_str()]));
+                }
+            }
+            (qself, path)
+        });
+        self.fix_item_paths(&mut body, target.module, module);
+
+        // Inline calls in the body as well, to handle targets that call other targets.
+        let mut names = binding_names(&*body);
+        names.extend(taken);
+        self.callers.push((def_id, names));
+        let old_module = self.module.replace(module);
+        self.visit_block(&mut body);
+        self.module = old_module;
+        self.callers.pop();
+
+        let mut stmts = lets;
+        stmts.extend(body.stmts.iter().cloned());
+        mk().block_expr(mk().block(stmts))
+    }
+
+    /// The name of the caller's local that `arg` reads, if the inlined body can use it in place
+    /// of `param`: an immutable local of a `Copy` scalar type, the same as that of `param`, which
+    /// nothing can change while the body runs.
+    fn substitutable_local(&self, param: &InlineParam, arg: &Expr) -> Option<Symbol> {
+        let cx = self.cx;
+        if param.mutbl == Mutability::Mutable {
+            return None;
+        }
+        let path = match_or!([arg.kind] ExprKind::Path(None, ref path) => path; return None);
+        if path.segments.len() != 1 {
+            return None;
+        }
+        let hir_id = cx.try_resolve_expr_to_hid(arg)?;
+        let node = cx.hir_map().find(cx.hir_map().hir_to_node_id(hir_id));
+        let pat = match_or!([node] Some(hir::Node::Binding(pat)) => pat; return None);
+        if !matches!([pat.kind] hir::PatKind::Binding(hir::BindingAnnotation::Unannotated, ..)) {
+            return None;
+        }
+
+        let arg_ty = cx.opt_node_type(arg.id)?;
+        let param_ty = cx.opt_node_type(cx.hir_map().hir_to_node_id(param.hir_id))?;
+        if arg_ty != param_ty || !arg_ty.is_scalar() {
+            return None;
+        }
+        Some(path.segments[0].ident.name)
+    }
+
+    /// Make absolute the paths in `target` that may refer to a different item when moved from
+    /// `from_module` into a function in `to_module`: any relative path, if the modules differ,
+    /// and otherwise paths whose name is bound in an enclosing function.
+    fn fix_item_paths<T: MutVisit>(&self, target: &mut T, from_module: HirId, to_module: HirId) {
+        let cx = self.cx;
+        let shadowed = self.callers.iter()
+            .flat_map(|(_, names)| names.iter().copied())
+            .collect::<HashSet<_>>();
+        fold_resolved_paths(target, cx, |qself, path, res| {
+            let def_id = match *res {
+                Res::Def(_, def_id) if qself.is_none() => def_id,
+                _ => return (qself, path),
+            };
+            let relative = path.segments.first().map_or(false, |s| {
+                s.ident.name != kw::PathRoot && s.ident.name != kw::Crate
+            });
+            let moved = relative && from_module != to_module;
+            let captured = path.segments.len() == 1 && shadowed.contains(&path.segments[0].ident.name);
+            if !moved && !captured {
+                return (qself, path);
+            }
+
+            let mut new_path = cx.def_path(def_id);
+            if let (Some(old), Some(new)) = (path.segments.last(), new_path.segments.last_mut()) {
+                new.args = old.args.clone();
+            }
+            (qself, new_path)
+        });
+    }
+}
+
+impl<'a, 'tcx> MutVisitor for InlineFolder<'a, 'tcx> {
+    fn flat_map_item(&mut self, i: P<Item>) -> SmallVec<[P<Item>; 1]> {
+        if !matches!([i.kind] ItemKind::Fn(..)) {
+            return mut_visit::noop_flat_map_item(i, self);
+        }
+        self.callers.push((self.cx.node_def_id(i.id), binding_names(&*i)));
+        let r = mut_visit::noop_flat_map_item(i, self);
+        self.callers.pop();
+        r
+    }
+
+    fn flat_map_impl_item(&mut self, i: ImplItem) -> SmallVec<[ImplItem; 1]> {
+        self.callers.push((self.cx.node_def_id(i.id), binding_names(&i)));
+        let r = mut_visit::noop_flat_map_impl_item(i, self);
+        self.callers.pop();
+        r
+    }
+
+    fn flat_map_stmt(&mut self, s: Stmt) -> SmallVec<[Stmt; 1]> {
+        if let StmtKind::Expr(ref e) | StmtKind::Semi(ref e) = s.kind {
+            let mut cur = leftmost_operand(e);
+            while let Some(operand) = cur {
+                self.stmt_starts.insert(operand.id);
+                cur = leftmost_operand(operand);
+            }
+        }
+        mut_visit::noop_flat_map_stmt(s, self)
+    }
+
+    fn visit_expr(&mut self, e: &mut P<Expr>) {
+        mut_visit::noop_visit_expr(e, self);
+
+        let def_id = match e.kind {
+            ExprKind::Call(ref callee, _) => {
+                match_or!([self.cx.try_resolve_expr(callee)] Some(x) => x; return)
+            }
+            _ => return,
+        };
+        let targets = self.targets;
+        let target = match_or!([targets.get(&def_id)] Some(x) => x; return);
+
+        if self.callers.iter().any(|&(caller, _)| caller == def_id) {
+            eprintln!("inline_function: {}: recursive call at {} was not inlined",
+                      target.ident, self.cx.session().source_map().span_to_string(e.span));
+            self.kept.insert(def_id);
+            return;
+        }
+
+        let module = match self.module {
+            Some(module) => module,
+            None => {
+                let hir_id = self.cx.hir_map().node_to_hir_id(e.id);
+                self.cx.hir_map().get_module_parent_node(hir_id)
+            }
+        };
+        let args = expect!([e.kind] ExprKind::Call(_, ref args) => args.clone());
+        let mut new_e = self.inline_call(def_id, &args, module);
+        if self.stmt_starts.contains(&e.id) {
+            new_e = mk().paren_expr(new_e);
+        }
+        *e = new_e;
+    }
+}
+
+
+/// # `extract_function` Command
+///
+/// Usage: `extract_function NAME`
+///
+/// Marks: `target`
+///
+/// Move the statements marked `target`, which must be consecutive statements of a single block
+/// in a free function, into a new function `NAME` placed right after the one containing them,
+/// and replace them with a call to it.  This is the inverse of `inline_function`, for breaking
+/// up long translated functions.  The new function is `unsafe` if the original one is, or if the
+/// statements are inside an `unsafe` block.
+///
+/// Locals declared before the statements and used by them become parameters.  A local the
+/// statements assign to, or borrow mutably, is passed by `&mut` if it may be read afterwards
+/// (later in the function, or in the next iteration of an enclosing loop), and a non-`Copy`
+/// local that is still used afterwards is passed by `&`; all others are passed by value.
+/// Locals declared by the statements and used after them are returned, as a tuple if there are
+/// several, and bound again at the call site.  If the statements end with the value of their
+/// block, the new function returns that value instead.
+///
+/// Nothing is changed if the statements contain a `return` or `?`, or a `break` or `continue`
+/// leaving them (which is also how the `goto`s of translated code jump), if the function has
+/// type parameters, or if the type of a parameter or returned value can't be named.  The reason
+/// is reported on stderr, along with the location of the offending expression.
+///
+/// Example:
+///
+/// ```ignore
+///     unsafe fn sum_squares(p: *const i32, n: usize) -> i32 {
+///         let mut total = 0;
+///         let mut i = 0;
+///         while i < n {
+///             let x = *p.add(i);  // (marked)
+///             total += x * x;     // (marked)
+///             i += 1;
+///         }
+///         total
+///     }
+/// ```
+///
+/// After running `extract_function add_square`:
+///
+/// ```ignore
+///     unsafe fn sum_squares(p: *const i32, n: usize) -> i32 {
+///         let mut total = 0;
+///         let mut i = 0;
+///         while i < n {
+///             add_square(p, i, &mut total);
+///             i += 1;
+///         }
+///         total
+///     }
+///
+///     unsafe fn add_square(p: *const i32, i: usize, total: &mut i32) {
+///         let x = *p.add(i);
+///         *total += x * x;
+///     }
+/// ```
+pub struct ExtractFunction {
+    name: String,
+}
+
+/// The marked statements: `stmts[range]` of the block `block`.
+struct Region {
+    block: NodeId,
+    range: Range<usize>,
+    span: Span,
+    /// The spans of the loops enclosing the block.
+    loops: Vec<Span>,
+    /// Whether the block is inside an `unsafe` block.
+    in_unsafe: bool,
+}
+
+/// How a local used by the extracted statements is passed to the new function.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PassMode {
+    Value,
+    Ref,
+    RefMut,
+}
+
+impl Transform for ExtractFunction {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        let mut found = false;
+        FlatMapNodes::visit(krate, |i: P<Item>| {
+            if found || !matches!([i.kind] ItemKind::Fn(..)) {
+                return smallvec![i];
+            }
+            let result = match find_region(&i, st) {
+                Ok(Some(region)) => extract_region(&i, &region, &self.name, cx),
+                Ok(None) => return smallvec![i],
+                Err(reason) => Err(reason),
+            };
+            found = true;
+            match result {
+                Ok((caller, callee)) => smallvec![caller, callee],
+                Err(reason) => {
+                    eprintln!("extract_function: {}: {}", i.ident, reason);
+                    smallvec![i]
+                }
+            }
+        });
+        if !found {
+            eprintln!("extract_function: no marked statements found in a free function");
+        }
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// Find the marked statements in the body of the function `i`, not counting those of nested
+/// items.
+fn find_region(i: &Item, st: &CommandState) -> Result<Option<Region>, String> {
+    struct RegionVisitor<'a> {
+        st: &'a CommandState,
+        loops: Vec<Span>,
+        unsafe_depth: usize,
+        region: Result<Option<Region>, String>,
+    }
+
+    impl<'a, 'ast> Visitor<'ast> for RegionVisitor<'a> {
+        fn visit_block(&mut self, b: &'ast Block) {
+            let is_unsafe = match b.rules {
+                BlockCheckMode::Unsafe(_) => true,
+                BlockCheckMode::Default => false,
+            };
+            if is_unsafe {
+                self.unsafe_depth += 1;
+            }
+
+            let marked = b.stmts.iter().enumerate()
+                .filter(|&(_, s)| self.st.marked(s.id, "target"))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            if let (Some(&first), Some(&last)) = (marked.first(), marked.last()) {
+                self.region = match self.region {
+                    Ok(None) if last + 1 - first != marked.len() =>
+                        Err("the marked statements are not consecutive".to_owned()),
+                    Ok(None) => Ok(Some(Region {
+                        block: b.id,
+                        range: first..last + 1,
+                        span: b.stmts[first].span.to(b.stmts[last].span),
+                        loops: self.loops.clone(),
+                        in_unsafe: self.unsafe_depth > 0,
+                    })),
+                    Ok(Some(_)) =>
+                        Err("the marked statements are in more than one block".to_owned()),
+                    Err(ref reason) => Err(reason.clone()),
+                };
+            }
+            visit::walk_block(self, b);
+
+            if is_unsafe {
+                self.unsafe_depth -= 1;
+            }
+        }
+
+        fn visit_expr(&mut self, e: &'ast Expr) {
+            match e.kind {
+                ExprKind::While(..) | ExprKind::ForLoop(..) | ExprKind::Loop(..) => {
+                    self.loops.push(e.span);
+                    visit::walk_expr(self, e);
+                    self.loops.pop();
+                }
+                _ => visit::walk_expr(self, e),
+            }
+        }
+
+        fn visit_item(&mut self, _i: &'ast Item) {}
+
+        fn visit_mac(&mut self, mac: &'ast Mac) {
+            visit::walk_mac(self, mac);
+        }
+    }
+
+    let body = expect!([i.kind] ItemKind::Fn(_, _, _, ref body) => body);
+    let mut v = RegionVisitor {
+        st,
+        loops: Vec::new(),
+        unsafe_depth: 0,
+        region: Ok(None),
+    };
+    visit::walk_block(&mut v, body);
+    v.region
+}
+
+/// Find a `return`, `?`, `break` or `continue` in `s` that jumps out of it, and return its span
+/// along with a description.
+fn find_escape(s: &Stmt) -> Option<(Span, &'static str)> {
+    struct EscapeVisitor {
+        loop_depth: usize,
+        /// Labels of the loops and blocks inside `s`
+        labels: HashSet<Symbol>,
+        found: Option<(Span, &'static str)>,
+    }
+
+    impl EscapeVisitor {
+        fn leaves(&self, label: &Option<Label>) -> bool {
+            match *label {
+                Some(ref label) => !self.labels.contains(&label.ident.name),
+                None => self.loop_depth == 0,
+            }
+        }
+    }
+
+    impl<'ast> Visitor<'ast> for EscapeVisitor {
+        fn visit_expr(&mut self, e: &'ast Expr) {
+            if self.found.is_some() {
+                return;
+            }
+            match e.kind {
+                ExprKind::Ret(_) => self.found = Some((e.span, "`return`")),
+                ExprKind::Try(_) => self.found = Some((e.span, "`?`")),
+                ExprKind::Break(ref label, _) if self.leaves(label) =>
+                    self.found = Some((e.span, "`break`")),
+                ExprKind::Continue(ref label) if self.leaves(label) =>
+                    self.found = Some((e.span, "`continue`")),
+                ExprKind::While(_, _, ref label) |
+                ExprKind::ForLoop(_, _, _, ref label) |
+                ExprKind::Loop(_, ref label) => {
+                    self.labels.extend(label.as_ref().map(|l| l.ident.name));
+                    self.loop_depth += 1;
+                    visit::walk_expr(self, e);
+                    self.loop_depth -= 1;
+                }
+                ExprKind::Block(_, Some(ref label)) => {
+                    self.labels.insert(label.ident.name);
+                    visit::walk_expr(self, e);
+                }
+                // These have their own `return` and loop targets.
+                ExprKind::Closure(..) | ExprKind::Async(..) => {}
+                _ => visit::walk_expr(self, e),
+            }
+        }
+
+        fn visit_item(&mut self, _i: &'ast Item) {}
+
+        fn visit_mac(&mut self, mac: &'ast Mac) {
+            visit::walk_mac(self, mac);
+        }
+    }
+
+    let mut v = EscapeVisitor {
+        loop_depth: 0,
+        labels: HashSet::new(),
+        found: None,
+    };
+    visit::walk_stmt(&mut v, s);
+    v.found
+}
+
+/// The root local of the place `e`, such as `x` in `x.a[i]`.
+fn place_root(mut e: &Expr) -> &Expr {
+    loop {
+        match e.kind {
+            ExprKind::Field(ref base, _) |
+            ExprKind::Index(ref base, _) |
+            ExprKind::Paren(ref base) => e = base,
+            _ => return e,
+        }
+    }
+}
+
+/// Move `region` out of the function `i` into a new function `name`.  Returns the updated
+/// function and the new one.
+fn extract_region(
+    i: &P<Item>,
+    region: &Region,
+    name: &str,
+    cx: &RefactorCtxt,
+) -> Result<(P<Item>, P<Item>), String> {
+    let (header, generics, body) = expect!([i.kind]
+        ItemKind::Fn(_, ref header, ref generics, ref body) => (header, generics, body));
+    let has_ty_params = generics.params.iter().any(|p| match p.kind {
+        GenericParamKind::Lifetime => false,
+        _ => true,
+    });
+    if has_ty_params {
+        return Err("functions with type parameters are not supported".to_owned());
+    }
+
+    let tcx = cx.ty_ctxt();
+    let source_map = cx.session().source_map();
+    let mut stmts = None;
+    visit_nodes(&**body, |b: &Block| {
+        if b.id == region.block {
+            stmts = Some(b.stmts.clone());
+        }
+    });
+    let stmts = stmts.expect("marked block not found");
+    let region_stmts = &stmts[region.range.clone()];
+
+    for s in region_stmts {
+        if let Some((span, what)) = find_escape(s) {
+            return Err(format!("{} at {} leaves the marked statements",
+                               what, source_map.span_to_string(span)));
+        }
+    }
+
+    // The value of the block, if the region ends with it
+    let tail = match region_stmts.last().map(|s| &s.kind) {
+        Some(&StmtKind::Expr(ref e)) if region.range.end == stmts.len() => {
+            Some(cx.node_type(e.id)).filter(|ty| !ty.is_unit())
+        }
+        _ => None,
+    };
+
+    let local_of = |e: &Expr| -> Option<HirId> {
+        match e.kind {
+            ExprKind::Path(None, ref path) if path.segments.len() == 1 => {}
+            _ => return None,
+        }
+        match cx.try_resolve_expr_hir(e) {
+            Some(Res::Local(hir_id)) => Some(hir_id),
+            _ => None,
+        }
+    };
+
+    // The local that `e` writes to, if any: through an assignment, a mutable borrow, or a method
+    // call borrowing its receiver mutably.
+    let is_mut_ref = |ty: ty::Ty| match ty.kind {
+        TyKind::Ref(_, _, hir::Mutability::MutMutable) => true,
+        _ => false,
+    };
+    let written_local = |e: &Expr| -> Option<HirId> {
+        let place = match e.kind {
+            ExprKind::Assign(ref lhs, _) |
+            ExprKind::AssignOp(_, ref lhs, _) |
+            ExprKind::AddrOf(Mutability::Mutable, ref lhs) => lhs,
+            ExprKind::MethodCall(_, ref args) => {
+                let recv = &args[0];
+                let autoref_mut =
+                    cx.opt_adjusted_node_type(recv.id).map_or(false, is_mut_ref) &&
+                    !is_mut_ref(cx.node_type(recv.id));
+                if !autoref_mut {
+                    return None;
+                }
+                recv
+            }
+            _ => return None,
+        };
+        local_of(place_root(place))
+    };
+
+
+    // (1) Find the locals used by the region, the ones it declares, and the ones it writes to.
+
+    let mut declared = HashSet::new();
+    let mut inputs = Vec::new();
+    let mut written = HashSet::new();
+    for s in region_stmts {
+        visit_nodes(s, |p: &Pat| {
+            if let PatKind::Ident(..) = p.kind {
+                declared.insert(cx.hir_map().node_to_hir_id(p.id));
+            }
+        });
+    }
+    let mut seen = HashSet::new();
+    for s in region_stmts {
+        visit_nodes(s, |e: &Expr| {
+            if let Some(hir_id) = local_of(e) {
+                if !declared.contains(&hir_id) && seen.insert(hir_id) {
+                    let ident = expect!([e.kind]
+                        ExprKind::Path(_, ref path) => path.segments[0].ident);
+                    inputs.push((hir_id, ident, cx.node_type(e.id)));
+                }
+            }
+
+            written.extend(written_local(e));
+        });
+    }
+
+    // Uses of locals that may run after the region: later in the function, or anywhere in a
+    // loop around it.  Code from macro expansions counts as being at the macro invocation.
+    let mut used_after = HashSet::new();
+    let mut written_after = HashSet::new();
+    visit_nodes(&**body, |e: &Expr| {
+        let span = e.span.source_callsite();
+        if region.span.contains(span) {
+            return;
+        }
+        if span.lo() < region.span.hi() && !region.loops.iter().any(|l| l.contains(span)) {
+            return;
+        }
+        used_after.extend(local_of(e));
+        written_after.extend(written_local(e));
+    });
+
+
+    // (2) Build the parameters and the arguments of the call.
+
+    let param_env = tcx.param_env(cx.node_def_id(i.id));
+    let mut params = Vec::with_capacity(inputs.len());
+    let mut args = Vec::with_capacity(inputs.len());
+    let mut derefs = HashSet::new();
+    for (hir_id, ident, ty) in inputs {
+        let reflected = reflect::try_reflect_tcx_ty(tcx, ty)
+            .ok_or_else(|| format!("the type of `{}` can't be named", ident))?;
+        let is_ref = match ty.kind {
+            TyKind::Ref(..) => true,
+            _ => false,
+        };
+        let is_copy = ty.is_copy_modulo_regions(tcx, param_env, DUMMY_SP);
+        let mode = if !used_after.contains(&hir_id) {
+            PassMode::Value
+        } else if written.contains(&hir_id) {
+            PassMode::RefMut
+        } else if !is_copy && !is_ref {
+            // References are reborrowed when passed, instead of moved
+            PassMode::Ref
+        } else {
+            PassMode::Value
+        };
+
+        let (param_ty, pat, arg) = match mode {
+            PassMode::Value => {
+                let mutbl = if written.contains(&hir_id) {
+                    Mutability::Mutable
+                } else {
+                    Mutability::Immutable
+                };
+                (reflected, mk().set_mutbl(mutbl).ident_pat(ident), mk().ident_expr(ident))
+            }
+            PassMode::Ref => (
+                mk().ref_ty(reflected),
+                mk().ident_pat(ident),
+                mk().addr_of_expr(mk().ident_expr(ident)),
+            ),
+            PassMode::RefMut => (
+                mk().mutbl().ref_ty(reflected),
+                mk().ident_pat(ident),
+                mk().mutbl().addr_of_expr(mk().ident_expr(ident)),
+            ),
+        };
+        if mode != PassMode::Value {
+            derefs.insert(hir_id);
+        }
+        params.push(mk().arg(param_ty, pat));
+        args.push(arg);
+    }
+
+
+    // (3) Build the results and the new function.
+
+    let mut outputs = Vec::new();
+    let mut by_ref = None;
+    for s in region_stmts {
+        let local = match_or!([s.kind] StmtKind::Local(ref l) => l; continue);
+        visit_nodes(&*local.pat, |p: &Pat| {
+            let (mode, ident) = match_or!([p.kind]
+                PatKind::Ident(mode, ident, _) => (mode, ident); return);
+            let hir_id = cx.hir_map().node_to_hir_id(p.id);
+            if !used_after.contains(&hir_id) {
+                return;
+            }
+            // Only rebind mutably what is still modified at the call site
+            let mutbl = if written_after.contains(&hir_id) {
+                Mutability::Mutable
+            } else {
+                Mutability::Immutable
+            };
+            match mode {
+                BindingMode::ByValue(_) => outputs.push((ident, mutbl, cx.node_type(p.id))),
+                BindingMode::ByRef(_) => by_ref = Some(ident),
+            }
+        });
+    }
+    if let Some(ident) = by_ref {
+        return Err(format!("`{}` is bound by reference and used after the marked statements",
+                           ident));
+    }
+
+    let mut new_body = mk().block(region_stmts.to_vec());
+    MutVisitNodes::visit(&mut new_body, |e: &mut P<Expr>| {
+        if local_of(&**e).map_or(false, |hir_id| derefs.contains(&hir_id)) {
+            *e = mk().unary_expr(ast::UnOp::Deref, e.clone());
+        }
+    });
+
+    let call = mk().call_expr(mk().path_expr(vec![name]), args);
+    let (output, replacement) = if let Some(ty) = tail {
+        let ty = reflect::try_reflect_tcx_ty(tcx, ty)
+            .ok_or_else(|| "the type of the block's value can't be named".to_owned())?;
+        (FunctionRetTy::Ty(ty), mk().expr_stmt(call))
+    } else if outputs.is_empty() {
+        (FunctionRetTy::Default(DUMMY_SP), mk().semi_stmt(call))
+    } else {
+        let mut tys = Vec::with_capacity(outputs.len());
+        let mut pats = Vec::with_capacity(outputs.len());
+        let mut values = Vec::with_capacity(outputs.len());
+        for &(ident, mutbl, ty) in &outputs {
+            tys.push(reflect::try_reflect_tcx_ty(tcx, ty)
+                .ok_or_else(|| format!("the type of `{}` can't be named", ident))?);
+            pats.push(mk().set_mutbl(mutbl).ident_pat(ident));
+            values.push(mk().ident_expr(ident));
+        }
+        let (ty, pat, value) = if outputs.len() == 1 {
+            (tys.pop().unwrap(), pats.pop().unwrap(), values.pop().unwrap())
+        } else {
+            (mk().tuple_ty(tys), mk().tuple_pat(pats), mk().tuple_expr(values))
+        };
+
+        // The last statement is no longer the value of the block
+        if let Some(last) = new_body.stmts.last_mut() {
+            if let StmtKind::Expr(ref e) = last.kind {
+                last.kind = StmtKind::Semi(e.clone());
+            }
+        }
+        new_body.stmts.push(mk().expr_stmt(value));
+        let local = mk().local(pat, None as Option<P<Ty>>, Some(call));
+        (FunctionRetTy::Ty(ty), mk().local_stmt(P(local)))
+    };
+
+    let unsafety = if region.in_unsafe { Unsafety::Unsafe } else { header.unsafety };
+    let callee = mk().unsafety(unsafety).fn_item(name, mk().fn_decl(params, output), new_body);
+
+    let mut caller = i.clone();
+    let mut replacement = Some(replacement);
+    MutVisitNodes::visit(&mut caller, |b: &mut P<Block>| {
+        if b.id == region.block {
+            b.stmts.splice(region.range.clone(), replacement.take());
+        }
+    });
+    Ok((caller, callee))
+}
+
+
 pub fn register_commands(reg: &mut Registry) {
     use super::mk;
 
@@ -811,6 +2017,11 @@ pub fn register_commands(reg: &mut Registry) {
     reg.register("sink_unsafe", |_args| mk(SinkUnsafe));
     reg.register("wrap_extern", |_args| mk(WrapExtern));
     reg.register("wrap_api", |_args| mk(WrapApi));
+    reg.register("inline_function", |_args| mk(InlineFunction));
+    reg.register("inline_single_use", |_args| mk(InlineSingleUse));
+    reg.register("extract_function", |args| mk(ExtractFunction {
+        name: args[0].clone(),
+    }));
     reg.register("abstract", |args| mk(Abstract {
         sig: args[0].clone(),
         pat: args[1].clone(),
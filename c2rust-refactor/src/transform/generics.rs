@@ -1,11 +1,15 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use rustc::hir::def::{DefKind, Res};
+use rustc::hir::PrimTy;
+use rustc::ty;
 use syntax::ast::*;
+use syntax::print::pprust;
 use syntax::ptr::P;
 use syntax::symbol::Symbol;
 
-use crate::ast_manip::{FlatMapNodes, MutVisitNodes};
+use crate::ast_manip::{FlatMapNodes, MutVisitNodes, visit_nodes};
 use crate::command::{CommandState, Registry};
-use crate::driver::{parse_ty};
+use crate::driver::{parse_expr, parse_ty, Phase};
 use crate::path_edit::fold_resolved_paths_with_id;
 use crate::transform::Transform;
 use crate::RefactorCtxt;
@@ -14,7 +18,7 @@ use c2rust_ast_builder::{mk, IntoSymbol};
 
 /// # `generalize_items` Command
 /// 
-/// Usage: `generalize_items VAR [TY]`
+/// Usage: `generalize_items VAR [TY] [--num-traits]`
 /// 
 /// Marks: `target`
 /// 
@@ -24,10 +28,20 @@ use c2rust_ast_builder::{mk, IntoSymbol};
 /// `target`, replacing type annotations inside that item that are marked `target`
 /// with uses of the type parameter.  Also update all uses of `target` items,
 /// passing `TY` as the new type argument when used inside a non-`target` item, and
-/// passing the type variable `VAR` when used inside a `target` item.
+/// passing the type variable `VAR` when used inside a `target` item.  Calls to
+/// functions that take a `VAR` argument are left alone, since the type argument
+/// can be inferred from the arguments.
 /// 
 /// If `TY` is not provided, it defaults to a copy of the first type annotation
 /// that was replaced with `VAR`.
+///
+/// The bounds on `VAR` in a generalized function are inferred from how its body
+/// uses values of the replaced types: arithmetic and bitwise operators require the
+/// corresponding `std::ops` trait, comparisons require `PartialOrd` or
+/// `PartialEq`, and scalar types used this way must also stay `Copy`.
+/// Literals of a replaced type become `VAR::from(n)` conversions from `u8`, or
+/// `num_traits::NumCast` conversions with `--num-traits`.  Uses the command can't
+/// infer a bound for, like method calls and casts, are reported as warnings.
 /// 
 /// Example:
 ///
@@ -70,10 +84,173 @@ use c2rust_ast_builder::{mk, IntoSymbol};
 pub struct GeneralizeItems {
     ty_var_name: Symbol,
     replacement_ty: Option<String>,
+    num_traits: bool,
+}
+
+/// What generalizing the body of a function requires.
+#[derive(Default)]
+struct BodyRequirements {
+    /// Trait bounds on the type parameter, in the order they were found
+    bounds: Vec<String>,
+    /// Replacements for literals of a replaced type
+    literals: HashMap<NodeId, P<Expr>>,
+    /// Uses of a replaced type that no bound could be inferred for
+    unsupported: Vec<String>,
+}
+
+impl BodyRequirements {
+    fn add_bound(&mut self, bound: String) {
+        if !self.bounds.contains(&bound) {
+            self.bounds.push(bound);
+        }
+    }
+}
+
+impl GeneralizeItems {
+    /// Walk the typed body of `item`, a function marked `target`, and collect the bounds the
+    /// type parameter needs for the body to type-check once its marked types are replaced.
+    fn body_requirements(
+        &self,
+        item: &Item,
+        body: &Block,
+        st: &CommandState,
+        cx: &RefactorCtxt,
+    ) -> BodyRequirements {
+        let mut replaced = HashSet::new();
+        visit_nodes(item, |ty: &Ty| {
+            if st.marked(ty.id, "target") && parent_item(cx, ty.id) == item.id {
+                if let Some(ty) = resolve_marked_ty(cx, ty) {
+                    replaced.insert(ty);
+                }
+            }
+        });
+
+        let is_generic = |id: NodeId| {
+            cx.opt_node_type(id).map_or(false, |ty| replaced.contains(&ty))
+        };
+        let var = self.ty_var_name;
+
+        let mut req = BodyRequirements::default();
+        visit_nodes(body, |e: &Expr| {
+            match e.kind {
+                ExprKind::Binary(op, ref lhs, ref rhs) if is_generic(lhs.id) => {
+                    match op.node {
+                        BinOpKind::Lt | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge =>
+                            req.add_bound("PartialOrd".to_owned()),
+                        BinOpKind::Eq | BinOpKind::Ne =>
+                            req.add_bound("PartialEq".to_owned()),
+                        BinOpKind::And | BinOpKind::Or => {}
+                        _ => {
+                            let name = op_trait(op.node);
+                            match op_rhs(cx, rhs, is_generic(rhs.id)) {
+                                Some(None) => req.add_bound(
+                                    format!("std::ops::{}<Output = {}>", name, var),
+                                ),
+                                Some(Some(rhs)) => req.add_bound(
+                                    format!("std::ops::{}<{}, Output = {}>", name, rhs, var),
+                                ),
+                                None => req.unsupported.push(
+                                    format!("operator `{}`", op.node.to_string()),
+                                ),
+                            }
+                        }
+                    }
+                }
+                ExprKind::AssignOp(op, ref lhs, ref rhs) if is_generic(lhs.id) => {
+                    let name = op_trait(op.node);
+                    match op_rhs(cx, rhs, is_generic(rhs.id)) {
+                        Some(None) => req.add_bound(format!("std::ops::{}Assign", name)),
+                        Some(Some(rhs)) => req.add_bound(
+                            format!("std::ops::{}Assign<{}>", name, rhs),
+                        ),
+                        None => req.unsupported.push(
+                            format!("operator `{}=`", op.node.to_string()),
+                        ),
+                    }
+                }
+                ExprKind::Unary(UnOp::Neg, ref arg) if is_generic(arg.id) =>
+                    req.add_bound(format!("std::ops::Neg<Output = {}>", var)),
+                ExprKind::Unary(UnOp::Not, ref arg) if is_generic(arg.id) =>
+                    req.add_bound(format!("std::ops::Not<Output = {}>", var)),
+                ExprKind::Lit(ref lit) if is_generic(e.id) => {
+                    match self.convert_literal(lit) {
+                        Some((bound, src)) => {
+                            req.add_bound(bound);
+                            req.literals.insert(e.id, parse_expr(cx.session(), &src));
+                        }
+                        None => req.unsupported.push(
+                            format!("literal `{}`", pprust::expr_to_string(e)),
+                        ),
+                    }
+                }
+                ExprKind::Cast(ref inner, _) if is_generic(inner.id) || is_generic(e.id) =>
+                    req.unsupported.push(format!("cast `{}`", pprust::expr_to_string(e))),
+                ExprKind::MethodCall(ref seg, ref args) if is_generic(args[0].id) =>
+                    req.unsupported.push(format!("method `{}`", seg.ident)),
+                _ => {}
+            }
+        });
+
+        // Operators take their operands by value, so a scalar used in more than one operation
+        // needs to stay `Copy`
+        if !req.bounds.is_empty() && replaced.iter().any(|ty| ty.is_scalar()) {
+            req.bounds.insert(0, "Copy".to_owned());
+        }
+
+        req
+    }
+
+    /// Get the bound and the source of the conversion that replaces a literal of a
+    /// replaced type.
+    fn convert_literal(&self, lit: &Lit) -> Option<(String, String)> {
+        let var = self.ty_var_name;
+        if self.num_traits {
+            let src = format!(
+                "<{} as num_traits::NumCast>::from({}).unwrap()",
+                var, pprust::lit_to_string(lit),
+            );
+            return Some(("num_traits::NumCast".to_owned(), src));
+        }
+
+        let val = match lit.kind {
+            LitKind::Int(val, _) => val,
+            LitKind::Float(sym, _) | LitKind::FloatUnsuffixed(sym) => {
+                let val = sym.as_str().replace('_', "").parse::<f64>().ok()?;
+                if val.fract() != 0.0 || val < 0.0 || val > 255.0 {
+                    return None;
+                }
+                val as u128
+            }
+            _ => return None,
+        };
+        if val > 255 {
+            return None;
+        }
+        Some(("From<u8>".to_owned(), format!("{}::from({}u8)", var, val)))
+    }
 }
 
 impl Transform for GeneralizeItems {
     fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        // (0) Infer the bounds each marked function needs, before its types are rewritten.
+
+        let mut item_bounds = HashMap::new();
+        let mut literals = HashMap::new();
+        visit_nodes(krate, |i: &Item| {
+            if !st.marked(i.id, "target") {
+                return;
+            }
+            if let ItemKind::Fn(_, _, _, ref body) = i.kind {
+                let req = self.body_requirements(i, body, st, cx);
+                for what in &req.unsupported {
+                    warn!("generalize_items: {}: could not infer a bound for {}",
+                          i.ident, what);
+                }
+                item_bounds.insert(i.id, req.bounds);
+                literals.extend(req.literals);
+            }
+        });
+
         // (1) Find marked types and replace with the named type variable.
 
         // Map from item NodeId to the concrete type that was replaced with the type variable.
@@ -100,15 +277,32 @@ impl Transform for GeneralizeItems {
             *ty = mk().ident_ty(self.ty_var_name)
         });
 
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            if let Some(new_e) = literals.remove(&e.id) {
+                *e = new_e;
+            }
+        });
+
         // (2) Add parameters to rewritten items.
 
         let mut item_def_ids = HashSet::new();
+        // Functions that take an argument of the new type, so callers can leave it to inference
+        let mut inferable_def_ids = HashSet::new();
         FlatMapNodes::visit(krate, |i: P<Item>| {
             if !st.marked(i.id, "target") {
                 return smallvec![i];
             }
-            item_def_ids.insert(cx.node_def_id(i.id));
+            let def_id = cx.node_def_id(i.id);
+            item_def_ids.insert(def_id);
+            let bounds = item_bounds.remove(&i.id).unwrap_or_default();
             smallvec![i.map(|mut i| {
+                if let ItemKind::Fn(ref decl, _, _, _) = i.kind {
+                    let var = self.ty_var_name;
+                    if decl.inputs.iter().any(|arg| mentions_ty_param(&arg.ty, var)) {
+                        inferable_def_ids.insert(def_id);
+                    }
+                }
+
                 {
                     let gen = match i.kind {
                         ItemKind::Fn(_, _, ref mut gen, _) => gen,
@@ -119,7 +313,17 @@ impl Transform for GeneralizeItems {
                         ItemKind::Impl(_, _, _, ref mut gen, _, _, _) => gen,
                         _ => panic!("item has no room for generics"),
                     };
-                    gen.params.push(mk().ty_param(self.ty_var_name));
+                    let mut param = mk().ty_param(self.ty_var_name);
+                    if !bounds.is_empty() {
+                        let bounds_ty = parse_ty(
+                            cx.session(),
+                            &format!("impl {}", bounds.join(" + ")),
+                        );
+                        if let TyKind::ImplTrait(_, ref bounds) = bounds_ty.kind {
+                            param.bounds = bounds.clone();
+                        }
+                    }
+                    gen.params.push(param);
                 }
                 i
             })]
@@ -127,14 +331,16 @@ impl Transform for GeneralizeItems {
 
         // (3) Rewrite references to each item, replacing `X` with `X<ty1>`.  If the reference to
         // rewritten item `X` appears inside another rewritten item `Y`, we instead replace `X`
-        // with `X<T>`, referring to `Y`'s instance of the type parameter.
+        // with `X<T>`, referring to `Y`'s instance of the type parameter.  References to
+        // functions whose type argument can be inferred are left unchanged.
 
         let replacement_ty = replacement_ty
             .expect("must provide a replacement type argument or mark");
 
         fold_resolved_paths_with_id(krate, cx, |path_id, qself, mut path, def| {
             match def.opt_def_id() {
-                Some(def_id) if item_def_ids.contains(&def_id) => (),
+                Some(def_id) if item_def_ids.contains(&def_id)
+                    && !inferable_def_ids.contains(&def_id) => (),
                 _ => return (qself, path),
             };
 
@@ -168,14 +374,91 @@ impl Transform for GeneralizeItems {
             (qself, path)
         });
     }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// Get the `NodeId` of the item containing `id`.
+fn parent_item(cx: &RefactorCtxt, id: NodeId) -> NodeId {
+    let hir_id = cx.hir_map().node_to_hir_id(id);
+    let parent_id = cx.hir_map().get_parent_item(hir_id);
+    cx.hir_map().hir_to_node_id(parent_id)
+}
+
+/// Resolve a marked type annotation to the type it names.  Only primitive types and paths to
+/// non-generic types and type aliases are supported.
+fn resolve_marked_ty<'tcx>(cx: &RefactorCtxt<'_, 'tcx>, t: &Ty) -> Option<ty::Ty<'tcx>> {
+    let tcx = cx.ty_ctxt();
+    match cx.try_resolve_ty_hir(t)? {
+        Res::PrimTy(PrimTy::Int(t)) => Some(tcx.mk_mach_int(t)),
+        Res::PrimTy(PrimTy::Uint(t)) => Some(tcx.mk_mach_uint(t)),
+        Res::PrimTy(PrimTy::Float(t)) => Some(tcx.mk_mach_float(t)),
+        Res::PrimTy(PrimTy::Bool) => Some(tcx.types.bool),
+        Res::PrimTy(PrimTy::Char) => Some(tcx.types.char),
+        Res::Def(DefKind::TyAlias, def_id)
+        | Res::Def(DefKind::Struct, def_id)
+        | Res::Def(DefKind::Enum, def_id)
+        | Res::Def(DefKind::Union, def_id) => Some(cx.def_type(def_id)),
+        _ => None,
+    }
+}
+
+/// The name of the `std::ops` trait for a binary operator.
+fn op_trait(op: BinOpKind) -> &'static str {
+    match op {
+        BinOpKind::Add => "Add",
+        BinOpKind::Sub => "Sub",
+        BinOpKind::Mul => "Mul",
+        BinOpKind::Div => "Div",
+        BinOpKind::Rem => "Rem",
+        BinOpKind::BitAnd => "BitAnd",
+        BinOpKind::BitOr => "BitOr",
+        BinOpKind::BitXor => "BitXor",
+        BinOpKind::Shl => "Shl",
+        BinOpKind::Shr => "Shr",
+        _ => panic!("no operator trait for {:?}", op),
+    }
+}
+
+/// The right-hand side type argument of an operator trait bound: `Some(None)` when the operand
+/// has the generic type, which is the trait's default, and `None` if the operand's type can't be
+/// named in the bound.
+fn op_rhs(cx: &RefactorCtxt, rhs: &Expr, rhs_is_generic: bool) -> Option<Option<String>> {
+    if rhs_is_generic {
+        return Some(None);
+    }
+    let ty = cx.opt_node_type(rhs.id)?;
+    if ty.is_scalar() {
+        Some(Some(ty.to_string()))
+    } else {
+        None
+    }
+}
+
+/// Check whether `ty` refers to the type parameter `name`.
+fn mentions_ty_param(ty: &Ty, name: Symbol) -> bool {
+    let mut found = false;
+    visit_nodes(ty, |p: &Path| {
+        if p.segments.len() == 1 && p.segments[0].ident.name == name {
+            found = true;
+        }
+    });
+    found
 }
 
 
 pub fn register_commands(reg: &mut Registry) {
     use super::mk;
 
-    reg.register("generalize_items", |args| mk(GeneralizeItems {
-        ty_var_name: args.get(0).map_or("T", |x| x).into_symbol(),
-        replacement_ty: args.get(1).cloned(),
-    }));
+    reg.register("generalize_items", |args| {
+        let num_traits = args.iter().any(|a| a == "--num-traits");
+        let args = args.iter().filter(|a| *a != "--num-traits").collect::<Vec<_>>();
+        mk(GeneralizeItems {
+            ty_var_name: args.get(0).map_or("T", |x| x.as_str()).into_symbol(),
+            replacement_ty: args.get(1).map(|x| x.to_string()),
+            num_traits,
+        })
+    });
 }
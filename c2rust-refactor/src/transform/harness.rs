@@ -0,0 +1,385 @@
+use std::fmt::Write;
+use rustc::ty::{self, TyKind};
+use rustc_target::spec::abi::Abi;
+use syntax::ast::*;
+use syntax::print::pprust;
+use syntax::ptr::P;
+
+use crate::ast_manip::MutVisitNodes;
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::reflect::reflect_tcx_ty;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `test_harness_gen` Command
+///
+/// Usage: `test_harness_gen [PREFIX]`
+///
+/// Marks: `target`
+///
+/// Generate differential tests comparing each translated function marked `target` against
+/// the original C function, in the style of the tests in `tests/builtins`.  For each module
+/// containing marked functions, a `#[cfg(test)] mod test_harness` is added with:
+///
+///  * an `extern "C"` block declaring the original functions under the link name `PREFIX`
+///    followed by the function name (`c_` by default), as after building the C library with
+///    `objcopy --prefix-symbols=PREFIX`, and
+///  * a `#[test]` per function that calls the C and the Rust function with the same inputs,
+///    and asserts that they return the same value and leave their buffers with the same
+///    contents.
+///
+/// Integer and floating-point parameters are tested with every combination of a few boundary
+/// values each.  Pointers to integers or floats are passed buffers of 16 elements, filled
+/// with a fixed pseudo-random sequence ending in a zero, so that character buffers are also
+/// C strings.  Integer parameters of functions taking buffers are often lengths, so they
+/// are only tested with values between zero and the buffer length.
+///
+/// Functions with other parameter or return types are left out and listed in the output.
+///
+/// Example:
+///
+/// ```ignore
+///     #[no_mangle]
+///     pub unsafe extern "C" fn clamp(mut x: libc::c_int, mut lo: libc::c_int,
+///                                    mut hi: libc::c_int) -> libc::c_int { ... }
+/// ```
+///
+/// After running `test_harness_gen` on `clamp`:
+///
+/// ```ignore
+///     #[cfg(test)]
+///     mod test_harness {
+///         use super::*;
+///
+///         extern "C" {
+///             #[link_name = "c_clamp"]
+///             fn clamp(x: libc::c_int, lo: libc::c_int, hi: libc::c_int) -> libc::c_int;
+///         }
+///
+///         #[test]
+///         fn test_clamp() {
+///             let x_values: &[libc::c_int] =
+///                 &[libc::c_int::min_value(), -1, 0, 1, libc::c_int::max_value()];
+///             ...
+///             for case in 0..cases {
+///                 let x = x_values[case % x_values.len()];
+///                 ...
+///                 let c_result = unsafe { clamp(x, lo, hi) };
+///                 let rust_result = unsafe { super::clamp(x, lo, hi) };
+///                 assert_eq!(c_result, rust_result, "case {}: x = {}, lo = {}, hi = {}",
+///                            case, x, lo, hi);
+///             }
+///         }
+///     }
+/// ```
+pub struct TestHarnessGen {
+    prefix: String,
+}
+
+impl Transform for TestHarnessGen {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |m: &mut Mod| {
+            let mut harnesses = Vec::new();
+            for item in &m.items {
+                if !st.marked(item.id, "target") {
+                    continue;
+                }
+                match fn_harness(cx, item) {
+                    Ok(harness) => harnesses.push(harness),
+                    Err(reason) => eprintln!("test_harness_gen: {}: {}", item.ident, reason),
+                }
+            }
+            if harnesses.is_empty() {
+                return;
+            }
+            if m.items.iter().any(|i| i.ident.as_str() == "test_harness") {
+                eprintln!("test_harness_gen: a `test_harness` item already exists next to {}",
+                          harnesses.iter().map(|h| &h.name[..]).collect::<Vec<_>>().join(", "));
+                return;
+            }
+
+            let src = harness_module(&self.prefix, &harnesses);
+            m.items.extend(st.parse_items(cx, &src));
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// The number of elements of the buffers passed for pointer parameters
+const BUFFER_LEN: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Signed,
+    Unsigned,
+    Float,
+}
+
+enum ParamShape {
+    Scalar(Scalar),
+    /// A pointer to a buffer of scalars, and whether it's mutable
+    Buffer(Scalar, bool),
+}
+
+struct Param {
+    name: String,
+    /// The type of the parameter, or of the buffer elements
+    ty: String,
+    shape: ParamShape,
+    decl_ty: String,
+}
+
+struct FnHarness {
+    name: String,
+    params: Vec<Param>,
+    ret: Option<(String, Scalar)>,
+}
+
+fn scalar(ty: ty::Ty) -> Option<Scalar> {
+    match ty.kind {
+        TyKind::Int(_) => Some(Scalar::Signed),
+        TyKind::Uint(_) => Some(Scalar::Unsigned),
+        TyKind::Float(_) => Some(Scalar::Float),
+        _ => None,
+    }
+}
+
+/// Check that the signature of `item` can be tested, and get its parameters.
+fn fn_harness(cx: &RefactorCtxt, item: &Item) -> Result<FnHarness, String> {
+    let (decl, header, generics) = match item.kind {
+        ItemKind::Fn(ref decl, ref header, ref generics, _) => (decl, header, generics),
+        _ => return Err("not a function".to_owned()),
+    };
+    if header.abi != Abi::C || decl.c_variadic() || !generics.params.is_empty() {
+        return Err("not a non-variadic `extern \"C\"` function".to_owned());
+    }
+
+    let tcx = cx.ty_ctxt();
+    let sig = tcx.fn_sig(cx.node_def_id(item.id));
+    let sig = sig.skip_binder();
+
+    let mut params = Vec::new();
+    for (i, (arg, &ty)) in decl.inputs.iter().zip(sig.inputs().iter()).enumerate() {
+        let name = match arg.pat.kind {
+            PatKind::Ident(_, ident, None) => ident.to_string(),
+            _ => format!("arg{}", i),
+        };
+        let decl_ty = pprust::ty_to_string(&arg.ty);
+        let (shape, ty) = match ty.kind {
+            TyKind::RawPtr(mt) if scalar(mt.ty).is_some() => {
+                let elem_ty = match arg.ty.kind {
+                    syntax::ast::TyKind::Ptr(ref mt) => mt.ty.clone(),
+                    _ => reflect_tcx_ty(tcx, mt.ty),
+                };
+                let mutable = mt.mutbl == rustc::hir::Mutability::MutMutable;
+                (ParamShape::Buffer(scalar(mt.ty).unwrap(), mutable),
+                 pprust::ty_to_string(&elem_ty))
+            }
+            _ => match scalar(ty) {
+                Some(s) => (ParamShape::Scalar(s), decl_ty.clone()),
+                None => {
+                    return Err(format!("unsupported parameter `{}: {}`", name, decl_ty));
+                }
+            },
+        };
+        params.push(Param { name, ty, shape, decl_ty });
+    }
+
+    let ret = match decl.output {
+        FunctionRetTy::Default(_) => None,
+        FunctionRetTy::Ty(_) if sig.output().is_unit() => None,
+        FunctionRetTy::Ty(ref ret_ty) => match scalar(sig.output()) {
+            Some(s) => Some((pprust::ty_to_string(ret_ty), s)),
+            None => {
+                return Err(format!("unsupported return type `{}`",
+                                   pprust::ty_to_string(ret_ty)));
+            }
+        },
+    };
+
+    Ok(FnHarness {
+        name: item.ident.to_string(),
+        params,
+        ret,
+    })
+}
+
+/// Build the source of the test module for `harnesses`.
+fn harness_module(prefix: &str, harnesses: &[FnHarness]) -> String {
+    let mut src = String::new();
+    src.push_str("#[cfg(test)]\nmod test_harness {\n    use super::*;\n\n    extern \"C\" {\n");
+    for h in harnesses {
+        let params = h.params.iter()
+            .map(|p| format!("{}: {}", p.name, p.decl_ty))
+            .collect::<Vec<_>>();
+        let ret = h.ret.as_ref().map_or(String::new(), |(ty, _)| format!(" -> {}", ty));
+        writeln!(src, "        #[link_name = \"{}{}\"]", prefix, h.name).unwrap();
+        writeln!(src, "        fn {}({}){};", h.name, params.join(", "), ret).unwrap();
+    }
+    src.push_str("    }\n");
+
+    if harnesses.iter().any(|h| h.has_buffers()) {
+        write!(src, "
+    const BUFFER_LEN: usize = {};
+
+    /// A fixed pseudo-random sequence for buffer `buf` of test case `case`, ending in a zero so
+    /// that buffers of characters are also valid C strings.
+    fn buffer_values(case: usize, buf: usize) -> [u32; BUFFER_LEN] {{
+        let mut state = (case * 31 + buf) as u32;
+        let mut values = [0; BUFFER_LEN];
+        for value in values[..BUFFER_LEN - 1].iter_mut() {{
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            *value = state >> 16;
+        }}
+        values
+    }}
+", BUFFER_LEN).unwrap();
+    }
+
+    for h in harnesses {
+        h.write_test(&mut src);
+    }
+    src.push_str("}\n");
+    src
+}
+
+impl FnHarness {
+    fn has_buffers(&self) -> bool {
+        self.params.iter().any(|p| match p.shape {
+            ParamShape::Buffer(..) => true,
+            ParamShape::Scalar(_) => false,
+        })
+    }
+
+    /// The values to test a scalar parameter of type `ty` with.
+    fn scalar_values(&self, s: Scalar, ty: &str) -> String {
+        match s {
+            Scalar::Float => "[0.0, -1.5, 1.0, 1e30]".to_owned(),
+            _ if self.has_buffers() => format!(
+                "[0, 1, (BUFFER_LEN / 2) as {ty}, BUFFER_LEN as {ty}]", ty = ty),
+            Scalar::Signed => format!(
+                "[{ty}::min_value(), -1, 0, 1, {ty}::max_value()]", ty = ty),
+            Scalar::Unsigned => format!("[0, 1, {ty}::max_value()]", ty = ty),
+        }
+    }
+
+    fn write_test(&self, src: &mut String) {
+        writeln!(src, "\n    #[test]\n    fn test_{}() {{", self.name).unwrap();
+
+        let scalars = self.params.iter()
+            .filter_map(|p| match p.shape {
+                ParamShape::Scalar(s) => Some((p, s)),
+                ParamShape::Buffer(..) => None,
+            })
+            .collect::<Vec<_>>();
+        for &(p, s) in &scalars {
+            writeln!(src, "        let {}_values: &[{}] = &{};",
+                     p.name, p.ty, self.scalar_values(s, &p.ty)).unwrap();
+        }
+        let lens = scalars.iter()
+            .map(|(p, _)| format!("{}_values.len()", p.name))
+            .collect::<Vec<_>>();
+        if lens.is_empty() {
+            src.push_str("        let cases = 1;\n");
+        } else {
+            writeln!(src, "        let cases = {};", lens.join(" * ")).unwrap();
+        }
+        src.push_str("        for case in 0..cases {\n");
+
+        // Each case is one combination of the scalar values
+        for (i, &(p, _)) in scalars.iter().enumerate() {
+            let index = if i == 0 {
+                "case".to_owned()
+            } else if i == 1 {
+                format!("case / {}", lens[0])
+            } else {
+                format!("case / ({})", lens[..i].join(" * "))
+            };
+            writeln!(src, "            let {name} = {name}_values[{index} % {len}];",
+                     name = p.name, index = index, len = lens[i]).unwrap();
+        }
+
+        let mut c_args = Vec::new();
+        let mut rust_args = Vec::new();
+        let mut buffers = Vec::new();
+        for (i, p) in self.params.iter().enumerate() {
+            match p.shape {
+                ParamShape::Scalar(_) => {
+                    c_args.push(p.name.clone());
+                    rust_args.push(p.name.clone());
+                }
+                ParamShape::Buffer(_, mutable) => {
+                    writeln!(src, "            let mut c_{} = [0 as {}; BUFFER_LEN];",
+                             p.name, p.ty).unwrap();
+                    writeln!(src, "            for (elem, value) in \
+                                   c_{}.iter_mut().zip(buffer_values(case, {}).iter()) {{",
+                             p.name, i).unwrap();
+                    writeln!(src, "                *elem = *value as {};", p.ty).unwrap();
+                    src.push_str("            }\n");
+                    if mutable {
+                        // Only buffers the functions may write to need to be compared
+                        writeln!(src, "            let mut rust_{} = c_{};", p.name, p.name)
+                            .unwrap();
+                        c_args.push(format!("c_{}.as_mut_ptr()", p.name));
+                        rust_args.push(format!("rust_{}.as_mut_ptr()", p.name));
+                        buffers.push(&p.name);
+                    } else {
+                        c_args.push(format!("c_{}.as_ptr()", p.name));
+                        rust_args.push(format!("c_{}.as_ptr()", p.name));
+                    }
+                }
+            }
+        }
+        let msg = scalars.iter()
+            .map(|(p, _)| format!("{} = {{}}", p.name))
+            .collect::<Vec<_>>();
+        let msg_args = scalars.iter()
+            .map(|(p, _)| p.name.clone())
+            .collect::<Vec<_>>();
+        let msg = if msg.is_empty() {
+            "\"case {}\", case".to_owned()
+        } else {
+            format!("\"case {{}}: {}\", case, {}", msg.join(", "), msg_args.join(", "))
+        };
+
+        let c_call = format!("unsafe {{ {}({}) }}", self.name, c_args.join(", "));
+        let rust_call = format!("unsafe {{ super::{}({}) }}", self.name, rust_args.join(", "));
+        match self.ret {
+            Some((_, s)) => {
+                writeln!(src, "            let c_result = {};", c_call).unwrap();
+                writeln!(src, "            let rust_result = {};", rust_call).unwrap();
+                if s == Scalar::Float {
+                    // Compare the bits, so that NaNs compare equal
+                    writeln!(src, "            assert_eq!(c_result.to_bits(), \
+                                   rust_result.to_bits(), {});", msg).unwrap();
+                } else {
+                    writeln!(src, "            assert_eq!(c_result, rust_result, {});", msg)
+                        .unwrap();
+                }
+            }
+            None => {
+                writeln!(src, "            {};", c_call).unwrap();
+                writeln!(src, "            {};", rust_call).unwrap();
+            }
+        }
+        for name in &buffers {
+            writeln!(src, "            assert_eq!(c_{name}, rust_{name}, {msg});",
+                     name = name, msg = msg).unwrap();
+        }
+
+        src.push_str("        }\n    }\n");
+    }
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("test_harness_gen", |args| mk(TestHarnessGen {
+        prefix: args.get(0).cloned().unwrap_or_else(|| "c_".to_owned()),
+    }));
+}
@@ -1,17 +1,21 @@
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::mem;
 use regex::Regex;
 use rustc::hir::HirId;
+use rustc::hir::def_id::DefId;
+use rustc::ty;
 use syntax::attr;
 use syntax::ast::*;
 use syntax::source_map::DUMMY_SP;
 use syntax::mut_visit::{self, MutVisitor};
 use syntax::ptr::P;
-use syntax::symbol::Symbol;
+use syntax::symbol::{sym, Symbol};
+use syntax::visit::{self, Visitor};
 use smallvec::SmallVec;
 
 use c2rust_ast_builder::{mk, Make, IntoSymbol};
-use crate::ast_manip::{FlatMapNodes, MutVisit, AstEquiv};
+use crate::ast_manip::{FlatMapNodes, MutVisit, MutVisitNodes, AstEquiv};
 use crate::command::{CommandState, Registry};
 use crate::driver::{self, Phase};
 use crate::path_edit::fold_resolved_paths;
@@ -77,6 +81,360 @@ impl Transform for RenameRegex {
     }
 }
 
+/// # `rename_regex` Command
+///
+/// Usage: `rename_regex KIND PAT REPL`
+///
+/// Replace `PAT` (a regular expression) with `REPL` in the names of all items of kind `KIND`,
+/// which is one of `fn`, `struct`, `static`, `const` or `field` (the fields of structs and
+/// unions), and update all references to them.  `REPL` can refer to capture groups of `PAT`, as
+/// in `$1` or `${name}`.  Functions and statics exported with `#[no_mangle]` get an
+/// `#[export_name]` with their old name, so their symbols stay the same.
+///
+/// Nothing is renamed if a new name would collide with another name in the same module and
+/// namespace, or with another field of the same struct; instead, every collision is reported.
+/// Otherwise, a table of the old and new names is printed.
+///
+/// Example:
+///
+/// ```ignore
+///     fn rust_parse_header() { ... }
+///     fn main() { rust_parse_header(); }
+/// ```
+///
+/// After running `rename_regex fn '^rust_(.*)' '$1'`:
+///
+/// ```ignore
+///     fn parse_header() { ... }
+///     fn main() { parse_header(); }
+/// ```
+pub struct RenameKindRegex {
+    kind: RenameKind,
+    pattern: String,
+    repl: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum RenameKind {
+    Fn,
+    Struct,
+    Static,
+    Const,
+    Field,
+}
+
+impl RenameKind {
+    fn from_arg(arg: &str) -> RenameKind {
+        match arg {
+            "fn" => RenameKind::Fn,
+            "struct" => RenameKind::Struct,
+            "static" => RenameKind::Static,
+            "const" => RenameKind::Const,
+            "field" => RenameKind::Field,
+            _ => panic!("rename_regex: unknown item kind `{}`", arg),
+        }
+    }
+
+    fn of_item(kind: &ItemKind) -> Option<RenameKind> {
+        match *kind {
+            ItemKind::Fn(..) => Some(RenameKind::Fn),
+            ItemKind::Struct(..) => Some(RenameKind::Struct),
+            ItemKind::Static(..) => Some(RenameKind::Static),
+            ItemKind::Const(..) => Some(RenameKind::Const),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Namespace {
+    Type,
+    Value,
+    Field,
+}
+
+/// A name in the crate, which `rename_regex` may change.
+struct ScopedName {
+    id: NodeId,
+    /// The module, function or struct the name must be unique in
+    scope: NodeId,
+    namespaces: Vec<Namespace>,
+    kind: Option<RenameKind>,
+    old: Symbol,
+    new: Option<String>,
+}
+
+impl ScopedName {
+    fn final_name(&self) -> String {
+        self.new.clone().unwrap_or_else(|| self.old.to_string())
+    }
+}
+
+struct NameCollector {
+    scope: NodeId,
+    names: Vec<ScopedName>,
+}
+
+impl NameCollector {
+    fn add(&mut self, id: NodeId, scope: NodeId, ident: Ident, namespaces: Vec<Namespace>,
+           kind: Option<RenameKind>) {
+        self.names.push(ScopedName { id, scope, namespaces, kind, old: ident.name, new: None });
+    }
+}
+
+impl<'ast> Visitor<'ast> for NameCollector {
+    fn visit_item(&mut self, i: &'ast Item) {
+        let namespaces = match i.kind {
+            ItemKind::Fn(..) | ItemKind::Static(..) | ItemKind::Const(..) => {
+                vec![Namespace::Value]
+            }
+            ItemKind::Struct(VariantData::Struct(..), _) => vec![Namespace::Type],
+            ItemKind::Struct(..) => vec![Namespace::Type, Namespace::Value],
+            ItemKind::Use(..) | ItemKind::Impl(..) | ItemKind::ForeignMod(..) |
+            ItemKind::GlobalAsm(..) | ItemKind::Mac(..) | ItemKind::MacroDef(..) => vec![],
+            _ => vec![Namespace::Type],
+        };
+        if !namespaces.is_empty() {
+            self.add(i.id, self.scope, i.ident, namespaces, RenameKind::of_item(&i.kind));
+        }
+
+        match i.kind {
+            ItemKind::Struct(VariantData::Struct(ref fields, _), _) |
+            ItemKind::Union(VariantData::Struct(ref fields, _), _) => {
+                for f in fields {
+                    if let Some(ident) = f.ident {
+                        self.add(f.id, i.id, ident, vec![Namespace::Field],
+                                 Some(RenameKind::Field));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        match i.kind {
+            ItemKind::Mod(..) | ItemKind::Fn(..) => {
+                let old_scope = mem::replace(&mut self.scope, i.id);
+                visit::walk_item(self, i);
+                self.scope = old_scope;
+            }
+            _ => visit::walk_item(self, i),
+        }
+    }
+
+    fn visit_foreign_item(&mut self, i: &'ast ForeignItem) {
+        let namespace = match i.kind {
+            ForeignItemKind::Ty => Namespace::Type,
+            _ => Namespace::Value,
+        };
+        self.add(i.id, self.scope, i.ident, vec![namespace], None);
+        visit::walk_foreign_item(self, i);
+    }
+
+    fn visit_mac(&mut self, mac: &'ast Mac) {
+        visit::walk_mac(self, mac);
+    }
+}
+
+fn is_valid_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    name != "_" && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Get the field named `name` of the struct `ty` points to, looking through any references and
+/// pointers.
+pub fn struct_field_def_id(mut ty: ty::Ty, name: Ident) -> Option<DefId> {
+    while let Some(inner) = ty.builtin_deref(true) {
+        ty = inner.ty;
+    }
+    match ty.kind {
+        ty::TyKind::Adt(def, _) if !def.is_enum() => def
+            .non_enum_variant()
+            .fields
+            .iter()
+            .find(|f| f.ident.name == name.name)
+            .map(|f| f.did),
+        _ => None,
+    }
+}
+
+impl Transform for RenameKindRegex {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        let re = Regex::new(&self.pattern).unwrap();
+
+        // (1) Collect the names of the crate, along with the scopes they are in, and find the
+        // new names of the items of the requested kind.
+
+        let mut collector = NameCollector {
+            scope: CRATE_NODE_ID,
+            names: vec![],
+        };
+        visit::walk_crate(&mut collector, krate);
+        let mut names = collector.names;
+        for name in &mut names {
+            if name.kind != Some(self.kind) {
+                continue;
+            }
+            if let Cow::Owned(new) = re.replace(&name.old.as_str(), &self.repl as &str) {
+                if new.as_str() != &*name.old.as_str() {
+                    name.new = Some(new);
+                }
+            }
+        }
+
+        // (2) Check that the new names are valid and don't collide with any others, before
+        // changing anything.
+
+        let mut errors = vec![];
+        for name in names.iter().filter(|n| n.new.is_some()) {
+            let new = name.new.as_ref().unwrap();
+            if !is_valid_ident(new) {
+                errors.push(format!("`{}` would be renamed to `{}`, which is not an identifier",
+                                    name.old, new));
+            }
+        }
+
+        let mut scoped: HashMap<(NodeId, Namespace, String), Vec<&ScopedName>> = HashMap::new();
+        for name in &names {
+            for &ns in &name.namespaces {
+                scoped.entry((name.scope, ns, name.final_name())).or_insert_with(Vec::new)
+                    .push(name);
+            }
+        }
+        let mut collisions = scoped.into_iter()
+            .filter(|(_, names)| names.len() > 1 && names.iter().any(|n| n.new.is_some()))
+            .collect::<Vec<_>>();
+        collisions.sort_by_key(|((scope, _, new), _)| (*scope, new.clone()));
+        for ((scope, _, new), names) in collisions {
+            let scope = if scope == CRATE_NODE_ID {
+                "the crate root".to_owned()
+            } else {
+                format!("`{}`", cx.ty_ctxt().def_path_str(cx.node_def_id(scope)))
+            };
+            let olds = names.iter().map(|n| format!("`{}`", n.old)).collect::<Vec<_>>();
+            let error = format!("{} would all be named `{}` in {}", olds.join(", "), new, scope);
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+
+        if !errors.is_empty() {
+            eprintln!("rename_regex: aborting without renaming anything:");
+            for error in errors {
+                eprintln!("    {}", error);
+            }
+            return;
+        }
+
+        // (3) Rename the definitions.  Exported items keep their symbol names.
+
+        let renamed = names.iter()
+            .filter_map(|n| n.new.as_ref().map(|new| (n.id, (n.old, mk().ident(new)))))
+            .collect::<HashMap<_, _>>();
+        if renamed.is_empty() {
+            return;
+        }
+
+        FlatMapNodes::visit(krate, |i: P<Item>| {
+            let (old, new_ident) = match renamed.get(&i.id) {
+                Some(&rename) => rename,
+                None => return smallvec![i],
+            };
+            smallvec![i.map(|mut i| {
+                i.ident = new_ident;
+                if attr::contains_name(&i.attrs, sym::no_mangle) &&
+                   !attr::contains_name(&i.attrs, sym::export_name) {
+                    i.attrs.retain(|attr| attr.path != sym::no_mangle);
+                    i.attrs.extend(mk().str_attr(sym::export_name, old).into_attrs());
+                }
+                i
+            })]
+        });
+
+        MutVisitNodes::visit(krate, |f: &mut StructField| {
+            if let Some(&(_, new_ident)) = renamed.get(&f.id) {
+                f.ident = Some(new_ident);
+            }
+        });
+
+        // (4) Rewrite the paths and field accesses referring to them.
+
+        let item_idents = renamed.iter()
+            .map(|(&id, &(_, new_ident))| (cx.hir_map().node_to_hir_id(id), new_ident))
+            .collect::<HashMap<_, _>>();
+        fold_resolved_paths(krate, cx, |qself, mut path, def| {
+            if let Some(hir_id) = cx.res_to_hir_id(def) {
+                if let Some(new_ident) = item_idents.get(&hir_id) {
+                    path.segments.last_mut().unwrap().ident = *new_ident;
+                }
+            }
+            (qself, path)
+        });
+
+        if self.kind != RenameKind::Field {
+            return;
+        }
+        let field_idents = renamed.iter()
+            .map(|(&id, &(_, new_ident))| (cx.node_def_id(id), new_ident))
+            .collect::<HashMap<_, _>>();
+        let new_field_ident = |ty: Option<ty::Ty>, ident: Ident| {
+            let did = struct_field_def_id(ty?, ident)?;
+            field_idents.get(&did).cloned()
+        };
+
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            let ty = cx.opt_node_type(e.id);
+            match e.kind {
+                ExprKind::Field(ref base, ref mut ident) => {
+                    if let Some(new_ident) = new_field_ident(cx.opt_node_type(base.id), *ident) {
+                        *ident = new_ident;
+                    }
+                }
+                ExprKind::Struct(_, ref mut fields, _) => {
+                    for f in fields {
+                        if let Some(new_ident) = new_field_ident(ty, f.ident) {
+                            f.ident = new_ident;
+                            f.is_shorthand = false;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        });
+
+        MutVisitNodes::visit(krate, |p: &mut P<Pat>| {
+            let ty = cx.opt_node_type(p.id);
+            if let PatKind::Struct(_, ref mut fields, _) = p.kind {
+                for f in fields {
+                    if let Some(new_ident) = new_field_ident(ty, f.ident) {
+                        f.ident = new_ident;
+                        f.is_shorthand = false;
+                    }
+                }
+            }
+        });
+
+        // (5) Summarize the renames.
+
+        let mut table = names.iter()
+            .filter_map(|n| n.new.as_ref().map(|new| (n.old.to_string(), new.clone())))
+            .collect::<Vec<_>>();
+        table.sort();
+        let width = table.iter().map(|(old, _)| old.len()).max().unwrap_or(0);
+        eprintln!("rename_regex: renamed {} names:", table.len());
+        for (old, new) in table {
+            eprintln!("    {:width$} -> {}", old, new, width = width);
+        }
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
 /// # `rename_unnamed` Command
 ///
 /// Usage: `rename_unnamed`
@@ -719,6 +1077,127 @@ impl Transform for DeleteItems {
 }
 
 
+/// # `merge_impls` Command
+///
+/// Usage: `merge_impls`
+///
+/// Merge inherent `impl` blocks for the same type within each module.  Blocks are merged when
+/// their self types, generics, and `where` clauses are equivalent and they have the same `#[cfg]`
+/// attributes.  The items of each later block are moved, in order, to the end of the first
+/// block, and the emptied blocks are deleted.  Any other attributes of a later block are copied
+/// onto each of its items.  Trait impls and blocks with doc comments are left where they are.
+///
+/// Example:
+///
+/// ```ignore
+///     impl S { fn f(&self) {} }
+///     fn g() {}
+///     impl S { fn h(&self) {} }
+/// ```
+///
+/// After running `merge_impls`:
+///
+/// ```ignore
+///     impl S { fn f(&self) {} fn h(&self) {} }
+///     fn g() {}
+/// ```
+pub struct MergeImpls;
+
+impl Transform for MergeImpls {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, _cx: &RefactorCtxt) {
+        struct MergeFolder;
+
+        impl MutVisitor for MergeFolder {
+            fn visit_mod(&mut self, m: &mut Mod) {
+                merge_impls_in_mod(m);
+                mut_visit::noop_visit_mod(m, self)
+            }
+        }
+
+        krate.visit(&mut MergeFolder)
+    }
+
+    fn min_phase(&self) -> Phase {
+        // `#[cfg]` attributes that hold are removed during macro expansion, so this has to run
+        // on the unexpanded source to tell cfg-gated blocks apart.
+        Phase::Phase1
+    }
+}
+
+fn merge_impls_in_mod(m: &mut Mod) {
+    let mut items: Vec<P<Item>> = Vec::with_capacity(m.items.len());
+    // Indices into `items` of the blocks that other blocks can be merged into
+    let mut targets: Vec<usize> = Vec::new();
+
+    for i in m.items.drain(..) {
+        if !is_inherent_impl(&i) {
+            items.push(i);
+            continue;
+        }
+
+        let target = targets.iter().cloned().find(|&idx| same_impl_group(&items[idx], &i));
+        let idx = match target {
+            Some(idx) if !i.attrs.iter().any(|attr| attr.check_name(sym::doc)) => idx,
+            Some(_) => {
+                items.push(i);
+                continue;
+            }
+            None => {
+                targets.push(items.len());
+                items.push(i);
+                continue;
+            }
+        };
+
+        let Item { attrs, kind, .. } = i.into_inner();
+        let extra_attrs = attrs.into_iter()
+            .filter(|attr| !attr.check_name(sym::cfg))
+            .collect::<Vec<_>>();
+        let moved = match kind {
+            ItemKind::Impl(.., impl_items) => impl_items,
+            _ => unreachable!(),
+        };
+        if let ItemKind::Impl(.., ref mut impl_items) = items[idx].kind {
+            impl_items.extend(moved.into_iter().map(|mut ii| {
+                if !extra_attrs.is_empty() {
+                    let mut attrs = extra_attrs.clone();
+                    attrs.append(&mut ii.attrs);
+                    ii.attrs = attrs;
+                }
+                ii
+            }));
+        }
+    }
+
+    m.items = items;
+}
+
+fn is_inherent_impl(i: &Item) -> bool {
+    match i.kind {
+        ItemKind::Impl(_, ImplPolarity::Positive, _, _, None, _, _) => true,
+        _ => false,
+    }
+}
+
+/// Check if the inherent impls `a` and `b` can be merged.
+fn same_impl_group(a: &Item, b: &Item) -> bool {
+    let cfgs = |i: &Item| {
+        i.attrs.iter().filter(|attr| attr.check_name(sym::cfg)).collect::<Vec<_>>()
+    };
+    match (&a.kind, &b.kind) {
+        (&ItemKind::Impl(unsafety1, _, defaultness1, ref generics1, _, ref ty1, _),
+         &ItemKind::Impl(unsafety2, _, defaultness2, ref generics2, _, ref ty2, _)) => {
+            unsafety1 == unsafety2 &&
+                defaultness1 == defaultness2 &&
+                generics1.ast_equiv(generics2) &&
+                ty1.ast_equiv(ty2) &&
+                cfgs(a).ast_equiv(&cfgs(b))
+        }
+        _ => false,
+    }
+}
+
+
 pub fn register_commands(reg: &mut Registry) {
     use super::mk;
 
@@ -728,6 +1207,12 @@ pub fn register_commands(reg: &mut Registry) {
         filter: args.get(2).map(|x| (x as &str).into_symbol()),
     }));
 
+    reg.register("rename_regex", |args| mk(RenameKindRegex {
+        kind: RenameKind::from_arg(&args[0]),
+        pattern: args[1].clone(),
+        repl: args[2].clone(),
+    }));
+
     reg.register("rename_unnamed", |_args| mk(RenameUnnamed));
 
     reg.register("replace_items", |_args| mk(ReplaceItems));
@@ -752,5 +1237,7 @@ pub fn register_commands(reg: &mut Registry) {
     }));
 
     reg.register("delete_items", |_args| mk(DeleteItems));
+
+    reg.register("merge_impls", |_args| mk(MergeImpls));
 }
 
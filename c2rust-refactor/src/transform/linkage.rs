@@ -1,29 +1,344 @@
 use std::collections::HashMap;
+use std::fmt;
 use rustc::hir::def_id::DefId;
+use rustc::ty::{ParamEnv, Ty, TyCtxt, TyKind};
 use syntax::ast::*;
 use syntax::attr;
 use syntax::ptr::P;
 use syntax::symbol::Symbol;
 use syntax_pos::sym;
 
+use c2rust_ast_builder::mk;
 use crate::ast_manip::{FlatMapNodes, MutVisitNodes, visit_nodes};
 use crate::ast_manip::fn_edit::{visit_fns, FnKind};
 use crate::command::{CommandState, Registry};
 use crate::driver::{Phase};
-use crate::path_edit::fold_resolved_paths;
+use crate::path_edit::{fold_resolved_paths, fold_resolved_paths_with_id};
+use crate::reflect;
 use crate::transform::Transform;
 use crate::RefactorCtxt;
 
 
+/// How well the type of a declaration matches the type of the definition it is being linked
+/// to, ordered from most to least compatible.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum AbiCompat {
+    /// The types are identical.
+    Identical,
+    /// The types differ, but have the same size, alignment, and ABI class (e.g. two raw
+    /// pointer types, or two integers of the same width), so an `as` cast fixes up each use.
+    Castable,
+    /// The types have the same size and alignment but a different ABI class (e.g. an integer
+    /// and a float, or two different structs).  Linking them reinterprets the bits, which is
+    /// only done when explicitly requested with `--force`.
+    Suspicious,
+    /// The types have a different size or alignment, or the signatures have a different
+    /// number of arguments or variadicness.  These are never linked.
+    Incompatible,
+}
+
+impl fmt::Display for AbiCompat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            AbiCompat::Identical => "identical",
+            AbiCompat::Castable => "castable",
+            AbiCompat::Suspicious => "suspicious",
+            AbiCompat::Incompatible => "incompatible",
+        };
+        f.write_str(s)
+    }
+}
+
+/// The location of a type that differs between a declaration and its definition.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum TyLoc {
+    /// The type of a function argument
+    Arg(usize),
+    /// The return type of a function
+    Ret,
+    /// The type of the whole item
+    Whole,
+}
+
+/// The result of comparing the type of a declaration against the type of a definition.
+pub struct AbiReport<'tcx> {
+    /// The declaration being linked.
+    pub old_def: DefId,
+    /// The definition it is linked to.
+    pub new_def: DefId,
+    /// The least compatible classification of any of the differences.
+    pub compat: AbiCompat,
+    /// Every type that differs, as `(location, decl type, def type, classification)`.
+    pub diffs: Vec<(TyLoc, Ty<'tcx>, Ty<'tcx>, AbiCompat)>,
+    /// Human-readable explanations of the differences.
+    pub notes: Vec<String>,
+}
+
+impl<'tcx> AbiReport<'tcx> {
+    fn new(old_def: DefId, new_def: DefId) -> AbiReport<'tcx> {
+        AbiReport {
+            old_def,
+            new_def,
+            compat: AbiCompat::Identical,
+            diffs: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    fn add_note(&mut self, compat: AbiCompat, note: String) {
+        self.compat = self.compat.max(compat);
+        self.notes.push(note);
+    }
+
+    fn add_diff(&mut self, loc: TyLoc, old_ty: Ty<'tcx>, new_ty: Ty<'tcx>, compat: AbiCompat) {
+        self.compat = self.compat.max(compat);
+        self.diffs.push((loc, old_ty, new_ty, compat));
+    }
+
+    /// Check whether the declaration may be linked to the definition.
+    pub fn allows_linking(&self, force: bool) -> bool {
+        match self.compat {
+            AbiCompat::Identical | AbiCompat::Castable => true,
+            AbiCompat::Suspicious => force,
+            AbiCompat::Incompatible => false,
+        }
+    }
+
+    /// Log a summary of the report for `symbol`, with the spans of both declarations, followed
+    /// by the details of each difference.  Castable differences are logged as info, the others
+    /// as warnings.  Identical symbols are not reported.
+    pub fn log(&self, tcx: TyCtxt<'tcx>, cmd: &str, symbol: Symbol, force: bool) {
+        if self.compat == AbiCompat::Identical {
+            return;
+        }
+        let action = if self.allows_linking(force) { "linking" } else { "not linking" };
+        let source_map = tcx.sess.source_map();
+        let mut lines = vec![
+            format!("{}: {}: {} ({})", cmd, symbol, self.compat, action),
+            format!("    declaration: {}", source_map.span_to_string(tcx.def_span(self.old_def))),
+            format!("    definition: {}", source_map.span_to_string(tcx.def_span(self.new_def))),
+        ];
+        for note in &self.notes {
+            lines.push(format!("    {}", note));
+        }
+        for &(loc, old_ty, new_ty, compat) in &self.diffs {
+            lines.push(format!("    {:?}: {:?} -> {:?} ({})", loc, old_ty, new_ty, compat));
+        }
+        if self.compat == AbiCompat::Castable {
+            info!("{}", lines.join("\n"));
+        } else {
+            warn!("{}", lines.join("\n"));
+        }
+    }
+
+    /// Look up the type change at `loc`, if there is one.
+    pub fn diff(&self, loc: TyLoc) -> Option<(Ty<'tcx>, Ty<'tcx>, AbiCompat)> {
+        self.diffs.iter()
+            .find(|&&(l, _, _, _)| l == loc)
+            .map(|&(_, old_ty, new_ty, compat)| (old_ty, new_ty, compat))
+    }
+}
+
+/// Classify the ABI compatibility of two types by comparing their layouts.
+fn classify_tys<'tcx>(tcx: TyCtxt<'tcx>, old_ty: Ty<'tcx>, new_ty: Ty<'tcx>) -> AbiCompat {
+    let old_ty = tcx.normalize_erasing_regions(ParamEnv::empty(), old_ty);
+    let new_ty = tcx.normalize_erasing_regions(ParamEnv::empty(), new_ty);
+    if old_ty == new_ty {
+        return AbiCompat::Identical;
+    }
+
+    let (old_layout, new_layout) = match (tcx.layout_of(ParamEnv::reveal_all().and(old_ty)),
+                                          tcx.layout_of(ParamEnv::reveal_all().and(new_ty))) {
+        (Ok(x), Ok(y)) => (x, y),
+        // We can't tell what the layout of (at least) one of the types is, e.g. because it's
+        // an extern type.
+        _ => return AbiCompat::Suspicious,
+    };
+
+    if old_layout.size != new_layout.size || old_layout.align.abi != new_layout.align.abi {
+        return AbiCompat::Incompatible;
+    }
+
+    // Fn pointers are not included: `as` can't convert a data pointer or a fn pointer of
+    // another type into one, so those differences need a `transmute`.
+    fn is_ptr(ty: Ty) -> bool {
+        match ty.kind {
+            TyKind::RawPtr(..) | TyKind::Ref(..) => true,
+            _ => false,
+        }
+    }
+
+    fn is_int(ty: Ty) -> bool {
+        match ty.kind {
+            TyKind::Int(..) | TyKind::Uint(..) | TyKind::Bool | TyKind::Char => true,
+            _ => false,
+        }
+    }
+
+    match (&old_ty.kind, &new_ty.kind) {
+        _ if is_ptr(old_ty) && is_ptr(new_ty) => AbiCompat::Castable,
+        _ if is_int(old_ty) && is_int(new_ty) => AbiCompat::Castable,
+        (&TyKind::Float(..), &TyKind::Float(..)) => AbiCompat::Castable,
+        // Pointer/integer and integer/float conversions are allowed by `as`, but change how the
+        // value is passed or what it means.  Fn pointers of different types are not castable.
+        _ => AbiCompat::Suspicious,
+    }
+}
+
+/// Compare the type of the declaration `old_did` against the type of the definition
+/// `new_did` it would be linked to.
+pub fn check_abi<'tcx>(tcx: TyCtxt<'tcx>, old_did: DefId, new_did: DefId) -> AbiReport<'tcx> {
+    let mut report = AbiReport::new(old_did, new_did);
+    let old_ty = tcx.type_of(old_did);
+    let new_ty = tcx.type_of(new_did);
+
+    let is_fn = |ty: Ty| match ty.kind {
+        TyKind::FnDef(..) => true,
+        _ => false,
+    };
+
+    if !is_fn(old_ty) || !is_fn(new_ty) {
+        if is_fn(old_ty) != is_fn(new_ty) {
+            report.add_note(AbiCompat::Incompatible,
+                            "one symbol is a function and the other is not".to_owned());
+        } else {
+            let compat = classify_tys(tcx, old_ty, new_ty);
+            if compat != AbiCompat::Identical {
+                report.add_diff(TyLoc::Whole, old_ty, new_ty, compat);
+            }
+        }
+        return report;
+    }
+
+    let old_sig = tcx.fn_sig(old_did);
+    let new_sig = tcx.fn_sig(new_did);
+    let (old_sig, new_sig) = match (old_sig.no_bound_vars(), new_sig.no_bound_vars()) {
+        (Some(x), Some(y)) => (x, y),
+        _ => {
+            report.add_note(AbiCompat::Incompatible,
+                            "old or new sig had late-bound regions".to_owned());
+            return report;
+        }
+    };
+
+    if old_sig.inputs().len() != new_sig.inputs().len() {
+        report.add_note(AbiCompat::Incompatible,
+                        format!("argument count differs: {} vs {}",
+                                old_sig.inputs().len(), new_sig.inputs().len()));
+        return report;
+    }
+
+    if old_sig.c_variadic != new_sig.c_variadic {
+        report.add_note(AbiCompat::Incompatible, "variadicness differs".to_owned());
+        return report;
+    }
+
+    for (i, (&old_ty, &new_ty)) in old_sig.inputs().iter()
+                                          .zip(new_sig.inputs().iter()).enumerate() {
+        let compat = classify_tys(tcx, old_ty, new_ty);
+        if compat != AbiCompat::Identical {
+            report.add_diff(TyLoc::Arg(i), old_ty, new_ty, compat);
+        }
+    }
+
+    let old_ty = old_sig.output();
+    let new_ty = new_sig.output();
+    let compat = classify_tys(tcx, old_ty, new_ty);
+    if compat != AbiCompat::Identical {
+        report.add_diff(TyLoc::Ret, old_ty, new_ty, compat);
+    }
+
+    report
+}
+
+/// Convert `e` from `from_ty` to `to_ty`, using a cast for castable types and a `transmute`
+/// for suspicious ones.
+pub fn convert_abi_ty<'tcx>(tcx: TyCtxt<'tcx>,
+                            e: P<Expr>,
+                            from_ty: Ty<'tcx>,
+                            to_ty: Ty<'tcx>,
+                            compat: AbiCompat) -> P<Expr> {
+    let to_ty_ast = reflect::reflect_tcx_ty(tcx, to_ty);
+    match compat {
+        AbiCompat::Identical => e,
+        AbiCompat::Castable => mk().cast_expr(e, to_ty_ast),
+        AbiCompat::Suspicious | AbiCompat::Incompatible => {
+            let from_ty_ast = reflect::reflect_tcx_ty(tcx, from_ty);
+            let transmute = mk().path_segment_with_args(
+                "transmute", mk().angle_bracketed_args(vec![from_ty_ast, to_ty_ast]));
+            let path = mk().path_expr(vec![
+                mk().path_segment(""),
+                mk().path_segment("std"),
+                mk().path_segment("mem"),
+                transmute,
+            ]);
+            mk().call_expr(path, vec![e])
+        }
+    }
+}
+
+/// Add conversions to every expression that referred to a replaced declaration, as described
+/// by the declaration's `AbiReport`.  `path_ids` maps the `NodeId` of each rewritten path expr
+/// to the `DefId` of the declaration it previously referred to.
+pub fn fix_abi_uses<'tcx>(krate: &mut Crate,
+                          tcx: TyCtxt<'tcx>,
+                          path_ids: &HashMap<NodeId, DefId>,
+                          reports: &HashMap<DefId, AbiReport<'tcx>>) {
+    MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+        if let Some(report) = path_ids.get(&e.id).and_then(|did| reports.get(did)) {
+            // This whole expr was a reference to the old declaration.  See if we need a
+            // conversion around the whole thing.  (This should only be true for statics.)
+            if let Some((old_ty, new_ty, compat)) = report.diff(TyLoc::Whole) {
+                // The rewritten expr has type `new_ty`, but its context expects `old_ty`.
+                *e = convert_abi_ty(tcx, e.clone(), new_ty, old_ty, compat);
+            }
+        }
+
+        let report = match e.kind {
+            ExprKind::Call(ref f, _) => path_ids.get(&f.id).and_then(|did| reports.get(did)),
+            _ => None,
+        };
+        if let Some(report) = report {
+            // This expr is a call to a rewritten fn.  Add conversions around args and around
+            // the whole expression.
+            expect!([e.kind] ExprKind::Call(_, ref mut args) => {
+                for (i, arg) in args.iter_mut().enumerate() {
+                    if let Some((old_ty, new_ty, compat)) = report.diff(TyLoc::Arg(i)) {
+                        // The new fn requires `new_ty`, where the old one needed `old_ty`.
+                        *arg = convert_abi_ty(tcx, arg.clone(), old_ty, new_ty, compat);
+                    }
+                }
+            });
+
+            if let Some((old_ty, new_ty, compat)) = report.diff(TyLoc::Ret) {
+                // The new fn returns `new_ty`, where the old context requires `old_ty`.
+                *e = convert_abi_ty(tcx, e.clone(), new_ty, old_ty, compat);
+            }
+        }
+    });
+}
+
+
 /// # `link_funcs` Command
 /// 
-/// Usage: `link_funcs`
+/// Usage: `link_funcs [--force]`
 /// 
 /// Link up function declarations and definitions with matching symbols across
 /// modules.  For every foreign `fn` whose symbol matches a `fn` definition
 /// elsewhere in the program, it replaces all uses of the foreign `fn` with a
 /// direct call of the `fn` definition, and deletes the foreign `fn`.
 /// 
+/// The signatures of the declaration and definition are compared first, and a
+/// report is logged for each symbol whose signatures differ:
+/// 
+///  * Castable differences (e.g. `*mut c_void` vs `*mut Foo`, or `c_uint` vs
+///    `c_int`) are fixed up by inserting casts around arguments and return values.
+///  * Suspicious differences (same size and alignment, but e.g. an integer passed
+///    where a float is expected, or a raw pointer where a fn pointer is expected)
+///    are only linked with `--force`, in which case the values are `transmute`d.
+///  * Incompatible differences (different sizes, argument counts, or
+///    variadicness) are never linked.
+/// 
 /// Example:
 /// 
 /// ```ignore
@@ -60,10 +375,14 @@ use crate::RefactorCtxt;
 ///         }
 ///     }
 /// ```
-pub struct LinkFuncs;
+pub struct LinkFuncs {
+    pub force: bool,
+}
 
 impl Transform for LinkFuncs {
     fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        let tcx = cx.ty_ctxt();
+
         // (1) Find all `#[no_mangle]` or `#[export_name=...]` functions, and index them by symbol.
         // (2) Find all extern fns, and index them by def_id.
         let mut symbol_to_def = HashMap::new();
@@ -82,29 +401,43 @@ impl Transform for LinkFuncs {
             }
         });
 
-        // (3) Adjust references to extern fns to refer to the `#[no_mangle]` definition instead.
-        fold_resolved_paths(krate, cx, |qself, path, def| {
+        // (3) Check that each extern fn is ABI-compatible with the definition it would be
+        // linked to, and decide which ones to link.
+        let mut extern_symbols = extern_def_to_symbol.iter().collect::<Vec<_>>();
+        extern_symbols.sort_by_key(|&(_, &symbol)| symbol.to_string());
+        let mut reports = HashMap::new();
+        for (&def_id, &symbol) in extern_symbols {
+            if let Some(&real_def_id) = symbol_to_def.get(&symbol) {
+                let report = check_abi(tcx, def_id, real_def_id);
+                report.log(tcx, "link_funcs", symbol, self.force);
+                if report.allows_linking(self.force) {
+                    reports.insert(def_id, report);
+                }
+            }
+        }
+
+        // (4) Adjust references to extern fns to refer to the `#[no_mangle]` definition instead.
+        let mut path_ids = HashMap::new();
+        fold_resolved_paths_with_id(krate, cx, |id, qself, path, def| {
             if let Some(def_id) = def.opt_def_id() {
-                if let Some(&symbol) = extern_def_to_symbol.get(&def_id) {
-                    if let Some(&real_def_id) = symbol_to_def.get(&symbol) {
-                        return (None, cx.def_path(real_def_id));
-                    }
+                if reports.contains_key(&def_id) {
+                    let symbol = extern_def_to_symbol[&def_id];
+                    path_ids.insert(id, def_id);
+                    return (None, cx.def_path(symbol_to_def[&symbol]));
                 }
             }
             (qself, path)
         });
 
-        // (4) Remove unused externs
+        // (5) Insert casts where the signatures differ
+        fix_abi_uses(krate, tcx, &path_ids, &reports);
+
+        // (6) Remove unused externs
         MutVisitNodes::visit(krate, |fm: &mut ForeignMod| {
             fm.items.retain(|i| {
                 let def_id = cx.node_def_id(i.id);
-                // Drop any items that resolve to a symbol in another module.
-                if let Some(&symbol) = extern_def_to_symbol.get(&def_id) {
-                    if let Some(&_real_def_id) = symbol_to_def.get(&symbol) {
-                        return false;
-                    }
-                }
-                true
+                // Drop any items that were linked to a symbol in another module.
+                !reports.contains_key(&def_id)
             });
         });
     }
@@ -336,7 +669,9 @@ impl Transform for CanonicalizeStructs {
 pub fn register_commands(reg: &mut Registry) {
     use super::mk;
 
-    reg.register("link_funcs", |_args| mk(LinkFuncs));
+    reg.register("link_funcs", |args| mk(LinkFuncs {
+        force: args.iter().any(|a| a == "--force"),
+    }));
     reg.register("link_incomplete_types", |_args| mk(LinkIncompleteTypes));
     reg.register("canonicalize_structs", |_args| mk(CanonicalizeStructs));
 }
@@ -18,6 +18,13 @@ pub trait Transform {
         // Most transforms should run on expanded code.
         Phase::Phase2
     }
+
+    /// Return whether casts that the transform made redundant should be removed afterward.  If
+    /// so, and the transform runs in `Phase3`, casts whose operand already has the cast type
+    /// are removed once it finishes.  The default is `true`.
+    fn cleanup_casts(&self) -> bool {
+        true
+    }
 }
 
 /// Adapter for turning a `Transform` into a `Command`.
@@ -30,6 +37,10 @@ impl<T: Transform> Command for TransformCommand<T> {
                 self.0.transform(&mut *st.krate_mut(), st, cx)
             })
             .expect("Failed to run compiler");
+
+        if self.0.min_phase() == Phase::Phase3 && self.0.cleanup_casts() {
+            casts::remove_cast_to_self_type(state);
+        }
     }
 }
 
@@ -49,24 +60,43 @@ macro_rules! transform_modules {
 }
 
 transform_modules! {
+    allocs,
+    bans,
+    bitfields,
+    bools,
+    bounds,
+    buffers,
+    callbacks,
     canonicalize_refs,
     casts,
     char_literals,
     control_flow,
+    dispatch,
+    docs,
     externs,
     format,
     funcs,
     generics,
+    harness,
     ionize,
     items,
     linkage,
     literals,
     reorganize_definitions,
     ownership,
+    refcounts,
     retype,
+    rotates,
     rewrite,
+    safe_api,
     statics,
+    stdio,
+    strings,
     structs,
     test,
+    transmutes,
+    variadic,
     vars,
 }
+
+pub(crate) mod util;
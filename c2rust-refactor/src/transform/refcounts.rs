@@ -0,0 +1,754 @@
+use std::collections::HashSet;
+use rustc::hir::HirId;
+use rustc::hir::def_id::DefId;
+use rustc::ty;
+use syntax::ast::*;
+use syntax::ptr::P;
+use syntax::visit::{self, Visitor};
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{FlatMapNodes, MutVisitNodes, par_collect_nodes, visit_nodes};
+use crate::ast_manip::util::remove_derives;
+use crate::command::{CommandState, Registry};
+use crate::driver::{Phase, parse_items};
+use crate::transform::util::{stmt_expr, strip_casts};
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `refcount_to_rc` Command
+///
+/// Usage: `refcount_to_rc`
+///
+/// Convert structs with a hand-written reference count, marked `#[refcount_field]` by the
+/// transpiler's `--detect-refcounts`, to `Rc<RefCell<_>>`:
+///
+///  * pointers to the struct become `Rc<RefCell<_>>`s, which are cloned wherever a pointer was
+///    passed on or copied, and `(*p).f` becomes `p.borrow().f` or `p.borrow_mut().f`,
+///  * `malloc` of the struct becomes `Rc::new(RefCell::new(mem::zeroed()))`,
+///  * updates of the count are removed, and
+///  * the function that decrements the count becomes one that drops its argument.  What it
+///    freed along with the object when the count reached zero moves to a `Drop` impl, and a
+///    destructor only called from there is removed.
+///
+/// Structs are only converted if all of their uses can be: pointers to them may only be stored
+/// in locals, passed to functions of the crate, returned, and dereferenced to access fields; the
+/// count may only be touched by the increments, the decrements and the check guarding the
+/// release; and the crate must not create threads.
+///
+/// Example:
+///
+/// ```ignore
+///     pub struct obj {
+///         #[refcount_field]
+///         pub refs: libc::c_int,
+///         pub data: *mut libc::c_char,
+///     }
+///     unsafe fn obj_retain(mut o: *mut obj) -> *mut obj {
+///         (*o).refs += 1;
+///         return o;
+///     }
+///     unsafe fn obj_release(mut o: *mut obj) {
+///         (*o).refs -= 1;
+///         if (*o).refs == 0 as libc::c_int {
+///             free((*o).data as *mut libc::c_void);
+///             free(o as *mut libc::c_void);
+///         };
+///     }
+/// ```
+///
+/// After running `refcount_to_rc`:
+///
+/// ```ignore
+///     pub struct obj {
+///         pub data: *mut libc::c_char,
+///     }
+///     impl Drop for obj {
+///         fn drop(&mut self) {
+///             unsafe {
+///                 free(self.data as *mut libc::c_void);
+///             }
+///         }
+///     }
+///     unsafe fn obj_retain(mut o: ::std::rc::Rc<::std::cell::RefCell<obj>>)
+///                          -> ::std::rc::Rc<::std::cell::RefCell<obj>> {
+///         return o;
+///     }
+///     unsafe fn obj_release(mut o: ::std::rc::Rc<::std::cell::RefCell<obj>>) {
+///         ::std::mem::drop(o);
+///     }
+/// ```
+pub struct RefcountToRc;
+
+impl Transform for RefcountToRc {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        let mut targets = vec![];
+        visit_nodes(krate, |i: &Item| {
+            if let ItemKind::Struct(VariantData::Struct(ref fields, _), _) = i.kind {
+                let refcount = fields.iter().find(|f| f.attrs.iter().any(is_refcount_attr));
+                if let Some(field) = refcount.and_then(|f| f.ident) {
+                    targets.push(Target {
+                        def_id: cx.node_def_id(i.id),
+                        name: i.ident,
+                        field,
+                    });
+                }
+            }
+        });
+
+        for target in targets {
+            match RcPlan::new(cx, krate, &target) {
+                Ok(plan) => plan.rewrite(cx, krate, &target),
+                Err(reason) => eprintln!("refcount_to_rc: {}: {}", target.name, reason),
+            }
+        }
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+fn is_refcount_attr(attr: &Attribute) -> bool {
+    match &attr.path.to_string() as &str {
+        "refcount_field" | "c2rust::refcount_field" => true,
+        _ => false,
+    }
+}
+
+/// A struct with a reference count field.
+struct Target {
+    def_id: DefId,
+    name: Ident,
+    field: Ident,
+}
+
+/// The `if (*p).refs == 0 { ... }` that releases the object.
+struct Release {
+    stmt: NodeId,
+    /// The function it is in, which drops its argument after the rewrite
+    func: DefId,
+    /// The released pointer
+    var: Ident,
+    /// Statements moving to the `Drop` impl
+    drop_stmts: Vec<Stmt>,
+    /// A destructor called by the release
+    dtor: Option<DefId>,
+    /// The released pointer and the parameter of the destructor, which become `self`
+    vars: Vec<HirId>,
+}
+
+/// The rewrites converting one struct.
+#[derive(Default)]
+struct RcPlan {
+    release: Option<Release>,
+    /// Increments and initializations of the count
+    removed: HashSet<NodeId>,
+    /// `(*p).f` that are read, and written
+    reads: HashSet<NodeId>,
+    writes: HashSet<NodeId>,
+    /// Pointers that are copied
+    clones: HashSet<NodeId>,
+    /// `malloc(...) as *mut S`
+    allocs: HashSet<NodeId>,
+    /// Assignments to a field whose value reads one, which must be evaluated first to avoid
+    /// borrowing the `RefCell` twice
+    hoisted: HashSet<NodeId>,
+}
+
+impl RcPlan {
+    fn new(cx: &RefactorCtxt, krate: &Crate, target: &Target) -> Result<RcPlan, String> {
+        let mut plan = RcPlan::default();
+
+        // Pointers may only appear in functions
+        let mut errors = par_collect_nodes(krate, |i: &Item| {
+            let kind = match i.kind {
+                ItemKind::Fn(..) | ItemKind::Mod(..) | ItemKind::Use(..) |
+                ItemKind::ExternCrate(..) | ItemKind::Mac(..) | ItemKind::MacroDef(..) => {
+                    return None
+                }
+                ItemKind::ForeignMod(..) => "foreign items",
+                _ => "items other than functions",
+            };
+            if mentions_ptr(cx, i, target.def_id) {
+                Some(format!("pointers to it are used in {}, such as `{}`", kind, i.ident))
+            } else {
+                None
+            }
+        });
+        if let Some(error) = errors.pop() {
+            return Err(error);
+        }
+
+        let mut spawns = false;
+        visit_nodes(krate, |e: &Expr| {
+            if let ExprKind::Path(_, ref path) = e.kind {
+                let name = path.segments.last().unwrap().ident.as_str();
+                if name == "pthread_create" || name == "thrd_create" || name == "spawn" {
+                    spawns = true;
+                }
+            }
+        });
+        if spawns {
+            return Err("the crate creates threads".to_owned());
+        }
+
+        // Find the release and the destructor it calls
+        let mut releases = par_collect_nodes(krate, |i: &Item| match i.kind {
+            ItemKind::Fn(ref decl, _, _, ref body) => {
+                find_release(cx, krate, target, i, decl, body)
+            }
+            _ => None,
+        });
+        let release = match releases.len() {
+            0 => return Err("found no release guarded by a check of the count".to_owned()),
+            1 => releases.pop().unwrap()?,
+            _ => return Err("found more than one release".to_owned()),
+        };
+
+        let mut checker = UseChecker {
+            cx,
+            target,
+            release: &release,
+            plan: &mut plan,
+            allowed: HashSet::new(),
+            moved: HashSet::new(),
+            error: None,
+        };
+        visit_nodes(krate, |i: &Item| {
+            if let ItemKind::Fn(_, _, _, ref body) = i.kind {
+                let def_id = cx.node_def_id(i.id);
+                if release.dtor == Some(def_id) {
+                    return;
+                }
+                checker.visit_block(body);
+            }
+        });
+        if let Some(error) = checker.error {
+            return Err(error);
+        }
+
+        plan.release = Some(release);
+        Ok(plan)
+    }
+
+    fn rewrite(self, cx: &RefactorCtxt, krate: &mut Crate, target: &Target) {
+        let release = self.release.as_ref().unwrap();
+        let rc_path = |ty: P<Ty>| {
+            mk().path_ty(vec![
+                mk().path_segment(""),
+                mk().path_segment("std"),
+                mk().path_segment("rc"),
+                mk().path_segment_with_args(
+                    "Rc",
+                    mk().angle_bracketed_args(vec![mk().path_ty(vec![
+                        mk().path_segment(""),
+                        mk().path_segment("std"),
+                        mk().path_segment("cell"),
+                        mk().path_segment_with_args("RefCell", mk().angle_bracketed_args(vec![ty])),
+                    ])]),
+                ),
+            ])
+        };
+
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            if self.reads.contains(&e.id) || self.writes.contains(&e.id) {
+                let method = if self.writes.contains(&e.id) { "borrow_mut" } else { "borrow" };
+                let (ptr, field) = expect!([e.kind] ExprKind::Field(ref base, field) =>
+                                           (deref_ptr(base).unwrap().clone(), field));
+                *e = mk().field_expr(mk().method_call_expr(ptr, method, Vec::<P<Expr>>::new()),
+                                     field);
+            } else if self.clones.contains(&e.id) {
+                *e = mk().method_call_expr(e.clone(), "clone", Vec::<P<Expr>>::new());
+            } else if self.allocs.contains(&e.id) {
+                let zeroed = mk().call_expr(mk().path_expr(vec!["", "std", "mem", "zeroed"]),
+                                            Vec::<P<Expr>>::new());
+                let cell = mk().call_expr(mk().path_expr(vec!["", "std", "cell", "RefCell", "new"]),
+                                          vec![zeroed]);
+                *e = mk().call_expr(mk().path_expr(vec!["", "std", "rc", "Rc", "new"]), vec![cell]);
+            }
+        });
+
+        FlatMapNodes::visit(krate, |s: Stmt| {
+            if self.removed.contains(&s.id) {
+                return smallvec![];
+            }
+            if s.id == release.stmt {
+                let drop = mk().call_expr(mk().path_expr(vec!["", "std", "mem", "drop"]),
+                                          vec![mk().ident_expr(release.var)]);
+                return smallvec![mk().semi_stmt(drop)];
+            }
+            let e = match stmt_expr(&s) {
+                Some(e) if self.hoisted.contains(&e.id) => e,
+                _ => return smallvec![s],
+            };
+            match e.kind {
+                ExprKind::Assign(ref lhs, ref rhs) | ExprKind::AssignOp(_, ref lhs, ref rhs) => {
+                    let name = match lhs.kind {
+                        ExprKind::Field(_, field) => format!("new_{}", field),
+                        _ => "new_value".to_owned(),
+                    };
+                    let local = mk().local_stmt(P(mk().local(mk().ident_pat(&name), None::<P<Ty>>,
+                                                             Some(rhs.clone()))));
+                    let mut assign = e.clone();
+                    match assign.kind {
+                        ExprKind::Assign(_, ref mut rhs) |
+                        ExprKind::AssignOp(_, _, ref mut rhs) => *rhs = mk().ident_expr(&name),
+                        _ => unreachable!(),
+                    }
+                    smallvec![local, mk().semi_stmt(assign)]
+                }
+                _ => smallvec![s],
+            }
+        });
+
+        MutVisitNodes::visit(krate, |t: &mut P<Ty>| {
+            let pointee = match t.kind {
+                TyKind::Ptr(ref mty) if cx.try_resolve_ty(&mty.ty) == Some(target.def_id) => {
+                    mty.ty.clone()
+                }
+                _ => return,
+            };
+            *t = rc_path(pointee);
+        });
+
+        // Move the rest of the release into a `Drop` impl, and drop the count and destructor
+        let drop_stmts = self_field_stmts(cx, release);
+        FlatMapNodes::visit(krate, |i: P<Item>| {
+            if let Some(dtor) = release.dtor {
+                if cx.hir_map().opt_local_def_id_from_node_id(i.id) == Some(dtor) {
+                    return smallvec![];
+                }
+            }
+            if cx.hir_map().opt_local_def_id_from_node_id(i.id) != Some(target.def_id) {
+                return smallvec![i];
+            }
+
+            let has_drop = !drop_stmts.is_empty();
+            let i = i.map(|mut i| {
+                if let ItemKind::Struct(VariantData::Struct(ref mut fields, _), _) = i.kind {
+                    fields.retain(|f| f.ident != Some(target.field));
+                }
+                // `Drop` types can't be `Copy`
+                if has_drop {
+                    remove_derives(&mut i.attrs, &["Copy"]);
+                }
+                i
+            });
+            if !has_drop {
+                return smallvec![i];
+            }
+
+            let mut drop_impl = parse_items(
+                cx.session(),
+                &format!("impl Drop for {} {{ fn drop(&mut self) {{ unsafe {{}} }} }}",
+                         target.name),
+            ).pop().unwrap();
+            MutVisitNodes::visit(&mut drop_impl, |b: &mut P<Block>| {
+                if let BlockCheckMode::Unsafe(_) = b.rules {
+                    b.stmts = drop_stmts.clone();
+                }
+            });
+            smallvec![i, drop_impl]
+        });
+    }
+}
+
+/// Check whether `i` mentions a pointer to the struct `def_id`.
+fn mentions_ptr(cx: &RefactorCtxt, i: &Item, def_id: DefId) -> bool {
+    let mut found = false;
+    visit_nodes(i, |t: &Ty| {
+        if let TyKind::Ptr(ref mty) = t.kind {
+            if cx.try_resolve_ty(&mty.ty) == Some(def_id) {
+                found = true;
+            }
+        }
+    });
+    found
+}
+
+fn is_target_ptr(target: &Target, ty: ty::Ty) -> bool {
+    match ty.kind {
+        ty::TyKind::RawPtr(ty::TypeAndMut { ty, .. }) => match ty.kind {
+            ty::TyKind::Adt(def, _) => def.did == target.def_id,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_target_ptr_expr(cx: &RefactorCtxt, target: &Target, e: &Expr) -> bool {
+    cx.opt_node_type(e.id).map_or(false, |ty| is_target_ptr(target, ty))
+}
+
+/// If `e` is `*p` or `(*p)`, get `p`.
+fn deref_ptr(e: &P<Expr>) -> Option<&P<Expr>> {
+    match e.kind {
+        ExprKind::Paren(ref inner) => deref_ptr(inner),
+        ExprKind::Unary(UnOp::Deref, ref ptr) => Some(ptr),
+        _ => None,
+    }
+}
+
+/// If `e` is `(*p).field` for a pointer to the target, get `p` and the field.
+fn target_field<'a>(cx: &RefactorCtxt,
+                    target: &Target,
+                    e: &'a Expr) -> Option<(&'a P<Expr>, Ident)> {
+    match e.kind {
+        ExprKind::Field(ref base, field) => {
+            let ptr = deref_ptr(base)?;
+            if is_target_ptr_expr(cx, target, ptr) {
+                Some((ptr, field))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// If `e` is `(*p).refs` for the count of the target, get `p`.
+fn count_ptr<'a>(cx: &RefactorCtxt, target: &Target, e: &'a Expr) -> Option<&'a P<Expr>> {
+    match target_field(cx, target, strip_casts(e)) {
+        Some((ptr, field)) if field == target.field => Some(ptr),
+        _ => None,
+    }
+}
+
+fn is_int_lit(e: &Expr, value: Option<u128>) -> bool {
+    match strip_casts(e).kind {
+        ExprKind::Lit(ref lit) => match lit.kind {
+            LitKind::Int(n, _) => value.map_or(true, |value| n == value),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Check whether `stmt` increments or initializes the count.
+fn is_count_update(cx: &RefactorCtxt, target: &Target, stmt: &Stmt) -> bool {
+    match stmt_expr(stmt).map(|e| &e.kind) {
+        Some(ExprKind::AssignOp(op, lhs, rhs)) => {
+            (op.node == BinOpKind::Add || op.node == BinOpKind::Sub)
+                && count_ptr(cx, target, lhs).is_some()
+                && is_int_lit(rhs, Some(1))
+        }
+        Some(ExprKind::Assign(lhs, rhs)) => {
+            count_ptr(cx, target, lhs).is_some() && is_int_lit(rhs, None)
+        }
+        _ => false,
+    }
+}
+
+/// If `e` calls `free` on the local `var`, possibly cast, or calls a function of the crate
+/// with `var` as its only argument, get the callee.
+fn free_call(cx: &RefactorCtxt, var: HirId, e: &Expr) -> Option<Option<DefId>> {
+    let (func, args) = match_or!([e.kind] ExprKind::Call(ref func, ref args) => (func, args);
+                                 return None);
+    if args.len() != 1 || cx.try_resolve_expr_to_hid(strip_casts(&args[0])) != Some(var) {
+        return None;
+    }
+    let callee = cx.try_resolve_expr(func)?;
+    let tcx = cx.ty_ctxt();
+    if tcx.is_foreign_item(callee) && tcx.item_name(callee).as_str() == "free" {
+        Some(None)
+    } else if callee.is_local() && !cx.ty_ctxt().is_foreign_item(callee) {
+        Some(Some(callee))
+    } else {
+        None
+    }
+}
+
+/// Find the `if (*p).refs == 0 { ... }` statement at the end of the function `i`, and what it
+/// frees along with the object.
+fn find_release(cx: &RefactorCtxt,
+                krate: &Crate,
+                target: &Target,
+                i: &Item,
+                decl: &FnDecl,
+                body: &Block) -> Option<Result<Release, String>> {
+    let stmt = body.stmts.iter().find(|s| match stmt_expr(s).map(|e| &e.kind) {
+        Some(ExprKind::If(cond, _, None)) => match cond.kind {
+            ExprKind::Binary(op, ref lhs, ref rhs) if op.node == BinOpKind::Eq => {
+                count_ptr(cx, target, lhs).is_some() && is_int_lit(rhs, Some(0))
+            }
+            _ => false,
+        },
+        _ => false,
+    })?;
+    let (cond, then) = expect!([stmt_expr(stmt).unwrap().kind]
+                               ExprKind::If(ref cond, ref then, None) => (cond, then));
+    Some((|| {
+        if body.stmts.last().map(|s| s.id) != Some(stmt.id) {
+            return Err(format!("`{}` uses the object after releasing it", i.ident));
+        }
+
+        let ptr = expect!([cond.kind] ExprKind::Binary(_, ref lhs, _) =>
+                          count_ptr(cx, target, lhs).unwrap());
+        let var_id = cx.try_resolve_expr_to_hid(ptr);
+        let param = decl.inputs.iter().find(|arg| {
+            Some(cx.hir_map().node_to_hir_id(arg.pat.id)) == var_id
+        });
+        let var = match param.map(|arg| &arg.pat.kind) {
+            Some(PatKind::Ident(_, ident, None)) => *ident,
+            _ => return Err(format!("`{}` releases an object it was not passed", i.ident)),
+        };
+        let var_id = var_id.unwrap();
+
+        let mut drop_stmts = vec![];
+        let mut dtor = None;
+        let mut vars = vec![var_id];
+        let mut freed = false;
+        for s in &then.stmts {
+            match stmt_expr(s).and_then(|e| free_call(cx, var_id, e)) {
+                Some(None) if !freed => freed = true,
+                Some(Some(callee)) if !freed => {
+                    let (stmts, param) = dtor_stmts(cx, krate, callee).ok_or_else(|| {
+                        format!("`{}` releases the object by calling `{}`, which doesn't free it",
+                                i.ident, cx.ty_ctxt().def_path_str(callee))
+                    })?;
+                    drop_stmts.extend(stmts);
+                    vars.push(param);
+                    dtor = Some(callee);
+                    freed = true;
+                }
+                _ => drop_stmts.push(s.clone()),
+            }
+        }
+        if !freed {
+            return Err(format!("`{}` doesn't free the object it releases", i.ident));
+        }
+
+        Ok(Release {
+            stmt: stmt.id,
+            func: cx.node_def_id(i.id),
+            var,
+            drop_stmts,
+            dtor,
+            vars,
+        })
+    })())
+}
+
+/// Get the statements of the destructor `def_id` other than the one freeing its only
+/// parameter, along with that parameter.
+fn dtor_stmts(cx: &RefactorCtxt, krate: &Crate, def_id: DefId) -> Option<(Vec<Stmt>, HirId)> {
+    let mut found = None;
+    visit_nodes(krate, |i: &Item| {
+        if cx.hir_map().opt_local_def_id_from_node_id(i.id) != Some(def_id) {
+            return;
+        }
+        if let ItemKind::Fn(ref decl, _, _, ref body) = i.kind {
+            if decl.inputs.len() == 1 {
+                found = Some((cx.hir_map().node_to_hir_id(decl.inputs[0].pat.id), body.clone()));
+            }
+        }
+    });
+    let (param, body) = found?;
+
+    let mut freed = false;
+    let stmts = body.stmts.iter().filter(|s| {
+        let frees = !freed && stmt_expr(s).and_then(|e| free_call(cx, param, e)) == Some(None);
+        freed |= frees;
+        !frees
+    }).cloned().collect();
+    if freed {
+        Some((stmts, param))
+    } else {
+        None
+    }
+}
+
+/// Rewrite the statements moving to the `Drop` impl to access the fields of `self` instead of
+/// those of the released object.
+fn self_field_stmts(cx: &RefactorCtxt, release: &Release) -> Vec<Stmt> {
+    let mut stmts = release.drop_stmts.clone();
+    MutVisitNodes::visit(&mut stmts, |e: &mut P<Expr>| {
+        let field = match e.kind {
+            ExprKind::Field(ref base, field) => match deref_ptr(base) {
+                Some(ptr) if cx.try_resolve_expr_to_hid(ptr).map_or(false, |hid| {
+                    release.vars.contains(&hid)
+                }) => field,
+                _ => return,
+            },
+            _ => return,
+        };
+        *e = mk().field_expr(mk().ident_expr("self"), field);
+    });
+    stmts
+}
+
+/// Check that every pointer to the target and every access to its count can be rewritten,
+/// collecting the rewrites into the plan.
+struct UseChecker<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    target: &'a Target,
+    release: &'a Release,
+    plan: &'a mut RcPlan,
+    /// Pointer expressions in a position where they can be rewritten
+    allowed: HashSet<NodeId>,
+    /// Pointers that are moved instead of cloned
+    moved: HashSet<NodeId>,
+    error: Option<String>,
+}
+
+impl<'a, 'tcx> UseChecker<'a, 'tcx> {
+    fn fail(&mut self, reason: String) {
+        if self.error.is_none() {
+            self.error = Some(reason);
+        }
+    }
+
+    fn is_ptr(&self, e: &Expr) -> bool {
+        is_target_ptr_expr(self.cx, self.target, e)
+    }
+
+    /// Allow the pointer `e` to be copied, or moved if `moved` is set.
+    fn allow(&mut self, e: &Expr, moved: bool) {
+        if self.is_ptr(e) {
+            self.allowed.insert(e.id);
+            if moved {
+                self.moved.insert(e.id);
+            }
+        }
+    }
+}
+
+impl<'a, 'tcx, 'ast> Visitor<'ast> for UseChecker<'a, 'tcx> {
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        if s.id == self.release.stmt {
+            return;
+        }
+        if is_count_update(self.cx, self.target, s) {
+            self.plan.removed.insert(s.id);
+            return;
+        }
+        visit::walk_stmt(self, s);
+    }
+
+    fn visit_local(&mut self, l: &'ast Local) {
+        if let Some(ref init) = l.init {
+            self.allow(init, false);
+        }
+        visit::walk_local(self, l);
+    }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        let cx = self.cx;
+
+        if let Some((ptr, field)) = target_field(cx, self.target, e) {
+            if field == self.target.field {
+                self.fail("the count is used other than to count references".to_owned());
+            }
+            if !self.plan.writes.contains(&e.id) {
+                self.plan.reads.insert(e.id);
+            }
+            self.allowed.insert(ptr.id);
+            self.moved.insert(ptr.id);
+            self.visit_expr(ptr);
+            return;
+        }
+
+        match e.kind {
+            ExprKind::Assign(ref lhs, ref rhs) | ExprKind::AssignOp(_, ref lhs, ref rhs) => {
+                let mut place = &**lhs;
+                while let ExprKind::Index(ref base, _) | ExprKind::Paren(ref base) = place.kind {
+                    place = base;
+                }
+                if target_field(cx, self.target, place).is_some() {
+                    self.plan.writes.insert(place.id);
+                    let reads = self.plan.reads.len();
+                    self.visit_expr(rhs);
+                    if self.plan.reads.len() != reads {
+                        self.plan.hoisted.insert(e.id);
+                    }
+                    self.visit_expr(lhs);
+                    return;
+                }
+                if self.is_ptr(lhs) {
+                    self.allow(lhs, true);
+                    self.allow(rhs, false);
+                }
+            }
+            ExprKind::Call(ref func, ref args) => {
+                let callee = cx.try_resolve_expr(func);
+                if callee.is_some() && self.release.dtor == callee {
+                    self.fail("its destructor is called outside of the release".to_owned());
+                }
+                let is_release = callee == Some(self.release.func);
+                let is_local_fn = callee.map_or(false, |callee| {
+                    callee.is_local() && !cx.ty_ctxt().is_foreign_item(callee)
+                });
+                if is_local_fn {
+                    for arg in args {
+                        let moved = is_release && matches!([arg.kind] ExprKind::Path(None, _));
+                        self.allow(arg, moved);
+                    }
+                }
+                if self.is_ptr(e) && is_local_fn {
+                    self.allowed.insert(e.id);
+                }
+            }
+            ExprKind::MethodCall(_, ref args) => {
+                for arg in &args[1..] {
+                    self.allow(arg, false);
+                }
+            }
+            ExprKind::AddrOf(Mutability::Mutable, ref inner) => {
+                if target_field(cx, self.target, inner).is_some() {
+                    self.fail(format!("a field of it is borrowed mutably: `{}`",
+                                      syntax::print::pprust::expr_to_string(e)));
+                }
+            }
+            ExprKind::Ret(Some(ref val)) => self.allow(val, true),
+            ExprKind::Paren(ref inner) if self.allowed.contains(&e.id) => {
+                self.allow(inner, self.moved.contains(&e.id));
+            }
+            ExprKind::Cast(ref inner, _) if self.is_ptr(e) => {
+                let is_alloc = match strip_casts(inner).kind {
+                    ExprKind::Call(ref func, _) => cx.try_resolve_expr(func).map_or(false, |f| {
+                        cx.ty_ctxt().is_foreign_item(f) && {
+                            let name = cx.ty_ctxt().item_name(f);
+                            name.as_str() == "malloc" || name.as_str() == "calloc"
+                        }
+                    }),
+                    _ => false,
+                };
+                if is_alloc && self.allowed.contains(&e.id) {
+                    self.plan.allocs.insert(e.id);
+                    return;
+                }
+            }
+            _ => {}
+        }
+
+        if self.is_ptr(e) {
+            if !self.allowed.contains(&e.id) {
+                self.fail(format!("unsupported use of a pointer to it: `{}`",
+                                  syntax::print::pprust::expr_to_string(e)));
+            } else if matches!([e.kind] ExprKind::Path(None, _)) && !self.moved.contains(&e.id) {
+                self.plan.clones.insert(e.id);
+            }
+        }
+        if let Some(ty) = cx.opt_node_type(e.id) {
+            if let ty::TyKind::Adt(def, _) = ty.kind {
+                if def.did == self.target.def_id {
+                    self.fail(format!("it is used by value: `{}`",
+                                      syntax::print::pprust::expr_to_string(e)));
+                }
+            }
+        }
+
+        visit::walk_expr(self, e);
+    }
+
+    fn visit_mac(&mut self, mac: &'ast Mac) {
+        visit::walk_mac(self, mac);
+    }
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("refcount_to_rc", |_args| mk(RefcountToRc));
+}
@@ -1,6 +1,10 @@
 use derive_more::From;
 use indexmap::IndexMap;
+use json::{self, JsonValue};
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path as FsPath, PathBuf};
 
 use crate::transform::Transform;
 use rustc::hir::def::{Namespace, PerNS};
@@ -25,7 +29,7 @@ use c2rust_ast_printer::pprust::{foreign_item_to_string, item_to_string};
 
 /// # `reoganize_definitions` Command
 ///
-/// Usage: `reorganize_definitions`
+/// Usage: `reorganize_definitions [NAME_MAP]`
 ///
 /// This refactoring operates on code transpiled with the
 /// `--reorganize-definitions` flag.
@@ -33,7 +37,17 @@ use c2rust_ast_printer::pprust::{foreign_item_to_string, item_to_string};
 /// This pass refactors a crate to de-duplicate declarations, move them into
 /// their relevant modules and import the items as needed, rather than using
 /// extern forward declarations for all types and functions in headers.
-pub struct ReorganizeDefinitions;
+///
+/// Definitions marked `#[internal_linkage]`, which the transpiler adds to C
+/// `static` functions and variables, are never merged with another
+/// translation unit's definition of the same name. When two of them end up in
+/// the same module, the later one is renamed after the module of its
+/// translation unit, e.g., the `helper` from `foo.c` becomes `helper_foo`. If
+/// `NAME_MAP` is given, these renamings are read from and recorded in that
+/// JSON file, so later runs keep the same names.
+pub struct ReorganizeDefinitions {
+    name_map: Option<PathBuf>,
+}
 
 /// Holds the information of the current `Crate`, which includes a `HashMap` to look up Items
 /// quickly, as well as other members that hold important information.
@@ -44,6 +58,8 @@ pub struct Reorganizer<'a, 'tcx: 'a> {
     modules: HashMap<Ident, ModuleInfo>,
 
     path_mapping: HashMap<DefId, (Path, NodeId)>,
+
+    name_map: &'a RefCell<NameMap>,
 }
 
 /// A ModuleInfo captures all information about a module that is needed to
@@ -66,12 +82,17 @@ struct ModuleInfo {
 }
 
 impl<'a, 'tcx> Reorganizer<'a, 'tcx> {
-    fn new(st: &'a CommandState, cx: &'a RefactorCtxt<'a, 'tcx>) -> Self {
+    fn new(
+        st: &'a CommandState,
+        cx: &'a RefactorCtxt<'a, 'tcx>,
+        name_map: &'a RefCell<NameMap>,
+    ) -> Self {
         Reorganizer {
             st,
             cx,
             modules: HashMap::new(),
             path_mapping: HashMap::new(),
+            name_map,
         }
     }
 
@@ -157,6 +178,7 @@ impl<'a, 'tcx> Reorganizer<'a, 'tcx> {
         FlatMapNodes::visit(krate, |mut item: P<Item>| {
             if let Some((_path, include_line)) = parse_source_header(&item.attrs) {
                 let header_item = item.clone();
+                let unit = self.unit_ident(&header_item);
                 if let ItemKind::Mod(module) = &mut item.kind {
                     module.items.retain(|item| {
                         let (dest_module_id, dest_module_ident) =
@@ -165,9 +187,9 @@ impl<'a, 'tcx> Reorganizer<'a, 'tcx> {
 
                         // Move the item to the `module_items` mapping.
                         let items = module_items.entry(dest_module_id).or_insert_with(|| {
-                            ModuleDefines::new(self.cx, dest_module_info.clone())
+                            ModuleDefines::new(self.cx, dest_module_info.clone(), self.name_map)
                         });
-                        let header_info = HeaderInfo::new(header_item.ident, include_line);
+                        let header_info = HeaderInfo::new(header_item.ident, include_line, unit);
                         let new_ident = match items.insert(item.clone(), Some(header_info)) {
                             // We moved the item, potentially renaming (if unnamed)
                             Ok(Some(ident)) => ident,
@@ -223,6 +245,17 @@ impl<'a, 'tcx> Reorganizer<'a, 'tcx> {
         })
     }
 
+    /// The ident of the module of the translation unit that included `header_item`, or of the
+    /// header itself if it is at the crate root.
+    fn unit_ident(&self, header_item: &Item) -> Ident {
+        let path = self.cx.def_path(self.cx.node_def_id(header_item.id));
+        let mut parents = path.segments.iter().rev().skip(1);
+        match parents.next() {
+            Some(seg) if seg.ident.name != kw::Crate && seg.ident.name != kw::PathRoot => seg.ident,
+            _ => header_item.ident,
+        }
+    }
+
     /// Add items in `module_items` to their respective modules and create any
     /// new modules.
     fn move_items(&mut self, krate: &mut Crate, mut module_items: HashMap<NodeId, ModuleDefines>) {
@@ -267,10 +300,12 @@ impl<'a, 'tcx> Reorganizer<'a, 'tcx> {
             smallvec![item]
         });
 
-        // Remove src_loc attributes
+        // Remove src_loc and internal_linkage attributes
         FlatMapNodes::visit(krate, |mut item: P<Item>| {
-            item.attrs
-                .retain(|attr| !attr.check_name(Symbol::intern("src_loc")));
+            item.attrs.retain(|attr| {
+                !attr.check_name(Symbol::intern("src_loc"))
+                    && !attr.check_name(Symbol::intern("internal_linkage"))
+            });
             smallvec![item]
         });
         FlatMapNodes::visit(krate, |mut item: ForeignItem| {
@@ -385,15 +420,81 @@ impl<'a, 'tcx> Reorganizer<'a, 'tcx> {
 struct HeaderInfo {
     ident: Ident,
     include_line: usize,
+    /// Module of the translation unit that included the header
+    unit: Ident,
 }
 
 impl HeaderInfo {
-    fn new(ident: Ident, include_line: usize) -> Self {
+    fn new(ident: Ident, include_line: usize, unit: Ident) -> Self {
         Self {
             ident,
             include_line,
+            unit,
+        }
+    }
+}
+
+/// New names of definitions with internal linkage that were renamed to keep them apart from
+/// the definitions of other translation units, by translation unit module and old name.
+#[derive(Debug, Default)]
+struct NameMap {
+    names: IndexMap<String, IndexMap<String, String>>,
+}
+
+impl NameMap {
+    /// Read the names recorded in `path` by an earlier run, if there are any.
+    fn load(path: &FsPath) -> Self {
+        let mut name_map = NameMap::default();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return name_map,
+        };
+        let js = match json::parse(&text) {
+            Ok(js) => js,
+            Err(e) => {
+                warn!("Ignoring invalid name map {}: {}", path.display(), e);
+                return name_map;
+            }
+        };
+        for (unit, names) in js.entries() {
+            let unit_names = name_map
+                .names
+                .entry(unit.to_owned())
+                .or_insert_with(IndexMap::new);
+            for (old, new) in names.entries() {
+                if let Some(new) = new.as_str() {
+                    unit_names.insert(old.to_owned(), new.to_owned());
+                }
+            }
+        }
+        name_map
+    }
+
+    fn save(&self, path: &FsPath) {
+        let mut js = JsonValue::new_object();
+        for (unit, names) in &self.names {
+            let mut unit_js = JsonValue::new_object();
+            for (old, new) in names {
+                unit_js[old.as_str()] = new.as_str().into();
+            }
+            js[unit.as_str()] = unit_js;
+        }
+        if let Err(e) = fs::write(path, json::stringify_pretty(js, 2)) {
+            warn!("Could not write name map {}: {}", path.display(), e);
         }
     }
+
+    /// Get the new name of the definition `ident` from the translation unit `unit`, picking
+    /// `<ident>_<unit>` unless an earlier run recorded another one.
+    fn rename(&mut self, unit: Ident, ident: Ident) -> Ident {
+        let new = self
+            .names
+            .entry(unit.to_string())
+            .or_insert_with(IndexMap::new)
+            .entry(ident.to_string())
+            .or_insert_with(|| format!("{}_{}", ident, unit));
+        Ident::from_str(new)
+    }
 }
 
 impl ModuleInfo {
@@ -525,10 +626,15 @@ struct ModuleDefines<'a, 'tcx: 'a> {
     impls: Vec<P<Item>>,
     // Set of imported definition NodeIds that must be made pub(crate) at least
     imports: HashSet<HirId>,
+    name_map: &'a RefCell<NameMap>,
 }
 
 impl<'a, 'tcx> ModuleDefines<'a, 'tcx> {
-    pub fn new(cx: &'a RefactorCtxt<'a, 'tcx>, info: ModuleInfo) -> Self {
+    pub fn new(
+        cx: &'a RefactorCtxt<'a, 'tcx>,
+        info: ModuleInfo,
+        name_map: &'a RefCell<NameMap>,
+    ) -> Self {
         Self {
             cx,
             info,
@@ -536,6 +642,7 @@ impl<'a, 'tcx> ModuleDefines<'a, 'tcx> {
             unnamed_items: PerNS::default(),
             impls: Vec::new(),
             imports: HashSet::new(),
+            name_map,
         }
     }
 
@@ -667,6 +774,17 @@ impl<'a, 'tcx> ModuleDefines<'a, 'tcx> {
             return Ok(ident);
         }
 
+        // Definitions with internal linkage belong to their own translation unit, so they are
+        // never unified with the definition of another unit
+        if has_internal_linkage(&new.attrs) {
+            let collides = self.idents[ns].get(&ident).map_or(false, |existing| {
+                has_internal_linkage(existing.kind.attrs())
+            });
+            if collides {
+                return self.insert_internal(ns, ident, new, parent_header);
+            }
+        }
+
         match self.idents[ns].get_mut(&ident) {
             Some(existing_decl) => match &mut existing_decl.kind {
                 DeclKind::Item(existing_item) => match (&existing_item.kind, &new.kind) {
@@ -746,6 +864,31 @@ impl<'a, 'tcx> ModuleDefines<'a, 'tcx> {
         }
     }
 
+    /// Insert a definition with internal linkage whose name another unit's definition already
+    /// took, renaming it after its own unit. Helper for `insert_ident`.
+    fn insert_internal(
+        &mut self,
+        ns: Namespace,
+        ident: Ident,
+        mut new: P<Item>,
+        parent_header: Option<HeaderInfo>,
+    ) -> Result<Ident, String> {
+        let unit = parent_header
+            .as_ref()
+            .map_or(self.info.ident, |header| header.unit);
+        let new_ident = self.name_map.borrow_mut().rename(unit, ident);
+        if self.idents[ns].contains_key(&new_ident) {
+            return Err(format!(
+                "Could not rename {} from {} to {}, which is already defined",
+                ident, unit, new_ident
+            ));
+        }
+
+        new.ident = new_ident;
+        self.idents[ns].insert(new_ident, MovedDecl::new(new, parent_header));
+        Ok(new_ident)
+    }
+
     /// Insert a foreign item with the given ident into a namespace. Helper for
     /// `insert_foreign`.
     fn insert_ident_foreign(
@@ -961,6 +1104,11 @@ fn foreign_equiv(foreign: &ForeignItem, item: &Item) -> bool {
     }
 }
 
+/// Check if the item has the `#[internal_linkage]` attribute of C `static` definitions
+fn has_internal_linkage(attrs: &[Attribute]) -> bool {
+    attr::contains_name(attrs, Symbol::intern("internal_linkage"))
+}
+
 /// Check if the `Item` has the `#[header_src = "/some/path"]` attribute
 fn has_source_header(attrs: &[Attribute]) -> bool {
     attr::contains_name(attrs, Symbol::intern("header_src"))
@@ -1000,8 +1148,17 @@ fn is_std(attrs: &[Attribute]) -> bool {
 
 impl Transform for ReorganizeDefinitions {
     fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
-        let mut reorg = Reorganizer::new(st, cx);
-        reorg.run(krate)
+        let name_map = RefCell::new(
+            self.name_map
+                .as_ref()
+                .map_or_else(NameMap::default, |path| NameMap::load(path)),
+        );
+        let mut reorg = Reorganizer::new(st, cx, &name_map);
+        reorg.run(krate);
+
+        if let Some(path) = &self.name_map {
+            name_map.borrow().save(path);
+        }
     }
 
     fn min_phase(&self) -> Phase {
@@ -1012,5 +1169,9 @@ impl Transform for ReorganizeDefinitions {
 pub fn register_commands(reg: &mut Registry) {
     use super::mk;
 
-    reg.register("reorganize_definitions", |_args| mk(ReorganizeDefinitions))
+    reg.register("reorganize_definitions", |args| {
+        mk(ReorganizeDefinitions {
+            name_map: args.get(0).map(PathBuf::from),
+        })
+    })
 }
@@ -0,0 +1,165 @@
+use rustc::ty::TyKind;
+use syntax::ast::*;
+use syntax::ptr::P;
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{AstEquiv, MutVisitNodes};
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `convert_rotates` Command
+///
+/// Usage: `convert_rotates`
+///
+/// Replace the rotate idioms of C code, `(x << n) | (x >> (W - n))` and
+/// `(x >> n) | (x << (W - n))` for an unsigned integer `x` of `W` bits, with
+/// `x.rotate_left(n)` and `x.rotate_right(n)`:
+///
+///  * the two halves may also be combined with `^` or `+`, and in either order
+///  * constant shift amounts only have to add up to `W`, as in `(x >> 2) | (x << 30)`, which
+///    becomes a rotate by the smaller amount
+///
+/// Unlike the shifts, which overflow when `n` is `0` or `W`, the rotates are defined for every
+/// `n`.  Signed and `usize` operands are left alone, and so are rotates whose `x` or `n` has side
+/// effects, since they are evaluated once instead of twice.
+pub struct ConvertRotates;
+
+impl Transform for ConvertRotates {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            if let Some(new_e) = rotate(cx, e) {
+                *e = new_e;
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+fn rotate(cx: &RefactorCtxt, e: &Expr) -> Option<P<Expr>> {
+    let (lhs, rhs) = match e.kind {
+        ExprKind::Binary(op, ref lhs, ref rhs) => match op.node {
+            BinOpKind::BitOr | BinOpKind::BitXor | BinOpKind::Add => (lhs, rhs),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let (x, left, right) = match (shift(lhs)?, shift(rhs)?) {
+        ((BinOpKind::Shl, x, left), (BinOpKind::Shr, x2, right))
+        | ((BinOpKind::Shr, x2, right), (BinOpKind::Shl, x, left))
+            if x.ast_equiv(x2) =>
+        {
+            (x, left, right)
+        }
+        _ => return None,
+    };
+    if !is_pure(x) {
+        return None;
+    }
+    let bits = match cx.opt_node_type(x.id)?.kind {
+        TyKind::Uint(uty) => uty.bit_width()? as u128,
+        _ => return None,
+    };
+
+    let (method, amount) = if is_complement(right, left, bits) {
+        ("rotate_left", left)
+    } else if is_complement(left, right, bits) {
+        ("rotate_right", right)
+    } else {
+        let (l, r) = (int_value(left)?, int_value(right)?);
+        if l + r != bits {
+            return None;
+        }
+        if r < l {
+            ("rotate_right", right)
+        } else {
+            ("rotate_left", left)
+        }
+    };
+    if !is_pure(amount) {
+        return None;
+    }
+
+    // The rotate methods take the amount as a `u32`
+    let amount = if let Some(n) = int_value(amount) {
+        mk().lit_expr(mk().int_lit(n, LitIntType::Unsuffixed))
+    } else {
+        let is_u32 = match cx.opt_node_type(amount.id).map(|ty| &ty.kind) {
+            Some(TyKind::Uint(UintTy::U32)) => true,
+            _ => false,
+        };
+        if is_u32 {
+            amount.clone()
+        } else {
+            mk().cast_expr(amount.clone(), mk().ident_ty("u32"))
+        }
+    };
+    Some(mk().id(e.id).span(e.span).method_call_expr(x.clone(), method, vec![amount]))
+}
+
+/// If `e` is a shift, return the operator, the shifted value and the shift amount.
+fn shift(e: &P<Expr>) -> Option<(BinOpKind, &P<Expr>, &P<Expr>)> {
+    match strip_parens(e).kind {
+        ExprKind::Binary(op, ref lhs, ref rhs)
+            if op.node == BinOpKind::Shl || op.node == BinOpKind::Shr =>
+        {
+            Some((op.node, strip_parens(lhs), strip_parens(rhs)))
+        }
+        _ => None,
+    }
+}
+
+/// Check if `e` is `bits - n`.
+fn is_complement(e: &Expr, n: &P<Expr>, bits: u128) -> bool {
+    match e.kind {
+        ExprKind::Binary(op, ref lhs, ref rhs) if op.node == BinOpKind::Sub => {
+            int_value(lhs) == Some(bits) && strip_parens(rhs).ast_equiv(n)
+        }
+        _ => false,
+    }
+}
+
+/// Check if `e` can be evaluated once instead of twice.
+fn is_pure(e: &Expr) -> bool {
+    match e.kind {
+        ExprKind::Path(..) | ExprKind::Lit(..) => true,
+        ExprKind::Paren(ref e)
+        | ExprKind::Field(ref e, _)
+        | ExprKind::Cast(ref e, _)
+        | ExprKind::Unary(_, ref e) => is_pure(e),
+        ExprKind::Index(ref base, ref index) => is_pure(base) && is_pure(index),
+        ExprKind::Binary(_, ref lhs, ref rhs) => is_pure(lhs) && is_pure(rhs),
+        _ => false,
+    }
+}
+
+/// Get the value of an integer literal, which may be cast, e.g. `32 as libc::c_int`.
+fn int_value(e: &Expr) -> Option<u128> {
+    match e.kind {
+        ExprKind::Paren(ref inner) | ExprKind::Cast(ref inner, _) => int_value(inner),
+        ExprKind::Lit(ref lit) => match lit.kind {
+            LitKind::Int(i, _) => Some(i),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn strip_parens(e: &P<Expr>) -> &P<Expr> {
+    match e.kind {
+        ExprKind::Paren(ref inner) => strip_parens(inner),
+        _ => e,
+    }
+}
+
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("convert_rotates", |_| mk(ConvertRotates));
+}
@@ -0,0 +1,302 @@
+use std::fmt::Write;
+use rustc::hir::Mutability;
+use rustc::ty::{self, TyKind};
+use rustc_target::spec::abi::Abi;
+use syntax::ast::*;
+use syntax::print::pprust;
+
+use crate::ast_manip::MutVisitNodes;
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `safe_api` Command
+///
+/// Usage: `safe_api [ERR_EXPR]`
+///
+/// Marks: `target`, `status`
+///
+/// Generate a safe wrapper for each `pub unsafe extern "C"` function marked `target`, in a
+/// `pub mod safe` next to it, so that a library can offer a safe API while its translated
+/// internals are left as they are.  Each wrapper takes safe versions of the parameters,
+/// converts them back, and makes the one unsafe call to the original function:
+///
+///  * a pointer to bytes or other scalars followed by an integer parameter is taken to be a
+///    buffer and its length, and becomes one `&[T]` or `&mut [T]` parameter, with `T` being
+///    `u8` for any bytes,
+///  * any other `*const c_char` is taken to be a C string, and becomes a `&CStr`,
+///  * any other pointer to a scalar or a struct may be null, and becomes an `Option<&T>` or
+///    `Option<&mut T>`, and
+///  * scalars are passed unchanged.
+///
+/// Other parameters, such as `void` pointers or function pointers, are passed unchanged, which
+/// makes the wrapper an `unsafe fn`.  They are listed in the output.
+///
+/// Functions that are also marked `status` return a status code.  Their wrappers return a
+/// `Result<(), Errno>` instead, computed by `ERR_EXPR` from the status code in `__status`.  The
+/// default is `if __status == 0 { Ok(()) } else { Err(Errno(__status)) }`.  `Errno` is a
+/// newtype around the status type generated in the `safe` module.
+///
+/// Example:
+///
+/// ```ignore
+///     #[no_mangle]
+///     pub unsafe extern "C" fn parse(mut s: *const c_char, mut out: *mut c_uint) -> c_int { ... }
+/// ```
+///
+/// After running `safe_api` with `parse` marked both `target` and `status`:
+///
+/// ```ignore
+///     pub mod safe {
+///         use super::*;
+///
+///         #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///         pub struct Errno(pub c_int);
+///
+///         pub fn parse(s: &::std::ffi::CStr, out: Option<&mut c_uint>) -> Result<(), Errno> {
+///             let __status = unsafe {
+///                 super::parse(s.as_ptr(), out.map_or(::std::ptr::null_mut(), |p| p as *mut c_uint))
+///             };
+///             if __status == 0 { Ok(()) } else { Err(Errno(__status)) }
+///         }
+///     }
+/// ```
+pub struct SafeApi {
+    err_expr: String,
+}
+
+impl Transform for SafeApi {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |m: &mut Mod| {
+            let mut wrappers = Vec::new();
+            for item in &m.items {
+                if !st.marked(item.id, "target") {
+                    continue;
+                }
+                match fn_wrapper(cx, item, st.marked(item.id, "status")) {
+                    Ok(wrapper) => {
+                        for param in &wrapper.unsafe_params {
+                            eprintln!("safe_api: {}: no safe type for parameter `{}`, so the \
+                                       wrapper is unsafe", wrapper.name, param);
+                        }
+                        wrappers.push(wrapper);
+                    }
+                    Err(reason) => eprintln!("safe_api: {}: {}", item.ident, reason),
+                }
+            }
+            if wrappers.is_empty() {
+                return;
+            }
+            if m.items.iter().any(|i| i.ident.as_str() == "safe") {
+                eprintln!("safe_api: a `safe` item already exists next to {}",
+                          wrappers.iter().map(|w| &w.name[..]).collect::<Vec<_>>().join(", "));
+                return;
+            }
+
+            let src = safe_module(&self.err_expr, &wrappers);
+            m.items.extend(st.parse_items(cx, &src));
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+struct FnWrapper {
+    name: String,
+    /// The parameters of the wrapper
+    params: Vec<String>,
+    /// The arguments passed to the original function, converted from the parameters
+    args: Vec<String>,
+    ret: Option<String>,
+    /// Whether the return value is a status code to convert to a `Result`
+    status: bool,
+    /// The parameters without a safe type, which make the wrapper unsafe
+    unsafe_params: Vec<String>,
+}
+
+fn is_scalar(ty: ty::Ty) -> bool {
+    match ty.kind {
+        TyKind::Int(_) | TyKind::Uint(_) | TyKind::Float(_) | TyKind::Bool | TyKind::Char => true,
+        _ => false,
+    }
+}
+
+fn is_byte(ty: ty::Ty) -> bool {
+    match ty.kind {
+        TyKind::Int(IntTy::I8) | TyKind::Uint(UintTy::U8) => true,
+        _ => false,
+    }
+}
+
+/// Check if pointers to `ty` can be replaced with references, i.e., `ty` is a scalar or a
+/// struct, but not `c_void`.
+fn is_referent(cx: &RefactorCtxt, ty: ty::Ty) -> bool {
+    match ty.kind {
+        TyKind::Adt(def, _) => !cx.ty_ctxt().def_path_str(def.did).ends_with("c_void"),
+        _ => is_scalar(ty),
+    }
+}
+
+/// Check that `item` is a `pub unsafe extern "C"` function, and work out its wrapper.
+fn fn_wrapper(cx: &RefactorCtxt, item: &Item, status: bool) -> Result<FnWrapper, String> {
+    let (decl, header, generics) = match item.kind {
+        ItemKind::Fn(ref decl, ref header, ref generics, _) => (decl, header, generics),
+        _ => return Err("not a function".to_owned()),
+    };
+    if !matches!([item.vis.node] VisibilityKind::Public) || header.unsafety != Unsafety::Unsafe ||
+       header.abi != Abi::C || decl.c_variadic() || !generics.params.is_empty() {
+        return Err("not a non-variadic `pub unsafe extern \"C\"` function".to_owned());
+    }
+
+    let tcx = cx.ty_ctxt();
+    let sig = tcx.fn_sig(cx.node_def_id(item.id));
+    let sig = sig.skip_binder();
+    let inputs = decl.inputs.iter().zip(sig.inputs().iter()).collect::<Vec<_>>();
+
+    let mut wrapper = FnWrapper {
+        name: item.ident.to_string(),
+        params: Vec::new(),
+        args: Vec::new(),
+        ret: None,
+        status,
+        unsafe_params: Vec::new(),
+    };
+    let param_name = |i: usize| match inputs[i].0.pat.kind {
+        PatKind::Ident(_, ident, None) => ident.to_string(),
+        _ => format!("arg{}", i),
+    };
+    let mut i = 0;
+    while i < inputs.len() {
+        let (arg, &ty) = inputs[i];
+        let name = param_name(i);
+        let decl_ty = pprust::ty_to_string(&arg.ty);
+        let (mt, pointee) = match (&ty.kind, &arg.ty.kind) {
+            (&TyKind::RawPtr(mt), &syntax::ast::TyKind::Ptr(ref ast_mt)) => (mt, &ast_mt.ty),
+            _ if is_scalar(ty) => {
+                wrapper.params.push(format!("{}: {}", name, decl_ty));
+                wrapper.args.push(name);
+                i += 1;
+                continue;
+            }
+            _ => {
+                wrapper.unsafe_param(name, decl_ty);
+                i += 1;
+                continue;
+            }
+        };
+        let mutable = mt.mutbl == Mutability::MutMutable;
+        let (ref_, as_ptr, null) = if mutable {
+            ("&mut ", "as_mut_ptr", "null_mut")
+        } else {
+            ("&", "as_ptr", "null")
+        };
+
+        // A buffer and its length
+        let len_ty = inputs.get(i + 1)
+            .filter(|&&(_, &len_ty)| len_ty.is_integral())
+            .map(|&(len_arg, _)| pprust::ty_to_string(&len_arg.ty));
+        if let Some(len_ty) = len_ty.filter(|_| is_scalar(mt.ty)) {
+            let mut ptr = format!("{}.{}()", name, as_ptr);
+            let elem_ty = if is_byte(mt.ty) {
+                if !matches!([mt.ty.kind] TyKind::Uint(UintTy::U8)) {
+                    ptr = format!("{} as {}", ptr, decl_ty);
+                }
+                "u8".to_owned()
+            } else {
+                pprust::ty_to_string(pointee)
+            };
+            wrapper.params.push(format!("{}: {}[{}]", name, ref_, elem_ty));
+            wrapper.args.push(ptr);
+            wrapper.args.push(format!("{}.len() as {}", name, len_ty));
+            i += 2;
+            continue;
+        }
+
+        if is_byte(mt.ty) && !mutable {
+            let mut ptr = format!("{}.as_ptr()", name);
+            if !matches!([mt.ty.kind] TyKind::Int(IntTy::I8)) {
+                ptr = format!("{} as {}", ptr, decl_ty);
+            }
+            wrapper.params.push(format!("{}: &::std::ffi::CStr", name));
+            wrapper.args.push(ptr);
+        } else if is_referent(cx, mt.ty) {
+            wrapper.params.push(format!("{}: Option<{}{}>", name, ref_,
+                                        pprust::ty_to_string(pointee)));
+            wrapper.args.push(format!("{}.map_or(::std::ptr::{}(), |p| p as {})",
+                                      name, null, decl_ty));
+        } else {
+            wrapper.unsafe_param(name, decl_ty);
+        }
+        i += 1;
+    }
+
+    if let FunctionRetTy::Ty(ref ret_ty) = decl.output {
+        wrapper.ret = Some(pprust::ty_to_string(ret_ty));
+    }
+    if status && !sig.output().is_integral() {
+        return Err("a status code must be an integer".to_owned());
+    }
+
+    Ok(wrapper)
+}
+
+impl FnWrapper {
+    fn unsafe_param(&mut self, name: String, decl_ty: String) {
+        let param = format!("{}: {}", name, decl_ty);
+        self.params.push(param.clone());
+        self.args.push(name);
+        self.unsafe_params.push(param);
+    }
+
+    fn write(&self, err_expr: &str, src: &mut String) {
+        let is_unsafe = !self.unsafe_params.is_empty();
+        let ret = if self.status {
+            " -> Result<(), Errno>".to_owned()
+        } else {
+            self.ret.as_ref().map_or(String::new(), |ty| format!(" -> {}", ty))
+        };
+        writeln!(src, "\n    pub {}fn {}({}){} {{", if is_unsafe { "unsafe " } else { "" },
+                 self.name, self.params.join(", "), ret).unwrap();
+
+        let mut call = format!("super::{}({})", self.name, self.args.join(", "));
+        if !is_unsafe {
+            call = format!("unsafe {{ {} }}", call);
+        }
+        if self.status {
+            writeln!(src, "        let __status = {};", call).unwrap();
+            writeln!(src, "        {}", err_expr).unwrap();
+        } else {
+            writeln!(src, "        {}", call).unwrap();
+        }
+        src.push_str("    }\n");
+    }
+}
+
+/// Build the source of the `safe` module for `wrappers`.
+fn safe_module(err_expr: &str, wrappers: &[FnWrapper]) -> String {
+    let mut src = String::new();
+    src.push_str("pub mod safe {\n    use super::*;\n");
+    if let Some(ty) = wrappers.iter().filter(|w| w.status).filter_map(|w| w.ret.as_ref()).next() {
+        src.push_str("\n    #[derive(Clone, Copy, Debug, PartialEq, Eq)]\n");
+        writeln!(src, "    pub struct Errno(pub {});", ty).unwrap();
+    }
+    for w in wrappers {
+        w.write(err_expr, &mut src);
+    }
+    src.push_str("}\n");
+    src
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("safe_api", |args| mk(SafeApi {
+        err_expr: args.get(0).cloned().unwrap_or_else(|| {
+            "if __status == 0 { Ok(()) } else { Err(Errno(__status)) }".to_owned()
+        }),
+    }));
+}
@@ -1,16 +1,21 @@
 use std::collections::{HashMap, HashSet};
 use std::mem;
 use rustc::hir::def_id::DefId;
+use rustc_target::spec::abi::Abi;
 use syntax::ast::*;
+use syntax::attr;
+use syntax::print::pprust;
 use syntax::ptr::P;
+use syntax::source_map::respan;
 use syntax::symbol::Symbol;
+use syntax_pos::sym;
 
-use crate::ast_manip::{FlatMapNodes, MutVisitNodes, fold_modules};
-use crate::ast_manip::fn_edit::mut_visit_fns;
+use crate::ast_manip::{FlatMapNodes, MutVisitNodes, fold_modules, visit_nodes};
+use crate::ast_manip::fn_edit::{FnKind, mut_visit_fns, visit_fns};
 use crate::command::{CommandState, Registry};
-use crate::driver::{parse_expr};
+use crate::driver::{Phase, parse_expr};
 use crate::matcher::{Bindings, BindingType, MatchCtxt, Subst, mut_visit_match_with};
-use crate::path_edit::fold_resolved_paths;
+use crate::path_edit::{fold_resolved_paths, fold_resolved_paths_with_id};
 use crate::transform::Transform;
 use c2rust_ast_builder::{mk, IntoSymbol};
 use crate::util::dataflow;
@@ -497,6 +502,813 @@ impl Transform for StaticToLocal {
 
 
 
+/// # `localize_statics` Command
+///
+/// Usage: `localize_statics`
+///
+/// Move each static that is used by only one function into that function.  Statics that are
+/// exported (`#[no_mangle]` or `#[export_name]`), whose address is taken, that are
+/// referenced outside that one function, or that are defined in a different module than the
+/// function are left alone.
+///
+/// If every call to the function overwrites the static before reading it, the static becomes
+/// a `let` local with the same type and initializer.  This is only assumed when the first
+/// statement accessing the static assigns it, and no branch, loop or early exit comes before
+/// that statement, so that the assignment runs on every path through the function.
+/// Otherwise its value has to persist across calls (this includes recursive functions), so
+/// the static item itself is moved into the function body.  Each static that is not turned
+/// into a `let` is reported on stderr, along with the reason.
+///
+/// The check for an overwrite is syntactic, not a dataflow analysis: a static that is assigned
+/// on every path only further down, such as in both branches of an `if`, or after a loop that
+/// doesn't touch it, is conservatively kept as a static.
+///
+/// Example:
+///
+/// ```ignore
+///     static mut SCRATCH: i32 = 0;
+///     static mut TOTAL: i32 = 0;
+///
+///     unsafe fn f(x: i32) -> i32 {
+///         SCRATCH = x * 2;
+///         SCRATCH + 1
+///     }
+///
+///     unsafe fn g(x: i32) -> i32 {
+///         TOTAL += x;
+///         TOTAL
+///     }
+/// ```
+///
+/// After running `localize_statics`:
+///
+/// ```ignore
+///     unsafe fn f(x: i32) -> i32 {
+///         // `SCRATCH` is always written before it's read
+///         let mut SCRATCH: i32 = 0;
+///         SCRATCH = x * 2;
+///         SCRATCH + 1
+///     }
+///
+///     unsafe fn g(x: i32) -> i32 {
+///         // `TOTAL` carries its value from one call to the next
+///         static mut TOTAL: i32 = 0;
+///         TOTAL += x;
+///         TOTAL
+///     }
+/// ```
+pub struct LocalizeStatics;
+
+impl Transform for LocalizeStatics {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        // (1) Collect module-level statics, skipping any that are exported.
+
+        let mut statics = HashMap::new();
+        let mut kept = Vec::new();
+        collect_module_statics(&krate.module.items, &mut |i: &P<Item>| {
+            if attr::contains_name(&i.attrs, sym::no_mangle) ||
+               attr::contains_name(&i.attrs, sym::export_name) {
+                kept.push((i.ident, "exported for FFI".to_owned()));
+            } else {
+                statics.insert(cx.node_def_id(i.id), i.clone());
+            }
+        });
+
+
+        // (2) Find out where each static is used, and which functions call which.
+
+        let static_of = |e: &Expr| {
+            cx.try_resolve_expr(e).filter(|def_id| statics.contains_key(def_id))
+        };
+
+        let mut total_uses = HashMap::new();
+        let mut escaped = HashSet::new();
+        visit_nodes(&*krate, |e: &Expr| {
+            if let Some(def_id) = static_of(e) {
+                *total_uses.entry(def_id).or_insert(0) += 1;
+            }
+            let borrowed = match e.kind {
+                ExprKind::AddrOf(_, ref inner) => Some(inner),
+                // Method calls may auto-ref their receiver
+                ExprKind::MethodCall(_, ref args) => args.first(),
+                _ => None,
+            };
+            if let Some(def_id) = borrowed.and_then(|b| static_of(place_base(b))) {
+                escaped.insert(def_id);
+            }
+        });
+
+        let module_of = |id: NodeId| {
+            cx.hir_map().get_module_parent(cx.hir_map().node_to_hir_id(id))
+        };
+
+        struct FnInfo {
+            callees: HashSet<DefId>,
+            uses: HashMap<DefId, usize>,
+            body: Option<P<Block>>,
+            name: Ident,
+            module: DefId,
+        }
+        let mut fns = HashMap::new();
+        visit_fns(&*krate, |fl| {
+            let mut callees = HashSet::new();
+            let mut uses = HashMap::new();
+            if let Some(ref block) = fl.block {
+                visit_nodes(&**block, |e: &Expr| {
+                    if let Some(def_id) = cx.try_resolve_expr(e) {
+                        if statics.contains_key(&def_id) {
+                            *uses.entry(def_id).or_insert(0) += 1;
+                        } else {
+                            callees.insert(def_id);
+                        }
+                    }
+                });
+            }
+            fns.insert(cx.node_def_id(fl.id), FnInfo {
+                callees,
+                uses,
+                body: fl.block.clone(),
+                name: fl.ident,
+                module: module_of(fl.id),
+            });
+        });
+
+        // Compute the transitive callees of each function, so we can tell which ones are
+        // (possibly mutually) recursive.
+        let mut reachable = fns.iter().map(|(&id, info)| {
+            let callees = info.callees.iter()
+                .filter(|callee| fns.contains_key(callee))
+                .copied()
+                .collect::<HashSet<_>>();
+            (id, callees)
+        }).collect::<HashMap<_, _>>();
+        dataflow::iterate(&mut reachable, |cur_id, cur, data| {
+            let mut changed = false;
+            for other_id in cur.iter().copied().collect::<Vec<_>>() {
+                if other_id == cur_id {
+                    continue;
+                }
+                for &callee in &data[other_id] {
+                    changed |= cur.insert(callee);
+                }
+            }
+            changed
+        });
+
+
+        // (3) Decide what to do with each static.
+
+        let mut static_ids = statics.keys().copied().collect::<Vec<_>>();
+        static_ids.sort_by_key(|id| statics[id].ident.name.as_str().to_string());
+
+        // Map from function to the statics to move into it, and whether each must persist
+        let mut moves: HashMap<DefId, Vec<(DefId, bool)>> = HashMap::new();
+        for static_id in static_ids {
+            let item = &statics[&static_id];
+            let users = fns.iter()
+                .filter(|(_, info)| info.uses.contains_key(&static_id))
+                .collect::<Vec<_>>();
+
+            let (&fn_id, info) = match users.as_slice() {
+                [] => {
+                    kept.push((item.ident, "unused".to_owned()));
+                    continue;
+                }
+                [user] => *user,
+                _ => {
+                    kept.push((item.ident, format!("used by {} functions", users.len())));
+                    continue;
+                }
+            };
+            if info.uses[&static_id] != total_uses.get(&static_id).copied().unwrap_or(0) {
+                kept.push((item.ident, "used outside of a function".to_owned()));
+                continue;
+            }
+            if escaped.contains(&static_id) {
+                kept.push((item.ident, "its address escapes".to_owned()));
+                continue;
+            }
+            // Paths in the type and initializer are relative to the module of the static
+            if module_of(item.id) != info.module {
+                kept.push((item.ident, format!("`{}` is in another module", info.name)));
+                continue;
+            }
+            if info.body.as_ref().map_or(false, |b| binds_name(b, item.ident)) {
+                kept.push((item.ident, format!("the name is already used in `{}`", info.name)));
+                continue;
+            }
+
+            let is_mut = match item.kind {
+                ItemKind::Static(_, Mutability::Mutable, _) => true,
+                _ => false,
+            };
+            let recursive = reachable[&fn_id].contains(&fn_id);
+            let written_first = info.body.as_ref().map_or(false, |b| {
+                first_access_is_write(&b.stmts, static_id, cx) == Some(true)
+            });
+            let persists = !is_mut || recursive || !written_first;
+            if persists {
+                let reason = if !is_mut {
+                    "immutable"
+                } else if recursive {
+                    "the function is recursive"
+                } else {
+                    "it may be read before it is written"
+                };
+                kept.push((item.ident, format!(
+                    "moved into `{}` as a static ({})", info.name, reason)));
+            }
+            moves.entry(fn_id).or_insert_with(Vec::new).push((static_id, persists));
+        }
+
+        for (name, reason) in &kept {
+            eprintln!("localize_statics: {}: {}", name, reason);
+        }
+
+
+        // (4) Move the statics.
+
+        let moved = moves.values()
+            .flat_map(|v| v.iter().map(|&(static_id, _)| static_id))
+            .collect::<HashSet<_>>();
+        FlatMapNodes::visit(krate, |i: P<Item>| {
+            if moved.contains(&cx.node_def_id(i.id)) {
+                smallvec![]
+            } else {
+                smallvec![i]
+            }
+        });
+
+        mut_visit_fns(krate, |fl| {
+            let static_moves = match moves.get(&cx.node_def_id(fl.id)) {
+                Some(x) => x,
+                None => return,
+            };
+            let block = match &mut fl.block {
+                Some(block) => block,
+                None => return,
+            };
+
+            // Refer to the statics by their plain name, which now resolves to the local.
+            fold_resolved_paths(block, cx, |qself, path, def| {
+                if let Some(def_id) = def.opt_def_id() {
+                    if moved.contains(&def_id) {
+                        return (None, mk().path(vec![statics[&def_id].ident]));
+                    }
+                }
+                (qself, path)
+            });
+
+            let mut new_stmts = Vec::with_capacity(static_moves.len() + block.stmts.len());
+            for &(static_id, persists) in static_moves {
+                let item = &statics[&static_id];
+                if persists {
+                    let mut item = item.clone();
+                    item.vis = respan(item.vis.span.shrink_to_lo(), VisibilityKind::Inherited);
+                    new_stmts.push(mk().item_stmt(item));
+                } else if let ItemKind::Static(ref ty, mutbl, ref init) = item.kind {
+                    let pat = mk().set_mutbl(mutbl).ident_pat(item.ident);
+                    let local = mk().local(pat, Some(ty.clone()), Some(init.clone()));
+                    new_stmts.push(mk().local_stmt(P(local)));
+                }
+            }
+            new_stmts.extend(mem::replace(&mut block.stmts, Vec::new()));
+            block.stmts = new_stmts;
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// # `fieldify_globals` Command
+///
+/// Usage: `fieldify_globals STRUCT`
+///
+/// Marks: `target`, `root`
+///
+/// Turn the statics marked `target` into the fields of a context struct that is passed
+/// explicitly to the functions using them.
+///
+///  1. Delete the marked statics, and define a struct `STRUCT` with a field of the same name
+///     and type for each one, along with a `STRUCT::new()` constructor that initializes the
+///     fields with the initializers of the statics.
+///  2. Give each function that uses a marked static a new first argument `ctx: &mut STRUCT`,
+///     and do the same for its callers, their callers and so on, up to the functions marked
+///     `root`.  Uses of the statics become uses of the fields of `ctx`, and calls pass `ctx`
+///     along.
+///  3. Functions marked `root` keep their signatures.  They start by constructing the struct
+///     with `let mut ctx = STRUCT::new();` and pass `&mut ctx` to the functions they call.
+///  4. Functions that gained an argument but are exported (`#[no_mangle]` or `#[export_name]`)
+///     or used as function pointers get a wrapper `foo_wrapper` with the original signature,
+///     ABI and symbol, like in `wrap_api`.  The wrapper passes a global instance of `STRUCT`,
+///     which is created on first use, and function pointers refer to the wrapper instead.
+///
+/// The struct is defined in the module of the first marked static.  Uses of the statics
+/// outside of function bodies are left alone.
+///
+/// Example:
+///
+/// ```ignore
+///     static mut pos: usize = 0;  // pos: target
+///
+///     unsafe fn next() -> usize {
+///         pos += 1;
+///         pos
+///     }
+///
+///     #[no_mangle]
+///     unsafe extern "C" fn skip(n: usize) {
+///         while next() < n {}
+///     }
+///
+///     unsafe fn run() {  // run: root
+///         skip(3);
+///     }
+/// ```
+///
+/// After running `fieldify_globals State`:
+///
+/// ```ignore
+///     pub struct State {
+///         pos: usize,
+///     }
+///
+///     impl State {
+///         pub fn new() -> State {
+///             State { pos: 0 }
+///         }
+///     }
+///
+///     static mut STATE_INSTANCE: Option<State> = None;
+///
+///     unsafe fn next(ctx: &mut State) -> usize {
+///         ctx.pos += 1;
+///         ctx.pos
+///     }
+///
+///     unsafe fn skip(ctx: &mut State, n: usize) {
+///         while next(ctx) < n {}
+///     }
+///
+///     #[export_name = "skip"]
+///     unsafe extern "C" fn skip_wrapper(n: usize) {
+///         skip(STATE_INSTANCE.get_or_insert_with(State::new), n)
+///     }
+///
+///     unsafe fn run() {
+///         let mut ctx = State::new();
+///         skip(&mut ctx, 3);
+///     }
+/// ```
+pub struct FieldifyGlobals {
+    pub struct_name: String,
+}
+
+impl Transform for FieldifyGlobals {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        // (1) Collect the marked statics.
+
+        let mut statics = HashMap::new();
+        let mut static_order = Vec::new();
+        visit_nodes(&*krate, |i: &Item| {
+            if !st.marked(i.id, "target") {
+                return;
+            }
+            if let ItemKind::Static(..) = i.kind {
+                let def_id = cx.node_def_id(i.id);
+                static_order.push(def_id);
+                statics.insert(def_id, i.clone());
+            }
+        });
+        if statics.is_empty() {
+            warn!("fieldify_globals: no statics are marked `target`");
+            return;
+        }
+
+
+        // (2) Find the functions that use the statics, directly or through their callees.
+
+        struct FnInfo {
+            uses_statics: bool,
+            callees: HashSet<DefId>,
+            is_root: bool,
+            wrapped_decl: Option<P<FnDecl>>,
+        }
+        let mut callee_ids = HashSet::new();
+        visit_nodes(&*krate, |e: &Expr| {
+            if let ExprKind::Call(ref callee, _) = e.kind {
+                callee_ids.insert(callee.id);
+            }
+        });
+        let mut fn_ptrs = HashSet::new();
+        visit_nodes(&*krate, |e: &Expr| {
+            if !callee_ids.contains(&e.id) {
+                if let Some(def_id) = cx.try_resolve_expr(e) {
+                    fn_ptrs.insert(def_id);
+                }
+            }
+        });
+
+        let mut fns = HashMap::new();
+        visit_fns(&*krate, |fl| {
+            let mut uses_statics = false;
+            let mut callees = HashSet::new();
+            if let Some(ref block) = fl.block {
+                visit_nodes(&**block, |e: &Expr| {
+                    if let Some(def_id) = cx.try_resolve_expr(e) {
+                        uses_statics |= statics.contains_key(&def_id);
+                    }
+                    if let ExprKind::Call(ref callee, _) = e.kind {
+                        callees.extend(cx.try_resolve_expr(callee));
+                    }
+                });
+            }
+            let def_id = cx.node_def_id(fl.id);
+            let exported = attr::contains_name(&fl.attrs, sym::no_mangle) ||
+                attr::contains_name(&fl.attrs, sym::export_name);
+            let needs_wrapper = exported || fn_ptrs.contains(&def_id);
+            fns.insert(def_id, FnInfo {
+                uses_statics,
+                callees,
+                is_root: st.marked(fl.id, "root"),
+                wrapped_decl: if fl.kind == FnKind::Normal && needs_wrapper {
+                    Some(fl.decl.clone())
+                } else {
+                    None
+                },
+            });
+        });
+
+        // Propagate the context argument to callers, stopping at the roots.
+        let mut takes_ctx = fns.iter()
+            .filter(|(_, info)| info.uses_statics && !info.is_root)
+            .map(|(&id, _)| id)
+            .collect::<HashSet<_>>();
+        loop {
+            let callers = fns.iter()
+                .filter(|(id, info)| !info.is_root && !takes_ctx.contains(id))
+                .filter(|(_, info)| info.callees.iter().any(|id| takes_ctx.contains(id)))
+                .map(|(&id, _)| id)
+                .collect::<Vec<_>>();
+            if callers.is_empty() {
+                break;
+            }
+            takes_ctx.extend(callers);
+        }
+
+
+        // (3) Rewrite the functions using the context, and the calls passing it along.
+
+        let struct_ty = mk().path_ty(vec![&self.struct_name as &str]);
+        mut_visit_fns(krate, |fl| {
+            let fn_def_id = cx.node_def_id(fl.id);
+            let info = &fns[&fn_def_id];
+            let uses_ctx = info.uses_statics ||
+                info.callees.iter().any(|id| takes_ctx.contains(id));
+            if fl.block.is_none() ||
+               !takes_ctx.contains(&fn_def_id) && !(info.is_root && uses_ctx) {
+                return;
+            }
+
+            // Name the context `ctx`, unless the function already uses that name
+            let mut ctx = "ctx".into_symbol();
+            let mut i = 0;
+            while fl.block.as_ref().map_or(false, |b| binds_name(b, Ident::with_dummy_span(ctx))) ||
+                  fl.decl.inputs.iter().any(|arg| binds_name_in_pat(&arg.pat, ctx)) {
+                ctx = format!("ctx_{}", i).into_symbol();
+                i += 1;
+            }
+
+            MutVisitNodes::visit(&mut fl.block, |e: &mut P<Expr>| {
+                if let Some(def_id) = cx.try_resolve_expr(e) {
+                    if let Some(item) = statics.get(&def_id) {
+                        *e = mk().field_expr(mk().ident_expr(ctx), item.ident);
+                        return;
+                    }
+                }
+                let callee = match_or!([e.kind] ExprKind::Call(ref f, _) => f; return);
+                if cx.try_resolve_expr(callee).map_or(false, |id| takes_ctx.contains(&id)) {
+                    pass_ctx(e, ctx, info.is_root);
+                }
+            });
+
+            if info.is_root {
+                let init = mk().call_expr(
+                    mk().path_expr(vec![&self.struct_name as &str, "new"]),
+                    Vec::<P<Expr>>::new(),
+                );
+                let pat = mk().mutbl().ident_pat(ctx);
+                let local = mk().local(pat, None as Option<P<Ty>>, Some(init));
+                let block = fl.block.as_mut().unwrap();
+                block.stmts.insert(0, mk().local_stmt(P(local)));
+            } else {
+                fl.decl.inputs.insert(0, mk().arg(
+                    mk().set_mutbl(Mutability::Mutable).ref_ty(&struct_ty),
+                    mk().ident_pat(ctx)));
+            }
+        });
+
+
+        // (4) Add wrappers for functions that are called from outside of Rust, and point
+        // function pointers at them.
+
+        let instance_name = format!("{}_INSTANCE", self.struct_name.to_uppercase());
+        let mut wrapper_map = HashMap::new();
+        FlatMapNodes::visit(krate, |i: P<Item>| {
+            if !matches!([i.kind] ItemKind::Fn(..)) {
+                return smallvec![i];
+            }
+            let def_id = cx.node_def_id(i.id);
+            if !takes_ctx.contains(&def_id) {
+                return smallvec![i];
+            }
+            let decl = match_or!([fns[&def_id].wrapped_decl] Some(ref d) => d.clone();
+                                 return smallvec![i]);
+            let old_abi = expect!([i.kind] ItemKind::Fn(_, ref header, _, _) => header.abi);
+
+            let symbol =
+                if let Some(sym) = attr::first_attr_value_str_by_name(&i.attrs, sym::export_name) {
+                    Some(sym)
+                } else if attr::contains_name(&i.attrs, sym::no_mangle) {
+                    Some(i.ident.name)
+                } else {
+                    None
+                };
+
+            // The function itself is only called from Rust from now on.
+            let i = i.map(|mut i| {
+                i.attrs.retain(|attr| {
+                    attr.path != sym::no_mangle &&
+                    attr.path != sym::export_name
+                });
+                match i.kind {
+                    ItemKind::Fn(_, ref mut header, _, _) => header.abi = Abi::Rust,
+                    _ => unreachable!(),
+                }
+                i
+            });
+
+            let arg_names = decl.inputs.iter().enumerate().map(|(idx, arg)| {
+                match arg.pat.kind {
+                    PatKind::Ident(_, ident, _) => ident.name,
+                    _ => format!("arg{}", idx).into_symbol(),
+                }
+            }).collect::<Vec<_>>();
+            let wrapper_decl = decl.map(|decl| {
+                let new_inputs = decl.inputs.iter().zip(arg_names.iter()).map(|(arg, &name)| {
+                    mk().arg(&arg.ty, mk().ident_pat(name))
+                }).collect();
+                FnDecl {
+                    inputs: new_inputs,
+                    .. decl
+                }
+            });
+
+            let instance = mk().method_call_expr(
+                mk().ident_expr(&instance_name as &str),
+                "get_or_insert_with",
+                vec![mk().path_expr(vec![&self.struct_name as &str, "new"])],
+            );
+            let mut wrapper_args = vec![instance];
+            wrapper_args.extend(arg_names.iter().map(|&name| mk().ident_expr(name)));
+
+            let wrapper_name = format!("{}_wrapper", i.ident.name.as_str());
+            let mut wrapper_mk = mk().vis(i.vis.clone()).unsafe_().abi(old_abi);
+            if let Some(symbol) = symbol {
+                wrapper_mk = wrapper_mk.str_attr(sym::export_name, symbol);
+            }
+            let wrapper = wrapper_mk.fn_item(
+                &wrapper_name,
+                wrapper_decl,
+                mk().block(vec![
+                    mk().expr_stmt(mk().call_expr(
+                        mk().path_expr(vec![i.ident.name]),
+                        wrapper_args,
+                    ))
+                ]),
+            );
+            wrapper_map.insert(def_id, wrapper_name);
+
+            smallvec![i, wrapper]
+        });
+
+        fold_resolved_paths_with_id(krate, cx, |id, q, p, d| {
+            if callee_ids.contains(&id) || q.is_some() {
+                return (q, p);
+            }
+            let def_id = match_or!([d.opt_def_id()] Some(x) => x; return (q, p));
+            let name = match_or!([wrapper_map.get(&def_id)] Some(x) => x; return (q, p));
+
+            let mut new_path = p.clone();
+            new_path.segments.pop();
+            new_path.segments.push(mk().path_segment(name));
+            (q, new_path)
+        });
+
+
+        // (5) Replace the statics with the struct definition.
+
+        let mut fields = String::new();
+        let mut inits = String::new();
+        for def_id in &static_order {
+            let item = &statics[def_id];
+            let (ty, init) = expect!([item.kind]
+                ItemKind::Static(ref ty, _, ref init) => (ty, init));
+            fields.push_str(&format!("    {}{}: {},\n",
+                                     pprust::vis_to_string(&item.vis),
+                                     item.ident,
+                                     pprust::ty_to_string(ty)));
+            inits.push_str(&format!("            {}: {},\n",
+                                    item.ident,
+                                    pprust::expr_to_string(init)));
+        }
+        let mut src = format!(
+            "pub struct {name} {{\n{fields}}}\n\n\
+             impl {name} {{\n    \
+                 pub fn new() -> {name} {{\n        \
+                     {name} {{\n{inits}        }}\n    \
+                 }}\n\
+             }}\n",
+            name = self.struct_name, fields = fields, inits = inits,
+        );
+        if !wrapper_map.is_empty() {
+            src.push_str(&format!("static mut {}: Option<{}> = None;\n",
+                                  instance_name, self.struct_name));
+        }
+        let mut new_items = Some(st.parse_items(cx, &src));
+
+        let first_static = statics[&static_order[0]].id;
+        MutVisitNodes::visit(krate, |m: &mut Mod| {
+            let pos = m.items.iter().position(|i| i.id == first_static);
+            m.items.retain(|i| !statics.values().any(|s| s.id == i.id));
+            if let Some(pos) = pos {
+                let items = new_items.take().unwrap();
+                m.items.splice(pos..pos, items);
+            }
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// Pass the context `ctx` as the first argument of the call `e`.  The context is borrowed
+/// mutably for the whole call, so if the other arguments use it themselves, they are evaluated
+/// into locals first.
+fn pass_ctx(e: &mut P<Expr>, ctx: Symbol, is_root: bool) {
+    let ctx_arg = if is_root {
+        mk().set_mutbl(Mutability::Mutable).addr_of_expr(mk().ident_expr(ctx))
+    } else {
+        mk().ident_expr(ctx)
+    };
+    let (callee, args) = expect!([e.kind] ExprKind::Call(ref f, ref args) => (f.clone(), args));
+
+    let mut names = HashSet::new();
+    for arg in args {
+        visit_nodes(&**arg, |e: &Expr| {
+            if let ExprKind::Path(None, ref path) = e.kind {
+                if let [ref seg] = path.segments[..] {
+                    names.insert(seg.ident.name);
+                }
+            }
+        });
+    }
+    if !names.contains(&ctx) {
+        let mut new_args = vec![ctx_arg];
+        new_args.extend(args.iter().cloned());
+        *e = mk().call_expr(callee, new_args);
+        return;
+    }
+
+    let mut stmts = Vec::new();
+    let mut new_args = vec![ctx_arg];
+    for (idx, arg) in args.iter().enumerate() {
+        let mut name = format!("arg{}", idx).into_symbol();
+        while names.contains(&name) {
+            name = format!("{}_", name.as_str()).into_symbol();
+        }
+        let local = mk().local(mk().ident_pat(name), None as Option<P<Ty>>, Some(arg.clone()));
+        stmts.push(mk().local_stmt(P(local)));
+        new_args.push(mk().ident_expr(name));
+    }
+    stmts.push(mk().expr_stmt(mk().call_expr(callee, new_args)));
+    *e = mk().block_expr(mk().block(stmts));
+}
+
+/// Check whether the pattern `pat` binds `name`.
+fn binds_name_in_pat(pat: &Pat, name: Symbol) -> bool {
+    let mut found = false;
+    visit_nodes(pat, |p: &Pat| {
+        if let PatKind::Ident(_, ident, _) = p.kind {
+            found |= ident.name == name;
+        }
+    });
+    found
+}
+
+fn collect_module_statics<F: FnMut(&P<Item>)>(items: &[P<Item>], callback: &mut F) {
+    for i in items {
+        match i.kind {
+            ItemKind::Static(..) => callback(i),
+            ItemKind::Mod(ref m) => collect_module_statics(&m.items, callback),
+            _ => {}
+        }
+    }
+}
+
+/// Get the variable at the base of a place expression like `x.a[i]`.
+fn place_base(e: &Expr) -> &Expr {
+    match e.kind {
+        ExprKind::Paren(ref inner) |
+        ExprKind::Field(ref inner, _) |
+        ExprKind::Index(ref inner, _) => place_base(inner),
+        _ => e,
+    }
+}
+
+/// Check whether `block` already binds `name`, so a local with that name would shadow it.
+fn binds_name(block: &Block, name: Ident) -> bool {
+    let mut found = false;
+    visit_nodes(block, |p: &Pat| {
+        if let PatKind::Ident(_, ident, _) = p.kind {
+            found |= ident.name == name.name;
+        }
+    });
+    visit_nodes(block, |i: &Item| {
+        found |= i.ident.name == name.name;
+    });
+    found
+}
+
+/// Check whether `stmt` contains an expression that may skip the statements after it or run
+/// them more than once: a branch, a loop, an early exit or a macro that could expand to one.
+fn has_control_flow(stmt: &Stmt) -> bool {
+    let mut found = false;
+    visit_nodes(stmt, |e: &Expr| {
+        found |= match e.kind {
+            ExprKind::If(..) | ExprKind::Match(..) | ExprKind::While(..) |
+            ExprKind::ForLoop(..) | ExprKind::Loop(..) | ExprKind::Ret(..) |
+            ExprKind::Break(..) | ExprKind::Continue(..) | ExprKind::Try(..) |
+            ExprKind::Mac(..) | ExprKind::Block(_, Some(_)) => true,
+            _ => false,
+        };
+    });
+    if let StmtKind::Mac(..) = stmt.kind {
+        found = true;
+    }
+    found
+}
+
+/// Find the first statement in `stmts` that accesses `static_id`, looking through plain and
+/// `unsafe` blocks.  Returns `Some(true)` if that access is an assignment that overwrites the
+/// whole static without reading it and no statement before it has control flow that could
+/// skip it, `Some(false)` for any other access, and `None` if the statements never access the
+/// static.
+fn first_access_is_write(stmts: &[Stmt], static_id: DefId, cx: &RefactorCtxt) -> Option<bool> {
+    let accesses = |target: &Stmt| {
+        let mut found = false;
+        visit_nodes(target, |e: &Expr| {
+            found |= cx.try_resolve_expr(e) == Some(static_id);
+        });
+        found
+    };
+    let reads = |target: &P<Expr>| {
+        let mut found = false;
+        visit_nodes(&**target, |e: &Expr| {
+            found |= cx.try_resolve_expr(e) == Some(static_id);
+        });
+        found
+    };
+
+    let mut branched = false;
+    for stmt in stmts {
+        if !accesses(stmt) {
+            branched |= has_control_flow(stmt);
+            continue;
+        }
+        if branched {
+            return Some(false);
+        }
+        let e = match stmt.kind {
+            StmtKind::Semi(ref e) | StmtKind::Expr(ref e) => e,
+            _ => return Some(false),
+        };
+        return match e.kind {
+            ExprKind::Block(ref b, None) => first_access_is_write(&b.stmts, static_id, cx),
+            ExprKind::Assign(ref lhs, ref rhs) => {
+                Some(cx.try_resolve_expr(place_base(lhs)) == Some(static_id) &&
+                     place_base(lhs).id == lhs.id && !reads(rhs))
+            }
+            _ => Some(false),
+        };
+    }
+    None
+}
+
+
 pub fn register_commands(reg: &mut Registry) {
     use super::mk;
 
@@ -506,4 +1318,8 @@ pub fn register_commands(reg: &mut Registry) {
     }));
     reg.register("static_to_local_ref", |_args| mk(Localize));
     reg.register("static_to_local", |_args| mk(StaticToLocal));
+    reg.register("localize_statics", |_args| mk(LocalizeStatics));
+    reg.register("fieldify_globals", |args| mk(FieldifyGlobals {
+        struct_name: args[0].clone(),
+    }));
 }
@@ -0,0 +1,503 @@
+use std::collections::{HashMap, HashSet};
+use std::str;
+use rustc::hir::HirId;
+use rustc::ty::TyKind;
+use syntax::ast::*;
+use syntax::ptr::P;
+use syntax::visit::{self, Visitor};
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::{FlatMapNodes, MutVisitNodes};
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::reflect::reflect_tcx_ty;
+use crate::transform::util::{foreign_fn_name, is_local, is_null, libc_call, stmt_expr, strip_casts};
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `stdio_to_std_io` Command
+///
+/// Usage: `stdio_to_std_io`
+///
+/// Replace local `FILE *` handles opened with `fopen` by an `Option<std::fs::File>`, and the
+/// stdio calls on them by their `std::io` equivalents:
+///
+///  * `fopen(path, mode)` becomes `OpenOptions::new()...open(path).ok()`, with the options
+///    set from the mode string (`r`, `w`, `a`, with or without `+`, `b` and `x`),
+///  * `f.is_null()` becomes `f.is_none()`,
+///  * `fread(buf, size, n, f)` becomes a `Read::read` into the `size * n` bytes at `buf`, and
+///    returns the number of whole elements read,
+///  * `fwrite(buf, size, n, f)` and `fputs(s, f)` become `Write::write_all`, and return `n`
+///    or `0`, and `0` or `-1` respectively, and
+///  * `fclose(f);` becomes `f = None;`, which drops and closes the file.
+///
+/// Since `File` is unbuffered, a single `read` may return fewer bytes than `fread` would
+/// before the end of the file, which is only a concern for pipes and special files.  The path
+/// passed to `fopen` must be valid UTF-8.
+///
+/// The handle must be a local initialized with `fopen` or null, and only ever assigned the
+/// result of `fopen` or null.  Handles that are used in any other way, such as being returned,
+/// passed to other functions across the FFI boundary, or passed to other stdio functions like
+/// `fprintf` or `fseek`, are left alone with an error.  So are handles opened with a mode that
+/// isn't a string literal, or whose `fread`/`fwrite` sizes aren't simple expressions that can
+/// be evaluated twice.
+///
+/// Example:
+///
+/// ```ignore
+///     let mut f: *mut FILE = fopen(path, b"rb\0" as *const u8 as *const libc::c_char);
+///     if f.is_null() {
+///         return -1;
+///     }
+///     let mut n: libc::c_ulong = fread(buf.as_mut_ptr() as *mut libc::c_void,
+///                                      1 as libc::c_int as libc::c_ulong,
+///                                      64 as libc::c_int as libc::c_ulong, f);
+///     fclose(f);
+/// ```
+///
+/// After running `stdio_to_std_io`:
+///
+/// ```ignore
+///     let mut f: Option<::std::fs::File> = ::std::fs::OpenOptions::new()
+///         .read(true)
+///         .open(::std::ffi::CStr::from_ptr(path).to_str().unwrap())
+///         .ok();
+///     if f.is_none() {
+///         return -1;
+///     }
+///     let mut n: libc::c_ulong = ::std::io::Read::read(
+///         f.as_mut().unwrap(),
+///         ::std::slice::from_raw_parts_mut(
+///             buf.as_mut_ptr() as *mut u8,
+///             (1 as libc::c_int as libc::c_ulong * 64 as libc::c_int as libc::c_ulong) as usize,
+///         ),
+///     ).unwrap_or(0) as libc::c_ulong;
+///     f = None;
+/// ```
+pub struct StdioToStdIo;
+
+impl Transform for StdioToStdIo {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |b: &mut P<Block>| {
+            for i in 0..b.stmts.len() {
+                let file = match file_local(cx, &b.stmts[i]) {
+                    Some(file) => file,
+                    None => continue,
+                };
+
+                match FilePlan::new(cx, &file, &b.stmts[i..]) {
+                    Ok(Some(plan)) => plan.rewrite(b, i, &file),
+                    Ok(None) => {}
+                    Err(reason) => eprintln!("stdio_to_std_io: {}: {}", file.name, reason),
+                }
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+struct FileInfo {
+    hir_id: HirId,
+    name: Ident,
+}
+
+/// If `stmt` declares a raw pointer local, get its info.  Whether it's a `FILE *` is decided
+/// by how it gets initialized.
+fn file_local(cx: &RefactorCtxt, stmt: &Stmt) -> Option<FileInfo> {
+    let l = match_or!([stmt.kind] StmtKind::Local(ref l) => l; return None);
+    let name = match_or!([l.pat.kind] PatKind::Ident(BindingMode::ByValue(_), name, None) => name;
+                         return None);
+    match cx.node_type(l.pat.id).kind {
+        TyKind::RawPtr(_) => {}
+        _ => return None,
+    }
+
+    Some(FileInfo {
+        hir_id: cx.hir_map().node_to_hir_id(l.pat.id),
+        name,
+    })
+}
+
+/// Check whether `e` can be evaluated more than once without side effects: a literal, a
+/// variable, or a call without arguments like `size_of::<T>()`, possibly cast.
+fn is_simple(e: &Expr) -> bool {
+    match strip_casts(e).kind {
+        ExprKind::Lit(..) | ExprKind::Path(..) => true,
+        ExprKind::Call(ref func, ref args) => args.is_empty() && is_simple(func),
+        _ => false,
+    }
+}
+
+/// The `OpenOptions` settings for the `fopen` mode `mode`, or `None` if it isn't valid.
+fn open_options(mode: &str) -> Option<Vec<&'static str>> {
+    let mut chars = mode.chars().filter(|&c| c != 'b');
+    let kind = chars.next()?;
+    let mut plus = false;
+    let mut exclusive = false;
+    for c in chars {
+        match c {
+            '+' if !plus => plus = true,
+            'x' if kind == 'w' && !exclusive => exclusive = true,
+            _ => return None,
+        }
+    }
+
+    let create = if exclusive { "create_new" } else { "create" };
+    let mut opts = match kind {
+        'r' => vec!["read"],
+        'w' => vec!["write", create, "truncate"],
+        'a' => vec!["append", "create"],
+        _ => return None,
+    };
+    if plus {
+        match kind {
+            'r' => opts.push("write"),
+            _ => opts.insert(0, "read"),
+        }
+    }
+    Some(opts)
+}
+
+/// Build the `OpenOptions` call replacing `fopen(path, mode)`.
+fn open_expr(args: &[P<Expr>]) -> Result<P<Expr>, &'static str> {
+    let mode = match strip_casts(&args[1]).kind {
+        ExprKind::Lit(Lit { kind: LitKind::ByteStr(ref b), .. }) => {
+            str::from_utf8(b).ok().map(|s| s.trim_end_matches('\0'))
+        }
+        _ => None,
+    };
+    let opts = mode.and_then(open_options)
+        .ok_or("it is opened with a mode that isn't a supported string literal")?;
+
+    let path = match strip_casts(&args[0]).kind {
+        ExprKind::Lit(Lit { kind: LitKind::ByteStr(ref b), .. })
+                if b.last() == Some(&0) && str::from_utf8(&b[..b.len() - 1]).is_ok() => {
+            mk().lit_expr(mk().str_lit(str::from_utf8(&b[..b.len() - 1]).unwrap()))
+        }
+        _ => {
+            let cstr = mk().call_expr(
+                mk().path_expr(vec!["", "std", "ffi", "CStr", "from_ptr"]),
+                vec![args[0].clone()],
+            );
+            mk().method_call_expr(
+                mk().method_call_expr(cstr, "to_str", Vec::<P<Expr>>::new()),
+                "unwrap",
+                Vec::<P<Expr>>::new(),
+            )
+        }
+    };
+
+    let mut e = mk().call_expr(
+        mk().path_expr(vec!["", "std", "fs", "OpenOptions", "new"]),
+        Vec::<P<Expr>>::new(),
+    );
+    for opt in opts {
+        e = mk().method_call_expr(e, opt, vec![mk().lit_expr(mk().bool_lit(true))]);
+    }
+    e = mk().method_call_expr(e, "open", vec![path]);
+    Ok(mk().method_call_expr(e, "ok", Vec::<P<Expr>>::new()))
+}
+
+/// How each use of a file handle gets rewritten.
+#[derive(Default)]
+struct FilePlan {
+    /// Whether the handle is opened with `fopen` anywhere
+    opened: bool,
+    /// Expressions to replace, by their `NodeId`
+    exprs: HashMap<NodeId, P<Expr>>,
+    /// `fclose` statements
+    closes: HashSet<NodeId>,
+}
+
+impl FilePlan {
+    /// Check the uses of the handle declared by `stmts[0]`.  Returns `None` if it is never
+    /// opened with `fopen`, and an error if it is but some use has no `std::io` equivalent.
+    fn new(cx: &RefactorCtxt,
+           file: &FileInfo,
+           stmts: &[Stmt]) -> Result<Option<FilePlan>, &'static str> {
+        let mut plan = FilePlan::default();
+
+        let l = expect!([stmts[0].kind] StmtKind::Local(ref l) => l);
+        let mut checker = UseChecker {
+            cx,
+            file,
+            plan: &mut plan,
+            error: None,
+        };
+        match l.init {
+            Some(ref init) => checker.visit_assigned(init),
+            None => return Ok(None),
+        }
+        for stmt in &stmts[1..] {
+            checker.visit_stmt(stmt);
+        }
+        let error = checker.error;
+
+        if !plan.opened {
+            return Ok(None);
+        }
+        if let Some(reason) = error {
+            return Err(reason);
+        }
+        Ok(Some(plan))
+    }
+
+    fn rewrite(&self, b: &mut P<Block>, decl: usize, file: &FileInfo) {
+        FlatMapNodes::visit(b, |s: Stmt| {
+            if self.closes.contains(&s.id) {
+                smallvec![mk().semi_stmt(mk().assign_expr(
+                    mk().ident_expr(file.name),
+                    mk().ident_expr("None"),
+                ))]
+            } else {
+                smallvec![s]
+            }
+        });
+        MutVisitNodes::visit(b, |e: &mut P<Expr>| {
+            if let Some(new_e) = self.exprs.get(&e.id) {
+                *e = new_e.clone();
+            }
+        });
+
+        let l = expect!([b.stmts[decl].kind] StmtKind::Local(ref mut l) => l);
+        l.ty = Some(mk().path_ty(vec![mk().path_segment_with_args(
+            "Option",
+            mk().angle_bracketed_args(vec![mk().path_ty(vec!["", "std", "fs", "File"])]),
+        )]));
+        l.pat = mk().mutbl().ident_pat(file.name);
+    }
+}
+
+/// Checks that a file handle is only opened, closed, and used with the supported stdio
+/// functions, and records the rewrites.
+struct UseChecker<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    file: &'a FileInfo,
+    plan: &'a mut FilePlan,
+    error: Option<&'static str>,
+}
+
+impl<'a, 'tcx> UseChecker<'a, 'tcx> {
+    fn fail(&mut self, reason: &'static str) {
+        if self.error.is_none() {
+            self.error = Some(reason);
+        }
+    }
+
+    fn is_file(&self, e: &Expr) -> bool {
+        is_local(self.cx, self.file.hir_id, e)
+    }
+
+    /// `f.as_mut().unwrap()`, to read from or write to.
+    fn file_ref(&self) -> P<Expr> {
+        mk().method_call_expr(
+            mk().method_call_expr(
+                mk().ident_expr(self.file.name),
+                "as_mut",
+                Vec::<P<Expr>>::new(),
+            ),
+            "unwrap",
+            Vec::<P<Expr>>::new(),
+        )
+    }
+
+    /// Record the value `e` assigned to the handle, which must be `fopen(...)` or null.
+    fn visit_assigned(&mut self, e: &Expr) {
+        if let Some(args) = libc_call(self.cx, e, "fopen", 2) {
+            match open_expr(args) {
+                Ok(open) => {
+                    self.plan.opened = true;
+                    self.plan.exprs.insert(e.id, open);
+                }
+                Err(reason) => self.fail(reason),
+            }
+            for arg in args {
+                self.visit_expr(arg);
+            }
+        } else if is_null(e) {
+            self.plan.exprs.insert(e.id, mk().ident_expr("None"));
+        } else {
+            self.fail("it is assigned a handle that wasn't opened here");
+        }
+    }
+
+    /// The bytes at `buf`, of `size * n`, as a slice.
+    fn buffer_slice(&mut self, buf: &P<Expr>, size: &P<Expr>, n: &P<Expr>,
+                    mutbl: Mutability) -> P<Expr> {
+        if !is_simple(size) || !is_simple(n) {
+            self.fail("it is read or written with sizes that aren't simple expressions");
+        }
+        let (func, ptr_mk) = match mutbl {
+            Mutability::Mutable => ("from_raw_parts_mut", mk().mutbl()),
+            Mutability::Immutable => ("from_raw_parts", mk()),
+        };
+        let len = mk().binary_expr(BinOpKind::Mul, size.clone(), n.clone());
+        mk().call_expr(
+            mk().path_expr(vec!["", "std", "slice", func]),
+            vec![
+                mk().cast_expr(P(strip_casts(buf).clone()),
+                               ptr_mk.ptr_ty(mk().path_ty(vec!["u8"]))),
+                mk().cast_expr(mk().paren_expr(len), mk().path_ty(vec!["usize"])),
+            ],
+        )
+    }
+
+    /// `Write::write_all(f, bytes).is_ok()`
+    fn write_all(&self, bytes: P<Expr>) -> P<Expr> {
+        mk().method_call_expr(
+            mk().call_expr(
+                mk().path_expr(vec!["", "std", "io", "Write", "write_all"]),
+                vec![self.file_ref(), bytes],
+            ),
+            "is_ok",
+            Vec::<P<Expr>>::new(),
+        )
+    }
+
+    /// If `e` is one of the supported stdio calls on the handle, record its rewrite and
+    /// return `true`.
+    fn visit_stdio_call(&mut self, e: &Expr) -> bool {
+        let cx = self.cx;
+        let ret_ty = || reflect_tcx_ty(cx.ty_ctxt(), cx.node_type(e.id));
+
+        let new_e = if let Some(args) = libc_call(cx, e, "fread", 4) {
+            if !self.is_file(&args[3]) {
+                return false;
+            }
+            let read = mk().method_call_expr(
+                mk().call_expr(
+                    mk().path_expr(vec!["", "std", "io", "Read", "read"]),
+                    vec![
+                        self.file_ref(),
+                        self.buffer_slice(&args[0], &args[1], &args[2], Mutability::Mutable),
+                    ],
+                ),
+                "unwrap_or",
+                vec![mk().lit_expr(mk().int_lit(0, LitIntType::Unsuffixed))],
+            );
+            let count = mk().cast_expr(read, ret_ty());
+            match strip_casts(&args[1]).kind {
+                ExprKind::Lit(Lit { kind: LitKind::Int(1, _), .. }) => count,
+                _ => mk().binary_expr(BinOpKind::Div, count, args[1].clone()),
+            }
+        } else if let Some(args) = libc_call(cx, e, "fwrite", 4) {
+            if !self.is_file(&args[3]) {
+                return false;
+            }
+            let bytes = self.buffer_slice(&args[0], &args[1], &args[2], Mutability::Immutable);
+            mk().ifte_expr(
+                self.write_all(bytes),
+                mk().block(vec![mk().expr_stmt(args[2].clone())]),
+                Some(mk().block_expr(mk().block(vec![mk().expr_stmt(
+                    mk().cast_expr(
+                        mk().lit_expr(mk().int_lit(0, LitIntType::Unsuffixed)),
+                        ret_ty(),
+                    ),
+                )]))),
+            )
+        } else if let Some(args) = libc_call(cx, e, "fputs", 2) {
+            if !self.is_file(&args[1]) {
+                return false;
+            }
+            let bytes = mk().method_call_expr(
+                mk().call_expr(
+                    mk().path_expr(vec!["", "std", "ffi", "CStr", "from_ptr"]),
+                    vec![args[0].clone()],
+                ),
+                "to_bytes",
+                Vec::<P<Expr>>::new(),
+            );
+            mk().ifte_expr(
+                self.write_all(bytes),
+                mk().block(vec![mk().expr_stmt(
+                    mk().cast_expr(
+                        mk().lit_expr(mk().int_lit(0, LitIntType::Unsuffixed)),
+                        ret_ty(),
+                    ),
+                )]),
+                Some(mk().block_expr(mk().block(vec![mk().expr_stmt(
+                    mk().cast_expr(
+                        mk().unary_expr("-", mk().lit_expr(
+                            mk().int_lit(1, LitIntType::Unsuffixed))),
+                        ret_ty(),
+                    ),
+                )]))),
+            )
+        } else {
+            return false;
+        };
+
+        let args = expect!([e.kind] ExprKind::Call(_, ref args) => args);
+        let (stream, others) = args.split_last().unwrap();
+        for arg in others {
+            self.visit_expr(arg);
+        }
+        if !self.is_file(stream) {
+            self.visit_expr(stream);
+        }
+        self.plan.exprs.insert(e.id, new_e);
+        true
+    }
+}
+
+impl<'a, 'tcx, 'ast> Visitor<'ast> for UseChecker<'a, 'tcx> {
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        let cx = self.cx;
+        if let Some(args) = stmt_expr(s).and_then(|e| libc_call(cx, e, "fclose", 1)) {
+            if self.is_file(&args[0]) {
+                self.plan.closes.insert(s.id);
+                return;
+            }
+        }
+        visit::walk_stmt(self, s);
+    }
+
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        let cx = self.cx;
+        if self.is_file(e) {
+            self.fail("it is used other than by the supported stdio functions, so it may be \
+                       closed or accessed elsewhere");
+            return;
+        }
+        if self.visit_stdio_call(e) {
+            return;
+        }
+
+        match e.kind {
+            ExprKind::Assign(ref lhs, ref rhs) if self.is_file(lhs) => {
+                self.visit_assigned(rhs);
+            }
+            ExprKind::AssignOp(_, ref lhs, _) if self.is_file(lhs) => {
+                self.fail("it is modified other than by assigning it a new handle");
+            }
+            ExprKind::MethodCall(ref seg, ref args) if self.is_file(&args[0]) &&
+                    seg.ident.as_str() == "is_null" => {
+                self.plan.exprs.insert(e.id, mk().method_call_expr(
+                    mk().ident_expr(self.file.name),
+                    "is_none",
+                    Vec::<P<Expr>>::new(),
+                ));
+            }
+            ExprKind::Call(ref func, ref args) if foreign_fn_name(cx, func).is_some() &&
+                    args.iter().any(|arg| self.is_file(arg)) => {
+                self.fail("it is passed to a C function other than fread, fwrite, fputs and \
+                           fclose");
+            }
+            _ => visit::walk_expr(self, e),
+        }
+    }
+
+    fn visit_mac(&mut self, mac: &'ast Mac) {
+        visit::walk_mac(self, mac);
+    }
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("stdio_to_std_io", |_args| mk(StdioToStdIo));
+}
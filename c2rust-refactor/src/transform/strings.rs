@@ -0,0 +1,689 @@
+use std::collections::HashMap;
+use std::str;
+use rustc::hir::{self, HirId};
+use rustc::hir::def_id::DefId;
+use rustc::ty::{self, TyKind};
+use syntax::ast::*;
+use syntax::attr;
+use syntax::mut_visit::{self, MutVisitor};
+use syntax::ptr::P;
+use syntax::visit::{self, Visitor};
+use syntax_pos::{sym, Span};
+
+use c2rust_ast_builder::mk;
+use crate::ast_manip::MutVisit;
+use crate::ast_manip::fn_edit::{mut_visit_fns, visit_fns};
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::reflect::reflect_tcx_ty;
+use crate::transform::util::{libc_call, strip_casts};
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `lift_string_conversions` Command
+///
+/// Usage: `lift_string_conversions`
+///
+/// Marks: `target`
+///
+/// Change the `*const c_char` parameters of functions marked `target` to `&str`, for
+/// functions that are only ever passed strings that Rust already has:
+///
+///  * translated string literals, which are passed as `str` literals,
+///  * `c.as_ptr()` for a `CString` `c`, which is passed as `c.to_str().unwrap()`, and
+///  * parameters of marked functions that are converted as well, which are passed on as they
+///    are.
+///
+/// In the body, `strlen(s)` becomes `s.len()`, and `strcmp(s, t) == 0` (or `!= 0`) becomes
+/// `s == t` when `t` is a string literal or converted parameter too, with other results of
+/// `strcmp` taken from `cmp`.  `s.is_null()` becomes `false`.
+///
+/// Parameters whose NUL terminator is still needed, because the body reads the bytes of the
+/// string through `*s` or `*s.offset(i)`, or passes the pointer on to C, become `&CStr`
+/// instead.  Bytes are then read from `s.to_bytes_with_nul()`, including the terminator, and
+/// `s.as_ptr()` is passed in place of the pointer.  Literals that aren't valid UTF-8 also need
+/// a `&CStr`.
+///
+/// A parameter is left alone, with an error naming the offending span, if some call passes it
+/// anything else, if the body does pointer arithmetic on it other than to read a byte, assigns
+/// to it, or takes its address, or if the function is exported or used other than by calling
+/// it.
+///
+/// Example:
+///
+/// ```ignore
+///     unsafe fn is_keyword(mut word: *const libc::c_char) -> libc::c_int {
+///         (strlen(word) < 8 &&
+///          strcmp(word, b"if\x00" as *const u8 as *const libc::c_char) == 0) as libc::c_int
+///     }
+///
+///     is_keyword(b"while\x00" as *const u8 as *const libc::c_char);
+/// ```
+///
+/// After running `lift_string_conversions` with `is_keyword` marked `target`:
+///
+/// ```ignore
+///     unsafe fn is_keyword(word: &str) -> libc::c_int {
+///         (word.len() as u64 < 8 && word == "if") as libc::c_int
+///     }
+///
+///     is_keyword("while");
+/// ```
+pub struct LiftStringConversions;
+
+/// A `*const c_char` parameter of a marked function
+struct StrParam {
+    name: Ident,
+    /// Whether the body needs the NUL terminator, so the parameter must be a `&CStr`
+    needs_nul: bool,
+    /// Why the parameter can't be converted, and where
+    rejected: Option<(String, Span)>,
+}
+
+impl StrParam {
+    fn reject(&mut self, reason: &str, span: Span) {
+        if self.rejected.is_none() {
+            self.rejected = Some((reason.to_owned(), span));
+        }
+    }
+
+    fn converted(&self) -> bool {
+        self.rejected.is_none()
+    }
+
+    /// The parameter as a string slice, which is either a `&str` or a `&CStr` without its
+    /// terminator.
+    fn as_bytes(&self) -> P<Expr> {
+        let method = if self.needs_nul { "to_bytes" } else { "as_bytes" };
+        mk().method_call_expr(mk().ident_expr(self.name), method, Vec::<P<Expr>>::new())
+    }
+}
+
+/// A string passed to a converted parameter
+#[derive(Clone)]
+enum StrArg {
+    /// A string literal, with its terminator
+    Literal(Vec<u8>),
+    /// `c.as_ptr()` for the `CString` `c`
+    CString(P<Expr>),
+    /// A parameter of the calling function
+    Param(HirId),
+}
+
+/// A use of a parameter in the body, by the `NodeId` of the expression to rewrite
+enum StrUse {
+    /// `strlen(s)`
+    Len(HirId),
+    /// `*s`, or `*s.offset(i)` with the index `i`
+    Byte(HirId, Option<P<Expr>>),
+    /// `s.is_null()`
+    IsNull(HirId),
+    /// `strcmp(a, b)`, or with a comparison, `strcmp(a, b) == 0` or `!= 0`
+    Compare(StrArg, StrArg, Option<BinOpKind>),
+    /// `s` as a pointer, passed on to C or used in any other way
+    Ptr(HirId),
+}
+
+impl Transform for LiftStringConversions {
+    fn transform(&self, krate: &mut Crate, st: &CommandState, cx: &RefactorCtxt) {
+        // (1) Collect the `*const c_char` parameters of the marked functions.
+
+        let mut params = HashMap::new();
+        let mut positions = HashMap::new();
+        visit_fns(krate, |fl| {
+            if !st.marked(fl.id, "target") || fl.block.is_none() {
+                return;
+            }
+            let def_id = cx.node_def_id(fl.id);
+            let exported = attr::contains_name(&fl.attrs, sym::no_mangle) ||
+                attr::contains_name(&fl.attrs, sym::export_name);
+            let mut found = false;
+            for (index, arg) in fl.decl.inputs.iter().enumerate() {
+                let name = match_or!([arg.pat.kind]
+                    PatKind::Ident(BindingMode::ByValue(_), name, None) => name; continue);
+                if !is_c_string_ty(cx.node_type(arg.pat.id)) {
+                    continue;
+                }
+                let mut param = StrParam { name, needs_nul: false, rejected: None };
+                if exported {
+                    param.reject("the function is exported, so C code may call it", fl.span);
+                }
+                let hir_id = cx.hir_map().node_to_hir_id(arg.pat.id);
+                params.insert(hir_id, param);
+                positions.insert((def_id, index), hir_id);
+                found = true;
+            }
+            if !found {
+                eprintln!("lift_string_conversions: {}: no `*const c_char` parameters",
+                          fl.ident);
+            }
+        });
+        if params.is_empty() {
+            return;
+        }
+
+
+        // (2) Check the uses of the parameters and the calls to their functions.
+
+        let mut checker = UseChecker {
+            cx,
+            params: &mut params,
+            positions: &positions,
+            uses: HashMap::new(),
+            args: HashMap::new(),
+        };
+        visit::walk_crate(&mut checker, krate);
+        let UseChecker { uses, args, .. } = checker;
+
+        // A parameter passed a rejected one can't be converted either, and one passed on to a
+        // parameter that isn't converted, or that needs a `&CStr`, needs a `&CStr` itself.
+        loop {
+            let mut changed = false;
+            for &(to, ref arg, span) in args.values() {
+                let from = match_or!([*arg] StrArg::Param(from) => from; continue);
+                if !params[&from].converted() {
+                    if params[&to].converted() {
+                        let name = params[&from].name;
+                        params.get_mut(&to).unwrap().reject(
+                            &format!("a call passes `{}`, which isn't converted", name),
+                            span,
+                        );
+                        changed = true;
+                    }
+                } else if !params[&from].needs_nul &&
+                          (!params[&to].converted() || params[&to].needs_nul) {
+                    params.get_mut(&from).unwrap().needs_nul = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        for param in params.values() {
+            if let Some((ref reason, span)) = param.rejected {
+                eprintln!("lift_string_conversions: {}: {} at {}", param.name, reason,
+                          cx.session().source_map().span_to_string(span));
+            }
+        }
+
+
+        // (3) Rewrite the uses and calls, then the parameters themselves.
+
+        let mut rewriter = Rewriter {
+            cx,
+            params: &params,
+            uses: &uses,
+            args: &args,
+        };
+        krate.visit(&mut rewriter);
+
+        mut_visit_fns(krate, |fl| {
+            for arg in &mut fl.decl.inputs {
+                let hir_id = match_or!([cx.hir_map().opt_node_to_hir_id(arg.pat.id)]
+                                       Some(x) => x; continue);
+                let param = match_or!([params.get(&hir_id)] Some(x) => x; continue);
+                if !param.converted() {
+                    continue;
+                }
+                arg.ty = if param.needs_nul {
+                    mk().ref_ty(mk().path_ty(vec!["", "std", "ffi", "CStr"]))
+                } else {
+                    mk().ref_ty(mk().path_ty(vec!["str"]))
+                };
+                arg.pat = mk().ident_pat(param.name);
+            }
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// Check whether `ty` is `*const c_char`.
+fn is_c_string_ty(ty: ty::Ty) -> bool {
+    match ty.kind {
+        TyKind::RawPtr(ty::TypeAndMut { ty, mutbl: hir::Mutability::MutImmutable }) => {
+            matches!([ty.kind] TyKind::Int(IntTy::I8), TyKind::Uint(UintTy::U8))
+        }
+        _ => false,
+    }
+}
+
+/// The bytes of the string literal `e`, in any of the forms translated C string literals
+/// take, such as `b"foo\x00" as *const u8 as *const libc::c_char` or
+/// `(*::std::mem::transmute::<&[u8; 4], &[libc::c_char; 4]>(b"foo\x00")).as_ptr()`.
+fn c_string_literal(e: &Expr) -> Option<&[u8]> {
+    match e.kind {
+        ExprKind::Lit(Lit { kind: LitKind::ByteStr(ref b), .. }) => Some(b),
+        ExprKind::Cast(ref inner, _) |
+        ExprKind::Paren(ref inner) |
+        ExprKind::Unary(UnOp::Deref, ref inner) => c_string_literal(inner),
+        ExprKind::MethodCall(ref seg, ref args) if seg.ident.as_str() == "as_ptr" => {
+            c_string_literal(&args[0])
+        }
+        ExprKind::Call(ref func, ref args) if args.len() == 1 => {
+            let path = match_or!([func.kind] ExprKind::Path(None, ref path) => path;
+                                 return None);
+            if path.segments.last()?.ident.as_str() != "transmute" {
+                return None;
+            }
+            c_string_literal(&args[0])
+        }
+        _ => None,
+    }
+}
+
+/// If `e` is `c.as_ptr()` for a `CString` `c`, get `c`.
+fn cstring_ptr<'a>(cx: &RefactorCtxt, e: &'a Expr) -> Option<&'a P<Expr>> {
+    let (seg, args) = match_or!([strip_casts(e).kind]
+        ExprKind::MethodCall(ref seg, ref args) => (seg, args); return None);
+    if seg.ident.as_str() != "as_ptr" {
+        return None;
+    }
+    let mut ty = cx.opt_node_type(args[0].id)?;
+    while let TyKind::Ref(_, inner, _) = ty.kind {
+        ty = inner;
+    }
+    match ty.kind {
+        TyKind::Adt(adt, _) if cx.ty_ctxt().item_name(adt.did).as_str() == "CString" => {
+            Some(&args[0])
+        }
+        _ => None,
+    }
+}
+
+fn is_zero(e: &Expr) -> bool {
+    match strip_casts(e).kind {
+        ExprKind::Lit(ref lit) => matches!([lit.kind] LitKind::Int(0, _)),
+        _ => false,
+    }
+}
+
+/// Checks the uses of the parameters and the arguments passed to them, and records how to
+/// rewrite them.
+struct UseChecker<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    params: &'a mut HashMap<HirId, StrParam>,
+    positions: &'a HashMap<(DefId, usize), HirId>,
+    uses: HashMap<NodeId, StrUse>,
+    /// Arguments passed to the parameters, with the parameter each is passed to
+    args: HashMap<NodeId, (HirId, StrArg, Span)>,
+}
+
+impl<'a, 'tcx> UseChecker<'a, 'tcx> {
+    /// The parameter `e` refers to, if it's one of ours.
+    fn param(&self, e: &Expr) -> Option<HirId> {
+        let mut e = e;
+        while let ExprKind::Paren(ref inner) = e.kind {
+            e = inner;
+        }
+        if !matches!([e.kind] ExprKind::Path(None, _)) {
+            return None;
+        }
+        self.cx.try_resolve_expr_to_hid(e).filter(|id| self.params.contains_key(id))
+    }
+
+    fn reject(&mut self, param: HirId, reason: &str, span: Span) {
+        self.params.get_mut(&param).unwrap().reject(reason, span);
+    }
+
+    /// A string operand of `strcmp`: a parameter or a literal.
+    fn operand(&self, e: &Expr) -> Option<StrArg> {
+        if let Some(param) = self.param(strip_casts(e)) {
+            return Some(StrArg::Param(param));
+        }
+        match c_string_literal(e) {
+            Some(b) if b.last() == Some(&0) && !b[..b.len() - 1].contains(&0) => {
+                Some(StrArg::Literal(b.to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Check an argument `arg` passed to the parameter `to`.
+    fn visit_arg<'ast>(&mut self, to: HirId, arg: &'ast Expr) {
+        let str_arg = if let Some(from) = self.param(strip_casts(arg)) {
+            StrArg::Param(from)
+        } else if let Some(b) = c_string_literal(arg) {
+            if b.last() != Some(&0) || b[..b.len() - 1].contains(&0) {
+                self.reject(to, "a call passes a literal that isn't a single C string",
+                            arg.span);
+                return;
+            }
+            if str::from_utf8(&b[..b.len() - 1]).is_err() {
+                self.params.get_mut(&to).unwrap().needs_nul = true;
+            }
+            StrArg::Literal(b.to_vec())
+        } else if let Some(c) = cstring_ptr(self.cx, arg) {
+            self.visit_expr(c);
+            StrArg::CString(c.clone())
+        } else {
+            self.reject(to, "a call passes something other than a string literal, a CString, \
+                             or a converted parameter", arg.span);
+            self.visit_expr(arg);
+            return;
+        };
+        self.args.insert(arg.id, (to, str_arg, arg.span));
+    }
+
+    /// If `e` is a `strcmp` between parameters and literals, get its operands.
+    fn compare(&self, e: &Expr) -> Option<(StrArg, StrArg)> {
+        let args = libc_call(self.cx, strip_casts(e), "strcmp", 2)?;
+        let a = self.operand(&args[0])?;
+        let b = self.operand(&args[1])?;
+        match (&a, &b) {
+            (StrArg::Literal(_), StrArg::Literal(_)) => None,
+            _ => Some((a, b)),
+        }
+    }
+}
+
+impl<'a, 'tcx, 'ast> Visitor<'ast> for UseChecker<'a, 'tcx> {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        let cx = self.cx;
+        if let Some(param) = self.param(e) {
+            self.params.get_mut(&param).unwrap().needs_nul = true;
+            self.uses.insert(e.id, StrUse::Ptr(param));
+            return;
+        }
+
+        match e.kind {
+            ExprKind::Path(..) => {
+                // A function with converted parameters may only be called.
+                if let Some(def_id) = cx.try_resolve_expr(e) {
+                    let ids = self.positions.iter()
+                        .filter(|&(&(func, _), _)| func == def_id)
+                        .map(|(_, &id)| id)
+                        .collect::<Vec<_>>();
+                    for id in ids {
+                        self.reject(id, "the function is used other than by calling it", e.span);
+                    }
+                }
+            }
+
+            ExprKind::Call(ref func, ref args) => {
+                if let Some(def_id) = cx.try_resolve_expr(func) {
+                    if self.positions.keys().any(|&(f, _)| f == def_id) {
+                        for (index, arg) in args.iter().enumerate() {
+                            match self.positions.get(&(def_id, index)) {
+                                Some(&to) => self.visit_arg(to, arg),
+                                None => self.visit_expr(arg),
+                            }
+                        }
+                        return;
+                    }
+                }
+
+                if let Some(args) = libc_call(cx, e, "strlen", 1) {
+                    if let Some(param) = self.param(strip_casts(&args[0])) {
+                        self.uses.insert(e.id, StrUse::Len(param));
+                        return;
+                    }
+                }
+                if let Some((a, b)) = self.compare(e) {
+                    self.uses.insert(e.id, StrUse::Compare(a, b, None));
+                    return;
+                }
+                visit::walk_expr(self, e);
+            }
+
+            ExprKind::Binary(op, ref lhs, ref rhs) if
+                    (op.node == BinOpKind::Eq || op.node == BinOpKind::Ne) && is_zero(rhs) => {
+                match self.compare(lhs) {
+                    Some((a, b)) => {
+                        self.uses.insert(e.id, StrUse::Compare(a, b, Some(op.node)));
+                    }
+                    None => visit::walk_expr(self, e),
+                }
+            }
+
+            ExprKind::Unary(UnOp::Deref, ref inner) => {
+                let mut inner = &**inner;
+                while let ExprKind::Paren(ref x) = inner.kind {
+                    inner = x;
+                }
+                if let Some(param) = self.param(inner) {
+                    self.params.get_mut(&param).unwrap().needs_nul = true;
+                    self.uses.insert(e.id, StrUse::Byte(param, None));
+                    return;
+                }
+                match inner.kind {
+                    ExprKind::MethodCall(ref seg, ref args) if seg.ident.as_str() == "offset" => {
+                        if let Some(param) = self.param(&args[0]) {
+                            self.params.get_mut(&param).unwrap().needs_nul = true;
+                            self.uses.insert(e.id, StrUse::Byte(param, Some(args[1].clone())));
+                            self.visit_expr(&args[1]);
+                            return;
+                        }
+                    }
+                    _ => {}
+                }
+                visit::walk_expr(self, e);
+            }
+
+            ExprKind::MethodCall(ref seg, ref args) => match self.param(&args[0]) {
+                Some(param) if seg.ident.as_str() == "is_null" => {
+                    self.uses.insert(e.id, StrUse::IsNull(param));
+                }
+                Some(param) => {
+                    self.reject(param, &format!("`{}` can't be done on a string slice", seg.ident),
+                                e.span);
+                    for arg in &args[1..] {
+                        self.visit_expr(arg);
+                    }
+                }
+                None => visit::walk_expr(self, e),
+            },
+
+            ExprKind::Assign(ref lhs, _) | ExprKind::AssignOp(_, ref lhs, _) => {
+                if let Some(param) = self.param(lhs) {
+                    self.reject(param, "it is assigned to", e.span);
+                }
+                visit::walk_expr(self, e);
+            }
+
+            ExprKind::AddrOf(_, ref inner) => {
+                if let Some(param) = self.param(inner) {
+                    self.reject(param, "its address is taken", e.span);
+                    return;
+                }
+                visit::walk_expr(self, e);
+            }
+
+            _ => visit::walk_expr(self, e),
+        }
+    }
+
+    fn visit_mac(&mut self, mac: &'ast Mac) {
+        visit::walk_mac(self, mac);
+    }
+}
+
+/// Rewrites the uses of the converted parameters, and the arguments passed to them.
+struct Rewriter<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    params: &'a HashMap<HirId, StrParam>,
+    uses: &'a HashMap<NodeId, StrUse>,
+    args: &'a HashMap<NodeId, (HirId, StrArg, Span)>,
+}
+
+impl<'a, 'tcx> Rewriter<'a, 'tcx> {
+    fn converted(&self, param: HirId) -> Option<&'a StrParam> {
+        let params = self.params;
+        params.get(&param).filter(|p| p.converted())
+    }
+
+    /// The type of `e`, to cast the replacement of `e` to.
+    fn ty(&self, e: &Expr) -> P<Ty> {
+        reflect_tcx_ty(self.cx.ty_ctxt(), self.cx.node_type(e.id))
+    }
+
+    /// The operands of a `strcmp` being replaced, as `&str`s if possible, and as byte slices
+    /// otherwise.
+    fn operands(&self, a: &StrArg, b: &StrArg) -> (P<Expr>, P<Expr>) {
+        let as_str = [a, b].iter().all(|arg| match **arg {
+            StrArg::Param(id) => !self.params[&id].needs_nul,
+            StrArg::Literal(ref bytes) => str::from_utf8(&bytes[..bytes.len() - 1]).is_ok(),
+            StrArg::CString(_) => false,
+        });
+        let operand = |arg: &StrArg| match *arg {
+            StrArg::Param(id) if as_str => mk().ident_expr(self.params[&id].name),
+            StrArg::Param(id) => self.params[&id].as_bytes(),
+            StrArg::Literal(ref bytes) if as_str => mk().lit_expr(
+                mk().str_lit(str::from_utf8(&bytes[..bytes.len() - 1]).unwrap())),
+            StrArg::Literal(ref bytes) => mk().addr_of_expr(mk().index_expr(
+                mk().lit_expr(mk().bytestr_lit(bytes[..bytes.len() - 1].to_vec())),
+                mk().range_expr(None as Option<P<Expr>>, None as Option<P<Expr>>),
+            )),
+            StrArg::CString(_) => unreachable!(),
+        };
+        (operand(a), operand(b))
+    }
+
+    fn rewrite_use(&mut self, e: &Expr, u: &StrUse) -> Option<P<Expr>> {
+        let all_converted = |rw: &Self, args: &[&StrArg]| args.iter().all(|arg| match **arg {
+            StrArg::Param(id) => rw.converted(id).is_some(),
+            _ => true,
+        });
+
+        Some(match *u {
+            StrUse::Len(id) => {
+                let param = self.converted(id)?;
+                let s = if param.needs_nul {
+                    param.as_bytes()
+                } else {
+                    mk().ident_expr(param.name)
+                };
+                let len = mk().method_call_expr(s, "len", Vec::<P<Expr>>::new());
+                mk().cast_expr(len, self.ty(e))
+            }
+
+            StrUse::Byte(id, ref index) => {
+                let param = self.converted(id)?;
+                let index = match *index {
+                    Some(ref index) => {
+                        let mut index = index.clone();
+                        self.visit_expr(&mut index);
+                        mk().cast_expr(index, mk().path_ty(vec!["usize"]))
+                    }
+                    None => mk().lit_expr(mk().int_lit(0, LitIntType::Unsuffixed)),
+                };
+                let bytes = mk().method_call_expr(
+                    mk().ident_expr(param.name),
+                    "to_bytes_with_nul",
+                    Vec::<P<Expr>>::new(),
+                );
+                mk().cast_expr(mk().index_expr(bytes, index), self.ty(e))
+            }
+
+            StrUse::IsNull(id) => {
+                self.converted(id)?;
+                mk().lit_expr(mk().bool_lit(false))
+            }
+
+            StrUse::Compare(ref a, ref b, op) => {
+                if !all_converted(self, &[a, b]) {
+                    return None;
+                }
+                let (a, b) = self.operands(a, b);
+                match op {
+                    Some(op) => mk().binary_expr(op, a, b),
+                    None => mk().cast_expr(mk().method_call_expr(a, "cmp", vec![b]), self.ty(e)),
+                }
+            }
+
+            StrUse::Ptr(id) => {
+                let param = self.converted(id)?;
+                mk().method_call_expr(mk().ident_expr(param.name), "as_ptr",
+                                      Vec::<P<Expr>>::new())
+            }
+        })
+    }
+
+    fn rewrite_arg(&mut self, to: HirId, arg: &StrArg) -> Option<P<Expr>> {
+        let to = match self.converted(to) {
+            Some(to) => to,
+            None => {
+                // The parameter we were passed to isn't converted, so pass on the pointer.
+                let from = match_or!([*arg] StrArg::Param(from) => from; return None);
+                let from = self.converted(from)?;
+                return Some(mk().method_call_expr(mk().ident_expr(from.name), "as_ptr",
+                                                  Vec::<P<Expr>>::new()));
+            }
+        };
+
+        Some(match *arg {
+            StrArg::Literal(ref bytes) if to.needs_nul => mk().method_call_expr(
+                mk().call_expr(
+                    mk().path_expr(vec!["", "std", "ffi", "CStr", "from_bytes_with_nul"]),
+                    vec![mk().lit_expr(mk().bytestr_lit(bytes.clone()))],
+                ),
+                "unwrap",
+                Vec::<P<Expr>>::new(),
+            ),
+            StrArg::Literal(ref bytes) => mk().lit_expr(
+                mk().str_lit(str::from_utf8(&bytes[..bytes.len() - 1]).unwrap())),
+
+            StrArg::CString(ref c) => {
+                let mut c = c.clone();
+                self.visit_expr(&mut c);
+                if to.needs_nul {
+                    mk().method_call_expr(c, "as_c_str", Vec::<P<Expr>>::new())
+                } else {
+                    mk().method_call_expr(
+                        mk().method_call_expr(c, "to_str", Vec::<P<Expr>>::new()),
+                        "unwrap",
+                        Vec::<P<Expr>>::new(),
+                    )
+                }
+            }
+
+            StrArg::Param(from) => {
+                let from = &self.params[&from];
+                let e = mk().ident_expr(from.name);
+                if from.needs_nul && !to.needs_nul {
+                    mk().method_call_expr(
+                        mk().method_call_expr(e, "to_str", Vec::<P<Expr>>::new()),
+                        "unwrap",
+                        Vec::<P<Expr>>::new(),
+                    )
+                } else {
+                    e
+                }
+            }
+        })
+    }
+}
+
+impl<'a, 'tcx> MutVisitor for Rewriter<'a, 'tcx> {
+    fn visit_expr(&mut self, e: &mut P<Expr>) {
+        let uses = self.uses;
+        let args = self.args;
+        let new_e = if let Some(u) = uses.get(&e.id) {
+            self.rewrite_use(e, u)
+        } else if let Some(&(to, ref arg, _)) = args.get(&e.id) {
+            self.rewrite_arg(to, arg)
+        } else {
+            None
+        };
+
+        match new_e {
+            Some(new_e) => *e = new_e,
+            None => mut_visit::noop_visit_expr(e, self),
+        }
+    }
+
+    fn visit_mac(&mut self, mac: &mut Mac) {
+        mut_visit::noop_visit_mac(mac, self);
+    }
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("lift_string_conversions", |_args| mk(LiftStringConversions));
+}
@@ -0,0 +1,177 @@
+use rustc::ty::{self, ParamEnv, TyKind};
+use syntax::ast::*;
+use syntax::ptr::P;
+
+use crate::ast_manip::MutVisitNodes;
+use crate::command::{CommandState, Registry};
+use crate::driver::Phase;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+use c2rust_ast_builder::mk;
+
+/// # `detransmute` Command
+///
+/// Usage: `detransmute`
+///
+/// Replace `transmute`s between numeric types of the same size with their safe equivalents:
+/// `to_bits`/`from_bits` between floats and integers, `to_ne_bytes`/`from_ne_bytes` between
+/// numbers and byte arrays, and `as` between integers that differ only in signedness.  An
+/// `unsafe` block containing nothing but such a transmute is removed along with it; run
+/// `fix_unused_unsafe` afterwards to clean up any other blocks that no longer need to be
+/// `unsafe`.  Transmutes between types without a safe equivalent are left alone.
+///
+/// Example:
+///
+/// ```ignore
+///     let bits: u32 = unsafe { mem::transmute::<f32, u32>(x) };
+///     let y: f64 = unsafe { mem::transmute::<i64, f64>(z) };
+/// ```
+///
+/// After running `detransmute`:
+///
+/// ```ignore
+///     let bits: u32 = x.to_bits();
+///     let y: f64 = f64::from_bits(z as u64);
+/// ```
+pub struct Detransmute;
+
+impl Transform for Detransmute {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            if e.id == DUMMY_NODE_ID {
+                return;
+            }
+
+            // `unsafe { transmute(x) }`
+            let new_e = match e.kind {
+                ExprKind::Block(ref b, None) if is_unsafe_block(b) && b.stmts.len() == 1 => {
+                    match b.stmts[0].kind {
+                        StmtKind::Expr(ref inner) => detransmute(cx, inner),
+                        _ => None,
+                    }
+                }
+                _ => detransmute(cx, e),
+            };
+            if let Some(new_e) = new_e {
+                *e = new_e;
+            }
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+fn is_unsafe_block(b: &Block) -> bool {
+    match b.rules {
+        BlockCheckMode::Unsafe(UnsafeSource::UserProvided) => true,
+        _ => false,
+    }
+}
+
+/// A type with a safe conversion to and from its bits.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Repr {
+    Int,
+    Float,
+    Bytes,
+}
+
+/// Get the safe equivalent of `e`, if it is a transmute between numeric types.
+fn detransmute(cx: &RefactorCtxt, e: &Expr) -> Option<P<Expr>> {
+    let (callee, args) = match e.kind {
+        ExprKind::Call(ref callee, ref args) if args.len() == 1 => (callee, args),
+        _ => return None,
+    };
+    let callee_did = cx.try_resolve_expr(callee)?;
+    let callee_str = cx.ty_ctxt().def_path_str(callee_did);
+    if callee_str != "std::intrinsics::transmute" && callee_str != "core::intrinsics::transmute" {
+        return None;
+    }
+
+    let arg = args[0].clone();
+    let from = cx.opt_node_type(arg.id)?;
+    let to = cx.opt_node_type(e.id)?;
+    let (from_repr, size) = repr(cx, from)?;
+    let (to_repr, to_size) = repr(cx, to)?;
+    if size != to_size {
+        return None;
+    }
+
+    let bits_ty = || mk().ident_ty(format!("u{}", size * 8));
+    let to_name = to.to_string();
+    Some(match (from_repr, to_repr) {
+        (Repr::Int, Repr::Int) if from == to => arg,
+        (Repr::Int, Repr::Int) => mk().cast_expr(arg, mk().ident_ty(&to_name)),
+        (Repr::Float, Repr::Float) => arg,
+
+        (Repr::Float, Repr::Int) => {
+            let bits = mk().method_call_expr(arg, "to_bits", Vec::<P<Expr>>::new());
+            if is_bits_ty(to) {
+                bits
+            } else {
+                mk().cast_expr(bits, mk().ident_ty(&to_name))
+            }
+        }
+        (Repr::Int, Repr::Float) => {
+            let bits = if is_bits_ty(from) {
+                arg
+            } else {
+                mk().cast_expr(arg, bits_ty())
+            };
+            mk().call_expr(mk().path_expr(vec![to_name.as_str(), "from_bits"]), vec![bits])
+        }
+
+        (Repr::Int, Repr::Bytes) => {
+            mk().method_call_expr(arg, "to_ne_bytes", Vec::<P<Expr>>::new())
+        }
+        (Repr::Float, Repr::Bytes) => {
+            let bits = mk().method_call_expr(arg, "to_bits", Vec::<P<Expr>>::new());
+            mk().method_call_expr(bits, "to_ne_bytes", Vec::<P<Expr>>::new())
+        }
+        (Repr::Bytes, Repr::Int) => {
+            mk().call_expr(mk().path_expr(vec![to_name.as_str(), "from_ne_bytes"]), vec![arg])
+        }
+        (Repr::Bytes, Repr::Float) => {
+            let bits_name = format!("u{}", size * 8);
+            let bits = mk().call_expr(
+                mk().path_expr(vec![bits_name.as_str(), "from_ne_bytes"]),
+                vec![arg],
+            );
+            mk().call_expr(mk().path_expr(vec![to_name.as_str(), "from_bits"]), vec![bits])
+        }
+
+        (Repr::Bytes, Repr::Bytes) => return None,
+    })
+}
+
+/// Classify `ty` and get its size in bytes.  Only `u8` arrays of the size of a primitive
+/// integer count as bytes.
+fn repr<'tcx>(cx: &RefactorCtxt<'_, 'tcx>, ty: ty::Ty<'tcx>) -> Option<(Repr, u64)> {
+    let tcx = cx.ty_ctxt();
+    let size = tcx.layout_of(ParamEnv::reveal_all().and(ty)).ok()?.size.bytes();
+    let repr = match ty.kind {
+        TyKind::Int(_) | TyKind::Uint(_) => Repr::Int,
+        TyKind::Float(_) => Repr::Float,
+        TyKind::Array(elem, _) if elem.kind == TyKind::Uint(UintTy::U8)
+            && size.is_power_of_two() && size <= 16 => Repr::Bytes,
+        _ => return None,
+    };
+    Some((repr, size))
+}
+
+/// Check whether `ty` is the unsigned integer type that `from_bits` and `to_bits` use.
+fn is_bits_ty(ty: ty::Ty) -> bool {
+    match ty.kind {
+        TyKind::Uint(UintTy::Usize) => false,
+        TyKind::Uint(_) => true,
+        _ => false,
+    }
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("detransmute", |_args| mk(Detransmute));
+}
@@ -0,0 +1,68 @@
+//! Expression helpers shared by the commands that rewrite translated C idioms.
+use rustc::hir::HirId;
+use syntax::ast::*;
+use syntax::ptr::P;
+use syntax::symbol::Symbol;
+
+use crate::RefactorCtxt;
+
+/// Look through any casts and parentheses around `e`.
+pub(crate) fn strip_casts(mut e: &Expr) -> &Expr {
+    while let ExprKind::Cast(ref inner, _) | ExprKind::Paren(ref inner) = e.kind {
+        e = inner;
+    }
+    e
+}
+
+/// Check whether `e` refers to the local `hir_id`, possibly through casts.
+pub(crate) fn is_local(cx: &RefactorCtxt, hir_id: HirId, e: &Expr) -> bool {
+    cx.try_resolve_expr_to_hid(strip_casts(e)) == Some(hir_id)
+}
+
+/// Check whether `e` is a literal `0`, possibly cast, as C's `NULL` is translated.
+pub(crate) fn is_null(e: &Expr) -> bool {
+    match strip_casts(e).kind {
+        ExprKind::Lit(ref lit) => matches!([lit.kind] LitKind::Int(0, _)),
+        _ => false,
+    }
+}
+
+/// Get the name `e` refers to, if it's a single identifier.
+pub(crate) fn path_name(e: &Expr) -> Option<Symbol> {
+    match e.kind {
+        ExprKind::Path(None, ref path) if path.segments.len() == 1 => {
+            Some(path.segments[0].ident.name)
+        }
+        _ => None,
+    }
+}
+
+/// The expression of an expression statement, with or without a semicolon.
+pub(crate) fn stmt_expr(stmt: &Stmt) -> Option<&P<Expr>> {
+    match stmt.kind {
+        StmtKind::Semi(ref e) | StmtKind::Expr(ref e) => Some(e),
+        _ => None,
+    }
+}
+
+/// The name of the foreign function `func` refers to.
+pub(crate) fn foreign_fn_name(cx: &RefactorCtxt, func: &Expr) -> Option<Symbol> {
+    let callee = cx.try_resolve_expr(func)?;
+    let tcx = cx.ty_ctxt();
+    if tcx.is_foreign_item(callee) {
+        Some(tcx.item_name(callee))
+    } else {
+        None
+    }
+}
+
+/// If `e` is a call to the libc function `name` with `nargs` arguments, get its arguments.
+pub(crate) fn libc_call<'a>(cx: &RefactorCtxt, e: &'a Expr, name: &str, nargs: usize)
+                            -> Option<&'a [P<Expr>]> {
+    let (func, args) = match_or!([e.kind] ExprKind::Call(ref func, ref args) => (func, args);
+                                 return None);
+    match foreign_fn_name(cx, func) {
+        Some(n) if n.as_str() == name && args.len() == nargs => Some(args),
+        _ => None,
+    }
+}
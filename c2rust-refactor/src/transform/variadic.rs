@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+use syntax::ast::*;
+use syntax::ptr::P;
+use syntax::symbol::Symbol;
+use smallvec::SmallVec;
+
+use c2rust_ast_builder::{mk, IntoSymbol};
+use crate::ast_manip::{FlatMapNodes, MutVisitNodes, visit_nodes};
+use crate::command::{CommandState, Registry};
+use crate::transform::util::path_name;
+use crate::transform::Transform;
+use crate::RefactorCtxt;
+
+
+/// # `forward_va_list` Command
+///
+/// Usage: `forward_va_list`
+///
+/// Untangle variadic functions that read their variable arguments through a `va_list`.  The
+/// transpiler translates `va_list ap; va_start(ap, fmt);` into a `VaListImpl` local that is
+/// started from the variable arguments, `let mut ap: VaListImpl; ap = args.clone();`.
+///
+/// A function that only passes its `va_list` on, like a `printf` that calls `vprintf`, is
+/// rewritten to pass the variable arguments directly:
+///
+/// ```ignore
+///     pub unsafe extern "C" fn printf(mut fmt: *const c_char, mut args: ...) -> c_int {
+///         let mut ap: ::std::ffi::VaListImpl;
+///         ap = args.clone();
+///         let mut n: c_int = vprintf(fmt, ap.as_va_list());
+///         return n;
+///     }
+/// ```
+///
+/// becomes
+///
+/// ```ignore
+///     pub unsafe extern "C" fn printf(mut fmt: *const c_char, mut args: ...) -> c_int {
+///         let mut n: c_int = vprintf(fmt, args.as_va_list());
+///         return n;
+///     }
+/// ```
+///
+/// Any other such function `foo` is split into a shared implementation `vfoo`, which takes the
+/// `va_list` as a `VaList` after the other arguments and contains the original body, and a
+/// variadic entry point `foo`, which forwards to it with `vfoo(fmt, args.as_va_list())`.  A
+/// function is left alone if the name `vfoo` is already taken.
+///
+/// Only functions that start a single `va_list` once, in a statement at the top level of their
+/// body, and otherwise only call methods on it, are rewritten.
+pub struct ForwardVaList;
+
+impl Transform for ForwardVaList {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, _cx: &RefactorCtxt) {
+        // Names that are already taken, which shared implementations must not reuse
+        let mut names = HashSet::new();
+        visit_nodes(krate, |i: &Item| {
+            names.insert(i.ident.name);
+        });
+
+        FlatMapNodes::visit(krate, |i: P<Item>| {
+            let va = match started_va_list(&i) {
+                Some(va) => va,
+                None => return smallvec![i],
+            };
+
+            if va.only_forwarded {
+                return smallvec![forward_args(i, &va)];
+            }
+
+            let name = format!("v{}", i.ident).into_symbol();
+            if !names.insert(name) {
+                warn!("not splitting `{}`: `{}` already exists", i.ident, name);
+                return smallvec![i];
+            }
+            split_va_list(i, &va, name)
+        });
+    }
+}
+
+/// A `va_list` started from the variable arguments of a function.
+struct StartedVaList {
+    /// The name of the variable arguments parameter
+    args: Symbol,
+    /// The `VaListImpl` local the arguments are copied into, and its pattern and type
+    local: Symbol,
+    local_pat: P<Pat>,
+    local_ty: P<Ty>,
+    /// The indices of the declaration of the local and of the statement starting it in the
+    /// function body
+    decl_idx: usize,
+    start_idx: usize,
+    /// Whether the local is only used to pass it on with `as_va_list`
+    only_forwarded: bool,
+}
+
+/// Find the `va_list` started by the function `i`, if it only starts one, and uses it in a
+/// way that can be rewritten.
+fn started_va_list(i: &Item) -> Option<StartedVaList> {
+    let (decl, generics, block) = match i.kind {
+        ItemKind::Fn(ref decl, _, ref generics, ref block) if decl.c_variadic() => {
+            (decl, generics, block)
+        }
+        _ => return None,
+    };
+    // The entry point passes its arguments on by name
+    if !generics.params.is_empty() ||
+       !decl.inputs.iter().all(|arg| matches!([arg.pat.kind] PatKind::Ident(_, _, None))) {
+        return None;
+    }
+    let args = expect!([decl.inputs.last()?.pat.kind] PatKind::Ident(_, ident, _) => ident.name);
+
+    // `ap = args.clone();`
+    let mut starts = block.stmts.iter().enumerate().filter_map(|(idx, s)| {
+        let e = match_or!([s.kind] StmtKind::Semi(ref e) => e; return None);
+        let (lhs, rhs) = match_or!([e.kind] ExprKind::Assign(ref lhs, ref rhs) => (lhs, rhs);
+                                   return None);
+        let (seg, call_args) = match_or!([rhs.kind] ExprKind::MethodCall(ref seg, ref call_args) =>
+                                         (seg, call_args); return None);
+        if seg.ident.name.as_str() != "clone" || path_name(&call_args[0]) != Some(args) {
+            return None;
+        }
+        Some((idx, path_name(lhs)?))
+    });
+    let (start_idx, local) = starts.next()?;
+    if starts.next().is_some() {
+        return None;
+    }
+
+    // `let mut ap: VaListImpl;`
+    let (decl_idx, local_pat, local_ty) = block.stmts[..start_idx].iter().enumerate()
+        .filter_map(|(idx, s)| {
+            let l = match_or!([s.kind] StmtKind::Local(ref l) => l; return None);
+            let name = match_or!([l.pat.kind] PatKind::Ident(_, ident, None) => ident.name;
+                                 return None);
+            let ty = l.ty.as_ref()?;
+            let path = match_or!([ty.kind] TyKind::Path(None, ref path) => path; return None);
+            if name != local || l.init.is_some() ||
+               path.segments.last()?.ident.name.as_str() != "VaListImpl" {
+                return None;
+            }
+            Some((idx, l.pat.clone(), ty.clone()))
+        })
+        .last()?;
+
+    // Apart from the start, the arguments must be unused, and the local only used to call
+    // methods on
+    let mut args_uses = 0;
+    let mut local_uses = 0;
+    let mut method_calls = 0;
+    let mut forwards = 0;
+    visit_nodes(&**block, |e: &Expr| {
+        match path_name(e) {
+            Some(name) if name == args => args_uses += 1,
+            Some(name) if name == local => local_uses += 1,
+            _ => {}
+        }
+        if let ExprKind::MethodCall(ref seg, ref call_args) = e.kind {
+            if path_name(&call_args[0]) == Some(local) {
+                method_calls += 1;
+                if seg.ident.name.as_str() == "as_va_list" {
+                    forwards += 1;
+                }
+            }
+        }
+    });
+    if args_uses != 1 || local_uses != method_calls + 1 {
+        return None;
+    }
+
+    Some(StartedVaList {
+        args,
+        local,
+        local_pat,
+        local_ty,
+        decl_idx,
+        start_idx,
+        only_forwarded: forwards == method_calls,
+    })
+}
+
+/// Pass the variable arguments of `i` on directly, instead of through its `va_list` local.
+fn forward_args(i: P<Item>, va: &StartedVaList) -> P<Item> {
+    i.map(|mut i| {
+        if let ItemKind::Fn(_, _, _, ref mut block) = i.kind {
+            remove_start(block, va);
+            MutVisitNodes::visit(block, |e: &mut P<Expr>| {
+                if let ExprKind::MethodCall(_, ref mut call_args) = e.kind {
+                    if path_name(&call_args[0]) == Some(va.local) {
+                        call_args[0] = mk().ident_expr(va.args);
+                    }
+                }
+            });
+        }
+        i
+    })
+}
+
+/// Move the body of `i` into a shared implementation `name` taking the `va_list` as a
+/// `VaList`, and make `i` forward its arguments to it.
+fn split_va_list(i: P<Item>, va: &StartedVaList, name: Symbol) -> SmallVec<[P<Item>; 1]> {
+    let (decl, header, mut body) = expect!([i.kind]
+        ItemKind::Fn(ref decl, ref header, _, ref block) => (decl.clone(), *header, block.clone()));
+    remove_start(&mut body, va);
+
+    // The `VaList` type is next to `VaListImpl`, in `std::ffi` or `core::ffi`
+    let mut va_list_ty = va.local_ty.clone();
+    if let TyKind::Path(_, ref mut path) = va_list_ty.kind {
+        path.segments.last_mut().unwrap().ident = mk().ident("VaList");
+    }
+    let shared_decl = decl.clone().map(|decl| {
+        let mut inputs = decl.inputs;
+        inputs.pop();
+        inputs.push(mk().arg(va_list_ty, va.local_pat.clone()));
+        FnDecl { inputs, .. decl }
+    });
+    let shared = mk().vis(i.vis.clone()).unsafety(header.unsafety).abi(header.abi)
+        .fn_item(name, shared_decl, body);
+
+    let forward_args = decl.inputs.iter().map(|arg| {
+        let name = expect!([arg.pat.kind] PatKind::Ident(_, ident, _) => ident.name);
+        if name == va.args {
+            mk().method_call_expr(mk().ident_expr(name), "as_va_list", Vec::<P<Expr>>::new())
+        } else {
+            mk().ident_expr(name)
+        }
+    }).collect();
+    let entry = i.map(|mut i| {
+        if let ItemKind::Fn(_, _, _, ref mut block) = i.kind {
+            *block = mk().block(vec![
+                mk().expr_stmt(mk().call_expr(mk().path_expr(vec![name]), forward_args)),
+            ]);
+        }
+        i
+    });
+
+    smallvec![entry, shared]
+}
+
+/// Remove the declaration of the `va_list` local and the statement starting it from `block`.
+fn remove_start(block: &mut P<Block>, va: &StartedVaList) {
+    block.stmts.remove(va.start_idx);
+    block.stmts.remove(va.decl_idx);
+}
+
+
+pub fn register_commands(reg: &mut Registry) {
+    use super::mk;
+
+    reg.register("forward_va_list", |_args| mk(ForwardVaList));
+}
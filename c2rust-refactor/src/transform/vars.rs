@@ -1,12 +1,14 @@
 use std::collections::hash_map::{HashMap, Entry};
 use std::collections::HashSet;
 use std::mem;
-use rustc::hir::def_id::LOCAL_CRATE;
-use rustc::hir::HirId;
-use rustc::ty::{TyKind, ParamEnv};
+use rustc::hir::{self, HirId};
+use rustc::hir::def_id::{DefId, LOCAL_CRATE};
+use rustc::ty::{self, TyKind, ParamEnv};
 use syntax::ast::*;
+use syntax::attr;
 use syntax::mut_visit::{self, MutVisitor};
 use syntax::ptr::P;
+use syntax::symbol::{sym, Symbol};
 use syntax::visit::{self, Visitor};
 
 use c2rust_ast_builder::mk;
@@ -15,6 +17,7 @@ use crate::command::{CommandState, DriverCommand, Registry};
 use crate::driver::{Phase};
 use crate::matcher::{MatchCtxt, Subst, mut_visit_match_with, replace_stmts};
 use crate::reflect::reflect_tcx_ty;
+use crate::transform::util::{is_local, strip_casts};
 use crate::transform::Transform;
 use rustc::middle::cstore::CrateStore;
 use crate::RefactorCtxt;
@@ -251,8 +254,13 @@ fn expr_has_side_effects(cx: &RefactorCtxt, e: &P<Expr>) -> bool {
 
 
 fn is_uninit_call(cx: &RefactorCtxt, e: &Expr) -> bool {
+    is_mem_fn_call(cx, e, "uninitialized")
+}
+
+/// Check if `e` is a call to the function `name` in `std::mem` or `core::mem`.
+fn is_mem_fn_call(cx: &RefactorCtxt, e: &Expr, name: &str) -> bool {
     let func = match_or!([e.kind] ExprKind::Call(ref func, _) => func; return false);
-    let def_id = cx.resolve_expr(func);
+    let def_id = match_or!([cx.try_resolve_expr(func)] Some(x) => x; return false);
     if def_id.krate == LOCAL_CRATE {
         return false;
     }
@@ -262,7 +270,7 @@ fn is_uninit_call(cx: &RefactorCtxt, e: &Expr) -> bool {
     (crate_name.as_str() == "std" || crate_name.as_str() == "core") &&
     path.data.len() == 2 &&
     path.data[0].data.get_opt_name().map_or(false, |sym| sym.as_str() == "mem") &&
-    path.data[1].data.get_opt_name().map_or(false, |sym| sym.as_str() == "uninitialized")
+    path.data[1].data.get_opt_name().map_or(false, |sym| sym.as_str() == name)
 }
 
 
@@ -478,6 +486,472 @@ impl Transform for UninitToDefault {
 }
 
 
+/// # `uninit_to_maybeuninit` Command
+///
+/// Usage: `uninit_to_maybeuninit`
+///
+/// Replace each local initialized with `mem::uninitialized()`.  Numbers and raw pointers are
+/// initialized to zero or null instead, structs that derive `Default` to `Default::default()`,
+/// and arrays of numbers or pointers to an array of zeroes.
+///
+/// Other locals become `MaybeUninit`s, as long as the statements that follow in the same block
+/// initialize them fully before any other use, either by assigning every field or by passing
+/// `&mut x` to a call that fills it in.  These writes go through `x.as_mut_ptr()`, and once the
+/// value is complete it is rebound with `assume_init()`:
+///
+/// ```ignore
+///     let mut s: stat = ::std::mem::uninitialized();
+///     fstat(fd, &mut s);
+/// ```
+///
+/// becomes
+///
+/// ```ignore
+///     let mut s: ::std::mem::MaybeUninit<stat> = ::std::mem::MaybeUninit::uninit();
+///     fstat(fd, s.as_mut_ptr());
+///     let mut s: stat = s.assume_init();
+/// ```
+///
+/// Locals that are used before they are known to be initialized get
+/// `MaybeUninit::uninit().assume_init()`, which is no safer than `mem::uninitialized()`, and a
+/// warning naming the local.
+pub struct UninitToMaybeUninit;
+
+impl Transform for UninitToMaybeUninit {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        let mut default_structs = HashSet::new();
+        visit_nodes(krate, |i: &Item| {
+            if let ItemKind::Struct(..) = i.kind {
+                if derives_default(i) {
+                    default_structs.insert(cx.node_def_id(i.id));
+                }
+            }
+        });
+
+        MutVisitNodes::visit(krate, |b: &mut P<Block>| {
+            let mut i = 0;
+            while i < b.stmts.len() {
+                let (l, hir_id, name) = match uninit_local(cx, &b.stmts[i]) {
+                    Some(x) => x,
+                    None => {
+                        i += 1;
+                        continue;
+                    }
+                };
+                let ty = cx.node_type(l.init.as_ref().unwrap().id);
+
+                if let Some(zero) = zero_value(cx, &default_structs, ty) {
+                    b.stmts[i] = mk().local_stmt(P(Local { init: Some(zero), .. l }));
+                    i += 1;
+                    continue;
+                }
+
+                let end = match find_init_end(cx, hir_id, ty, &b.stmts[i + 1..]) {
+                    Ok(end) => i + 1 + end,
+                    Err(reason) => {
+                        eprintln!("uninit_to_maybeuninit: {}: {}", name, reason);
+                        let init = mk().method_call_expr(
+                            mk().call_expr(
+                                mk().path_expr(vec!["", "std", "mem", "MaybeUninit", "uninit"]),
+                                Vec::<P<Expr>>::new(),
+                            ),
+                            "assume_init",
+                            Vec::<P<Expr>>::new(),
+                        );
+                        b.stmts[i] = mk().local_stmt(P(Local { init: Some(init), .. l }));
+                        i += 1;
+                        continue;
+                    }
+                };
+
+                let ast_ty = l.ty.clone().unwrap_or_else(|| reflect_tcx_ty(cx.ty_ctxt(), ty));
+                let maybe_uninit_ty = mk().path_ty(vec![
+                    mk().path_segment(""),
+                    mk().path_segment("std"),
+                    mk().path_segment("mem"),
+                    mk().path_segment_with_args(
+                        "MaybeUninit",
+                        mk().angle_bracketed_args(vec![ast_ty.clone()]),
+                    ),
+                ]);
+                let uninit = mk().call_expr(
+                    mk().path_expr(vec!["", "std", "mem", "MaybeUninit", "uninit"]),
+                    Vec::<P<Expr>>::new(),
+                );
+                b.stmts[i] = mk().local_stmt(P(mk().local(
+                    mk().mutbl().ident_pat(name),
+                    Some(maybe_uninit_ty),
+                    Some(uninit),
+                )));
+
+                for stmt in &mut b.stmts[i + 1..=end] {
+                    MutVisitNodes::visit(stmt, |e: &mut P<Expr>| {
+                        let as_mut_ptr = || mk().method_call_expr(
+                            mk().ident_expr(name),
+                            "as_mut_ptr",
+                            Vec::<P<Expr>>::new(),
+                        );
+                        let new_e = match e.kind {
+                            ExprKind::AddrOf(_, ref inner) if is_local(cx, hir_id, inner) => {
+                                as_mut_ptr()
+                            }
+                            ExprKind::Field(ref base, field) if is_local(cx, hir_id, base) => {
+                                let place = mk().unary_expr(UnOp::Deref, as_mut_ptr());
+                                mk().field_expr(mk().paren_expr(place), field)
+                            }
+                            _ => return,
+                        };
+                        *e = new_e;
+                    });
+                }
+
+                let assume_init = mk().method_call_expr(
+                    mk().ident_expr(name),
+                    "assume_init",
+                    Vec::<P<Expr>>::new(),
+                );
+                b.stmts.insert(end + 1, mk().local_stmt(P(mk().local(
+                    l.pat.clone(),
+                    Some(ast_ty),
+                    Some(assume_init),
+                ))));
+                i = end + 2;
+            }
+        })
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+fn derives_default(i: &Item) -> bool {
+    i.attrs.iter()
+        .filter(|attr| attr.check_name(sym::derive))
+        .filter_map(|attr| attr.meta_item_list())
+        .any(|list| list.iter().any(|m| m.check_name(Symbol::intern("Default"))))
+}
+
+/// If `stmt` is `let x = mem::uninitialized();`, get the local along with its `HirId` and name.
+fn uninit_local(cx: &RefactorCtxt, stmt: &Stmt) -> Option<(Local, HirId, Ident)> {
+    let l = match_or!([stmt.kind] StmtKind::Local(ref l) => l; return None);
+    if !l.init.as_ref().map_or(false, |e| is_uninit_call(cx, e)) {
+        return None;
+    }
+    let name = match_or!([l.pat.kind] PatKind::Ident(BindingMode::ByValue(_), name, None) => name;
+                         return None);
+    let hir_id = cx.hir_map().node_to_hir_id(l.pat.id);
+    Some(((**l).clone(), hir_id, name))
+}
+
+/// Build a zero value of type `ty`, if it has an obvious one.
+fn zero_value<'tcx>(cx: &RefactorCtxt<'_, 'tcx>,
+                    default_structs: &HashSet<DefId>,
+                    ty: ty::Ty<'tcx>) -> Option<P<Expr>> {
+    let no_args = Vec::<P<Expr>>::new;
+    Some(match ty.kind {
+        TyKind::Bool => mk().lit_expr(mk().bool_lit(false)),
+        TyKind::Char => mk().lit_expr(mk().char_lit('\0')),
+        TyKind::Int(ity) => mk().lit_expr(mk().int_lit(0, ity)),
+        TyKind::Uint(uty) => mk().lit_expr(mk().int_lit(0, uty)),
+        TyKind::Float(fty) => mk().lit_expr(mk().float_lit("0", fty)),
+        TyKind::RawPtr(mt) => {
+            let null = match mt.mutbl {
+                hir::Mutability::MutMutable => "null_mut",
+                hir::Mutability::MutImmutable => "null",
+            };
+            mk().call_expr(mk().path_expr(vec!["", "std", "ptr", null]), no_args())
+        }
+        TyKind::Adt(adt, _) if adt.is_struct() && default_structs.contains(&adt.did) => {
+            mk().call_expr(mk().path_expr(vec!["Default", "default"]), no_args())
+        }
+        // Repeat expressions need a `Copy` element, so structs are out
+        TyKind::Array(elem, _) if elem.ty_adt_def().is_some() => return None,
+        TyKind::Array(elem, len) => {
+            let len = len.eval_usize(cx.ty_ctxt(), ParamEnv::empty());
+            mk().repeat_expr(
+                zero_value(cx, default_structs, elem)?,
+                mk().lit_expr(mk().int_lit(len as u128, "usize")),
+            )
+        }
+        _ => return None,
+    })
+}
+
+/// Find the statement among `stmts` that completes the initialization of the local `hir_id`,
+/// which must not be used in any other way before that.
+fn find_init_end<'tcx>(cx: &RefactorCtxt<'_, 'tcx>,
+                       hir_id: HirId,
+                       ty: ty::Ty<'tcx>,
+                       stmts: &[Stmt]) -> Result<usize, &'static str> {
+    let mut fields_left = match ty.kind {
+        TyKind::Adt(adt, _) if adt.is_struct() => {
+            adt.non_enum_variant().fields.iter().map(|f| f.ident.name).collect::<HashSet<_>>()
+        }
+        _ => HashSet::new(),
+    };
+
+    for (idx, stmt) in stmts.iter().enumerate() {
+        let mut uses = 0;
+        visit_nodes(stmt, |e: &Expr| {
+            if matches!([e.kind] ExprKind::Path(..)) && is_local(cx, hir_id, e) {
+                uses += 1;
+            }
+        });
+        if uses == 0 {
+            continue;
+        }
+
+        // `x.f = ...;`
+        if let StmtKind::Semi(ref e) = stmt.kind {
+            if let ExprKind::Assign(ref lhs, _) = e.kind {
+                if let ExprKind::Field(ref base, field) = lhs.kind {
+                    if uses == 1 && is_local(cx, hir_id, base) &&
+                       fields_left.remove(&field.name) {
+                        if fields_left.is_empty() {
+                            return Ok(idx);
+                        }
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // `f(&mut x);`
+        let e = match stmt.kind {
+            StmtKind::Local(ref l) => l.init.as_ref(),
+            StmtKind::Semi(ref e) | StmtKind::Expr(ref e) => Some(e),
+            _ => None,
+        };
+        if e.map_or(false, |e| filled_by_calls(cx, hir_id, e) == uses) {
+            return Ok(idx);
+        }
+
+        return Err("used before it is fully initialized");
+    }
+
+    Err("not fully initialized in the same block")
+}
+
+/// Count the calls that receive `&mut x` for the local `hir_id` as an argument and are always
+/// evaluated along with `e`.
+fn filled_by_calls(cx: &RefactorCtxt, hir_id: HirId, e: &Expr) -> usize {
+    let count_arg = |arg: &P<Expr>| {
+        match strip_casts(arg).kind {
+            ExprKind::AddrOf(Mutability::Mutable, ref place) if is_local(cx, hir_id, place) => 1,
+            _ => filled_by_calls(cx, hir_id, arg),
+        }
+    };
+    match e.kind {
+        ExprKind::Call(ref func, ref args) => {
+            filled_by_calls(cx, hir_id, func) + args.iter().map(count_arg).sum::<usize>()
+        }
+        ExprKind::MethodCall(_, ref args) => args.iter().map(count_arg).sum(),
+        ExprKind::Binary(op, ref lhs, ref rhs) => match op.node {
+            BinOpKind::And | BinOpKind::Or => filled_by_calls(cx, hir_id, lhs),
+            _ => filled_by_calls(cx, hir_id, lhs) + filled_by_calls(cx, hir_id, rhs),
+        },
+        ExprKind::Assign(ref lhs, ref rhs) | ExprKind::AssignOp(_, ref lhs, ref rhs) => {
+            filled_by_calls(cx, hir_id, lhs) + filled_by_calls(cx, hir_id, rhs)
+        }
+        ExprKind::Cast(ref e, _) | ExprKind::Type(ref e, _) | ExprKind::Paren(ref e) |
+        ExprKind::Unary(_, ref e) | ExprKind::If(ref e, _, _) => filled_by_calls(cx, hir_id, e),
+        _ => 0,
+    }
+}
+
+
+/// # `zeroed_to_default` Command
+///
+/// Usage: `zeroed_to_default`
+///
+/// Replace `mem::zeroed()` with `Default::default()` for structs whose `Default` value is all
+/// zeroes, and `mem::zeroed::<S>()` with `S::default()`.  An `unsafe` block around the call is
+/// removed along with it.
+///
+/// A struct qualifies if every field has a zero `Default` value, i.e., is a number, `bool`,
+/// `char`, an `Option` of a reference or function pointer, an array of at most 32 such values,
+/// or another struct that qualifies.  Structs with references, raw or function pointers, enums
+/// or unions, which have no `Default` value or none that is zero, are left alone, and so are
+/// structs with a hand-written `Default` impl.  Structs that don't implement `Default` yet get a
+/// `#[derive(Default)]`.
+///
+/// Example:
+///
+/// ```ignore
+///     #[derive(Copy, Clone)]
+///     #[repr(C)]
+///     pub struct point {
+///         pub x: libc::c_int,
+///         pub y: libc::c_int,
+///     }
+///
+///     let mut p: point = unsafe { ::std::mem::zeroed() };
+/// ```
+///
+/// becomes
+///
+/// ```ignore
+///     #[derive(Copy, Clone)]
+///     #[derive(Default)]
+///     #[repr(C)]
+///     pub struct point {
+///         pub x: libc::c_int,
+///         pub y: libc::c_int,
+///     }
+///
+///     let mut p: point = Default::default();
+/// ```
+pub struct ZeroedToDefault;
+
+impl Transform for ZeroedToDefault {
+    fn transform(&self, krate: &mut Crate, _st: &CommandState, cx: &RefactorCtxt) {
+        // Structs implementing `Default`, and whether the impl is derived
+        let mut default_impls = HashMap::new();
+        visit_nodes(krate, |i: &Item| {
+            if let ItemKind::Impl(_, _, _, _, Some(ref trait_ref), ref self_ty, _) = i.kind {
+                if trait_ref.path.segments.last()
+                    .map_or(true, |seg| seg.ident.name.as_str() != "Default") {
+                    return;
+                }
+                if let Some(def_id) = cx.try_resolve_ty(self_ty) {
+                    let derived =
+                        attr::contains_name(&i.attrs, Symbol::intern("automatically_derived"));
+                    default_impls.insert(def_id, derived);
+                }
+            }
+        });
+
+        let mut zero_defaults = ZeroDefaults {
+            cx,
+            default_impls,
+            checked: HashMap::new(),
+            needs_derive: HashSet::new(),
+        };
+        let mut rewritten = HashSet::new();
+        MutVisitNodes::visit(krate, |e: &mut P<Expr>| {
+            // `unsafe { mem::zeroed() }`, with the call already rewritten
+            if let ExprKind::Block(ref b, None) = e.kind {
+                if let (BlockCheckMode::Unsafe(UnsafeSource::UserProvided), [stmt]) =
+                    (b.rules, &b.stmts[..]) {
+                    if let StmtKind::Expr(ref inner) = stmt.kind {
+                        if rewritten.contains(&inner.id) {
+                            *e = inner.clone();
+                        }
+                    }
+                }
+                return;
+            }
+
+            if !is_mem_fn_call(cx, e, "zeroed") {
+                return;
+            }
+            let ty = cx.node_type(e.id);
+            if let Err(reason) = zero_defaults.check(ty) {
+                eprintln!("zeroed_to_default: {}: {}", ty, reason);
+                return;
+            }
+
+            // `mem::zeroed::<S>()` names the struct, so keep naming it
+            let func = expect!([e.kind] ExprKind::Call(ref func, _) => func);
+            let ty_arg = match func.kind {
+                ExprKind::Path(None, ref path) => path.segments.last()
+                    .and_then(|seg| seg.args.as_ref())
+                    .and_then(|args| match **args {
+                        GenericArgs::AngleBracketed(ref args) => match args.args[..] {
+                            [GenericArg::Type(ref ty)] => Some(ty),
+                            _ => None,
+                        },
+                        _ => None,
+                    }),
+                _ => None,
+            };
+            let default_path = match ty_arg.map(|ty| &ty.kind) {
+                Some(&syntax::ast::TyKind::Path(None, ref path)) => {
+                    let mut path = path.clone();
+                    path.segments.push(mk().path_segment("default"));
+                    path
+                }
+                _ => mk().path(vec!["Default", "default"]),
+            };
+            rewritten.insert(e.id);
+            *e = mk().id(e.id).span(e.span)
+                .call_expr(mk().path_expr(default_path), Vec::<P<Expr>>::new());
+        });
+
+        let needs_derive = zero_defaults.needs_derive;
+        MutVisitNodes::visit(krate, |i: &mut P<Item>| {
+            if let ItemKind::Struct(..) = i.kind {
+                if needs_derive.contains(&cx.node_def_id(i.id)) {
+                    let derive = mk().call_attr("derive", vec!["Default"]).into_attrs().remove(0);
+                    i.attrs.insert(0, derive);
+                }
+            }
+        });
+    }
+
+    fn min_phase(&self) -> Phase {
+        Phase::Phase3
+    }
+}
+
+/// Which types have a `Default` value that is all zeroes, like the one `mem::zeroed` returns.
+struct ZeroDefaults<'a, 'tcx: 'a> {
+    cx: &'a RefactorCtxt<'a, 'tcx>,
+    /// Structs implementing `Default`, and whether the impl is derived
+    default_impls: HashMap<DefId, bool>,
+    /// Structs already checked, with the result
+    checked: HashMap<DefId, Result<(), String>>,
+    /// Structs that need a `#[derive(Default)]`
+    needs_derive: HashSet<DefId>,
+}
+
+impl<'a, 'tcx> ZeroDefaults<'a, 'tcx> {
+    /// Check that `ty` has a zero `Default` value, or give the reason it doesn't.
+    fn check(&mut self, ty: ty::Ty<'tcx>) -> Result<(), String> {
+        let tcx = self.cx.ty_ctxt();
+        match ty.kind {
+            TyKind::Bool | TyKind::Char | TyKind::Int(_) | TyKind::Uint(_) |
+            TyKind::Float(_) => Ok(()),
+            // `Default` is only implemented for arrays of up to 32 elements
+            TyKind::Array(elem, len) => {
+                if len.eval_usize(tcx, ParamEnv::empty()) > 32 {
+                    return Err(format!("`{}` has no `Default` value", ty));
+                }
+                self.check(elem)
+            }
+            TyKind::Adt(adt, substs) if tcx.def_path_str(adt.did).ends_with("option::Option") => {
+                // `None` is zero for the types with a null niche
+                match substs.type_at(0).kind {
+                    TyKind::Ref(..) | TyKind::FnPtr(_) => Ok(()),
+                    _ => Err(format!("`{}` may not be zero when it is `None`", ty)),
+                }
+            }
+            TyKind::Adt(adt, substs) if adt.is_struct() && adt.did.is_local() &&
+                                        substs.is_empty() => {
+                if let Some(result) = self.checked.get(&adt.did) {
+                    return result.clone();
+                }
+                let result: Result<(), String> = match self.default_impls.get(&adt.did) {
+                    Some(false) => Err(format!("`{}` has a hand-written `Default` impl", ty)),
+                    _ => adt.non_enum_variant().fields.iter()
+                        .map(|f| self.check(f.ty(tcx, substs)).map_err(|reason| {
+                            format!("field `{}`: {}", f.ident, reason)
+                        }))
+                        .collect(),
+                };
+                if result.is_ok() && !self.default_impls.contains_key(&adt.did) {
+                    self.needs_derive.insert(adt.did);
+                }
+                self.checked.insert(adt.did, result.clone());
+                result
+            }
+            _ => Err(format!("`{}` has no zero `Default` value", ty)),
+        }
+    }
+}
+
+
 /// # `remove_redundant_let_types` Command
 ///
 /// Usage: `remove_redundant_let_types`
@@ -554,6 +1028,8 @@ pub fn register_commands(reg: &mut Registry) {
     reg.register("sink_lets", |_args| mk(SinkLets));
     reg.register("fold_let_assign", |_args| mk(FoldLetAssign));
     reg.register("uninit_to_default", |_args| mk(UninitToDefault));
+    reg.register("uninit_to_maybeuninit", |_args| mk(UninitToMaybeUninit));
+    reg.register("zeroed_to_default", |_args| mk(ZeroedToDefault));
     reg.register("remove_redundant_let_types", |_args| mk(RemoveRedundantLetTypes));
     reg.register("expand_local_ptr_tys", |_args| {
         Box::new(DriverCommand::new(Phase::Phase3, move |st, cx| {
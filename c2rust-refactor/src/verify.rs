@@ -0,0 +1,269 @@
+//! Checking that the crate still builds as commands are applied to it.
+//!
+//! With `--checkpoint-cmd CMD`, the refactorer writes the rewritten crate to a copy of the
+//! project after every command and runs `CMD` in the copy, stopping at the first command that
+//! makes `CMD` fail.  With `--bisect`, it then narrows the failure down to the nodes marked for
+//! that command which cause it.
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use syntax::ast::NodeId;
+use syntax::source_map::FileName;
+use syntax::symbol::Symbol;
+
+use crate::command::{FuncCommand, RefactorState, Registry, StateSnapshot};
+use crate::file_io::FileIO;
+use crate::interact::collect_spans;
+use crate::{Checkpoint, Command};
+
+/// A copy of the project directory to run check commands in.
+struct CheckDir {
+    /// The project directory, which source file paths are relative to
+    root: PathBuf,
+    /// The copy
+    dir: PathBuf,
+}
+
+impl CheckDir {
+    /// Copy the current directory to a fresh temporary directory.  Build outputs and hidden
+    /// files, such as `target` and `.git`, are left out.
+    fn new() -> io::Result<CheckDir> {
+        static DIR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let root = env::current_dir()?;
+        let dir = env::temp_dir().join(format!(
+            "c2rust-refactor-verify-{}-{}",
+            process::id(),
+            DIR_COUNT.fetch_add(1, Ordering::SeqCst),
+        ));
+        copy_dir(&root, &dir)?;
+        Ok(CheckDir { root, dir })
+    }
+
+    /// Get the path of the copy of `path`, or `None` if `path` is outside the project.
+    fn mirror_path(&self, path: &Path) -> Option<PathBuf> {
+        let path = self.root.join(path);
+        let rel = path.strip_prefix(&self.root).ok().map(Path::to_path_buf).or_else(|| {
+            let root = self.root.canonicalize().ok()?;
+            let path = path.canonicalize().ok()?;
+            path.strip_prefix(&root).ok().map(Path::to_path_buf)
+        })?;
+        Some(self.dir.join(rel))
+    }
+
+    /// Write `s` to the copy of `path`, if `path` is in the project.
+    fn write_copy(&self, path: &Path, s: &str) -> io::Result<bool> {
+        let dest = match self.mirror_path(path) {
+            Some(dest) => dest,
+            None => return Ok(false),
+        };
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(dest, s)?;
+        Ok(true)
+    }
+
+    /// Write the crate as rewritten so far to the copy, and run `cmd` in it.  On failure,
+    /// returns the output of `cmd`.
+    fn check(&self, state: &RefactorState, cmd: &str) -> Result<(), String> {
+        // Start from the original sources, so that files the commands have stopped changing
+        // since the previous check are reverted
+        for sf in state.source_map().files().iter() {
+            if let (FileName::Real(path), Some(src)) = (&sf.name, &sf.src) {
+                self.write_copy(path, src)
+                    .unwrap_or_else(|e| panic!("Could not write {:?}: {}", path, e));
+            }
+        }
+        state.save_crate_to(&CheckDirIO(self));
+
+        let output = process::Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .current_dir(&self.dir)
+            .output()
+            .unwrap_or_else(|e| panic!("Could not run `{}`: {}", cmd, e));
+        if output.status.success() {
+            Ok(())
+        } else {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            Err(text)
+        }
+    }
+}
+
+impl Drop for CheckDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == "target" || name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &dest.join(&name))?;
+        } else {
+            fs::copy(entry.path(), dest.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes rewritten files into a `CheckDir` instead of the project.
+struct CheckDirIO<'a>(&'a CheckDir);
+
+impl<'a> FileIO for CheckDirIO<'a> {
+    fn read_file(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write_file(&self, path: &Path, s: &str) -> io::Result<()> {
+        if !self.0.write_copy(path, s)? {
+            warn!("not checking {:?}, which is outside of the project", path);
+        }
+        Ok(())
+    }
+}
+
+fn describe(cmd: &Command) -> String {
+    let mut s = cmd.name.clone();
+    for arg in &cmd.args {
+        s.push(' ');
+        s.push_str(arg);
+    }
+    s
+}
+
+/// Run `commands`, checking the crate with `checkpoint.cmd` after each one.  If a command makes
+/// the check fail, report it, save the crate as it was before that command, and exit.
+pub fn run_checkpointed(
+    state: &mut RefactorState,
+    commands: &[Command],
+    checkpoint: &Checkpoint,
+) {
+    let check_dir = CheckDir::new()
+        .unwrap_or_else(|e| panic!("Could not copy the project for checking: {}", e));
+    if let Err(output) = check_dir.check(state, &checkpoint.cmd) {
+        eprintln!("`{}` fails before refactoring:\n{}", checkpoint.cmd, output);
+        process::exit(1);
+    }
+
+    for (i, cmd) in commands.iter().enumerate() {
+        if &cmd.name == "interact" {
+            panic!("`interact` must be the only command");
+        }
+
+        let before = state.snapshot();
+        if let Err(e) = state.run(&cmd.name, &cmd.args) {
+            eprintln!("{:?}", e);
+            process::exit(1);
+        }
+
+        let output = match check_dir.check(state, &checkpoint.cmd) {
+            Ok(()) => continue,
+            Err(output) => output,
+        };
+        eprintln!(
+            "`{}` fails after command {} of {}: {}\n{}",
+            checkpoint.cmd,
+            i + 1,
+            commands.len(),
+            describe(cmd),
+            output,
+        );
+
+        if checkpoint.bisect {
+            bisect(state, &check_dir, &checkpoint.cmd, cmd, &before);
+        }
+
+        eprintln!("Saving the crate as it was before `{}`", describe(cmd));
+        state.restore(before);
+        state.save_crate();
+        process::exit(1);
+    }
+
+    state.save_crate();
+}
+
+/// Find a minimal set of the nodes marked for `cmd` that make the check fail, by rerunning `cmd`
+/// from the state `before` with halves of its marks, and report it.
+fn bisect(
+    state: &mut RefactorState,
+    check_dir: &CheckDir,
+    check_cmd: &str,
+    cmd: &Command,
+    before: &StateSnapshot,
+) {
+    let mut marks = before.marks().iter().copied().collect::<Vec<_>>();
+    marks.sort();
+    if marks.is_empty() {
+        eprintln!("Nothing to bisect: no nodes were marked for `{}`", describe(cmd));
+        return;
+    }
+
+    let mut fails_with = |marks: &[(NodeId, Symbol)]| {
+        state.restore(before.clone());
+        *state.marks_mut() = marks.iter().copied().collect();
+        state.run(&cmd.name, &cmd.args).is_err() || check_dir.check(state, check_cmd).is_err()
+    };
+    while marks.len() > 1 {
+        let (first, second) = marks.split_at(marks.len() / 2);
+        if fails_with(first) {
+            marks = first.to_vec();
+        } else if fails_with(second) {
+            marks = second.to_vec();
+        } else {
+            // The failure needs marks from both halves
+            break;
+        }
+    }
+
+    let spans = collect_spans(before.krate(), marks.iter().map(|&(id, _)| id).collect());
+    eprintln!("`{}` fails with these {} marked nodes:", describe(cmd), marks.len());
+    for (id, label) in marks {
+        let loc = spans
+            .get(&id)
+            .map_or_else(|| "unknown location".to_owned(), |&span| {
+                state.source_map().span_to_string(span)
+            });
+        eprintln!("  {}:{} at {}", id.as_usize(), label.as_str(), loc);
+    }
+}
+
+/// # `verify_equivalence` Command
+///
+/// Usage: `verify_equivalence CMD`
+///
+/// Write the crate as rewritten so far to a copy of the current directory, and run the shell
+/// command `CMD` there, e.g., `cargo check` or `cargo test`.  If `CMD` fails, print its
+/// output and exit.
+///
+/// To check after every command, pass `--checkpoint-cmd CMD` instead, which also reports which
+/// command caused the failure.
+fn register_verify_equivalence(reg: &mut Registry) {
+    reg.register("verify_equivalence", |args| {
+        let check_cmd = args.join(" ");
+        Box::new(FuncCommand(move |rs: &mut RefactorState| {
+            let check_dir = CheckDir::new()
+                .unwrap_or_else(|e| panic!("Could not copy the project for checking: {}", e));
+            if let Err(output) = check_dir.check(rs, &check_cmd) {
+                eprintln!("`{}` fails:\n{}", check_cmd, output);
+                process::exit(1);
+            }
+        }))
+    });
+}
+
+pub fn register_commands(reg: &mut Registry) {
+    register_verify_equivalence(reg);
+}
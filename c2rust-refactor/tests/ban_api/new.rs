@@ -0,0 +1,57 @@
+#![feature(rustc_private)]
+extern crate libc;
+
+pub mod mem {
+    extern "C" {
+        pub fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+        pub fn free(_: *mut libc::c_void);
+        pub fn memcpy(
+            _: *mut libc::c_void,
+            _: *const libc::c_void,
+            _: libc::c_ulong,
+        ) -> *mut libc::c_void;
+    }
+}
+
+pub static mut COPIES: libc::c_int = 0;
+
+pub mod buf {
+    use crate::mem::malloc as alloc;
+    use crate::mem::{free as release, memcpy};
+    use std::mem::transmute as reinterpret;
+
+    pub unsafe fn dup(p: *const u8, n: usize) -> *mut u8 {
+        let q = alloc(n as libc::c_ulong) as *mut u8;
+        memcpy(
+            q as *mut libc::c_void,
+            p as *const libc::c_void,
+            n as libc::c_ulong,
+        );
+        crate::COPIES += 1;
+        q
+    }
+
+    pub unsafe fn second(p: *const u8) -> u8 {
+        *p.offset(1)
+    }
+
+    pub unsafe fn release_buf(p: *mut u8) {
+        release(p as *mut libc::c_void);
+    }
+
+    pub fn bits(f: f32) -> u32 {
+        unsafe { reinterpret(f) }
+    }
+}
+
+fn main() {
+    unsafe {
+        let p = buf::dup(b"ab".as_ptr(), 2);
+        let q = mem::malloc(1) as *mut u8;
+        *q = buf::second(p);
+        mem::free(q as *mut libc::c_void);
+        buf::release_buf(p);
+        COPIES -= 1;
+    }
+    buf::bits(1.0);
+}
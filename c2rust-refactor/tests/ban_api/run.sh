@@ -0,0 +1,33 @@
+#!/bin/sh
+set -e
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+tmp=`mktemp -d`
+$refactor ban_api config.toml --json $tmp/bans.json -- old.rs $rustflags 2>$tmp/report
+
+# The uses through `use` renames and method calls are counted with the direct ones
+for expected in \
+    'ban_api: alloc: total 4 (budget 3, exceeded)' \
+    'ban_api: memcpy: total 1' \
+    'ban_api: transmute: total 1' \
+    'ban_api: offset: total 1' \
+    'ban_api: static_mut: total 2' \
+    'ban_api: raw_deref: total 2' \
+    '    crate::buf: 2' \
+    '    crate: 2'
+do
+    grep -qxF "$expected" $tmp/report
+done
+grep -qF '"crate::buf": 2' $tmp/bans.json
+
+# Only `alloc` has more uses than its budget
+$refactor ban_api config.toml --fail-over 2 -- old.rs $rustflags 2>$tmp/report || status=$?
+[ "$status" = 1 ]
+grep -qxF 'ban_api: 1 of 6 rules over budget' $tmp/report
+
+rm -r $tmp
+cp old.rs old.new
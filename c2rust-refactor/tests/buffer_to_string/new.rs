@@ -0,0 +1,40 @@
+extern "C" {
+    fn puts(s: *const i8) -> i32;
+    fn strlen(s: *const i8) -> u64;
+}
+
+pub unsafe extern "C" fn print_int(mut n: i32) -> i32 {
+    let mut buf: Vec<i8> = Vec::with_capacity(16);
+    let mut div: i32 = 1 as i32;
+    if n < 0 as i32 {
+        buf.push('-' as i32 as i8);
+        n = -n
+    }
+    while n / div >= 10 as i32 {
+        div *= 10 as i32
+    }
+    while div > 0 as i32 && (buf.len() as i32) < 15 as i32 {
+        buf.push(('0' as i32 + n / div % 10 as i32) as i8);
+        div /= 10 as i32
+    }
+    buf.push('\u{0}' as i32 as i8);
+    puts(buf.as_ptr());
+    return ((buf.len() - 1) as u64) as i32;
+}
+
+pub unsafe extern "C" fn print_digit(mut n: i32) {
+    let mut buf: [i8; 4] = [0; 4];
+    let mut len: i32 = 0 as i32;
+    let fresh1 = len;
+    len = len + 1;
+    buf[fresh1 as usize] = ('0' as i32 + n) as i8;
+    puts(buf.as_ptr());
+    buf[len as usize] = 0 as i8;
+}
+
+fn main() {
+    unsafe {
+        print_int(-1234);
+        print_digit(7);
+    }
+}
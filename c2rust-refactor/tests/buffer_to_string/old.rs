@@ -0,0 +1,44 @@
+extern "C" {
+    fn puts(s: *const i8) -> i32;
+    fn strlen(s: *const i8) -> u64;
+}
+
+pub unsafe extern "C" fn print_int(mut n: i32) -> i32 {
+    let mut buf: [i8; 16] = [0; 16];
+    let mut len: i32 = 0 as i32;
+    let mut div: i32 = 1 as i32;
+    if n < 0 as i32 {
+        buf[len as usize] = '-' as i32 as i8;
+        len += 1;
+        n = -n
+    }
+    while n / div >= 10 as i32 {
+        div *= 10 as i32
+    }
+    while div > 0 as i32 && len < 15 as i32 {
+        let fresh0 = len;
+        len = len + 1;
+        buf[fresh0 as usize] = ('0' as i32 + n / div % 10 as i32) as i8;
+        div /= 10 as i32
+    }
+    buf[len as usize] = '\u{0}' as i32 as i8;
+    puts(buf.as_ptr());
+    return strlen(buf.as_ptr()) as i32;
+}
+
+pub unsafe extern "C" fn print_digit(mut n: i32) {
+    let mut buf: [i8; 4] = [0; 4];
+    let mut len: i32 = 0 as i32;
+    let fresh1 = len;
+    len = len + 1;
+    buf[fresh1 as usize] = ('0' as i32 + n) as i8;
+    puts(buf.as_ptr());
+    buf[len as usize] = 0 as i8;
+}
+
+fn main() {
+    unsafe {
+        print_int(-1234);
+        print_digit(7);
+    }
+}
@@ -0,0 +1,58 @@
+#![feature(rustc_private)]
+#![allow(dead_code, non_camel_case_types, unused_mut)]
+extern crate libc;
+
+pub type event_cb = Option<unsafe extern "C" fn(_: *mut libc::c_void, _: libc::c_int) -> ()>;
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct emitter {
+    pub id: libc::c_int,
+    pub on_event: event_cb,
+    pub user_data: *mut libc::c_void,
+}
+#[no_mangle]
+pub unsafe extern "C" fn emitter_init(mut e: *mut emitter, mut id: libc::c_int) {
+    (*e).id = id;
+    (*e).on_event = None;
+    (*e).user_data = 0 as *mut libc::c_void;
+}
+#[no_mangle]
+pub unsafe extern "C" fn emitter_on(
+    mut e: *mut emitter,
+    mut cb: event_cb,
+    mut data: *mut libc::c_void,
+) {
+    (*e).on_event = cb;
+    (*e).user_data = data;
+}
+#[no_mangle]
+pub unsafe extern "C" fn emitter_emit(mut e: *mut emitter, mut event: libc::c_int) {
+    if (*e).on_event.is_some() {
+        (*e).on_event.expect("non-null function pointer")((*e).user_data, (*e).id + event);
+    };
+}
+unsafe extern "C" fn count_events(mut data: *mut libc::c_void, mut event: libc::c_int) {
+    let mut total: *mut libc::c_int = data as *mut libc::c_int;
+    *total += event;
+}
+unsafe fn main_0() -> libc::c_int {
+    let mut total: libc::c_int = 0 as libc::c_int;
+    let mut e: emitter = emitter {
+        id: 0,
+        on_event: None,
+        user_data: 0 as *mut libc::c_void,
+    };
+    emitter_init(&mut e, 100 as libc::c_int);
+    emitter_emit(&mut e, 1 as libc::c_int);
+    emitter_on(
+        &mut e,
+        Some(count_events as unsafe extern "C" fn(_: *mut libc::c_void, _: libc::c_int) -> ()),
+        &mut total as *mut libc::c_int as *mut libc::c_void,
+    );
+    emitter_emit(&mut e, 2 as libc::c_int);
+    emitter_emit(&mut e, 3 as libc::c_int);
+    return total;
+}
+pub fn main() {
+    unsafe { println!("{}", main_0()) }
+}
@@ -0,0 +1,43 @@
+const N: usize = 4;
+
+pub fn scale(m: &mut [f32], rows: usize, s: f32) {
+    for m_row in m[..rows * N].chunks_exact_mut(N) {
+        for j in 0..N {
+            m_row[j] *= s;
+        }
+    }
+}
+
+pub fn add_rows(dst: &mut [f32], src: &[f32], rows: usize) {
+    for (i, (dst_row, src_row)) in dst[..rows * 3]
+        .chunks_exact_mut(3)
+        .zip(src[..rows * 3].chunks_exact(3))
+        .enumerate()
+    {
+        for j in 0..3 {
+            dst_row[j] = src_row[j] + i as f32;
+        }
+    }
+}
+
+pub fn column_sums(m: &[f32], out: &mut [f32], rows: usize) {
+    for i in 0..rows {
+        for j in 0..N {
+            out[j] += m[j * N + i];
+        }
+    }
+}
+
+fn main() {
+    let mut m = [1.0; 8];
+    scale(&mut m, 2, 2.0);
+    assert_eq!(m, [2.0; 8]);
+
+    let mut dst = [0.0; 6];
+    add_rows(&mut dst, &[0.5; 6], 2);
+    assert_eq!(dst, [0.5, 0.5, 0.5, 1.5, 1.5, 1.5]);
+
+    let mut out = [0.0; 4];
+    column_sums(&[1.0; 16], &mut out, 4);
+    assert_eq!(out, [4.0; 4]);
+}
@@ -0,0 +1,94 @@
+#![allow(unused_parens, non_camel_case_types)]
+
+//! Rotate idioms as the translator emits them.  `run.sh` runs the original with overflow
+//! checks off, since its shifts overflow for a rotate by `0` or the full width, and the
+//! rewritten program with them on, and checks that they print the same results.
+
+pub mod libc {
+    pub type c_int = i32;
+}
+pub type uint8_t = u8;
+pub type uint32_t = u32;
+pub type uint64_t = u64;
+
+pub fn rotl32(x: uint32_t, n: libc::c_int) -> uint32_t {
+    x << n | x >> 32 as libc::c_int - n
+}
+
+pub fn rotr32(x: uint32_t, n: u32) -> uint32_t {
+    (x >> n) | (x << (32 - n))
+}
+
+pub fn rotl64(x: uint64_t, n: libc::c_int) -> uint64_t {
+    (x >> 64i32 - n) ^ (x << n)
+}
+
+pub fn rotl8(x: uint8_t, n: u32) -> uint8_t {
+    (x << n) + (x >> (8 - n))
+}
+
+// SHA-256 `Sigma0` and `sigma1`, with `ROTR` expanded
+pub fn big_sigma0(x: uint32_t) -> uint32_t {
+    (x >> 2i32 | x << 32i32 - 2i32)
+        ^ (x >> 13i32 | x << 32i32 - 13i32)
+        ^ (x >> 22i32 | x << 32i32 - 22i32)
+}
+
+pub fn small_sigma1(x: uint32_t) -> uint32_t {
+    (x >> 17 | x << 15) ^ (x >> 19 | x << 13) ^ x >> 10
+}
+
+pub struct State {
+    pub h: [uint32_t; 2],
+}
+
+pub fn rotl_field(s: &State, i: usize) -> uint32_t {
+    s.h[i] << 5 | s.h[i] >> 27
+}
+
+// Arithmetic shifts don't rotate
+pub fn signed(x: i32, n: libc::c_int) -> i32 {
+    x << n | x >> 32 - n
+}
+
+// The amounts don't add up to the width
+pub fn wrong_width(x: uint32_t, n: libc::c_int) -> uint32_t {
+    x << n | x >> 31 - n
+}
+
+// `x` would be evaluated once instead of twice
+pub fn side_effects(p: &mut uint32_t) -> uint32_t {
+    next(p) << 3 | next(p) >> 29
+}
+
+fn next(p: &mut uint32_t) -> uint32_t {
+    *p = p.wrapping_mul(1103515245).wrapping_add(12345);
+    *p
+}
+
+fn main() {
+    let x32 = 0x8123_4567u32;
+    let x64 = 0x8123_4567_89ab_cdefu64;
+    for n in 0..=32 {
+        println!("rotl32 {} {:x}", n, rotl32(x32, n));
+        println!("rotr32 {} {:x}", n, rotr32(x32, n as u32));
+    }
+    // With `^` and `+`, the wrapped shifts of the original don't match a rotate by 0 or the full
+    // width, which C leaves undefined anyway
+    for n in 1..64 {
+        println!("rotl64 {} {:x}", n, rotl64(x64, n));
+    }
+    for n in 1..8 {
+        println!("rotl8 {} {:x}", n, rotl8(0x96, n));
+    }
+    println!("big_sigma0 {:x}", big_sigma0(x32));
+    println!("small_sigma1 {:x}", small_sigma1(x32));
+    let s = State { h: [x32, !x32] };
+    println!("rotl_field {:x} {:x}", rotl_field(&s, 0), rotl_field(&s, 1));
+    for n in 1..31 {
+        println!("signed {} {:x}", n, signed(-0x1234_5678, n));
+        println!("wrong_width {} {:x}", n, wrong_width(x32, n));
+    }
+    let mut seed = 1;
+    println!("side_effects {:x}", side_effects(&mut seed));
+}
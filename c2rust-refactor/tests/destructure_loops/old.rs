@@ -0,0 +1,57 @@
+pub unsafe extern "C" fn clamp(mut x: i32, mut lo: i32, mut hi: i32) -> i32 {
+    let mut result: i32 = 0;
+    's_10: loop {
+        if x < lo {
+            result = lo;
+            break 's_10;
+        }
+        if x > hi {
+            result = hi;
+            break;
+        } else {
+            result = x;
+        }
+        println!("in range");
+        break;
+    }
+    return result;
+}
+
+pub unsafe extern "C" fn sum(mut n: i32) -> i32 {
+    let mut total: i32 = 0;
+    let mut i: i32 = 0;
+    // Runs more than once, so it stays a loop
+    's_20: loop {
+        if i >= n {
+            break;
+        }
+        total += i;
+        i += 1;
+    }
+    // Continues, so it stays a loop
+    loop {
+        if total > 100 {
+            total -= 100;
+            continue;
+        }
+        break;
+    }
+    // Breaks out of the inner loop, but not the outer one
+    loop {
+        while i > 0 {
+            i -= 1;
+            if i == 5 {
+                break;
+            }
+        }
+        break;
+    }
+    return total;
+}
+
+fn main() {
+    unsafe {
+        println!("{}", clamp(5, 0, 10));
+        println!("{}", sum(20));
+    }
+}
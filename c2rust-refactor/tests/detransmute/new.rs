@@ -0,0 +1,28 @@
+use std::mem;
+
+fn float_bits(x: f32) -> u32 {
+    x.to_bits()
+}
+
+fn bits_float(x: i64) -> f64 {
+    f64::from_bits(x as u64)
+}
+
+fn flip_sign(x: i32) -> u32 {
+    x as u32
+}
+
+fn int_bytes(x: u32) -> [u8; 4] {
+    x.to_ne_bytes()
+}
+
+fn bytes_float(x: [u8; 8]) -> f64 {
+    let y = f64::from_bits(u64::from_ne_bytes(x));
+    y
+}
+
+fn int_ptr(x: usize) -> *const u8 {
+    unsafe { mem::transmute(x) }
+}
+
+fn main() {}
@@ -0,0 +1,28 @@
+use std::mem;
+
+fn float_bits(x: f32) -> u32 {
+    unsafe { mem::transmute::<f32, u32>(x) }
+}
+
+fn bits_float(x: i64) -> f64 {
+    unsafe { mem::transmute::<i64, f64>(x) }
+}
+
+fn flip_sign(x: i32) -> u32 {
+    unsafe { ::std::mem::transmute(x) }
+}
+
+fn int_bytes(x: u32) -> [u8; 4] {
+    unsafe { mem::transmute(x) }
+}
+
+fn bytes_float(x: [u8; 8]) -> f64 {
+    let y = unsafe { mem::transmute::<[u8; 8], f64>(x) };
+    y
+}
+
+fn int_ptr(x: usize) -> *const u8 {
+    unsafe { mem::transmute(x) }
+}
+
+fn main() {}
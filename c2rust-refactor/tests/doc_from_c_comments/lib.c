@@ -0,0 +1,35 @@
+#include <stddef.h>
+
+/**
+ * Add two integers.
+ *
+ * Overflow wraps around, as it does for unsigned integers.
+ *
+ * @param a the first operand
+ * @param b the second operand,
+ *          which may be negative
+ * @return the sum of both operands
+ */
+int add(int a, int b) {
+    return a + b;
+}
+
+/// Clamp a value to a range.
+/// @param[in] x the value
+/// @param[in] lo the lower bound
+/// @param[in] hi the upper bound
+int clamp(int x, int lo, int hi) {
+    if (x < lo) return lo;
+    if (x > hi) return hi;
+    return x;
+}
+
+/** Scale a value. */
+int scale(int x) {
+    return x * 2;
+}
+
+/* Not a doc comment */
+int negate(int x) {
+    return -x;
+}
@@ -0,0 +1,49 @@
+#![feature(custom_attribute)]
+#![allow(dead_code)]
+
+// lib.c:13
+/// Add two integers.
+///
+/// Overflow wraps around, as it does for unsigned integers.
+///
+/// * `a`: the first operand
+/// * `b`: the second operand, which may be negative
+/// * Returns: the sum of both operands
+pub unsafe extern "C" fn add(a: i32, b: i32) -> i32 {
+    a.wrapping_add(b)
+}
+
+#[header_src = "lib.c:1"]
+pub mod lib_c {
+    /// Clamp a value to a range.
+    ///
+    /// * `x`: the value
+    /// * `lo`: the lower bound
+    /// * `hi`: the upper bound
+    #[src_loc = "21:0"]
+    pub fn clamp(x: i32, lo: i32, hi: i32) -> i32 {
+        if x < lo {
+            return lo;
+        }
+        if x > hi {
+            return hi;
+        }
+        x
+    }
+
+    #[src_loc = "33:0"]
+    pub fn negate(x: i32) -> i32 {
+        -x
+    }
+
+    #[src_loc = "99:0"]
+    pub fn missing() {}
+}
+
+// lib.c:28
+/// Already documented.
+pub fn scale(x: i32) -> i32 {
+    x * 2
+}
+
+fn main() {}
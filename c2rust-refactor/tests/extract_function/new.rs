@@ -0,0 +1,41 @@
+unsafe fn sum_squares(p: *const i32, n: usize) -> i32 {
+    let mut total = 0;
+    let mut i = 0;
+    while i < n {
+        add_square(p, &mut i, &mut total);
+    }
+    total
+}
+
+unsafe fn add_square(p: *const i32, i: &mut usize, total: &mut i32) {
+    let x = *p.add(*i);
+    *total += x * x;
+    *i += 1;
+}
+
+fn spread(values: &[i32]) -> i32 {
+    let first = values[0];
+    let (lo, hi) = bounds(first, values);
+    hi - lo
+}
+
+fn bounds(first: i32, values: &[i32]) -> (i32, i32) {
+    let mut lo = first;
+    let mut hi = first;
+    for &v in values {
+        if v < lo {
+            lo = v;
+        }
+        if v > hi {
+            hi = v;
+        }
+    }
+    (lo, hi)
+}
+
+fn main() {
+    let values = [3, 1, 4, 1, 5];
+    let squares = unsafe { sum_squares(values.as_ptr(), values.len()) };
+    assert_eq!(squares, 52);
+    assert_eq!(spread(&values), 4);
+}
@@ -0,0 +1,32 @@
+unsafe fn sum_squares(p: *const i32, n: usize) -> i32 {
+    let mut total = 0;
+    let mut i = 0;
+    while i < n {
+        let x = *p.add(i);
+        total += x * x;
+        i += 1;
+    }
+    total
+}
+
+fn spread(values: &[i32]) -> i32 {
+    let first = values[0];
+    let mut lo = first;
+    let mut hi = first;
+    for &v in values {
+        if v < lo {
+            lo = v;
+        }
+        if v > hi {
+            hi = v;
+        }
+    }
+    hi - lo
+}
+
+fn main() {
+    let values = [3, 1, 4, 1, 5];
+    let squares = unsafe { sum_squares(values.as_ptr(), values.len()) };
+    assert_eq!(squares, 52);
+    assert_eq!(spread(&values), 4);
+}
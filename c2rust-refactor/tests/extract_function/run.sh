@@ -0,0 +1,17 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor \
+    select target 'crate; desc(match_stmt(let x = *p.add(i);) ||
+        match_stmt(total += x * x;) || match_stmt(i += 1;));' \; \
+    extract_function add_square \; \
+    clear_marks \; \
+    select target 'crate; desc(match_stmt(let mut lo = first;) ||
+        match_stmt(let mut hi = first;) ||
+        match_stmt(for &v in values { if v < lo { lo = v; } if v > hi { hi = v; } }));' \; \
+    extract_function bounds \
+    -- old.rs $rustflags
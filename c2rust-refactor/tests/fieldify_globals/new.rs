@@ -0,0 +1,85 @@
+#![allow(non_upper_case_globals)]
+
+pub struct Tokenizer {
+    cur_pos: usize,
+    cur_line: i32,
+    buf: [u8; 16],
+}
+
+impl Tokenizer {
+    pub fn new() -> Tokenizer {
+        Tokenizer {
+            cur_pos: 0,
+            cur_line: 1,
+            buf: [0; 16],
+        }
+    }
+}
+static mut TOKENIZER_INSTANCE: Option<Tokenizer> = None;
+
+unsafe fn next_char(ctx: &mut Tokenizer) -> u8 {
+    let c = ctx.buf[ctx.cur_pos];
+    if c != 0 {
+        ctx.cur_pos += 1;
+    }
+    if c == b'\n' {
+        ctx.cur_line += 1;
+    }
+    c
+}
+
+unsafe fn peek_char(ctx: &mut Tokenizer) -> u8 {
+    ctx.buf[ctx.cur_pos]
+}
+
+unsafe fn skip_to(ctx: &mut Tokenizer, pos: usize) {
+    while ctx.cur_pos < pos && next_char(ctx) != 0 {}
+}
+
+fn is_space(c: u8) -> bool {
+    c == b' ' || c == b'\n'
+}
+
+unsafe fn next_token(ctx: &mut Tokenizer) -> usize {
+    while is_space(peek_char(ctx)) {
+        {
+            let arg0 = ctx.cur_pos + 1;
+            skip_to(ctx, arg0)
+        };
+    }
+    let start = ctx.cur_pos;
+    while peek_char(ctx) != 0 && !is_space(peek_char(ctx)) {
+        next_char(ctx);
+    }
+    ctx.cur_pos - start
+}
+
+pub unsafe fn current_line(ctx: &mut Tokenizer) -> i32 {
+    ctx.cur_line
+}
+#[export_name = "current_line"]
+pub unsafe extern "C" fn current_line_wrapper() -> i32 {
+    current_line(TOKENIZER_INSTANCE.get_or_insert_with(Tokenizer::new))
+}
+
+pub unsafe fn count_tokens(input: &[u8]) -> i32 {
+    let mut ctx = Tokenizer::new();
+    let mut i = 0;
+    while i < input.len() && i < 15 {
+        ctx.buf[i] = input[i];
+        i += 1;
+    }
+    let mut count = 0;
+    while next_token(&mut ctx) > 0 {
+        count += 1;
+    }
+    count * 100 + ctx.cur_line
+}
+
+fn main() {
+    let mut ctx = Tokenizer::new();
+    unsafe {
+        assert_eq!(count_tokens(b"ab cd\n ef"), 302);
+        assert!(current_line(&mut ctx) >= 1);
+    }
+}
@@ -0,0 +1,64 @@
+#![allow(non_upper_case_globals)]
+
+static mut cur_pos: usize = 0;
+static mut cur_line: i32 = 1;
+static mut buf: [u8; 16] = [0; 16];
+
+unsafe fn next_char() -> u8 {
+    let c = buf[cur_pos];
+    if c != 0 {
+        cur_pos += 1;
+    }
+    if c == b'\n' {
+        cur_line += 1;
+    }
+    c
+}
+
+unsafe fn peek_char() -> u8 {
+    buf[cur_pos]
+}
+
+unsafe fn skip_to(pos: usize) {
+    while cur_pos < pos && next_char() != 0 {}
+}
+
+fn is_space(c: u8) -> bool {
+    c == b' ' || c == b'\n'
+}
+
+unsafe fn next_token() -> usize {
+    while is_space(peek_char()) {
+        skip_to(cur_pos + 1);
+    }
+    let start = cur_pos;
+    while peek_char() != 0 && !is_space(peek_char()) {
+        next_char();
+    }
+    cur_pos - start
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn current_line() -> i32 {
+    cur_line
+}
+
+pub unsafe fn count_tokens(input: &[u8]) -> i32 {
+    let mut i = 0;
+    while i < input.len() && i < 15 {
+        buf[i] = input[i];
+        i += 1;
+    }
+    let mut count = 0;
+    while next_token() > 0 {
+        count += 1;
+    }
+    count * 100 + cur_line
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(count_tokens(b"ab cd\n ef"), 302);
+        assert!(current_line() >= 1);
+    }
+}
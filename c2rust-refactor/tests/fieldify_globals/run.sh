@@ -0,0 +1,11 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor \
+    select target 'crate; child(static);' \; \
+    select root 'crate; child(fn && (name("count_tokens") || name("main")));' \; \
+    fieldify_globals Tokenizer -- old.rs $rustflags
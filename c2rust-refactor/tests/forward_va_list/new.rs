@@ -0,0 +1,42 @@
+#![feature(c_variadic)]
+
+extern "C" {
+    fn vprintf(_: *const i8, _: ::std::ffi::VaList) -> i32;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn log_printf(mut fmt: *const i8, mut args: ...) -> i32 {
+    let mut n: i32 = vprintf(fmt, args.as_va_list());
+    return n;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sum(mut count: i32, mut args: ...) -> i32 {
+    vsum(count, args.as_va_list())
+}
+pub unsafe extern "C" fn vsum(mut count: i32, mut ap: ::std::ffi::VaList) -> i32 {
+    let mut total: i32 = 0;
+    let mut i: i32 = 0;
+    while i < count {
+        total += ap.arg::<i32>();
+        i += 1
+    }
+    return total;
+}
+
+// Starts two `va_list`s, so it is left alone
+#[no_mangle]
+pub unsafe extern "C" fn add_first(mut count: i32, mut args: ...) -> i32 {
+    let mut ap: ::std::ffi::VaListImpl;
+    let mut aq: ::std::ffi::VaListImpl;
+    ap = args.clone();
+    aq = args.clone();
+    return count + ap.arg::<i32>() + aq.arg::<i32>();
+}
+
+fn main() {
+    unsafe {
+        log_printf(b"%d\n\x00" as *const u8 as *const i8, sum(2, 1, 2));
+        log_printf(b"%d\n\x00" as *const u8 as *const i8, add_first(1, 2));
+    }
+}
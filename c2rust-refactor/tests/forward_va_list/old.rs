@@ -0,0 +1,43 @@
+#![feature(c_variadic)]
+
+extern "C" {
+    fn vprintf(_: *const i8, _: ::std::ffi::VaList) -> i32;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn log_printf(mut fmt: *const i8, mut args: ...) -> i32 {
+    let mut ap: ::std::ffi::VaListImpl;
+    ap = args.clone();
+    let mut n: i32 = vprintf(fmt, ap.as_va_list());
+    return n;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sum(mut count: i32, mut args: ...) -> i32 {
+    let mut ap: ::std::ffi::VaListImpl;
+    let mut total: i32 = 0;
+    let mut i: i32 = 0;
+    ap = args.clone();
+    while i < count {
+        total += ap.arg::<i32>();
+        i += 1
+    }
+    return total;
+}
+
+// Starts two `va_list`s, so it is left alone
+#[no_mangle]
+pub unsafe extern "C" fn add_first(mut count: i32, mut args: ...) -> i32 {
+    let mut ap: ::std::ffi::VaListImpl;
+    let mut aq: ::std::ffi::VaListImpl;
+    ap = args.clone();
+    aq = args.clone();
+    return count + ap.arg::<i32>() + aq.arg::<i32>();
+}
+
+fn main() {
+    unsafe {
+        log_printf(b"%d\n\x00" as *const u8 as *const i8, sum(2, 1, 2));
+        log_printf(b"%d\n\x00" as *const u8 as *const i8, add_first(1, 2));
+    }
+}
@@ -0,0 +1,13 @@
+fn clamp<T: Copy + PartialOrd>(x: T, lo: T, hi: T) -> T {
+    if x < lo {
+        lo
+    } else if x > hi {
+        hi
+    } else {
+        x
+    }
+}
+
+fn main() {
+    let c = clamp(1.5, 0.0, 1.0);
+}
@@ -0,0 +1,13 @@
+fn clamp(x: f64, lo: f64, hi: f64) -> f64 {
+    if x < lo {
+        lo
+    } else if x > hi {
+        hi
+    } else {
+        x
+    }
+}
+
+fn main() {
+    let c = clamp(1.5, 0.0, 1.0);
+}
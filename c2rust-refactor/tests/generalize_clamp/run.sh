@@ -0,0 +1,12 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor \
+    select target 'crate; desc(match_ty(f64));' \; \
+    select target 'crate; desc(fn && name("clamp"));' \; \
+    generalize_items \
+    -- old.rs $rustflags
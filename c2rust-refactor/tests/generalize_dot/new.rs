@@ -0,0 +1,14 @@
+fn dot<T: Copy + From<u8> + std::ops::AddAssign + std::ops::Mul<Output = T>>(
+    a: &[T],
+    b: &[T],
+) -> T {
+    let mut sum = T::from(0u8);
+    for i in 0..a.len() {
+        sum += a[i] * b[i];
+    }
+    sum
+}
+
+fn main() {
+    let d = dot(&[1.0, 2.0], &[3.0, 4.0]);
+}
@@ -0,0 +1,11 @@
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    let mut sum = 0.0;
+    for i in 0..a.len() {
+        sum += a[i] * b[i];
+    }
+    sum
+}
+
+fn main() {
+    let d = dot(&[1.0, 2.0], &[3.0, 4.0]);
+}
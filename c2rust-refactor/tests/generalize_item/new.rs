@@ -6,7 +6,7 @@ struct S<T> {
 fn f<T>(x: T, y: i32) {}
 
 fn g(s: S<i16>) {
-    f::<i16>(s.x, s.y);
+    f(s.x, s.y);
 }
 
 fn main() {}
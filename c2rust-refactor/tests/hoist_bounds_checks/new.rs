@@ -0,0 +1,60 @@
+pub unsafe fn copy(dst: &mut [u8], src: &[u8], n: i32) {
+    let mut i: i32 = 0;
+    assert!(n <= 0 || n as usize <= dst.len());
+    assert!(n <= 0 || n as usize <= src.len());
+    while i < n {
+        *dst.get_unchecked_mut(i as usize) = *src.get_unchecked(i as usize);
+        i += 1
+    }
+}
+
+pub fn sum(xs: &[u32], n: usize) -> u32 {
+    let mut total: u32 = 0;
+    let mut i: usize;
+    i = 0;
+    assert!(n <= xs.len());
+    while i < n {
+        total = total.wrapping_add(*unsafe { xs.get_unchecked(i) });
+        i = i.wrapping_add(1);
+    }
+    total
+}
+
+pub fn mark(flags: &[bool], out: &mut [u8], n: u32) {
+    let mut i: u32 = 1;
+    assert!(n <= 1 || n as usize <= flags.len());
+    while i < n {
+        if *unsafe { flags.get_unchecked(i as usize) } {
+            out[i as usize] = 1;
+        }
+        i = i + 1;
+    }
+}
+
+pub fn find(xs: &[i32], n: usize, x: i32) -> Option<usize> {
+    let mut i: usize = 0;
+    while i < n {
+        if xs[i] == x {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+fn main() {
+    let src = [1, 2, 3, 4];
+    let mut dst = [0; 4];
+    unsafe { copy(&mut dst, &src, 3) };
+    assert_eq!(dst, [1, 2, 3, 0]);
+    unsafe { copy(&mut dst, &src, -1) };
+    assert_eq!(dst, [1, 2, 3, 0]);
+
+    assert_eq!(sum(&[1, 2, 3, 4], 4), 10);
+
+    let mut out = [0; 4];
+    mark(&[true, true, false, true], &mut out, 4);
+    assert_eq!(out, [0, 1, 0, 1]);
+
+    assert_eq!(find(&[5, 6, 7], 5, 6), Some(1));
+}
@@ -0,0 +1,37 @@
+#!/bin/sh
+set -e
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor hoist_bounds_checks unchecked -- old.rs $rustflags
+
+# The rewritten loops must behave like the original ones
+tmp=`mktemp -d`
+rustc $rustflags -o $tmp/hoist_bounds_checks old.new
+$tmp/hoist_bounds_checks
+
+# Count the calls to `panic_bounds_check` in the optimized code of function `$2` of `$1`
+bounds_checks() {
+    rustc $rustflags -O --crate-type lib --emit llvm-ir -o $tmp/$2.ll $1 2>/dev/null
+    awk -v f="$2" '/^define / { infn = index($0, length(f) f "17h") > 0 }
+                   /^}/ { infn = 0 }
+                   infn && /panic_bounds_check/ { n++ }
+                   END { print n + 0 }' $tmp/$2.ll
+}
+
+# The loops of the original functions check the bounds on every iteration, and the rewritten
+# ones only in the assertions before them
+for f in copy sum; do
+    if [ `bounds_checks old.rs $f` -eq 0 ]; then
+        echo "$f already has no bounds checks in its loop" >&2
+        exit 1
+    fi
+    if [ `bounds_checks old.new $f` -ne 0 ]; then
+        echo "$f still has bounds checks in its loop" >&2
+        exit 1
+    fi
+done
+rm -r $tmp
@@ -0,0 +1,45 @@
+pub struct Foo {
+    flags: i32,
+    count: i32,
+}
+
+unsafe fn bump(p: *mut Foo) {
+    let flags = {
+        let p: *mut Foo = p;
+        (*p).flags
+    };
+    (*p).flags = flags + 1;
+    (*p).count += 1;
+}
+
+unsafe fn run(p: *mut Foo) -> i32 {
+    let mut total = {
+        let p: *mut Foo = p;
+        (*p).flags
+    } * 10;
+    while {
+        let p: *mut Foo = p;
+        (*p).flags
+    } < 5
+    {
+        bump(p);
+        total += 1;
+    }
+    if {
+        let p: *mut Foo = p;
+        (*p).flags
+    } == 5
+    {
+        total += {
+            let p: *mut Foo = p;
+            (*p).flags
+        };
+    }
+    total
+}
+
+fn main() {
+    let mut foo = Foo { flags: 1, count: 0 };
+    let total = unsafe { run(&mut foo) };
+    println!("{} {}", total, foo.count);
+}
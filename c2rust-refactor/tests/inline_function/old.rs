@@ -0,0 +1,32 @@
+pub struct Foo {
+    flags: i32,
+    count: i32,
+}
+
+unsafe fn get_flags(p: *mut Foo) -> i32 {
+    (*p).flags
+}
+
+unsafe fn bump(p: *mut Foo) {
+    let flags = get_flags(p);
+    (*p).flags = flags + 1;
+    (*p).count += 1;
+}
+
+unsafe fn run(p: *mut Foo) -> i32 {
+    let mut total = get_flags(p) * 10;
+    while get_flags(p) < 5 {
+        bump(p);
+        total += 1;
+    }
+    if get_flags(p) == 5 {
+        total += get_flags(p);
+    }
+    total
+}
+
+fn main() {
+    let mut foo = Foo { flags: 1, count: 0 };
+    let total = unsafe { run(&mut foo) };
+    println!("{} {}", total, foo.count);
+}
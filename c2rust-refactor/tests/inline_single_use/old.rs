@@ -0,0 +1,52 @@
+pub struct Buf {
+    len: i32,
+    cap: i32,
+}
+
+unsafe fn is_full(b: *mut Buf, extra: i32) -> i32 {
+    ((*b).len + extra >= (*b).cap) as i32
+}
+
+unsafe fn push(b: *mut Buf) {
+    if is_full(b, 1) != 0 {
+        (*b).cap *= 2;
+    }
+    (*b).len += 1;
+}
+
+unsafe fn len(b: *mut Buf) -> i32 {
+    (*b).len
+}
+
+unsafe fn clear(b: *mut Buf) {
+    (*b).len = 0;
+}
+
+fn fact(n: i32) -> i32 {
+    if n <= 1 { 1 } else { n * fact(n - 1) }
+}
+
+fn next(counter: &mut i32) -> i32 {
+    *counter += 1;
+    *counter
+}
+
+fn add(x: i32, y: i32) -> i32 {
+    x + y
+}
+
+fn main() {
+    let mut buf = Buf { len: 0, cap: 1 };
+    let b: *mut Buf = &mut buf;
+    let reset: unsafe fn(*mut Buf) = clear;
+    let mut counter = 0;
+    unsafe {
+        push(b);
+        push(b);
+        println!("{} {}", len(b), (*b).cap);
+        reset(b);
+        println!("{}", len(b));
+    }
+    let total = add(next(&mut counter), next(&mut counter));
+    println!("{} {}", total, fact(5));
+}
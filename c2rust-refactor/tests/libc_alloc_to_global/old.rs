@@ -0,0 +1,107 @@
+#![feature(rustc_private)]
+extern crate libc;
+
+extern "C" {
+    #[no_mangle]
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    #[no_mangle]
+    fn calloc(_: libc::c_ulong, _: libc::c_ulong) -> *mut libc::c_void;
+    #[no_mangle]
+    fn realloc(_: *mut libc::c_void, _: libc::c_ulong) -> *mut libc::c_void;
+    #[no_mangle]
+    fn free(__ptr: *mut libc::c_void);
+    #[no_mangle]
+    fn memset(_: *mut libc::c_void, _: libc::c_int, _: libc::c_ulong) -> *mut libc::c_void;
+}
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct point {
+    pub x: libc::c_int,
+    pub y: libc::c_int,
+}
+#[no_mangle]
+pub unsafe extern "C" fn sum_squares(mut n: libc::c_int) -> libc::c_int {
+    let mut squares: *mut libc::c_int = malloc(
+        (n as libc::c_ulong).wrapping_mul(::std::mem::size_of::<libc::c_int>() as libc::c_ulong),
+    ) as *mut libc::c_int;
+    if squares.is_null() {
+        return -(1 as libc::c_int);
+    }
+    let mut i: libc::c_int = 0 as libc::c_int;
+    while i < n {
+        *squares.offset(i as isize) = i * i;
+        i += 1
+    }
+    squares = realloc(
+        squares as *mut libc::c_void,
+        ((2 as libc::c_int * n) as libc::c_ulong)
+            .wrapping_mul(::std::mem::size_of::<libc::c_int>() as libc::c_ulong),
+    ) as *mut libc::c_int;
+    while i < 2 as libc::c_int * n {
+        *squares.offset(i as isize) = i * i;
+        i += 1
+    }
+    let mut total: libc::c_int = 0 as libc::c_int;
+    i = 0 as libc::c_int;
+    while i < 2 as libc::c_int * n {
+        total += *squares.offset(i as isize);
+        i += 1
+    }
+    free(squares as *mut libc::c_void);
+    return total;
+}
+#[no_mangle]
+pub unsafe extern "C" fn count_digits(mut s: *const libc::c_char) -> libc::c_int {
+    let mut counts: *mut libc::c_int = calloc(
+        10 as libc::c_int as libc::c_ulong,
+        ::std::mem::size_of::<libc::c_int>() as libc::c_ulong,
+    ) as *mut libc::c_int;
+    while *s != 0 {
+        *counts.offset((*s as libc::c_int - '0' as i32) as isize) += 1;
+        s = s.offset(1)
+    }
+    let mut most: libc::c_int = *counts.offset(1 as libc::c_int as isize);
+    free(counts as *mut libc::c_void);
+    return most;
+}
+#[no_mangle]
+pub unsafe extern "C" fn midpoint(mut a: point, mut b: point) -> libc::c_int {
+    let mut p: *mut point = 0 as *mut point;
+    p = malloc(::std::mem::size_of::<point>() as libc::c_ulong) as *mut point;
+    memset(
+        p as *mut libc::c_void,
+        0 as libc::c_int,
+        ::std::mem::size_of::<point>() as libc::c_ulong,
+    );
+    (*p).x = (a.x + b.x) / 2 as libc::c_int;
+    (*p).y = (a.y + b.y) / 2 as libc::c_int;
+    let mut sum: libc::c_int = (*p).x + (*p).y;
+    free(p as *mut libc::c_void);
+    return sum;
+}
+#[no_mangle]
+pub unsafe extern "C" fn new_point(mut x: libc::c_int, mut y: libc::c_int) -> *mut point {
+    let mut p: *mut point = malloc(::std::mem::size_of::<point>() as libc::c_ulong) as *mut point;
+    (*p).x = x;
+    (*p).y = y;
+    return p;
+}
+fn main() {
+    unsafe {
+        let p = new_point(1, 2);
+        let zero: libc::c_char = 0;
+        let digits = [
+            b'1' as libc::c_char,
+            b'7' as libc::c_char,
+            b'1' as libc::c_char,
+            zero,
+        ];
+        println!(
+            "{} {} {}",
+            sum_squares(4),
+            count_digits(digits.as_ptr()),
+            midpoint(*p, point { x: 5, y: 8 })
+        );
+        free(p as *mut libc::c_void);
+    }
+}
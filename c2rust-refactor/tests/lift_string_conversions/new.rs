@@ -0,0 +1,48 @@
+#![feature(libc)]
+extern crate libc;
+
+extern "C" {
+    #[no_mangle]
+    fn strlen(_: *const libc::c_char) -> libc::c_ulong;
+    #[no_mangle]
+    fn strcmp(_: *const libc::c_char, _: *const libc::c_char) -> libc::c_int;
+    #[no_mangle]
+    fn puts(_: *const libc::c_char) -> libc::c_int;
+}
+
+unsafe fn is_keyword(word: &str) -> libc::c_int {
+    if word.len() as u64 > 5 as libc::c_int as libc::c_ulong {
+        return 0 as libc::c_int;
+    }
+    (word == "if" || word == "while") as libc::c_int
+}
+
+unsafe fn shout(msg: &::std::ffi::CStr) -> libc::c_int {
+    if msg.to_bytes_with_nul()[0] as i8 as libc::c_int == 0 as libc::c_int {
+        return -(1 as libc::c_int);
+    }
+    puts(msg.as_ptr())
+}
+
+unsafe fn count_spaces(mut s: *const libc::c_char) -> libc::c_int {
+    let mut n: libc::c_int = 0 as libc::c_int;
+    while *s as libc::c_int != 0 as libc::c_int {
+        if *s as libc::c_int == ' ' as i32 {
+            n += 1
+        }
+        s = s.offset(1)
+    }
+    n
+}
+
+fn main() {
+    unsafe {
+        println!("{}", is_keyword("if"));
+        println!("{}", is_keyword("return"));
+        println!("{}", is_keyword("while"));
+        let name = ::std::ffi::CString::new("a b c").unwrap();
+        shout(::std::ffi::CStr::from_bytes_with_nul(b"hello\x00").unwrap());
+        shout(name.as_c_str());
+        println!("{}", count_spaces(name.as_ptr()));
+    }
+}
@@ -0,0 +1,50 @@
+#![feature(libc)]
+extern crate libc;
+
+extern "C" {
+    #[no_mangle]
+    fn strlen(_: *const libc::c_char) -> libc::c_ulong;
+    #[no_mangle]
+    fn strcmp(_: *const libc::c_char, _: *const libc::c_char) -> libc::c_int;
+    #[no_mangle]
+    fn puts(_: *const libc::c_char) -> libc::c_int;
+}
+
+unsafe fn is_keyword(mut word: *const libc::c_char) -> libc::c_int {
+    if strlen(word) > 5 as libc::c_int as libc::c_ulong {
+        return 0 as libc::c_int;
+    }
+    (strcmp(word, b"if\x00" as *const u8 as *const libc::c_char) == 0 as libc::c_int ||
+         strcmp(word, b"while\x00" as *const u8 as *const libc::c_char) == 0 as libc::c_int)
+        as libc::c_int
+}
+
+unsafe fn shout(mut msg: *const libc::c_char) -> libc::c_int {
+    if *msg as libc::c_int == 0 as libc::c_int {
+        return -(1 as libc::c_int);
+    }
+    puts(msg)
+}
+
+unsafe fn count_spaces(mut s: *const libc::c_char) -> libc::c_int {
+    let mut n: libc::c_int = 0 as libc::c_int;
+    while *s as libc::c_int != 0 as libc::c_int {
+        if *s as libc::c_int == ' ' as i32 {
+            n += 1
+        }
+        s = s.offset(1)
+    }
+    n
+}
+
+fn main() {
+    unsafe {
+        println!("{}", is_keyword(b"if\x00" as *const u8 as *const libc::c_char));
+        println!("{}", is_keyword(b"return\x00" as *const u8 as *const libc::c_char));
+        println!("{}", is_keyword(b"while\x00" as *const u8 as *const libc::c_char));
+        let name = ::std::ffi::CString::new("a b c").unwrap();
+        shout(b"hello\x00" as *const u8 as *const libc::c_char);
+        shout(name.as_ptr());
+        println!("{}", count_spaces(name.as_ptr()));
+    }
+}
@@ -0,0 +1,52 @@
+mod a {
+    #[no_mangle]
+    pub unsafe extern "C" fn same(x: i32) -> i32 {
+        x
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn ptr_arg(p: *mut u8) -> i32 {
+        *p as i32
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn float_arg(x: f32) -> i32 {
+        x as i32
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn wide_arg(x: i64) -> i32 {
+        x as i32
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn fn_ptr_arg(f: fn(i32) -> i32) -> i32 {
+        f(5)
+    }
+}
+
+// `same` is identical, `ptr_arg` is castable, `float_arg` is suspicious (an
+// integer passed where a float is expected), and `wide_arg` is incompatible.
+// `fn_ptr_arg` is suspicious too: a raw pointer can't be cast to a fn pointer.
+mod b {
+    extern "C" {
+        fn float_arg(x: i32) -> i32;
+        fn wide_arg(x: i32) -> i32;
+        fn fn_ptr_arg(f: *mut u8) -> i32;
+    }
+
+    pub unsafe fn use_all(p: *mut i8) -> i32 {
+        crate::a::same(1)
+            + crate::a::ptr_arg(p as *mut u8) as u32 as i32
+            + float_arg(2)
+            + wide_arg(3)
+            + fn_ptr_arg(p as *mut u8)
+    }
+}
+
+fn main() {
+    let mut x = 4i8;
+    unsafe {
+        crate::b::use_all(&mut x);
+    }
+}
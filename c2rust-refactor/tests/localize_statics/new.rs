@@ -0,0 +1,53 @@
+#[no_mangle]
+pub static mut EXPORTED: i32 = 0;
+
+mod counter {
+    pub static mut COUNT: i32 = 0;
+}
+
+unsafe fn sum_squares(x: i32) -> i32 {
+    let mut SCRATCH: [i32; 4] = [0; 4];
+    SCRATCH = [x, x + 1, x + 2, x + 3];
+    let mut sum = 0;
+    let mut i = 0;
+    while i < 4 {
+        sum += SCRATCH[i] * SCRATCH[i];
+        i += 1;
+    }
+    sum
+}
+
+unsafe fn accumulate(x: i32) -> i32 {
+    static mut TOTAL: i32 = 0;
+    TOTAL += x;
+    TOTAL
+}
+
+unsafe fn bump() {
+    EXPORTED += 1;
+}
+
+// The write to `DOUBLED` is skipped when `x` is negative
+unsafe fn double(x: i32) -> i32 {
+    static mut DOUBLED: i32 = 0;
+    if x < 0 {
+        return -1;
+    }
+    DOUBLED = x * 2;
+    DOUBLED
+}
+
+unsafe fn count() -> i32 {
+    counter::COUNT += 1;
+    counter::COUNT
+}
+
+fn main() {
+    unsafe {
+        println!("{}", sum_squares(1));
+        accumulate(2);
+        println!("{}", accumulate(3));
+        bump();
+        println!("{} {}", double(4), count());
+    }
+}
@@ -0,0 +1,5 @@
+fn main() {
+    let x = 4;
+    let y = x * 3 + 1;
+    println!("{}", y);
+}
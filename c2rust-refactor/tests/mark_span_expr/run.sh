@@ -0,0 +1,11 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor \
+    mark_span old.rs 39 44 target expr \; \
+    rewrite_expr 'marked!($e:Expr)' '0' \
+    -- old.rs $rustflags
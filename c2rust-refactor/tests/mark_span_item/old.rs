@@ -0,0 +1,7 @@
+fn unused(x: i32) -> i32 {
+    x * 2
+}
+
+fn main() {
+    println!("{}", 1);
+}
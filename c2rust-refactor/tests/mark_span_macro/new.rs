@@ -0,0 +1,11 @@
+macro_rules! double {
+    ($e:expr) => {
+        $e * 2
+    };
+}
+
+fn main() {
+    let x = 4;
+    let y = double!(x);
+    println!("{}", y);
+}
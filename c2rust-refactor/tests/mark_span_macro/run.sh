@@ -0,0 +1,12 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+# The range covers a macro invocation, so nothing can be marked.
+$refactor \
+    mark_span old.rs 105 115 target expr \
+    -- old.rs $rustflags 2>&1 | grep -q 'lies inside the macro invocation' || exit 1
+cp old.rs old.new
@@ -0,0 +1,39 @@
+pub struct S {
+    x: i32,
+}
+
+impl S {
+    pub fn new(x: i32) -> S {
+        S { x }
+    }
+    pub fn get(&self) -> i32 {
+        // The original text of unchanged methods is kept
+        self.x
+    }
+    #[allow(unused)]
+    fn twice(&self) -> i32 {
+        self.x * 2
+    }
+}
+
+impl Clone for S {
+    fn clone(&self) -> S {
+        S { x: self.x }
+    }
+}
+
+fn helper(s: &S) -> i32 {
+    s.get() + 1
+}
+
+#[cfg(not(target_os = "none"))]
+impl S {
+    pub fn describe(&self) -> String {
+        format!("S({})", self.x)
+    }
+}
+
+fn main() {
+    let s = S::new(1).clone();
+    println!("{} {} {}", helper(&s), s.describe(), s.twice());
+}
@@ -0,0 +1,45 @@
+pub struct S {
+    x: i32,
+}
+
+impl S {
+    pub fn new(x: i32) -> S {
+        S { x }
+    }
+}
+
+impl Clone for S {
+    fn clone(&self) -> S {
+        S { x: self.x }
+    }
+}
+
+fn helper(s: &S) -> i32 {
+    s.get() + 1
+}
+
+impl S {
+    pub fn get(&self) -> i32 {
+        // The original text of unchanged methods is kept
+        self.x
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl S {
+    pub fn describe(&self) -> String {
+        format!("S({})", self.x)
+    }
+}
+
+#[allow(unused)]
+impl S {
+    fn twice(&self) -> i32 {
+        self.x * 2
+    }
+}
+
+fn main() {
+    let s = S::new(1).clone();
+    println!("{} {} {}", helper(&s), s.describe(), s.twice());
+}
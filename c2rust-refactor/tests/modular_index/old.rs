@@ -0,0 +1,61 @@
+const N: u32 = 8;
+
+pub struct Queue {
+    items: [i32; 8],
+    head: u32,
+    tail: u32,
+}
+
+pub unsafe fn enqueue(q: *mut Queue, x: i32) {
+    (*q).items[(*q).tail as usize] = x;
+    (*q).tail = (*q).tail.wrapping_add(1).wrapping_rem(N);
+}
+
+pub unsafe fn dequeue(q: *mut Queue) -> i32 {
+    let x = (*q).items[(*q).head as usize];
+    (*q).head = (*q).head.wrapping_add(1).wrapping_rem(N);
+    x
+}
+
+pub fn stride(xs: &[i32; 5], start: i32) -> i32 {
+    let mut digits = 0;
+    let mut i = start;
+    for _ in 0..5 {
+        digits = digits * 10 + xs[i as usize];
+        i = (i + 2) % 5;
+    }
+    digits
+}
+
+// `j` indexes two arrays, so it is left alone
+pub fn copy_rotated(src: &[i32; 3], dst: &mut [i32; 3], start: usize) {
+    let mut j = start;
+    for _ in 0..3 {
+        dst[j] = src[j];
+        j = (j + 1) % 3;
+    }
+}
+
+fn main() {
+    let mut q = Queue {
+        items: [0; 8],
+        head: 0,
+        tail: 0,
+    };
+    unsafe {
+        for x in 0..10 {
+            enqueue(&mut q, x);
+        }
+        assert_eq!(q.tail, 2);
+        assert_eq!(dequeue(&mut q), 8);
+        assert_eq!(dequeue(&mut q), 9);
+        assert_eq!(dequeue(&mut q), 2);
+        assert_eq!(q.head, 3);
+    }
+
+    assert_eq!(stride(&[1, 2, 3, 4, 5], 1), 24135);
+
+    let mut dst = [0; 3];
+    copy_rotated(&[1, 2, 3], &mut dst, 2);
+    assert_eq!(dst, [1, 2, 3]);
+}
@@ -0,0 +1,23 @@
+//! Calls through a function pointer loaded from a dispatch table require the join of the
+//! permissions of every function stored in the table: `dispatch` can't know which handler it
+//! calls, so `p` must be writable for `set`, even though `get` only reads it.
+
+#![feature(custom_attribute, attr_literals)]
+
+type Handler = unsafe fn(*mut i32, i32);
+
+unsafe fn get(p: *mut i32, _x: i32) {
+    let _y = *p;
+}
+
+unsafe fn set(p: *mut i32, x: i32) {
+    *p = x;
+}
+
+static HANDLERS: [Handler; 2] = [get, set];
+
+unsafe fn dispatch(op: usize, p: *mut i32, x: i32) {
+    HANDLERS[op](p, x);
+}
+
+fn main() {}
@@ -0,0 +1,55 @@
+//! Procedural macros for the `proc_macro_derive` test.  They mimic the shape of common real-world
+//! macros without depending on `syn` or `quote`.
+extern crate proc_macro;
+
+use proc_macro::{TokenStream, TokenTree};
+
+/// Get the name of the struct or fn in `input`: the identifier after the `struct` or `fn`
+/// keyword.
+fn item_name(input: TokenStream) -> String {
+    let mut tokens = input.into_iter();
+    while let Some(tt) = tokens.next() {
+        if let TokenTree::Ident(ident) = tt {
+            let keyword = ident.to_string();
+            if keyword == "struct" || keyword == "fn" {
+                return tokens.next().expect("missing item name").to_string();
+            }
+        }
+    }
+    panic!("expected a struct or fn");
+}
+
+/// Derive `crate::Serialize` the way serde does: inside a `const`, without marking the impl
+/// `#[automatically_derived]`.
+#[proc_macro_derive(Serialize)]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let name = item_name(input);
+    format!(
+        "const _IMPL_SERIALIZE_FOR_{0}: () = {{
+            impl crate::Serialize for {0} {{
+                fn serialize(&self) -> String {{
+                    format!(\"{0}\")
+                }}
+            }}
+        }};",
+        name
+    )
+    .parse()
+    .unwrap()
+}
+
+/// Pass the annotated fn through, and add a static holding its name.
+#[proc_macro_attribute]
+pub fn traced(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let name = item_name(item.clone());
+    let mut out = item;
+    let name_static: TokenStream = format!(
+        "#[allow(dead_code)] static TRACED_{}: &str = \"{}\";",
+        name.to_uppercase(),
+        name
+    )
+    .parse()
+    .unwrap();
+    out.extend(name_static);
+    out
+}
@@ -0,0 +1,36 @@
+// Test that derives and attribute macros from procedural macro crates are left alone, while the
+// code around them gets rewritten.
+
+use fixture_macros::{traced, Serialize};
+
+pub trait Serialize {
+    fn serialize(&self) -> String;
+}
+
+#[derive(Clone, Serialize)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[cfg_attr(all(), derive(Debug, Serialize))]
+#[derive(Clone)]
+pub struct Size {
+    pub w: i32,
+    pub h: i32,
+}
+
+#[traced]
+fn area(s: &Size) -> i32 {
+    mul(s.w, s.h)
+}
+
+fn mul(a: i32, b: i32) -> i32 {
+    a * b
+}
+
+fn main() {
+    let p = Point { x: 1, y: 2 };
+    let s = Size { w: p.x, h: p.y }.clone();
+    println!("{} {:?} {}", p.clone().serialize(), s, area(&s));
+}
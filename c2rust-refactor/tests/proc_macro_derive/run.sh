@@ -0,0 +1,16 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+macros_dir=$(mktemp -d)
+rustc --crate-type proc-macro --edition 2018 --out-dir $macros_dir fixture_macros.rs || exit 1
+macros_lib=$(ls $macros_dir/*fixture_macros.*)
+
+$refactor rename_regex fn '^helper_(.*)$' '$1' \
+    -- old.rs $rustflags --extern fixture_macros=$macros_lib
+status=$?
+rm -rf $macros_dir
+exit $status
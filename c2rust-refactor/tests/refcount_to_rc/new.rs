@@ -0,0 +1,77 @@
+#![feature(rustc_private, custom_attribute)]
+extern crate libc;
+
+extern "C" {
+    #[no_mangle]
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    #[no_mangle]
+    fn free(__ptr: *mut libc::c_void);
+    #[no_mangle]
+    fn strlen(_: *const libc::c_char) -> libc::c_ulong;
+    #[no_mangle]
+    fn strdup(_: *const libc::c_char) -> *mut libc::c_char;
+    #[no_mangle]
+    fn puts(_: *const libc::c_char) -> libc::c_int;
+}
+pub type size_t = libc::c_ulong;
+#[derive(Clone)]
+#[repr(C)]
+pub struct string {
+    pub len: size_t,
+    pub data: *mut libc::c_char,
+}
+impl Drop for string {
+    fn drop(&mut self) {
+        unsafe {
+            puts(self.data);
+            free(self.data as *mut libc::c_void);
+        }
+    }
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_new(
+    mut s: *const libc::c_char,
+) -> ::std::rc::Rc<::std::cell::RefCell<string>> {
+    let mut str: ::std::rc::Rc<::std::cell::RefCell<string>> =
+        ::std::rc::Rc::new(::std::cell::RefCell::new(::std::mem::zeroed()));
+    str.borrow_mut().len = strlen(s);
+    str.borrow_mut().data = strdup(s);
+    return str;
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_retain(
+    mut str: ::std::rc::Rc<::std::cell::RefCell<string>>,
+) -> ::std::rc::Rc<::std::cell::RefCell<string>> {
+    return str;
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_release(mut str: ::std::rc::Rc<::std::cell::RefCell<string>>) {
+    ::std::mem::drop(str);
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_len(mut str: ::std::rc::Rc<::std::cell::RefCell<string>>) -> size_t {
+    return str.borrow().len;
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_truncate(
+    mut str: ::std::rc::Rc<::std::cell::RefCell<string>>,
+    mut len: size_t,
+) {
+    if len < str.borrow().len {
+        str.borrow_mut().len = len;
+        *str.borrow().data.offset(len as isize) = 0 as libc::c_int as libc::c_char
+    };
+}
+unsafe fn main_0() -> libc::c_int {
+    let mut a: ::std::rc::Rc<::std::cell::RefCell<string>> =
+        string_new(b"hello\x00" as *const u8 as *const libc::c_char);
+    let mut b: ::std::rc::Rc<::std::cell::RefCell<string>> = string_retain(a.clone());
+    string_release(a);
+    string_truncate(b.clone(), 4 as libc::c_int as size_t);
+    let mut len: size_t = string_len(b.clone());
+    string_release(b);
+    return len as libc::c_int;
+}
+fn main() {
+    unsafe { ::std::process::exit(main_0()) }
+}
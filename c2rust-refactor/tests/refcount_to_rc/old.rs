@@ -0,0 +1,73 @@
+#![feature(rustc_private, custom_attribute)]
+extern crate libc;
+
+extern "C" {
+    #[no_mangle]
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    #[no_mangle]
+    fn free(__ptr: *mut libc::c_void);
+    #[no_mangle]
+    fn strlen(_: *const libc::c_char) -> libc::c_ulong;
+    #[no_mangle]
+    fn strdup(_: *const libc::c_char) -> *mut libc::c_char;
+    #[no_mangle]
+    fn puts(_: *const libc::c_char) -> libc::c_int;
+}
+pub type size_t = libc::c_ulong;
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct string {
+    #[refcount_field]
+    pub refs: libc::c_int,
+    pub len: size_t,
+    pub data: *mut libc::c_char,
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_new(mut s: *const libc::c_char) -> *mut string {
+    let mut str: *mut string =
+        malloc(::std::mem::size_of::<string>() as libc::c_ulong) as *mut string;
+    (*str).refs = 1 as libc::c_int;
+    (*str).len = strlen(s);
+    (*str).data = strdup(s);
+    return str;
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_retain(mut str: *mut string) -> *mut string {
+    (*str).refs += 1;
+    return str;
+}
+unsafe extern "C" fn string_free(mut str: *mut string) {
+    puts((*str).data);
+    free((*str).data as *mut libc::c_void);
+    free(str as *mut libc::c_void);
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_release(mut str: *mut string) {
+    (*str).refs -= 1;
+    if (*str).refs == 0 as libc::c_int {
+        string_free(str);
+    };
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_len(mut str: *mut string) -> size_t {
+    return (*str).len;
+}
+#[no_mangle]
+pub unsafe extern "C" fn string_truncate(mut str: *mut string, mut len: size_t) {
+    if len < (*str).len {
+        (*str).len = len;
+        *(*str).data.offset(len as isize) = 0 as libc::c_int as libc::c_char
+    };
+}
+unsafe fn main_0() -> libc::c_int {
+    let mut a: *mut string = string_new(b"hello\x00" as *const u8 as *const libc::c_char);
+    let mut b: *mut string = string_retain(a);
+    string_release(a);
+    string_truncate(b, 4 as libc::c_int as size_t);
+    let mut len: size_t = string_len(b);
+    string_release(b);
+    return len as libc::c_int;
+}
+fn main() {
+    unsafe { ::std::process::exit(main_0() as i32) }
+}
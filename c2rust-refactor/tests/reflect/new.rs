@@ -1,4 +1,5 @@
 #![feature(type_ascription)]
+#![feature(never_type)]
 
 struct S<T>(T);
 
@@ -8,6 +9,25 @@ impl S<(i32, S<Option<i32>>)> {
 
 fn g() {}
 
+fn diverge() -> ! {
+    (::std::process::exit: _)(0: i32): _
+}
+
+fn absurd(x: !) -> i32 {
+    x: _
+}
+
+// Function pointers behind raw pointers and in arrays, taking and returning slices
+fn nested(
+    p: *const fn(&[u8]) -> &[u8],
+    q: *mut fn(*const [u8]) -> i32,
+    t: [fn(&[u8; 4]) -> &[u8]; 2],
+) {
+    let p2 = p: *const fn(_: &[u8]) -> &[u8];
+    let q2 = q: *mut fn(_: *const [u8]) -> i32;
+    let t2 = t: [fn(_: &[u8; 4usize]) -> &[u8]; 2usize];
+}
+
 fn main() {
     let s = (crate::S::<(i32, crate::S<::std::option::Option<i32>>)>: _)(
         (
@@ -38,4 +58,9 @@ fn main() {
         ::std::ops::RangeFull: ::std::ops::RangeFull,
     ): ::std::vec::Drain<i32>;
 
+    let d: fn() -> ! = crate::diverge: _;
+    let d2 = d: fn() -> !;
+    let a: fn(!) -> i32 = crate::absurd: _;
+    let a2 = a: fn(_: _) -> i32;
+
 }
@@ -1,4 +1,5 @@
 #![feature(type_ascription)]
+#![feature(never_type)]
 
 struct S<T>(T);
 
@@ -8,6 +9,25 @@ impl S<(i32, S<Option<i32>>)> {
 
 fn g() {}
 
+fn diverge() -> ! {
+    ::std::process::exit(0)
+}
+
+fn absurd(x: !) -> i32 {
+    x
+}
+
+// Function pointers behind raw pointers and in arrays, taking and returning slices
+fn nested(
+    p: *const fn(&[u8]) -> &[u8],
+    q: *mut fn(*const [u8]) -> i32,
+    t: [fn(&[u8; 4]) -> &[u8]; 2],
+) {
+    let p2 = p;
+    let q2 = q;
+    let t2 = t;
+}
+
 fn main() {
     let s = S((0, S(None)));
     let x = s.f();
@@ -20,4 +40,9 @@ fn main() {
     f(&mut v);
     Vec::drain(&mut v, ..);
 
+    let d: fn() -> ! = diverge;
+    let d2 = d;
+    let a: fn(!) -> i32 = absurd;
+    let a2 = a;
+
 }
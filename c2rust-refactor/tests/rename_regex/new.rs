@@ -0,0 +1,105 @@
+static mut STATE: i32 = 0;
+fn init() {
+    unsafe { STATE = 1 };
+}
+fn cleanup() {
+    unsafe { STATE = 0 };
+}
+#[no_mangle]
+pub extern "C" fn rust_init() {
+    init();
+}
+#[no_mangle]
+pub extern "C" fn rust_cleanup() {
+    cleanup();
+}
+#[export_name = "rust_parse_header"]
+pub extern "C" fn parse_header(x: i32) -> i32 {
+    x + 0
+}
+fn parse_body(x: i32) -> i32 {
+    parse_header(x) * 2
+}
+pub fn parse_footer(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = parse_body;
+    f(x) - 1
+}
+#[export_name = "rust_read_byte"]
+pub extern "C" fn read_byte(x: i32) -> i32 {
+    x + 3
+}
+fn read_word(x: i32) -> i32 {
+    read_byte(x) * 2
+}
+pub fn write_byte(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = read_word;
+    f(x) - 1
+}
+#[export_name = "rust_write_word"]
+pub extern "C" fn write_word(x: i32) -> i32 {
+    x + 6
+}
+fn node_new(x: i32) -> i32 {
+    write_word(x) * 2
+}
+pub fn node_free(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = node_new;
+    f(x) - 1
+}
+#[export_name = "rust_node_append"]
+pub extern "C" fn node_append(x: i32) -> i32 {
+    x + 9
+}
+fn list_len(x: i32) -> i32 {
+    node_append(x) * 2
+}
+pub fn list_push(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = list_len;
+    f(x) - 1
+}
+#[export_name = "rust_list_pop"]
+pub extern "C" fn list_pop(x: i32) -> i32 {
+    x + 12
+}
+fn buf_reserve(x: i32) -> i32 {
+    list_pop(x) * 2
+}
+pub fn buf_clear(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = buf_reserve;
+    f(x) - 1
+}
+#[export_name = "rust_str_eq"]
+pub extern "C" fn str_eq(x: i32) -> i32 {
+    x + 15
+}
+fn str_len(x: i32) -> i32 {
+    str_eq(x) * 2
+}
+pub fn hash_bytes(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = str_len;
+    f(x) - 1
+}
+fn main() {
+    rust_init();
+    let mut total = 0;
+    total += parse_header(1);
+    total += parse_body(1);
+    total += parse_footer(1);
+    total += read_byte(1);
+    total += read_word(1);
+    total += write_byte(1);
+    total += write_word(1);
+    total += node_new(1);
+    total += node_free(1);
+    total += node_append(1);
+    total += list_len(1);
+    total += list_push(1);
+    total += list_pop(1);
+    total += buf_reserve(1);
+    total += buf_clear(1);
+    total += str_eq(1);
+    total += str_len(1);
+    total += hash_bytes(1);
+    rust_cleanup();
+    println!("{}", total);
+}
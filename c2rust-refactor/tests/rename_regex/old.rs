@@ -0,0 +1,105 @@
+static mut STATE: i32 = 0;
+fn init() {
+    unsafe { STATE = 1 };
+}
+fn cleanup() {
+    unsafe { STATE = 0 };
+}
+#[no_mangle]
+pub extern "C" fn rust_init() {
+    init();
+}
+#[no_mangle]
+pub extern "C" fn rust_cleanup() {
+    cleanup();
+}
+#[no_mangle]
+pub extern "C" fn rust_parse_header(x: i32) -> i32 {
+    x + 0
+}
+fn rust_parse_body(x: i32) -> i32 {
+    rust_parse_header(x) * 2
+}
+pub fn rust_parse_footer(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = rust_parse_body;
+    f(x) - 1
+}
+#[no_mangle]
+pub extern "C" fn rust_read_byte(x: i32) -> i32 {
+    x + 3
+}
+fn rust_read_word(x: i32) -> i32 {
+    rust_read_byte(x) * 2
+}
+pub fn rust_write_byte(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = rust_read_word;
+    f(x) - 1
+}
+#[no_mangle]
+pub extern "C" fn rust_write_word(x: i32) -> i32 {
+    x + 6
+}
+fn rust_node_new(x: i32) -> i32 {
+    rust_write_word(x) * 2
+}
+pub fn rust_node_free(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = rust_node_new;
+    f(x) - 1
+}
+#[no_mangle]
+pub extern "C" fn rust_node_append(x: i32) -> i32 {
+    x + 9
+}
+fn rust_list_len(x: i32) -> i32 {
+    rust_node_append(x) * 2
+}
+pub fn rust_list_push(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = rust_list_len;
+    f(x) - 1
+}
+#[no_mangle]
+pub extern "C" fn rust_list_pop(x: i32) -> i32 {
+    x + 12
+}
+fn rust_buf_reserve(x: i32) -> i32 {
+    rust_list_pop(x) * 2
+}
+pub fn rust_buf_clear(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = rust_buf_reserve;
+    f(x) - 1
+}
+#[no_mangle]
+pub extern "C" fn rust_str_eq(x: i32) -> i32 {
+    x + 15
+}
+fn rust_str_len(x: i32) -> i32 {
+    rust_str_eq(x) * 2
+}
+pub fn rust_hash_bytes(x: i32) -> i32 {
+    let f: fn(i32) -> i32 = rust_str_len;
+    f(x) - 1
+}
+fn main() {
+    rust_init();
+    let mut total = 0;
+    total += rust_parse_header(1);
+    total += rust_parse_body(1);
+    total += rust_parse_footer(1);
+    total += rust_read_byte(1);
+    total += rust_read_word(1);
+    total += rust_write_byte(1);
+    total += rust_write_word(1);
+    total += rust_node_new(1);
+    total += rust_node_free(1);
+    total += rust_node_append(1);
+    total += rust_list_len(1);
+    total += rust_list_push(1);
+    total += rust_list_pop(1);
+    total += rust_buf_reserve(1);
+    total += rust_buf_clear(1);
+    total += rust_str_eq(1);
+    total += rust_str_len(1);
+    total += rust_hash_bytes(1);
+    rust_cleanup();
+    println!("{}", total);
+}
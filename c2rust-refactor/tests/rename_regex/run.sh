@@ -0,0 +1,15 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+# Stripping the prefix from `rust_init` and `rust_cleanup` collides with
+# `init` and `cleanup`, so nothing may be renamed
+out=$($refactor rename_regex fn '^rust_(.*)$' '$1' -- old.rs $rustflags 2>&1)
+echo "$out" | grep -q 'would all be named `init` in the crate root' || exit 1
+echo "$out" | grep -q 'would all be named `cleanup` in the crate root' || exit 1
+[ ! -f old.new ] || exit 1
+
+$refactor rename_regex fn '^rust_([a-z]+_[a-z_]+)$' '$1' -- old.rs $rustflags
@@ -0,0 +1,32 @@
+#![feature(custom_attribute)]
+#![allow(dead_code)]
+
+pub mod util_h {
+    // =============== BEGIN util_h ================
+
+    pub unsafe extern "C" fn helper() -> i32 {
+        1
+    }
+    // Identical to the helper of bar, but private to foo
+    pub unsafe extern "C" fn helper_foo() -> i32 {
+        1
+    }
+}
+
+pub mod bar {
+
+    pub unsafe fn bar() -> i32 {
+        crate::util_h::helper()
+    }
+}
+
+pub mod foo {
+
+    pub unsafe fn foo() -> i32 {
+        crate::util_h::helper_foo()
+    }
+}
+
+fn main() {
+    println!("hello!");
+}
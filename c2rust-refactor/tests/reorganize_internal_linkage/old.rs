@@ -0,0 +1,38 @@
+#![feature(custom_attribute)]
+
+#![allow(dead_code)]
+
+pub mod bar {
+    #[header_src = "/home/user/some/workspace/foobar/util.h:3"]
+    pub mod util_h {
+        #[internal_linkage]
+        #[src_loc = "2:0"]
+        pub unsafe extern "C" fn helper() -> i32 {
+            1
+        }
+    }
+
+    pub unsafe fn bar() -> i32 {
+        util_h::helper()
+    }
+}
+
+pub mod foo {
+    #[header_src = "/home/user/some/workspace/foobar/util.h:3"]
+    pub mod util_h {
+        // Identical to the helper of bar, but private to foo
+        #[internal_linkage]
+        #[src_loc = "2:0"]
+        pub unsafe extern "C" fn helper() -> i32 {
+            1
+        }
+    }
+
+    pub unsafe fn foo() -> i32 {
+        util_h::helper()
+    }
+}
+
+fn main() {
+    println!("hello!");
+}
@@ -0,0 +1,22 @@
+#![allow(non_camel_case_types)]
+
+type c_int = i32;
+
+fn sum(buf: &[u8], n: c_int) -> c_int {
+    let mut total = 0;
+    for &b in &buf[..n as usize] {
+        total += b as c_int;
+    }
+    total
+}
+
+fn scale(x: c_int, n: c_int) -> i32 {
+    x * n as i32
+}
+
+fn main() {
+    let buf = [1u8, 2, 3, 4];
+    let n: c_int = 3;
+    println!("{}", sum(&buf as &[u8], n));
+    println!("{}", scale(2, n));
+}
@@ -0,0 +1,13 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+# Retyping `n` to `i32` makes the casts between `c_int` and `i32` redundant.  They
+# are removed without running `remove_redundant_casts`, but the unsizing cast of
+# `&buf` is kept.
+$refactor \
+    select target 'crate; desc(arg && any_child(match_pat(n)));' \; \
+    retype_argument i32 '__old as i32' '__new as c_int' -- old.rs $rustflags
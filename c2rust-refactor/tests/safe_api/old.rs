@@ -0,0 +1,95 @@
+#![allow(non_camel_case_types)]
+
+use std::ffi::c_void;
+use std::os::raw::{c_char, c_int, c_uint};
+
+pub type size_t = usize;
+
+#[no_mangle]
+pub unsafe extern "C" fn fnv1a(mut data: *const u8, mut len: size_t) -> c_uint {
+    let mut hash: c_uint = 2166136261 as c_uint;
+    let mut i: size_t = 0 as size_t;
+    while i < len {
+        hash ^= *data.offset(i as isize) as c_uint;
+        hash = hash.wrapping_mul(16777619 as c_uint);
+        i = i.wrapping_add(1)
+    }
+    return hash;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn parse_uint(mut s: *const c_char, mut out: *mut c_uint) -> c_int {
+    let mut value: c_uint = 0 as c_uint;
+    if *s as c_int == 0 {
+        return 22 as c_int;
+    }
+    while *s as c_int != 0 {
+        let mut c: c_int = *s as c_int;
+        if c < '0' as i32 || c > '9' as i32 {
+            return 22 as c_int;
+        }
+        value = value
+            .wrapping_mul(10 as c_uint)
+            .wrapping_add((c - '0' as i32) as c_uint);
+        s = s.offset(1)
+    }
+    if !out.is_null() {
+        *out = value
+    }
+    return 0 as c_int;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn for_each(
+    mut items: *const c_uint,
+    mut count: size_t,
+    mut f: Option<unsafe extern "C" fn(_: *mut c_void, _: c_uint) -> ()>,
+    mut user_data: *mut c_void,
+) {
+    let mut i: size_t = 0 as size_t;
+    while i < count {
+        f.expect("non-null function pointer")(user_data, *items.offset(i as isize));
+        i = i.wrapping_add(1)
+    }
+}
+
+fn main() {}
+
+#[cfg(test)]
+mod tests {
+    use super::safe;
+    use std::ffi::{c_void, CString};
+    use std::os::raw::c_uint;
+
+    #[test]
+    fn hash() {
+        assert_eq!(safe::fnv1a(b""), 2166136261);
+        assert_eq!(safe::fnv1a(b"a"), 0xe40c292c);
+    }
+
+    #[test]
+    fn parse() {
+        let mut value = 0;
+        let s = CString::new("1234").unwrap();
+        assert_eq!(safe::parse_uint(&s, Some(&mut value)), Ok(()));
+        assert_eq!(value, 1234);
+        let s = CString::new("12a").unwrap();
+        assert_eq!(safe::parse_uint(&s, None), Err(safe::Errno(22)));
+    }
+
+    #[test]
+    fn unsafe_wrapper() {
+        unsafe extern "C" fn add(total: *mut c_void, x: c_uint) {
+            *(total as *mut c_uint) += x;
+        }
+        let mut total: c_uint = 0;
+        unsafe {
+            safe::for_each(
+                &[1, 2, 3],
+                Some(add),
+                &mut total as *mut c_uint as *mut c_void,
+            );
+        }
+        assert_eq!(total, 6);
+    }
+}
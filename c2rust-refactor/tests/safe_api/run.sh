@@ -0,0 +1,19 @@
+#!/bin/sh
+set -e
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor \
+    select target 'crate; desc(fn && (name("^fnv1a$") || name("^parse_uint$") || name("^for_each$")));' \; \
+    select status 'crate; desc(fn && name("^parse_uint$"));' \; \
+    safe_api 'if __status == 0 { Ok(()) } else { Err(Errno(__status)) }' \
+    -- old.rs $rustflags
+
+# The wrappers must be usable from safe Rust
+tmp=`mktemp -d`
+rustc $rustflags --test -o $tmp/safe_api old.new
+$tmp/safe_api
+rm -r $tmp
@@ -0,0 +1,8 @@
+fn add(a: i32, b: i32) -> i32 {
+    let sum = a + b;
+    sum
+}
+
+fn main() {
+    println!("{}", add(1, 2));
+}
@@ -0,0 +1,30 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+# The expr mark nests inside the stmt mark in both formats.
+$refactor \
+    select stmt 'crate; desc(match_stmt(let sum = a + b;));' \; \
+    select expr 'crate; desc(match_expr(a + b));' \; \
+    show_marks --format html --out marks.html \
+    -- old.rs $rustflags || exit 1
+for expected in \
+    '<tr><td><span class="mark label-0">expr</span></td><td>1</td><td><a href="#file-0">old.rs</a></td></tr>' \
+    '<span class="mark-label">stmt</span>let sum = <span class="mark label-0" title="expr (expr ' \
+    '<span class="mark-label">expr</span>a + b</span>;</span>'
+do
+    grep -qF "$expected" marks.html || exit 1
+done
+rm marks.html
+
+$refactor \
+    select stmt 'crate; desc(match_stmt(let sum = a + b;));' \; \
+    select expr 'crate; desc(match_expr(a + b));' \; \
+    show_marks \
+    -- old.rs $rustflags 2>/dev/null |
+    grep -qF "$(printf '\033[1;32m[stmt:\033[22mlet sum = \033[1;31m[expr:\033[22ma + b\033[1;31m]\033[0m\033[32m;\033[1;32m]\033[0m')" ||
+    exit 1
+cp old.rs old.new
@@ -0,0 +1,432 @@
+#![allow(unused_parens, unused_variables, non_camel_case_types)]
+
+//! Condition shapes produced by the translator.  `run.sh` compiles and runs both the original
+//! and the simplified program and checks that they print the same results.
+
+pub mod libc {
+    pub type c_int = i32;
+    pub type c_uint = u32;
+}
+
+fn tick(log: &mut Vec<i32>, id: i32, v: bool) -> bool {
+    log.push(id);
+    v
+}
+
+fn c01(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x != 0
+}
+
+fn c02(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x != y
+}
+
+fn c03(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x == y
+}
+
+fn c04(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x < y
+}
+
+fn c05(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x <= y
+}
+
+fn c06(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x != 0
+}
+
+fn c07(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x == 0
+}
+
+fn c08(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x >= y
+}
+
+fn c09(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    !(f < g)
+}
+
+fn c10(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    a || b
+}
+
+fn c11(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    !(a || b)
+}
+
+fn c12(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    !(a && b)
+}
+
+fn c13(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    a
+}
+
+fn c14(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    a
+}
+
+fn c15(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    !a
+}
+
+fn c16(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x <= y
+}
+
+fn c17(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x != 0
+}
+
+fn c18(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    (x & 1) != 0
+}
+
+fn c19(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    !!x != 0
+}
+
+fn c20(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    (if x != 0 { 1 } else { 0 }) == 1
+}
+
+fn c21(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    tick(log, 1, a) && !tick(log, 2, b)
+}
+
+fn c22(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    !(tick(log, 1, a) || tick(log, 2, b))
+}
+
+fn c23(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    a && b
+}
+
+fn c24(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x == 0
+}
+
+fn c25(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    x != 0
+}
+
+fn c26(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    f != g
+}
+
+fn c27(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    !(f < g)
+}
+
+fn c28(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    a
+}
+
+fn c29(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    !(!a || b)
+}
+
+fn c30(
+    x: libc::c_int,
+    y: libc::c_int,
+    a: bool,
+    b: bool,
+    f: f64,
+    g: f64,
+    log: &mut Vec<i32>,
+) -> bool {
+    (x > 0) as libc::c_int + (y > 0) as libc::c_int != 0
+}
+
+type Shape = fn(libc::c_int, libc::c_int, bool, bool, f64, f64, &mut Vec<i32>) -> bool;
+
+fn main() {
+    let shapes: [Shape; 30] = [
+        c01,
+        c02,
+        c03,
+        c04,
+        c05,
+        c06,
+        c07,
+        c08,
+        c09,
+        c10,
+        c11,
+        c12,
+        c13,
+        c14,
+        c15,
+        c16,
+        c17,
+        c18,
+        c19,
+        c20,
+        c21,
+        c22,
+        c23,
+        c24,
+        c25,
+        c26,
+        c27,
+        c28,
+        c29,
+        c30,
+    ];
+    let ints = [-2, -1, 0, 1, 2];
+    let bools = [false, true];
+    let floats = [-1.0, 0.0, ::std::f64::NAN];
+
+    for (i, shape) in shapes.iter().enumerate() {
+        for &x in &ints {
+            for &y in &ints {
+                for &a in &bools {
+                    for &b in &bools {
+                        for &f in &floats {
+                            for &g in &floats {
+                                let mut log = Vec::new();
+                                let r = shape(x, y, a, b, f, g, &mut log);
+                                println!("{} {:?} {} {:?}", i, (x, y, a, b, f, g), r, log);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
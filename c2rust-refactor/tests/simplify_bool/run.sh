@@ -0,0 +1,19 @@
+#!/bin/sh
+set -e
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor simplify_bool -- old.rs $rustflags
+
+# Both versions must evaluate every shape identically, including the order of
+# side effects
+tmp=`mktemp -d`
+rustc $rustflags --crate-name old -o $tmp/old old.rs
+rustc $rustflags --crate-name new -o $tmp/new old.new
+$tmp/old > $tmp/old.out
+$tmp/new > $tmp/new.out
+diff $tmp/old.out $tmp/new.out
+rm -r $tmp
@@ -0,0 +1,106 @@
+#![feature(rustc_private)]
+extern crate libc;
+
+extern "C" {
+    #[no_mangle]
+    fn fopen(__filename: *const libc::c_char, __modes: *const libc::c_char) -> *mut FILE;
+    #[no_mangle]
+    fn fclose(__stream: *mut FILE) -> libc::c_int;
+    #[no_mangle]
+    fn fread(__ptr: *mut libc::c_void, __size: libc::c_ulong, __n: libc::c_ulong,
+             __stream: *mut FILE) -> libc::c_ulong;
+    #[no_mangle]
+    fn fwrite(__ptr: *const libc::c_void, __size: libc::c_ulong, __n: libc::c_ulong,
+              __s: *mut FILE) -> libc::c_ulong;
+    #[no_mangle]
+    fn fputs(__s: *const libc::c_char, __stream: *mut FILE) -> libc::c_int;
+    #[no_mangle]
+    fn fflush(__stream: *mut FILE) -> libc::c_int;
+}
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct _IO_FILE {
+    pub _flags: libc::c_int,
+}
+pub type FILE = _IO_FILE;
+#[no_mangle]
+pub unsafe extern "C" fn count_bytes(mut path: *const libc::c_char) -> libc::c_long {
+    let mut f: Option<::std::fs::File> = ::std::fs::OpenOptions::new()
+        .read(true)
+        .open(::std::ffi::CStr::from_ptr(path).to_str().unwrap())
+        .ok();
+    if f.is_none() {
+        return -(1 as libc::c_int) as libc::c_long;
+    }
+    let mut buf: [libc::c_char; 64] = [0; 64];
+    let mut total: libc::c_long = 0 as libc::c_int as libc::c_long;
+    loop {
+        let mut n: libc::c_ulong = ::std::io::Read::read(
+            f.as_mut().unwrap(),
+            ::std::slice::from_raw_parts_mut(
+                buf.as_mut_ptr() as *mut u8,
+                (1 as libc::c_int as libc::c_ulong
+                    * ::std::mem::size_of::<[libc::c_char; 64]>() as libc::c_ulong)
+                    as usize,
+            ),
+        )
+        .unwrap_or(0) as u64;
+        if n == 0 as libc::c_int as libc::c_ulong {
+            break;
+        }
+        total = (total as libc::c_ulong).wrapping_add(n) as libc::c_long as libc::c_long
+    }
+    f = None;
+    return total;
+}
+#[no_mangle]
+pub unsafe extern "C" fn write_values(mut values: *const libc::c_int,
+                                      mut n: libc::c_ulong) -> libc::c_int {
+    let mut out: Option<::std::fs::File> = None;
+    out = ::std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open("values.bin")
+        .ok();
+    if out.is_none() {
+        return -(1 as libc::c_int);
+    }
+    if ::std::io::Write::write_all(
+        out.as_mut().unwrap(),
+        ::std::ffi::CStr::from_ptr(b"VALS\0" as *const u8 as *const libc::c_char).to_bytes(),
+    )
+    .is_ok()
+    {
+        0 as i32
+    } else {
+        -1 as i32
+    };
+    let mut written: libc::c_ulong = if ::std::io::Write::write_all(
+        out.as_mut().unwrap(),
+        ::std::slice::from_raw_parts(
+            values as *const u8,
+            (::std::mem::size_of::<libc::c_int>() as libc::c_ulong * n) as usize,
+        ),
+    )
+    .is_ok()
+    {
+        n
+    } else {
+        0 as u64
+    };
+    out = None;
+    return (written == n) as libc::c_int;
+}
+#[no_mangle]
+pub unsafe extern "C" fn log_line(mut msg: *const libc::c_char) {
+    let mut log: *mut FILE = fopen(b"log.txt\0" as *const u8 as *const libc::c_char,
+                                   b"a\0" as *const u8 as *const libc::c_char);
+    if log.is_null() {
+        return;
+    }
+    fputs(msg, log);
+    fflush(log);
+    fclose(log);
+}
+fn main() {}
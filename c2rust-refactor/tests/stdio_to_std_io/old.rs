@@ -0,0 +1,79 @@
+#![feature(rustc_private)]
+extern crate libc;
+
+extern "C" {
+    #[no_mangle]
+    fn fopen(__filename: *const libc::c_char, __modes: *const libc::c_char) -> *mut FILE;
+    #[no_mangle]
+    fn fclose(__stream: *mut FILE) -> libc::c_int;
+    #[no_mangle]
+    fn fread(__ptr: *mut libc::c_void, __size: libc::c_ulong, __n: libc::c_ulong,
+             __stream: *mut FILE) -> libc::c_ulong;
+    #[no_mangle]
+    fn fwrite(__ptr: *const libc::c_void, __size: libc::c_ulong, __n: libc::c_ulong,
+              __s: *mut FILE) -> libc::c_ulong;
+    #[no_mangle]
+    fn fputs(__s: *const libc::c_char, __stream: *mut FILE) -> libc::c_int;
+    #[no_mangle]
+    fn fflush(__stream: *mut FILE) -> libc::c_int;
+}
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct _IO_FILE {
+    pub _flags: libc::c_int,
+}
+pub type FILE = _IO_FILE;
+#[no_mangle]
+pub unsafe extern "C" fn count_bytes(mut path: *const libc::c_char) -> libc::c_long {
+    let mut f: *mut FILE = fopen(path, b"rb\0" as *const u8 as *const libc::c_char);
+    if f.is_null() {
+        return -(1 as libc::c_int) as libc::c_long;
+    }
+    let mut buf: [libc::c_char; 64] = [0; 64];
+    let mut total: libc::c_long = 0 as libc::c_int as libc::c_long;
+    loop {
+        let mut n: libc::c_ulong = fread(
+            buf.as_mut_ptr() as *mut libc::c_void,
+            1 as libc::c_int as libc::c_ulong,
+            ::std::mem::size_of::<[libc::c_char; 64]>() as libc::c_ulong,
+            f,
+        );
+        if n == 0 as libc::c_int as libc::c_ulong {
+            break;
+        }
+        total = (total as libc::c_ulong).wrapping_add(n) as libc::c_long as libc::c_long
+    }
+    fclose(f);
+    return total;
+}
+#[no_mangle]
+pub unsafe extern "C" fn write_values(mut values: *const libc::c_int,
+                                      mut n: libc::c_ulong) -> libc::c_int {
+    let mut out: *mut FILE = 0 as *mut FILE;
+    out = fopen(b"values.bin\0" as *const u8 as *const libc::c_char,
+                b"w\0" as *const u8 as *const libc::c_char);
+    if out.is_null() {
+        return -(1 as libc::c_int);
+    }
+    fputs(b"VALS\0" as *const u8 as *const libc::c_char, out);
+    let mut written: libc::c_ulong = fwrite(
+        values as *const libc::c_void,
+        ::std::mem::size_of::<libc::c_int>() as libc::c_ulong,
+        n,
+        out,
+    );
+    fclose(out);
+    return (written == n) as libc::c_int;
+}
+#[no_mangle]
+pub unsafe extern "C" fn log_line(mut msg: *const libc::c_char) {
+    let mut log: *mut FILE = fopen(b"log.txt\0" as *const u8 as *const libc::c_char,
+                                   b"a\0" as *const u8 as *const libc::c_char);
+    if log.is_null() {
+        return;
+    }
+    fputs(msg, log);
+    fflush(log);
+    fclose(log);
+}
+fn main() {}
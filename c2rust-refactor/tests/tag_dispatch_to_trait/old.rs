@@ -0,0 +1,97 @@
+// Test that `switch`es on the kind of a shape, as the transpiler emits them, turn into the
+// methods of a trait implemented for each kind.
+
+#![allow(non_camel_case_types, unused_mut)]
+
+pub type shape_kind = u32;
+pub const CIRCLE: shape_kind = 0;
+pub const RECT: shape_kind = 1;
+pub const SQUARE: shape_kind = 2;
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct shape {
+    pub kind: shape_kind,
+    pub a: f64,
+    pub b: f64,
+    pub scaled: i32,
+}
+
+pub unsafe extern "C" fn shape_area(mut s: *const shape) -> f64 {
+    match (*s).kind as u32 {
+        0 => return 3.0 * (*s).a * (*s).a,
+        1 => return (*s).a * (*s).b,
+        2 => return (*s).a * (*s).a,
+        _ => {}
+    }
+    return 0.0;
+}
+
+pub unsafe extern "C" fn shape_corners(mut s: *const shape) -> i32 {
+    match (*s).kind as u32 {
+        1 | 2 => 4,
+        _ => 0,
+    }
+}
+
+pub unsafe extern "C" fn shape_scale(mut s: *mut shape, mut k: f64) {
+    match (*s).kind as u32 {
+        0 => (*s).a *= k,
+        1 => {
+            (*s).a *= k;
+            (*s).b *= k
+        }
+        _ => {}
+    }
+    (*s).scaled += 1;
+}
+
+// Doesn't start with the `match`, so it's left alone
+pub unsafe extern "C" fn shape_is_round(mut s: *const shape) -> bool {
+    let mut kind = (*s).kind;
+    match kind {
+        0 => true,
+        _ => false,
+    }
+}
+
+fn main() {
+    let mut shapes = [
+        shape {
+            kind: CIRCLE,
+            a: 1.0,
+            b: 0.0,
+            scaled: 0,
+        },
+        shape {
+            kind: RECT,
+            a: 2.0,
+            b: 3.0,
+            scaled: 0,
+        },
+        shape {
+            kind: SQUARE,
+            a: 2.0,
+            b: 0.0,
+            scaled: 0,
+        },
+        shape {
+            kind: 7,
+            a: 1.0,
+            b: 1.0,
+            scaled: 0,
+        },
+    ];
+    for s in shapes.iter_mut() {
+        unsafe {
+            shape_scale(s, 2.0);
+            println!(
+                "{} {} {} {}",
+                shape_area(s),
+                shape_corners(s),
+                shape_is_round(s),
+                s.scaled,
+            );
+        }
+    }
+}
@@ -0,0 +1,21 @@
+#!/bin/sh
+set -e
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor \
+    select target 'crate; desc(field && name("^kind$"));' \; \
+    tag_dispatch_to_trait ShapeOps Circle=0 Rect=1 Square=2 \
+    -- old.rs $rustflags
+
+# Dispatching through the trait must give the same results as the `match`es
+tmp=`mktemp -d`
+rustc $rustflags --crate-name old -o $tmp/old old.rs
+rustc $rustflags --crate-name new -o $tmp/new old.new
+$tmp/old > $tmp/old.out
+$tmp/new > $tmp/new.out
+diff $tmp/old.out $tmp/new.out
+rm -r $tmp
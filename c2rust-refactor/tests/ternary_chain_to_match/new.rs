@@ -0,0 +1,106 @@
+const OP_ADD: i32 = 2;
+const OP_SUB: i32 = 3;
+
+static mut COUNTER: i32 = 0;
+
+unsafe fn next_op() -> i32 {
+    COUNTER += 1;
+    return COUNTER;
+}
+
+pub unsafe extern "C" fn op_weight(mut op: i32) -> i32 {
+    return match op {
+        0 => 10,
+        1 => 20,
+        -1 => 30,
+        _ => -(1),
+    };
+}
+
+pub unsafe extern "C" fn apply(mut op: i32, mut a: i32, mut b: i32) -> i32 {
+    return match op {
+        OP_ADD => a + b,
+        OP_SUB => {
+            let mut d: i32 = a - b;
+            d
+        }
+        _ => 0,
+    };
+}
+
+pub unsafe extern "C" fn digit_value(mut c: u8) -> i32 {
+    return match c {
+        b'0' => 0,
+        b'1' => 1,
+        50 => 2,
+        _ => -(1),
+    };
+}
+
+// Not rewritten: the conditions compare different values
+pub unsafe extern "C" fn either(mut a: i32, mut b: i32) -> i32 {
+    return if a == 1 {
+        1
+    } else if b == 2 {
+        2
+    } else {
+        0
+    };
+}
+
+// Not rewritten: the second condition isn't an equality
+pub unsafe extern "C" fn bucket(mut n: i32) -> i32 {
+    return if n == 0 {
+        0
+    } else if n < 10 {
+        1
+    } else {
+        2
+    };
+}
+
+// Not rewritten: the scrutinee has side effects
+pub unsafe extern "C" fn poll() -> i32 {
+    return if next_op() == 1 {
+        1
+    } else if next_op() == 2 {
+        2
+    } else {
+        0
+    };
+}
+
+// Not rewritten: the cast changes the value of the constant
+pub unsafe extern "C" fn wrapped(mut c: u8) -> i32 {
+    return if c == 300i32 as u8 {
+        1
+    } else if c == 1 {
+        2
+    } else {
+        0
+    };
+}
+
+// Not rewritten: a single comparison
+pub unsafe extern "C" fn is_zero(mut n: i32) -> i32 {
+    return if n == 0 { 1 } else { 0 };
+}
+
+fn main() {
+    unsafe {
+        println!(
+            "{} {} {}",
+            op_weight(1),
+            apply(OP_SUB, 5, 3),
+            digit_value(b'2')
+        );
+        println!(
+            "{} {} {} {} {}",
+            either(1, 2),
+            bucket(5),
+            poll(),
+            wrapped(44),
+            is_zero(0)
+        );
+    }
+}
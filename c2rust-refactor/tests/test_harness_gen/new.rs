@@ -0,0 +1,128 @@
+#![feature(rustc_private)]
+extern crate libc;
+
+#[no_mangle]
+pub unsafe extern "C" fn clamp(
+    mut x: libc::c_int,
+    mut lo: libc::c_int,
+    mut hi: libc::c_int,
+) -> libc::c_int {
+    if x < lo {
+        return lo;
+    }
+    if x > hi {
+        return hi;
+    }
+    return x;
+}
+#[no_mangle]
+pub unsafe extern "C" fn scale(
+    mut n: libc::c_uint,
+    mut buf: *mut libc::c_short,
+    mut factor: libc::c_int,
+) -> libc::c_long {
+    let mut total: libc::c_long = 0 as libc::c_int as libc::c_long;
+    let mut i: libc::c_uint = 0 as libc::c_int as libc::c_uint;
+    while i < n {
+        *buf.offset(i as isize) =
+            (*buf.offset(i as isize) as libc::c_int * factor) as libc::c_short;
+        total += *buf.offset(i as isize) as libc::c_long;
+        i = i.wrapping_add(1)
+    }
+    return total;
+}
+#[no_mangle]
+pub unsafe extern "C" fn apply(
+    mut f: Option<unsafe extern "C" fn(_: libc::c_int) -> libc::c_int>,
+    mut x: libc::c_int,
+) -> libc::c_int {
+    return f.expect("non-null function pointer")(x);
+}
+fn main() {}
+
+#[cfg(test)]
+mod test_harness {
+    use super::*;
+
+    extern "C" {
+        #[link_name = "c_clamp"]
+        fn clamp(x: libc::c_int, lo: libc::c_int, hi: libc::c_int) -> libc::c_int;
+        #[link_name = "c_scale"]
+        fn scale(n: libc::c_uint, buf: *mut libc::c_short, factor: libc::c_int) -> libc::c_long;
+    }
+
+    const BUFFER_LEN: usize = 16;
+
+    /// A fixed pseudo-random sequence for buffer `buf` of test case `case`, ending in a zero so
+    /// that buffers of characters are also valid C strings.
+    fn buffer_values(case: usize, buf: usize) -> [u32; BUFFER_LEN] {
+        let mut state = (case * 31 + buf) as u32;
+        let mut values = [0; BUFFER_LEN];
+        for value in values[..BUFFER_LEN - 1].iter_mut() {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            *value = state >> 16;
+        }
+        values
+    }
+
+    #[test]
+    fn test_clamp() {
+        let x_values: &[libc::c_int] =
+            &[libc::c_int::min_value(), -1, 0, 1, libc::c_int::max_value()];
+        let lo_values: &[libc::c_int] =
+            &[libc::c_int::min_value(), -1, 0, 1, libc::c_int::max_value()];
+        let hi_values: &[libc::c_int] =
+            &[libc::c_int::min_value(), -1, 0, 1, libc::c_int::max_value()];
+        let cases = x_values.len() * lo_values.len() * hi_values.len();
+        for case in 0..cases {
+            let x = x_values[case % x_values.len()];
+            let lo = lo_values[case / x_values.len() % lo_values.len()];
+            let hi = hi_values[case / (x_values.len() * lo_values.len()) % hi_values.len()];
+            let c_result = unsafe { clamp(x, lo, hi) };
+            let rust_result = unsafe { super::clamp(x, lo, hi) };
+            assert_eq!(
+                c_result, rust_result,
+                "case {}: x = {}, lo = {}, hi = {}",
+                case, x, lo, hi
+            );
+        }
+    }
+
+    #[test]
+    fn test_scale() {
+        let n_values: &[libc::c_uint] = &[
+            0,
+            1,
+            (BUFFER_LEN / 2) as libc::c_uint,
+            BUFFER_LEN as libc::c_uint,
+        ];
+        let factor_values: &[libc::c_int] = &[
+            0,
+            1,
+            (BUFFER_LEN / 2) as libc::c_int,
+            BUFFER_LEN as libc::c_int,
+        ];
+        let cases = n_values.len() * factor_values.len();
+        for case in 0..cases {
+            let n = n_values[case % n_values.len()];
+            let factor = factor_values[case / n_values.len() % factor_values.len()];
+            let mut c_buf = [0 as libc::c_short; BUFFER_LEN];
+            for (elem, value) in c_buf.iter_mut().zip(buffer_values(case, 1).iter()) {
+                *elem = *value as libc::c_short;
+            }
+            let mut rust_buf = c_buf;
+            let c_result = unsafe { scale(n, c_buf.as_mut_ptr(), factor) };
+            let rust_result = unsafe { super::scale(n, rust_buf.as_mut_ptr(), factor) };
+            assert_eq!(
+                c_result, rust_result,
+                "case {}: n = {}, factor = {}",
+                case, n, factor
+            );
+            assert_eq!(
+                c_buf, rust_buf,
+                "case {}: n = {}, factor = {}",
+                case, n, factor
+            );
+        }
+    }
+}
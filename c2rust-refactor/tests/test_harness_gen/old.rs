@@ -0,0 +1,41 @@
+#![feature(rustc_private)]
+extern crate libc;
+
+#[no_mangle]
+pub unsafe extern "C" fn clamp(
+    mut x: libc::c_int,
+    mut lo: libc::c_int,
+    mut hi: libc::c_int,
+) -> libc::c_int {
+    if x < lo {
+        return lo;
+    }
+    if x > hi {
+        return hi;
+    }
+    return x;
+}
+#[no_mangle]
+pub unsafe extern "C" fn scale(
+    mut n: libc::c_uint,
+    mut buf: *mut libc::c_short,
+    mut factor: libc::c_int,
+) -> libc::c_long {
+    let mut total: libc::c_long = 0 as libc::c_int as libc::c_long;
+    let mut i: libc::c_uint = 0 as libc::c_int as libc::c_uint;
+    while i < n {
+        *buf.offset(i as isize) =
+            (*buf.offset(i as isize) as libc::c_int * factor) as libc::c_short;
+        total += *buf.offset(i as isize) as libc::c_long;
+        i = i.wrapping_add(1)
+    }
+    return total;
+}
+#[no_mangle]
+pub unsafe extern "C" fn apply(
+    mut f: Option<unsafe extern "C" fn(_: libc::c_int) -> libc::c_int>,
+    mut x: libc::c_int,
+) -> libc::c_int {
+    return f.expect("non-null function pointer")(x);
+}
+fn main() {}
@@ -0,0 +1,20 @@
+#!/bin/sh
+set -e
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+$refactor \
+    select target 'crate; desc(fn && (name("^clamp$") || name("^scale$") || name("^apply$")));' \; \
+    test_harness_gen \
+    -- old.rs $rustflags
+
+# The generated tests must pass against the C originals
+tmp=`mktemp -d`
+cc -c stub.c -o $tmp/stub.o
+ar rcs $tmp/libstub.a $tmp/stub.o
+rustc $rustflags --test -o $tmp/harness old.new -L $tmp -l static=stub
+$tmp/harness
+rm -r $tmp
@@ -0,0 +1,17 @@
+/* The original C functions, with their symbols renamed as by
+ * `objcopy --prefix-symbols=c_` so they can be linked next to the translation */
+
+int c_clamp(int x, int lo, int hi) {
+  if (x < lo) return lo;
+  if (x > hi) return hi;
+  return x;
+}
+
+long c_scale(unsigned n, short *buf, int factor) {
+  long total = 0;
+  for (unsigned i = 0; i < n; i++) {
+    buf[i] = buf[i] * factor;
+    total += buf[i];
+  }
+  return total;
+}
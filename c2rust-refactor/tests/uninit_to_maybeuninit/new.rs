@@ -0,0 +1,68 @@
+use std::mem;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct stat {
+    pub st_size: i64,
+    pub st_mode: u32,
+}
+
+#[derive(Copy, Clone)]
+pub struct pair {
+    pub a: i32,
+    pub b: f64,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct point {
+    pub x: i32,
+    pub y: i32,
+}
+
+extern "C" {
+    fn fstat(fd: i32, buf: *mut stat) -> i32;
+}
+
+unsafe fn file_size(fd: i32) -> i64 {
+    let mut st: ::std::mem::MaybeUninit<stat> = ::std::mem::MaybeUninit::uninit();
+    if fstat(fd, st.as_mut_ptr()) != 0 {
+        return -1;
+    }
+    let mut st: stat = st.assume_init();
+    st.st_size
+}
+
+unsafe fn scratch() -> i32 {
+    let mut tmp: i32 = 0i32;
+    let mut p: *mut u8 = ::std::ptr::null_mut();
+    let mut buf: [u8; 16] = [0u8; 16usize];
+    let mut pt: point = Default::default();
+    tmp = 3;
+    p = buf.as_mut_ptr();
+    *p = 1;
+    pt.x = tmp;
+    tmp + buf[0] as i32 + pt.x
+}
+
+unsafe fn fields() -> f64 {
+    let mut v: ::std::mem::MaybeUninit<pair> = ::std::mem::MaybeUninit::uninit();
+    (*v.as_mut_ptr()).a = 1;
+    (*v.as_mut_ptr()).b = 2.0;
+    let mut v: pair = v.assume_init();
+    v.b + v.a as f64
+}
+
+unsafe fn incomplete() -> i32 {
+    let mut v: pair = ::std::mem::MaybeUninit::uninit().assume_init();
+    v.a = 1;
+    v.a
+}
+
+fn main() {
+    unsafe {
+        file_size(0);
+        scratch();
+        fields();
+        incomplete();
+    }
+}
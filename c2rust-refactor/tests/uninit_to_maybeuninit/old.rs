@@ -0,0 +1,66 @@
+use std::mem;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct stat {
+    pub st_size: i64,
+    pub st_mode: u32,
+}
+
+#[derive(Copy, Clone)]
+pub struct pair {
+    pub a: i32,
+    pub b: f64,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct point {
+    pub x: i32,
+    pub y: i32,
+}
+
+extern "C" {
+    fn fstat(fd: i32, buf: *mut stat) -> i32;
+}
+
+unsafe fn file_size(fd: i32) -> i64 {
+    let mut st: stat = mem::uninitialized();
+    if fstat(fd, &mut st) != 0 {
+        return -1;
+    }
+    st.st_size
+}
+
+unsafe fn scratch() -> i32 {
+    let mut tmp: i32 = mem::uninitialized();
+    let mut p: *mut u8 = mem::uninitialized();
+    let mut buf: [u8; 16] = mem::uninitialized();
+    let mut pt: point = mem::uninitialized();
+    tmp = 3;
+    p = buf.as_mut_ptr();
+    *p = 1;
+    pt.x = tmp;
+    tmp + buf[0] as i32 + pt.x
+}
+
+unsafe fn fields() -> f64 {
+    let mut v: pair = mem::uninitialized();
+    v.a = 1;
+    v.b = 2.0;
+    v.b + v.a as f64
+}
+
+unsafe fn incomplete() -> i32 {
+    let mut v: pair = mem::uninitialized();
+    v.a = 1;
+    v.a
+}
+
+fn main() {
+    unsafe {
+        file_size(0);
+        scratch();
+        fields();
+        incomplete();
+    }
+}
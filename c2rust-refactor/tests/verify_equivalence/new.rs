@@ -0,0 +1,8 @@
+fn double(x: i32) -> i32 {
+    x * 2
+}
+
+fn main() {
+    let a = 2;
+    println!("{}", double(a));
+}
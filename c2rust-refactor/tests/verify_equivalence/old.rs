@@ -0,0 +1,8 @@
+fn double(x: i32) -> i32 {
+    x * 2
+}
+
+fn main() {
+    let a = 1 + 1;
+    println!("{}", double(a));
+}
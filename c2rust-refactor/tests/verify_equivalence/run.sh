@@ -0,0 +1,21 @@
+#!/bin/sh
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+# The second command introduces a type error, which must be attributed to it.  The crate is
+# then saved as it was after the first command.
+if $refactor \
+    --checkpoint-cmd "rustc $rustflags --emit metadata --out-dir out old.rs" \
+    rewrite_expr '1 + 1' '2' \; \
+    rewrite_expr 'x * 2' 'x * 2.0' \
+    -- old.rs $rustflags 2>verify.log
+then
+    echo "checkpoint did not fail"
+    exit 1
+fi
+grep -q 'fails after command 2 of 2: rewrite_expr x \* 2 x \* 2.0' verify.log
+grep -q 'E0277' verify.log
+rm verify.log
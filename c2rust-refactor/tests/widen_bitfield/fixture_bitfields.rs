@@ -0,0 +1,138 @@
+//! A stand-in for `c2rust_bitfields::BitfieldStruct` for the `widen_bitfield` test, which
+//! derives the same accessors without depending on `syn` or `quote`.
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+/// The `name`, `ty` and `bits` of a `#[bitfield]` attribute, and the field storing it
+struct Bitfield {
+    storage: String,
+    name: String,
+    ty: String,
+    bits: String,
+}
+
+/// Parse the `key = "value", ...` arguments of a `#[bitfield(...)]` attribute.
+fn bitfield_args(attr: TokenStream) -> Option<Vec<(String, String)>> {
+    let mut tokens = attr.into_iter();
+    match tokens.next() {
+        Some(TokenTree::Ident(ref ident)) if ident.to_string() == "bitfield" => {}
+        _ => return None,
+    }
+    let args = match tokens.next() {
+        Some(TokenTree::Group(group)) => group.stream(),
+        _ => return None,
+    };
+
+    let mut pairs = vec![];
+    let mut key = None;
+    for tt in args {
+        match tt {
+            TokenTree::Ident(ident) => key = Some(ident.to_string()),
+            TokenTree::Literal(lit) => {
+                let value = lit.to_string().trim_matches('"').to_string();
+                pairs.push((key.take()?, value));
+            }
+            _ => {}
+        }
+    }
+    Some(pairs)
+}
+
+#[proc_macro_derive(BitfieldStruct, attributes(bitfield))]
+pub fn derive_bitfield_struct(input: TokenStream) -> TokenStream {
+    let mut tokens = input.into_iter();
+    let mut name = None;
+    let mut body = None;
+    while let Some(tt) = tokens.next() {
+        match tt {
+            TokenTree::Ident(ref ident) if ident.to_string() == "struct" => {
+                name = tokens.next().map(|tt| tt.to_string());
+            }
+            TokenTree::Group(ref group) if group.delimiter() == Delimiter::Brace => {
+                body = Some(group.stream());
+            }
+            _ => {}
+        }
+    }
+    let name = name.expect("expected a struct");
+
+    // Collect the `#[bitfield]` attributes of each field, which come before its name
+    let mut bitfields = vec![];
+    let mut pending = vec![];
+    let mut prev: Option<String> = None;
+    for tt in body.expect("expected a struct with named fields") {
+        match tt {
+            TokenTree::Group(ref group) if group.delimiter() == Delimiter::Bracket => {
+                pending.extend(bitfield_args(group.stream()));
+            }
+            TokenTree::Punct(ref punct) if punct.as_char() == ':' => {
+                let storage = prev.take().expect("expected a field name");
+                for args in pending.drain(..) {
+                    let mut bf = Bitfield {
+                        storage: storage.clone(),
+                        name: String::new(),
+                        ty: String::new(),
+                        bits: String::new(),
+                    };
+                    for (key, value) in args {
+                        match &*key {
+                            "name" => bf.name = value,
+                            "ty" => bf.ty = value,
+                            "bits" => bf.bits = value,
+                            _ => {}
+                        }
+                    }
+                    if !bf.name.is_empty() {
+                        bitfields.push(bf);
+                    }
+                }
+            }
+            TokenTree::Ident(ref ident) => prev = Some(ident.to_string()),
+            _ => {}
+        }
+    }
+
+    let mut methods = String::new();
+    for bf in bitfields {
+        let mut range = bf.bits.split("..=");
+        let lo: u32 = range.next().unwrap().parse().unwrap();
+        let hi: u32 = range.next().unwrap().parse().unwrap();
+        let width = hi - lo + 1;
+        let signed = bf.ty.rsplit("::").next().unwrap().starts_with('i');
+        let extend = if signed {
+            format!("((val << {0}) as i128 >> {0}) as {1}", 128 - width, bf.ty)
+        } else {
+            format!("val as {}", bf.ty)
+        };
+        methods.push_str(&format!(
+            "pub fn {name}(&self) -> {ty} {{
+                let mut val: u128 = 0;
+                for (i, bit) in ({lo}..={hi}).enumerate() {{
+                    if self.{storage}[bit / 8] >> (bit % 8) & 1 != 0 {{
+                        val |= 1 << i;
+                    }}
+                }}
+                {extend}
+            }}
+            pub fn set_{name}(&mut self, val: {ty}) {{
+                let val = val as u128;
+                for (i, bit) in ({lo}..={hi}).enumerate() {{
+                    if val >> i & 1 != 0 {{
+                        self.{storage}[bit / 8] |= 1 << (bit % 8);
+                    }} else {{
+                        self.{storage}[bit / 8] &= !(1 << (bit % 8));
+                    }}
+                }}
+            }}",
+            name = bf.name,
+            ty = bf.ty,
+            lo = lo,
+            hi = hi,
+            storage = bf.storage,
+            extend = extend,
+        ));
+    }
+
+    format!("impl {} {{ {} }}", name, methods).parse().unwrap()
+}
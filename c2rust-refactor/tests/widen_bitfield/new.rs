@@ -0,0 +1,53 @@
+// Test that widening a bitfield keeps its users working the same for the values it held.
+
+use fixture_bitfields::BitfieldStruct;
+
+#[derive(BitfieldStruct, Clone, Copy)]
+#[repr(C)]
+pub struct flags {
+    #[bitfield(name = "mode", ty = "u8", bits = "0..=7")]
+    #[bitfield(name = "level", ty = "u8", bits = "8..=11")]
+    pub mode_level: [u8; 2],
+    pub count: i32,
+}
+
+fn make(mode: i8, level: u8) -> flags {
+    let mut init = flags {
+        mode_level: [0; 2],
+        count: 0,
+    };
+    init.set_mode(mode as u8);
+    init.set_level(level);
+    init
+}
+
+fn bump(f: &mut flags) {
+    if (f.mode() as i8) < 3 {
+        f.set_mode((f.mode() as i8 + 1) as u8);
+    }
+    f.count += 1;
+}
+
+fn is_negative(m: i8) -> bool {
+    m < 0
+}
+
+fn describe(f: &flags) -> i32 {
+    f.mode() as i8 as i32 * 100 + f.level() as i32
+}
+
+fn main() {
+    for mode in -4..=3 {
+        for &level in &[0, 5, 15] {
+            let mut f = make(mode, level);
+            println!(
+                "{} {} {}",
+                describe(&f),
+                is_negative(f.mode() as i8),
+                f.level()
+            );
+            bump(&mut f);
+            println!("{} {} {}", describe(&f), f.mode() as i8, f.count);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// Test that widening a bitfield keeps its users working the same for the values it held.
+
+use fixture_bitfields::BitfieldStruct;
+
+#[derive(BitfieldStruct, Clone, Copy)]
+#[repr(C)]
+pub struct flags {
+    #[bitfield(name = "mode", ty = "i8", bits = "0..=2")]
+    #[bitfield(name = "level", ty = "u8", bits = "3..=6")]
+    pub mode_level: [u8; 1],
+    pub count: i32,
+}
+
+fn make(mode: i8, level: u8) -> flags {
+    let mut init = flags {
+        mode_level: [0; 1],
+        count: 0,
+    };
+    init.set_mode(mode);
+    init.set_level(level);
+    init
+}
+
+fn bump(f: &mut flags) {
+    if f.mode() < 3 {
+        f.set_mode(f.mode() + 1);
+    }
+    f.count += 1;
+}
+
+fn is_negative(m: i8) -> bool {
+    m < 0
+}
+
+fn describe(f: &flags) -> i32 {
+    f.mode() as i32 * 100 + f.level() as i32
+}
+
+fn main() {
+    for mode in -4..=3 {
+        for &level in &[0, 5, 15] {
+            let mut f = make(mode, level);
+            println!("{} {} {}", describe(&f), is_negative(f.mode()), f.level());
+            bump(&mut f);
+            println!("{} {} {}", describe(&f), f.mode(), f.count);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+#!/bin/sh
+set -e
+
+# work around System Integrity Protection on macOS
+if [ `uname` = 'Darwin' ]; then
+    export LD_LIBRARY_PATH=$not_LD_LIBRARY_PATH
+fi
+
+tmp=`mktemp -d`
+rustc --crate-type proc-macro --edition 2018 --out-dir $tmp fixture_bitfields.rs
+bitfields_lib=$(ls $tmp/*fixture_bitfields.*)
+
+$refactor widen_bitfield flags mode u8 \
+    -- old.rs $rustflags --extern fixture_bitfields=$bitfields_lib
+
+# The widened bitfield must behave the same for all the values the old one held
+rustc $rustflags --extern fixture_bitfields=$bitfields_lib --crate-name old -o $tmp/old old.rs
+rustc $rustflags --extern fixture_bitfields=$bitfields_lib --crate-name new -o $tmp/new old.new
+$tmp/old > $tmp/old.out
+$tmp/new > $tmp/new.out
+diff $tmp/old.out $tmp/new.out
+rm -r $tmp
@@ -0,0 +1,24 @@
+use std::mem;
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct point {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct node {
+    pub value: i32,
+    pub next: *mut node,
+}
+
+fn main() {
+    let mut p: point = unsafe { ::std::mem::zeroed() };
+    let q = unsafe { mem::zeroed::<point>() };
+    let mut n: node = unsafe { mem::zeroed() };
+    let buf: [u8; 16] = unsafe { mem::zeroed() };
+    p.x = q.y + buf[0] as i32;
+    n.value = p.x;
+}
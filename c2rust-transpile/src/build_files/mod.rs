@@ -65,6 +65,9 @@ pub struct CrateConfig<'lcmd> {
     pub pragmas: PragmaSet,
     pub crates: CrateSet,
     pub link_cmd: &'lcmd LinkCmd,
+    /// Whether statements of the crate were moved into C shims in `c2rust_shims.c`, which
+    /// `build.rs` compiles
+    pub shims: bool,
 }
 
 /// Emit `Cargo.toml` and `lib.rs` for a library or `main.rs` for a binary.
@@ -97,7 +100,7 @@ pub fn emit_build_files<'lcmd>(
         emit_rust_toolchain(tcfg, &build_dir);
     }
     crate_cfg.and_then(|ccfg| {
-        emit_build_rs(tcfg, &reg, &build_dir, ccfg.link_cmd);
+        emit_build_rs(tcfg, &reg, &build_dir, ccfg.link_cmd, ccfg.shims);
         emit_lib_rs(tcfg, &reg, &build_dir, ccfg.modules, ccfg.pragmas, &ccfg.crates)
     })
 }
@@ -184,6 +187,26 @@ fn convert_module_list(
     res
 }
 
+/// Get the path that `lib.rs` makes `module` available at, e.g., `crate::src::foo`, or `None`
+/// if it is a binary module that isn't part of the library.
+pub fn get_module_path(tcfg: &TranspilerConfig, build_dir: &Path, module: &Path) -> Option<Vec<String>> {
+    if tcfg.is_binary(module) {
+        return None;
+    }
+
+    // This mirrors the module tree built by `convert_module_list`
+    let mut path = vec!["crate".to_string()];
+    match module.strip_prefix(build_dir) {
+        Ok(relpath) => {
+            for sm in relpath.iter() {
+                path.push(get_module_name(Path::new(sm), true, false, false).unwrap());
+            }
+        }
+        Err(_) => path.push(get_module_name(module, true, false, false).unwrap()),
+    }
+    Some(path)
+}
+
 fn convert_dependencies_list(crates: CrateSet) -> Vec<ExternCrateDetails> {
     crates.into_iter().map(|dep| dep.into()).collect()
 }
@@ -196,15 +219,17 @@ fn get_lib_rs_file_name(tcfg: &TranspilerConfig) -> &str {
     }
 }
 
-/// Emit `build.rs` to make it easier to link in native libraries
+/// Emit `build.rs` to make it easier to link in native libraries, and to compile the C shims
 fn emit_build_rs(
     tcfg: &TranspilerConfig,
     reg: &Handlebars,
     build_dir: &Path,
     link_cmd: &LinkCmd,
+    shims: bool,
 ) -> Option<PathBuf> {
     let json = json!({
         "libraries": link_cmd.libs,
+        "shims": shims,
     });
     let output = reg.render("build.rs", &json).unwrap();
     let output_path = build_dir.join("build.rs");
@@ -285,6 +310,7 @@ fn emit_cargo_toml<'lcmd>(
             "cross_checks": tcfg.cross_checks,
             "cross_check_backend": tcfg.cross_check_backend,
             "dependencies": dependencies,
+            "shims": ccfg.shims,
         });
         json.as_object_mut()
             .unwrap()
@@ -130,6 +130,8 @@ fn not_located<T>(t: T) -> Located<T> {
 fn parse_cast_kind(kind: &str) -> CastKind {
     match kind {
         "BitCast" => CastKind::BitCast,
+        // Block pointers are translated to `void` pointers
+        "AnyPointerToBlockPointerCast" => CastKind::BitCast,
         "LValueToRValue" => CastKind::LValueToRValue,
         "NoOp" => CastKind::NoOp,
         "ToUnion" => CastKind::ToUnion,
@@ -168,6 +170,9 @@ fn parse_attributes(attributes: &[Value]) -> IndexSet<Attribute> {
     let mut expect_section_value = false;
     let mut expect_alias_value = false;
     let mut expect_visibility_value = false;
+    let mut expect_aligned_value = false;
+    let mut expect_deprecated_value = false;
+    let mut expect_cleanup_value = false;
 
     for attr in attributes {
         let attr_str = attr
@@ -176,13 +181,27 @@ fn parse_attributes(attributes: &[Value]) -> IndexSet<Attribute> {
             .as_str();
 
         match attr_str {
+            // The message could be spelled like any attribute
+            s if expect_deprecated_value => {
+                let message = if s.is_empty() { None } else { Some(s.into()) };
+                attrs.insert(Attribute::Deprecated(message));
+
+                expect_deprecated_value = false;
+            }
+            s if expect_cleanup_value => {
+                attrs.insert(Attribute::Cleanup(s.into()));
+
+                expect_cleanup_value = false;
+            }
             "alias" => expect_alias_value = true,
             "always_inline" => {
                 attrs.insert(Attribute::AlwaysInline);
             }
+            "cleanup" => expect_cleanup_value = true,
             "cold" => {
                 attrs.insert(Attribute::Cold);
             }
+            "deprecated" => expect_deprecated_value = true,
             "gnu_inline" => {
                 attrs.insert(Attribute::GnuInline);
             }
@@ -193,6 +212,7 @@ fn parse_attributes(attributes: &[Value]) -> IndexSet<Attribute> {
                 attrs.insert(Attribute::Used);
             },
             "visibility" => expect_visibility_value = true,
+            "aligned" | "_Alignas" | "alignas" => expect_aligned_value = true,
             "section" => expect_section_value = true,
             s if expect_section_value => {
                 attrs.insert(Attribute::Section(s.into()));
@@ -209,6 +229,13 @@ fn parse_attributes(attributes: &[Value]) -> IndexSet<Attribute> {
 
                 expect_visibility_value = false;
             }
+            s if expect_aligned_value => {
+                if let Ok(align) = s.parse() {
+                    attrs.insert(Attribute::Aligned(align));
+                }
+
+                expect_aligned_value = false;
+            }
             _ => {}
         }
     }
@@ -569,6 +596,17 @@ impl ConversionContext {
                     self.processed_nodes.insert(new_id, OTHER_TYPE);
                 }
 
+                // Reported when a declaration using the type is translated
+                TypeTag::TagTypeUnknown if expected_ty & OTHER_TYPE != 0 => {
+                    let name = ty_node
+                        .extras
+                        .get(0)
+                        .and_then(|name| name.as_string())
+                        .map_or_else(|| "<unknown>".to_string(), |name| name.to_owned());
+                    self.add_type(new_id, not_located(CTypeKind::UnsupportedBuiltin(name)));
+                    self.processed_nodes.insert(new_id, OTHER_TYPE);
+                }
+
                 TypeTag::TagInt128 if expected_ty & OTHER_TYPE != 0 => {
                     self.add_type(new_id, not_located(CTypeKind::Int128));
                     self.processed_nodes.insert(new_id, OTHER_TYPE);
@@ -608,6 +646,15 @@ impl ConversionContext {
                     self.processed_nodes.insert(new_id, OTHER_TYPE);
                 }
 
+                TypeTag::TagBitIntType if expected_ty & OTHER_TYPE != 0 => {
+                    let is_unsigned = ty_node.extras[0].as_boolean().expect("BitInt signedness not found");
+                    let width = ty_node.extras[1].as_u64().expect("BitInt width not found");
+
+                    let bit_int_ty = CTypeKind::BitInt(!is_unsigned, width);
+                    self.add_type(new_id, not_located(bit_int_ty));
+                    self.processed_nodes.insert(new_id, OTHER_TYPE);
+                }
+
                 TypeTag::TagComplexType if expected_ty & OTHER_TYPE != 0 => {
                     let subelt = ty_node.extras[0].as_u64().expect("Complex child not found");
                     let subelt_new = self.visit_type(subelt);
@@ -1012,9 +1059,10 @@ impl ConversionContext {
                     let raw_inputs = node.extras[2].as_array().expect("input constraints array");
                     let raw_outputs = node.extras[3].as_array().expect("output constraints array");
                     let raw_clobbers = node.extras[4].as_array().expect("clobber array");
+                    let goto_supported = node.extras[5].as_boolean().expect("asm goto flag");
 
-                    let (input_children, output_children) =
-                        node.children.split_at(raw_inputs.len());
+                    let (input_children, rest) = node.children.split_at(raw_inputs.len());
+                    let (output_children, label_children) = rest.split_at(raw_outputs.len());
 
                     let inputs: Vec<AsmOperand> = raw_inputs
                         .iter()
@@ -1047,12 +1095,22 @@ impl ConversionContext {
                         .map(|c| c.as_string().expect("clobber string").to_owned())
                         .collect();
 
+                    let labels: Vec<CLabelId> = label_children
+                        .iter()
+                        .map(|l| {
+                            let label = l.expect("asm goto label not found");
+                            CStmtId(self.visit_node_type(label, LABEL_STMT))
+                        })
+                        .collect();
+
                     let stmt = CStmtKind::Asm {
                         is_volatile,
                         asm,
                         inputs,
                         outputs,
                         clobbers,
+                        labels,
+                        goto_supported,
                     };
                     self.add_stmt(new_id, located(node, stmt));
                     self.processed_nodes.insert(new_id, OTHER_STMT);
@@ -1069,13 +1127,33 @@ impl ConversionContext {
                     self.expr_possibly_as_stmt(expected_ty, new_id, node, expr);
                 }
 
+                ASTEntryTag::TagGenericSelectionExpr if expected_ty & (EXPR | STMT) != 0 => {
+                    let selected = node.children[0].expect("Expected selected generic association");
+                    let ty_old = node.type_id.expect("Expected expression to have type");
+                    let ty = self.visit_qualified_type(ty_old);
+
+                    let expr = CExprKind::GenericSelection(ty, self.visit_expr(selected));
+
+                    self.expr_possibly_as_stmt(expected_ty, new_id, node, expr);
+                }
+
                 ASTEntryTag::TagOffsetOfExpr if expected_ty & (EXPR | STMT) != 0 => {
                     let ty_old = node.type_id.expect("Expected expression to have type");
                     let ty = self.visit_qualified_type(ty_old);
                     // Either we're able to evaluate the offsetof to an int constant expr
                     // or else we have to use the offset_of! macro from the memoffset crate
                     let offset_of = if let Some(value) = node.extras[0].as_u64() {
-                        let kind = OffsetOfKind::Constant(value);
+                        let kind = match node.extras.get(1).and_then(|qty| qty.as_u64()) {
+                            Some(qty_int) => {
+                                let qty = self.visit_qualified_type(qty_int);
+                                let field = node.extras[2]
+                                    .as_u64()
+                                    .expect("Expected offset of field");
+                                let field_id = self.visit_decl(field);
+                                OffsetOfKind::Member(value, qty, field_id)
+                            }
+                            None => OffsetOfKind::Constant(value),
+                        };
 
                         CExprKind::OffsetOf(ty, kind)
                     } else {
@@ -1441,7 +1519,9 @@ impl ConversionContext {
                     let arg_ty = node.extras[1].as_u64().expect("expected type id");
                     let arg_ty = self.visit_qualified_type(arg_ty);
 
-                    let operator = CExprKind::UnaryType(ty, kind, expr, arg_ty);
+                    let value = node.extras.get(2).and_then(|v| v.as_u64());
+
+                    let operator = CExprKind::UnaryType(ty, kind, expr, arg_ty, value);
 
                     self.expr_possibly_as_stmt(expected_ty, new_id, node, operator);
                 }
@@ -1562,6 +1642,45 @@ impl ConversionContext {
                     self.expr_possibly_as_stmt(expected_ty, new_id, node, stmt_expr)
                 }
 
+                ASTEntryTag::TagBlockExpr => {
+                    let num_params = node.extras[0]
+                        .as_u64()
+                        .expect("Expected number of block parameters")
+                        as usize;
+                    let byrefs = node.extras[1]
+                        .as_array()
+                        .expect("Expected block captures");
+
+                    let params = node.children[..num_params]
+                        .iter()
+                        .map(|id| {
+                            let param = id.expect("Block parameter not found");
+                            CDeclId(self.visit_node_type(param, VAR_DECL))
+                        })
+                        .collect();
+
+                    let body = node.children[num_params].expect("Expected block body");
+                    let body = self.visit_stmt(body);
+
+                    let captures = node.children[num_params + 1..]
+                        .iter()
+                        .zip(byrefs)
+                        .map(|(id, byref)| {
+                            let var = id.expect("Captured variable not found");
+                            let var = CDeclId(self.visit_node_type(var, VAR_DECL));
+                            let byref = byref.as_boolean().expect("Expected capture kind");
+                            (var, byref)
+                        })
+                        .collect();
+
+                    let ty_old = node.type_id.expect("Expected expression to have type");
+                    let typ = self.visit_qualified_type(ty_old);
+
+                    let block = CExprKind::Block { typ, params, body, captures };
+
+                    self.expr_possibly_as_stmt(expected_ty, new_id, node, block)
+                }
+
                 ASTEntryTag::TagVAArgExpr => {
                     let child_id = node.children[0].expect("Expected subexpression");
                     let child = self.visit_expr(child_id);
@@ -1628,6 +1747,23 @@ impl ConversionContext {
                     self.expr_possibly_as_stmt(expected_ty, new_id, node, e)
                 }
 
+                ASTEntryTag::TagTypesCompatibleExpr => {
+                    let ty = node.type_id.expect("Expected expression to have type");
+                    let ty = self.visit_qualified_type(ty);
+
+                    let lhs = node.extras[0].as_u64().expect("Expected first type");
+                    let lhs = self.visit_qualified_type(lhs);
+                    let rhs = node.extras[1].as_u64().expect("Expected second type");
+                    let rhs = self.visit_qualified_type(rhs);
+                    let compatible = node.extras[2]
+                        .as_boolean()
+                        .expect("Expected result of type comparison");
+
+                    let e = CExprKind::TypesCompatible(ty, lhs, rhs, compatible);
+
+                    self.expr_possibly_as_stmt(expected_ty, new_id, node, e)
+                }
+
                 ASTEntryTag::TagChooseExpr => {
                     let condition =
                         node.children[0].expect("ChooseExpr condition not found");
@@ -1775,6 +1911,10 @@ impl ConversionContext {
                     let is_implicit = node.extras[1]
                         .as_boolean()
                         .expect("Expected to find implicit");
+                    let attributes = node.extras[2]
+                        .as_array()
+                        .expect("Expected attribute array on typedef");
+                    let attrs = parse_attributes(attributes);
 
                     let typ_old = node
                         .type_id
@@ -1785,6 +1925,7 @@ impl ConversionContext {
                         name,
                         typ,
                         is_implicit,
+                        attrs,
                     };
 
                     self.add_decl(new_id, located(node, typdef_decl));
@@ -1793,6 +1934,10 @@ impl ConversionContext {
 
                 ASTEntryTag::TagEnumDecl if expected_ty & ENUM_DECL != 0 => {
                     let name = expect_opt_str(&node.extras[0]).unwrap().map(str::to_string);
+                    let attributes = node.extras[1]
+                        .as_array()
+                        .expect("Expected attribute array on enum");
+                    let attrs = parse_attributes(attributes);
 
                     let variants = node
                         .children
@@ -1811,6 +1956,7 @@ impl ConversionContext {
                         name,
                         variants,
                         integral_type,
+                        attrs,
                     };
 
                     self.add_decl(new_id, located(node, enum_decl));
@@ -1893,7 +2039,7 @@ impl ConversionContext {
                     let has_def = node.extras[1]
                         .as_boolean()
                         .expect("Expected has_def flag on struct");
-                    let attrs = node.extras[2]
+                    let attributes = node.extras[2]
                         .as_array()
                         .expect("Expected attribute array on record");
                     let manual_alignment =
@@ -1921,7 +2067,7 @@ impl ConversionContext {
                     };
 
                     let mut is_packed = false;
-                    for attr in attrs {
+                    for attr in attributes {
                         match attr
                             .as_string()
                             .expect("Records attributes should be strings")
@@ -1931,6 +2077,7 @@ impl ConversionContext {
                             _ => {}
                         }
                     }
+                    let attrs = parse_attributes(attributes);
 
                     let record = CDeclKind::Struct {
                         name,
@@ -1940,6 +2087,7 @@ impl ConversionContext {
                         max_field_alignment,
                         platform_byte_size,
                         platform_alignment,
+                        attrs,
                     };
 
                     self.add_decl(new_id, located(node, record));
@@ -1951,6 +2099,11 @@ impl ConversionContext {
                     let has_def = node.extras[1]
                         .as_boolean()
                         .expect("Expected has_def flag on struct");
+                    let attributes = node.extras[2]
+                        .as_array()
+                        .expect("Expected attribute array on record");
+                    let attrs = parse_attributes(attributes);
+                    let platform_byte_size = node.extras[5].as_u64().expect("Expected union size");
                     let fields: Option<Vec<CDeclId>> = if has_def {
                         Some(
                             node.children
@@ -1967,7 +2120,12 @@ impl ConversionContext {
                         None
                     };
 
-                    let record = CDeclKind::Union { name, fields };
+                    let record = CDeclKind::Union {
+                        name,
+                        fields,
+                        platform_byte_size,
+                        attrs,
+                    };
 
                     self.add_decl(new_id, located(node, record));
                     self.processed_nodes.insert(new_id, RECORD_DECL);
@@ -2025,6 +2183,64 @@ impl ConversionContext {
                     self.typed_context.c_decls_top.push(CDeclId(new_id));
                 }
 
+                ASTEntryTag::TagMacroFunctionDef if expected_ty & MACRO_DECL != 0 => {
+                    let name = node.extras[0]
+                        .as_string()
+                        .expect("Macros must have a name")
+                        .to_owned();
+
+                    let params = node.extras[1]
+                        .as_array()
+                        .expect("Expected macro parameters array")
+                        .iter()
+                        .map(|param| {
+                            param
+                                .as_string()
+                                .expect("Macro parameters must have a name")
+                                .to_owned()
+                        })
+                        .collect();
+
+                    let replacements = node
+                        .children
+                        .iter()
+                        .map(|id| {
+                            let expr_id = id.expect("Macro replacement expr not found");
+                            self.visit_expr(expr_id)
+                        })
+                        .collect();
+
+                    let args = node.extras[2]
+                        .as_array()
+                        .expect("Expected macro arguments array")
+                        .iter()
+                        .map(|args| {
+                            args.as_array()
+                                .expect("Expected macro expansion arguments array")
+                                .iter()
+                                .map(|arg| {
+                                    let arg = arg.as_array().expect("Expected macro argument");
+                                    let param = arg[0].as_u64().expect("Expected parameter index");
+                                    let expr_id = arg[1].as_u64().expect("Expected argument expr");
+                                    (param as usize, self.visit_expr(expr_id))
+                                })
+                                .collect()
+                        })
+                        .collect();
+
+                    let mac_function = CDeclKind::MacroFunction {
+                        name,
+                        params,
+                        replacements,
+                        args,
+                    };
+                    self.add_decl(new_id, located(node, mac_function));
+                    self.processed_nodes.insert(new_id, MACRO_DECL);
+
+                    // See the comment on TagMacroObjectDef
+                    self.typed_context.c_decls_top.push(CDeclId(new_id));
+                }
+
                 ASTEntryTag::TagNonCanonicalDecl if expected_ty & DECL != 0 => {
                     let canonical_decl = node.children[0]
                         .expect("NonCanonicalDecl must point to a canonical decl");
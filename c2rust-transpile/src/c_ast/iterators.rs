@@ -42,10 +42,10 @@ fn immediate_expr_children(kind: &CExprKind) -> Vec<SomeId> {
         BadExpr => vec![],
         DesignatedInitExpr(..) => vec![], // the relevant information will be found in the semantic initializer
         ShuffleVector(..) | ConvertVector(..) => vec![],
-        OffsetOf(..) | Literal(..) | ImplicitValueInit(..) => vec![],
+        OffsetOf(..) | Literal(..) | ImplicitValueInit(..) | TypesCompatible(..) => vec![],
         DeclRef(..) => vec![], // don't follow references back!
         Unary(_ty, _op, subexpr, _) => intos![subexpr],
-        UnaryType(_ty, _op, opt_expr_id, _) => opt_expr_id.iter().map(|&x| x.into()).collect(),
+        UnaryType(_ty, _op, opt_expr_id, _, _) => opt_expr_id.iter().map(|&x| x.into()).collect(),
         Binary(_ty, _op, lhs, rhs, _, _) => intos![lhs, rhs],
         Call(_, f, ref args) => {
             let mut res = intos![f];
@@ -70,10 +70,11 @@ fn immediate_expr_children(kind: &CExprKind) -> Vec<SomeId> {
         | ExplicitCast(_, e, _, _, _)
         | Member(_, e, _, _, _)
         | Paren(_, e)
+        | GenericSelection(_, e)
         | CompoundLiteral(_, e)
         | Predefined(_, e)
         | VAArg(_, e) => intos![e],
-        Statements(_, s) => vec![s.into()],
+        Statements(_, s) | Block { body: s, .. } => vec![s.into()],
     }
 }
 
@@ -87,11 +88,12 @@ fn immediate_expr_children_all_types(kind: &CExprKind) -> Vec<SomeId> {
         }
         // We need to iterate the struct type if this offsetof is variable,
         // since it may not get instantiated
-        OffsetOf(_, OffsetOfKind::Variable(qty, _, _)) => intos![qty.ctype],
-        OffsetOf(..) | Literal(..) | ImplicitValueInit(..) => vec![],
+        OffsetOf(_, OffsetOfKind::Variable(qty, _, _))
+        | OffsetOf(_, OffsetOfKind::Member(_, qty, _)) => intos![qty.ctype],
+        OffsetOf(..) | Literal(..) | ImplicitValueInit(..) | TypesCompatible(..) => vec![],
         DeclRef(..) => vec![], // don't follow references back!
         Unary(_ty, _op, subexpr, _) => intos![subexpr],
-        UnaryType(_ty, _op, opt_expr_id, qty) => {
+        UnaryType(_ty, _op, opt_expr_id, qty, _) => {
             let mut res = intos![qty.ctype];
             if let Some(expr_id) = opt_expr_id {
                 res.push(expr_id.into());
@@ -125,11 +127,13 @@ fn immediate_expr_children_all_types(kind: &CExprKind) -> Vec<SomeId> {
         ExplicitCast(qty, e, _, _, _)
         | ImplicitCast(qty, e, _, _, _)
         | Paren(qty, e)
+        | GenericSelection(qty, e)
         | CompoundLiteral(qty, e)
         | VAArg(qty, e) => {
             intos![qty.ctype, e]
         }
         Statements(_, s) => vec![s.into()],
+        Block { typ, body, .. } => intos![typ.ctype, body],
     }
 }
 
@@ -174,6 +178,9 @@ fn immediate_decl_children(kind: &CDeclKind) -> Vec<SomeId> {
         Field { typ, .. } => intos![typ.ctype],
         MacroObject {
             ref replacements, ..
+        }
+        | MacroFunction {
+            ref replacements, ..
         } => replacements.iter().map(|&x| x.into()).collect(),
         NonCanonicalDecl { canonical_decl } => intos![canonical_decl],
     }
@@ -262,7 +269,8 @@ fn immediate_type_children(kind: &CTypeKind) -> Vec<SomeId> {
         Elaborated(_) => vec![], // These are references to previous definitions
         TypeOfExpr(e) => intos![e],
         Void | Bool | Short | Int | Long | LongLong | UShort | UInt | ULong | ULongLong | SChar
-        | UChar | Char | Double | LongDouble | Float | Int128 | UInt128 | BuiltinFn | Half => {
+        | UChar | Char | Double | LongDouble | Float | Int128 | UInt128 | BitInt(..) | BuiltinFn
+        | Half | UnsupportedBuiltin(_) => {
             vec![]
         }
 
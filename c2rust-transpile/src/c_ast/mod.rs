@@ -218,6 +218,18 @@ impl TypedAstContext {
         self.include_map[file].first().map(|loc| loc.line)
     }
 
+    /// Get the files included directly by the main file, in the order they are included.
+    pub fn get_main_file_includes(&self) -> Vec<FileId> {
+        let mut includes: Vec<FileId> = (0..self.files.len())
+            .filter(|&file| self.include_map[file].len() == 1)
+            .collect();
+        includes.sort_by_key(|&file| {
+            let loc = &self.include_map[file][0];
+            (loc.line, loc.column)
+        });
+        includes
+    }
+
     pub fn find_file_id(&self, path: &Path) -> Option<FileId> {
         self.files.iter().position(|f| f.path.as_ref().map_or(false, |p| p == path))
     }
@@ -239,6 +251,14 @@ impl TypedAstContext {
         self.c_decls.iter()
     }
 
+    pub fn iter_exprs(&self) -> impl Iterator<Item = (&CExprId, &CExpr)> {
+        self.c_exprs.iter()
+    }
+
+    pub fn iter_stmts(&self) -> impl Iterator<Item = (&CStmtId, &CStmt)> {
+        self.c_stmts.iter()
+    }
+
     pub fn iter_mut_decls(&mut self) -> indexmap::map::IterMut<CDeclId, CDecl> {
         self.c_decls.iter_mut()
     }
@@ -381,6 +401,18 @@ impl TypedAstContext {
         }
     }
 
+    /// If the given expression is a `_Generic` selection (possibly parenthesized), return the
+    /// expression it selected.
+    pub fn generic_selection_result(&self, mut expr_id: CExprId) -> Option<CExprId> {
+        loop {
+            match self.c_exprs.get(&expr_id).map(|e| &e.kind) {
+                Some(&CExprKind::Paren(_, subexpr)) => expr_id = subexpr,
+                Some(&CExprKind::GenericSelection(_, result)) => return Some(result),
+                _ => return None,
+            }
+        }
+    }
+
     /// Resolve expression value, ignoring any casts
     pub fn resolve_expr_value(&self, expr_id: CExprId) -> &CExprKind {
         let expr = &self.index(expr_id).kind;
@@ -465,14 +497,17 @@ impl TypedAstContext {
 
             CExprKind::Literal(_, _) |
             CExprKind::DeclRef(_, _, _) |
-            CExprKind::UnaryType(_, _, _, _) |
-            CExprKind::OffsetOf(..) => true,
+            CExprKind::Block { .. } |
+            CExprKind::UnaryType(_, _, _, _, _) |
+            CExprKind::OffsetOf(..) |
+            CExprKind::TypesCompatible(..) => true,
 
             CExprKind::DesignatedInitExpr(_,_,e) |
             CExprKind::ImplicitCast(_, e, _, _, _) |
             CExprKind::ExplicitCast(_, e, _, _, _) |
             CExprKind::Member(_, e, _, _, _) |
             CExprKind::Paren(_, e) |
+            CExprKind::GenericSelection(_, e) |
             CExprKind::CompoundLiteral(_, e) |
             CExprKind::Unary(_, _, e, _) => self.is_expr_pure(e),
 
@@ -486,6 +521,28 @@ impl TypedAstContext {
         }
     }
 
+    /// Evaluate a condition built from `__builtin_types_compatible_p`, which is known at
+    /// translation time, possibly negated or combined with `&&` and `||`. Return `None` if the
+    /// value of the condition depends on anything else.
+    pub fn type_condition(&self, expr: CExprId) -> Option<bool> {
+        match self.index(expr).kind {
+            CExprKind::TypesCompatible(_, _, _, compatible) => Some(compatible),
+            CExprKind::Paren(_, e) | CExprKind::ImplicitCast(_, e, _, _, _) => {
+                self.type_condition(e)
+            }
+            CExprKind::Unary(_, UnOp::Not, e, _) => self.type_condition(e).map(|b| !b),
+            CExprKind::Binary(_, BinOp::And, lhs, rhs, _, _) => match self.type_condition(lhs) {
+                Some(true) => self.type_condition(rhs),
+                lhs => lhs.filter(|&b| !b),
+            },
+            CExprKind::Binary(_, BinOp::Or, lhs, rhs, _, _) => match self.type_condition(lhs) {
+                Some(false) => self.type_condition(rhs),
+                lhs => lhs.filter(|&b| b),
+            },
+            _ => None,
+        }
+    }
+
     // Pessimistically try to check if an expression doesn't return. If it does, or we can't tell
     /// that it doesn't, return `false`.
     pub fn expr_diverges(&self, expr_id: CExprId) -> bool {
@@ -509,6 +566,34 @@ impl TypedAstContext {
         }
     }
 
+    /// Find the function named by the `cleanup` attribute of a variable, preferring its
+    /// definition over other declarations.
+    pub fn cleanup_function(&self, var_id: CDeclId) -> Option<CDeclId> {
+        let name = self.c_decls[&var_id]
+            .kind
+            .get_attrs()?
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::Cleanup(name) => Some(name),
+                _ => None,
+            })
+            .next()?;
+
+        let mut found = None;
+        for (&decl_id, decl) in &self.c_decls {
+            match decl.kind {
+                CDeclKind::Function { name: ref fn_name, ref body, .. } if fn_name == name => {
+                    if body.is_some() {
+                        return Some(decl_id);
+                    }
+                    found.get_or_insert(decl_id);
+                }
+                _ => {}
+            }
+        }
+        found
+    }
+
     pub fn prune_unused_decls(&mut self) {
         // Starting from a set of root declarations, walk each one to find declarations it
         // depends on. Then walk each of those, recursively.
@@ -607,6 +692,15 @@ impl TypedAstContext {
                                     to_walk.push(parent_id);
                                 }
                             }
+                            CDeclKind::Variable { .. } => {
+                                // The cleanup function of a variable is called without being
+                                // referenced by any expression.
+                                if let Some(fn_id) = self.cleanup_function(decl_id) {
+                                    if used.insert(fn_id) {
+                                        to_walk.push(fn_id);
+                                    }
+                                }
+                            }
                             _ => {}
                         }
                     }
@@ -633,20 +727,31 @@ impl TypedAstContext {
         self.c_decls_top.retain(|x| used.contains(x));
     }
 
+    /// Canonical ordering of declarations: by source location, then by name. Declarations
+    /// without a location sort first. This is independent of the order in which the
+    /// exporter happened to produce the declarations.
+    pub fn compare_decls(&self, a: &CDecl, b: &CDecl) -> Ordering {
+        let by_loc = match (&a.loc, &b.loc) {
+            (None, None) => Ordering::Equal,
+            (None, _) => Ordering::Less,
+            (_, None) => Ordering::Greater,
+            (Some(a), Some(b)) => self.compare_src_locs(&a.begin(), &b.begin()),
+        };
+        by_loc.then_with(|| a.kind.get_name().cmp(&b.kind.get_name()))
+    }
+
     pub fn sort_top_decls(&mut self) {
-        // Group and sort declarations by file and by position
+        // Group and sort declarations by file and by position. The sorts are stable so that
+        // any remaining ties keep the order clang emitted them in.
         let mut decls_top = mem::replace(&mut self.c_decls_top, vec![]);
-        decls_top.sort_unstable_by(|a, b| {
-            let a = self.index(*a);
-            let b = self.index(*b);
-            match (&a.loc, &b.loc) {
-                (None, None) => Ordering::Equal,
-                (None, _) => Ordering::Less,
-                (_, None) => Ordering::Greater,
-                (Some(a), Some(b)) => self.compare_src_locs(&a.begin(), &b.begin()),
-            }
-        });
+        decls_top.sort_by(|a, b| self.compare_decls(self.index(*a), self.index(*b)));
         self.c_decls_top = decls_top;
+
+        // We also iterate over all declarations when exporting types and picking names, so
+        // put those in the same canonical order.
+        let mut decls: Vec<(CDeclId, CDecl)> = self.c_decls.drain(..).collect();
+        decls.sort_by(|(_, a), (_, b)| self.compare_decls(a, b));
+        self.c_decls = decls.into_iter().collect();
     }
 
     pub fn has_inner_struct_decl(&self, decl_id: CDeclId) -> bool {
@@ -809,11 +914,12 @@ impl Index<CExprId> for TypedAstContext {
         match self.c_exprs.get(&index) {
             None => &BADEXPR, // panic!("Could not find {:?} in TypedAstContext", index),
             Some(e) => {
-                // Transparently index through Paren expressions
-                if let CExprKind::Paren(_, subexpr) = e.kind {
-                    self.index(subexpr)
-                } else {
-                    e
+                // Transparently index through Paren and _Generic expressions
+                match e.kind {
+                    CExprKind::Paren(_, subexpr) | CExprKind::GenericSelection(_, subexpr) => {
+                        self.index(subexpr)
+                    }
+                    _ => e,
                 }
             }
         }
@@ -881,6 +987,7 @@ pub enum CDeclKind {
         name: Option<String>,
         variants: Vec<CEnumConstantId>,
         integral_type: Option<CQualTypeId>,
+        attrs: IndexSet<Attribute>,
     },
 
     EnumConstant {
@@ -893,6 +1000,7 @@ pub enum CDeclKind {
         name: String,
         typ: CQualTypeId,
         is_implicit: bool,
+        attrs: IndexSet<Attribute>,
     },
 
     // Struct
@@ -904,12 +1012,15 @@ pub enum CDeclKind {
         max_field_alignment: Option<u64>,
         platform_byte_size: u64,
         platform_alignment: u64,
+        attrs: IndexSet<Attribute>,
     },
 
     // Union
     Union {
         name: Option<String>,
         fields: Option<Vec<CFieldId>>,
+        platform_byte_size: u64,
+        attrs: IndexSet<Attribute>,
     },
 
     // Field
@@ -926,6 +1037,15 @@ pub enum CDeclKind {
         replacements: Vec<CExprId>,
     },
 
+    MacroFunction {
+        name: String,
+        params: Vec<String>,
+        replacements: Vec<CExprId>,
+        /// For each replacement, the subexpressions that the macro arguments expand to, along
+        /// with the index of the corresponding parameter
+        args: Vec<Vec<(usize, CExprId)>>,
+    },
+
     NonCanonicalDecl {
         canonical_decl: CDeclId,
     }
@@ -949,6 +1069,19 @@ impl CDeclKind {
             } => Some(i),
             &CDeclKind::Field { name: ref i, .. } => Some(i),
             &CDeclKind::MacroObject { ref name, .. } => Some(name),
+            &CDeclKind::MacroFunction { ref name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn get_attrs(&self) -> Option<&IndexSet<Attribute>> {
+        match self {
+            &CDeclKind::Function { ref attrs, .. }
+            | &CDeclKind::Variable { ref attrs, .. }
+            | &CDeclKind::Typedef { ref attrs, .. }
+            | &CDeclKind::Enum { ref attrs, .. }
+            | &CDeclKind::Struct { ref attrs, .. }
+            | &CDeclKind::Union { ref attrs, .. } => Some(attrs),
             _ => None,
         }
     }
@@ -959,6 +1092,10 @@ impl CDeclKind {
 pub enum OffsetOfKind {
     /// An Integer Constant Expr
     Constant(u64),
+    /// An Integer Constant Expr giving the offset of a member of a struct,
+    /// which can also be computed from the struct itself
+    /// Offset, Struct Type, Field Decl Id
+    Member(u64, CQualTypeId, CDeclId),
     /// Contains more information to generate
     /// an offset_of! macro invocation
     /// Struct Type, Field Decl Id, Index Expr
@@ -980,8 +1117,9 @@ pub enum CExprKind {
     // Unary operator.
     Unary(CQualTypeId, UnOp, CExprId, LRValue),
 
-    // Unary type operator.
-    UnaryType(CQualTypeId, UnTypeOp, Option<CExprId>, CQualTypeId),
+    // Unary type operator. For `alignof`, Clang's computed value is kept when it is known so
+    // that we do not depend on Rust agreeing with C about the alignment of the operand.
+    UnaryType(CQualTypeId, UnTypeOp, Option<CExprId>, CQualTypeId, Option<u64>),
 
     // Offsetof expression.
     OffsetOf(CQualTypeId, OffsetOfKind),
@@ -1053,6 +1191,22 @@ pub enum CExprKind {
     // GNU choose expr. Condition, true expr, false expr, was condition true?
     Choose(CQualTypeId, CExprId, CExprId, CExprId, bool),
 
+    // GNU `__builtin_types_compatible_p`. The two types, and whether they are compatible
+    TypesCompatible(CQualTypeId, CQualTypeId, CQualTypeId, bool),
+
+    // C11 generic selection, with the association that was selected. Like `Paren`, this is
+    // transparent when indexing into the context.
+    GenericSelection(CQualTypeId, CExprId),
+
+    // Apple block literal, with its parameters, body, and captured variables. Each capture
+    // records whether it is a `__block` variable, which is captured by reference.
+    Block {
+        typ: CQualTypeId,
+        params: Vec<CDeclId>,
+        body: CStmtId,
+        captures: Vec<(CDeclId, bool)>,
+    },
+
     // GNU/C11 atomic expr
     Atomic {
         typ: CQualTypeId,
@@ -1093,7 +1247,7 @@ impl CExprKind {
             CExprKind::Literal(ty, _)
             | CExprKind::OffsetOf(ty, _)
             | CExprKind::Unary(ty, _, _, _)
-            | CExprKind::UnaryType(ty, _, _, _)
+            | CExprKind::UnaryType(ty, _, _, _, _)
             | CExprKind::Binary(ty, _, _, _, _, _)
             | CExprKind::ImplicitCast(ty, _, _, _, _)
             | CExprKind::ExplicitCast(ty, _, _, _, _)
@@ -1114,6 +1268,9 @@ impl CExprKind {
             | CExprKind::ConvertVector(ty, _)
             | CExprKind::DesignatedInitExpr(ty, _, _) => Some(ty),
             | CExprKind::Choose(ty, _, _, _, _)
+            | CExprKind::TypesCompatible(ty, _, _, _)
+            | CExprKind::GenericSelection(ty, _)
+            | CExprKind::Block{typ: ty, ..}
             | CExprKind::Atomic{typ: ty, ..} => Some(ty),
         }
     }
@@ -1127,6 +1284,7 @@ impl CExprKind {
     pub fn get_bool(&self) -> Option<bool> {
         match *self {
             CExprKind::Literal(_, ref lit) => Some(lit.get_bool()),
+            CExprKind::TypesCompatible(_, _, _, compatible) => Some(compatible),
             _ => None,
         }
     }
@@ -1377,6 +1535,10 @@ pub enum CStmtKind {
         outputs: Vec<AsmOperand>,
         clobbers: Vec<String>,
         is_volatile: bool,
+        /// Labels that an `asm goto` can jump to, in operand order
+        labels: Vec<CLabelId>,
+        /// Whether `asm goto` can be translated for the target
+        goto_supported: bool,
     },
 }
 
@@ -1479,6 +1641,11 @@ pub enum CTypeKind {
     Int128,
     UInt128,
 
+    // Bit-precise integer types (C23 6.2.5). Ex: `unsigned _BitInt(24)`
+    //
+    // Holds whether the type is signed and its width in bits.
+    BitInt(bool, u64),
+
     Complex(CTypeId),
 
     // Pointer types (6.7.5.1)
@@ -1537,6 +1704,10 @@ pub enum CTypeKind {
     Vector(CQualTypeId, usize),
 
     Half,
+
+    // A builtin type the translator doesn't support, such as the decimal floating-point
+    // `_Decimal64`. Holds the name of the type.
+    UnsupportedBuiltin(String),
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -1568,6 +1739,12 @@ pub enum Attribute {
     Used,
     /// __attribute((visibility("hidden")))
     Visibility(String),
+    /// _Alignas(16), alignas(16), __attribute__((aligned(16))) with the alignment in bytes
+    Aligned(u64),
+    /// __attribute__((deprecated)), __attribute__((deprecated("msg"))) with the message, if any
+    Deprecated(Option<String>),
+    /// __attribute__((cleanup(foo))) with the name of the cleanup function
+    Cleanup(String),
 }
 
 impl CTypeKind {
@@ -1605,6 +1782,7 @@ impl CTypeKind {
             CTypeKind::ULong => true,
             CTypeKind::ULongLong => true,
             CTypeKind::UInt128 => true,
+            CTypeKind::BitInt(signed, _) => !signed,
             _ => false,
         }
     }
@@ -1618,10 +1796,20 @@ impl CTypeKind {
             CTypeKind::Long => true,
             CTypeKind::LongLong => true,
             CTypeKind::Int128 => true,
+            CTypeKind::BitInt(signed, _) => signed,
             _ => false,
         }
     }
 
+    /// The width in bits of the smallest Rust integer that can hold a `_BitInt(N)`, or `None` if
+    /// this is not a bit-precise integer type or no Rust integer is wide enough.
+    pub fn bit_int_storage_width(&self) -> Option<u64> {
+        match *self {
+            CTypeKind::BitInt(_, width) if width <= 128 => Some(width.next_power_of_two().max(8)),
+            _ => None,
+        }
+    }
+
     pub fn is_floating_type(&self) -> bool {
         match *self {
             CTypeKind::Float => true,
@@ -88,7 +88,12 @@ impl<W: Write> Printer<W> {
                 Ok(())
             }
 
-            Some(&CExprKind::UnaryType(_, kind, opt_expr, arg_ty)) => {
+            Some(&CExprKind::Block { body, .. }) => {
+                self.writer.write_all(b"^")?;
+                self.print_stmt(body, false, false, context)
+            }
+
+            Some(&CExprKind::UnaryType(_, kind, opt_expr, arg_ty, _)) => {
                 let kind_str = match kind {
                     UnTypeOp::SizeOf => b"sizeof(".as_ref(),
                     UnTypeOp::AlignOf => b"alignof(".as_ref(),
@@ -106,6 +111,15 @@ impl<W: Write> Printer<W> {
 
             Some(&CExprKind::OffsetOf(_, ref kind)) => match kind {
                 OffsetOfKind::Constant(val) => self.writer.write_fmt(format_args!("{}", val)),
+                OffsetOfKind::Member(_, qty, decl_id) => {
+                    self.writer.write_all(b"offset_of!(")?;
+                    self.print_qtype(*qty, None, context)?;
+                    self.writer.write_all(b", ")?;
+                    self.print_decl_name(*decl_id, context)?;
+                    self.writer.write_all(b")")?;
+
+                    Ok(())
+                }
                 OffsetOfKind::Variable(qty, decl_id, expr_id) => {
                     self.writer.write_all(b"offset_of!(")?;
                     self.print_qtype(*qty, None, context)?;
@@ -201,6 +215,8 @@ impl<W: Write> Printer<W> {
                 self.writer.write_all(b"?")
             }
             Some(&CExprKind::ImplicitValueInit { .. }) => self.writer.write_all(b"{}"),
+            // We only keep the selected association of a `_Generic`
+            Some(&CExprKind::GenericSelection(_, val)) => self.print_expr(val, context),
             Some(&CExprKind::Paren(_, val)) => {
                 self.writer.write_all(b"(")?;
                 self.print_expr(val, context)?;
@@ -226,6 +242,14 @@ impl<W: Write> Printer<W> {
                 self.writer.write_all(b")")
             }
 
+            Some(&CExprKind::TypesCompatible(_, lhs, rhs, _)) => {
+                self.writer.write_all(b"__builtin_types_compatible_p(")?;
+                self.print_qtype(lhs, None, context)?;
+                self.writer.write_all(b", ")?;
+                self.print_qtype(rhs, None, context)?;
+                self.writer.write_all(b")")
+            }
+
             Some(&CExprKind::Atomic{ref name, ptr, order, val1, order_fail, val2, weak, ..}) => {
                 self.writer.write_fmt(format_args!("{}(", name))?;
 
@@ -728,6 +752,20 @@ impl<W: Write> Printer<W> {
                 Ok(())
             }
 
+            Some(&CDeclKind::MacroFunction {
+                ref name,
+                ref params,
+                ref replacements,
+                ..
+            }) => {
+                self.writer.write_fmt(format_args!("#define {}({}) ", name, params.join(", ")))?;
+                for replacement in replacements {
+                    self.print_expr(*replacement, context)?;
+                }
+
+                Ok(())
+            }
+
             Some(&CDeclKind::NonCanonicalDecl {
                 ref canonical_decl,
             }) => {
@@ -824,6 +862,15 @@ impl<W: Write> Printer<W> {
                     &CTypeKind::LongDouble => self.writer.write_all(b"long double"),
                     &CTypeKind::Int128 => self.writer.write_all(b"__int128"),
                     &CTypeKind::UInt128 => self.writer.write_all(b"unsigned __int128"),
+                    &CTypeKind::BitInt(true, width) => {
+                        self.writer.write_fmt(format_args!("_BitInt({})", width))
+                    }
+                    &CTypeKind::BitInt(false, width) => {
+                        self.writer.write_fmt(format_args!("unsigned _BitInt({})", width))
+                    }
+                    &CTypeKind::UnsupportedBuiltin(ref name) => {
+                        self.writer.write_all(name.as_bytes())
+                    }
                     _ => unimplemented!("Printer::print_type({:?})", ty),
                 }?;
 
@@ -435,6 +435,8 @@ impl GenTerminator<StructureLabel<StmtOrDecl>> {
 #[derive(Clone, Debug, Default)]
 pub struct SwitchCases {
     cases: Vec<(P<Pat>, Label)>,
+    /// The value of each case in `cases`
+    case_values: Vec<ConstIntExpr>,
     default: Option<Label>,
 }
 
@@ -570,8 +572,9 @@ impl Cfg<Label, StmtOrDecl> {
             .flat_map(|&stmt_id| DFExpr::new(&translator.ast_context, stmt_id.into()))
             .flat_map(SomeId::stmt)
             .flat_map(|x| match translator.ast_context[x].kind {
-                CStmtKind::Goto(target) => Some((target, x)),
-                _ => None,
+                CStmtKind::Goto(target) => vec![(target, x)],
+                CStmtKind::Asm { ref labels, .. } => labels.iter().map(|&l| (l, x)).collect(),
+                _ => vec![],
             })
         {
             c_label_to_goto
@@ -580,7 +583,10 @@ impl Cfg<Label, StmtOrDecl> {
                 .insert(x);
         }
 
-        let mut cfg_builder = CfgBuilder::new(c_label_to_goto);
+        let mut label_cleanups = IndexMap::new();
+        cleanups_at_labels(translator, stmt_ids, &mut vec![], &mut label_cleanups);
+
+        let mut cfg_builder = CfgBuilder::new(c_label_to_goto, label_cleanups);
         let entry = cfg_builder.entry;
         cfg_builder.per_stmt_stack.push(PerStmt::new(
             stmt_ids.get(0).cloned(),
@@ -779,6 +785,55 @@ impl<Lbl: Copy + Ord + Hash + Debug, Stmt> Cfg<Lbl, Stmt> {
     }
 }
 
+/// Find the variables with a cleanup function that are in scope at each C label in `stmt_ids`,
+/// given those in scope before them.
+fn cleanups_at_labels(
+    translator: &Translation,
+    stmt_ids: &[CStmtId],
+    in_scope: &mut Vec<CDeclId>,
+    label_cleanups: &mut IndexMap<CLabelId, IndexSet<CDeclId>>,
+) {
+    for &stmt_id in stmt_ids {
+        match translator.ast_context[stmt_id].kind {
+            CStmtKind::Label(sub) => {
+                label_cleanups.insert(stmt_id, in_scope.iter().cloned().collect());
+                cleanups_at_labels(translator, &[sub], in_scope, label_cleanups);
+            }
+            // Labels and declarations directly under a `case` share the scope of the `switch`
+            CStmtKind::Case(_, sub, _) | CStmtKind::Default(sub) => {
+                cleanups_at_labels(translator, &[sub], in_scope, label_cleanups)
+            }
+            CStmtKind::Decls(ref decls) => in_scope.extend(
+                decls
+                    .iter()
+                    .filter(|&&decl| translator.ast_context.cleanup_function(decl).is_some()),
+            ),
+            CStmtKind::Compound(ref stmts) => {
+                cleanups_at_labels(translator, stmts, &mut in_scope.clone(), label_cleanups)
+            }
+            CStmtKind::If {
+                true_variant,
+                false_variant,
+                ..
+            } => {
+                for &branch in Some(true_variant).iter().chain(&false_variant) {
+                    cleanups_at_labels(translator, &[branch], &mut in_scope.clone(), label_cleanups);
+                }
+            }
+            CStmtKind::Switch { body, .. }
+            | CStmtKind::While { body, .. }
+            | CStmtKind::DoWhile { body, .. } => {
+                cleanups_at_labels(translator, &[body], &mut in_scope.clone(), label_cleanups)
+            }
+            CStmtKind::ForLoop { init, body, .. } => {
+                let stmts: Vec<CStmtId> = init.into_iter().chain(Some(body)).collect();
+                cleanups_at_labels(translator, &stmts, &mut in_scope.clone(), label_cleanups)
+            }
+            _ => {}
+        }
+    }
+}
+
 /// This stores all of the state required to construct a control-flow graph from C statements. Once
 /// the graph is constructed, we only really care about the 'graph' field.
 #[derive(Clone, Debug)]
@@ -798,6 +853,14 @@ struct CfgBuilder {
     break_labels: Vec<Label>,
     /// Like 'break_labels', but for 'continue'.
     continue_labels: Vec<Label>,
+    /// Variables with a cleanup function currently in scope, in declaration order.
+    cleanups: Vec<CDeclId>,
+    /// How many of `cleanups` were in scope when entering the construct that each 'break' and
+    /// 'continue' label leads out of.
+    cleanup_depths: IndexMap<Label, usize>,
+    /// Global (immutable) mapping of `CLabelId` -> variables with a cleanup function in scope at
+    /// that label
+    label_cleanups: IndexMap<CLabelId, IndexSet<CDeclId>>,
     /// Accumulates information for the 'case'/'default' encountered so far while translating the
     /// body of a 'switch'.
     switch_expr_cases: Vec<SwitchCases>,
@@ -1265,6 +1328,7 @@ impl CfgBuilder {
         // Open a new scope
         let new_vars = self.current_variables();
         self.currently_live.push(new_vars);
+        let cleanup_depth = self.cleanups.len();
 
         let b = cont(self);
 
@@ -1272,10 +1336,22 @@ impl CfgBuilder {
         self.currently_live
             .pop()
             .expect("Found no live currently live scope to close");
+        self.cleanups.truncate(cleanup_depth);
 
         b
     }
 
+    /// Build the calls to the cleanup functions of the variables going out of scope when control
+    /// leaves a construct entered with `depth` cleanup variables in scope, in reverse order of
+    /// their declarations.
+    fn cleanup_stmts(&self, translator: &Translation, depth: usize) -> Vec<Stmt> {
+        self.cleanups[depth..]
+            .iter()
+            .rev()
+            .map(|&decl| translator.cleanup_local_stmt(decl))
+            .collect()
+    }
+
     fn current_variables(&self) -> IndexSet<CDeclId> {
         self.currently_live
             .last()
@@ -1307,7 +1383,10 @@ impl CfgBuilder {
     }
 
     /// Create a new `CfgBuilder` with a single entry label.
-    fn new(c_label_to_goto: IndexMap<CLabelId, IndexSet<CStmtId>>) -> CfgBuilder {
+    fn new(
+        c_label_to_goto: IndexMap<CLabelId, IndexSet<CStmtId>>,
+        label_cleanups: IndexMap<CLabelId, IndexSet<CDeclId>>,
+    ) -> CfgBuilder {
         let entry = Label::Synthetic(0);
 
         CfgBuilder {
@@ -1322,6 +1401,9 @@ impl CfgBuilder {
 
             break_labels: vec![],
             continue_labels: vec![],
+            cleanups: vec![],
+            cleanup_depths: IndexMap::new(),
+            label_cleanups,
             switch_expr_cases: vec![],
 
             currently_live: vec![IndexSet::new()],
@@ -1345,6 +1427,7 @@ impl CfgBuilder {
             |slf| -> Result<Option<Label>, TranslationError> {
                 let mut lbl = Some(entry);
                 let last = stmt_ids.last();
+                let cleanup_depth = slf.cleanups.len();
 
                 // We feed the optional output label into the entry label of the next block
                 for stmt in stmt_ids {
@@ -1353,6 +1436,23 @@ impl CfgBuilder {
                     lbl = slf.convert_stmt_help(translator, ctx, *stmt, sub_in_tail, new_label)?;
                 }
 
+                // Falling off the end of the block leaves the scope of its cleanup variables,
+                // unless the value of a statement expression still has to be computed
+                let computes_value = match in_tail {
+                    Some(ImplicitReturnType::StmtExpr(..)) => true,
+                    _ => false,
+                };
+                if let (Some(end), false) = (lbl, computes_value) {
+                    let stmts = slf.cleanup_stmts(translator, cleanup_depth);
+                    if !stmts.is_empty() {
+                        let next = slf.fresh_label();
+                        let mut wip = slf.new_wip_block(end);
+                        wip.extend(stmts);
+                        slf.add_wip_block(wip, Jump(next));
+                        lbl = Some(next);
+                    }
+                }
+
                 Ok(lbl)
             },
         )
@@ -1403,6 +1503,10 @@ impl CfgBuilder {
 
                         wip.push_decl(*decl);
                         wip.defined.insert(*decl);
+
+                        if translator.is_cleanup_local(*decl) {
+                            self.cleanups.push(*decl);
+                        }
                     }
                     Ok(Some(wip))
                 }
@@ -1439,7 +1543,10 @@ impl CfgBuilder {
                     let (stmts, val) = translator.convert_condition(ctx, true, scrutinee)?.discard_unsafe();
                     wip.extend(stmts);
 
-                    let cond_val = translator.ast_context[scrutinee].kind.get_bool();
+                    let cond_val = translator
+                        .ast_context
+                        .type_condition(scrutinee)
+                        .or_else(|| translator.ast_context[scrutinee].kind.get_bool());
                     self.add_wip_block(
                         wip,
                         match cond_val {
@@ -1483,6 +1590,11 @@ impl CfgBuilder {
                     condition,
                     body: body_stmt,
                 } => {
+                    if let Some(stmts) = translator.convert_loop_idiom(ctx, stmt_id)? {
+                        wip.extend(stmts);
+                        return Ok(Some(wip));
+                    }
+
                     let cond_entry = self.fresh_label();
                     let body_entry = self.fresh_label();
                     let next_entry = self.fresh_label();
@@ -1509,7 +1621,9 @@ impl CfgBuilder {
                     let saw_unmatched_break = self.last_per_stmt_mut().saw_unmatched_break;
                     let saw_unmatched_continue = self.last_per_stmt_mut().saw_unmatched_continue;
                     self.break_labels.push(next_entry);
+                    self.cleanup_depths.insert(next_entry, self.cleanups.len());
                     self.continue_labels.push(cond_entry);
+                    self.cleanup_depths.insert(cond_entry, self.cleanups.len());
 
                     let body_stuff =
                         self.convert_stmt_help(translator, ctx, body_stmt, None, body_entry)?;
@@ -1543,7 +1657,9 @@ impl CfgBuilder {
                     let saw_unmatched_break = self.last_per_stmt_mut().saw_unmatched_break;
                     let saw_unmatched_continue = self.last_per_stmt_mut().saw_unmatched_continue;
                     self.break_labels.push(next_entry);
+                    self.cleanup_depths.insert(next_entry, self.cleanups.len());
                     self.continue_labels.push(cond_entry);
+                    self.cleanup_depths.insert(cond_entry, self.cleanups.len());
 
                     let body_stuff =
                         self.convert_stmt_help(translator, ctx, body_stmt, None, body_entry)?;
@@ -1583,6 +1699,11 @@ impl CfgBuilder {
                     increment,
                     body,
                 } => {
+                    if let Some(stmts) = translator.convert_loop_idiom(ctx, stmt_id)? {
+                        wip.extend(stmts);
+                        return Ok(Some(wip));
+                    }
+
                     let init_entry = self.fresh_label();
                     let cond_entry = self.fresh_label();
                     let body_entry = self.fresh_label();
@@ -1629,7 +1750,9 @@ impl CfgBuilder {
                         let saw_unmatched_break = slf.last_per_stmt_mut().saw_unmatched_break;
                         let saw_unmatched_continue = slf.last_per_stmt_mut().saw_unmatched_continue;
                         slf.break_labels.push(next_label);
+                        slf.cleanup_depths.insert(next_label, slf.cleanups.len());
                         slf.continue_labels.push(incr_entry);
+                        slf.cleanup_depths.insert(incr_entry, slf.cleanups.len());
 
                         let body_stuff =
                             slf.convert_stmt_help(translator, ctx, body, None, body_entry)?;
@@ -1677,6 +1800,12 @@ impl CfgBuilder {
 
                 CStmtKind::Goto(label_id) => {
                     let tgt_label = Label::FromC(label_id);
+                    // Leave the scopes of the cleanup variables that aren't in scope at the
+                    // label. If we don't know the label, their `Drop`s will have to do.
+                    if let Some(at_label) = self.label_cleanups.get(&label_id) {
+                        let left = self.cleanups.iter().rev().filter(|d| !at_label.contains(*d));
+                        wip.extend(left.map(|&decl| translator.cleanup_local_stmt(decl)));
+                    }
                     self.add_wip_block(wip, Jump(tgt_label));
                     self.last_per_stmt_mut()
                         .c_labels_used
@@ -1719,7 +1848,12 @@ impl CfgBuilder {
                         }
                     }
 
-                    wip.extend(translator.convert_expr(ctx.unused(), expr)?.into_stmts());
+                    // With `--shim-untranslatable-stmts`, a statement that can't be translated
+                    // may be moved into a C function instead
+                    match translator.convert_expr(ctx.unused(), expr) {
+                        Ok(val) => wip.extend(val.into_stmts()),
+                        Err(e) => wip.extend(translator.convert_shim_stmt(ctx, stmt_id).ok_or(e)?),
+                    }
 
                     // If we can tell the expression is going to diverge, there is no falling through to
                     // the next block.
@@ -1739,6 +1873,7 @@ impl CfgBuilder {
                         "Cannot find what to break from in this ({:?}) 'break' statement",
                         stmt_id,
                     ))?;
+                    wip.extend(self.cleanup_stmts(translator, self.cleanup_depths[&tgt_label]));
                     self.add_wip_block(wip, Jump(tgt_label));
 
                     Ok(None)
@@ -1750,6 +1885,7 @@ impl CfgBuilder {
                         "Cannot find what to continue from in this ({:?}) 'continue' statement",
                         stmt_id,
                     ))?;
+                    wip.extend(self.cleanup_stmts(translator, self.cleanup_depths[&tgt_label]));
                     self.add_wip_block(wip, Jump(tgt_label));
 
                     Ok(None)
@@ -1775,14 +1911,15 @@ impl CfgBuilder {
                             mk().lit_expr(mk().int_lit((-n) as u128, LitIntType::Unsuffixed)),
                         ),
                     };
-                    self.switch_expr_cases
+                    let switch_cases = self
+                        .switch_expr_cases
                         .last_mut()
                         .ok_or(format_err!(
                             "Cannot find the 'switch' wrapping this ({:?}) 'case' statement",
                             stmt_id,
-                        ))?
-                        .cases
-                        .push((mk().lit_pat(branch), this_label));
+                        ))?;
+                    switch_cases.cases.push((mk().lit_pat(branch), this_label));
+                    switch_cases.case_values.push(cie);
 
                     // Sub stmt
                     let sub_stmt_next =
@@ -1815,11 +1952,15 @@ impl CfgBuilder {
                     let next_label = self.fresh_label();
                     let body_label = self.fresh_label();
 
-                    // Convert the condition
+                    // Convert the condition, which is matched against integer literals
                     let (stmts, val) = translator
                         .convert_expr(ctx.used(), scrutinee)?
                         .discard_unsafe();
                     wip.extend(stmts);
+                    let val = match translator.ast_context[scrutinee].kind.get_type() {
+                        Some(ty) => translator.bit_int_storage(ty, val),
+                        None => val,
+                    };
 
                     let wip_label = wip.label;
                     self.add_wip_block(wip, End); // NOTE: the `End` here is temporary and gets updated
@@ -1829,6 +1970,7 @@ impl CfgBuilder {
                     let saw_unmatched_case = self.last_per_stmt_mut().saw_unmatched_case;
                     let saw_unmatched_default = self.last_per_stmt_mut().saw_unmatched_default;
                     self.break_labels.push(next_label);
+                    self.cleanup_depths.insert(next_label, self.cleanups.len());
                     self.switch_expr_cases.push(SwitchCases::default());
 
                     let body_stuff =
@@ -1847,7 +1989,24 @@ impl CfgBuilder {
                         .pop()
                         .expect("No 'SwitchCases' to pop");
 
-                    let mut cases: Vec<_> = switch_case.cases.clone();
+                    // A `switch` on a Rust enum can match on its variants, unless some case
+                    // isn't one of them
+                    let variant_pats = translator
+                        .rust_enum_scrutinee(scrutinee)
+                        .and_then(|enum_id| {
+                            switch_case
+                                .case_values
+                                .iter()
+                                .map(|&value| translator.rust_enum_variant_pat(enum_id, value))
+                                .collect::<Option<Vec<_>>>()
+                        });
+                    let (val, mut cases): (_, Vec<_>) = match (variant_pats, &val.kind) {
+                        (Some(pats), ExprKind::Cast(e, _)) => {
+                            let labels = switch_case.cases.iter().map(|&(_, label)| label);
+                            (e.clone(), pats.into_iter().zip(labels).collect())
+                        }
+                        _ => (val.clone(), switch_case.cases.clone()),
+                    };
                     cases.push((
                         mk().wild_pat(),
                         switch_case.default.unwrap_or(next_label),
@@ -1867,8 +2026,50 @@ impl CfgBuilder {
                     ref inputs,
                     ref outputs,
                     ref clobbers,
+                    ref labels,
+                    goto_supported,
+                } if !labels.is_empty() => {
+                    let (stmts, selector) = translator.convert_asm_goto(
+                        ctx,
+                        DUMMY_SP,
+                        is_volatile,
+                        asm,
+                        inputs,
+                        outputs,
+                        clobbers,
+                        labels.len(),
+                        goto_supported,
+                    )?;
+                    wip.extend(stmts);
+
+                    // The assembly records which label it jumped to, if any, in `selector`
+                    let next_label = self.fresh_label();
+                    let mut cases = vec![];
+                    for (i, &label_id) in labels.iter().enumerate() {
+                        let n = mk().int_lit(i as u128 + 1, LitIntType::Unsuffixed);
+                        let pat = mk().lit_pat(mk().lit_expr(n));
+                        cases.push((pat, Label::FromC(label_id)));
+                        self.last_per_stmt_mut()
+                            .c_labels_used
+                            .entry(label_id)
+                            .or_insert(IndexSet::new())
+                            .insert(stmt_id);
+                    }
+                    cases.push((mk().wild_pat(), next_label));
+                    self.add_wip_block(wip, Switch { expr: selector, cases });
+
+                    Ok(Some(self.new_wip_block(next_label)))
+                }
+
+                CStmtKind::Asm {
+                    is_volatile,
+                    ref asm,
+                    ref inputs,
+                    ref outputs,
+                    ref clobbers,
+                    ..
                 } => {
-                    wip.extend(translator.convert_asm(
+                    let stmts = translator.convert_asm(
                         ctx,
                         DUMMY_SP,
                         is_volatile,
@@ -1876,7 +2077,12 @@ impl CfgBuilder {
                         inputs,
                         outputs,
                         clobbers,
-                    )?);
+                        None,
+                    );
+                    match stmts {
+                        Ok(stmts) => wip.extend(stmts),
+                        Err(e) => wip.extend(translator.convert_shim_stmt(ctx, stmt_id).ok_or(e)?),
+                    }
                     Ok(Some(wip))
                 }
             };
@@ -41,6 +41,51 @@ impl CompileCmd {
             },
         }
     }
+
+    /// The directories this command searches for system headers, relative to its working
+    /// directory. A `command` string is split on whitespace, without regard to shell quoting.
+    pub fn system_include_dirs(&self) -> Vec<PathBuf> {
+        let args = match self.command {
+            Some(ref command) if self.arguments.is_empty() => {
+                command.split_whitespace().collect::<Vec<_>>()
+            }
+            _ => self.arguments.iter().map(String::as_str).collect(),
+        };
+        system_include_dirs(&args)
+            .into_iter()
+            .map(|dir| self.directory.join(dir))
+            .collect()
+    }
+}
+
+/// The directories searched for system headers by a compiler invoked with `args`: those
+/// added with `-isystem` or `-idirafter`, and the default ones under the `--sysroot`.
+pub fn system_include_dirs(args: &[&str]) -> Vec<PathBuf> {
+    let mut sysroot = PathBuf::from("/");
+    let mut dirs = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        for &flag in &["-isystem", "-idirafter", "--sysroot", "-isysroot"] {
+            if !arg.starts_with(flag) {
+                continue;
+            }
+            let value = match &arg[flag.len()..] {
+                "" => args.next().cloned(),
+                rest => Some(rest.trim_start_matches('=')),
+            };
+            if let Some(value) = value {
+                if flag.ends_with("sysroot") {
+                    sysroot = PathBuf::from(value);
+                } else {
+                    dirs.push(PathBuf::from(value));
+                }
+            }
+            break;
+        }
+    }
+    dirs.push(sysroot.join("usr/include"));
+    dirs.push(sysroot.join("usr/local/include"));
+    dirs
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -3,7 +3,8 @@ use crate::c_ast::*;
 use crate::renamer::*;
 use crate::diagnostics::TranslationError;
 use c2rust_ast_builder::mk;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
 use std::ops::Index;
 use syntax::ast::*;
 use syntax::ptr::P;
@@ -16,11 +17,49 @@ enum FieldKey {
 
 pub struct TypeConverter {
     pub translate_valist: bool,
+    pub translate_blocks: bool,
+    /// Translate `_BitInt(N)` into the newtypes named by `bit_int_newtype_name`
+    pub strict_bit_ints: bool,
+    /// The signedness and width of the `_BitInt` newtypes used so far
+    bit_int_newtypes: BTreeSet<(bool, u64)>,
     renamer: Renamer<CDeclId>,
     fields: HashMap<CDeclId, Renamer<FieldKey>>,
     suffix_names: HashMap<(CDeclId, &'static str), String>,
     features: HashSet<&'static str>,
     emit_no_std: bool,
+    rename_collisions: RenameCollisions,
+}
+
+/// The Rust keywords, which are all reserved names
+pub const RUST_KEYWORDS: [&str; 55] = [
+    // Keywords currently in use
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "Self", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "dyn",
+    // Keywords reserved for future use
+    "abstract", "alignof", "become", "box", "do", "final", "macro", "offsetof", "override",
+    "priv", "proc", "pure", "sizeof", "typeof", "unsized", "virtual", "yield", "async", "try",
+];
+
+/// Create a renamer that keeps clear of `reserved_names`, and renames identifiers colliding with
+/// Rust keywords as `rename_collisions` says.
+pub fn reserved_renamer<T: Clone + Eq + Hash>(
+    reserved_names: &[&str],
+    rename_collisions: RenameCollisions,
+) -> Renamer<T> {
+    let mut renamer = Renamer::new(reserved_names);
+    if rename_collisions == RenameCollisions::Underscore {
+        renamer.underscore_names(&RUST_KEYWORDS);
+    }
+    renamer
+}
+
+/// The name of the newtype a `_BitInt(width)` translates into with `--strict-bit-ints`, e.g.,
+/// `BitInt24` or `UBitInt7`.
+pub fn bit_int_newtype_name(signed: bool, width: u64) -> String {
+    let prefix = if signed { "" } else { "U" };
+    format!("{}BitInt{}", prefix, width)
 }
 
 pub const RESERVED_NAMES: [&str; 103] = [
@@ -133,14 +172,18 @@ pub const RESERVED_NAMES: [&str; 103] = [
 ];
 
 impl TypeConverter {
-    pub fn new(emit_no_std: bool) -> TypeConverter {
+    pub fn new(emit_no_std: bool, rename_collisions: RenameCollisions) -> TypeConverter {
         TypeConverter {
             translate_valist: false,
-            renamer: Renamer::new(&RESERVED_NAMES),
+            translate_blocks: false,
+            strict_bit_ints: false,
+            bit_int_newtypes: BTreeSet::new(),
+            renamer: reserved_renamer(&RESERVED_NAMES, rename_collisions),
             fields: HashMap::new(),
             suffix_names: HashMap::new(),
             features: HashSet::new(),
             emit_no_std,
+            rename_collisions,
         }
     }
 
@@ -148,6 +191,10 @@ impl TypeConverter {
         &self.features
     }
 
+    pub fn bit_int_newtypes_used(&self) -> &BTreeSet<(bool, u64)> {
+        &self.bit_int_newtypes
+    }
+
     pub fn declare_decl_name(&mut self, decl_id: CDeclId, name: &str) -> String {
         self.renamer
             .insert(decl_id, name)
@@ -184,7 +231,8 @@ impl TypeConverter {
         let name = if name.is_empty() { "c2rust_unnamed" } else { name };
 
         if !self.fields.contains_key(&record_id) {
-            self.fields.insert(record_id, Renamer::new(&RESERVED_NAMES));
+            let renamer = reserved_renamer(&RESERVED_NAMES, self.rename_collisions);
+            self.fields.insert(record_id, renamer);
         }
 
         self.fields
@@ -200,7 +248,8 @@ impl TypeConverter {
         padding_idx: usize,
     ) -> String {
         if !self.fields.contains_key(&record_id) {
-            self.fields.insert(record_id, Renamer::new(&RESERVED_NAMES));
+            let renamer = reserved_renamer(&RESERVED_NAMES, self.rename_collisions);
+            self.fields.insert(record_id, renamer);
         }
 
         let key = FieldKey::Padding(padding_idx);
@@ -309,6 +358,7 @@ impl TypeConverter {
         ctype: CTypeId,
     ) -> Result<P<Ty>, TranslationError> {
         if self.translate_valist && ctxt.is_va_list(ctype) {
+            self.features.insert("c_variadic");
             let std_or_core = if self.emit_no_std { "core" } else { "std" };
             let path = vec!["", std_or_core, "ffi", "VaList"];
             let ty = mk().path_ty(path);
@@ -334,6 +384,21 @@ impl TypeConverter {
             CTypeKind::Float => Ok(mk().path_ty(mk().path(vec!["libc", "c_float"]))),
             CTypeKind::Int128 => Ok(mk().path_ty(mk().path(vec!["i128"]))),
             CTypeKind::UInt128 => Ok(mk().path_ty(mk().path(vec!["u128"]))),
+            CTypeKind::BitInt(signed, width) => {
+                // `_BitInt(N)` is stored in the smallest integer that can hold it; the translator
+                // takes care of wrapping values back into N bits.
+                let storage = ctxt
+                    .index(ctype)
+                    .kind
+                    .bit_int_storage_width()
+                    .ok_or_else(|| format_err!("Unsupported bit-precise integer width {}", width))?;
+                if self.strict_bit_ints {
+                    self.bit_int_newtypes.insert((signed, width));
+                    return Ok(mk().path_ty(vec![bit_int_newtype_name(signed, width)]));
+                }
+                let prefix = if signed { "i" } else { "u" };
+                Ok(mk().path_ty(mk().path(vec![format!("{}{}", prefix, storage)])))
+            }
 
             CTypeKind::Pointer(qtype) => self.convert_pointer(ctxt, qtype),
 
@@ -404,6 +469,19 @@ impl TypeConverter {
 
             CTypeKind::TypeOf(ty) => self.convert(ctxt, ty),
 
+            // Blocks are passed around as pointers to block literals, whose layout depends on
+            // what they capture
+            CTypeKind::BlockPointer(_) if self.translate_blocks => {
+                Ok(mk().mutbl().ptr_ty(mk().path_ty(vec!["libc", "c_void"])))
+            }
+
+            CTypeKind::UnsupportedBuiltin(ref name) if name.starts_with("_Decimal") => Err(
+                format_err!("Unsupported decimal floating-point type `{}`", name).into(),
+            ),
+            CTypeKind::UnsupportedBuiltin(ref name) => {
+                Err(format_err!("Unsupported builtin type `{}`", name).into())
+            }
+
             ref t => Err(format_err!("Unsupported type {:?}", t).into()),
         }
     }
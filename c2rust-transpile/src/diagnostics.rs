@@ -30,7 +30,7 @@ pub fn init(mut enabled_warnings: HashSet<Diagnostic>, log_level: log::LevelFilt
     enabled_warnings.extend(DEFAULT_WARNINGS.iter().cloned());
 
     let colors = ColoredLevelConfig::new();
-    fern::Dispatch::new()
+    let dispatch = fern::Dispatch::new()
         .format(move |out, message, record| {
             let level_label = match record.level() {
                 Level::Error => "error",
@@ -62,9 +62,10 @@ pub fn init(mut enabled_warnings: HashSet<Diagnostic>, log_level: log::LevelFilt
                 .map(|d| enabled_warnings.contains(&d))
                 .unwrap_or(true)
         })
-        .chain(io::stderr())
-        .apply()
-        .expect("Could not set up diagnostics");
+        .chain(io::stderr());
+    // The logger can only be set once per process, so when the transpiler runs several times,
+    // as in a test suite, the diagnostics of the first run's configuration are kept.
+    let _ = dispatch.apply();
 }
 
 
@@ -0,0 +1,197 @@
+//! Inventory of the notable C constructs a file uses, so that integrators can decide whether
+//! to attempt translation before running the translator.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use failure::Error;
+
+use crate::c_ast::*;
+use c2rust_ast_exporter as ast_exporter;
+
+/// A C construct that the translator doesn't support, or only partially supports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    /// GNU inline assembly statements
+    InlineAsm,
+    /// Variables, parameters and fields with variably modified types, e.g., `int buf[n]`
+    VariableLengthArray,
+    /// Definitions of variadic functions
+    VariadicDefinition,
+    /// `goto` statements, which need the relooper
+    Goto,
+    /// Declarations using `long double`
+    LongDouble,
+    /// Declarations using `_Complex` types
+    Complex,
+    /// Bit-field members
+    Bitfield,
+}
+
+/// Where a file uses a [`Feature`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FeatureLocation {
+    pub file: Option<PathBuf>,
+    pub line: u64,
+    pub column: u64,
+}
+
+/// The uses of each [`Feature`] in a file, in source order.
+#[derive(Clone, Debug, Default)]
+pub struct FeatureReport {
+    uses: BTreeMap<Feature, Vec<FeatureLocation>>,
+}
+
+impl FeatureReport {
+    /// Check if the file uses `feature` at all.
+    pub fn uses(&self, feature: Feature) -> bool {
+        self.uses.contains_key(&feature)
+    }
+
+    /// Get the number of uses of `feature`.
+    pub fn count(&self, feature: Feature) -> usize {
+        self.locations(feature).len()
+    }
+
+    /// Get the locations of the uses of `feature`.
+    pub fn locations(&self, feature: Feature) -> &[FeatureLocation] {
+        self.uses.get(&feature).map_or(&[][..], |locs| &locs[..])
+    }
+
+    /// Iterate over the features the file uses, with their locations.
+    pub fn iter(&self) -> impl Iterator<Item = (Feature, &[FeatureLocation])> {
+        self.uses.iter().map(|(&feature, locs)| (feature, &locs[..]))
+    }
+
+    fn add<T>(&mut self, ast_context: &TypedAstContext, feature: Feature, node: &Located<T>) {
+        let loc = match node.loc {
+            Some(ref loc) => loc,
+            None => return,
+        };
+        let file = ast_context
+            .file_id(node)
+            .and_then(|id| ast_context.get_file_path(id))
+            .map(Path::to_path_buf);
+        self.uses.entry(feature).or_default().push(FeatureLocation {
+            file,
+            line: loc.begin_line,
+            column: loc.begin_column,
+        });
+    }
+
+    /// Find the features used by the declarations and statements of the file `main_file`,
+    /// ignoring the headers it includes.
+    fn new(ast_context: &TypedAstContext, main_file: FileId) -> Self {
+        let mut report = FeatureReport::default();
+
+        for (_, decl) in ast_context.iter_decls() {
+            if ast_context.file_id(decl) != Some(main_file) {
+                continue;
+            }
+            let mut features = vec![];
+            match decl.kind {
+                CDeclKind::Function { typ, body: Some(_), .. } => {
+                    if let CTypeKind::Function(ret, _, is_variadic, _, _) =
+                        ast_context.resolve_type(typ).kind
+                    {
+                        if is_variadic {
+                            features.push(Feature::VariadicDefinition);
+                        }
+                        type_features(ast_context, ret.ctype, &mut features);
+                    }
+                }
+                CDeclKind::Variable { typ, .. } => {
+                    type_features(ast_context, typ.ctype, &mut features);
+                }
+                CDeclKind::Field { typ, bitfield_width, .. } => {
+                    if bitfield_width.is_some() {
+                        features.push(Feature::Bitfield);
+                    }
+                    type_features(ast_context, typ.ctype, &mut features);
+                }
+                _ => {}
+            }
+            features.sort();
+            features.dedup();
+            for feature in features {
+                report.add(ast_context, feature, decl);
+            }
+        }
+
+        for (_, stmt) in ast_context.iter_stmts() {
+            if ast_context.file_id(stmt) != Some(main_file) {
+                continue;
+            }
+            match stmt.kind {
+                CStmtKind::Asm { .. } => report.add(ast_context, Feature::InlineAsm, stmt),
+                CStmtKind::Goto(_) => report.add(ast_context, Feature::Goto, stmt),
+                _ => {}
+            }
+        }
+
+        for locs in report.uses.values_mut() {
+            locs.sort();
+        }
+        report
+    }
+}
+
+/// Collect the features used by the type `ctype`, looking through pointers and arrays.
+fn type_features(ast_context: &TypedAstContext, ctype: CTypeId, features: &mut Vec<Feature>) {
+    match ast_context.resolve_type(ctype).kind {
+        CTypeKind::VariableArray(elt, _) => {
+            features.push(Feature::VariableLengthArray);
+            type_features(ast_context, elt, features);
+        }
+        CTypeKind::ConstantArray(elt, _) | CTypeKind::IncompleteArray(elt) => {
+            type_features(ast_context, elt, features);
+        }
+        CTypeKind::Pointer(pointee) => type_features(ast_context, pointee.ctype, features),
+        CTypeKind::LongDouble => features.push(Feature::LongDouble),
+        CTypeKind::Complex(_) => features.push(Feature::Complex),
+        _ => {}
+    }
+}
+
+/// Find the notable C constructs that the file `source` uses, without translating it.
+/// `args` are the compiler arguments to parse the file with, e.g., `-I` and `-D` flags.
+///
+/// The file is parsed by the same AST exporter the translator uses, with the same
+/// limitations: like during translation, the exporter aborts on the GNU labels-as-values
+/// extension (computed `goto`).
+pub fn analyze_features(source: &Path, args: &[&str]) -> Result<FeatureReport, Error> {
+    static DB_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let source = source.canonicalize()?;
+    let directory = source.parent().unwrap_or_else(|| Path::new("/"));
+
+    // The exporter reads the compiler arguments from a compilation database
+    let db_dir = std::env::temp_dir().join(format!(
+        "c2rust-features-{}-{}",
+        process::id(),
+        DB_COUNT.fetch_add(1, Ordering::SeqCst),
+    ));
+    fs::create_dir_all(&db_dir)?;
+    let cc_db = db_dir.join("compile_commands.json");
+    let mut arguments = vec!["cc".to_owned()];
+    arguments.extend(args.iter().map(|&arg| arg.to_owned()));
+    arguments.push("-c".to_owned());
+    arguments.push(source.to_string_lossy().into_owned());
+    let commands = serde_json::json!([{
+        "directory": directory,
+        "arguments": arguments,
+        "file": source
+    }]);
+    fs::write(&cc_db, commands.to_string())?;
+
+    let untyped_context = ast_exporter::get_untyped_ast(&source, &cc_db, &[], false);
+    fs::remove_dir_all(&db_dir)?;
+    let untyped_context = untyped_context?;
+
+    let typed_context = ConversionContext::new(&untyped_context).typed_context;
+    let main_file = typed_context.find_file_id(&source).unwrap_or(0);
+    Ok(FeatureReport::new(&typed_context, main_file))
+}
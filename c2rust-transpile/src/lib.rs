@@ -36,12 +36,15 @@ pub mod c_ast;
 pub mod cfg;
 mod compile_cmds;
 pub mod convert_type;
+mod features;
 pub mod renamer;
 pub mod rust_ast;
 pub mod translator;
 pub mod with_stmts;
 
+use std::cell::RefCell;
 use std::collections::HashSet;
+use std::fmt;
 use std::fs::{self, File};
 use std::io;
 use std::io::prelude::*;
@@ -50,22 +53,27 @@ use std::process;
 
 use failure::Error;
 use regex::Regex;
+use syntax::ast::Crate;
 
 use crate::c_ast::Printer;
 use crate::c_ast::*;
 pub use crate::diagnostics::Diagnostic;
+pub use crate::renamer::RenameCollisions;
+pub use crate::features::{analyze_features, Feature, FeatureLocation, FeatureReport};
 use c2rust_ast_exporter as ast_exporter;
 
-use crate::build_files::{emit_build_files, get_build_dir, CrateConfig};
+use crate::build_files::{emit_build_files, get_build_dir, get_module_path, CrateConfig};
 use crate::compile_cmds::get_compile_commands;
 use crate::convert_type::RESERVED_NAMES;
-pub use crate::translator::ReplaceMode;
+pub use crate::translator::{InternalVisibility, PthreadsBackend, ReplaceMode};
+pub use crate::translator::{OutputStyle, OutputStyleOverrides, StringLiterals};
+use crate::translator::{CrateUnit, InlineFns};
 use std::prelude::v1::Vec;
 
 type PragmaVec = Vec<(&'static str, Vec<&'static str>)>;
 type PragmaSet = indexmap::IndexSet<(&'static str, &'static str)>;
 type CrateSet = indexmap::IndexSet<ExternCrate>;
-type TranspileResult = (PathBuf, Option<PragmaVec>, Option<CrateSet>);
+type TranspileResult = (PathBuf, Option<PragmaVec>, Option<CrateSet>, Option<String>);
 
 /// Configuration settings for the translation process
 #[derive(Debug)]
@@ -98,7 +106,13 @@ pub struct TranspilerConfig {
     pub emit_modules: bool,
     pub fail_on_error: bool,
     pub replace_unsupported_decls: ReplaceMode,
+    /// Move statements that can't be translated into C functions in a `c2rust_shims.c` file
+    /// next to the output, and call those instead of failing the whole function
+    pub shim_untranslatable_stmts: bool,
     pub translate_valist: bool,
+    /// Translate `__builtin_prefetch` to the nightly `core::intrinsics` prefetches rather than
+    /// a no-op that only evaluates the address
+    pub translate_prefetch: bool,
     pub overwrite_existing: bool,
     pub reduce_type_annotations: bool,
     pub reorganize_definitions: bool,
@@ -106,8 +120,59 @@ pub struct TranspilerConfig {
     pub emit_no_std: bool,
     pub output_dir: Option<PathBuf>,
     pub translate_const_macros: bool,
+    pub translate_fn_macros: bool,
+    /// Mark the functions translated from function-like macros `#[inline(always)]`, so that they
+    /// are inlined like the macro expansions they replace
+    pub inline_fn_macros: bool,
+    pub translate_enums: bool,
+    /// Emit `#[repr(transparent)]` instead of `#[repr(C)]` for structs with a single field
+    /// that isn't zero-sized, so that they are passed across FFI like that field
+    pub emit_repr_transparent: bool,
+    /// Translate `_BitInt(N)` into `BitIntN` and `UBitIntN` newtypes over the storage integer,
+    /// so that the exact width is part of the Rust type
+    pub strict_bit_ints: bool,
+    pub translate_fn_tables: bool,
+    /// Translate static tables of string literals into slices of `CStr`s
+    pub translate_string_tables: bool,
+    /// Whether identical string literals passed to extern functions as a `char *` share one
+    /// `static mut` array
+    pub string_literals: StringLiterals,
+    /// Translate Apple blocks into closures wrapped in the Blocks ABI
+    pub translate_blocks: bool,
+    /// How C identifiers that are Rust keywords are renamed
+    pub rename_collisions: RenameCollisions,
+    /// Mark functions whose result callers check as an error code with `#[must_use]`
+    pub must_use_error_codes: bool,
+    /// Mark struct fields used as reference counts with `#[refcount_field]` and report their
+    /// uses in a `.refcounts.json` file next to each output file
+    pub detect_refcounts: bool,
+    /// Move constant byte arrays with at least this many elements into `.bin` files next to
+    /// each output file, and include them with `include_bytes!`
+    pub embed_byte_arrays: Option<usize>,
+    /// Move the definitions of output files with more than this many bytes of Rust into
+    /// `mod`s in files of at most this size, in a directory next to each output file
+    pub split_output_size: Option<usize>,
+    /// C functions taking a level and a message that should become `log` crate macros
+    pub log_functions: Vec<String>,
+    /// Translate pthreads mutexes, once controls and threads into `std::sync` and
+    /// `std::thread`, taking mutexes from the selected crate
+    pub translate_pthreads: Option<PthreadsBackend>,
     pub disable_refactoring: bool,
     pub log_level: log::LevelFilter,
+    /// Only emit FFI declarations and type definitions, without translating function bodies
+    pub header_only: bool,
+    /// Visibility of functions whose C symbol isn't exported chosen with
+    /// `--internal-visibility`, if any
+    pub internal_visibility: Option<InternalVisibility>,
+    /// Declare the types defined in system headers as aliases of the `libc` crate's types, or
+    /// opaquely, instead of translating their definitions
+    pub skip_system_headers: bool,
+    /// Names of the system types whose definitions are translated with `--skip-system-headers`
+    pub system_type_allowlist: Vec<String>,
+    /// Style of the emitted code selected with `--output-style`, if any
+    pub output_style: Option<OutputStyle>,
+    /// Settings of the output style that were chosen individually
+    pub output_style_overrides: OutputStyleOverrides,
 
     // Options that control build files
     /// Emit `Cargo.toml` and `lib.rs`
@@ -115,9 +180,64 @@ pub struct TranspilerConfig {
     /// Names of translation units containing main functions that we should make
     /// into binaries
     pub binaries: Vec<String>,
+
+    /// Callbacks run over the translated Rust AST before it is emitted
+    pub ast_transforms: AstTransforms,
 }
 
 impl TranspilerConfig {
+    /// Register a callback that post-processes the translated Rust AST of each output
+    /// file after translation and before it is pretty-printed, e.g., to add attributes or
+    /// rename items. Callbacks run in the order they were registered.
+    ///
+    /// The callback receives a `Crate` whose module holds the top-level items of the file,
+    /// including the `mod` items of headers when `--reorganize-definitions` is on. Only
+    /// these items are emitted; changes to the crate attributes are ignored, as are any
+    /// features or extern crates the new code would require.
+    ///
+    /// This hook exposes the AST of rustc's internal `syntax` crate, which has no
+    /// stability guarantees: transforms may break whenever the pinned nightly toolchain
+    /// is updated. The spans of translated nodes are only used to place C comments, so
+    /// moving nodes around may also move their comments.
+    pub fn with_ast_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(&mut Crate) + 'static,
+    {
+        self.ast_transforms.0.push(Box::new(transform));
+        self
+    }
+
+    /// Resolve one setting of the output style: an explicit override wins, then the selected
+    /// `--output-style`, and without either the setting keeps its `default`.
+    fn output_style_setting(&self, setting: Option<bool>, default: bool) -> bool {
+        setting.unwrap_or_else(|| match self.output_style {
+            Some(style) => style == OutputStyle::Readable,
+            None => default,
+        })
+    }
+
+    /// Import `std`/`core` modules with `use` instead of spelling out `::std::` paths
+    pub fn import_std_paths(&self) -> bool {
+        self.output_style_setting(self.output_style_overrides.import_std_paths, false)
+    }
+
+    /// Leave out redundant casts, such as casting an integer literal to one type and then
+    /// straight to another
+    pub fn elide_redundant_casts(&self) -> bool {
+        self.output_style_setting(self.output_style_overrides.elide_redundant_casts, false)
+    }
+
+    /// End functions with a trailing expression instead of a `return` statement
+    pub fn trailing_return_exprs(&self) -> bool {
+        self.output_style_setting(self.output_style_overrides.trailing_return_exprs, false)
+    }
+
+    /// Use boolean Rust expressions directly as conditions instead of comparing them, cast to
+    /// `c_int`, against zero
+    pub fn simplify_bool_conditions(&self) -> bool {
+        self.output_style_setting(self.output_style_overrides.simplify_bool_conditions, true)
+    }
+
     fn is_binary(&self, file: &Path) -> bool {
         let file = Path::new(file.file_stem().unwrap());
         let name = get_module_name(file, false, false, false).unwrap();
@@ -131,6 +251,29 @@ impl TranspilerConfig {
     }
 }
 
+/// Callbacks registered with `TranspilerConfig::with_ast_transform`
+#[derive(Default)]
+pub struct AstTransforms(Vec<Box<dyn Fn(&mut Crate)>>);
+
+impl AstTransforms {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Run all callbacks over `krate`, in order.
+    pub fn apply(&self, krate: &mut Crate) {
+        for transform in &self.0 {
+            transform(krate);
+        }
+    }
+}
+
+impl fmt::Debug for AstTransforms {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AstTransforms({} callbacks)", self.0.len())
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum ExternCrate {
     C2RustBitfields,
@@ -139,6 +282,8 @@ pub enum ExternCrate {
     NumTraits,
     Memoffset,
     Libc,
+    Log,
+    ParkingLot,
 }
 
 #[derive(Serialize)]
@@ -169,6 +314,8 @@ impl From<ExternCrate> for ExternCrateDetails {
             ExternCrate::NumTraits => Self::new("num-traits", "0.2", true),
             ExternCrate::Memoffset => Self::new("memoffset", "0.5", true),
             ExternCrate::Libc => Self::new("libc", "0.2", false),
+            ExternCrate::Log => Self::new("log", "0.4", true),
+            ExternCrate::ParkingLot => Self::new("parking_lot", "0.9", false),
         }
     }
 }
@@ -274,20 +421,36 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
                 .unwrap_or_else(PathBuf::new);
         }
 
+        // Inline functions from headers are only translated once per crate, which needs
+        // to know the module layout from `lib.rs`
+        let inline_fns = RefCell::new(InlineFns::default());
+        let inline_fns = if tcfg.emit_build_files {
+            Some(&inline_fns)
+        } else {
+            None
+        };
+
         let results = cmds
             .iter()
-            .map(|cmd| transpile_single(&tcfg, cmd.abs_file(),
-                                        &ancestor_path,
-                                        &build_dir,
-                                        cc_db,
-                                        extra_clang_args))
+            .map(|cmd| {
+                let mut system_include_dirs = cmd.system_include_dirs();
+                system_include_dirs.extend(compile_cmds::system_include_dirs(&clang_args));
+                transpile_single(&tcfg, cmd.abs_file(),
+                                 &ancestor_path,
+                                 &build_dir,
+                                 cc_db,
+                                 extra_clang_args,
+                                 system_include_dirs,
+                                 inline_fns)
+            })
             .collect::<Vec<TranspileResult>>();
         let mut modules = vec![];
         let mut modules_skipped = false;
         let mut pragmas = PragmaSet::new();
         let mut crates = CrateSet::new();
+        let mut shims = vec![];
         for res in results {
-            let (module, pragma_vec, crate_set) = res;
+            let (module, pragma_vec, crate_set, shim_source) = res;
             modules.push(module);
 
             if let Some(pv) = pragma_vec {
@@ -303,10 +466,22 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
             if let Some(cs) = crate_set {
                 crates.extend(cs);
             }
+
+            shims.extend(shim_source);
         }
         pragmas.sort();
         crates.sort();
 
+        let has_shims = !shims.is_empty();
+        if has_shims {
+            if modules_skipped {
+                // The shims of the skipped files would be missing
+                warn!("Can't emit c2rust_shims.c after incremental transpiler run; skipped.");
+            } else {
+                emit_shims(&build_dir, &shims);
+            }
+        }
+
         if tcfg.emit_build_files {
             if modules_skipped {
                 // If we skipped a file, we may not have collected all required pragmas
@@ -319,7 +494,8 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
                 modules,
                 pragmas,
                 crates,
-                link_cmd: lcmd
+                link_cmd: lcmd,
+                shims: has_shims,
             };
             if lcmd.top_level {
                 top_level_ccfg = Some(ccfg);
@@ -391,6 +567,7 @@ fn invoke_refactor(build_dir: &PathBuf) -> Result<(), Error> {
         "rename_unnamed",
         ";",
         "reorganize_definitions",
+        "name_map.json",
     ];
     let status = process::Command::new(cmd_path.into_os_string())
         .args(&args)
@@ -436,11 +613,13 @@ fn transpile_single(
     build_dir: &Path,
     cc_db: &Path,
     extra_clang_args: &[&str],
+    system_include_dirs: Vec<PathBuf>,
+    inline_fns: Option<&RefCell<InlineFns>>,
 ) -> TranspileResult {
     let output_path = get_output_path(tcfg, &input_path, ancestor_path, build_dir);
     if output_path.exists() && !tcfg.overwrite_existing {
         println!("Skipping existing file {}", output_path.display());
-        return (output_path, None, None);
+        return (output_path, None, None, None);
     }
 
     let file = input_path.file_name().unwrap().to_str().unwrap();
@@ -494,9 +673,14 @@ fn transpile_single(
         println!("{:#?}", Printer::new(io::stdout()).print(&typed_context));
     }
 
+    let crate_unit = inline_fns.and_then(|inline_fns| {
+        let module_path = get_module_path(tcfg, build_dir, &output_path)?;
+        Some(CrateUnit { inline_fns, module_path })
+    });
+
     // Perform the translation
-    let (translated_string, pragmas, crates) =
-        translator::translate(typed_context, &tcfg, input_path);
+    let (translated_string, pragmas, crates, refcount_report, embedded_bytes, shims, parts) =
+        translator::translate(typed_context, &tcfg, input_path, system_include_dirs, crate_unit);
 
     let mut file = match File::create(&output_path) {
         Ok(file) => file,
@@ -508,7 +692,56 @@ fn transpile_single(
         Err(e) => panic!("Unable to write translation to file {}: {}", output_path.display(), e),
     };
 
-    (output_path, Some(pragmas), Some(crates))
+    for (name, bytes) in embedded_bytes {
+        let data_path = output_path.with_file_name(name);
+        if let Err(e) = fs::write(&data_path, bytes) {
+            panic!("Unable to write embedded data {}: {}", data_path.display(), e);
+        }
+    }
+
+    for (name, source) in parts {
+        let part_path = output_path.with_file_name(name);
+        let written = part_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&part_path, source));
+        if let Err(e) = written {
+            panic!("Unable to write output part {}: {}", part_path.display(), e);
+        }
+    }
+
+    if let Some(report) = refcount_report {
+        let report_path = output_path.with_extension("refcounts.json");
+        if let Err(e) = fs::write(&report_path, report) {
+            panic!("Unable to write refcount report {}: {}", report_path.display(), e);
+        }
+    }
+
+    (output_path, Some(pragmas), Some(crates), shims)
+}
+
+/// Write the statements moved into C shims by the translation of each file of a crate to
+/// `c2rust_shims.c`.
+fn emit_shims(build_dir: &Path, shims: &[String]) {
+    if !build_dir.exists() {
+        fs::create_dir_all(&build_dir).expect(&format!(
+            "couldn't create build directory: {}",
+            build_dir.display()
+        ));
+    }
+
+    let shims_path = build_dir.join("c2rust_shims.c");
+    let mut source = String::from(
+        "/* Statements that couldn't be translated, called from the translated functions */
+",
+    );
+    for shim_source in shims {
+        source.push('\n');
+        source.push_str(shim_source);
+    }
+    if let Err(e) = fs::write(&shims_path, source) {
+        panic!("Unable to write shims {}: {}", shims_path.display(), e);
+    }
 }
 
 fn get_output_path(
@@ -37,9 +37,20 @@ impl<T: Clone + Eq + Hash> Scope<T> {
     }
 }
 
+/// How identifiers that collide with Rust keywords are renamed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenameCollisions {
+    /// Append a numbered suffix, like any other reserved name: `type` becomes `type_0`
+    Number,
+    /// Append an underscore: `type` becomes `type_`
+    Underscore,
+}
+
 pub struct Renamer<T> {
     scopes: Vec<Scope<T>>,
     next_fresh: u64,
+    /// Reserved names that get an underscore appended instead of a numbered suffix
+    underscored: HashSet<String>,
 }
 
 impl<T: Clone + Eq + Hash> Renamer<T> {
@@ -51,9 +62,16 @@ impl<T: Clone + Eq + Hash> Renamer<T> {
         Renamer {
             scopes: vec![Scope::new_with_reserved(set)],
             next_fresh: 0,
+            underscored: HashSet::new(),
         }
     }
 
+    /// Rename the given reserved names by appending an underscore rather than a number. If
+    /// the underscored name is taken as well, it gets a numbered suffix in turn.
+    pub fn underscore_names(&mut self, names: &[&str]) {
+        self.underscored.extend(names.iter().map(|&x| x.to_owned()));
+    }
+
     /// Introduces a new name binding scope
     pub fn add_scope(&mut self) {
         self.scopes.push(Scope::new())
@@ -86,7 +104,12 @@ impl<T: Clone + Eq + Hash> Renamer<T> {
     /// Assigns a name that doesn't collide with anything in the context of a particular
     /// scope, defaulting to the current scope if None is provided
     fn pick_name_in_scope(&mut self, basename: &str, scope: Option<usize>) -> String {
-        let mut target = basename.to_string();
+        let basename = if self.underscored.contains(basename) {
+            format!("{}_", basename)
+        } else {
+            basename.to_string()
+        };
+        let mut target = basename.clone();
 
         for i in 0.. {
             if self.is_target_used(&target) {
@@ -196,6 +219,17 @@ mod tests {
         assert_eq!(reserved2, "reserved_0");
     }
 
+    #[test]
+    fn underscored() {
+        let mut renamer = Renamer::new(&["match", "type"]);
+        renamer.underscore_names(&["match"]);
+
+        assert_eq!(renamer.insert(1, "match").unwrap(), "match_");
+        assert_eq!(renamer.insert(2, "match_").unwrap(), "match__0");
+        assert_eq!(renamer.insert(3, "type").unwrap(), "type_0");
+        assert_eq!(renamer.get(&1).unwrap(), "match_");
+    }
+
     #[test]
     fn scoped() {
         let mut renamer = Renamer::new(&[]);
@@ -230,6 +230,11 @@ impl CommentTraverser {
         }
     }
 
+    /// The last position given to a reinserted comment so far.
+    pub fn position(&self) -> BytePos {
+        BytePos(self.store.current_position)
+    }
+
     /// Turn the traverser back into a `CommentStore`.
     pub fn into_comment_store(self) -> CommentStore {
         //        assert!(old_comments.is_empty());
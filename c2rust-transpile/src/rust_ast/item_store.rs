@@ -48,9 +48,12 @@ impl PathedMultiImports {
         self.0.entry(path).or_insert(MultiImport::new())
     }
 
-    pub fn into_items(self) -> Vec<P<Item>> {
+    /// Build the `use` items, sorted by path and leaf name so that they don't depend on the
+    /// order in which the imports were added.
+    pub fn into_items(mut self) -> Vec<P<Item>> {
         fn build_items((mut path, imports): (Vec<String>, MultiImport)) -> P<Item> {
             let mut leaves = imports.leaves;
+            leaves.sort();
             let attrs = imports
                 .attrs
                 .unwrap_or_else(|| mk());
@@ -64,6 +67,7 @@ impl PathedMultiImports {
             }
         }
 
+        self.0.sort_keys();
         self.0.into_iter().map(build_items).collect()
     }
 }
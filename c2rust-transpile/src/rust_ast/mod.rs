@@ -1,5 +1,6 @@
 pub mod comment_store;
 pub mod item_store;
+pub mod path_roots;
 pub mod traverse;
 
 use syntax_pos::{BytePos, Span};
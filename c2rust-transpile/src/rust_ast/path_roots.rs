@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use syntax::ast::*;
+use syntax::parse::token::{Nonterminal, Token, TokenKind};
+use syntax::ptr::P;
+use syntax::symbol::kw;
+use syntax::tokenstream::{TokenStream, TokenTree};
+use syntax::visit::{self, Visitor};
+
+/// Collect the first segment of every path in `items`, e.g., `libc` for `libc::c_int` or
+/// `::libc::c_int`. These are the names `use` items must bring into scope for the paths to
+/// resolve. Paths inside macro invocations are included: those in interpolated AST fragments
+/// exactly, and those spelled out in tokens as every identifier followed by `::`.
+pub fn path_roots(items: &[P<Item>]) -> HashSet<String> {
+    let mut roots = PathRoots(HashSet::new());
+    for item in items {
+        roots.visit_item(item);
+    }
+    roots.0
+}
+
+struct PathRoots(HashSet<String>);
+
+impl PathRoots {
+    /// Visit an AST fragment interpolated into a macro invocation. The fragment is owned by
+    /// the token stream rather than the AST, so it needs a visitor of its own.
+    fn visit_nonterminal(&mut self, nt: &Nonterminal) {
+        let mut roots = PathRoots(HashSet::new());
+        match *nt {
+            Nonterminal::NtItem(ref item) => roots.visit_item(item),
+            Nonterminal::NtBlock(ref block) => roots.visit_block(block),
+            Nonterminal::NtStmt(ref stmt) => roots.visit_stmt(stmt),
+            Nonterminal::NtPat(ref pat) => roots.visit_pat(pat),
+            Nonterminal::NtExpr(ref expr) | Nonterminal::NtLiteral(ref expr) => {
+                roots.visit_expr(expr)
+            }
+            Nonterminal::NtTy(ref ty) => roots.visit_ty(ty),
+            Nonterminal::NtPath(ref path) => roots.visit_path(path, DUMMY_NODE_ID),
+            _ => {}
+        }
+        self.0.extend(roots.0);
+    }
+}
+
+impl<'a> Visitor<'a> for PathRoots {
+    fn visit_path(&mut self, path: &'a Path, _id: NodeId) {
+        let root = path.segments.iter().find(|seg| seg.ident.name != kw::PathRoot);
+        if let Some(seg) = root {
+            self.0.insert(seg.ident.to_string());
+        }
+        visit::walk_path(self, path);
+    }
+
+    fn visit_mac(&mut self, mac: &'a Mac) {
+        self.visit_path(&mac.path, DUMMY_NODE_ID);
+        self.visit_tts(mac.tts.clone());
+    }
+
+    fn visit_tts(&mut self, tts: TokenStream) {
+        let mut trees = tts.trees().peekable();
+        while let Some(tt) = trees.next() {
+            match tt {
+                TokenTree::Token(Token { kind: TokenKind::Ident(name, _), .. }) => {
+                    if let Some(TokenTree::Token(Token { kind: TokenKind::ModSep, .. })) =
+                        trees.peek()
+                    {
+                        self.0.insert(name.to_string());
+                    }
+                }
+                TokenTree::Token(Token { kind: TokenKind::Interpolated(nt), .. }) => {
+                    self.visit_nonterminal(&nt)
+                }
+                TokenTree::Delimited(_, _, tts) => self.visit_tts(tts),
+                _ => {}
+            }
+        }
+    }
+}
@@ -11,6 +11,9 @@ impl<'c> Translation<'c> {
     /// directly) the resulting translated assembly statements will be unlikely to work
     /// without further manual translation. The translator will properly translate
     /// the arguments to the assembly statement, however.
+    ///
+    /// If `goto_selector` is set, it names a variable that is passed to the
+    /// assembly as an additional register output after all other outputs.
     pub fn convert_asm(
         &self,
         ctx: ExprContext,
@@ -20,6 +23,7 @@ impl<'c> Translation<'c> {
         inputs: &[AsmOperand],
         outputs: &[AsmOperand],
         clobbers: &[String],
+        goto_selector: Option<&str>,
     ) -> Result<Vec<Stmt>, TranslationError> {
         if !self.tcfg.translate_asm {
             return Err(TranslationError::generic(
@@ -159,6 +163,14 @@ impl<'c> Translation<'c> {
                 push_expr(&mut tokens, mk().lit_expr(mk().str_lit(constraints)));
                 push_expr(&mut tokens, mk().paren_expr(result));
             }
+
+            if let (true, Some(selector)) = (is_output, goto_selector) {
+                if !first {
+                    tokens.push(TokenTree::token(token::Comma, DUMMY_SP))
+                }
+                push_expr(&mut tokens, mk().lit_expr(mk().str_lit("=r")));
+                push_expr(&mut tokens, mk().paren_expr(mk().ident_expr(selector)));
+            }
         }
 
         // Clobbers
@@ -193,4 +205,152 @@ impl<'c> Translation<'c> {
 
         Ok(stmts)
     }
+    /// Convert an `asm goto` statement that can jump to one of `num_labels`
+    /// labels. Rust inline assembly can't jump out of the assembly, so we
+    /// instead give each label operand a local label inside the assembly that
+    /// records the label's 1-based index in a selector register and resumes
+    /// after the assembly. The selector is 0 if the assembly falls through.
+    ///
+    /// Returns the translated statements and the selector expression, which
+    /// the caller branches on.
+    pub fn convert_asm_goto(
+        &self,
+        ctx: ExprContext,
+        span: Span,
+        is_volatile: bool,
+        asm: &str,
+        inputs: &[AsmOperand],
+        outputs: &[AsmOperand],
+        clobbers: &[String],
+        num_labels: usize,
+        goto_supported: bool,
+    ) -> Result<(Vec<Stmt>, P<Expr>), TranslationError> {
+        if !self.tcfg.translate_asm {
+            return Err(TranslationError::generic(
+                "Inline assembly tranlationg not enabled.",
+            ));
+        }
+        if !goto_supported {
+            return Err(TranslationError::generic(
+                "asm goto is only supported on x86 and x86_64",
+            ));
+        }
+
+        let asm = asm_goto_template(asm, outputs.len(), inputs.len(), num_labels)?;
+
+        // `let selector: u32;`
+        let selector = self.renamer.borrow_mut().fresh();
+        let selector_local = mk().local(
+            mk().ident_pat(&selector),
+            Some(mk().path_ty(vec!["u32"])),
+            None as Option<P<Expr>>,
+        );
+        let mut stmts = vec![mk().local_stmt(P(selector_local))];
+        stmts.extend(self.convert_asm(
+            ctx,
+            span,
+            is_volatile,
+            &asm,
+            inputs,
+            outputs,
+            clobbers,
+            Some(&selector),
+        )?);
+
+        Ok((stmts, mk().ident_expr(&selector)))
+    }
+}
+
+/// Rewrite the LLVM assembly template of an `asm goto` statement so that it
+/// records which label it jumps to in a selector register, which becomes a new
+/// output operand after the existing outputs.
+fn asm_goto_template(
+    asm: &str,
+    num_outputs: usize,
+    num_inputs: usize,
+    num_labels: usize,
+) -> Result<String, TranslationError> {
+    let label_name = |label: &str| format!("${{:private}}c2rust_asm_goto_{}_${{:uid}}", label);
+    let selector = num_outputs;
+
+    // Operands are numbered outputs first, then inputs, then labels. Input
+    // operands move up by one to make room for the selector, and references to
+    // labels become references to our local labels.
+    let mut result = String::new();
+    let mut chars = asm.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        let braced = chars.peek() == Some(&'{');
+        if braced {
+            chars.next();
+        }
+        let mut digits = String::new();
+        while let Some(&d) = chars.peek() {
+            if !d.is_ascii_digit() {
+                break;
+            }
+            digits.push(d);
+            chars.next();
+        }
+        let mut modifier = String::new();
+        if braced {
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => modifier.push(c),
+                    None => return Err(format_err!("Unterminated operand in asm string").into()),
+                }
+            }
+        }
+
+        let operand = match digits.parse::<usize>() {
+            Ok(operand) => operand,
+            // `$$`, `${:uid}` and the like
+            Err(_) => {
+                result.push('$');
+                if braced {
+                    result.push('{');
+                    result.push_str(&modifier);
+                    result.push('}');
+                } else if chars.peek() == Some(&'$') {
+                    result.push('$');
+                    chars.next();
+                }
+                continue;
+            }
+        };
+
+        if operand >= num_outputs + num_inputs {
+            let label = operand - num_outputs - num_inputs;
+            if label >= num_labels {
+                return Err(format_err!("Invalid asm operand {}", operand).into());
+            }
+            result.push_str(&label_name(&label.to_string()));
+            continue;
+        }
+
+        let operand = if operand >= num_outputs {
+            operand + 1
+        } else {
+            operand
+        };
+        if braced {
+            result.push_str(&format!("${{{}{}}}", operand, modifier));
+        } else {
+            result.push_str(&format!("${}", operand));
+        }
+    }
+
+    result.push_str(&format!("\n\tmovl $$0, ${}", selector));
+    result.push_str(&format!("\n\tjmp {}", label_name("end")));
+    for label in 0..num_labels {
+        result.push_str(&format!("\n{}:", label_name(&label.to_string())));
+        result.push_str(&format!("\n\tmovl $${}, ${}", label + 1, selector));
+        result.push_str(&format!("\n\tjmp {}", label_name("end")));
+    }
+    result.push_str(&format!("\n{}:", label_name("end")));
+    Ok(result)
 }
@@ -0,0 +1,222 @@
+//! Translation of C23 bit-precise integer types, `_BitInt(N)`.
+//!
+//! A `_BitInt(N)` is stored in the smallest Rust integer with at least N bits. Arithmetic happens
+//! in that storage type, so every operation that can carry a value out of the low N bits is
+//! followed by a wrap back into range: unsigned values are masked and signed values are
+//! sign-extended from bit N - 1. Since values are always kept in range, comparisons and
+//! conversions out of a `_BitInt` need no special handling.
+//!
+//! With `--strict-bit-ints`, a `_BitInt(N)` is instead translated into a `#[repr(transparent)]`
+//! newtype over its storage integer, `BitIntN` or `UBitIntN`, defined in each output file that
+//! uses it:
+//!
+//! ```ignore
+//! #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+//! #[repr(transparent)]
+//! pub struct BitInt24(pub i32);
+//! impl BitInt24 {
+//!     pub const fn new(value: i32) -> Self {
+//!         BitInt24(value << 8 >> 8)
+//!     }
+//! }
+//! ```
+//!
+//! Operations then compute on the `.0` field, and their results are wrapped back into range by
+//! `new`.
+
+use super::output_style::int_lit_value;
+use super::*;
+
+impl<'c> Translation<'c> {
+    /// The signedness, width and storage width of `ctype` if it is a `_BitInt(N)` that can be
+    /// translated.
+    fn bit_int_type(&self, ctype: CTypeId) -> Option<(bool, u64, u64)> {
+        let kind = &self.ast_context.resolve_type(ctype).kind;
+        match (kind, kind.bit_int_storage_width()) {
+            (&CTypeKind::BitInt(signed, width), Some(storage)) => Some((signed, width, storage)),
+            _ => None,
+        }
+    }
+
+    /// The name of the newtype `ctype` translates into with `--strict-bit-ints`, imported into
+    /// the current module if needed.
+    fn strict_bit_int(&self, ctype: CTypeId) -> Option<String> {
+        if !self.tcfg.strict_bit_ints {
+            return None;
+        }
+        let (signed, width, _) = self.bit_int_type(ctype)?;
+        let name = bit_int_newtype_name(signed, width);
+        if let Some(cur_file) = *self.cur_file.borrow() {
+            self.import_bit_int_newtype(cur_file, &name);
+        }
+        Some(name)
+    }
+
+    /// Import the `_BitInt` newtype `name`, which is defined in the main module, into the module
+    /// of `file_id`.
+    pub fn import_bit_int_newtype(&self, file_id: FileId, name: &str) {
+        if file_id != self.main_file {
+            self.items
+                .borrow_mut()
+                .entry(file_id)
+                .or_insert(ItemStore::new())
+                .add_use(vec!["super".into()], name);
+        }
+    }
+
+    /// The integer type that stores values of `ctype`, if it is a `_BitInt(N)`.
+    pub fn bit_int_storage_ty(&self, ctype: CTypeId) -> Option<P<Ty>> {
+        let (signed, _, storage) = self.bit_int_type(ctype)?;
+        let prefix = if signed { "i" } else { "u" };
+        Some(mk().path_ty(vec![format!("{}{}", prefix, storage)]))
+    }
+
+    /// Wrap `val`, computed in the storage type of `ctype`, back into the range of `ctype` if it
+    /// is a `_BitInt(N)` narrower than its storage. With `--strict-bit-ints`, the value is
+    /// wrapped by the `new` constructor of the newtype instead. Any other value is returned
+    /// unchanged.
+    pub fn wrap_bit_int(&self, ctype: CTypeId, val: P<Expr>) -> P<Expr> {
+        if let Some(name) = self.strict_bit_int(ctype) {
+            return bit_int_new(&name, val);
+        }
+        match self.bit_int_type(ctype) {
+            Some((signed, width, storage)) => wrap_in_storage(signed, width, storage, val),
+            None => val,
+        }
+    }
+
+    /// Make a value of `ctype` out of `val`, a value of its storage type that is already in
+    /// range. Only strict `_BitInt` newtypes need their constructor.
+    pub fn bit_int_in_range(&self, ctype: CTypeId, val: P<Expr>) -> P<Expr> {
+        match self.strict_bit_int(ctype) {
+            Some(name) => bit_int_new(&name, val),
+            None => val,
+        }
+    }
+
+    /// The storage integer of `val`, a value of `ctype`: the `.0` field of a strict `_BitInt`
+    /// newtype. A newtype made out of an integer literal in range is unwrapped back into the
+    /// literal. Any other value is returned unchanged.
+    pub fn bit_int_storage(&self, ctype: CTypeId, val: P<Expr>) -> P<Expr> {
+        let name = match self.strict_bit_int(ctype) {
+            Some(name) => name,
+            None => return val,
+        };
+        if let ExprKind::Call(ref callee, ref args) = val.kind {
+            if pprust::expr_to_string(callee) != format!("{}::new", name) {
+                return mk().field_expr(val, "0");
+            }
+            let lit = match args.first().map(|arg| &arg.kind) {
+                Some(&ExprKind::Cast(ref inner, _)) => int_lit_value(inner),
+                Some(_) => int_lit_value(&args[0]),
+                None => None,
+            };
+            let (signed, width, _) = self.bit_int_type(ctype).unwrap();
+            let bound_bits = if signed { width - 1 } else { width };
+            if lit.map_or(false, |v| bound_bits >= 128 || v < 1u128 << bound_bits) {
+                return args[0].clone();
+            }
+        }
+        mk().field_expr(val, "0")
+    }
+
+    /// The newtypes of the `_BitInt`s used with `--strict-bit-ints`, and their constructors.
+    pub fn bit_int_newtype_items(&self) -> Vec<P<Item>> {
+        let newtypes = self.type_converter.borrow().bit_int_newtypes_used().clone();
+        let mut items = vec![];
+        for (signed, width) in newtypes {
+            let name = bit_int_newtype_name(signed, width);
+            let storage = CTypeKind::BitInt(signed, width)
+                .bit_int_storage_width()
+                .expect("Translated _BitInt without a storage type");
+            let prefix = if signed { "i" } else { "u" };
+            let storage_ty = || mk().path_ty(vec![format!("{}{}", prefix, storage)]);
+
+            let field = mk().pub_().enum_field(storage_ty());
+            items.push(
+                mk().pub_()
+                    .call_attr(
+                        "derive",
+                        vec![
+                            "Copy",
+                            "Clone",
+                            "PartialEq",
+                            "Eq",
+                            "PartialOrd",
+                            "Ord",
+                            "Hash",
+                            "Debug",
+                            "Default",
+                        ],
+                    )
+                    .call_attr("repr", vec!["transparent"])
+                    .struct_item(&name, vec![field], true),
+            );
+
+            let value = wrap_in_storage(signed, width, storage, mk().ident_expr("value"));
+            let decl = mk().fn_decl(
+                vec![mk().arg(storage_ty(), mk().ident_pat("value"))],
+                FunctionRetTy::Ty(mk().ident_ty("Self")),
+            );
+            let body = mk().call_expr(mk().ident_expr(&name), vec![value]);
+            let new = mk().pub_().const_().method_impl_item(
+                "new",
+                decl,
+                mk().block(vec![mk().expr_stmt(body)]),
+            );
+            items.push(mk().impl_item(mk().ident_ty(&name), vec![new]));
+        }
+        items
+    }
+
+    /// Check whether `expr` is an integer literal, possibly negated, whose value is already in
+    /// range for the `_BitInt` type `ctype`. Converting such a literal needs no wrapping.
+    pub fn literal_fits_bit_int(&self, ctype: CTypeId, expr: CExprId) -> bool {
+        let (signed, width) = match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::BitInt(signed, width) => (signed, width),
+            _ => return false,
+        };
+
+        let (negated, val) = match self.ast_context[expr].kind {
+            CExprKind::Literal(_, CLiteral::Integer(val, _)) => (false, val),
+            CExprKind::Unary(_, c_ast::UnOp::Negate, arg, _) => match self.ast_context[arg].kind {
+                CExprKind::Literal(_, CLiteral::Integer(val, _)) => (true, val),
+                _ => return false,
+            },
+            _ => return false,
+        };
+        let val = u128::from(val);
+
+        match (signed, negated) {
+            (false, false) => width >= 64 || val < 1u128 << width,
+            (false, true) => val == 0,
+            (true, false) => val < 1u128 << (width - 1),
+            (true, true) => val <= 1u128 << (width - 1),
+        }
+    }
+}
+
+/// Build the strict `_BitInt` newtype `name` out of `val` with its wrapping constructor.
+fn bit_int_new(name: &str, val: P<Expr>) -> P<Expr> {
+    mk().call_expr(mk().path_expr(vec![name, "new"]), vec![val])
+}
+
+/// Wrap `val`, a value of the `storage`-bit integer type, into the range of a `_BitInt(width)`:
+/// unsigned values are masked, and signed values sign-extended from bit `width - 1`.
+fn wrap_in_storage(signed: bool, width: u64, storage: u64, val: P<Expr>) -> P<Expr> {
+    if storage == width {
+        return val;
+    }
+
+    if signed {
+        // Move bit N - 1 into the sign bit, then shift back arithmetically
+        let shift = storage - width;
+        let shift_lit = || mk().lit_expr(mk().int_lit(shift.into(), LitIntType::Unsuffixed));
+        let shl = mk().binary_expr(BinOpKind::Shl, val, shift_lit());
+        mk().binary_expr(BinOpKind::Shr, shl, shift_lit())
+    } else {
+        let mask = (1u128 << width) - 1;
+        let mask = mk().lit_expr(mk().float_unsuffixed_lit(format!("0x{:x}", mask)));
+        mk().binary_expr(BinOpKind::BitAnd, val, mask)
+    }
+}
@@ -0,0 +1,753 @@
+//! Translation of Apple blocks, for `--translate-blocks`.
+//!
+//! A block pointer points to a block literal: a header holding the function that invokes the
+//! block, followed by the variables the block captured. The Blocks runtime moves literals to the
+//! heap with `Block_copy` and frees them with `Block_release`, calling the copy and dispose
+//! helpers listed in the descriptor the header points to.
+//!
+//! Block pointers are translated to `*mut libc::c_void`, and calls through them go through a
+//! helper that calls the invoke function in the header. A block literal that captures variables
+//! is translated to a Rust closure, stored in a `#[repr(C)]` struct that starts with the header
+//! and whose copy and dispose helpers clone and drop the closure. A block literal that captures
+//! nothing becomes a global block, a `static` header with an invoke function of its own, as clang
+//! compiles it. Variables declared `__block` are shared between the function declaring them and
+//! the blocks capturing them, so they are translated to an `Arc<UnsafeCell<T>>`.
+
+use super::*;
+
+/// The block literal has copy and dispose helpers
+const BLOCK_HAS_COPY_DISPOSE: u128 = 1 << 25;
+/// The block literal is a global block
+const BLOCK_IS_GLOBAL: u128 = 1 << 28;
+
+/// The names of the block header types, generated once per module:
+///
+/// ```no_run
+/// #[derive(Copy, Clone)]
+/// #[repr(C)]
+/// pub struct C2RustBlock {
+///     pub isa: *mut libc::c_void,
+///     pub flags: libc::c_int,
+///     pub reserved: libc::c_int,
+///     pub invoke: *mut libc::c_void,
+///     pub descriptor: *const C2RustBlockDescriptor,
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BlockShim {
+    header: String,
+    descriptor: String,
+    /// The descriptor of global blocks, which have no helpers
+    global_descriptor: String,
+}
+
+/// The names of the items for blocks of one type, generated once per module
+#[derive(Clone, Debug)]
+pub struct BlockSignature {
+    /// The `#[repr(C)]` struct of block literals holding a closure
+    literal: String,
+    /// The invoke function of such literals, which calls their closure
+    invoke: String,
+    /// The descriptor of such literals
+    descriptor: String,
+    /// The function calling a block of this type through its header
+    call: String,
+}
+
+fn void_ptr_ty() -> P<Ty> {
+    mk().mutbl().ptr_ty(mk().path_ty(vec!["libc", "c_void"]))
+}
+
+fn int_lit_expr(i: u128) -> P<Expr> {
+    mk().lit_expr(mk().int_lit(i, LitIntType::Unsuffixed))
+}
+
+/// `::std::<path>`, with generic arguments `args` on the last segment
+fn std_path(path: &[&str], args: Vec<P<Ty>>) -> Path {
+    let mut segments = vec![mk().path_segment(""), mk().path_segment("std")];
+    let (&last, init) = path.split_last().expect("empty path");
+    segments.extend(init.iter().map(|&seg| mk().path_segment(seg)));
+    if args.is_empty() {
+        segments.push(mk().path_segment(last));
+    } else {
+        segments.push(mk().path_segment_with_args(last, mk().angle_bracketed_args(args)));
+    }
+    mk().path(segments)
+}
+
+/// `*(ptr as *mut ty)`
+fn deref_as(ptr: &str, ty: &str) -> P<Expr> {
+    let cast = mk().cast_expr(mk().ident_expr(ptr), mk().mutbl().ptr_ty(mk().ident_ty(ty)));
+    mk().unary_expr(ast::UnOp::Deref, cast)
+}
+
+fn size_of_expr(ty: &str) -> P<Expr> {
+    let size_of = std_path(&["mem", "size_of"], vec![mk().ident_ty(ty)]);
+    let call = mk().call_expr(mk().path_expr(size_of), vec![] as Vec<P<Expr>>);
+    mk().cast_expr(call, mk().path_ty(vec!["libc", "c_ulong"]))
+}
+
+impl<'c> Translation<'c> {
+    /// Find the variables declared `__block`, which are captured by reference.
+    pub fn find_byref_vars(&self) -> IndexSet<CDeclId> {
+        self.ast_context
+            .iter_exprs()
+            .filter_map(|(_, expr)| match expr.kind {
+                CExprKind::Block { ref captures, .. } => Some(captures),
+                _ => None,
+            })
+            .flat_map(|captures| captures.iter())
+            .filter(|&&(_, by_ref)| by_ref)
+            .map(|&(decl_id, _)| decl_id)
+            .collect()
+    }
+
+    /// Convert a block literal of block pointer type `typ`, which takes `params`, runs `body`
+    /// and captures the variables in `captures`, to a block pointer.
+    pub fn convert_block_literal(
+        &self,
+        ctx: ExprContext,
+        typ: CQualTypeId,
+        params: &[CDeclId],
+        body: CStmtId,
+        captures: &[(CDeclId, bool)],
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        if !self.tcfg.translate_blocks {
+            return Err(
+                format_err!("Apple blocks are only translated with --translate-blocks").into(),
+            );
+        }
+        let shim = self.block_shim();
+        let (ret, _) = self.block_prototype(typ.ctype)?;
+        let body_ctx = ctx.not_static().set_const(false);
+
+        if captures.is_empty() {
+            // A global block:
+            //
+            // static mut global_block: C2RustBlock = C2RustBlock {
+            //     isa: unsafe { &_NSConcreteGlobalBlock as *const [*mut libc::c_void; 32] as *mut libc::c_void },
+            //     flags: 1 << 28,
+            //     reserved: 0,
+            //     invoke: global_block_invoke as *mut libc::c_void,
+            //     descriptor: &GLOBAL_BLOCK_DESCRIPTOR,
+            // };
+            let name = self.renamer.borrow_mut().pick_name_root("global_block");
+            let invoke = self
+                .renamer
+                .borrow_mut()
+                .pick_name_root(&format!("{}_invoke", name));
+
+            let (mut args, ret_ty, block) =
+                self.convert_block_body(body_ctx, &invoke, params, ret, body)?;
+            args.insert(0, mk().arg(void_ptr_ty(), mk().wild_pat()));
+            let invoke_fn =
+                mk().unsafe_()
+                    .abi("C")
+                    .fn_item(&invoke, mk().fn_decl(args, ret_ty), block);
+
+            let isa_array_ty = mk().array_ty(void_ptr_ty(), int_lit_expr(32));
+            let isa = mk().cast_expr(
+                mk().cast_expr(
+                    mk().addr_of_expr(mk().ident_expr("_NSConcreteGlobalBlock")),
+                    mk().ptr_ty(isa_array_ty),
+                ),
+                void_ptr_ty(),
+            );
+            let isa = mk().block_expr(mk().unsafe_().block(vec![mk().expr_stmt(isa)]));
+            let header = mk().struct_expr(
+                vec![shim.header.as_str()],
+                vec![
+                    mk().field("isa", isa),
+                    mk().field("flags", int_lit_expr(BLOCK_IS_GLOBAL)),
+                    mk().field("reserved", int_lit_expr(0)),
+                    mk().field(
+                        "invoke",
+                        mk().cast_expr(mk().ident_expr(&invoke), void_ptr_ty()),
+                    ),
+                    mk().field(
+                        "descriptor",
+                        mk().addr_of_expr(mk().ident_expr(&shim.global_descriptor)),
+                    ),
+                ],
+            );
+            let global = mk()
+                .mutbl()
+                .static_item(&name, mk().ident_ty(&shim.header), header);
+            self.add_block_items(vec![invoke_fn, global]);
+
+            // Statics can't borrow mutably, but the runtime never writes to global blocks
+            let val = if ctx.is_static {
+                mk().cast_expr(
+                    mk().cast_expr(
+                        mk().addr_of_expr(mk().ident_expr(&name)),
+                        mk().ptr_ty(mk().ident_ty(&shim.header)),
+                    ),
+                    void_ptr_ty(),
+                )
+            } else {
+                mk().cast_expr(
+                    mk().cast_expr(
+                        mk().mutbl().addr_of_expr(mk().ident_expr(&name)),
+                        mk().mutbl().ptr_ty(mk().ident_ty(&shim.header)),
+                    ),
+                    void_ptr_ty(),
+                )
+            };
+            return Ok(WithStmts::new_unsafe_val(val));
+        }
+
+        // A block literal on the stack, holding a closure:
+        //
+        // let mut block_literal = C2RustBlockLiteral {
+        //     header: C2RustBlock { .. },
+        //     closure: ::std::sync::Arc::new({
+        //         let counter = counter.clone();
+        //         move |mut x: libc::c_int| -> libc::c_int { .. }
+        //     }),
+        // };
+        if ctx.is_static {
+            return Err(format_err!("Blocks capturing variables can't be static").into());
+        }
+        let sig = self.block_signature(typ.ctype)?;
+        let name = self.renamer.borrow_mut().pick_name("block_literal");
+
+        let (args, ret_ty, block) = self.convert_block_body(body_ctx, &name, params, ret, body)?;
+        let closure = mk().closure_expr(
+            CaptureBy::Value,
+            Movability::Movable,
+            mk().fn_decl(args, ret_ty),
+            mk().block_expr(block),
+        );
+
+        // Variables captured by reference are shared through their `Arc`
+        let mut stmts = vec![];
+        for &(decl_id, by_ref) in captures {
+            if !by_ref {
+                continue;
+            }
+            let var = self
+                .renamer
+                .borrow()
+                .get(&decl_id)
+                .ok_or_else(|| format_err!("Did not find name for captured variable"))?;
+            let clone =
+                mk().method_call_expr(mk().ident_expr(&var), "clone", vec![] as Vec<P<Expr>>);
+            let local = mk().local(mk().ident_pat(&var), None as Option<P<Ty>>, Some(clone));
+            stmts.push(mk().local_stmt(P(local)));
+        }
+        stmts.push(mk().expr_stmt(closure));
+        let closure = mk().call_expr(
+            mk().path_expr(std_path(&["sync", "Arc", "new"], vec![])),
+            vec![mk().block_expr(mk().block(stmts))],
+        );
+
+        let isa = mk().cast_expr(
+            mk().method_call_expr(
+                mk().ident_expr("_NSConcreteStackBlock"),
+                "as_mut_ptr",
+                vec![] as Vec<P<Expr>>,
+            ),
+            void_ptr_ty(),
+        );
+        let header = mk().struct_expr(
+            vec![shim.header.as_str()],
+            vec![
+                mk().field("isa", isa),
+                mk().field("flags", int_lit_expr(BLOCK_HAS_COPY_DISPOSE)),
+                mk().field("reserved", int_lit_expr(0)),
+                mk().field(
+                    "invoke",
+                    mk().cast_expr(mk().ident_expr(&sig.invoke), void_ptr_ty()),
+                ),
+                mk().field(
+                    "descriptor",
+                    mk().addr_of_expr(mk().ident_expr(&sig.descriptor)),
+                ),
+            ],
+        );
+        let literal = mk().struct_expr(
+            vec![sig.literal.as_str()],
+            vec![mk().field("header", header), mk().field("closure", closure)],
+        );
+        let local = mk().local(
+            mk().set_mutbl("mut").ident_pat(&name),
+            None as Option<P<Ty>>,
+            Some(literal),
+        );
+
+        let val = mk().cast_expr(
+            mk().cast_expr(
+                mk().mutbl().addr_of_expr(mk().ident_expr(&name)),
+                mk().mutbl().ptr_ty(mk().ident_ty(&sig.literal)),
+            ),
+            void_ptr_ty(),
+        );
+        let mut val = WithStmts::new(vec![mk().local_stmt(P(local))], val);
+        val.set_unsafe();
+        Ok(val)
+    }
+
+    /// Convert a call of `func` with `args`, if `func` is a block pointer.
+    pub fn convert_block_call(
+        &self,
+        ctx: ExprContext,
+        func: CExprId,
+        args: &[CExprId],
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        let func_ty = match self.ast_context[func].kind.get_type() {
+            Some(func_ty) => func_ty,
+            None => return Ok(None),
+        };
+        match self.ast_context.resolve_type(func_ty).kind {
+            CTypeKind::BlockPointer(..) => {}
+            _ => return Ok(None),
+        }
+        if !self.tcfg.translate_blocks {
+            return Err(format_err!(
+                "Calls to Apple blocks are only translated with --translate-blocks"
+            )
+            .into());
+        }
+
+        let sig = self.block_signature(func_ty)?;
+        let block = self.convert_expr(ctx.used(), func)?;
+        let args = self.convert_call_args(ctx.used(), None, args)?;
+        let call = block.and_then(|block| -> Result<_, TranslationError> {
+            Ok(args.map(|mut args| {
+                args.insert(0, block);
+                mk().call_expr(mk().ident_expr(&sig.call), args)
+            }))
+        })?;
+        Ok(Some(call))
+    }
+
+    /// Convert the declaration of a local variable declared `__block`, named `rust_name`,
+    /// to an `Arc<UnsafeCell<T>>` that the blocks capturing it share.
+    pub fn convert_byref_local(
+        &self,
+        ctx: ExprContext,
+        rust_name: &str,
+        initializer: Option<CExprId>,
+        typ: CQualTypeId,
+    ) -> Result<cfg::DeclStmtInfo, TranslationError> {
+        let mut stmts = self.compute_variable_array_sizes(ctx, typ.ctype)?;
+        let (ty, _, init) = self.convert_variable(ctx, initializer, typ)?;
+        let mut init = init?;
+        stmts.append(init.stmts_mut());
+        let init = init.into_value();
+        let zeroed = self
+            .implicit_default_expr(typ.ctype, false)?
+            .to_pure_expr()
+            .expect("Expected decl initializer to not have any statements");
+
+        let cell_ty = mk().path_ty(std_path(&["cell", "UnsafeCell"], vec![ty]));
+        let shared_ty = mk().path_ty(std_path(&["sync", "Arc"], vec![cell_ty]));
+        let share = |val: P<Expr>| {
+            let cell = mk().call_expr(
+                mk().path_expr(std_path(&["cell", "UnsafeCell", "new"], vec![])),
+                vec![val],
+            );
+            mk().call_expr(
+                mk().path_expr(std_path(&["sync", "Arc", "new"], vec![])),
+                vec![cell],
+            )
+        };
+        let local = |val: P<Expr>| {
+            let pat = mk().ident_pat(rust_name);
+            mk().local_stmt(P(mk().local(
+                pat,
+                Some(shared_ty.clone()),
+                Some(share(val)),
+            )))
+        };
+
+        let assign = mk().assign_expr(self.byref_var(rust_name), init.clone());
+        let mut assign_stmts = stmts.clone();
+        assign_stmts.push(mk().semi_stmt(assign));
+
+        let mut decl_and_assign = stmts;
+        decl_and_assign.push(local(init));
+
+        Ok(cfg::DeclStmtInfo::new(
+            vec![local(zeroed)],
+            assign_stmts,
+            decl_and_assign,
+        ))
+    }
+
+    /// The value of the `__block` variable `rust_name`, `(*counter.get())`
+    pub fn byref_var(&self, rust_name: &str) -> P<Expr> {
+        let get = mk().method_call_expr(mk().ident_expr(rust_name), "get", vec![] as Vec<P<Expr>>);
+        mk().unary_expr(ast::UnOp::Deref, get)
+    }
+
+    /// Convert the parameters and body of a block into those of a function or closure.
+    fn convert_block_body(
+        &self,
+        ctx: ExprContext,
+        name: &str,
+        params: &[CDeclId],
+        ret: CQualTypeId,
+        body: CStmtId,
+    ) -> Result<(Vec<Param>, FunctionRetTy, P<Block>), TranslationError> {
+        self.with_scope(|| {
+            let mut args = vec![];
+            let mut stmts = vec![];
+            for &param in params {
+                let (ident, typ) = match self.ast_context[param].kind {
+                    CDeclKind::Variable { ref ident, typ, .. } => (ident, typ),
+                    _ => return Err(format_err!("Block parameter is not a variable").into()),
+                };
+                let (ty, mutbl, _) = self.convert_variable(ctx, None, typ)?;
+                let pat = if ident.is_empty() {
+                    mk().wild_pat()
+                } else {
+                    let var = self
+                        .renamer
+                        .borrow_mut()
+                        .insert(param, ident)
+                        .ok_or_else(|| {
+                            format_err!("Failed to insert block parameter '{}'", ident)
+                        })?;
+                    mk().set_mutbl(mutbl).ident_pat(var)
+                };
+                args.push(mk().arg(ty, pat));
+                stmts.append(&mut self.compute_variable_array_sizes(ctx, typ.ctype)?);
+            }
+
+            let (ret_ty, implicit_ret) = match self.ast_context.resolve_type(ret.ctype).kind {
+                CTypeKind::Void => (
+                    FunctionRetTy::Default(DUMMY_SP),
+                    cfg::ImplicitReturnType::Void,
+                ),
+                _ => (
+                    FunctionRetTy::Ty(self.convert_type(ret.ctype)?),
+                    cfg::ImplicitReturnType::NoImplicitReturnType,
+                ),
+            };
+
+            let body_ids = match self.ast_context[body].kind {
+                CStmtKind::Compound(ref stmts) => stmts,
+                _ => return Err(format_err!("Block body is not a compound statement").into()),
+            };
+            stmts.append(&mut self.convert_function_body(ctx, name, body_ids, implicit_ret)?);
+            Ok((args, ret_ty, stmts_block(stmts)))
+        })
+    }
+
+    /// The return and parameter types of the function a block pointer type points to.
+    fn block_prototype(
+        &self,
+        block_ty: CTypeId,
+    ) -> Result<(CQualTypeId, Vec<CQualTypeId>), TranslationError> {
+        let fn_ty = match self.ast_context.resolve_type(block_ty).kind {
+            CTypeKind::BlockPointer(fn_ty) => fn_ty,
+            _ => return Err(format_err!("Block expression is not a block pointer").into()),
+        };
+        match self.ast_context.resolve_type(fn_ty.ctype).kind {
+            CTypeKind::Function(ret, ref params, false, _, _) => Ok((ret, params.clone())),
+            _ => Err(format_err!("Variadic blocks are not supported").into()),
+        }
+    }
+
+    fn add_block_items(&self, items: Vec<P<Item>>) {
+        let mut all_items = self.items.borrow_mut();
+        let store = all_items
+            .entry(self.cur_file())
+            .or_insert_with(ItemStore::new);
+        for item in items {
+            store.add_item(item);
+        }
+    }
+
+    /// Get the names of the block header types, generating them in the current module the first
+    /// time they are requested.
+    fn block_shim(&self) -> BlockShim {
+        let cur_file = self.cur_file();
+        if let Some(shim) = self.block_shims.borrow().get(&cur_file) {
+            return shim.clone();
+        }
+
+        let shim = {
+            let mut renamer = self.renamer.borrow_mut();
+            BlockShim {
+                header: renamer.pick_name_root("C2RustBlock"),
+                descriptor: renamer.pick_name_root("C2RustBlockDescriptor"),
+                global_descriptor: renamer.pick_name_root("GLOBAL_BLOCK_DESCRIPTOR"),
+            }
+        };
+
+        let int_ty = || mk().path_ty(vec!["libc", "c_int"]);
+        let ulong_ty = || mk().path_ty(vec!["libc", "c_ulong"]);
+        let helper_ty = |nargs: usize| {
+            let args = (0..nargs)
+                .map(|_| mk().arg(void_ptr_ty(), mk().wild_pat()))
+                .collect();
+            let fn_ty = mk()
+                .unsafe_()
+                .abi("C")
+                .barefn_ty(mk().fn_decl(args, FunctionRetTy::Default(DUMMY_SP)));
+            mk().path_ty(vec![mk().path_segment_with_args(
+                "Option",
+                mk().angle_bracketed_args(vec![fn_ty]),
+            )])
+        };
+
+        let header = mk()
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone"])
+            .call_attr("repr", vec!["C"])
+            .struct_item(
+                &shim.header,
+                vec![
+                    mk().pub_().struct_field("isa", void_ptr_ty()),
+                    mk().pub_().struct_field("flags", int_ty()),
+                    mk().pub_().struct_field("reserved", int_ty()),
+                    mk().pub_().struct_field("invoke", void_ptr_ty()),
+                    mk().pub_()
+                        .struct_field("descriptor", mk().ptr_ty(mk().ident_ty(&shim.descriptor))),
+                ],
+                false,
+            );
+        let descriptor = mk()
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone"])
+            .call_attr("repr", vec!["C"])
+            .struct_item(
+                &shim.descriptor,
+                vec![
+                    mk().pub_().struct_field("reserved", ulong_ty()),
+                    mk().pub_().struct_field("size", ulong_ty()),
+                    mk().pub_().struct_field("copy_helper", helper_ty(2)),
+                    mk().pub_().struct_field("dispose_helper", helper_ty(1)),
+                ],
+                false,
+            );
+        let global_descriptor = mk().static_item(
+            &shim.global_descriptor,
+            mk().ident_ty(&shim.descriptor),
+            mk().struct_expr(
+                vec![shim.descriptor.as_str()],
+                vec![
+                    mk().field("reserved", int_lit_expr(0)),
+                    mk().field("size", size_of_expr(&shim.header)),
+                    mk().field("copy_helper", mk().ident_expr("None")),
+                    mk().field("dispose_helper", mk().ident_expr("None")),
+                ],
+            ),
+        );
+        self.add_block_items(vec![header, descriptor, global_descriptor]);
+
+        // The classes of block literals, from the Blocks runtime
+        let isa_array_ty = || mk().array_ty(void_ptr_ty(), int_lit_expr(32));
+        {
+            let mut items = self.items.borrow_mut();
+            let store = items.entry(cur_file).or_insert_with(ItemStore::new);
+            for &isa in &["_NSConcreteStackBlock", "_NSConcreteGlobalBlock"] {
+                store.add_foreign_item(mk().mutbl().static_foreign_item(isa, isa_array_ty()));
+            }
+        }
+
+        self.block_shims.borrow_mut().insert(cur_file, shim.clone());
+        shim
+    }
+
+    /// Get the names of the items for blocks of type `block_ty`, generating them in the current
+    /// module the first time they are requested:
+    ///
+    /// ```no_run
+    /// #[repr(C)]
+    /// pub struct C2RustBlockLiteral {
+    ///     pub header: C2RustBlock,
+    ///     pub closure: ::std::sync::Arc<dyn Fn(libc::c_int) -> libc::c_int>,
+    /// }
+    /// unsafe extern "C" fn block_invoke(block: *mut libc::c_void, a0: libc::c_int) -> libc::c_int {
+    ///     ((*(block as *mut C2RustBlockLiteral)).closure)(a0)
+    /// }
+    /// unsafe extern "C" fn block_copy(dst: *mut libc::c_void, src: *mut libc::c_void) {
+    ///     ::std::ptr::write(&mut (*(dst as *mut C2RustBlockLiteral)).closure,
+    ///                       (*(src as *mut C2RustBlockLiteral)).closure.clone());
+    /// }
+    /// unsafe extern "C" fn block_dispose(src: *mut libc::c_void) {
+    ///     ::std::ptr::drop_in_place(&mut (*(src as *mut C2RustBlockLiteral)).closure);
+    /// }
+    /// static BLOCK_DESCRIPTOR: C2RustBlockDescriptor = C2RustBlockDescriptor { .. };
+    /// unsafe fn block_call(block: *mut libc::c_void, a0: libc::c_int) -> libc::c_int {
+    ///     let invoke: unsafe extern "C" fn(*mut libc::c_void, libc::c_int) -> libc::c_int =
+    ///         ::std::mem::transmute((*(block as *mut C2RustBlock)).invoke);
+    ///     invoke(block, a0)
+    /// }
+    /// ```
+    fn block_signature(&self, block_ty: CTypeId) -> Result<BlockSignature, TranslationError> {
+        let cur_file = self.cur_file();
+        if let Some(sig) = self.block_signatures.borrow().get(&(cur_file, block_ty)) {
+            return Ok(sig.clone());
+        }
+
+        let shim = self.block_shim();
+        let (ret, params) = self.block_prototype(block_ty)?;
+        let param_tys = params
+            .iter()
+            .map(|param| self.convert_type(param.ctype))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ret_ty = match self.ast_context.resolve_type(ret.ctype).kind {
+            CTypeKind::Void => None,
+            _ => Some(self.convert_type(ret.ctype)?),
+        };
+        let fn_ret_ty = || match ret_ty {
+            Some(ref ty) => FunctionRetTy::Ty(ty.clone()),
+            None => FunctionRetTy::Default(DUMMY_SP),
+        };
+
+        let (sig, copy, dispose) = {
+            let mut renamer = self.renamer.borrow_mut();
+            let sig = BlockSignature {
+                literal: renamer.pick_name_root("C2RustBlockLiteral"),
+                invoke: renamer.pick_name_root("block_invoke"),
+                descriptor: renamer.pick_name_root("BLOCK_DESCRIPTOR"),
+                call: renamer.pick_name_root("block_call"),
+            };
+            let copy = renamer.pick_name_root("block_copy");
+            let dispose = renamer.pick_name_root("block_dispose");
+            (sig, copy, dispose)
+        };
+
+        let arg_names = (0..params.len())
+            .map(|i| format!("a{}", i))
+            .collect::<Vec<_>>();
+        let fn_args = |first: &str| {
+            let mut args = vec![mk().arg(void_ptr_ty(), mk().ident_pat(first))];
+            for (name, ty) in arg_names.iter().zip(&param_tys) {
+                args.push(mk().arg(ty.clone(), mk().ident_pat(name)));
+            }
+            args
+        };
+        let call_args = |first: Option<&str>| {
+            first
+                .into_iter()
+                .chain(arg_names.iter().map(|name| name.as_str()))
+                .map(|name| mk().ident_expr(name))
+                .collect::<Vec<_>>()
+        };
+
+        // `dyn Fn(A) -> R`
+        let fn_trait = mk().path(vec![mk().path_segment_with_args(
+            "Fn",
+            ParenthesizedArgs {
+                output: ret_ty.clone(),
+                ..mk().parenthesized_args(param_tys.clone())
+            },
+        )]);
+        let bound = GenericBound::Trait(
+            PolyTraitRef::new(vec![], fn_trait, DUMMY_SP),
+            TraitBoundModifier::None,
+        );
+        let closure_ty = P(mk().ty(TyKind::TraitObject(vec![bound], TraitObjectSyntax::Dyn)));
+
+        let literal = mk().pub_().call_attr("repr", vec!["C"]).struct_item(
+            &sig.literal,
+            vec![
+                mk().pub_()
+                    .struct_field("header", mk().ident_ty(&shim.header)),
+                mk().pub_().struct_field(
+                    "closure",
+                    mk().path_ty(std_path(&["sync", "Arc"], vec![closure_ty])),
+                ),
+            ],
+            false,
+        );
+
+        let closure_of = |ptr: &str| mk().field_expr(deref_as(ptr, &sig.literal), "closure");
+        let call_closure = mk().call_expr(mk().paren_expr(closure_of("block")), call_args(None));
+        let invoke = mk().unsafe_().abi("C").fn_item(
+            &sig.invoke,
+            mk().fn_decl(fn_args("block"), fn_ret_ty()),
+            mk().block(vec![mk().expr_stmt(call_closure)]),
+        );
+
+        let void_fn_decl = |args: Vec<Param>| mk().fn_decl(args, FunctionRetTy::Default(DUMMY_SP));
+        let write = mk().call_expr(
+            mk().path_expr(std_path(&["ptr", "write"], vec![])),
+            vec![
+                mk().mutbl().addr_of_expr(closure_of("dst")),
+                mk().method_call_expr(closure_of("src"), "clone", vec![] as Vec<P<Expr>>),
+            ],
+        );
+        let copy_helper = mk().unsafe_().abi("C").fn_item(
+            &copy,
+            void_fn_decl(vec![
+                mk().arg(void_ptr_ty(), mk().ident_pat("dst")),
+                mk().arg(void_ptr_ty(), mk().ident_pat("src")),
+            ]),
+            mk().block(vec![mk().semi_stmt(write)]),
+        );
+        let drop_closure = mk().call_expr(
+            mk().path_expr(std_path(&["ptr", "drop_in_place"], vec![])),
+            vec![mk().mutbl().addr_of_expr(closure_of("src"))],
+        );
+        let dispose_helper = mk().unsafe_().abi("C").fn_item(
+            &dispose,
+            void_fn_decl(vec![mk().arg(void_ptr_ty(), mk().ident_pat("src"))]),
+            mk().block(vec![mk().semi_stmt(drop_closure)]),
+        );
+
+        let some =
+            |name: &str| mk().call_expr(mk().ident_expr("Some"), vec![mk().ident_expr(name)]);
+        let descriptor = mk().static_item(
+            &sig.descriptor,
+            mk().ident_ty(&shim.descriptor),
+            mk().struct_expr(
+                vec![shim.descriptor.as_str()],
+                vec![
+                    mk().field("reserved", int_lit_expr(0)),
+                    mk().field("size", size_of_expr(&sig.literal)),
+                    mk().field("copy_helper", some(&copy)),
+                    mk().field("dispose_helper", some(&dispose)),
+                ],
+            ),
+        );
+
+        let mut invoke_arg_tys = vec![void_ptr_ty()];
+        invoke_arg_tys.extend(param_tys.iter().cloned());
+        let invoke_args = invoke_arg_tys
+            .into_iter()
+            .map(|ty| mk().arg(ty, mk().wild_pat()))
+            .collect();
+        let invoke_ty = mk().unsafe_().abi("C").barefn_ty(
+            mk().fn_decl(
+                invoke_args,
+                FunctionRetTy::Ty(
+                    ret_ty
+                        .clone()
+                        .unwrap_or_else(|| mk().tuple_ty(vec![] as Vec<P<Ty>>)),
+                ),
+            ),
+        );
+        let transmute = mk().call_expr(
+            mk().path_expr(std_path(&["mem", "transmute"], vec![])),
+            vec![mk().field_expr(deref_as("block", &shim.header), "invoke")],
+        );
+        let invoke_local = mk().local(mk().ident_pat("invoke"), Some(invoke_ty), Some(transmute));
+        let call = mk().unsafe_().fn_item(
+            &sig.call,
+            mk().fn_decl(fn_args("block"), fn_ret_ty()),
+            mk().block(vec![
+                mk().local_stmt(P(invoke_local)),
+                mk().expr_stmt(mk().call_expr(mk().ident_expr("invoke"), call_args(Some("block")))),
+            ]),
+        );
+
+        self.add_block_items(vec![
+            literal,
+            invoke,
+            copy_helper,
+            dispose_helper,
+            descriptor,
+            call,
+        ]);
+        self.block_signatures
+            .borrow_mut()
+            .insert((cur_file, block_ty), sig.clone());
+        Ok(sig)
+    }
+}
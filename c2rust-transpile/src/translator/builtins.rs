@@ -3,6 +3,50 @@
 
 use super::*;
 
+/// How a checked function of `_FORTIFY_SOURCE` checks the size of its destination
+#[derive(Copy, Clone)]
+enum FortifyCheck {
+    /// `(dst, src, len, objsz)`, fails if `len > objsz`
+    Len,
+    /// `(dst, src, objsz)`, fails if `src` and its terminator don't fit
+    StrCpy,
+    /// `(dst, src, objsz)`, fails if `dst` followed by `src` doesn't fit
+    StrCat,
+    /// `(dst, flag, objsz, fmt, ...)`, fails if the formatted string doesn't fit
+    Sprintf,
+    /// `(dst, maxlen, flag, objsz, fmt, ...)`, fails if `maxlen > objsz`
+    Snprintf,
+    /// `(flag, fmt, ...)`, never fails
+    Printf,
+    /// `(stream, flag, fmt, ...)`, never fails
+    Fprintf,
+}
+
+/// Look up the unchecked libc function a `__*_chk` function or `__builtin___*_chk` builtin
+/// stands for
+fn fortify_function(name: &str) -> Option<(&'static str, FortifyCheck)> {
+    let name = if name.starts_with("__builtin___") { &name[9..] } else { name };
+    let func = match name {
+        "__memcpy_chk" => ("memcpy", FortifyCheck::Len),
+        "__memmove_chk" => ("memmove", FortifyCheck::Len),
+        "__memset_chk" => ("memset", FortifyCheck::Len),
+        "__strncpy_chk" => ("strncpy", FortifyCheck::Len),
+        "__strcpy_chk" => ("strcpy", FortifyCheck::StrCpy),
+        "__strcat_chk" => ("strcat", FortifyCheck::StrCat),
+        "__sprintf_chk" => ("sprintf", FortifyCheck::Sprintf),
+        "__snprintf_chk" => ("snprintf", FortifyCheck::Snprintf),
+        "__printf_chk" => ("printf", FortifyCheck::Printf),
+        "__fprintf_chk" => ("fprintf", FortifyCheck::Fprintf),
+        _ => return None,
+    };
+    Some(func)
+}
+
+/// Is `name` a checked function of `_FORTIFY_SOURCE` that we translate inline?
+pub fn is_fortify_function(name: &str) -> bool {
+    fortify_function(name).is_some()
+}
+
 impl<'c> Translation<'c> {
     /// Convert a call to a builtin function to a Rust expression
     pub fn convert_builtin(
@@ -189,7 +233,41 @@ impl<'c> Translation<'c> {
             // If the target does not support data prefetch, the address expression is evaluated if
             // it includes side effects but no other code is generated and GCC does not issue a warning.
             // void __builtin_prefetch (const void *addr, ...);
-            "__builtin_prefetch" => self.convert_expr(ctx.unused(), args[0]),
+            "__builtin_prefetch" => {
+                // The optional arguments select a read (0) or write (1) prefetch and the degree
+                // of temporal locality from 0 to 3, and must be constants
+                let constant_arg = |idx: usize, default: u64| match args.get(idx) {
+                    None => Some(default),
+                    Some(&arg) => match *self.ast_context.resolve_expr_value(arg) {
+                        CExprKind::Literal(_, CLiteral::Integer(i, _)) => Some(i),
+                        _ => None,
+                    },
+                };
+                let (rw, locality) = match (constant_arg(1, 0), constant_arg(2, 3)) {
+                    (Some(rw), Some(locality)) if self.tcfg.translate_prefetch => (rw, locality),
+                    _ => return self.convert_expr(ctx.unused(), args[0]),
+                };
+
+                self.use_feature("core_intrinsics");
+
+                let prefetch_name = if rw == 0 {
+                    "prefetch_read_data"
+                } else {
+                    "prefetch_write_data"
+                };
+                let prefetch_func =
+                    mk().path_expr(vec!["", std_or_core, "intrinsics", prefetch_name]);
+                let locality = mk().lit_expr(mk().int_lit(locality.into(), "i32"));
+                let addr = self.convert_expr(ctx.used(), args[0])?;
+                addr.and_then(|addr| {
+                    let call_expr = mk().call_expr(prefetch_func, vec![addr, locality]);
+                    self.convert_side_effects_expr(
+                        ctx,
+                        WithStmts::new_val(call_expr),
+                        "Builtin is not supposed to be used",
+                    )
+                })
+            }
 
             "__builtin_memcpy"
             | "__builtin_memchr"
@@ -277,6 +355,10 @@ impl<'c> Translation<'c> {
             "__builtin_va_copy" => {
                  if ctx.is_unused() && args.len() == 2 {
                      if let Some((_dst_va_id, _src_va_id)) = self.match_vacopy(args[0], args[1]) {
+                         if !self.translate_valist() {
+                             return self.convert_va_copy_abi(ctx, args[0], args[1]);
+                         }
+
                          let dst = self.convert_expr(ctx.expect_valistimpl().used(), args[0])?;
                          let src = self.convert_expr(ctx.expect_valistimpl().used(), args[1])?;
 
@@ -582,10 +664,75 @@ impl<'c> Translation<'c> {
                 ))
             }
 
+            // Checked variants of the string and stdio functions used by `_FORTIFY_SOURCE`
+            name if is_fortify_function(name) => self.convert_fortify_call(ctx, fexp, args),
+
             _ => Err(format_translation_err!(self.ast_context.display_loc(src_loc), "Unimplemented builtin {}", builtin_name)),
         }
     }
 
+    /// Convert a call through a type-generic math macro (as defined by `<tgmath.h>` using
+    /// `_Generic`) into the equivalent `f32`/`f64` method call, e.g. `x.sqrt()`.
+    ///
+    /// Clang has already resolved the `_Generic` to the libm function of the right width, so
+    /// we only need to map that function to its method. Returns `None` if the callee is not a
+    /// `_Generic` selection of a known libm function.
+    pub fn convert_tgmath_call(
+        &self,
+        ctx: ExprContext,
+        func: CExprId,
+        args: &[CExprId],
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        // The float methods live in std, so we keep calling libm when building no_std
+        if self.tcfg.emit_no_std {
+            return Ok(None);
+        }
+
+        let fexp = match self.ast_context[func].kind {
+            CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) => fexp,
+            _ => return Ok(None),
+        };
+        let selected = match self.ast_context.generic_selection_result(fexp) {
+            Some(selected) => selected,
+            None => return Ok(None),
+        };
+        let decl_id = match self.ast_context[selected].kind {
+            CExprKind::DeclRef(_, decl_id, _) => decl_id,
+            _ => return Ok(None),
+        };
+        let name = match self.ast_context[decl_id].kind {
+            // Only map library functions, not ones the translation unit defines itself
+            CDeclKind::Function { ref name, body: None, .. } => name,
+            _ => return Ok(None),
+        };
+
+        // `long double` variants (suffix `l`) have no native Rust equivalent
+        let base = name.trim_end_matches('f');
+        let (method, arity) = match base {
+            "sqrt" | "cbrt" | "sin" | "cos" | "tan" | "asin" | "acos" | "atan" | "sinh"
+            | "cosh" | "tanh" | "asinh" | "acosh" | "atanh" | "exp" | "exp2" | "expm1"
+            | "log2" | "log10" | "floor" | "ceil" | "round" | "trunc" => (base, 1),
+            "log" => ("ln", 1),
+            "log1p" => ("ln_1p", 1),
+            "fabs" => ("abs", 1),
+            "atan2" | "hypot" | "copysign" => (base, 2),
+            "pow" => ("powf", 2),
+            "fmax" => ("max", 2),
+            "fmin" => ("min", 2),
+            _ => return Ok(None),
+        };
+        if args.len() != arity {
+            return Ok(None);
+        }
+
+        let args = self.convert_exprs(ctx.used(), args)?;
+        Ok(Some(args.map(|args| {
+            let mut args = args.into_iter();
+            let receiver = args.next().unwrap();
+            mk().method_call_expr(receiver, method, args.collect::<Vec<_>>())
+        })))
+    }
+
     // This translation logic handles converting code that uses
     // https://gcc.gnu.org/onlinedocs/gcc/Integer-Overflow-Builtins.html
     fn convert_overflow_arith(
@@ -656,4 +803,224 @@ impl<'c> Translation<'c> {
             }
         })
     }
+
+    /// Is `fexp` a reference to a library function we translate with `convert_fortify_call`?
+    pub fn is_fortify_callee(&self, fexp: CExprId) -> bool {
+        self.fortify_callee(fexp).is_some()
+    }
+
+    fn fortify_callee(&self, fexp: CExprId) -> Option<(&'static str, FortifyCheck)> {
+        let decl_id = match self.ast_context[fexp].kind {
+            CExprKind::DeclRef(_, decl_id, _) => decl_id,
+            _ => return None,
+        };
+        match self.ast_context[decl_id].kind {
+            CDeclKind::Function { ref name, body: None, .. } => fortify_function(name),
+            _ => None,
+        }
+    }
+
+    /// Converts a call to one of the checked functions `_FORTIFY_SOURCE` substitutes for the
+    /// string and stdio functions, e.g. `__builtin___memcpy_chk` or `__printf_chk`.
+    ///
+    /// When the size of the destination is unknown, the call is lowered to the unchecked libc
+    /// function. Otherwise, the check is spelled out and aborts the program when it fails, so
+    /// the translation does not depend on glibc's fortify symbols.
+    pub fn convert_fortify_call(
+        &self,
+        ctx: ExprContext,
+        fexp: CExprId,
+        args: &[CExprId],
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let (func_name, check) = self
+            .fortify_callee(fexp)
+            .ok_or_else(|| TranslationError::generic("Expected a fortified function"))?;
+        let (min_args, flag_arg, objsz_arg) = match check {
+            FortifyCheck::Len => (4, None, Some(3)),
+            FortifyCheck::StrCpy | FortifyCheck::StrCat => (3, None, Some(2)),
+            FortifyCheck::Sprintf => (4, Some(1), Some(2)),
+            FortifyCheck::Snprintf => (5, Some(2), Some(3)),
+            FortifyCheck::Printf => (2, Some(0), None),
+            FortifyCheck::Fprintf => (3, Some(1), None),
+        };
+        if args.len() < min_args {
+            return Err(TranslationError::generic("Missing arguments to fortified function"));
+        }
+
+        // The flag only tells glibc how strictly to check format strings, so it is dropped
+        let mut flag_stmts = vec![];
+        if let Some(flag) = flag_arg {
+            if !self.ast_context.is_expr_pure(args[flag]) {
+                flag_stmts = self.convert_expr(ctx.unused(), args[flag])?.into_stmts();
+            }
+        }
+
+        let call_args: Vec<CExprId> = args
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| Some(i) != flag_arg && Some(i) != objsz_arg)
+            .map(|(_, &arg)| arg)
+            .collect();
+        let call_args = self.convert_exprs(ctx.used(), &call_args)?;
+        let objsz = match objsz_arg {
+            Some(objsz) => self.fortify_object_size(ctx, args[objsz])?,
+            None => None,
+        };
+        let objsz = WithStmts::with_stmts_opt(objsz);
+
+        let mut val = call_args.and_then(|call_args| {
+            objsz.and_then(|objsz| self.convert_fortify_check(func_name, check, call_args, objsz))
+        })?;
+        val.prepend_stmts(flag_stmts);
+
+        if ctx.is_used() {
+            Ok(val)
+        } else {
+            val.and_then(|call| {
+                Ok(WithStmts::new(
+                    vec![mk().semi_stmt(call)],
+                    self.panic_or_err(&format!("__{}_chk not used", func_name)),
+                ))
+            })
+        }
+    }
+
+    /// Builds the call to the unchecked `func_name`, preceded by the check of the destination
+    /// size `objsz` if it is known
+    fn convert_fortify_check(
+        &self,
+        func_name: &str,
+        check: FortifyCheck,
+        mut args: Vec<P<Expr>>,
+        objsz: Option<P<Expr>>,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let size_t = || mk().path_ty(vec!["libc", "size_t"]);
+        let libc_fn = |name: &str| mk().path_expr(vec!["libc", name]);
+
+        // Arguments that are both checked and passed on are only evaluated once
+        let mut stmts = vec![];
+        let mut bind = |val: P<Expr>| {
+            let name = self.renamer.borrow_mut().fresh();
+            stmts.push(mk().local_stmt(P(mk().local(
+                mk().ident_pat(&name),
+                None as Option<P<Ty>>,
+                Some(val),
+            ))));
+            mk().ident_expr(name)
+        };
+
+        // The Rust type of `size_t` in the translation may differ from the one libc uses
+        match check {
+            FortifyCheck::Len => args[2] = mk().cast_expr(args[2].clone(), size_t()),
+            FortifyCheck::Snprintf => args[1] = mk().cast_expr(args[1].clone(), size_t()),
+            FortifyCheck::Fprintf => {
+                let file = mk().mutbl().ptr_ty(mk().path_ty(vec!["libc", "FILE"]));
+                args[0] = mk().cast_expr(args[0].clone(), file);
+            }
+            _ => {}
+        }
+
+        let objsz = match objsz {
+            Some(objsz) => mk().cast_expr(objsz, size_t()),
+            None => return Ok(WithStmts::new_val(mk().call_expr(libc_fn(func_name), args))),
+        };
+        let (cond, call) = match check {
+            FortifyCheck::Len => {
+                args[2] = bind(args[2].clone());
+                let cond = mk().binary_expr(BinOpKind::Gt, args[2].clone(), objsz);
+                (cond, mk().call_expr(libc_fn(func_name), args))
+            }
+            FortifyCheck::StrCpy | FortifyCheck::StrCat => {
+                args[1] = bind(args[1].clone());
+                let mut len = mk().call_expr(libc_fn("strlen"), vec![args[1].clone()]);
+                if let FortifyCheck::StrCat = check {
+                    args[0] = bind(args[0].clone());
+                    let dst_len = mk().call_expr(libc_fn("strlen"), vec![args[0].clone()]);
+                    len = mk().binary_expr(BinOpKind::Add, dst_len, len);
+                }
+                let cond = mk().binary_expr(BinOpKind::Ge, len, objsz);
+                (cond, mk().call_expr(libc_fn(func_name), args))
+            }
+            FortifyCheck::Sprintf => {
+                // Write at most `objsz` bytes, and fail if that truncated the output
+                let objsz = bind(objsz);
+                args.insert(1, objsz.clone());
+                let written = bind(mk().call_expr(libc_fn("snprintf"), args));
+                let zero = mk().lit_expr(mk().int_lit(0, ""));
+                let succeeded = mk().binary_expr(BinOpKind::Ge, written.clone(), zero);
+                let len = mk().cast_expr(written.clone(), size_t());
+                let truncated = mk().binary_expr(BinOpKind::Ge, len, objsz);
+                (mk().binary_expr(BinOpKind::And, succeeded, truncated), written)
+            }
+            FortifyCheck::Snprintf => {
+                args[1] = bind(args[1].clone());
+                let cond = mk().binary_expr(BinOpKind::Gt, args[1].clone(), objsz);
+                (cond, mk().call_expr(libc_fn(func_name), args))
+            }
+            FortifyCheck::Printf | FortifyCheck::Fprintf => {
+                return Err(TranslationError::generic("Unexpected object size for printf"))
+            }
+        };
+
+        let abort = mk().call_expr(libc_fn("abort"), vec![] as Vec<P<Expr>>);
+        let fail = mk().ifte_expr(
+            cond,
+            mk().block(vec![mk().semi_stmt(abort)]),
+            None as Option<P<Expr>>,
+        );
+        stmts.push(mk().semi_stmt(fail));
+        Ok(WithStmts::new(stmts, call))
+    }
+
+    /// The size of the destination a fortified call checks, or `None` if it is unknown
+    fn fortify_object_size(
+        &self,
+        ctx: ExprContext,
+        objsz: CExprId,
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        match *self.ast_context.resolve_expr_value(objsz) {
+            // `(size_t)-1`
+            CExprKind::Literal(_, CLiteral::Integer(i, _)) if i == u64::max_value() => Ok(None),
+            CExprKind::Unary(_, c_ast::UnOp::Negate, one, _) => {
+                match *self.ast_context.resolve_expr_value(one) {
+                    CExprKind::Literal(_, CLiteral::Integer(1, _)) => Ok(None),
+                    _ => self.convert_expr(ctx.used(), objsz).map(Some),
+                }
+            }
+
+            // We translate `__builtin_object_size` to its fallback, which would fail every
+            // check, so it only gives a size for an array variable
+            CExprKind::Call(_, func, ref call_args)
+                if self.is_builtin_object_size(func) && !call_args.is_empty() =>
+            {
+                let typ = match *self.ast_context.resolve_expr_value(call_args[0]) {
+                    CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                        CDeclKind::Variable { typ, .. } => typ.ctype,
+                        _ => return Ok(None),
+                    },
+                    _ => return Ok(None),
+                };
+                match self.ast_context.resolve_type(typ).kind {
+                    CTypeKind::ConstantArray(..) => self.compute_size_of_type(ctx, typ).map(Some),
+                    _ => Ok(None),
+                }
+            }
+
+            _ => self.convert_expr(ctx.used(), objsz).map(Some),
+        }
+    }
+
+    fn is_builtin_object_size(&self, func: CExprId) -> bool {
+        let fexp = match self.ast_context[func].kind {
+            CExprKind::ImplicitCast(_, fexp, CastKind::BuiltinFnToFnPtr, _, _) => fexp,
+            _ => return false,
+        };
+        match self.ast_context[fexp].kind {
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Function { ref name, .. } => name == "__builtin_object_size",
+                _ => false,
+            },
+            _ => false,
+        }
+    }
 }
@@ -0,0 +1,128 @@
+//! Translation of local variables declared with `__attribute__((cleanup(f)))`.
+//!
+//! C calls `f(&x)` whenever such a variable `x` goes out of scope. We store the variable in a
+//! guard struct generated for `f`, which pairs the value with a flag telling whether the cleanup
+//! is still due, and refer to the variable through the guard's first field:
+//!
+//! ```no_run
+//! pub struct free_buf_guard(pub *mut libc::c_char, pub bool);
+//! impl free_buf_guard {
+//!     pub unsafe fn cleanup(&mut self) {
+//!         if self.1 {
+//!             self.1 = false;
+//!             free_buf(&mut self.0);
+//!         }
+//!     }
+//! }
+//! impl Drop for free_buf_guard {
+//!     fn drop(&mut self) {
+//!         unsafe { self.cleanup() }
+//!     }
+//! }
+//! ```
+//!
+//! The relooper hoists declarations out of their blocks, so dropping the guard alone would run
+//! the cleanup too late. Instead, the CFG builder calls `cleanup` wherever control leaves the
+//! scope of the variable by falling off the end of its block, `break`, `continue` or `goto`, and
+//! the `Drop` impl takes care of `return`s. The flag makes sure each initialization is cleaned
+//! up exactly once, and a guard whose declaration was never reached doesn't clean up at all.
+//! Cleanups of variables declared in the first clause of a `for` loop only run on `return`.
+
+use super::*;
+
+impl<'c> Translation<'c> {
+    /// Get the name of the guard struct calling the cleanup function of a local variable of
+    /// the given type, generating it in the current module the first time it is requested.
+    pub fn cleanup_guard(
+        &self,
+        fn_id: CDeclId,
+        typ: CQualTypeId,
+    ) -> Result<String, TranslationError> {
+        let cur_file = self.cur_file();
+        if let Some(name) = self.cleanup_guards.borrow().get(&(cur_file, fn_id)) {
+            return Ok(name.clone());
+        }
+
+        let fn_name = self
+            .renamer
+            .borrow()
+            .get(&fn_id)
+            .ok_or_else(|| format_err!("Cleanup function not declared"))?;
+        if self.tcfg.reorganize_definitions {
+            self.add_import(cur_file, fn_id, &fn_name);
+        }
+        let name = self
+            .renamer
+            .borrow_mut()
+            .pick_name_root(&format!("{}_guard", fn_name));
+
+        let ty = self.convert_type(typ.ctype)?;
+        let fields = vec![
+            mk().pub_().enum_field(ty),
+            mk().pub_().enum_field(mk().ident_ty("bool")),
+        ];
+        let struct_item = mk().pub_().struct_item(&name, fields, true);
+
+        let self_field = |field: &str| mk().field_expr(mk().ident_expr("self"), field);
+        let self_mut_ref = || mk().self_arg(SelfKind::Region(None, Mutability::Mutable));
+        let unit = FunctionRetTy::Default(DUMMY_SP);
+
+        // if self.1 { self.1 = false; f(&mut self.0); }
+        let call = mk().call_expr(
+            mk().path_expr(vec![fn_name]),
+            vec![mk().set_mutbl("mut").addr_of_expr(self_field("0"))],
+        );
+        let disarm = mk().assign_expr(self_field("1"), mk().lit_expr(mk().bool_lit(false)));
+        let body = mk().ifte_expr(
+            self_field("1"),
+            mk().block(vec![mk().semi_stmt(disarm), mk().semi_stmt(call)]),
+            None as Option<P<Expr>>,
+        );
+        let cleanup = mk().pub_().unsafe_().method_impl_item(
+            "cleanup",
+            mk().fn_decl(vec![self_mut_ref()], unit.clone()),
+            mk().block(vec![mk().expr_stmt(body)]),
+        );
+        let impl_item = mk().impl_item(mk().ident_ty(&name), vec![cleanup]);
+
+        let call_cleanup =
+            mk().method_call_expr(mk().ident_expr("self"), "cleanup", Vec::<P<Expr>>::new());
+        let unsafe_block = mk().unsafe_().block(vec![mk().expr_stmt(call_cleanup)]);
+        let drop = mk().method_impl_item(
+            "drop",
+            mk().fn_decl(vec![self_mut_ref()], unit),
+            mk().block(vec![mk().expr_stmt(mk().block_expr(unsafe_block))]),
+        );
+        let drop_item = mk().impl_trait_item(vec!["Drop"], mk().ident_ty(&name), vec![drop]);
+
+        let mut items = self.items.borrow_mut();
+        let store = items.entry(cur_file).or_insert_with(ItemStore::new);
+        store.add_item(struct_item);
+        store.add_item(impl_item);
+        store.add_item(drop_item);
+        self.cleanup_guards
+            .borrow_mut()
+            .insert((cur_file, fn_id), name.clone());
+
+        Ok(name)
+    }
+
+    /// Build the statement running the cleanup of a local variable, if it is still due.
+    pub fn cleanup_local_stmt(&self, decl_id: CDeclId) -> Stmt {
+        let name = self
+            .renamer
+            .borrow()
+            .get(&decl_id)
+            .expect("Cleanup variable not named");
+        let mut guard = mk().ident_expr(name);
+        if self.aligned_locals.borrow().contains(&decl_id) {
+            guard = mk().field_expr(guard, "0");
+        }
+        mk().semi_stmt(mk().method_call_expr(guard, "cleanup", Vec::<P<Expr>>::new()))
+    }
+
+    /// Check if a local variable is stored in a cleanup guard.
+    pub fn is_cleanup_local(&self, decl_id: CDeclId) -> bool {
+        self.cleanup_locals.borrow().contains(&decl_id)
+    }
+}
@@ -102,8 +102,9 @@ impl<'c> NodeVisitor for CommentLocator<'c> {
         // Don't traverse into macro object replacement expressions, as they are
         // in other places.
         if let SomeId::Decl(id) = id {
-            if let CDeclKind::MacroObject{..} = self.ast_context[id].kind {
-                return false;
+            match self.ast_context[id].kind {
+                CDeclKind::MacroObject { .. } | CDeclKind::MacroFunction { .. } => return false,
+                _ => {}
             }
         }
 
@@ -0,0 +1,94 @@
+//! Translation of `const` global aggregates into immutable `static`s.
+//!
+//! Globals are normally translated to `static mut`, since C code may write to them and a
+//! `static` holding raw pointers isn't `Sync`. Arrays and structs that are `const` in C, such
+//! as a `static const struct entry table[] = { ... };` lookup table, can't be written, so
+//! they are translated to plain `static`s, which can be read and indexed without `unsafe`.
+//! Struct and union element types holding pointers, e.g., to string literals, are marked
+//! `Sync` so that the table can be shared. Tables of bare pointers, whose element type can't
+//! be marked, and tables initialized at startup stay `static mut`.
+
+use super::*;
+
+impl<'c> Translation<'c> {
+    /// Find the `const` global aggregates that can be translated to immutable `static`s, and
+    /// the records that need to be `Sync` for it.
+    pub fn find_const_tables(&self) -> (IndexSet<CDeclId>, IndexSet<CRecordId>) {
+        let mut tables = IndexSet::new();
+        let mut sync_records = IndexSet::new();
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            let (initializer, typ) = match decl.kind {
+                CDeclKind::Variable {
+                    has_static_duration: true,
+                    has_thread_duration: false,
+                    is_defn: true,
+                    initializer: Some(initializer),
+                    typ,
+                    ..
+                } if typ.qualifiers.is_const => (initializer, typ),
+                _ => continue,
+            };
+            if self.static_initializer_is_uncompilable(Some(initializer), typ) {
+                continue;
+            }
+
+            let elt = match self.const_table_element(typ.ctype) {
+                Some(elt) => elt,
+                None => continue,
+            };
+            if self.contains_pointers(elt) {
+                match self.ast_context.resolve_type(elt).kind {
+                    CTypeKind::Struct(record_id) | CTypeKind::Union(record_id) => {
+                        sync_records.insert(record_id);
+                    }
+                    _ => continue,
+                }
+            }
+            tables.insert(decl_id);
+        }
+        (tables, sync_records)
+    }
+
+    /// Get the element type of an array, or the type itself for a struct or union.
+    fn const_table_element(&self, ctype: CTypeId) -> Option<CTypeId> {
+        match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::ConstantArray(elt, _) => Some(self.const_table_element(elt).unwrap_or(elt)),
+            CTypeKind::Struct(_) | CTypeKind::Union(_) => Some(ctype),
+            _ => None,
+        }
+    }
+
+    /// Check if values of type `ctype` hold raw pointers, which keep them from being `Sync`.
+    /// Function pointers are translated to `Option<fn>`, which is.
+    fn contains_pointers(&self, ctype: CTypeId) -> bool {
+        match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::Pointer(_) => !self.ast_context.is_function_pointer(ctype),
+            CTypeKind::ConstantArray(elt, _) | CTypeKind::IncompleteArray(elt) => {
+                self.contains_pointers(elt)
+            }
+            CTypeKind::Struct(record_id) | CTypeKind::Union(record_id) => self
+                .record_field_types(record_id)
+                .into_iter()
+                .any(|field_ty| self.contains_pointers(field_ty)),
+            _ => false,
+        }
+    }
+
+    /// Check if `decl_id` is translated to an immutable `static`.
+    pub fn is_const_table(&self, decl_id: CDeclId) -> bool {
+        self.const_tables.contains(&decl_id)
+    }
+
+    /// Mark a record that is the element type of a `const` table as `Sync`, i.e.,
+    /// `unsafe impl Sync for entry {}`.
+    pub fn convert_sync_impl(&self, decl_id: CDeclId) -> Option<P<Item>> {
+        if !self.sync_records.contains(&decl_id) {
+            return None;
+        }
+        let name = self.type_converter.borrow().resolve_decl_name(decl_id)?;
+        Some(
+            mk().unsafe_()
+                .impl_trait_item(vec!["Sync"], mk().path_ty(vec![name]), vec![]),
+        )
+    }
+}
@@ -0,0 +1,504 @@
+//! Translation of C enums into Rust `enum`s with `--translate-enums`.
+//!
+//! By default, a C enum becomes a type alias for its underlying integer type plus one constant
+//! per enumerator. With `--translate-enums`, enums whose enumerators all have distinct values
+//! become Rust enums instead: `#[repr(C)]` for `int`-sized enums, or the matching primitive
+//! representation, e.g. `#[repr(u8)]`, for packed enums and enums with a fixed underlying
+//! type. The constants are kept, but now refer to the variants, so the rest of the translation
+//! can keep using them.
+//!
+//! A C enum may hold any value of its underlying type, which a Rust enum can't, so enums that
+//! are given other values than their enumerators (see `find_int_valued_enums`) keep the
+//! constants. The integers that are still converted into a Rust enum, such as the results of
+//! extern functions, go through a generated `from_int` function that checks the value.
+
+use super::*;
+use std::collections::HashSet;
+
+impl<'c> Translation<'c> {
+    /// Check if the C enum `enum_id` is translated into a Rust `enum`. Enums that can't be
+    /// represented as one, such as enums with duplicate values, enums that are given values
+    /// other than their enumerators (see `find_int_valued_enums`), and enums that foreign code
+    /// can store values of (see `find_ffi_int_enums`), fall back to constants. So do enums
+    /// without an enumerator for `0`, which zero-initialized objects hold.
+    pub fn is_rust_enum(&self, enum_id: CEnumId) -> bool {
+        if !self.tcfg.translate_enums
+            || self.int_valued_enums.contains(&enum_id)
+            || self.ffi_safety.int_enums.contains_key(&enum_id)
+        {
+            return false;
+        }
+
+        let variants = match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { ref variants, .. } => variants,
+            _ => return false,
+        };
+        if variants.is_empty() || self.rust_enum_repr(enum_id).is_none() {
+            return false;
+        }
+
+        // Unnamed enums are just a way to declare integer constants
+        match self.type_converter.borrow().resolve_decl_name(enum_id) {
+            Some(ref name) if !name.starts_with("C2RustUnnamed") => {}
+            _ => return false,
+        }
+
+        let mut values = HashSet::new();
+        let distinct = variants.iter().all(|&variant_id| match self.ast_context[variant_id].kind {
+            CDeclKind::EnumConstant { value, .. } => {
+                rust_enum_discriminant(value).map_or(false, |value| values.insert(value))
+            }
+            _ => false,
+        });
+        distinct && values.contains(&0)
+    }
+
+    /// Find the enums that are given values other than their enumerators: enums converted
+    /// from an integer that isn't the value of one of their enumerators, such as a combination
+    /// of flags or the result of arithmetic, enums updated by compound assignments, increments
+    /// or decrements, and enums accessed through pointers to other types.
+    pub fn find_int_valued_enums(&self) -> IndexSet<CEnumId> {
+        let mut enums = IndexSet::new();
+        for (_, expr) in self.ast_context.iter_exprs() {
+            match expr.kind {
+                CExprKind::ImplicitCast(ty, inner, kind, _, _)
+                | CExprKind::ExplicitCast(ty, inner, kind, _, _) => {
+                    if let Some(enum_id) = self.enum_type(ty.ctype) {
+                        if !self.is_enum_value(enum_id, inner) {
+                            enums.insert(enum_id);
+                        }
+                    } else if kind == CastKind::BitCast {
+                        // Pointers to enums converted from or to pointers to other types, other
+                        // than `void *`, which allocations are
+                        let source_ty = self.ast_context[inner].kind.get_type();
+                        let pointees = source_ty
+                            .into_iter()
+                            .chain(Some(ty.ctype))
+                            .filter_map(|ty| match self.ast_context.resolve_type(ty).kind {
+                                CTypeKind::Pointer(pointee) => Some(pointee.ctype),
+                                _ => None,
+                            })
+                            .filter(|&pointee| {
+                                self.ast_context.resolve_type(pointee).kind != CTypeKind::Void
+                            })
+                            .collect::<Vec<_>>();
+                        if let [from, to] = pointees[..] {
+                            let (from_enum, to_enum) = (self.enum_type(from), self.enum_type(to));
+                            if from_enum != to_enum {
+                                enums.extend(from_enum);
+                                enums.extend(to_enum);
+                            }
+                        }
+                    }
+                }
+                CExprKind::Unary(_, op, operand, _) => match op {
+                    c_ast::UnOp::PreIncrement
+                    | c_ast::UnOp::PostIncrement
+                    | c_ast::UnOp::PreDecrement
+                    | c_ast::UnOp::PostDecrement => {
+                        enums.extend(self.enum_type_of(operand));
+                    }
+                    _ => {}
+                },
+                CExprKind::Binary(_, op, lhs, ..) if op.underlying_assignment().is_some() => {
+                    enums.extend(self.enum_type_of(lhs));
+                }
+                _ => {}
+            }
+        }
+        enums
+    }
+
+    /// Check if `expr` is always the value of one of the enumerators of `enum_id`: an
+    /// expression of the enum type, or a constant with the value of an enumerator.
+    fn is_enum_value(&self, enum_id: CEnumId, expr: CExprId) -> bool {
+        match self.ast_context[expr].kind {
+            CExprKind::ImplicitCast(_, inner, CastKind::IntegralCast, _, _)
+            | CExprKind::Paren(_, inner) => self.is_enum_value(enum_id, inner),
+            CExprKind::Conditional(_, _, lhs, rhs) => {
+                self.is_enum_value(enum_id, lhs) && self.is_enum_value(enum_id, rhs)
+            }
+            _ if self.enum_type_of(expr) == Some(enum_id) => true,
+            _ => self
+                .enum_value_of(expr)
+                .map_or(false, |value| self.enum_has_value(enum_id, value)),
+        }
+    }
+
+    /// Get the value of `expr` if it is an enumerator or an integer literal, possibly negated.
+    fn enum_value_of(&self, expr: CExprId) -> Option<i64> {
+        match self.ast_context[expr].kind {
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::EnumConstant { value, .. } => rust_enum_discriminant(value),
+                _ => None,
+            },
+            CExprKind::Literal(_, CLiteral::Integer(value, _)) if value <= i64::max_value() as u64 => {
+                Some(value as i64)
+            }
+            CExprKind::Unary(_, c_ast::UnOp::Negate, inner, _) => {
+                self.enum_value_of(inner).map(|value| -value)
+            }
+            CExprKind::ImplicitCast(_, inner, CastKind::IntegralCast, _, _)
+            | CExprKind::Paren(_, inner) => self.enum_value_of(inner),
+            _ => None,
+        }
+    }
+
+    fn enum_has_value(&self, enum_id: CEnumId, value: i64) -> bool {
+        match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { ref variants, .. } => variants.iter().any(|&variant_id| {
+                match self.ast_context[variant_id].kind {
+                    CDeclKind::EnumConstant { value: v, .. } => rust_enum_discriminant(v) == Some(value),
+                    _ => false,
+                }
+            }),
+            _ => false,
+        }
+    }
+
+    fn enum_type(&self, ty: CTypeId) -> Option<CEnumId> {
+        match self.ast_context.resolve_type(ty).kind {
+            CTypeKind::Enum(enum_id) => Some(enum_id),
+            _ => None,
+        }
+    }
+
+    fn enum_type_of(&self, expr: CExprId) -> Option<CEnumId> {
+        self.enum_type(self.ast_context[expr].kind.get_type()?)
+    }
+
+    /// Get the representation of the Rust enum for `enum_id` that matches the size and
+    /// signedness of its underlying type. `#[repr(C)]` enums have the size of a C `int`; other
+    /// underlying types need a primitive representation, except for `char` and `long`, whose
+    /// sizes and signedness vary between platforms.
+    fn rust_enum_repr(&self, enum_id: CEnumId) -> Option<&'static str> {
+        let integral_type = match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { integral_type: Some(integral_type), .. } => integral_type,
+            _ => return None,
+        };
+        match self.ast_context.resolve_type(integral_type.ctype).kind {
+            CTypeKind::Int | CTypeKind::UInt => Some("C"),
+            CTypeKind::SChar => Some("i8"),
+            CTypeKind::UChar => Some("u8"),
+            CTypeKind::Short => Some("i16"),
+            CTypeKind::UShort => Some("u16"),
+            CTypeKind::LongLong => Some("i64"),
+            CTypeKind::ULongLong => Some("u64"),
+            _ => None,
+        }
+    }
+
+    /// Convert a C enum declaration into a Rust enum with the representation given by
+    /// `rust_enum_repr`.
+    pub fn convert_rust_enum(
+        &self,
+        enum_id: CEnumId,
+        span: Span,
+    ) -> Result<ConvertedDecl, TranslationError> {
+        let enum_name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(enum_id)
+            .expect("Enums should already be renamed");
+        let variants = match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { ref variants, .. } => variants,
+            _ => return Err(format_err!("{:?} is not an enum", enum_id).into()),
+        };
+
+        let variants = variants
+            .iter()
+            .map(|&variant_id| {
+                let name = self
+                    .renamer
+                    .borrow()
+                    .get(&variant_id)
+                    .expect("Enum constant not named");
+                let value = match self.ast_context[variant_id].kind {
+                    CDeclKind::EnumConstant { value, .. } => rust_enum_discriminant(value),
+                    _ => None,
+                }
+                .ok_or_else(|| format_err!("Bad variant {:?} of enum {}", variant_id, enum_name))?;
+                Ok((name, value))
+            })
+            .collect::<Result<Vec<_>, TranslationError>>()?;
+
+        let repr = self
+            .rust_enum_repr(enum_id)
+            .ok_or_else(|| format_err!("No representation for enum {}", enum_name))?;
+        let enum_item = mk()
+            .span(span)
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone", "PartialEq", "Eq"])
+            .call_attr("repr", vec![repr])
+            .enum_item(
+                &enum_name,
+                variants
+                    .iter()
+                    .map(|&(ref name, value)| mk().unit_variant(name, Some(signed_int_expr(value))))
+                    .collect(),
+            );
+        let from_int = self.rust_enum_from_int_impl(enum_id, &enum_name, &variants);
+        Ok(ConvertedDecl::Items(vec![enum_item, from_int]))
+    }
+
+    /// Convert an enum constant of a Rust enum into a constant holding its variant, i.e.
+    /// `pub const A: E = E::A;`.
+    pub fn convert_rust_enum_constant(
+        &self,
+        variant_id: CEnumConstantId,
+        span: Span,
+    ) -> Result<ConvertedDecl, TranslationError> {
+        let name = self
+            .renamer
+            .borrow_mut()
+            .get(&variant_id)
+            .expect("Enum constant not named");
+        let enum_id = self.ast_context.parents[&variant_id];
+        let enum_name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(enum_id)
+            .expect("Enums should already be renamed");
+
+        let ty = mk().path_ty(vec![enum_name.clone()]);
+        let val = mk().path_expr(vec![enum_name, name.clone()]);
+        Ok(ConvertedDecl::Item(
+            mk().span(span).pub_().const_item(name, ty, val),
+        ))
+    }
+
+    /// Build a pattern matching the variant of the Rust enum `enum_id` with the given value.
+    pub fn rust_enum_variant_pat(&self, enum_id: CEnumId, value: ConstIntExpr) -> Option<P<Pat>> {
+        let variants = match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { ref variants, .. } => variants,
+            _ => return None,
+        };
+        let value = rust_enum_discriminant(value)?;
+        let variant_id = variants.iter().cloned().find(|&variant_id| {
+            match self.ast_context[variant_id].kind {
+                CDeclKind::EnumConstant { value: v, .. } => rust_enum_discriminant(v) == Some(value),
+                _ => false,
+            }
+        })?;
+
+        let enum_name = self.type_converter.borrow().resolve_decl_name(enum_id)?;
+        let variant_name = self.renamer.borrow().get(&variant_id)?;
+        if let Some(cur_file) = *self.cur_file.borrow() {
+            self.add_import(cur_file, enum_id, &enum_name);
+        }
+        Some(mk().qpath_pat(None, vec![enum_name, variant_name]))
+    }
+
+    /// Convert an integer into the Rust enum `enum_id` with its `from_int` function, which
+    /// panics if the integer isn't the value of one of the variants.
+    pub fn rust_enum_from_int(&self, enum_id: CEnumId, val: P<Expr>) -> P<Expr> {
+        let enum_name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(enum_id)
+            .expect("Enums should already be renamed");
+        if let Some(cur_file) = *self.cur_file.borrow() {
+            self.add_import(cur_file, enum_id, &enum_name);
+        }
+        let int_ty = self.rust_enum_int_ty(enum_id);
+        mk().call_expr(
+            mk().path_expr(vec![enum_name, "from_int".to_owned()]),
+            vec![mk().cast_expr(val, int_ty)],
+        )
+    }
+
+    /// Build the `from_int` function of the Rust enum `enum_name`, which converts the integers
+    /// the enum is represented by back into the enum:
+    ///
+    /// ```ignore
+    /// impl E {
+    ///     pub fn from_int(value: libc::c_int) -> E {
+    ///         match value {
+    ///             0 => E::A,
+    ///             1 => E::B,
+    ///             _ => panic!("{} is not a value of enum E", value),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn rust_enum_from_int_impl(
+        &self,
+        enum_id: CEnumId,
+        enum_name: &str,
+        variants: &[(String, i64)],
+    ) -> P<Item> {
+        let value = || mk().ident_expr("value");
+        let mut arms = variants
+            .iter()
+            .map(|&(ref name, discriminant)| {
+                mk().arm(
+                    mk().lit_pat(signed_int_expr(discriminant)),
+                    None,
+                    mk().path_expr(vec![enum_name, name.as_str()]),
+                )
+            })
+            .collect::<Vec<_>>();
+        let msg = format!("{{}} is not a value of enum {}", enum_name);
+        let panic_args = vec![
+            TokenTree::token(
+                token::Interpolated(Rc::new(Nonterminal::NtExpr(mk().lit_expr(mk().str_lit(msg))))),
+                DUMMY_SP,
+            ),
+            TokenTree::token(token::Comma, DUMMY_SP),
+            TokenTree::token(token::Interpolated(Rc::new(Nonterminal::NtExpr(value()))), DUMMY_SP),
+        ];
+        arms.push(mk().arm(
+            mk().wild_pat(),
+            None,
+            mk().mac_expr(mk().mac(vec!["panic"], panic_args, MacDelimiter::Parenthesis)),
+        ));
+
+        let decl = mk().fn_decl(
+            vec![mk().arg(self.rust_enum_int_ty(enum_id), mk().ident_pat("value"))],
+            FunctionRetTy::Ty(mk().ident_ty(enum_name)),
+        );
+        let from_int = mk().pub_().method_impl_item(
+            "from_int",
+            decl,
+            mk().block(vec![mk().expr_stmt(mk().match_expr(value(), arms))]),
+        );
+        mk().impl_item(mk().ident_ty(enum_name), vec![from_int])
+    }
+
+    /// Get the integer type that the Rust enum `enum_id` has the representation of.
+    pub fn rust_enum_int_ty(&self, enum_id: CEnumId) -> P<Ty> {
+        let int_ty = match self.rust_enum_repr(enum_id) {
+            // `#[repr(C)]` enums are transmuted from the `int` or `unsigned` they hold
+            Some("C") | None => match self.ast_context[enum_id].kind {
+                CDeclKind::Enum { integral_type: Some(integral_type), .. } => {
+                    match self.ast_context.resolve_type(integral_type.ctype).kind {
+                        CTypeKind::UInt => "c_uint",
+                        _ => "c_int",
+                    }
+                }
+                _ => "c_int",
+            },
+            Some(repr) => repr,
+        };
+        if int_ty.starts_with("c_") {
+            mk().path_ty(vec!["libc", int_ty])
+        } else {
+            mk().ident_ty(int_ty)
+        }
+    }
+
+    /// If `expr` is an enum value promoted to an integer, e.g. the scrutinee of a `switch`,
+    /// return the Rust enum it was promoted from.
+    pub fn rust_enum_scrutinee(&self, expr: CExprId) -> Option<CEnumId> {
+        match self.ast_context[expr].kind {
+            CExprKind::ImplicitCast(_, inner, CastKind::IntegralCast, _, _) => {
+                match self.ast_context[inner].kind.get_type().map(|ty| &self.ast_context.resolve_type(ty).kind) {
+                    Some(&CTypeKind::Enum(enum_id)) if self.is_rust_enum(enum_id) => Some(enum_id),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Translate `a == b` and `a != b` on values of the same Rust enum into a comparison of
+    /// the enum values themselves, rather than of the integers they are promoted to in C.
+    pub fn convert_rust_enum_comparison(
+        &self,
+        ctx: ExprContext,
+        op: c_ast::BinOp,
+        lhs: CExprId,
+        rhs: CExprId,
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        let op = match op {
+            c_ast::BinOp::EqualEqual => BinOpKind::Eq,
+            c_ast::BinOp::NotEqual => BinOpKind::Ne,
+            _ => return Ok(None),
+        };
+        if !self.tcfg.translate_enums || ctx.is_unused() {
+            return Ok(None);
+        }
+
+        let (lhs_enum, lhs) = match self.rust_enum_operand(lhs) {
+            Some(operand) => operand,
+            None => return Ok(None),
+        };
+        let (rhs_enum, rhs) = match self.rust_enum_operand(rhs) {
+            Some(operand) => operand,
+            None => return Ok(None),
+        };
+        if lhs_enum != rhs_enum {
+            return Ok(None);
+        }
+
+        let lhs = self.convert_rust_enum_operand(ctx, lhs)?;
+        let rhs = self.convert_rust_enum_operand(ctx, rhs)?;
+        let cmp = lhs.and_then(|lhs| -> Result<_, TranslationError> {
+            Ok(rhs.map(|rhs| bool_to_int(mk().binary_expr(op, lhs, rhs))))
+        })?;
+        Ok(Some(cmp))
+    }
+
+    /// Find the Rust enum value behind an operand of a comparison, which is either an
+    /// enumerator or an enum-typed expression promoted to an integer.
+    fn rust_enum_operand(&self, mut expr: CExprId) -> Option<(CEnumId, CExprId)> {
+        while let CExprKind::ImplicitCast(_, inner, CastKind::IntegralCast, _, _) =
+            self.ast_context[expr].kind
+        {
+            expr = inner;
+        }
+
+        let enum_id = match self.ast_context[expr].kind {
+            CExprKind::DeclRef(_, decl_id, _) if self.is_enum_constant(decl_id) => {
+                self.ast_context.parents[&decl_id]
+            }
+            ref kind => match kind.get_type().map(|ty| &self.ast_context.resolve_type(ty).kind) {
+                Some(&CTypeKind::Enum(enum_id)) => enum_id,
+                _ => return None,
+            },
+        };
+
+        if self.is_rust_enum(enum_id) {
+            Some((enum_id, expr))
+        } else {
+            None
+        }
+    }
+
+    fn is_enum_constant(&self, decl_id: CDeclId) -> bool {
+        match self.ast_context[decl_id].kind {
+            CDeclKind::EnumConstant { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn convert_rust_enum_operand(
+        &self,
+        ctx: ExprContext,
+        expr: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let val = self.convert_expr(ctx.used(), expr)?;
+        match self.ast_context[expr].kind {
+            // References to enumerators are cast to their C type, `int`
+            CExprKind::DeclRef(..) => Ok(val.map(|x| match x.kind {
+                ExprKind::Cast(ref e, _) => e.clone(),
+                _ => x,
+            })),
+            _ => Ok(val),
+        }
+    }
+}
+
+/// Get the discriminant of a Rust enum variant, if it fits in the `int` used by `#[repr(C)]`.
+fn rust_enum_discriminant(value: ConstIntExpr) -> Option<i64> {
+    let value = match value {
+        ConstIntExpr::U(value) if value <= i32::max_value() as u64 => value as i64,
+        ConstIntExpr::I(value) => value,
+        _ => return None,
+    };
+    if value >= i32::min_value() as i64 && value <= i32::max_value() as i64 {
+        Some(value)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,181 @@
+//! Detection of functions returning error codes, for `--must-use-error-codes`.
+//!
+//! Many C functions report failure through their return value, e.g. `0` on success and a
+//! negative value on error, and their callers test it with `if (f(...) < 0)`,
+//! `if (f(...) != 0)` or `if (f(...))`, or store it in a variable that they test afterwards.
+//! With `--must-use-error-codes`, functions returning an integer or enum whose result is
+//! tested like this at least half of the time they are called get `#[must_use]`, so that
+//! Rust code ignoring the result gets a warning.
+
+use super::*;
+use crate::c_ast::iterators::DFNodes;
+use std::collections::HashSet;
+
+impl<'c> Translation<'c> {
+    /// Find the names of the functions whose return value is used as an error code.
+    pub fn find_error_code_fns(&self) -> IndexSet<String> {
+        let mut calls = vec![];
+        let mut tested_calls = HashSet::new();
+        let mut tested_vars = HashSet::new();
+        // Variables and the values stored into them
+        let mut stores = vec![];
+
+        for (_, decl) in self.ast_context.iter_decls() {
+            let body = match decl.kind {
+                CDeclKind::Function { body: Some(body), .. } => body,
+                _ => continue,
+            };
+
+            for id in DFNodes::new(&self.ast_context, SomeId::Stmt(body)) {
+                // Expressions whose value is tested
+                let mut tested = vec![];
+                match id {
+                    SomeId::Stmt(stmt_id) => match self.ast_context[stmt_id].kind {
+                        CStmtKind::If { scrutinee: cond, .. }
+                        | CStmtKind::While { condition: cond, .. }
+                        | CStmtKind::DoWhile { condition: cond, .. }
+                        | CStmtKind::ForLoop { condition: Some(cond), .. } => tested.push(cond),
+                        _ => {}
+                    },
+                    SomeId::Expr(expr_id) => match self.ast_context[expr_id].kind {
+                        CExprKind::Call(_, callee, _) => {
+                            if let Some(name) = self.called_function_name(callee) {
+                                calls.push((expr_id, name));
+                            }
+                        }
+                        CExprKind::Binary(_, op, lhs, rhs, _, _) => match op {
+                            c_ast::BinOp::Less
+                            | c_ast::BinOp::Greater
+                            | c_ast::BinOp::LessEqual
+                            | c_ast::BinOp::GreaterEqual
+                            | c_ast::BinOp::EqualEqual
+                            | c_ast::BinOp::NotEqual => {
+                                if self.is_error_code_bound(rhs) {
+                                    tested.push(lhs);
+                                }
+                                if self.is_error_code_bound(lhs) {
+                                    tested.push(rhs);
+                                }
+                            }
+                            c_ast::BinOp::And | c_ast::BinOp::Or => {
+                                tested.push(lhs);
+                                tested.push(rhs);
+                            }
+                            c_ast::BinOp::Assign => {
+                                if let Some(SomeId::Decl(var)) = self.tested_value(lhs) {
+                                    stores.push((var, rhs));
+                                }
+                            }
+                            _ => {}
+                        },
+                        CExprKind::Unary(_, c_ast::UnOp::Not, arg, _) => tested.push(arg),
+                        CExprKind::Conditional(_, cond, _, _) => tested.push(cond),
+                        _ => {}
+                    },
+                    SomeId::Decl(decl_id) => {
+                        if let CDeclKind::Variable { initializer: Some(init), .. } =
+                            self.ast_context[decl_id].kind
+                        {
+                            stores.push((decl_id, init));
+                        }
+                    }
+                    _ => {}
+                }
+
+                for expr_id in tested {
+                    match self.tested_value(expr_id) {
+                        Some(SomeId::Expr(call)) => {
+                            tested_calls.insert(call);
+                        }
+                        Some(SomeId::Decl(var)) => {
+                            tested_vars.insert(var);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        // A call is also tested if the variable it is stored in is
+        for (var, value) in stores {
+            if tested_vars.contains(&var) {
+                if let Some(SomeId::Expr(call)) = self.tested_value(value) {
+                    tested_calls.insert(call);
+                }
+            }
+        }
+
+        // Count the calls to each function, and how many of them are tested
+        let mut counts: IndexMap<String, (usize, usize)> = IndexMap::new();
+        for (call, name) in calls {
+            let count = counts.entry(name).or_insert((0, 0));
+            count.0 += 1;
+            if tested_calls.contains(&call) {
+                count.1 += 1;
+            }
+        }
+
+        self.ast_context
+            .iter_decls()
+            .filter_map(|(_, decl)| match decl.kind {
+                CDeclKind::Function { typ, ref name, .. } => Some((typ, name)),
+                _ => None,
+            })
+            .filter(|&(typ, name)| {
+                let returns_code = match self.ast_context.resolve_type(typ).kind {
+                    CTypeKind::Function(ret, ..) => {
+                        let ret = &self.ast_context.resolve_type(ret.ctype).kind;
+                        (ret.is_integral_type() && !ret.is_bool()) || ret.is_enum()
+                    }
+                    _ => false,
+                };
+                let (total, tested) = counts.get(name).cloned().unwrap_or((0, 0));
+                returns_code && tested > 0 && tested * 2 >= total
+            })
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+
+    /// Get the name of the function `callee` refers to directly.
+    fn called_function_name(&self, callee: CExprId) -> Option<String> {
+        match self.ast_context[callee].kind {
+            CExprKind::ImplicitCast(_, e, CastKind::FunctionToPointerDecay, _, _)
+            | CExprKind::Paren(_, e) => self.called_function_name(e),
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Function { ref name, .. } => Some(name.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Check whether `expr` is a value error codes are compared against: zero or a negative
+    /// integer literal.
+    fn is_error_code_bound(&self, expr: CExprId) -> bool {
+        match *self.ast_context.resolve_expr_value(expr) {
+            CExprKind::Literal(_, CLiteral::Integer(0, _)) => true,
+            CExprKind::Unary(_, c_ast::UnOp::Negate, arg, _) => {
+                match *self.ast_context.resolve_expr_value(arg) {
+                    CExprKind::Literal(_, CLiteral::Integer(..)) => true,
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the call or variable whose value `expr` tests, looking through implicit casts,
+    /// parentheses, and assignments.
+    fn tested_value(&self, expr: CExprId) -> Option<SomeId> {
+        match self.ast_context[expr].kind {
+            CExprKind::ImplicitCast(_, e, _, _, _) | CExprKind::Paren(_, e) => self.tested_value(e),
+            CExprKind::Binary(_, c_ast::BinOp::Assign, _, rhs, _, _) => self.tested_value(rhs),
+            CExprKind::Call(..) => Some(SomeId::Expr(expr)),
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Variable { .. } => Some(SomeId::Decl(decl_id)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
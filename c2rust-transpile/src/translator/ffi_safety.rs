@@ -0,0 +1,407 @@
+//! Checks of the foreign declarations we emit against the rules of rustc's `improper_ctypes`
+//! lint, and rewrites of the declarations that break them or that are ABI hazards.
+//!
+//! * A Rust enum from `--translate-enums` passed to or returned from an extern function is
+//!   passed as its integer representation instead, and converted at each call site, since
+//!   foreign code may hand back values that are not a variant of the enum. Enums that foreign
+//!   code can store values of, behind a pointer or in a struct field, are translated to
+//!   integer constants instead of Rust enums.
+//! * A parameter of a zero-sized type, i.e., an empty struct, is left out of the declaration,
+//!   as C leaves it out of the call. Its argument is still evaluated at call sites.
+//! * Declarations that use 128-bit integers, which have no stable ABI, or that use zero-sized
+//!   types in any other way, are kept as they are but with the lint allowed.
+//!
+//! Functions whose address is taken keep their signature, so that it matches the type of the
+//! function pointer. Every declaration that is changed is listed in a summary.
+
+use super::*;
+use std::collections::HashSet;
+
+/// How a parameter or the return value of an extern function crosses the FFI boundary
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FfiPassing {
+    /// As the type it is translated to
+    Direct,
+    /// A Rust enum, passed as its integer representation
+    EnumRepr(CEnumId),
+    /// A zero-sized type, which is left out
+    Omitted,
+}
+
+/// The passing of the parameters and return value of an extern function
+#[derive(Clone, Debug)]
+pub struct FfiSignature {
+    pub params: Vec<FfiPassing>,
+    pub ret: FfiPassing,
+}
+
+/// The changes that keep the foreign declarations of a file FFI-safe
+#[derive(Debug, Default)]
+pub struct FfiSafety {
+    /// Extern functions with parameters or a return value that are not passed directly
+    pub fns: IndexMap<CDeclId, FfiSignature>,
+    /// Enums that foreign code can store values of, with the first declaration exposing them
+    pub int_enums: IndexMap<CEnumId, CDeclId>,
+    /// Declarations kept with the lint allowed, with the reason
+    pub allowed: IndexMap<CDeclId, &'static str>,
+}
+
+impl<'c> Translation<'c> {
+    /// Find the enums that would be translated into Rust enums, but that foreign code can
+    /// store values of: enums behind a pointer, in a struct or union field, or in the
+    /// signature of a function pointer, in the type of a foreign declaration. The parameters
+    /// and return values of extern functions are converted at call sites instead.
+    pub fn find_ffi_int_enums(&self) -> IndexMap<CEnumId, CDeclId> {
+        let address_taken = self.address_taken_fns();
+        let mut int_enums = IndexMap::new();
+        for decl_id in self.foreign_decls() {
+            let mut enums = IndexSet::new();
+            let mut seen = HashSet::new();
+            match self.ast_context[decl_id].kind {
+                CDeclKind::Function { typ, .. } if !address_taken.contains(&decl_id) => {
+                    if let CTypeKind::Function(ret, ref params, ..) =
+                        self.ast_context.resolve_type(typ).kind
+                    {
+                        for ty in params.iter().chain(Some(&ret)) {
+                            self.stored_enums(ty.ctype, false, &mut enums, &mut seen);
+                        }
+                    }
+                }
+                CDeclKind::Function { typ, .. } => {
+                    self.stored_enums(typ, true, &mut enums, &mut seen);
+                }
+                CDeclKind::Variable { typ, .. } => {
+                    self.stored_enums(typ.ctype, true, &mut enums, &mut seen);
+                }
+                _ => {}
+            }
+            for enum_id in enums {
+                if self.is_rust_enum(enum_id) {
+                    int_enums.entry(enum_id).or_insert(decl_id);
+                }
+            }
+        }
+        int_enums
+    }
+
+    /// Collect the enums in `ty` into `enums`, including `ty` itself only if `stored`.
+    fn stored_enums(
+        &self,
+        ty: CTypeId,
+        stored: bool,
+        enums: &mut IndexSet<CEnumId>,
+        seen: &mut HashSet<CRecordId>,
+    ) {
+        match self.ast_context.resolve_type(ty).kind {
+            CTypeKind::Enum(enum_id) if stored => {
+                enums.insert(enum_id);
+            }
+            CTypeKind::Pointer(pointee)
+            | CTypeKind::Reference(pointee)
+            | CTypeKind::BlockPointer(pointee) => {
+                self.stored_enums(pointee.ctype, true, enums, seen)
+            }
+            CTypeKind::ConstantArray(elt, _)
+            | CTypeKind::IncompleteArray(elt)
+            | CTypeKind::VariableArray(elt, _) => self.stored_enums(elt, true, enums, seen),
+            CTypeKind::Function(ret, ref params, ..) => {
+                for ty in params.iter().chain(Some(&ret)) {
+                    self.stored_enums(ty.ctype, true, enums, seen);
+                }
+            }
+            CTypeKind::Struct(record_id) | CTypeKind::Union(record_id) => {
+                // Records are the only types that can refer to themselves
+                if !seen.insert(record_id) {
+                    return;
+                }
+                for field_ty in self.record_field_types(record_id) {
+                    self.stored_enums(field_ty, true, enums, seen);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Decide how the parameters and return values of extern functions are passed, and which
+    /// foreign declarations keep types the lint rejects. Must run after `find_ffi_int_enums`,
+    /// as enums translated to constants are passed directly.
+    pub fn find_ffi_signatures(
+        &self,
+    ) -> (IndexMap<CDeclId, FfiSignature>, IndexMap<CDeclId, &'static str>) {
+        let address_taken = self.address_taken_fns();
+        let mut fns = IndexMap::new();
+        let mut allowed = IndexMap::new();
+        for decl_id in self.foreign_decls() {
+            let mut kept = vec![];
+            match self.ast_context[decl_id].kind {
+                CDeclKind::Function { typ, .. } if !address_taken.contains(&decl_id) => {
+                    let (ret, params) = match self.ast_context.resolve_type(typ).kind {
+                        CTypeKind::Function(ret, ref params, ..) => (ret, params),
+                        _ => continue,
+                    };
+                    let sig = FfiSignature {
+                        params: params.iter().map(|ty| self.ffi_passing(ty.ctype, true)).collect(),
+                        ret: self.ffi_passing(ret.ctype, false),
+                    };
+                    for (ty, passing) in params.iter().zip(&sig.params) {
+                        if *passing == FfiPassing::Direct {
+                            kept.push(ty.ctype);
+                        }
+                    }
+                    if sig.ret == FfiPassing::Direct {
+                        kept.push(ret.ctype);
+                    }
+                    let direct = |&passing: &FfiPassing| passing == FfiPassing::Direct;
+                    if !direct(&sig.ret) || !sig.params.iter().all(direct) {
+                        fns.insert(decl_id, sig);
+                    }
+                }
+                CDeclKind::Function { typ, .. } => kept.push(typ),
+                CDeclKind::Variable { typ, .. } => kept.push(typ.ctype),
+                _ => {}
+            }
+
+            let mut seen = HashSet::new();
+            let reason = kept
+                .into_iter()
+                .filter_map(|ty| self.improper_ctype(ty, &mut seen))
+                .next();
+            if let Some(reason) = reason {
+                allowed.insert(decl_id, reason);
+            }
+        }
+        (fns, allowed)
+    }
+
+    fn ffi_passing(&self, ty: CTypeId, is_param: bool) -> FfiPassing {
+        match self.ast_context.resolve_type(ty).kind {
+            CTypeKind::Enum(enum_id) if self.is_rust_enum(enum_id) => FfiPassing::EnumRepr(enum_id),
+            CTypeKind::Struct(record_id) if is_param && self.is_zero_sized_record(record_id) => {
+                FfiPassing::Omitted
+            }
+            _ => FfiPassing::Direct,
+        }
+    }
+
+    /// Check `ty` for types that the `improper_ctypes` lint rejects, looking through pointers
+    /// and function pointers like the lint does.
+    fn improper_ctype(&self, ty: CTypeId, seen: &mut HashSet<CRecordId>) -> Option<&'static str> {
+        match self.ast_context.resolve_type(ty).kind {
+            CTypeKind::Int128 | CTypeKind::UInt128 => {
+                Some("128-bit integers have no stable ABI, check it matches on the target")
+            }
+            CTypeKind::Struct(record_id) if self.is_zero_sized_record(record_id) => {
+                Some("uses a zero-sized type other than as a parameter")
+            }
+            CTypeKind::Pointer(pointee)
+            | CTypeKind::Reference(pointee)
+            | CTypeKind::BlockPointer(pointee) => self.improper_ctype(pointee.ctype, seen),
+            CTypeKind::ConstantArray(elt, _)
+            | CTypeKind::IncompleteArray(elt)
+            | CTypeKind::VariableArray(elt, _) => self.improper_ctype(elt, seen),
+            CTypeKind::Function(ret, ref params, ..) => params
+                .iter()
+                .chain(Some(&ret))
+                .filter_map(|ty| self.improper_ctype(ty.ctype, seen))
+                .next(),
+            CTypeKind::Struct(record_id) | CTypeKind::Union(record_id)
+                if seen.insert(record_id) =>
+            {
+                self.record_field_types(record_id)
+                    .into_iter()
+                    .filter_map(|ty| self.improper_ctype(ty, seen))
+                    .next()
+            }
+            _ => None,
+        }
+    }
+
+    /// Empty structs, a GNU extension, are zero-sized in C and translate to Rust structs
+    /// without fields.
+    fn is_zero_sized_record(&self, record_id: CRecordId) -> bool {
+        match self.ast_context[record_id].kind {
+            CDeclKind::Struct { fields: Some(_), platform_byte_size: 0, .. } => true,
+            _ => false,
+        }
+    }
+
+    pub fn record_field_types(&self, record_id: CRecordId) -> Vec<CTypeId> {
+        let fields = match self.ast_context[record_id].kind {
+            CDeclKind::Struct { fields: Some(ref fields), .. }
+            | CDeclKind::Union { fields: Some(ref fields), .. } => fields,
+            _ => return vec![],
+        };
+        fields
+            .iter()
+            .filter_map(|&field_id| match self.ast_context[field_id].kind {
+                CDeclKind::Field { typ, .. } => Some(typ.ctype),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The declarations that are translated into foreign items
+    fn foreign_decls(&self) -> Vec<CDeclId> {
+        self.ast_context
+            .iter_decls()
+            .filter(|&(_, decl)| match decl.kind {
+                CDeclKind::Function { body, is_global, is_implicit, .. } => {
+                    !is_implicit && (body.is_none() || (self.tcfg.header_only && is_global))
+                }
+                CDeclKind::Variable { has_static_duration, has_thread_duration, is_defn, .. } => {
+                    (has_static_duration || has_thread_duration)
+                        && (!is_defn || self.tcfg.header_only)
+                }
+                _ => false,
+            })
+            .map(|(&decl_id, _)| decl_id)
+            .collect()
+    }
+
+    /// Find the functions referenced other than by calling them directly.
+    fn address_taken_fns(&self) -> HashSet<CDeclId> {
+        let mut callees = HashSet::new();
+        for (_, expr) in self.ast_context.iter_exprs() {
+            if let CExprKind::Call(_, func, _) = expr.kind {
+                if let CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) =
+                    self.ast_context[func].kind
+                {
+                    callees.insert(fexp);
+                }
+            }
+        }
+
+        let mut address_taken = HashSet::new();
+        for (expr_id, expr) in self.ast_context.iter_exprs() {
+            if let CExprKind::DeclRef(_, decl_id, _) = expr.kind {
+                if !callees.contains(expr_id) {
+                    address_taken.insert(decl_id);
+                }
+            }
+        }
+        address_taken
+    }
+
+    /// Rewrite the foreign item translated from `decl_id` as decided by `find_ffi_signatures`.
+    pub fn make_ffi_safe(&self, decl_id: CDeclId, mut item: ForeignItem) -> ForeignItem {
+        if let (Some(sig), ForeignItemKind::Fn(decl, _)) =
+            (self.ffi_safety.fns.get(&decl_id), &mut item.kind)
+        {
+            let inputs = mem::replace(&mut decl.inputs, vec![]);
+            // Variadic functions have a trailing `...` parameter
+            let passings = sig.params.iter().chain(std::iter::repeat(&FfiPassing::Direct));
+            for (mut param, passing) in inputs.into_iter().zip(passings) {
+                match *passing {
+                    FfiPassing::Direct => {}
+                    FfiPassing::EnumRepr(enum_id) => param.ty = self.rust_enum_int_ty(enum_id),
+                    FfiPassing::Omitted => continue,
+                }
+                decl.inputs.push(param);
+            }
+            if let FfiPassing::EnumRepr(enum_id) = sig.ret {
+                if let FunctionRetTy::Ty(ty) = &mut decl.output {
+                    *ty = self.rust_enum_int_ty(enum_id);
+                }
+            }
+        }
+        if self.ffi_safety.allowed.contains_key(&decl_id) {
+            item.attrs.extend(mk().call_attr("allow", vec!["improper_ctypes"]).into_attrs());
+        }
+        item
+    }
+
+    /// Convert the arguments of a call to an extern function with the signature `sig`. The
+    /// arguments of parameters that are left out are only evaluated.
+    pub fn convert_ffi_args(
+        &self,
+        ctx: ExprContext,
+        sig: &FfiSignature,
+        callee: Option<CDeclId>,
+        args: &[CExprId],
+    ) -> Result<WithStmts<Vec<P<Expr>>>, TranslationError> {
+        let passings = sig.params.iter().chain(std::iter::repeat(&FfiPassing::Direct));
+        let args = args
+            .iter()
+            .zip(passings)
+            .enumerate()
+            .map(|(index, (&arg, passing))| -> Result<_, TranslationError> {
+                Ok(match *passing {
+                    FfiPassing::Direct => {
+                        self.convert_call_arg(ctx.used(), callee, index, arg)?.map(Some)
+                    }
+                    FfiPassing::EnumRepr(enum_id) => {
+                        let int_ty = self.rust_enum_int_ty(enum_id);
+                        self.convert_expr(ctx.used(), arg)?
+                            .map(|arg| Some(mk().cast_expr(arg, int_ty)))
+                    }
+                    FfiPassing::Omitted => self.convert_expr(ctx.unused(), arg)?.map(|_| None),
+                })
+            })
+            .collect::<Result<WithStmts<Vec<_>>, _>>()?;
+        Ok(args.map(|args| args.into_iter().filter_map(|arg| arg).collect()))
+    }
+
+    /// Convert the result of a call to an extern function with the signature `sig` into the
+    /// type of the call expression.
+    pub fn convert_ffi_ret(&self, sig: &FfiSignature, call: WithStmts<P<Expr>>) -> WithStmts<P<Expr>> {
+        match sig.ret {
+            FfiPassing::EnumRepr(enum_id) => call.map(|call| self.rust_enum_from_int(enum_id, call)),
+            _ => call,
+        }
+    }
+
+    /// Describe every foreign declaration that was changed, if any.
+    pub fn ffi_safety_summary(&self) -> Option<String> {
+        let decl_name = |decl_id: CDeclId| {
+            self.renamer.borrow().get(&decl_id).unwrap_or_else(|| format!("{:?}", decl_id))
+        };
+        let mut lines = vec![];
+        for (&decl_id, sig) in &self.ffi_safety.fns {
+            let name = decl_name(decl_id);
+            for (idx, passing) in sig.params.iter().enumerate() {
+                match *passing {
+                    FfiPassing::Direct => {}
+                    FfiPassing::EnumRepr(_) => lines.push(format!(
+                        "fn {}: parameter {} is passed as its enum's integer type",
+                        name, idx + 1
+                    )),
+                    FfiPassing::Omitted => lines.push(format!(
+                        "fn {}: zero-sized parameter {} is left out",
+                        name, idx + 1
+                    )),
+                }
+            }
+            if let FfiPassing::EnumRepr(_) = sig.ret {
+                lines.push(format!("fn {}: returns its enum's integer type", name));
+            }
+        }
+        for (&enum_id, &decl_id) in &self.ffi_safety.int_enums {
+            let enum_name = self
+                .type_converter
+                .borrow()
+                .resolve_decl_name(enum_id)
+                .unwrap_or_else(|| format!("{:?}", enum_id));
+            lines.push(format!(
+                "enum {}: translated to integer constants, as {} can store its values",
+                enum_name,
+                decl_name(decl_id),
+            ));
+        }
+        for (&decl_id, reason) in &self.ffi_safety.allowed {
+            lines.push(format!(
+                "{}: allowed `improper_ctypes`: {}",
+                decl_name(decl_id),
+                reason
+            ));
+        }
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "Changed foreign declarations to keep them FFI-safe:\n  {}",
+                lines.join("\n  ")
+            ))
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Translation of function-like macros into functions with `--translate-fn-macros`.
+//!
+//! Function-like macros are normally expanded before translation, so every use of a macro such
+//! as `#define MAX(a, b) ((a) > (b) ? (a) : (b))` becomes a copy of its body. With
+//! `--translate-fn-macros`, a macro that expands to an expression is translated to a function
+//! instead, using the types of the arguments and of the expansion at its first use, and uses of
+//! the macro with the same types become calls to that function. The AST exporter skips macros
+//! that stringize or paste their arguments, and we skip macros that use an argument as an lvalue,
+//! only evaluate it conditionally, or refer to local variables of the function they are used in.
+//! Uses whose arguments have side effects keep the expansion, since a macro may evaluate its
+//! arguments more than once.
+
+use super::*;
+use crate::c_ast::iterators::immediate_children_all_types;
+
+/// Signature of a function-like macro translated to a function
+#[derive(Clone, Debug)]
+pub struct FnMacro {
+    params: Vec<CTypeId>,
+    ret: CQualTypeId,
+}
+
+impl<'c> Translation<'c> {
+    pub fn convert_fn_macro(
+        &self,
+        ctx: ExprContext,
+        decl_id: CDeclId,
+        span: Span,
+    ) -> Result<ConvertedDecl, TranslationError> {
+        let name = self
+            .renamer
+            .borrow_mut()
+            .get(&decl_id)
+            .expect("Macro function not named");
+
+        trace!("Converting macro {:?} to a function: {:?}", decl_id, self.ast_context[decl_id]);
+
+        match self.convert_fn_macro_item(ctx, decl_id, &name, span) {
+            Ok((item, fn_macro)) => {
+                self.fn_macros.borrow_mut().insert(decl_id, Some(fn_macro));
+                Ok(ConvertedDecl::Item(item))
+            }
+            Err(e) => {
+                self.fn_macros.borrow_mut().insert(decl_id, None);
+                info!("Could not translate macro {} to a function: {}", name, e);
+                Ok(ConvertedDecl::NoItem)
+            }
+        }
+    }
+
+    fn convert_fn_macro_item(
+        &self,
+        ctx: ExprContext,
+        decl_id: CDeclId,
+        name: &str,
+        span: Span,
+    ) -> Result<(P<Item>, FnMacro), TranslationError> {
+        let (params, template, template_args) = match self.ast_context[decl_id].kind {
+            CDeclKind::MacroFunction {
+                ref params,
+                ref replacements,
+                ref args,
+                ..
+            } if !replacements.is_empty() => (params, replacements[0], &args[0]),
+            _ => return Err(TranslationError::generic("Macro has no expansions")),
+        };
+
+        // The types of the parameters come from the arguments of the first expansion
+        let mut param_tys: Vec<Option<CTypeId>> = vec![None; params.len()];
+        for &(param, arg) in template_args {
+            let ty = self.fn_macro_arg_type(arg)?;
+            match param_tys[param] {
+                Some(param_ty) if param_ty != ty => {
+                    let msg = format_err!("Parameter {} is used with different types", params[param]);
+                    return Err(msg.into());
+                }
+                _ => param_tys[param] = Some(ty),
+            }
+        }
+        let fn_macro = FnMacro {
+            params: param_tys
+                .into_iter()
+                .zip(params)
+                .map(|(ty, param)| ty.ok_or_else(|| format_err!("Parameter {} is unused", param)))
+                .collect::<Result<_, _>>()?,
+            ret: self.ast_context[template]
+                .kind
+                .get_qual_type()
+                .ok_or_else(|| format_err!("Macro expansion has no type"))?,
+        };
+
+        let args = template_args
+            .iter()
+            .map(|&(param, arg)| (arg, param))
+            .collect::<HashMap<_, _>>();
+        let mut evaluated = vec![false; params.len()];
+        self.check_fn_macro_body(template, &args, false, &mut evaluated)?;
+        if let Some(param) = evaluated.iter().position(|&evaluated| !evaluated) {
+            let msg = format_err!("Parameter {} is only evaluated conditionally", params[param]);
+            return Err(msg.into());
+        }
+
+        self.with_scope(|| {
+            let names = params
+                .iter()
+                .map(|param| self.renamer.borrow_mut().pick_name(param))
+                .collect::<Vec<_>>();
+
+            let mut fn_args = vec![];
+            for (name, &ty) in names.iter().zip(&fn_macro.params) {
+                fn_args.push(mk().arg(self.convert_type(ty)?, mk().ident_pat(name.as_str())));
+            }
+            let ret = match self.ast_context.resolve_type(fn_macro.ret.ctype).kind {
+                CTypeKind::Void => FunctionRetTy::Default(DUMMY_SP),
+                _ => FunctionRetTy::Ty(self.convert_type(fn_macro.ret.ctype)?),
+            };
+            let decl = mk().fn_decl(fn_args, ret);
+
+            // Arguments in the body refer to the parameters of the function. We may be in the
+            // middle of translating another macro, whose arguments we restore afterwards.
+            let body_args = template_args
+                .iter()
+                .map(|&(param, arg)| (arg, names[param].clone()))
+                .collect();
+            let outer_args = mem::replace(&mut *self.fn_macro_args.borrow_mut(), body_args);
+            let body = self.convert_expr(
+                ctx.used().not_static().set_const(false).set_expanding_macro(decl_id),
+                template,
+            );
+            *self.fn_macro_args.borrow_mut() = outer_args;
+
+            let mut builder = mk().span(span).pub_().unsafe_();
+            if self.tcfg.inline_fn_macros {
+                builder = builder.call_attr("inline", vec!["always"]);
+            }
+            let item = builder.fn_item(name, decl, body?.to_block());
+            Ok((item, fn_macro))
+        })
+    }
+
+    /// Get the type that an argument is passed to a translated macro with.
+    fn fn_macro_arg_type(&self, arg: CExprId) -> Result<CTypeId, TranslationError> {
+        let kind = &self.ast_context[arg].kind;
+        if let LRValue::LValue = kind.lrvalue() {
+            return Err(TranslationError::generic("Macro argument is used as an lvalue"));
+        }
+        let ty = kind
+            .get_qual_type()
+            .ok_or_else(|| format_err!("Macro argument has no type"))?;
+        Ok(self.ast_context.resolve_type_id(ty.ctype))
+    }
+
+    /// Check that the parameters of a macro can be passed to the function it's translated to,
+    /// and record which parameters the body of the macro always evaluates.
+    fn check_fn_macro_body(
+        &self,
+        expr_id: CExprId,
+        args: &HashMap<CExprId, usize>,
+        conditional: bool,
+        evaluated: &mut [bool],
+    ) -> Result<(), TranslationError> {
+        if let Some(&param) = args.get(&expr_id) {
+            if !conditional {
+                evaluated[param] = true;
+            }
+            return Ok(());
+        }
+
+        match self.ast_context[expr_id].kind {
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Variable {
+                    has_static_duration: false,
+                    has_thread_duration: false,
+                    ..
+                } => Err(TranslationError::generic("Macro refers to a local variable")),
+                _ => Ok(()),
+            },
+
+            CExprKind::Statements(..)
+            | CExprKind::VAArg(..)
+            | CExprKind::Predefined(..)
+            | CExprKind::Block { .. } => {
+                Err(TranslationError::generic("Macro expands to an unsupported expression"))
+            }
+
+            CExprKind::UnaryType(_, _, Some(arg), _, _) => {
+                let uses_param = DFExpr::new(&self.ast_context, arg.into()).any(|id| match id {
+                    SomeId::Expr(expr_id) => args.contains_key(&expr_id),
+                    _ => false,
+                });
+                if uses_param {
+                    Err(TranslationError::generic("Macro argument is not evaluated"))
+                } else {
+                    Ok(())
+                }
+            }
+
+            CExprKind::Binary(_, BinOp::And, lhs, rhs, _, _)
+            | CExprKind::Binary(_, BinOp::Or, lhs, rhs, _, _)
+            | CExprKind::BinaryConditional(_, lhs, rhs) => {
+                self.check_fn_macro_body(lhs, args, conditional, evaluated)?;
+                self.check_fn_macro_body(rhs, args, true, evaluated)
+            }
+
+            CExprKind::Conditional(_, cond, lhs, rhs) => {
+                self.check_fn_macro_body(cond, args, conditional, evaluated)?;
+                self.check_fn_macro_body(lhs, args, true, evaluated)?;
+                self.check_fn_macro_body(rhs, args, true, evaluated)
+            }
+
+            _ => {
+                for child in immediate_children_all_types(&self.ast_context, expr_id.into()) {
+                    if let SomeId::Expr(child) = child {
+                        self.check_fn_macro_body(child, args, conditional, evaluated)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Translate the expansion `expr_id` of the function-like macro `macro_id` to a call to the
+    /// function the macro was translated to, if it has been and the arguments are compatible.
+    pub fn convert_fn_macro_call(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+        macro_id: CDeclId,
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        // Calls to the function aren't allowed in static initializers
+        if ctx.is_static || ctx.is_const {
+            return Ok(None);
+        }
+
+        // Ensure that we've converted this macro and that it has a valid definition
+        let fn_macro = self.fn_macros.borrow().get(&macro_id).cloned();
+        let fn_macro = match fn_macro {
+            Some(Some(fn_macro)) => fn_macro,
+            Some(None) => return Ok(None),
+            None => {
+                self.convert_decl(ctx, macro_id)?;
+                match self.fn_macros.borrow().get(&macro_id) {
+                    Some(Some(fn_macro)) => fn_macro.clone(),
+                    _ => return Ok(None),
+                }
+            }
+        };
+
+        let args = match self.ast_context[macro_id].kind {
+            CDeclKind::MacroFunction {
+                ref replacements,
+                ref args,
+                ..
+            } => match replacements.iter().position(|&id| id == expr_id) {
+                Some(pos) => &args[pos],
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        let ret_ty = self.ast_context[expr_id].kind.get_qual_type();
+        let ret_matches = ret_ty.map_or(false, |ty| {
+            self.ast_context.resolve_type_id(ty.ctype)
+                == self.ast_context.resolve_type_id(fn_macro.ret.ctype)
+        });
+        if !ret_matches {
+            return Ok(None);
+        }
+
+        let mut arg_ids: Vec<Option<CExprId>> = vec![None; fn_macro.params.len()];
+        for &(param, arg) in args {
+            match self.fn_macro_arg_type(arg) {
+                Ok(ty) if ty == fn_macro.params[param] => {}
+                _ => return Ok(None),
+            }
+            // The macro may evaluate its arguments any number of times
+            if !self.ast_context.is_expr_pure(arg) {
+                return Ok(None);
+            }
+            arg_ids[param].get_or_insert(arg);
+        }
+        let arg_ids = match arg_ids.into_iter().collect::<Option<Vec<_>>>() {
+            Some(arg_ids) => arg_ids,
+            None => return Ok(None),
+        };
+
+        let rustname = self
+            .renamer
+            .borrow_mut()
+            .get(&macro_id)
+            .ok_or_else(|| format_err!("Macro name not declared"))?;
+
+        if let Some(cur_file) = self.cur_file.borrow().as_ref() {
+            self.add_import(*cur_file, macro_id, &rustname);
+        }
+
+        let args = self.convert_exprs(ctx.used(), &arg_ids)?;
+        let call = args.map(|args| mk().call_expr(mk().path_expr(vec![rustname]), args));
+        self.convert_side_effects_expr(
+            ctx,
+            call,
+            "Function call expression is not supposed to be used",
+        )
+        .map(Some)
+    }
+
+    /// Import the types in the signature of the function that a macro was translated to.
+    pub fn import_fn_macro_types(&self, decl_id: CDeclId, decl_file_id: FileId) {
+        if let Some(Some(fn_macro)) = self.fn_macros.borrow().get(&decl_id) {
+            for &ty in &fn_macro.params {
+                self.import_type(ty, decl_file_id);
+            }
+            self.import_type(fn_macro.ret.ctype, decl_file_id);
+        }
+    }
+
+    /// Get the translation of a macro argument in the body of the function that the macro is
+    /// being translated to.
+    pub fn convert_fn_macro_arg(&self, expr_id: CExprId) -> Option<WithStmts<P<Expr>>> {
+        self.fn_macro_args
+            .borrow()
+            .get(&expr_id)
+            .map(|name| WithStmts::new_val(mk().path_expr(vec![name.clone()])))
+    }
+}
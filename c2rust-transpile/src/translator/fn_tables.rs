@@ -0,0 +1,200 @@
+//! Translation of function pointer tables into arrays of `fn`s with `--translate-fn-tables`.
+//!
+//! C function pointers become `Option<fn>`, so a dispatch table such as
+//! `static handler_t table[] = { h1, h2, h3 };` is normally translated to an array of
+//! `Option`s that has to be unwrapped on every call. With `--translate-fn-tables`, tables
+//! whose entries are all functions are translated to arrays of plain `fn`s instead, and
+//! calls through them index the array directly. Tables with entries that may be null, or
+//! that are used in any way other than reading their elements, keep the `Option`.
+
+use super::*;
+use std::collections::HashSet;
+
+impl<'c> Translation<'c> {
+    /// Find the static function pointer tables that can be translated to arrays of `fn`s.
+    pub fn find_fn_tables(&self) -> IndexSet<CDeclId> {
+        let mut tables = self
+            .ast_context
+            .iter_decls()
+            .filter(|&(&decl_id, _)| self.is_fn_table_candidate(decl_id))
+            .map(|(&decl_id, _)| decl_id)
+            .collect::<IndexSet<_>>();
+        self.remove_tables_with_other_uses(&mut tables);
+        tables
+    }
+
+    /// Remove the tables that are used in any way other than reading their elements, e.g.,
+    /// by assigning to one of their elements or taking their address. Uses that only compute
+    /// a size are fine.
+    pub fn remove_tables_with_other_uses(&self, tables: &mut IndexSet<CDeclId>) {
+        if tables.is_empty() {
+            return;
+        }
+
+        // References to a table that read one of its elements, or that are only
+        // used to compute a size
+        let mut element_refs = HashSet::new();
+        for (_, expr) in self.ast_context.iter_exprs() {
+            match expr.kind {
+                CExprKind::ImplicitCast(_, elt, CastKind::LValueToRValue, _, _) => {
+                    element_refs.extend(self.table_element(elt).map(|(table, _)| table));
+                }
+                CExprKind::UnaryType(_, _, Some(arg), _, _) => {
+                    for id in DFExpr::new(&self.ast_context, arg.into()) {
+                        if let SomeId::Expr(expr_id) = id {
+                            element_refs.insert(expr_id);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (expr_id, expr) in self.ast_context.iter_exprs() {
+            if let CExprKind::DeclRef(_, decl_id, _) = expr.kind {
+                if !element_refs.contains(expr_id) {
+                    tables.remove(&decl_id);
+                }
+            }
+        }
+    }
+
+    /// Check if `decl_id` is a static array of function pointers initialized with
+    /// functions only.
+    fn is_fn_table_candidate(&self, decl_id: CDeclId) -> bool {
+        let (initializer, typ) = match self.ast_context[decl_id].kind {
+            CDeclKind::Variable {
+                has_static_duration: true,
+                has_thread_duration: false,
+                is_externally_visible: false,
+                is_defn: true,
+                initializer: Some(initializer),
+                typ,
+                ..
+            } => (initializer, typ),
+            _ => return false,
+        };
+
+        let (elt, len) = match self.ast_context.resolve_type(typ.ctype).kind {
+            CTypeKind::ConstantArray(elt, len) => (elt, len),
+            _ => return false,
+        };
+        match self.fn_table_pointee(elt) {
+            // Calls through K&R function pointers are transmuted, so leave them alone
+            Some(CTypeKind::Function(_, _, _, _, true)) => {}
+            _ => return false,
+        }
+
+        // Entries that aren't explicitly initialized are null
+        let entries = match self.ast_context[initializer].kind {
+            CExprKind::InitList(_, ref entries, _, _) if entries.len() == len => entries,
+            _ => return false,
+        };
+        entries.iter().all(|&entry| self.fn_table_entry(entry).is_some())
+    }
+
+    /// Get the function type that the element type `elt` of a table points to.
+    fn fn_table_pointee(&self, elt: CTypeId) -> Option<&CTypeKind> {
+        let pointee = self.ast_context.get_pointee_qual_type(elt)?;
+        Some(&self.ast_context.resolve_type(pointee.ctype).kind)
+    }
+
+    /// Get the reference to the function that initializes an entry of a table, i.e., the
+    /// `f` in `f` or `&f`.
+    fn fn_table_entry(&self, entry: CExprId) -> Option<CExprId> {
+        let fn_ref = match self.ast_context[entry].kind {
+            CExprKind::ImplicitCast(_, fn_ref, CastKind::FunctionToPointerDecay, _, _)
+            | CExprKind::Unary(_, c_ast::UnOp::AddressOf, fn_ref, _) => fn_ref,
+            _ => return None,
+        };
+        match self.ast_context[fn_ref].kind {
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Function { .. } => Some(fn_ref),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// If `expr` is an element `table[i]` of a table, get the reference to `table` and the
+    /// index `i`.
+    pub fn table_element(&self, expr: CExprId) -> Option<(CExprId, CExprId)> {
+        let (lhs, rhs) = match self.ast_context[expr].kind {
+            CExprKind::ArraySubscript(_, lhs, rhs, _) => (lhs, rhs),
+            _ => return None,
+        };
+        // C allows both `table[i]` and `i[table]`
+        [(lhs, rhs), (rhs, lhs)].iter().filter_map(|&(arr, index)| {
+            match self.ast_context[arr].kind {
+                CExprKind::ImplicitCast(_, table, CastKind::ArrayToPointerDecay, _, _) => {
+                    match self.ast_context[table].kind {
+                        CExprKind::DeclRef(..) => Some((table, index)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }).next()
+    }
+
+    /// Check if `expr` is an element of a table translated to an array of `fn`s.
+    pub fn is_fn_table_element(&self, expr: CExprId) -> bool {
+        self.table_element(expr).map_or(false, |(table, _)| {
+            match self.ast_context[table].kind {
+                CExprKind::DeclRef(_, decl_id, _) => self.fn_tables.contains(&decl_id),
+                _ => false,
+            }
+        })
+    }
+
+    /// Convert the type and initializer of a table into an array of `fn`s, i.e.,
+    /// `[unsafe extern "C" fn(..) -> ..; N]` and `[f1, f2, ..]`.
+    pub fn convert_fn_table(
+        &self,
+        ctx: ExprContext,
+        initializer: CExprId,
+        typ: CQualTypeId,
+    ) -> Result<(P<Ty>, P<Expr>), TranslationError> {
+        let (elt, len) = match self.ast_context.resolve_type(typ.ctype).kind {
+            CTypeKind::ConstantArray(elt, len) => (elt, len),
+            _ => return Err(format_err!("Function table {:?} is not an array", typ).into()),
+        };
+        let fn_ty = self
+            .ast_context
+            .get_pointee_qual_type(elt)
+            .ok_or_else(|| format_err!("Function table element {:?} is not a pointer", elt))?;
+        let fn_ty = self.convert_type(fn_ty.ctype)?;
+        let len = mk().lit_expr(mk().int_lit(len as u128, LitIntType::Unsuffixed));
+        let ty = mk().array_ty(fn_ty, len);
+
+        let entries = match self.ast_context[initializer].kind {
+            CExprKind::InitList(_, ref entries, _, _) => entries,
+            _ => return Err(format_err!("Function table {:?} has no initializer list", typ).into()),
+        };
+        let entries = entries
+            .iter()
+            .map(|&entry| -> Result<P<Expr>, TranslationError> {
+                let fn_ref = self.fn_table_entry(entry).ok_or_else(|| {
+                    format_err!("Function table entry {:?} is not a function", entry)
+                })?;
+                let fn_ref = self.convert_expr(ctx.used(), fn_ref)?;
+                Ok(fn_ref.to_pure_expr().ok_or_else(|| {
+                    format_err!("Expected no side-effects in function table entry")
+                })?)
+            })
+            .collect::<Result<Vec<_>, TranslationError>>()?;
+
+        Ok((ty, mk().array_expr(entries)))
+    }
+
+    /// Convert a read of an element of a table in a context that expects a C function
+    /// pointer, i.e., `Some(table[i])`.
+    pub fn convert_fn_table_read(
+        &self,
+        ctx: ExprContext,
+        elt: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let val = self.convert_expr(ctx, elt)?;
+        Ok(val.map(|x| mk().call_expr(mk().ident_expr("Some"), vec![x])))
+    }
+}
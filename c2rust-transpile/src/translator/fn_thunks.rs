@@ -0,0 +1,169 @@
+//! Thunks for functions stored in function pointers of a different type.
+//!
+//! C code often stores a function in a pointer whose prototype differs only in its pointer
+//! types, e.g. `int handler(struct conn *)` in an `int (*)(void *)`, or passes a function that
+//! ignores trailing arguments of the pointer type. Rather than transmuting the function pointer,
+//! we call such functions through a generated thunk with the exact target signature.
+
+use super::*;
+
+impl<'c> Translation<'c> {
+    /// Convert the function `expr` refers to into a pointer of type `target_ty` by way of a
+    /// thunk, if the signatures are compatible enough for one.
+    pub fn convert_fn_ptr_thunk(
+        &self,
+        target_ty: CTypeId,
+        expr: CExprId,
+    ) -> Result<Option<P<Expr>>, TranslationError> {
+        let fn_id = match self.referenced_function(expr) {
+            Some(fn_id) => fn_id,
+            None => return Ok(None),
+        };
+        let name = match self.fn_ptr_thunk(target_ty, fn_id)? {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        Ok(Some(mk().call_expr(
+            mk().ident_expr("Some"),
+            vec![mk().path_expr(vec![name])],
+        )))
+    }
+
+    /// Get the function `expr` names, looking through parentheses, decay and `&`.
+    pub fn referenced_function(&self, expr: CExprId) -> Option<CDeclId> {
+        match self.ast_context[expr].kind {
+            CExprKind::ImplicitCast(_, e, CastKind::FunctionToPointerDecay, _, _)
+            | CExprKind::Unary(_, c_ast::UnOp::AddressOf, e, _)
+            | CExprKind::Paren(_, e) => self.referenced_function(e),
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Function { .. } => Some(decl_id),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the name of the thunk that calls `fn_id` through a pointer of type `target_ty`,
+    /// generating it in the current module the first time it is requested:
+    ///
+    /// ```no_run
+    /// unsafe extern "C" fn handler_thunk(a0: *mut libc::c_void) -> libc::c_int {
+    ///     handler(a0 as *mut conn)
+    /// }
+    /// ```
+    ///
+    /// Returns `None` if the signatures differ in anything other than data pointer types or
+    /// trailing parameters the function does not take.
+    fn fn_ptr_thunk(
+        &self,
+        target_ty: CTypeId,
+        fn_id: CDeclId,
+    ) -> Result<Option<String>, TranslationError> {
+        let cur_file = self.cur_file();
+        let key = (cur_file, target_ty, fn_id);
+        if let Some(name) = self.fn_thunks.borrow().get(&key) {
+            return Ok(Some(name.clone()));
+        }
+
+        let (target_ret, target_params) = match self.ast_context.get_pointee_qual_type(target_ty) {
+            Some(pointee) => match self.prototype(pointee.ctype) {
+                Some(proto) => proto,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        let (src_ret, src_params) = match self.ast_context[fn_id].kind {
+            CDeclKind::Function { typ, .. } => match self.prototype(typ) {
+                Some(proto) => proto,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        if src_params.len() > target_params.len()
+            || !self.thunk_compatible(src_ret.ctype, target_ret.ctype)
+            || src_params
+                .iter()
+                .zip(&target_params)
+                .any(|(src, target)| !self.thunk_compatible(src.ctype, target.ctype))
+        {
+            return Ok(None);
+        }
+
+        let fn_name = self
+            .renamer
+            .borrow_mut()
+            .get(&fn_id)
+            .ok_or_else(|| TranslationError::generic("Did not find name for thunk target"))?;
+        let thunk_name = self
+            .renamer
+            .borrow_mut()
+            .pick_name_root(&format!("{}_thunk", fn_name));
+
+        let mut args = vec![];
+        let mut call_args = vec![];
+        for (i, target) in target_params.iter().enumerate() {
+            let arg_name = format!("a{}", i);
+            args.push(mk().arg(self.convert_type(target.ctype)?, mk().ident_pat(&arg_name)));
+
+            if let Some(src) = src_params.get(i) {
+                let arg = mk().ident_expr(&arg_name);
+                if self.same_type(src.ctype, target.ctype) {
+                    call_args.push(arg);
+                } else {
+                    call_args.push(mk().cast_expr(arg, self.convert_type(src.ctype)?));
+                }
+            }
+        }
+
+        let call = mk().call_expr(mk().path_expr(vec![fn_name]), call_args);
+        let (ret_ty, body) = if let CTypeKind::Void = self.ast_context.resolve_type(target_ret.ctype).kind {
+            (FunctionRetTy::Default(DUMMY_SP), mk().semi_stmt(call))
+        } else {
+            let ret_ty = self.convert_type(target_ret.ctype)?;
+            let val = if self.same_type(src_ret.ctype, target_ret.ctype) {
+                call
+            } else {
+                mk().cast_expr(call, ret_ty.clone())
+            };
+            (FunctionRetTy::Ty(ret_ty), mk().expr_stmt(val))
+        };
+
+        let decl = mk().fn_decl(args, ret_ty);
+        let item = mk()
+            .unsafe_()
+            .abi("C")
+            .fn_item(&thunk_name, decl, mk().block(vec![body]));
+
+        self.items
+            .borrow_mut()
+            .entry(cur_file)
+            .or_insert_with(ItemStore::new)
+            .add_item(item);
+        self.fn_thunks.borrow_mut().insert(key, thunk_name.clone());
+
+        Ok(Some(thunk_name))
+    }
+
+    /// The return and parameter types of a non-variadic, returning function prototype.
+    fn prototype(&self, fn_ty: CTypeId) -> Option<(CQualTypeId, Vec<CQualTypeId>)> {
+        match self.ast_context.resolve_type(fn_ty).kind {
+            CTypeKind::Function(ret, ref params, false, false, true) => Some((ret, params.clone())),
+            _ => None,
+        }
+    }
+
+    fn same_type(&self, a: CTypeId, b: CTypeId) -> bool {
+        self.ast_context.resolve_type(a).kind == self.ast_context.resolve_type(b).kind
+    }
+
+    /// Check whether a thunk can pass a value of type `target` on as `src` with a cast: the
+    /// types must be the same, or both pointers to data.
+    fn thunk_compatible(&self, src: CTypeId, target: CTypeId) -> bool {
+        let is_data_ptr = |ty: CTypeId| {
+            self.ast_context.resolve_type(ty).kind.is_pointer()
+                && !self.ast_context.is_function_pointer(ty)
+        };
+        self.same_type(src, target) || (is_data_ptr(src) && is_data_ptr(target))
+    }
+}
@@ -0,0 +1,273 @@
+//! Sharing of inline functions defined in headers between the units of an output crate.
+//!
+//! Every C file that includes a header with an inline function definition gets its own copy
+//! of that function. When the files of a crate are translated together, only the first one
+//! to translate such a function emits a Rust definition for it, and the others import that
+//! definition through its path in the crate. Functions whose signature mentions a struct or
+//! union aren't shared, since each unit declares its own copy of those types.
+
+use super::*;
+
+/// The inline functions that the units of an output crate have translated so far.
+#[derive(Debug, Default)]
+pub struct InlineFns {
+    defs: IndexMap<InlineFnLoc, InlineFnDef>,
+    /// Names of functions that units declared without defining before any unit defined them
+    extern_refs: IndexSet<String>,
+}
+
+/// The module of an output crate that a C file is translated into.
+pub struct CrateUnit<'c> {
+    pub inline_fns: &'c RefCell<InlineFns>,
+    /// Path to the module, e.g., `crate::src::foo`
+    pub module_path: Vec<String>,
+}
+
+/// Canonical source location of an inline function definition
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct InlineFnLoc {
+    file: PathBuf,
+    line: u64,
+    column: u64,
+}
+
+#[derive(Debug)]
+struct InlineFnDef {
+    /// C name of the function
+    name: String,
+    /// Whether the C function has external linkage
+    is_global: bool,
+    /// Whether some unit emitted an externally visible definition of the function
+    external: bool,
+    /// Path to the Rust definition, e.g., `crate::src::foo::min`
+    path: Vec<String>,
+    /// Translation of the function without its attributes, used to detect conflicting
+    /// definitions, e.g., from different macro configurations
+    text: String,
+    /// C file that the definition was translated from
+    unit: PathBuf,
+}
+
+impl<'c> Translation<'c> {
+    /// Get the canonical location of `decl_id` if it is an inline function definition from a
+    /// header that can be shared with the other units of the crate.
+    fn shared_inline_fn_loc(&self, decl_id: CDeclId) -> Option<InlineFnLoc> {
+        self.crate_unit.as_ref()?;
+        let decl = &self.ast_context[decl_id];
+        let typ = match decl.kind {
+            CDeclKind::Function {
+                is_inline: true,
+                body: Some(_),
+                typ,
+                ..
+            } => typ,
+            _ => return None,
+        };
+        if !self.is_unit_independent_type(typ) {
+            return None;
+        }
+
+        let file_id = self.ast_context.file_id(decl)?;
+        if file_id == self.main_file {
+            return None;
+        }
+        let file = self.ast_context.get_file_path(file_id)?;
+        let loc = decl.loc.as_ref()?;
+        Some(InlineFnLoc {
+            file: file.canonicalize().unwrap_or_else(|_| file.to_owned()),
+            line: loc.begin_line,
+            column: loc.begin_column,
+        })
+    }
+
+    /// Check if `ctype` is translated to the same Rust type in every unit, i.e., it doesn't
+    /// mention a struct or union.
+    fn is_unit_independent_type(&self, ctype: CTypeId) -> bool {
+        match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::Struct(_) | CTypeKind::Union(_) | CTypeKind::TypeOfExpr(_) => false,
+            CTypeKind::Pointer(qty)
+            | CTypeKind::Reference(qty)
+            | CTypeKind::BlockPointer(qty)
+            | CTypeKind::Attributed(qty, _)
+            | CTypeKind::Vector(qty, _) => self.is_unit_independent_type(qty.ctype),
+            CTypeKind::Complex(ty)
+            | CTypeKind::ConstantArray(ty, _)
+            | CTypeKind::IncompleteArray(ty)
+            | CTypeKind::VariableArray(ty, _)
+            | CTypeKind::TypeOf(ty)
+            | CTypeKind::Decayed(ty)
+            | CTypeKind::Elaborated(ty)
+            | CTypeKind::Paren(ty) => self.is_unit_independent_type(ty),
+            CTypeKind::Function(ret, ref args, ..) => {
+                self.is_unit_independent_type(ret.ctype)
+                    && args.iter().all(|arg| self.is_unit_independent_type(arg.ctype))
+            }
+            _ => true,
+        }
+    }
+
+    /// Check if `decl_id` is the first definition of an inline function that an earlier unit
+    /// of the crate declared without defining, so the definition has to be externally visible.
+    pub fn needs_external_inline_def(&self, decl_id: CDeclId) -> bool {
+        let loc = match self.shared_inline_fn_loc(decl_id) {
+            Some(loc) => loc,
+            None => return false,
+        };
+        let inline_fns = self.crate_unit.as_ref().unwrap().inline_fns.borrow();
+        match self.ast_context[decl_id].kind {
+            CDeclKind::Function {
+                ref name,
+                is_global: true,
+                ..
+            } => !inline_fns.defs.contains_key(&loc) && inline_fns.extern_refs.contains(name),
+            _ => false,
+        }
+    }
+
+    /// Share the translation of `decl_id` with the other units of the crate if it is an inline
+    /// function from a header. Returns `ConvertedDecl::NoItem` if another unit already
+    /// translated the function and it was imported from there instead.
+    pub fn share_inline_fn(&self, decl_id: CDeclId, converted: ConvertedDecl) -> ConvertedDecl {
+        if self.crate_unit.is_none() {
+            return converted;
+        }
+        match converted {
+            ConvertedDecl::Item(item) => self.share_inline_fn_def(decl_id, item),
+            ConvertedDecl::ForeignItem(item) => self.share_inline_fn_decl(decl_id, item),
+            converted => converted,
+        }
+    }
+
+    fn share_inline_fn_def(&self, decl_id: CDeclId, mut item: P<Item>) -> ConvertedDecl {
+        let loc = match self.shared_inline_fn_loc(decl_id) {
+            Some(loc) => loc,
+            None => return ConvertedDecl::Item(item),
+        };
+        let (name, is_global, external) = match self.ast_context[decl_id].kind {
+            CDeclKind::Function {
+                ref name,
+                is_global,
+                is_extern,
+                ref attrs,
+                ..
+            } => {
+                let is_extern = is_extern || self.needs_external_inline_def(decl_id);
+                let external =
+                    is_global && is_extern && !attrs.contains(&c_ast::Attribute::GnuInline);
+                (name, is_global, external)
+            }
+            _ => return ConvertedDecl::Item(item),
+        };
+
+        let text = {
+            let mut item = (*item).clone();
+            item.attrs.clear();
+            item.vis.node = VisibilityKind::Inherited;
+            pprust::item_to_string(&item)
+        };
+        let unit = self.crate_unit.as_ref().unwrap();
+        let cur_unit = self
+            .ast_context
+            .get_file_path(self.main_file)
+            .map_or_else(PathBuf::new, |path| path.to_owned());
+
+        let mut inline_fns = unit.inline_fns.borrow_mut();
+        if !inline_fns.defs.contains_key(&loc) {
+            let mut path = unit.module_path.clone();
+            if self.tcfg.reorganize_definitions {
+                let file_id = self.ast_context.file_id(&self.ast_context[decl_id]);
+                let file_path = file_id.and_then(|id| self.ast_context.get_file_path(id));
+                path.push(clean_path(&self.mod_names, file_path));
+            }
+            path.push(item.ident.to_string());
+
+            // The other units import the function, so it has to be public
+            item.vis.node = VisibilityKind::Public;
+            inline_fns.defs.insert(
+                loc,
+                InlineFnDef {
+                    name: name.clone(),
+                    is_global,
+                    external,
+                    path,
+                    text,
+                    unit: cur_unit,
+                },
+            );
+            return ConvertedDecl::Item(item);
+        }
+
+        let def = inline_fns.defs.get_mut(&loc).unwrap();
+        if def.text != text {
+            warn!(
+                "Inline function {} is translated differently in {} and {}, \
+                 so each keeps its own definition",
+                name,
+                def.unit.display(),
+                cur_unit.display(),
+            );
+            ConvertedDecl::Item(item)
+        } else if external && !def.external {
+            // This unit provides the externally visible definition that the C program
+            // links against
+            def.external = true;
+            ConvertedDecl::Item(item)
+        } else if def.path.last() == Some(&item.ident.to_string()) {
+            self.import_shared_inline_fn(decl_id, &def.path);
+            ConvertedDecl::NoItem
+        } else {
+            ConvertedDecl::Item(item)
+        }
+    }
+
+    /// Import the definition of an inline function that another unit translated instead of
+    /// declaring it as an external symbol, which might not exist.
+    fn share_inline_fn_decl(&self, decl_id: CDeclId, item: ForeignItem) -> ConvertedDecl {
+        let name = match self.ast_context[decl_id].kind {
+            CDeclKind::Function {
+                ref name,
+                body: None,
+                ..
+            } => name,
+            _ => return ConvertedDecl::ForeignItem(item),
+        };
+
+        let unit = self.crate_unit.as_ref().unwrap();
+        let mut inline_fns = unit.inline_fns.borrow_mut();
+        let def = inline_fns
+            .defs
+            .values()
+            .find(|def| def.is_global && def.name == *name);
+        match def {
+            Some(def) if def.path.last() == Some(&item.ident.to_string()) => {
+                self.import_shared_inline_fn(decl_id, &def.path);
+                ConvertedDecl::NoItem
+            }
+            Some(_) => ConvertedDecl::ForeignItem(item),
+            None => {
+                inline_fns.extern_refs.insert(name.clone());
+                ConvertedDecl::ForeignItem(item)
+            }
+        }
+    }
+
+    fn import_shared_inline_fn(&self, decl_id: CDeclId, path: &[String]) {
+        let (module_path, name) = path.split_at(path.len() - 1);
+        let name = &name[0];
+        let file_id = self.ast_context.file_id(&self.ast_context[decl_id]);
+        let mut items = self.items.borrow_mut();
+        match file_id {
+            Some(file_id) if self.tcfg.reorganize_definitions && file_id != self.main_file => {
+                // Re-export the function from the submodule of its header, where the other
+                // items from that header expect it
+                items
+                    .entry(file_id)
+                    .or_insert(ItemStore::new())
+                    .add_use_with_attr(module_path.to_vec(), name, mk().pub_());
+                let mod_name = clean_path(&self.mod_names, self.ast_context.get_file_path(file_id));
+                items[&self.main_file].add_use(vec!["self".into(), mod_name], name);
+            }
+            _ => items[&self.main_file].add_use(module_path.to_vec(), name),
+        }
+    }
+}
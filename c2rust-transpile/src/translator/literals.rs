@@ -5,17 +5,44 @@
 use super::*;
 use std::iter;
 
+/// Arrays with at least this many elements are initialized element by element when they are
+/// sparse, see `convert_sparse_array`.
+const SPARSE_ARRAY_MIN_LEN: usize = 256;
+/// An array is sparse if at most one in this many of its elements is initialized explicitly.
+const SPARSE_ARRAY_DENSITY: usize = 8;
+
 impl<'c> Translation<'c> {
     /// Generate an integer literal corresponding to the given type, value, and base.
     pub fn mk_int_lit(&self, ty: CQualTypeId, val: u64, base: IntBase) -> Result<P<Expr>, TranslationError> {
-        let lit = match base {
-            IntBase::Dec => mk().int_lit(val.into(), LitIntType::Unsuffixed),
-            IntBase::Hex => mk().float_unsuffixed_lit(format!("0x{:x}", val)),
-            IntBase::Oct => mk().float_unsuffixed_lit(format!("0o{:o}", val)),
+        // `_BitInt` literals, like `5wb`, are cast to their storage integer
+        let target_ty = match self.bit_int_storage_ty(ty.ctype) {
+            Some(storage_ty) => storage_ty,
+            None => self.convert_type(ty.ctype)?,
         };
+        let lit = mk().cast_expr(mk().lit_expr(int_lit_in_base(val, base)), target_ty);
+        Ok(self.bit_int_in_range(ty.ctype, lit))
+    }
 
-        let target_ty = self.convert_type(ty.ctype)?;
-        Ok(mk().cast_expr(mk().lit_expr(lit), target_ty))
+    /// Get the address literal of a cast from an integer literal to a pointer, e.g. the
+    /// `0x40021000` in `(volatile uint32_t *)0x40021000`, written in the same base as the C
+    /// literal. Addresses too large for the `i32` Rust infers for an unsuffixed literal are cast
+    /// to `usize` first.
+    pub fn fixed_address_lit(&self, expr: CExprId) -> Option<P<Expr>> {
+        match self.ast_context[expr].kind {
+            CExprKind::Paren(_, e)
+            | CExprKind::ImplicitCast(_, e, CastKind::IntegralCast, _, _) => {
+                self.fixed_address_lit(e)
+            }
+            CExprKind::Literal(_, CLiteral::Integer(val, base)) => {
+                let lit = mk().lit_expr(int_lit_in_base(val, base));
+                if val <= i32::max_value() as u64 {
+                    Some(lit)
+                } else {
+                    Some(mk().cast_expr(lit, mk().ident_ty("usize")))
+                }
+            }
+            _ => None,
+        }
     }
 
     /// Given an integer value this attempts to either generate the corresponding enum
@@ -64,6 +91,10 @@ impl<'c> Translation<'c> {
             _ => signed_int_expr(value),
         };
 
+        if self.is_rust_enum(def_id) {
+            return self.rust_enum_from_int(def_id, value);
+        }
+
         let target_ty = self.convert_type(enum_type_id).unwrap();
 
         mk().cast_expr(value, target_ty)
@@ -201,7 +232,11 @@ impl<'c> Translation<'c> {
         opt_union_field_id: Option<CFieldId>,
     ) -> Result<WithStmts<P<Expr>>, TranslationError> {
         match self.ast_context.resolve_type(ty.ctype).kind {
-            CTypeKind::ConstantArray(ty, n) => {
+            CTypeKind::ConstantArray(elem_ty, n) => {
+                if let Some(array) = self.embed_byte_array(ctx, ty, elem_ty, n, ids)? {
+                    return Ok(array);
+                }
+
                 // Convert all of the provided initializer values
 
                 // Need to check to see if the next item is a string literal,
@@ -221,34 +256,16 @@ impl<'c> Translation<'c> {
                 if is_string {
                     let v = ids.first().unwrap();
                     self.convert_expr(ctx.used(), *v)
+                } else if let Some(table) = self.convert_sparse_array(ctx, elem_ty, n, ids)? {
+                    Ok(table)
                 } else {
                     Ok(ids
                         .iter()
-                        .map(|id| {
-                            self.convert_expr(ctx.used(), *id)?
-                                .result_map(|x| {
-                                    // Array literals require all of their elements to be
-                                    // the correct type; they will not use implicit casts to
-                                    // change mut to const. This becomes a problem when an
-                                    // array literal is used in a position where there is no
-                                    // type information available to force its type to the
-                                    // correct const or mut variation. To avoid this issue
-                                    // we manually insert the otherwise elided casts in this
-                                    // particular context.
-                                    if let CExprKind::ImplicitCast(ty, _, CastKind::ConstCast, _, _) =
-                                        self.ast_context[*id].kind
-                                    {
-                                        let t = self.convert_type(ty.ctype)?;
-                                        Ok(mk().cast_expr(x, t))
-                                    } else {
-                                        Ok(x)
-                                    }
-                                })
-                        })
+                        .map(|id| self.convert_array_element(ctx, *id))
                         .chain(
                             // Pad out the array literal with default values to the desired size
                             iter::repeat(
-                                self.implicit_default_expr(ty, ctx.is_static)
+                                self.implicit_default_expr(elem_ty, ctx.is_static)
                             ).take(n - ids.len())
                         )
                         .collect::<Result<WithStmts<Vec<P<Expr>>>, TranslationError>>()?
@@ -294,6 +311,165 @@ impl<'c> Translation<'c> {
         }
     }
 
+    fn convert_array_element(
+        &self,
+        ctx: ExprContext,
+        id: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        self.convert_expr(ctx.used(), id)?.result_map(|x| {
+            // Array literals require all of their elements to be the correct type; they will
+            // not use implicit casts to change mut to const. This becomes a problem when an
+            // array literal is used in a position where there is no type information available
+            // to force its type to the correct const or mut variation. To avoid this issue we
+            // manually insert the otherwise elided casts in this particular context.
+            if let CExprKind::ImplicitCast(ty, _, CastKind::ConstCast, _, _) =
+                self.ast_context[id].kind
+            {
+                let t = self.convert_type(ty.ctype)?;
+                Ok(mk().cast_expr(x, t))
+            } else {
+                Ok(x)
+            }
+        })
+    }
+
+    /// Initialize a large array whose elements are mostly left to their default value, such as
+    /// a lookup table with a few designated entries, by assigning the explicitly initialized
+    /// elements to a default array, instead of listing every element in a huge array literal:
+    ///
+    /// ```ignore
+    /// static mut classes: [u8; 65536] = {
+    ///     let mut table = [0; 65536];
+    ///     table[65] = 1;
+    ///     table[97] = 1;
+    ///     table
+    /// };
+    /// ```
+    ///
+    /// Assigning to array elements is allowed in constant expressions, so this works for
+    /// statics too. Clang's initializer list already has an entry for each element up to the
+    /// last one initialized, with range designators expanded and overlapping designators
+    /// resolved to the last one, so every element gets the same value as in C.
+    fn convert_sparse_array(
+        &self,
+        ctx: ExprContext,
+        elem_ty: CTypeId,
+        n: usize,
+        ids: &[CExprId],
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        let explicit = ids
+            .iter()
+            .enumerate()
+            .filter(|&(_, &id)| match self.ast_context[id].kind {
+                CExprKind::ImplicitValueInit(..) => false,
+                _ => true,
+            })
+            .collect::<Vec<_>>();
+        if n < SPARSE_ARRAY_MIN_LEN || explicit.len() * SPARSE_ARRAY_DENSITY > n {
+            return Ok(None);
+        }
+
+        let name = self.renamer.borrow_mut().pick_name("table");
+        let len = mk().lit_expr(mk().int_lit(n as u128, LitIntType::Unsuffixed));
+        let mut table = self
+            .implicit_default_expr(elem_ty, ctx.is_static)?
+            .map(|default| {
+                vec![mk().local_stmt(P(mk().local(
+                    mk().mutbl().ident_pat(&name),
+                    None as Option<P<Ty>>,
+                    Some(mk().repeat_expr(default, len)),
+                )))]
+            });
+        for (index, &id) in explicit {
+            let index = mk().lit_expr(mk().int_lit(index as u128, LitIntType::Unsuffixed));
+            let elem = self.convert_array_element(ctx, id)?.map(|val| {
+                let lhs = mk().index_expr(mk().ident_expr(&name), index);
+                mk().semi_stmt(mk().assign_expr(lhs, val))
+            });
+            table = table.and_then(|mut stmts| -> Result<_, TranslationError> {
+                Ok(elem.map(|assignment| {
+                    stmts.push(assignment);
+                    stmts
+                }))
+            })?;
+        }
+        Ok(Some(table.map(|mut stmts| {
+            stmts.push(mk().expr_stmt(mk().ident_expr(&name)));
+            mk().block_expr(mk().block(stmts))
+        })))
+    }
+
+    /// Move an initializer of an array of bytes with at least `--embed-byte-arrays` elements
+    /// into a data file next to the output file, and initialize the array from an
+    /// `include_bytes!` of that file instead of with an array literal. Arrays of `char` need a
+    /// transmute, which is not allowed in statics, so they are only moved elsewhere.
+    fn embed_byte_array(
+        &self,
+        ctx: ExprContext,
+        ty: CQualTypeId,
+        elem_ty: CTypeId,
+        len: usize,
+        ids: &[CExprId],
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        if self.tcfg.embed_byte_arrays.map_or(true, |min_len| len < min_len) {
+            return Ok(None);
+        }
+        let needs_transmute = match self.ast_context.resolve_type(elem_ty).kind {
+            CTypeKind::UChar => false,
+            CTypeKind::Char | CTypeKind::SChar if !ctx.is_static => true,
+            _ => return Ok(None),
+        };
+        let mut bytes = Vec::with_capacity(len);
+        for &id in ids {
+            match self.byte_value(id) {
+                Some(byte) => bytes.push(byte),
+                None => return Ok(None),
+            }
+        }
+        // Elements without an initializer are zero
+        bytes.resize(len, 0);
+
+        let name = {
+            let mut embedded_bytes = self.embedded_bytes.borrow_mut();
+            let name = format!("{}.{}.bin", self.data_file_stem, embedded_bytes.len());
+            embedded_bytes.insert(name.clone(), bytes);
+            name
+        };
+        let name_token = TokenTree::token(
+            token::Interpolated(Rc::new(Nonterminal::NtExpr(mk().lit_expr(mk().str_lit(&name))))),
+            DUMMY_SP,
+        );
+        let include = mk().mac_expr(mk().mac(
+            vec!["include_bytes"],
+            vec![name_token],
+            MacDelimiter::Parenthesis,
+        ));
+
+        if !needs_transmute {
+            return Ok(Some(WithStmts::new_val(mk().unary_expr(ast::UnOp::Deref, include))));
+        }
+        let len_lit = mk().lit_expr(mk().int_lit(len as u128, LitIntType::Unsuffixed));
+        let source_ty = mk().ref_ty(mk().array_ty(mk().path_ty(vec!["u8"]), len_lit));
+        let target_ty = mk().ref_ty(self.convert_type(ty.ctype)?);
+        if ctx.is_const { self.use_feature("const_transmute"); }
+        let pointer = transmute_expr(source_ty, target_ty, include, self.tcfg.emit_no_std);
+        let array = mk().unary_expr(ast::UnOp::Deref, pointer);
+        Ok(Some(WithStmts::new_unsafe_val(array)))
+    }
+
+    /// Get the value of an integer or character constant in a byte array initializer,
+    /// converted to a byte like C does.
+    fn byte_value(&self, id: CExprId) -> Option<u8> {
+        match *self.ast_context.resolve_expr_value(id) {
+            CExprKind::Literal(_, CLiteral::Integer(val, _))
+            | CExprKind::Literal(_, CLiteral::Character(val)) => Some(val as u8),
+            CExprKind::Unary(_, c_ast::UnOp::Negate, arg, _) => {
+                self.byte_value(arg).map(u8::wrapping_neg)
+            }
+            _ => None,
+        }
+    }
+
     fn convert_union_literal(
         &self,
         ctx: ExprContext,
@@ -305,27 +481,66 @@ impl<'c> Translation<'c> {
         let union_field_id = opt_union_field_id.expect("union field ID");
 
         match self.ast_context.index(union_id).kind {
-            CDeclKind::Union { .. } => {
+            CDeclKind::Union {
+                platform_byte_size,
+                ..
+            } => {
                 let union_name = self
                     .type_converter
                     .borrow()
                     .resolve_decl_name(union_id)
                     .unwrap();
                 match self.ast_context.index(union_field_id).kind {
-                    CDeclKind::Field { typ: field_ty, .. } => {
+                    CDeclKind::Field {
+                        typ: field_ty,
+                        platform_type_bitwidth,
+                        ..
+                    } => {
                         let val = if ids.is_empty() {
                             self.implicit_default_expr(field_ty.ctype, ctx.is_static)?
                         } else {
                             self.convert_expr(ctx.used(), ids[0])?
                         };
+                        let field_name = self
+                            .type_converter
+                            .borrow()
+                            .resolve_field_name(Some(union_id), union_field_id)
+                            .unwrap();
+
+                        // A union literal leaves the bytes past the initialized member
+                        // uninitialized, but C zero-fills them. Outside of constant
+                        // initializers, write a smaller member into a zeroed union instead.
+                        if !ctx.is_static
+                            && !ctx.is_const
+                            && platform_type_bitwidth < platform_byte_size * 8
+                        {
+                            let name = self.renamer.borrow_mut().pick_name("init");
+                            let zeroed = mk().call_expr(
+                                mk().path_expr(vec!["", "std", "mem", "zeroed"]),
+                                vec![] as Vec<P<Expr>>,
+                            );
+                            let local = mk().local(
+                                mk().mutbl().ident_pat(name.clone()),
+                                Some(mk().path_ty(vec![union_name])),
+                                Some(zeroed),
+                            );
+                            return val.and_then(|v| {
+                                let field =
+                                    mk().field_expr(mk().ident_expr(name.clone()), field_name);
+                                let mut init = WithStmts::new(
+                                    vec![
+                                        mk().local_stmt(P(local)),
+                                        mk().semi_stmt(mk().assign_expr(field, v)),
+                                    ],
+                                    mk().ident_expr(name),
+                                );
+                                init.set_unsafe();
+                                Ok(init)
+                            });
+                        }
 
                         Ok(val.map(|v| {
                             let name = vec![mk().path_segment(union_name)];
-                            let field_name = self
-                                .type_converter
-                                .borrow()
-                                .resolve_field_name(Some(union_id), union_field_id)
-                                .unwrap();
                             let fields = vec![mk().field(field_name, v)];
                             mk().struct_expr(name, fields)
                         }))
@@ -337,3 +552,12 @@ impl<'c> Translation<'c> {
         }
     }
 }
+
+/// Generate an integer literal token for the given value, written in the given base.
+fn int_lit_in_base(val: u64, base: IntBase) -> Lit {
+    match base {
+        IntBase::Dec => mk().int_lit(val.into(), LitIntType::Unsuffixed),
+        IntBase::Hex => mk().float_unsuffixed_lit(format!("0x{:x}", val)),
+        IntBase::Oct => mk().float_unsuffixed_lit(format!("0o{:o}", val)),
+    }
+}
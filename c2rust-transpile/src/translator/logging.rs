@@ -0,0 +1,194 @@
+//! Translation of calls to C logging functions into `log` crate macros
+
+use super::*;
+
+/// Map the name of a C logging level (e.g. `INFO` or `LOG_WARNING`) to the name of the
+/// corresponding `log` macro.
+fn log_macro_for_level(name: &str) -> Option<&'static str> {
+    let name = name.rsplit('_').next().unwrap_or(name).to_ascii_lowercase();
+    match name.as_str() {
+        "trace" | "verbose" => Some("trace"),
+        "debug" | "dbg" => Some("debug"),
+        "info" | "notice" => Some("info"),
+        "warn" | "warning" => Some("warn"),
+        "error" | "err" | "crit" | "critical" | "fatal" | "alert" | "emerg" => Some("error"),
+        _ => None,
+    }
+}
+
+/// Build a `log::Level::*` path for the given `log` macro name.
+fn log_level_path(macro_name: &str) -> P<Expr> {
+    let variant = match macro_name {
+        "trace" => "Trace",
+        "debug" => "Debug",
+        "info" => "Info",
+        "warn" => "Warn",
+        _ => "Error",
+    };
+    mk().path_expr(vec!["log", "Level", variant])
+}
+
+impl<'c> Translation<'c> {
+    /// Convert a call to one of the logging functions named with `--log-function` into a `log`
+    /// crate macro. The function must take a level and a message without any format
+    /// arguments, i.e. `my_log(INFO, "message")` becomes `log::info!("message")`.
+    ///
+    /// Returns `None` for calls that don't have this shape, which are translated as usual.
+    pub fn convert_log_call(
+        &self,
+        ctx: ExprContext,
+        func: CExprId,
+        args: &[CExprId],
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        if self.tcfg.log_functions.is_empty() || args.len() != 2 {
+            return Ok(None);
+        }
+
+        let fn_name = match self.ast_context[func].kind {
+            CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) => {
+                match self.ast_context[fexp].kind {
+                    CExprKind::DeclRef(_, decl_id, _) => self.ast_context[decl_id].kind.get_name(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match fn_name {
+            Some(name) if self.tcfg.log_functions.contains(name) => {}
+            _ => return Ok(None),
+        }
+
+        let msg = match self.log_message(args[1]) {
+            Some(msg) => msg,
+            None => return Ok(None),
+        };
+        let msg = mk().lit_expr(mk().str_lit(msg));
+        let msg_token = TokenTree::token(
+            token::Interpolated(Rc::new(Nonterminal::NtExpr(msg))),
+            DUMMY_SP,
+        );
+
+        let mac = if let Some(macro_name) = self.static_log_level(args[0]) {
+            // The level is known at translation time, so use the level-specific macro
+            WithStmts::new_val(mk().mac(
+                vec!["log", macro_name],
+                vec![msg_token],
+                MacDelimiter::Parenthesis,
+            ))
+        } else {
+            let level = match self.dynamic_log_level(ctx, args[0])? {
+                Some(level) => level,
+                None => return Ok(None),
+            };
+            level.map(|level| {
+                let tokens = vec![
+                    TokenTree::token(token::Interpolated(Rc::new(Nonterminal::NtExpr(level))), DUMMY_SP),
+                    TokenTree::token(token::Comma, DUMMY_SP),
+                    msg_token,
+                ];
+                mk().mac(vec!["log", "log"], tokens, MacDelimiter::Parenthesis)
+            })
+        };
+
+        self.use_crate(ExternCrate::Log);
+        Ok(Some(mac.map(|mac| mk().mac_expr(mac))))
+    }
+
+    /// Get the text of a logging message, if it is a string literal without any format
+    /// specifiers.
+    fn log_message(&self, expr: CExprId) -> Option<String> {
+        let bytes = match self.ast_context.resolve_expr_value(expr) {
+            CExprKind::Literal(_, CLiteral::String(bytes, 1)) => bytes,
+            _ => return None,
+        };
+        let msg = std::str::from_utf8(bytes).ok()?;
+        let msg = msg.trim_end_matches('\0');
+        if msg.contains('%') {
+            return None;
+        }
+
+        // `log` adds its own line endings
+        let msg = msg.trim_end_matches('\n');
+        Some(msg.replace('{', "{{").replace('}', "}}"))
+    }
+
+    /// If the level of a logging call is a named constant (an enum constant or a macro), get
+    /// the `log` macro for it.
+    fn static_log_level(&self, mut expr: CExprId) -> Option<&'static str> {
+        loop {
+            if let Some(macros) = self.ast_context.macro_expansions.get(&expr) {
+                let level = macros.iter()
+                    .filter_map(|&mac| self.ast_context[mac].kind.get_name())
+                    .filter_map(|name| log_macro_for_level(name))
+                    .next();
+                if level.is_some() {
+                    return level;
+                }
+            }
+            match self.ast_context[expr].kind {
+                CExprKind::ImplicitCast(_, e, _, _, _) | CExprKind::ExplicitCast(_, e, _, _, _) => {
+                    expr = e;
+                }
+                CExprKind::DeclRef(_, decl_id, _) => {
+                    return match self.ast_context[decl_id].kind {
+                        CDeclKind::EnumConstant { ref name, .. } => log_macro_for_level(name),
+                        _ => None,
+                    };
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Map a level that is only known at runtime to a `log::Level`. This only works if the level
+    /// has an enum type whose constants all name logging levels; the result is a `match` from
+    /// each constant's value to its `log::Level`.
+    fn dynamic_log_level(
+        &self,
+        ctx: ExprContext,
+        expr: CExprId,
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        let mut level_expr = expr;
+        while let CExprKind::ImplicitCast(_, e, _, _, _) = self.ast_context[level_expr].kind {
+            level_expr = e;
+        }
+        let enum_id = match self.ast_context[level_expr].kind.get_type()
+            .map(|ty| &self.ast_context.resolve_type(ty).kind)
+        {
+            Some(&CTypeKind::Enum(enum_id)) => enum_id,
+            _ => return Ok(None),
+        };
+        let variants = match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { ref variants, .. } => variants,
+            _ => return Ok(None),
+        };
+
+        let mut arms = vec![];
+        for &variant in variants {
+            let (name, value) = match self.ast_context[variant].kind {
+                CDeclKind::EnumConstant { ref name, value } => (name, value),
+                _ => return Ok(None),
+            };
+            let macro_name = match log_macro_for_level(name) {
+                Some(macro_name) => macro_name,
+                None => return Ok(None),
+            };
+            let pat = match value {
+                ConstIntExpr::U(v) => mk().lit_expr(mk().int_lit(v as u128, "i64")),
+                ConstIntExpr::I(v) if v >= 0 => mk().lit_expr(mk().int_lit(v as u128, "i64")),
+                ConstIntExpr::I(v) => mk().unary_expr(
+                    "-",
+                    mk().lit_expr(mk().int_lit((v as i128).abs() as u128, "i64")),
+                ),
+            };
+            arms.push(mk().arm(mk().lit_pat(pat), None as Option<P<Expr>>, log_level_path(macro_name)));
+        }
+        // C enums can hold values other than their constants
+        arms.push(mk().arm(mk().wild_pat(), None as Option<P<Expr>>, log_level_path("error")));
+
+        let level = self.convert_expr(ctx.used(), level_expr)?;
+        Ok(Some(level.map(|level| {
+            mk().match_expr(mk().cast_expr(level, mk().path_ty(vec!["i64"])), arms)
+        })))
+    }
+}
@@ -0,0 +1,530 @@
+//! Recognition of loops that fill or copy memory.
+//!
+//! Loops like `for (i = 0; i < n; i++) dst[i] = src[i];` would otherwise be translated to
+//! element-by-element pointer writes. Before a loop is added to the CFG, we check if it is a
+//! canonical fill or copy loop: a counter that starts anywhere, is compared against a
+//! loop-invariant bound with `<` and incremented by one, and a body that does nothing but
+//! assign the element of `dst` at the counter. Such loops are replaced with a single bulk
+//! operation:
+//!
+//! * copies between two distinct arrays become `dst[i..n].copy_from_slice(&src[i..n])`,
+//! * copies involving pointers become `ptr::copy`, guarded by a check that the buffers don't
+//!   overlap in a way that the forward loop would observe, and otherwise run the original loop,
+//! * fills with zero, or of byte-sized elements, become `ptr::write_bytes`.
+//!
+//! Other fills are left alone, since `ptr::write_bytes` can only repeat a single byte.
+
+use super::*;
+
+/// Array or pointer that a fill or copy loop indexes
+struct LoopBase {
+    var: CDeclId,
+    /// Array or pointer expression that is indexed by the loop counter
+    expr: CExprId,
+    is_array: bool,
+}
+
+enum LoopOp {
+    Fill { dst: LoopBase, val: CExprId },
+    Copy { dst: LoopBase, src: LoopBase },
+}
+
+/// A fill or copy loop
+struct IdiomLoop {
+    /// Initialization of the counter, if the loop has one
+    init: Option<CStmtId>,
+    /// Whether the counter is declared by the loop, so it isn't visible after it
+    declares_counter: bool,
+    /// The loop condition, `counter < bound`
+    cond: CExprId,
+    /// Read of the counter on the left-hand side of the condition
+    counter_val: CExprId,
+    bound: CExprId,
+    /// Element assignment in the body
+    assign: CExprId,
+    incr: CExprId,
+    op: LoopOp,
+}
+
+impl<'c> Translation<'c> {
+    /// Translate `stmt_id` to a bulk fill or copy if it is a loop that does nothing else.
+    pub fn convert_loop_idiom(
+        &self,
+        ctx: ExprContext,
+        stmt_id: CStmtId,
+    ) -> Result<Option<Vec<Stmt>>, TranslationError> {
+        let idiom = match self.match_idiom_loop(stmt_id) {
+            Some(idiom) => idiom,
+            None => return Ok(None),
+        };
+
+        self.with_scope(|| -> Result<Option<Vec<Stmt>>, TranslationError> {
+            let mut stmts = vec![];
+            if let Some(init) = idiom.init {
+                match self.ast_context[init].kind {
+                    CStmtKind::Decls(ref decls) => {
+                        let info = self.convert_decl_stmt_info(ctx, decls[0])?;
+                        stmts.extend(info.decl_and_assign.unwrap_or_default());
+                    }
+                    CStmtKind::Expr(expr) => {
+                        stmts.extend(self.convert_expr(ctx.unused(), expr)?.into_stmts());
+                    }
+                    _ => return Ok(None),
+                }
+            }
+
+            let cond = self.convert_condition(ctx, true, idiom.cond)?.to_expr();
+            let start = || {
+                self.convert_expr(ctx.used(), idiom.counter_val)
+                    .map(WithStmts::to_expr)
+            };
+            let end = || {
+                self.convert_expr(ctx.used(), idiom.bound)
+                    .map(WithStmts::to_expr)
+            };
+            let count = || -> Result<P<Expr>, TranslationError> {
+                let count = mk().binary_expr(BinOpKind::Sub, end()?, start()?);
+                Ok(mk().cast_expr(count, mk().path_ty(vec!["usize"])))
+            };
+            let offset = |ptr: P<Expr>| -> Result<P<Expr>, TranslationError> {
+                let start = mk().cast_expr(start()?, mk().path_ty(vec!["isize"]));
+                Ok(mk().method_call_expr(ptr, "offset", vec![start]))
+            };
+            let std_or_core = if self.tcfg.emit_no_std { "core" } else { "std" };
+
+            let mut bulk = vec![];
+            let mut fallback = None;
+            match idiom.op {
+                LoopOp::Fill { ref dst, val } => {
+                    let byte = self.convert_expr(ctx.used(), val)?.to_expr();
+                    let byte = mk().cast_expr(byte, mk().path_ty(vec!["u8"]));
+                    let write_bytes = mk().path_expr(vec!["", std_or_core, "ptr", "write_bytes"]);
+                    let dst = offset(self.convert_loop_base_ptr(ctx, dst, true)?)?;
+                    bulk.push(
+                        mk().semi_stmt(mk().call_expr(write_bytes, vec![dst, byte, count()?])),
+                    );
+                }
+
+                LoopOp::Copy { ref dst, ref src } if dst.is_array && src.is_array => {
+                    let range = || -> Result<P<Expr>, TranslationError> {
+                        let usize_ty = mk().path_ty(vec!["usize"]);
+                        Ok(mk().range_expr(
+                            Some(mk().cast_expr(start()?, usize_ty.clone())),
+                            Some(mk().cast_expr(end()?, usize_ty)),
+                        ))
+                    };
+                    let dst = self.convert_expr(ctx.used(), dst.expr)?.to_expr();
+                    let src = self.convert_expr(ctx.used(), src.expr)?.to_expr();
+                    let src = mk().addr_of_expr(mk().index_expr(src, range()?));
+                    let copy = mk().method_call_expr(
+                        mk().index_expr(dst, range()?),
+                        "copy_from_slice",
+                        vec![src],
+                    );
+                    bulk.push(mk().semi_stmt(copy));
+                }
+
+                LoopOp::Copy { ref dst, ref src } => {
+                    // A forward copy loop is only a `memmove` if it doesn't overwrite source
+                    // elements before reading them, i.e., `dst` doesn't start inside `src`
+                    let addr = |ptr: P<Expr>| mk().cast_expr(ptr, mk().path_ty(vec!["usize"]));
+                    let dst_ptr = || self.convert_loop_base_ptr(ctx, dst, true);
+                    let src_ptr = || self.convert_loop_base_ptr(ctx, src, false);
+                    let src_end = mk().method_call_expr(
+                        src_ptr()?,
+                        "offset",
+                        vec![mk().cast_expr(end()?, mk().path_ty(vec!["isize"]))],
+                    );
+                    let no_overlap = mk().binary_expr(
+                        BinOpKind::Or,
+                        mk().binary_expr(BinOpKind::Le, addr(dst_ptr()?), addr(src_ptr()?)),
+                        mk().binary_expr(BinOpKind::Ge, addr(offset(dst_ptr()?)?), addr(src_end)),
+                    );
+
+                    let copy = mk().path_expr(vec!["", std_or_core, "ptr", "copy"]);
+                    let args = vec![offset(src_ptr()?)?, offset(dst_ptr()?)?, count()?];
+                    bulk.push(mk().semi_stmt(mk().call_expr(copy, args)));
+
+                    let mut body = self.convert_expr(ctx.unused(), idiom.assign)?.into_stmts();
+                    body.extend(self.convert_expr(ctx.unused(), idiom.incr)?.into_stmts());
+                    let orig_loop = mk().while_expr(
+                        self.convert_condition(ctx, true, idiom.cond)?.to_expr(),
+                        mk().block(body),
+                        None as Option<Ident>,
+                    );
+                    fallback = Some((no_overlap, orig_loop));
+                }
+            }
+
+            if !idiom.declares_counter {
+                // The counter ends up at the bound, as after the original loop
+                bulk.push(mk().semi_stmt(mk().assign_expr(start()?, end()?)));
+            }
+
+            let bulk = match fallback {
+                Some((no_overlap, orig_loop)) => {
+                    let orig_loop = mk().block(vec![mk().semi_stmt(orig_loop)]);
+                    let ifte = mk().ifte_expr(
+                        no_overlap,
+                        mk().block(bulk),
+                        Some(mk().block_expr(orig_loop)),
+                    );
+                    vec![mk().semi_stmt(ifte)]
+                }
+                None => bulk,
+            };
+            stmts.push(mk().semi_stmt(mk().ifte_expr(
+                cond,
+                mk().block(bulk),
+                None as Option<P<Expr>>,
+            )));
+
+            if idiom.declares_counter {
+                // Keep the counter local to the loop
+                Ok(Some(vec![
+                    mk().semi_stmt(mk().block_expr(mk().block(stmts)))
+                ]))
+            } else {
+                Ok(Some(stmts))
+            }
+        })
+    }
+
+    /// Get a pointer to the first element of the base of a fill or copy loop.
+    fn convert_loop_base_ptr(
+        &self,
+        ctx: ExprContext,
+        base: &LoopBase,
+        mutbl: bool,
+    ) -> Result<P<Expr>, TranslationError> {
+        let expr = self.convert_expr(ctx.used(), base.expr)?.to_expr();
+        if base.is_array {
+            let method = if mutbl { "as_mut_ptr" } else { "as_ptr" };
+            Ok(mk().method_call_expr(expr, method, vec![] as Vec<P<Expr>>))
+        } else {
+            Ok(expr)
+        }
+    }
+
+    fn match_idiom_loop(&self, stmt_id: CStmtId) -> Option<IdiomLoop> {
+        let (init, cond, assign, incr) = match self.ast_context[stmt_id].kind {
+            CStmtKind::ForLoop {
+                init,
+                condition: Some(cond),
+                increment: Some(incr),
+                body,
+            } => match self.loop_body_exprs(body)?.as_slice() {
+                &[assign] => (init, cond, assign, incr),
+                _ => return None,
+            },
+            CStmtKind::While { condition, body } => match self.loop_body_exprs(body)?.as_slice() {
+                &[assign, incr] => (None, condition, assign, incr),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        // The condition is `counter < bound`, where both have the same type
+        let (counter_val, bound) = match self.ast_context[cond].kind {
+            CExprKind::Binary(_, BinOp::Less, lhs, rhs, _, _) => (lhs, rhs),
+            _ => return None,
+        };
+        let counter = self.loop_counter_read(counter_val)?;
+        let counter_ty = match self.ast_context[counter].kind {
+            CDeclKind::Variable {
+                has_static_duration: false,
+                has_thread_duration: false,
+                typ,
+                ..
+            } => typ,
+            _ => return None,
+        };
+        let bound_ty = self.ast_context[bound].kind.get_qual_type()?;
+        if counter_ty.qualifiers.is_volatile
+            || !self
+                .ast_context
+                .resolve_type(counter_ty.ctype)
+                .kind
+                .is_integral_type()
+            || self.ast_context.resolve_type_id(counter_ty.ctype)
+                != self.ast_context.resolve_type_id(bound_ty.ctype)
+        {
+            return None;
+        }
+
+        // The counter is initialized by the loop or starts at its current value
+        let declares_counter = match init.map(|init| &self.ast_context[init].kind) {
+            None => false,
+            Some(CStmtKind::Decls(decls)) => match **decls {
+                [decl] if decl == counter => true,
+                _ => return None,
+            },
+            Some(&CStmtKind::Expr(expr)) => match self.ast_context[expr].kind {
+                CExprKind::Binary(_, BinOp::Assign, lhs, _, _, _)
+                    if self.is_decl_ref(lhs, counter) =>
+                {
+                    false
+                }
+                _ => return None,
+            },
+            Some(_) => return None,
+        };
+
+        // The counter is incremented by one
+        match self.ast_context[incr].kind {
+            CExprKind::Unary(_, UnOp::PreIncrement, arg, _)
+            | CExprKind::Unary(_, UnOp::PostIncrement, arg, _)
+                if self.is_decl_ref(arg, counter) => {}
+            CExprKind::Binary(_, BinOp::AssignAdd, lhs, rhs, _, _)
+                if self.is_decl_ref(lhs, counter) && self.is_int_literal(rhs, 1) => {}
+            _ => return None,
+        }
+
+        // The body assigns `dst[counter]`
+        let (elt, val) = match self.ast_context[assign].kind {
+            CExprKind::Binary(_, BinOp::Assign, lhs, rhs, _, _) => (lhs, rhs),
+            _ => return None,
+        };
+        let (dst, elt_ty) = self.loop_element(elt, counter)?;
+        let elt_kind = &self.ast_context.resolve_type(elt_ty.ctype).kind;
+        let is_scalar =
+            elt_kind.is_integral_type() || elt_kind.is_floating_type() || elt_kind.is_pointer();
+        if elt_ty.qualifiers.is_volatile || !is_scalar {
+            return None;
+        }
+
+        let op = match self.ast_context[val].kind {
+            // Copy from `src[counter]`
+            CExprKind::ImplicitCast(_, src_elt, CastKind::LValueToRValue, _, _)
+                if self.loop_element(src_elt, counter).is_some() =>
+            {
+                let (src, src_elt_ty) = self.loop_element(src_elt, counter)?;
+                if src_elt_ty.qualifiers.is_volatile
+                    || self.ast_context.resolve_type_id(src_elt_ty.ctype)
+                        != self.ast_context.resolve_type_id(elt_ty.ctype)
+                    || (src.is_array && dst.is_array && src.var == dst.var)
+                {
+                    return None;
+                }
+                LoopOp::Copy { dst, src }
+            }
+
+            // Fill with a loop-invariant value that is a repeated byte
+            _ => {
+                let is_byte = match *elt_kind {
+                    CTypeKind::Char | CTypeKind::SChar | CTypeKind::UChar => true,
+                    _ => false,
+                };
+                if !(is_byte || self.is_zero_expr(val)) {
+                    return None;
+                }
+                let excluded = [counter, dst.var];
+                if !self.is_loop_invariant(val, &excluded) {
+                    return None;
+                }
+                LoopOp::Fill { dst, val }
+            }
+        };
+
+        // Nothing that the loop reads may be modified through the elements it writes
+        let mut excluded = vec![counter];
+        match op {
+            LoopOp::Fill { ref dst, .. } => excluded.push(dst.var),
+            LoopOp::Copy { ref dst, ref src } => excluded.extend(&[dst.var, src.var]),
+        }
+        if !self.is_loop_invariant(bound, &excluded) {
+            return None;
+        }
+        let mut vars = vec![counter];
+        match op {
+            LoopOp::Fill { ref dst, val } => {
+                vars.extend(self.loop_base_var(dst));
+                vars.extend(self.referenced_vars(val));
+            }
+            LoopOp::Copy { ref dst, ref src } => {
+                vars.extend(self.loop_base_var(dst));
+                vars.extend(self.loop_base_var(src));
+            }
+        }
+        vars.extend(self.referenced_vars(bound));
+        if vars.iter().any(|&var| self.is_address_taken(var)) {
+            return None;
+        }
+
+        Some(IdiomLoop {
+            init,
+            declares_counter,
+            cond,
+            counter_val,
+            bound,
+            assign,
+            incr,
+            op,
+        })
+    }
+
+    /// Get the expressions that make up the body of a loop, if it only contains expressions.
+    fn loop_body_exprs(&self, body: CStmtId) -> Option<Vec<CExprId>> {
+        match self.ast_context[body].kind {
+            CStmtKind::Expr(expr) => Some(vec![expr]),
+            CStmtKind::Compound(ref stmts) => stmts
+                .iter()
+                .map(|&stmt| match self.ast_context[stmt].kind {
+                    CStmtKind::Expr(expr) => Some(expr),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// Get the variable that `expr` reads, if it is a read of a variable.
+    fn loop_counter_read(&self, expr: CExprId) -> Option<CDeclId> {
+        match self.ast_context[expr].kind {
+            CExprKind::ImplicitCast(_, arg, CastKind::LValueToRValue, _, _) => {
+                match self.ast_context[arg].kind {
+                    CExprKind::DeclRef(_, decl_id, _) => Some(decl_id),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn is_decl_ref(&self, expr: CExprId, decl: CDeclId) -> bool {
+        match self.ast_context[expr].kind {
+            CExprKind::DeclRef(_, decl_id, _) => decl_id == decl,
+            _ => false,
+        }
+    }
+
+    fn is_int_literal(&self, expr: CExprId, val: u64) -> bool {
+        match self.ast_context[expr].kind {
+            CExprKind::ImplicitCast(_, arg, _, _, _) => self.is_int_literal(arg, val),
+            CExprKind::Literal(_, CLiteral::Integer(n, _)) => n == val,
+            _ => false,
+        }
+    }
+
+    /// Check if `expr` is a zero literal of any type, whose bytes are all zero.
+    fn is_zero_expr(&self, expr: CExprId) -> bool {
+        match self.ast_context[expr].kind {
+            CExprKind::ImplicitCast(_, arg, _, _, _)
+            | CExprKind::ExplicitCast(_, arg, _, _, _)
+            | CExprKind::Paren(_, arg) => self.is_zero_expr(arg),
+            CExprKind::Literal(_, CLiteral::Integer(0, _))
+            | CExprKind::Literal(_, CLiteral::Character(0)) => true,
+            CExprKind::Literal(_, CLiteral::Floating(val, _)) => val == 0.0,
+            _ => false,
+        }
+    }
+
+    /// Match `base[counter]`, returning the base and the element type.
+    fn loop_element(&self, elt: CExprId, counter: CDeclId) -> Option<(LoopBase, CQualTypeId)> {
+        let (elt_ty, base, idx) = match self.ast_context[elt].kind {
+            CExprKind::ArraySubscript(ty, base, idx, _) => (ty, base, idx),
+            _ => return None,
+        };
+        if self.loop_counter_read(idx) != Some(counter) {
+            return None;
+        }
+
+        let (var, expr, is_array) = match self.ast_context[base].kind {
+            CExprKind::ImplicitCast(_, arr, CastKind::ArrayToPointerDecay, _, _) => {
+                match self.ast_context[arr].kind {
+                    CExprKind::DeclRef(_, decl_id, _) => (decl_id, arr, true),
+                    _ => return None,
+                }
+            }
+            _ => (self.loop_counter_read(base)?, base, false),
+        };
+        if var == counter || !self.is_loop_base_var(var) {
+            return None;
+        }
+        Some((
+            LoopBase {
+                var,
+                expr,
+                is_array,
+            },
+            elt_ty,
+        ))
+    }
+
+    fn is_loop_base_var(&self, decl: CDeclId) -> bool {
+        match self.ast_context[decl].kind {
+            CDeclKind::Variable { typ, .. } => !typ.qualifiers.is_volatile,
+            _ => false,
+        }
+    }
+
+    /// Get the variable of a pointer base, which the loop must not modify through aliases.
+    fn loop_base_var(&self, base: &LoopBase) -> Option<CDeclId> {
+        if base.is_array {
+            None
+        } else {
+            Some(base.var)
+        }
+    }
+
+    /// Check if `expr` is a side-effect free expression that doesn't read memory other than the
+    /// variables it refers to, none of which may be in `excluded`.
+    fn is_loop_invariant(&self, expr: CExprId, excluded: &[CDeclId]) -> bool {
+        match self.ast_context[expr].kind {
+            CExprKind::Literal(..) | CExprKind::UnaryType(..) | CExprKind::OffsetOf(..) => true,
+            CExprKind::DeclRef(_, decl_id, _) => {
+                let is_value = match self.ast_context[decl_id].kind {
+                    CDeclKind::Variable { typ, .. } => !typ.qualifiers.is_volatile,
+                    CDeclKind::EnumConstant { .. } => true,
+                    _ => false,
+                };
+                is_value && !excluded.contains(&decl_id)
+            }
+            CExprKind::ImplicitCast(_, arg, kind, _, _)
+            | CExprKind::ExplicitCast(_, arg, kind, _, _) => match kind {
+                CastKind::ArrayToPointerDecay | CastKind::FunctionToPointerDecay => false,
+                _ => self.is_loop_invariant(arg, excluded),
+            },
+            CExprKind::Paren(_, arg)
+            | CExprKind::Unary(_, UnOp::Plus, arg, _)
+            | CExprKind::Unary(_, UnOp::Negate, arg, _)
+            | CExprKind::Unary(_, UnOp::Complement, arg, _)
+            | CExprKind::Unary(_, UnOp::Not, arg, _) => self.is_loop_invariant(arg, excluded),
+            CExprKind::Binary(_, op, lhs, rhs, _, _) => {
+                op.underlying_assignment().is_none()
+                    && op != BinOp::Assign
+                    && op != BinOp::Comma
+                    && self.is_loop_invariant(lhs, excluded)
+                    && self.is_loop_invariant(rhs, excluded)
+            }
+            _ => false,
+        }
+    }
+
+    fn referenced_vars(&self, expr: CExprId) -> Vec<CDeclId> {
+        DFExpr::new(&self.ast_context, expr.into())
+            .filter_map(|id| match id {
+                SomeId::Expr(expr_id) => match self.ast_context[expr_id].kind {
+                    CExprKind::DeclRef(_, decl_id, _) => Some(decl_id),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Check if the address of the variable `decl` is taken anywhere.
+    fn is_address_taken(&self, decl: CDeclId) -> bool {
+        self.ast_context
+            .iter_exprs()
+            .any(|(_, expr)| match expr.kind {
+                CExprKind::Unary(_, UnOp::AddressOf, mut arg, _) => {
+                    while let CExprKind::Paren(_, inner) = self.ast_context[arg].kind {
+                        arg = inner;
+                    }
+                    self.is_decl_ref(arg, decl)
+                }
+                _ => false,
+            })
+    }
+}
@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::mem;
 use std::ops::Index;
 use std::path::{self, PathBuf};
@@ -19,12 +19,13 @@ use syntax::ptr::*;
 use syntax::source_map::{FilePathMapping, SourceMap};
 use syntax::tokenstream::{TokenStream, TokenTree};
 use syntax::{ast, with_globals};
-use syntax_pos::{FileName, Span, DUMMY_SP};
+use syntax_pos::{BytePos, FileName, Span, DUMMY_SP};
 use syntax_pos::edition::Edition;
 
 use crate::rust_ast::pos_to_span;
 use crate::rust_ast::comment_store::CommentStore;
 use crate::rust_ast::item_store::ItemStore;
+use crate::rust_ast::path_roots::path_roots;
 use crate::rust_ast::traverse::Traversal;
 use c2rust_ast_builder::{mk, Builder, IntoSymbol};
 use c2rust_ast_printer::pprust::{self, Comments, PrintState};
@@ -33,7 +34,7 @@ use crate::c_ast;
 use crate::c_ast::iterators::{DFExpr, SomeId};
 use crate::c_ast::*;
 use crate::cfg;
-use crate::convert_type::TypeConverter;
+use crate::convert_type::{bit_int_newtype_name, reserved_renamer, TypeConverter};
 use crate::renamer::Renamer;
 use crate::with_stmts::WithStmts;
 use crate::{ExternCrate, ExternCrateDetails, TranspilerConfig};
@@ -41,17 +42,49 @@ use c2rust_ast_exporter::clang_ast::LRValue;
 
 mod assembly;
 mod atomics;
+mod bit_ints;
+mod blocks;
 mod builtins;
+mod cleanup;
 mod comments;
+mod const_tables;
+mod enums;
+mod error_codes;
+mod ffi_safety;
+mod fn_macros;
+mod fn_tables;
+mod fn_thunks;
+mod inline_fns;
 mod literals;
+mod logging;
+mod loop_idioms;
 mod main_function;
 mod named_references;
 mod operators;
+mod output_style;
+mod pthreads;
+mod refcounts;
 mod simd;
+mod split_output;
+mod stmt_shims;
+mod string_literals;
+mod string_tables;
 mod structs;
+mod system_headers;
 mod variadic;
 
 pub use crate::diagnostics::{TranslationError, TranslationErrorKind};
+pub use self::inline_fns::{CrateUnit, InlineFns};
+use self::blocks::{BlockShim, BlockSignature};
+use self::ffi_safety::FfiSafety;
+use self::fn_macros::FnMacro;
+use self::pthreads::PthreadObjects;
+use self::refcounts::RefcountField;
+use self::stmt_shims::StmtShim;
+use self::system_headers::SystemType;
+pub use self::output_style::{OutputStyle, OutputStyleOverrides};
+pub use self::pthreads::PthreadsBackend;
+pub use self::string_literals::StringLiterals;
 use crate::CrateSet;
 use crate::PragmaVec;
 
@@ -103,6 +136,18 @@ pub enum ReplaceMode {
     Extern,
 }
 
+/// Visibility of translated functions whose C symbol is not exported from the library, i.e.,
+/// `static` and non-`extern` inline functions and functions with hidden visibility.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InternalVisibility {
+    /// Make them `pub(crate)`
+    Crate,
+    /// Keep them private to the module of their translation unit. Definitions moved into header
+    /// modules and functions with hidden visibility, which other translation units may call,
+    /// are still `pub(crate)`.
+    Private,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct ExprContext {
     used: bool,
@@ -263,6 +308,64 @@ pub struct Translation<'c> {
     function_context: RefCell<FunContext>,
     potential_flexible_array_members: RefCell<IndexSet<CDeclId>>,
     macro_expansions: RefCell<IndexMap<CDeclId, Option<MacroExpansion>>>,
+    /// Function-like macros translated to functions
+    fn_macros: RefCell<IndexMap<CDeclId, Option<FnMacro>>>,
+    /// Names of the parameters that macro arguments refer to while translating the body of a
+    /// function-like macro
+    fn_macro_args: RefCell<HashMap<CExprId, String>>,
+    container_of_helpers: RefCell<IndexMap<(FileId, CRecordId, CFieldId), String>>,
+    /// Thunks calling functions through pointers of a mismatched type
+    fn_thunks: RefCell<IndexMap<(FileId, CTypeId, CDeclId), String>>,
+    /// Arrays holding string literals passed to extern functions as a `char *`, by their
+    /// bytes and, with `--string-literals unique-per-use`, the literal
+    literal_statics: RefCell<IndexMap<(FileId, Vec<u8>, Option<CExprId>), String>>,
+    align_assertions: RefCell<IndexSet<(FileId, CTypeId)>>,
+    align_wrappers: RefCell<IndexMap<(FileId, u64), String>>,
+    aligned_locals: RefCell<IndexSet<CDeclId>>,
+    /// Guard structs calling cleanup functions, and the locals stored in them
+    cleanup_guards: RefCell<IndexMap<(FileId, CDeclId), String>>,
+    cleanup_locals: RefCell<IndexSet<CDeclId>>,
+    /// Block header types and the items for each block type, generated per module
+    block_shims: RefCell<IndexMap<FileId, BlockShim>>,
+    block_signatures: RefCell<IndexMap<(FileId, CTypeId), BlockSignature>>,
+    /// Local variables declared `__block`, which blocks capture by reference
+    byref_vars: IndexSet<CDeclId>,
+    /// Function pointer tables translated to arrays of `fn`s
+    fn_tables: IndexSet<CDeclId>,
+    /// Tables of strings translated to slices of `CStr`s
+    string_tables: IndexSet<CDeclId>,
+    /// pthreads variables translated to `std` types, and the locks and unlocks of their mutexes
+    pthreads: PthreadObjects,
+    /// Names of the lock guards of the mutexes locked in the current function
+    pthread_guards: RefCell<IndexMap<CDeclId, String>>,
+    /// `const` global aggregates translated to immutable `static`s
+    const_tables: IndexSet<CDeclId>,
+    /// Records marked `Sync` because they are the element type of a `const` table
+    sync_records: IndexSet<CRecordId>,
+    /// Names of the functions whose result is checked as an error code
+    error_code_fns: IndexSet<String>,
+    /// Struct fields used as reference counts, with their uses
+    refcount_fields: IndexMap<CFieldId, RefcountField>,
+    /// Byte arrays moved into data files next to the output file, by file name
+    embedded_bytes: RefCell<IndexMap<String, Vec<u8>>>,
+    /// Start of the names of those data files, the name of the output file without its
+    /// extension
+    data_file_stem: String,
+    /// Statements moved into C shims with `--shim-untranslatable-stmts`
+    stmt_shims: RefCell<IndexMap<CStmtId, StmtShim>>,
+    /// Enums given values other than their enumerators, which stay integer constants with
+    /// `--translate-enums`
+    int_valued_enums: IndexSet<CEnumId>,
+    /// Changes to the foreign declarations that keep them FFI-safe
+    ffi_safety: FfiSafety,
+    /// Directories searched for system headers by the compile command
+    system_include_dirs: Vec<PathBuf>,
+    /// Types from system headers that are declared in place of their definitions, with
+    /// `--skip-system-headers`
+    system_types: IndexMap<CDeclId, SystemType>,
+    /// Module of the output crate this file is translated into, if the crate's files are
+    /// translated together
+    crate_unit: Option<CrateUnit<'c>>,
 
     // Comment support
     pub comment_context: CommentContext, // Incoming comments
@@ -409,6 +512,29 @@ fn mk_linkage(in_extern_block: bool, new_name: &str, old_name: &str) -> Builder
     }
 }
 
+/// Whether the C declaration has hidden (or internal) symbol visibility
+fn is_hidden(attrs: &IndexSet<c_ast::Attribute>) -> bool {
+    attrs.iter().any(|attr| match attr {
+        c_ast::Attribute::Visibility(vis) => vis == "hidden" || vis == "internal",
+        _ => false,
+    })
+}
+
+/// Build `#[deprecated]` for a C declaration marked deprecated, or
+/// `#[deprecated(note = "msg")]` if the C attribute has a message.
+fn mk_deprecated(note: Option<&str>) -> ast::Attribute {
+    let deprecated = Ident::from_str("deprecated");
+    let item = match note {
+        None => attr::mk_word_item(deprecated),
+        Some(note) => {
+            let note =
+                attr::mk_name_value_item_str(Ident::from_str("note"), note.into_symbol(), DUMMY_SP);
+            attr::mk_list_item(deprecated, vec![NestedMetaItem::MetaItem(note)])
+        }
+    };
+    attr::mk_attr_outer(item)
+}
+
 pub fn signed_int_expr(value: i64) -> P<Expr> {
     if value < 0 {
         mk().unary_expr(
@@ -498,12 +624,28 @@ pub fn translate_failure(tcfg: &TranspilerConfig, msg: &str) {
     }
 }
 
-pub fn translate(
+pub fn translate<'c>(
     ast_context: TypedAstContext,
-    tcfg: &TranspilerConfig,
+    tcfg: &'c TranspilerConfig,
     main_file: PathBuf,
-) -> (String, PragmaVec, CrateSet) {
-    let mut t = Translation::new(ast_context, tcfg, main_file.as_path());
+    system_include_dirs: Vec<PathBuf>,
+    crate_unit: Option<CrateUnit<'c>>,
+) -> (
+    String,
+    PragmaVec,
+    CrateSet,
+    Option<String>,
+    IndexMap<String, Vec<u8>>,
+    Option<String>,
+    IndexMap<String, String>,
+) {
+    let mut t = Translation::new(
+        ast_context,
+        tcfg,
+        main_file.as_path(),
+        system_include_dirs,
+        crate_unit,
+    );
     let ctx = ExprContext {
         used: true,
         is_static: false,
@@ -551,6 +693,46 @@ pub fn translate(
         prefix_names(&mut t, prefix);
     }
 
+    if t.tcfg.translate_fn_tables {
+        t.fn_tables = t.find_fn_tables();
+    }
+
+    if t.tcfg.translate_blocks {
+        t.byref_vars = t.find_byref_vars();
+    }
+
+    // `CStr` is only available in `std`
+    if t.tcfg.translate_string_tables && !t.tcfg.emit_no_std {
+        t.string_tables = t.find_string_tables();
+    }
+
+    // `std::sync` and `std::thread` are only available in `std`
+    if t.tcfg.translate_pthreads.is_some() && !t.tcfg.emit_no_std {
+        t.pthreads = t.find_pthread_objects();
+    }
+
+    // With `--header-only`, no definitions of the tables are emitted
+    if !t.tcfg.header_only {
+        let (const_tables, sync_records) = t.find_const_tables();
+        t.const_tables = const_tables;
+        t.sync_records = sync_records;
+    }
+
+    if t.tcfg.must_use_error_codes {
+        t.error_code_fns = t.find_error_code_fns();
+    }
+
+    if t.tcfg.detect_refcounts {
+        t.refcount_fields = t.find_refcount_fields();
+        if !t.refcount_fields.is_empty() {
+            t.use_feature("custom_attribute");
+        }
+    }
+
+    if t.tcfg.translate_valist && t.only_forwards_va_lists() {
+        t.type_converter.borrow_mut().translate_valist = false;
+    }
+
     // `with_globals` sets up a thread-local variable required by the syntax crate.
     with_globals(Edition::Edition2018, || {
         // Identify typedefs that name unnamed types and collapse the two declarations
@@ -638,6 +820,7 @@ pub fn translate(
                     Name::VarName(ident)
                 }
                 CDeclKind::MacroObject { ref name, .. } => Name::VarName(name),
+                CDeclKind::MacroFunction { ref name, .. } => Name::VarName(name),
                 _ => Name::NoName,
             };
             match decl_name {
@@ -658,6 +841,23 @@ pub fn translate(
             }
         }
 
+        // System types are recognized by the names of their typedefs, which needs the
+        // typedefs of unnamed types to be collapsed
+        if t.tcfg.skip_system_headers {
+            t.system_types = t.find_system_types();
+        }
+
+        if t.tcfg.translate_enums {
+            t.int_valued_enums = t.find_int_valued_enums();
+        }
+
+        // Check the foreign declarations once everything is named, which deciding whether an
+        // enum becomes a Rust enum depends on
+        t.ffi_safety.int_enums = t.find_ffi_int_enums();
+        let (ffi_fns, ffi_allowed) = t.find_ffi_signatures();
+        t.ffi_safety.fns = ffi_fns;
+        t.ffi_safety.allowed = ffi_allowed;
+
         {
             let convert_type = |decl_id: CDeclId, decl: &CDecl| {
                 let decl_file_id = t.ast_context.file_id(decl);
@@ -683,6 +883,9 @@ pub fn translate(
                         translate_failure(&t.tcfg, &msg);
                     }
                 }
+                if let Some(sync_impl) = t.convert_sync_impl(decl_id) {
+                    t.insert_item(sync_impl, decl);
+                }
                 t.cur_file.borrow_mut().take();
 
                 if t.tcfg.reorganize_definitions
@@ -719,6 +922,7 @@ pub fn translate(
                 CDeclKind::Function { is_implicit, .. } => !is_implicit,
                 CDeclKind::Variable { .. } => true,
                 CDeclKind::MacroObject { .. } => tcfg.translate_const_macros,
+                CDeclKind::MacroFunction { .. } => tcfg.translate_fn_macros && !tcfg.header_only,
                 _ => false,
             };
             if needs_export {
@@ -731,7 +935,10 @@ pub fn translate(
                 {
                     *t.cur_file.borrow_mut() = decl_file_id;
                 }
-                match t.convert_decl(ctx, *top_id) {
+                let converted = t
+                    .convert_decl(ctx, *top_id)
+                    .map(|converted| t.share_inline_fn(*top_id, converted));
+                match converted {
                     Ok(ConvertedDecl::Item(item)) => {
                         t.insert_item(item, decl);
                     }
@@ -773,7 +980,7 @@ pub fn translate(
         }
 
         // Add the main entry point
-        if let Some(main_id) = t.ast_context.c_main {
+        if let Some(main_id) = t.ast_context.c_main.filter(|_| !tcfg.header_only) {
             match t.convert_main(main_id) {
                 Ok(item) => t.items.borrow_mut()[&t.main_file].add_item(item),
                 Err(e) => {
@@ -783,6 +990,11 @@ pub fn translate(
             }
         }
 
+        // Define the `_BitInt` newtypes used with `--strict-bit-ints`
+        for item in t.bit_int_newtype_items() {
+            t.items.borrow_mut()[&t.main_file].add_item(item);
+        }
+
         // Initialize global statics when necessary
         if !t.sectioned_static_initializers.borrow().is_empty() {
             let (initializer_fn, initializer_static) = t.generate_global_static_init();
@@ -826,6 +1038,38 @@ pub fn translate(
         // Main file item store
         let (items, foreign_items, uses) = t.items.borrow_mut()[&t.main_file].drain();
 
+        let mut all_items = mod_items;
+
+        // This could have been merged in with items below; however, it's more idiomatic to have
+        // imports near the top of the file than randomly scattered about. Also, there is probably
+        // no reason to have comments associated with imports so it doesn't need to go through
+        // the comment store process below
+        all_items.extend(uses.into_items());
+
+        // New uses from submodules
+        let (_, _, new_uses) = new_uses.drain();
+        all_items.extend(new_uses.into_items());
+
+        if !foreign_items.is_empty() {
+            all_items.push(mk().abi("C").foreign_items(foreign_items));
+        }
+
+        // Add the items accumulated
+        all_items.extend(items);
+
+        let all_items = if t.tcfg.import_std_paths() {
+            output_style::import_std_paths(all_items)
+        } else {
+            all_items
+        };
+        let all_items = apply_ast_transforms(t.tcfg, all_items);
+        // The extern crates the parts use are declared by the file
+        let used_roots = path_roots(&all_items);
+        let (all_items, parts) = match t.tcfg.split_output_size {
+            Some(max_size) => split_output::split_items(all_items, max_size, &t.data_file_stem),
+            None => (all_items, vec![]),
+        };
+
         // Re-order comments
         // FIXME: We shouldn't have to replace with an empty comment store here,
         // that's bad design
@@ -833,18 +1077,24 @@ pub fn translate(
 
         // Add a comment mapping span to each node that should have a
         // comment printed before it. The pretty printer picks up these
-        // spans and uses them to decide when to emit comments.
-        mod_items = mod_items
+        // spans and uses them to decide when to emit comments. The items of
+        // each part come after those of the file, so the comments of each
+        // file are a range of positions.
+        let all_items: Vec<P<Item>> = all_items
             .into_iter()
             .map(|p_i| p_i.map(|i| traverser.traverse_item(i)))
             .collect();
-        let foreign_items: Vec<ForeignItem> = foreign_items
+        let main_end = traverser.position();
+        let parts: Vec<(String, Vec<P<Item>>, BytePos)> = parts
             .into_iter()
-            .map(|fi| traverser.traverse_foreign_item(fi))
-            .collect();
-        let items: Vec<P<Item>> = items
-            .into_iter()
-            .map(|p_i| p_i.map(|i| traverser.traverse_item(i)))
+            .map(|part| {
+                let file_name = part.file_name(&t.data_file_stem);
+                let items = split_output::part_items(part)
+                    .into_iter()
+                    .map(|p_i| p_i.map(|i| traverser.traverse_item(i)))
+                    .collect();
+                (file_name, items, traverser.position())
+            })
             .collect();
 
         let mut reordered_comment_store = traverser.into_comment_store();
@@ -859,43 +1109,74 @@ pub fn translate(
         let sm = SourceMap::new(FilePathMapping::empty());
         sm.new_source_file(FileName::Custom("<dummy>".to_string()), " ".to_string());
 
-        let comments = Comments::new(&sm, reordered_comment_store.into_comments());
+        // Each part prints the comments of its range, the file prints the others
+        let mut all_comments = reordered_comment_store.into_comments();
+        let mut part_start = main_end;
+        let mut part_sources = IndexMap::new();
+        for (file_name, items, part_end) in parts {
+            let (part_comments, rest): (Vec<_>, Vec<_>) = all_comments
+                .into_iter()
+                .partition(|c| c.pos > part_start && c.pos <= part_end);
+            all_comments = rest;
+            part_start = part_end;
+
+            let comments = Comments::new(&sm, part_comments);
+            let source = pprust::to_string_with_comments(comments, |s| {
+                for item in items {
+                    s.print_item(&*item);
+                }
+
+                s.print_remaining_comments();
+            });
+            part_sources.insert(file_name, source);
+        }
+
+        let comments = Comments::new(&sm, all_comments);
 
         // pass all converted items to the Rust pretty printer
         let translation = pprust::to_string_with_comments(comments, |s| {
-            print_header(s, &t, t.tcfg.is_binary(main_file.as_path()));
+            print_header(s, &t, t.tcfg.is_binary(main_file.as_path()), &used_roots);
 
-            for mod_item in mod_items {
-                s.print_item(&*mod_item);
+            for item in all_items {
+                s.print_item(&*item);
             }
 
-            // This could have been merged in with items below; however, it's more idiomatic to have
-            // imports near the top of the file than randomly scattered about. Also, there is probably
-            // no reason to have comments associated with imports so it doesn't need to go through
-            // the above comment store process
-            for use_item in uses.into_items() {
-                s.print_item(&use_item);
-            }
+            s.print_remaining_comments();
+        });
 
-            // Print new uses from submodules
-            let (_, _, new_uses) = new_uses.drain();
-            for use_item in new_uses.into_items() {
-                s.print_item(&use_item);
-            }
+        if let Some(summary) = t.ffi_safety_summary() {
+            warn!("{}: {}", main_file.display(), summary);
+        }
 
-            if !foreign_items.is_empty() {
-                s.print_item(&mk().abi("C").foreign_items(foreign_items))
-            }
+        let refcount_report = if t.tcfg.detect_refcounts {
+            Some(t.refcount_report())
+        } else {
+            None
+        };
+        let embedded_bytes = t.embedded_bytes.replace(IndexMap::new());
+        let shims = t.stmt_shims_source();
+        (translation, pragmas, crates, refcount_report, embedded_bytes, shims, part_sources)
+    })
+}
 
-            // Add the items accumulated
-            for x in items {
-                s.print_item(&*x);
-            }
+/// Run the `TranspilerConfig::ast_transforms` callbacks over the top-level items of an
+/// output file.
+fn apply_ast_transforms(tcfg: &TranspilerConfig, items: Vec<P<Item>>) -> Vec<P<Item>> {
+    if tcfg.ast_transforms.is_empty() {
+        return items;
+    }
 
-            s.print_remaining_comments();
-        });
-        (translation, pragmas, crates)
-    })
+    let mut krate = Crate {
+        module: Mod {
+            inner: DUMMY_SP,
+            items,
+            inline: true,
+        },
+        attrs: vec![],
+        span: DUMMY_SP,
+    };
+    tcfg.ast_transforms.apply(&mut krate);
+    krate.module.items
 }
 
 fn make_submodule(
@@ -946,12 +1227,31 @@ fn make_submodule(
         .mod_item(mod_name, mk().mod_(items))
 }
 
-/// Pretty-print the leading pragmas and extern crate declarations
-fn print_header(s: &mut pprust::State, t: &Translation, is_binary: bool) {
+/// Pretty-print the leading pragmas and extern crate declarations. `used_roots` are the first
+/// segments of the paths in the items of the file, see `path_roots`.
+///
+/// Everything is printed in a canonical order so that the header doesn't depend on the order
+/// in which the translator encountered the constructs that need it.
+fn print_header(
+    s: &mut pprust::State,
+    t: &Translation,
+    is_binary: bool,
+    used_roots: &HashSet<String>,
+) {
+    let mut extern_crates = t
+        .extern_crates
+        .borrow()
+        .iter()
+        .map(|c| ExternCrateDetails::from(*c))
+        .collect::<Vec<_>>();
+    extern_crates.sort_by(|a, b| a.ident.cmp(&b.ident));
+
     if t.tcfg.emit_modules && !is_binary {
-        for c in t.extern_crates.borrow().iter() {
+        // Feature gates and `#[macro_use]` crates go in the crate root, so a module only
+        // imports the crates its paths refer to
+        for c in extern_crates.iter().filter(|c| used_roots.contains(&c.ident)) {
             s.print_item(&mk().use_simple_item(
-                vec![String::new(), ExternCrateDetails::from(*c).ident],
+                vec![String::new(), c.ident.clone()],
                 None as Option<Ident>,
             ));
         }
@@ -996,8 +1296,7 @@ fn print_header(s: &mut pprust::State, t: &Translation, is_binary: bool) {
 
         if is_binary {
             // Add `extern crate X;` to the top of the file
-            for extern_crate in t.extern_crates.borrow().iter() {
-                let extern_crate = ExternCrateDetails::from(*extern_crate);
+            for extern_crate in &extern_crates {
                 if extern_crate.macro_use {
                     s.print_item(
                         &mk()
@@ -1049,6 +1348,36 @@ fn add_src_loc_attr(attrs: &mut Vec<ast::Attribute>, src_loc: &Option<SrcLoc>) {
     }
 }
 
+/// Mark the definitions of static functions and variables with `#[internal_linkage]`, so that
+/// `reorganize_definitions` keeps the copies that different translation units make of a
+/// static definition in a header apart, even when they are identical. Static inline functions
+/// are shared between units like other inline functions, and aren't marked.
+fn add_internal_linkage_attr(item: &mut Item, decl: &CDecl) {
+    let is_internal = match decl.kind {
+        CDeclKind::Function {
+            is_global: false,
+            is_inline: false,
+            body: Some(_),
+            ..
+        } => true,
+        CDeclKind::Variable {
+            has_static_duration: true,
+            is_externally_visible: false,
+            is_defn: true,
+            ..
+        } => true,
+        _ => false,
+    };
+    match item.kind {
+        ItemKind::Fn(..) | ItemKind::Static(..) if is_internal => {
+            item.attrs.push(attr::mk_attr_outer(attr::mk_word_item(Ident::from_str(
+                "internal_linkage",
+            ))));
+        }
+        _ => {}
+    }
+}
+
 /// This represents all of the ways a C expression can be used in a C program. Making this
 /// distinction is important for:
 ///
@@ -1090,14 +1419,21 @@ impl<'c> Translation<'c> {
         mut ast_context: TypedAstContext,
         tcfg: &'c TranspilerConfig,
         main_file: &path::Path,
+        system_include_dirs: Vec<PathBuf>,
+        crate_unit: Option<CrateUnit<'c>>,
     ) -> Self {
         let comment_context = CommentContext::new(&mut ast_context);
-        let mut type_converter = TypeConverter::new(tcfg.emit_no_std);
+        let mut type_converter = TypeConverter::new(tcfg.emit_no_std, tcfg.rename_collisions);
 
         if tcfg.translate_valist {
             type_converter.translate_valist = true
         }
+        type_converter.translate_blocks = tcfg.translate_blocks;
+        type_converter.strict_bit_ints = tcfg.strict_bit_ints;
 
+        let data_file_stem = main_file
+            .file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().replace('-', "_"));
         let main_file = ast_context.find_file_id(main_file).unwrap_or(0);
         let items = indexmap!{main_file => ItemStore::new()};
 
@@ -1106,7 +1442,7 @@ impl<'c> Translation<'c> {
             type_converter: RefCell::new(type_converter),
             ast_context,
             tcfg,
-            renamer: RefCell::new(Renamer::new(&[
+            renamer: RefCell::new(reserved_renamer(&[
                 // Keywords currently in use
                 "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false",
                 "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
@@ -1118,11 +1454,40 @@ impl<'c> Translation<'c> {
                 "async", "try", "yield", // Prevent use for other reasons
                 "main",  // prelude names
                 "drop", "Some", "None", "Ok", "Err",
-            ])),
+            ], tcfg.rename_collisions)),
             zero_inits: RefCell::new(IndexMap::new()),
             function_context: RefCell::new(FunContext::new()),
             potential_flexible_array_members: RefCell::new(IndexSet::new()),
             macro_expansions: RefCell::new(IndexMap::new()),
+            fn_macros: RefCell::new(IndexMap::new()),
+            fn_macro_args: RefCell::new(HashMap::new()),
+            container_of_helpers: RefCell::new(IndexMap::new()),
+            fn_thunks: RefCell::new(IndexMap::new()),
+            literal_statics: RefCell::new(IndexMap::new()),
+            align_assertions: RefCell::new(IndexSet::new()),
+            align_wrappers: RefCell::new(IndexMap::new()),
+            aligned_locals: RefCell::new(IndexSet::new()),
+            cleanup_guards: RefCell::new(IndexMap::new()),
+            cleanup_locals: RefCell::new(IndexSet::new()),
+            block_shims: RefCell::new(IndexMap::new()),
+            block_signatures: RefCell::new(IndexMap::new()),
+            byref_vars: IndexSet::new(),
+            fn_tables: IndexSet::new(),
+            string_tables: IndexSet::new(),
+            pthreads: PthreadObjects::default(),
+            pthread_guards: RefCell::new(IndexMap::new()),
+            const_tables: IndexSet::new(),
+            sync_records: IndexSet::new(),
+            error_code_fns: IndexSet::new(),
+            refcount_fields: IndexMap::new(),
+            embedded_bytes: RefCell::new(IndexMap::new()),
+            data_file_stem,
+            stmt_shims: RefCell::new(IndexMap::new()),
+            int_valued_enums: IndexSet::new(),
+            ffi_safety: FfiSafety::default(),
+            system_include_dirs,
+            system_types: IndexMap::new(),
+            crate_unit,
             comment_context,
             comment_store: RefCell::new(CommentStore::new()),
             spans: HashMap::new(),
@@ -1173,6 +1538,10 @@ impl<'c> Translation<'c> {
             pragmas.push(("cross_check", vec!["yes"]));
         }
 
+        // The type converter keeps its features in a hash set, so sort to keep the output
+        // stable across runs
+        features.sort();
+        features.dedup();
         if !features.is_empty() {
             pragmas.push(("feature", features));
         }
@@ -1236,6 +1605,9 @@ impl<'c> Translation<'c> {
             };
 
             match self.ast_context[expr_id].kind {
+                // Immutable statics can be referred to safely
+                CExprKind::DeclRef(_, decl_id, LRValue::LValue)
+                    if self.is_const_table(decl_id) => {}
                 CExprKind::DeclRef(_, _, LRValue::LValue) => return true,
                 | CExprKind::ImplicitCast(_, _, cast_kind, _, _)
                 | CExprKind::ExplicitCast(_, _, cast_kind, _, _) => match cast_kind {
@@ -1436,12 +1808,61 @@ impl<'c> Translation<'c> {
         &self,
         ctx: ExprContext,
         decl_id: CDeclId,
+    ) -> Result<ConvertedDecl, TranslationError> {
+        let converted = self.convert_decl_kind(ctx, decl_id)?;
+        Ok(self.add_deprecation(decl_id, converted))
+    }
+
+    /// Mark the items a deprecated C declaration was converted to with `#[deprecated]`. When a
+    /// declaration is converted to several items, the first one is the declaration itself.
+    fn add_deprecation(&self, decl_id: CDeclId, converted: ConvertedDecl) -> ConvertedDecl {
+        let note = self.ast_context[decl_id]
+            .kind
+            .get_attrs()
+            .and_then(|attrs| {
+                attrs.iter().find_map(|attr| match attr {
+                    c_ast::Attribute::Deprecated(note) => Some(note.as_ref().map(String::as_str)),
+                    _ => None,
+                })
+            });
+        let attr = match note {
+            Some(note) => mk_deprecated(note),
+            None => return converted,
+        };
+
+        match converted {
+            ConvertedDecl::Item(mut item) => {
+                item.attrs.push(attr);
+                ConvertedDecl::Item(item)
+            }
+            ConvertedDecl::ForeignItem(mut item) => {
+                item.attrs.push(attr);
+                ConvertedDecl::ForeignItem(item)
+            }
+            ConvertedDecl::Items(mut items) => {
+                if let Some(item) = items.first_mut() {
+                    item.attrs.push(attr);
+                }
+                ConvertedDecl::Items(items)
+            }
+            ConvertedDecl::NoItem => ConvertedDecl::NoItem,
+        }
+    }
+
+    fn convert_decl_kind(
+        &self,
+        ctx: ExprContext,
+        decl_id: CDeclId,
     ) -> Result<ConvertedDecl, TranslationError> {
         let decl = self
             .ast_context
             .get_decl(&decl_id)
             .ok_or_else(|| format_err!("Missing decl {:?}", decl_id))?;
 
+        if let Some(&system_type) = self.system_types.get(&decl_id) {
+            return self.convert_system_type(decl_id, system_type);
+        }
+
         let mut s = self.get_span(SomeId::Decl(decl_id)).unwrap_or(DUMMY_SP);
 
         match decl.kind {
@@ -1516,7 +1937,6 @@ impl<'c> Translation<'c> {
                     self.use_crate(ExternCrate::C2RustBitfields);
                 }
 
-                let mut reprs = vec![simple_metaitem("C")];
                 let max_field_alignment = if is_packed {
                     // `__attribute__((packed))` forces a max alignment of 1,
                     // overriding `#pragma pack`; this is also what clang does
@@ -1524,6 +1944,21 @@ impl<'c> Translation<'c> {
                 } else {
                     max_field_alignment
                 };
+
+                // A struct with a single field, which needs no padding, packing or alignment,
+                // is laid out and passed across FFI exactly like that field, unless it is
+                // zero-sized, which `repr(transparent)` doesn't make ABI-identical
+                let is_transparent = self.tcfg.emit_repr_transparent
+                    && !has_bitfields
+                    && field_entries.len() == 1
+                    && platform_byte_size != 0
+                    && max_field_alignment.is_none()
+                    && manual_alignment.is_none();
+                let mut reprs = if is_transparent {
+                    vec![simple_metaitem("transparent")]
+                } else {
+                    vec![simple_metaitem("C")]
+                };
                 match max_field_alignment {
                     Some(1) => reprs.push(simple_metaitem("packed")),
                     Some(mf) if mf > 1 => reprs.push(int_arg_metaitem("packed", mf as u128)),
@@ -1653,6 +2088,10 @@ impl<'c> Translation<'c> {
                 "Field declarations should be handled inside structs/unions",
             )),
 
+            CDeclKind::Enum { .. } if self.is_rust_enum(decl_id) => {
+                self.convert_rust_enum(decl_id, s)
+            }
+
             CDeclKind::Enum {
                 integral_type: Some(integral_type),
                 ..
@@ -1668,6 +2107,12 @@ impl<'c> Translation<'c> {
                 ))
             }
 
+            CDeclKind::EnumConstant { .. }
+                if self.is_rust_enum(self.ast_context.parents[&decl_id]) =>
+            {
+                self.convert_rust_enum_constant(decl_id, s)
+            }
+
             CDeclKind::EnumConstant { value, .. } => {
                 let name = self
                     .renamer
@@ -1718,6 +2163,11 @@ impl<'c> Translation<'c> {
                     return Ok(ConvertedDecl::NoItem);
                 }
 
+                // Calls to these are translated without glibc's fortify symbols
+                if body.is_none() && builtins::is_fortify_function(name) {
+                    return Ok(ConvertedDecl::NoItem);
+                }
+
                 let (ret, is_var): (Option<CQualTypeId>, bool) =
                     match self.ast_context.resolve_type(typ).kind {
                         CTypeKind::Function(ret, _, is_var, is_noreturn, _) => {
@@ -1747,19 +2197,53 @@ impl<'c> Translation<'c> {
                 }
 
                 let is_main = self.ast_context.c_main == Some(decl_id);
+                let is_extern = is_extern || self.needs_external_inline_def(decl_id);
+
+                // With `--header-only`, functions the library exports are declared and
+                // everything else is left out
+                let body = if self.tcfg.header_only {
+                    if !is_global || is_main || (is_inline && !is_extern) {
+                        return Ok(ConvertedDecl::NoItem);
+                    }
+                    None
+                } else {
+                    body
+                };
 
                 let converted_function = self.convert_function(
                     ctx, s, is_global, is_inline, is_main, is_var, is_extern,
                     new_name, name, &args, ret, body, attrs,
                 );
 
-                converted_function.or_else(|e| match self.tcfg.replace_unsupported_decls {
-                    ReplaceMode::Extern if body.is_none() => self.convert_function(
-                        ctx, s, is_global, false, is_main, is_var, is_extern,
-                        new_name, name, &args, ret, None, attrs,
-                    ),
-                    _ => Err(e),
-                })
+                let converted_function =
+                    converted_function.or_else(|e| match self.tcfg.replace_unsupported_decls {
+                        ReplaceMode::Extern if body.is_none() => self.convert_function(
+                            ctx, s, is_global, false, is_main, is_var, is_extern,
+                            new_name, name, &args, ret, None, attrs,
+                        ),
+                        _ => Err(e),
+                    })?;
+
+                match converted_function {
+                    ConvertedDecl::Item(item) if self.error_code_fns.contains(name) => {
+                        let must_use =
+                            mk().attribute(AttrStyle::Outer, vec!["must_use"], TokenStream::empty());
+                        Ok(ConvertedDecl::Item(item.map(|mut item| {
+                            item.attrs.push(must_use);
+                            item
+                        })))
+                    }
+                    ConvertedDecl::Items(mut items) if self.error_code_fns.contains(name) => {
+                        let must_use =
+                            mk().attribute(AttrStyle::Outer, vec!["must_use"], TokenStream::empty());
+                        items[0].attrs.push(must_use);
+                        Ok(ConvertedDecl::Items(items))
+                    }
+                    ConvertedDecl::ForeignItem(item) => {
+                        Ok(ConvertedDecl::ForeignItem(self.make_ffi_safe(decl_id, item)))
+                    }
+                    converted => Ok(converted),
+                }
             }
 
             CDeclKind::Typedef { ref typ, .. } => {
@@ -1789,24 +2273,31 @@ impl<'c> Translation<'c> {
                 ))
             }
 
-            // Externally-visible variable without initializer (definition elsewhere)
+            // With `--header-only`, variables that aren't externally visible are left out
+            CDeclKind::Variable {
+                is_externally_visible: false,
+                ..
+            } if self.tcfg.header_only => Ok(ConvertedDecl::NoItem),
+
+            // Externally-visible variable without initializer (definition elsewhere), or any
+            // externally-visible variable with `--header-only`
             CDeclKind::Variable {
                 is_externally_visible: true,
                 has_static_duration,
                 has_thread_duration,
-                is_defn: false,
+                is_defn,
                 ref ident,
                 initializer,
                 typ,
                 ref attrs,
                 ..
-            } => {
+            } if !is_defn || self.tcfg.header_only => {
                 assert!(
                     has_static_duration || has_thread_duration,
                     "An extern variable must be static or thread-local"
                 );
                 assert!(
-                    initializer.is_none(),
+                    is_defn || initializer.is_none(),
                     "An extern variable that isn't a definition can't have an initializer"
                 );
 
@@ -1820,8 +2311,9 @@ impl<'c> Translation<'c> {
                     .get(&decl_id)
                     .expect("Variables should already be renamed");
                 let (ty, mutbl, _) = self.convert_variable(ctx.static_(), None, typ)?;
-                // When putting extern statics into submodules, they need to be public to be accessible
-                let visibility = if self.tcfg.reorganize_definitions {
+                // When putting extern statics into submodules, they need to be public to be accessible,
+                // and bindings emitted with `--header-only` are there to be used by other modules
+                let visibility = if self.tcfg.reorganize_definitions || self.tcfg.header_only {
                     "pub"
                 } else {
                     ""
@@ -1843,9 +2335,8 @@ impl<'c> Translation<'c> {
                     };
                 }
 
-                Ok(ConvertedDecl::ForeignItem(
-                    extern_item.static_foreign_item(&new_name, ty),
-                ))
+                let extern_item = extern_item.static_foreign_item(&new_name, ty);
+                Ok(ConvertedDecl::ForeignItem(self.make_ffi_safe(decl_id, extern_item)))
             }
 
             // Static-storage or thread-local variable with initializer (definition here)
@@ -1871,7 +2362,17 @@ impl<'c> Translation<'c> {
 
                 // Collect problematic static initializers and offload them to sections for the linker
                 // to initialize for us
-                let (ty, init) = if self.static_initializer_is_uncompilable(initializer, typ) {
+                let (ty, init) = if let Some(converted) =
+                    self.convert_pthread_static(decl_id, new_name)
+                {
+                    converted
+                } else if self.fn_tables.contains(&decl_id) {
+                    let initializer = initializer
+                        .ok_or_else(|| format_err!("Function table without initializer"))?;
+                    self.convert_fn_table(ctx.static_(), initializer, typ)?
+                } else if self.string_tables.contains(&decl_id) {
+                    self.convert_string_table(decl_id)?
+                } else if self.static_initializer_is_uncompilable(initializer, typ) {
                     // Note: We don't pass has_static_duration through here. Extracted initializers
                     // are run outside of the static initializer.
                     let (ty, _, init) =
@@ -1915,6 +2416,11 @@ impl<'c> Translation<'c> {
                     (ty, init)
                 };
 
+                let symbol_vis = if is_externally_visible {
+                    self.mk_symbol_visibility(attrs, ident)
+                } else {
+                    None
+                };
                 let static_def = if is_externally_visible {
                     mk_linkage(false, new_name, ident).pub_().abi("C")
                 } else if self.cur_file.borrow().is_some() {
@@ -1924,8 +2430,16 @@ impl<'c> Translation<'c> {
                 };
 
                 // Force mutability due to the potential for raw pointers occuring in the type
-                // and because we may be assigning to these variables in the external initializer
-                let mut static_def = static_def.span(s).mutbl();
+                // and because we may be assigning to these variables in the external initializer,
+                // unless the variable is a `const` table, a table of strings or a `Once`, which
+                // are never written
+                let mut static_def = static_def.span(s);
+                if !self.is_const_table(decl_id)
+                    && !self.string_tables.contains(&decl_id)
+                    && !self.pthreads.onces.contains(&decl_id)
+                {
+                    static_def = static_def.mutbl();
+                }
                 if has_thread_duration {
                     static_def = static_def.single_attr("thread_local");
                 }
@@ -1941,9 +2455,11 @@ impl<'c> Translation<'c> {
                     }
                 }
 
-                Ok(ConvertedDecl::Item(
-                    static_def.static_item(new_name, ty, init),
-                ))
+                let static_item = static_def.static_item(new_name, ty, init);
+                match symbol_vis {
+                    Some(symbol_vis) => Ok(ConvertedDecl::Items(vec![static_item, symbol_vis])),
+                    None => Ok(ConvertedDecl::Item(static_item)),
+                }
             }
 
             CDeclKind::Variable { .. } => Err(TranslationError::generic(
@@ -1988,6 +2504,8 @@ impl<'c> Translation<'c> {
                 }
             }
 
+            CDeclKind::MacroFunction { .. } => self.convert_fn_macro(ctx, decl_id, s),
+
             // Do not translate non-canonical decls. They will be translated at
             // their canonical declaration.
             CDeclKind::NonCanonicalDecl { .. } => Ok(ConvertedDecl::NoItem),
@@ -2041,6 +2559,58 @@ impl<'c> Translation<'c> {
         // common type to minimize casts.
     }
 
+    /// `global_asm!` directive giving the symbol `name` of a definition the ELF visibility of
+    /// its C declaration, if that isn't the default. Rust exports every `#[no_mangle]` symbol
+    /// from a dylib, so hidden, internal and protected definitions keep their C name (other
+    /// objects of the library still link against it) and get their visibility from the
+    /// assembler. The directive also declares the symbol global, as the assembler drops an
+    /// unreferenced undefined symbol when the definition is in another codegen unit.
+    fn mk_symbol_visibility(
+        &self,
+        attrs: &IndexSet<c_ast::Attribute>,
+        name: &str,
+    ) -> Option<P<Item>> {
+        let vis = attrs.iter().find_map(|attr| match attr {
+            c_ast::Attribute::Visibility(vis) if vis != "default" => Some(vis),
+            _ => None,
+        })?;
+
+        self.use_feature("global_asm");
+        let directive = mk().lit_expr(mk().str_lit(format!(".globl {}\n.{} {}", name, vis, name)));
+        let tokens = vec![TokenTree::token(
+            token::Interpolated(Rc::new(Nonterminal::NtExpr(directive))),
+            DUMMY_SP,
+        )];
+        let mac = mk().mac(vec!["global_asm"], tokens, MacDelimiter::Parenthesis);
+        Some(mk().call_attr("cfg", vec!["target_os = \"linux\""]).mac_item(mac))
+    }
+
+    /// Visibility of a function definition with external linkage. Only functions whose symbol
+    /// is hidden can be restricted to the crate, and only with `--internal-visibility`.
+    fn external_fn_vis(&self, attrs: &IndexSet<c_ast::Attribute>) -> &'static str {
+        if self.tcfg.internal_visibility.is_some() && is_hidden(attrs) {
+            "pub(crate)"
+        } else {
+            "pub"
+        }
+    }
+
+    /// Visibility of a `static` or non-`extern` inline function definition. With
+    /// `--reorganize-definitions`, definitions from headers are moved into modules of their own
+    /// and have to be reachable from the modules including the header.
+    fn internal_fn_vis(&self) -> &'static str {
+        let cur_file = *self.cur_file.borrow();
+        match self.tcfg.internal_visibility {
+            None if cur_file.is_some() => "pub",
+            None => "priv",
+            Some(InternalVisibility::Crate) => "pub(crate)",
+            Some(InternalVisibility::Private) => match cur_file {
+                Some(file_id) if file_id != self.main_file => "pub(crate)",
+                _ => "priv",
+            },
+        }
+    }
+
     fn convert_function(
         &self,
         ctx: ExprContext,
@@ -2145,6 +2715,7 @@ impl<'c> Translation<'c> {
                 for &(_, _, typ) in arguments {
                     body_stmts.append(&mut self.compute_variable_array_sizes(ctx, typ.ctype)?);
                 }
+                body_stmts.append(&mut self.pthread_guard_locals(body));
 
                 let body_ids = match self.ast_context.index(body).kind {
                     CStmtKind::Compound(ref stmts) => stmts,
@@ -2155,24 +2726,30 @@ impl<'c> Translation<'c> {
                 if let Some(span) = self.get_span(SomeId::Stmt(body)) {
                     block.span = span;
                 }
+                if self.tcfg.trailing_return_exprs() {
+                    output_style::trailing_return_expr(&mut block);
+                }
 
                 // Only add linkage attributes if the function is `extern`
+                let mut symbol_vis = None;
                 let mut mk_ = if is_main {
                     // Cross-check this function as if it was called `main`
                     // FIXME: pass in a vector of NestedMetaItem elements,
                     // but strings have to do for now
                     self.mk_cross_check(mk(), vec!["entry(djb2=\"main\")", "exit(djb2=\"main\")"])
                 } else if is_global && !is_inline {
-                    mk_linkage(false, new_name, name).abi("C").pub_()
+                    let vis = self.external_fn_vis(attrs);
+                    symbol_vis = self.mk_symbol_visibility(attrs, name);
+                    mk_linkage(false, new_name, name).abi("C").vis(vis)
                 } else if is_inline && is_extern && !attrs.contains(&c_ast::Attribute::GnuInline) {
                     // c99 extern inline functions should be pub, but not gnu_inline attributed
                     // extern inlines, which become subject to their gnu89 visibility (private)
 
-                    mk_linkage(false, new_name, name).abi("C").pub_()
-                } else if self.cur_file.borrow().is_some() {
-                    mk().abi("C").pub_()
+                    let vis = self.external_fn_vis(attrs);
+                    symbol_vis = self.mk_symbol_visibility(attrs, name);
+                    mk_linkage(false, new_name, name).abi("C").vis(vis)
                 } else {
-                    mk().abi("C")
+                    mk().abi("C").vis(self.internal_fn_vis())
                 };
 
                 for attr in attrs {
@@ -2180,6 +2757,7 @@ impl<'c> Translation<'c> {
                         c_ast::Attribute::AlwaysInline => mk_.single_attr("inline(always)"),
                         c_ast::Attribute::Cold => mk_.single_attr("cold"),
                         c_ast::Attribute::NoInline => mk_.single_attr("inline(never)"),
+                        c_ast::Attribute::Section(name) => mk_.str_attr("link_section", name),
                         _ => continue,
                     };
                 }
@@ -2210,14 +2788,17 @@ impl<'c> Translation<'c> {
                     // specifies internal linkage in all other cases due to name mangling by rustc.
                 }
 
-                Ok(ConvertedDecl::Item(
-                    mk_.span(span).unsafe_().fn_item(new_name, decl, block),
-                ))
+                let function = mk_.span(span).unsafe_().fn_item(new_name, decl, block);
+                match symbol_vis {
+                    Some(symbol_vis) => Ok(ConvertedDecl::Items(vec![function, symbol_vis])),
+                    None => Ok(ConvertedDecl::Item(function)),
+                }
             } else {
                 // Translating an extern function declaration
 
-                // When putting extern fns into submodules, they need to be public to be accessible
-                let visibility = if self.tcfg.reorganize_definitions {
+                // When putting extern fns into submodules, they need to be public to be accessible,
+                // and bindings emitted with `--header-only` are there to be used by other modules
+                let visibility = if self.tcfg.reorganize_definitions || self.tcfg.header_only {
                     "pub"
                 } else {
                     ""
@@ -2413,7 +2994,7 @@ impl<'c> Translation<'c> {
             match x {
                 SomeId::Expr(e) => match self.ast_context[e].kind {
                     CExprKind::DeclRef(_, d, _) if d == decl_id => return true,
-                    CExprKind::UnaryType(_, _, Some(_), _) => iter.prune(1),
+                    CExprKind::UnaryType(_, _, Some(_), _, _) => iter.prune(1),
                     _ => {}
                 },
                 SomeId::Type(t) => {
@@ -2443,7 +3024,10 @@ impl<'c> Translation<'c> {
                 typ,
                 ..
             } => {
-                if self.static_initializer_is_uncompilable(initializer, typ) {
+                if !self.fn_tables.contains(&decl_id)
+                    && !self.string_tables.contains(&decl_id)
+                    && self.static_initializer_is_uncompilable(initializer, typ)
+                {
                     let ident2 = self
                         .renamer
                         .borrow_mut()
@@ -2488,6 +3072,7 @@ impl<'c> Translation<'c> {
                 ref ident,
                 initializer,
                 typ,
+                ref attrs,
                 ..
             } => {
                 assert!(
@@ -2501,7 +3086,41 @@ impl<'c> Translation<'c> {
                     .insert(decl_id, &ident)
                     .expect(&format!("Failed to insert variable '{}'", ident));
 
-                if self.ast_context.is_va_list(typ.ctype) {
+                if self.byref_vars.contains(&decl_id) {
+                    return self.convert_byref_local(ctx, &rust_name, initializer, typ);
+                }
+
+                // `pthread_t`s translated with `--translate-pthreads` hold a `JoinHandle`
+                if self.pthreads.threads.contains(&decl_id) {
+                    return Ok(self.convert_thread_handle_local(&rust_name));
+                }
+
+                // Over-aligned locals are stored in a wrapper struct carrying the alignment.
+                // References to the variable go through the wrapper's field, so this has
+                // to be known before we translate the initializer.
+                let align_wrapper = attrs
+                    .iter()
+                    .filter_map(|attr| match attr {
+                        c_ast::Attribute::Aligned(align) => Some(*align),
+                        _ => None,
+                    })
+                    .max()
+                    .map(|align| self.align_wrapper(align));
+                if align_wrapper.is_some() {
+                    self.aligned_locals.borrow_mut().insert(decl_id);
+                }
+
+                // Locals with a cleanup function are stored in a guard running it, inside of
+                // the alignment wrapper if there is one
+                let cleanup_guard = match self.ast_context.cleanup_function(decl_id) {
+                    Some(fn_id) => Some(self.cleanup_guard(fn_id, typ)?),
+                    None => None,
+                };
+                if cleanup_guard.is_some() {
+                    self.cleanup_locals.borrow_mut().insert(decl_id);
+                }
+
+                if self.translate_valist() && self.ast_context.is_va_list(typ.ctype) {
                     // translate `va_list` variables to `VaListImpl`s and omit the initializer.
                     let pat_mut = mk().set_mutbl("mut").ident_pat(rust_name.clone());
                     let ty = {
@@ -2538,6 +3157,32 @@ impl<'c> Translation<'c> {
                 } else {
                     zeroed.to_pure_expr()
                 }.expect("Expected decl initializer to not have any statements");
+
+                let (ty, mutbl, init, zeroed) = match cleanup_guard {
+                    // The guard starts out disarmed in case its declaration is skipped
+                    Some(guard) => {
+                        let wrap = |e: P<Expr>, armed: bool| {
+                            let armed = mk().lit_expr(mk().bool_lit(armed));
+                            mk().call_expr(mk().path_expr(vec![guard.as_str()]), vec![e, armed])
+                        };
+                        let guard_ty = mk().ident_ty(guard.as_str());
+                        (guard_ty, Mutability::Mutable, wrap(init, true), wrap(zeroed, false))
+                    }
+                    None => (ty, mutbl, init, zeroed),
+                };
+                let (ty, init, zeroed) = match align_wrapper {
+                    Some(wrapper) => {
+                        let wrapped_ty = mk().path_ty(vec![mk().path_segment_with_args(
+                            wrapper.as_str(),
+                            mk().angle_bracketed_args(vec![ty]),
+                        )]);
+                        let wrap = |e: P<Expr>| {
+                            mk().call_expr(mk().path_expr(vec![wrapper.as_str()]), vec![e])
+                        };
+                        (wrapped_ty, wrap(init), wrap(zeroed))
+                    }
+                    None => (ty, init, zeroed),
+                };
                 let pat_mut = mk().set_mutbl("mut").ident_pat(rust_name.clone());
                 let local_mut = mk().local(pat_mut, Some(ty.clone()), Some(zeroed));
                 if has_self_reference {
@@ -2704,7 +3349,8 @@ impl<'c> Translation<'c> {
             | CTypeKind::ULongLong
             | CTypeKind::LongDouble
             | CTypeKind::Int128
-            | CTypeKind::UInt128 => initializer.is_none(),
+            | CTypeKind::UInt128
+            | CTypeKind::BitInt(..) => initializer.is_none(),
             CTypeKind::Float | CTypeKind::Double => initializer.is_none(),
             CTypeKind::Struct(_) | CTypeKind::Union(_) | CTypeKind::Enum(_) => false,
             CTypeKind::Function(..) => unreachable!("Can't have a function directly as a type"),
@@ -2960,11 +3606,16 @@ impl<'c> Translation<'c> {
 
             let elts = self.compute_size_of_type(ctx, elts)?;
             return elts.and_then(|lhs| {
-                let len = self.convert_expr(ctx.used().not_static(), len)?;
-                Ok(len.map(|len| {
-                    let rhs = cast_int(len, "usize", true);
-                    mk().binary_expr(BinOpKind::Mul, lhs, rhs)
-                }))
+                // Reuse the length computed where the array type was declared; only a VLA
+                // type spelled out in the operand itself evaluates its length here
+                let stored = self.renamer.borrow().get(&CDeclId(len.0));
+                let len = match stored {
+                    Some(name) => WithStmts::new_val(mk().path_expr(vec![name])),
+                    None => self
+                        .convert_expr(ctx.used().not_static(), len)?
+                        .map(|len| cast_int(len, "usize", true)),
+                };
+                Ok(len.map(|rhs| mk().binary_expr(BinOpKind::Mul, lhs, rhs)))
             });
         }
         let ty = self.convert_type(type_id)?;
@@ -3016,6 +3667,103 @@ impl<'c> Translation<'c> {
         Ok(WithStmts::new_val(call))
     }
 
+    /// Get the name of the wrapper struct used to over-align locals to the given alignment,
+    /// generating it in the current module the first time it is requested:
+    ///
+    /// ```no_run
+    /// #[derive(Copy, Clone)]
+    /// #[repr(C, align(32))]
+    /// pub struct C2RustAligned32<T>(pub T);
+    /// ```
+    fn align_wrapper(&self, align: u64) -> String {
+        let cur_file = self.cur_file();
+        if let Some(name) = self.align_wrappers.borrow().get(&(cur_file, align)) {
+            return name.clone();
+        }
+
+        let name = self
+            .renamer
+            .borrow_mut()
+            .pick_name_root(&format!("C2RustAligned{}", align));
+
+        let repr_attr = mk().meta_item(
+            vec!["repr"],
+            MetaItemKind::List(vec![
+                simple_metaitem("C"),
+                int_arg_metaitem("align", align as u128),
+            ]),
+        );
+        let field = mk().pub_().enum_field(mk().ident_ty("T"));
+        let item = mk()
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone"])
+            .meta_item_attr(AttrStyle::Outer, repr_attr)
+            .generic_over(mk().ty_param("T"))
+            .struct_item(&name, vec![field], true);
+
+        self.items
+            .borrow_mut()
+            .entry(cur_file)
+            .or_insert_with(ItemStore::new)
+            .add_item(item);
+        self.align_wrappers
+            .borrow_mut()
+            .insert((cur_file, align), name.clone());
+
+        name
+    }
+
+    /// Determine whether the alignment Rust computes for the translation of a type may
+    /// disagree with the alignment C gives it.
+    fn alignment_may_differ(&self, type_id: CTypeId) -> bool {
+        match self.ast_context.resolve_type(type_id).kind {
+            CTypeKind::LongDouble => true,
+            CTypeKind::Complex(elt)
+            | CTypeKind::ConstantArray(elt, _)
+            | CTypeKind::IncompleteArray(elt)
+            | CTypeKind::VariableArray(elt, _) => self.alignment_may_differ(elt),
+            CTypeKind::Struct(decl_id) | CTypeKind::Union(decl_id) => {
+                match self.ast_context[decl_id].kind {
+                    CDeclKind::Struct { fields: Some(ref fields), .. }
+                    | CDeclKind::Union { fields: Some(ref fields), .. } => {
+                        fields.iter().any(|&field_id| match self.ast_context[field_id].kind {
+                            CDeclKind::Field { typ, .. } => self.alignment_may_differ(typ.ctype),
+                            _ => false,
+                        })
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Emit a compile-time assertion that Rust agrees with Clang about the alignment of a
+    /// type. The assertion is emitted once per type into the current module.
+    fn assert_align_of_type(&self, type_id: CTypeId, align: u64) -> Result<(), TranslationError> {
+        let cur_file = self.cur_file();
+        if !self.align_assertions.borrow_mut().insert((cur_file, type_id)) {
+            return Ok(());
+        }
+
+        // const _: [(); N] = [(); ::std::mem::align_of::<T>()];
+        let align_of = self.compute_align_of_type(type_id, false)?.to_expr();
+        let expected_ty = mk().array_ty(
+            mk().tuple_ty(vec![] as Vec<P<Ty>>),
+            mk().lit_expr(mk().int_lit(align as u128, "")),
+        );
+        let actual = mk().repeat_expr(mk().tuple_expr(vec![] as Vec<P<Expr>>), align_of);
+        let item = mk().const_item("_", expected_ty, actual);
+
+        self.items
+            .borrow_mut()
+            .entry(cur_file)
+            .or_insert_with(ItemStore::new)
+            .add_item(item);
+
+        Ok(())
+    }
+
     fn convert_exprs(&self, ctx: ExprContext, exprs: &[CExprId])
                      -> Result<WithStmts<Vec<P<Expr>>>, TranslationError>
     {
@@ -3046,7 +3794,11 @@ impl<'c> Translation<'c> {
 
         trace!("Converting expr {:?}: {:?}", expr_id, self.ast_context[expr_id]);
 
-        if self.tcfg.translate_const_macros {
+        if let Some(converted) = self.convert_fn_macro_arg(expr_id) {
+            return Ok(converted);
+        }
+
+        if self.tcfg.translate_const_macros || self.tcfg.translate_fn_macros {
             if let Some(converted) = self.convert_macro_expansion(ctx, expr_id)? {
                 return Ok(converted);
             }
@@ -3068,27 +3820,37 @@ impl<'c> Translation<'c> {
                 Err(TranslationError::generic("convert vector not supported"))
             }
 
-            CExprKind::UnaryType(_ty, kind, opt_expr, arg_ty) => {
+            CExprKind::UnaryType(ty, kind, opt_expr, arg_ty, value) => {
                 let result = match kind {
-                    UnTypeOp::SizeOf => match opt_expr {
-                        None => self.compute_size_of_type(ctx, arg_ty.ctype)?,
-                        Some(_) => {
-                            let inner = self.variable_array_base_type(arg_ty.ctype);
-                            let inner_size = self.compute_size_of_type(ctx, inner)?;
-
-                            if let Some(sz) = self.compute_size_of_expr(arg_ty.ctype) {
-                                inner_size.map(|x| mk().binary_expr(BinOpKind::Mul, sz, x))
-                            } else {
-                                // Otherwise, use the pointer and make a deref of a pointer offset expression
-                                inner_size
+                    // An expression operand is never translated, so `sizeof(*p++)` does not
+                    // increment `p`: the size only depends on the operand's type, and for a
+                    // VLA type on the lengths computed when the array was declared
+                    UnTypeOp::SizeOf => self.compute_size_of_type(ctx, arg_ty.ctype)?,
+                    UnTypeOp::AlignOf | UnTypeOp::PreferredAlignOf => {
+                        let preferred = match kind {
+                            UnTypeOp::PreferredAlignOf => true,
+                            _ => false,
+                        };
+                        match value {
+                            // Rust's idea of the alignment of some types (e.g. long double,
+                            // max_align_t) may not match C's, so use Clang's value for those
+                            Some(align) if self.alignment_may_differ(arg_ty.ctype) => {
+                                WithStmts::new_val(mk().lit_expr(mk().int_lit(align as u128, "")))
                             }
+                            // For an expression operand (a GNU extension), Clang's value
+                            // may come from the declaration it names rather than its type,
+                            // so only the type's own alignment is checked
+                            Some(align) if !preferred && opt_expr.is_none() => {
+                                self.assert_align_of_type(arg_ty.ctype, align)?;
+                                self.compute_align_of_type(arg_ty.ctype, preferred)?
+                            }
+                            _ => self.compute_align_of_type(arg_ty.ctype, preferred)?,
                         }
-                    },
-                    UnTypeOp::AlignOf => self.compute_align_of_type(arg_ty.ctype, false)?,
-                    UnTypeOp::PreferredAlignOf => self.compute_align_of_type(arg_ty.ctype, true)?,
+                    }
                 };
 
-                Ok(result.map(|x| mk().cast_expr(x, mk().path_ty(vec!["libc", "c_ulong"]))))
+                let ty = self.convert_type(ty.ctype)?;
+                Ok(result.map(|x| mk().cast_expr(x, ty)))
             }
 
             CExprKind::DeclRef(qual_ty, decl_id, lrvalue) => {
@@ -3124,7 +3886,20 @@ impl<'c> Translation<'c> {
                     }
                 }
 
-                let mut val = mk().path_expr(vec![rustname]);
+                // `__block` variables are shared with the blocks capturing them
+                let mut val = if self.byref_vars.contains(&decl_id) {
+                    self.byref_var(&rustname)
+                } else {
+                    mk().path_expr(vec![rustname])
+                };
+
+                // Over-aligned locals live inside of an alignment wrapper
+                if self.aligned_locals.borrow().contains(&decl_id) {
+                    val = mk().field_expr(val, "0");
+                }
+                if self.is_cleanup_local(decl_id) {
+                    val = mk().field_expr(val, "0");
+                }
 
                 // If the variable is volatile and used as something that isn't an LValue, this
                 // constitutes a volatile read.
@@ -3142,7 +3917,9 @@ impl<'c> Translation<'c> {
 
                 // Most references to the va_list should refer to the VaList
                 // type, not VaListImpl
-                if !ctx.expecting_valistimpl && self.ast_context.is_va_list(qual_ty.ctype) {
+                if !ctx.expecting_valistimpl && self.translate_valist()
+                    && self.ast_context.is_va_list(qual_ty.ctype)
+                {
                     val = mk().method_call_expr(val, "as_va_list", Vec::<P<Expr>>::new());
                 }
 
@@ -3187,6 +3964,28 @@ impl<'c> Translation<'c> {
                 OffsetOfKind::Constant(val) => {
                     Ok(WithStmts::new_val(self.mk_int_lit(ty, *val, IntBase::Dec)?))
                 }
+                OffsetOfKind::Member(val, qty, field_id) => {
+                    // `offset_of!` is not const-evaluable and cannot be used on the fields of
+                    // packed structs, so those offsets stay integer constants
+                    let struct_id = match self.ast_context.resolve_type(qty.ctype).kind {
+                        CTypeKind::Struct(struct_id)
+                            if !ctx.is_static
+                                && !ctx.is_const
+                                && !self.ast_context.is_packed_struct_decl(struct_id) =>
+                        {
+                            struct_id
+                        }
+                        _ => {
+                            let lit = self.mk_int_lit(ty, *val, IntBase::Dec)?;
+                            return Ok(WithStmts::new_val(lit));
+                        }
+                    };
+
+                    // offset_of!(Struct, field) as ty
+                    let offset = self.offset_of_member(struct_id, *field_id)?;
+                    let cast_ty = self.convert_type(ty.ctype)?;
+                    Ok(WithStmts::new_val(mk().cast_expr(offset, cast_ty)))
+                }
                 OffsetOfKind::Variable(qty, field_id, expr_id) => {
                     self.use_crate(ExternCrate::Memoffset);
 
@@ -3257,11 +4056,24 @@ impl<'c> Translation<'c> {
                     _ => {}
                 }
 
+                if kind == CastKind::LValueToRValue && self.is_fn_table_element(expr) {
+                    return self.convert_fn_table_read(ctx, expr);
+                }
+                if kind == CastKind::LValueToRValue && self.is_string_table_element(expr) {
+                    return self.convert_string_table_read(ctx, expr);
+                }
+
                 let source_ty = self.ast_context[expr]
                     .kind
                     .get_qual_type()
                     .ok_or_else(|| format_err!("bad source type"))?;
 
+                if is_explicit && kind == CastKind::BitCast {
+                    if let Some(val) = self.convert_container_of(ctx, ty, expr)? {
+                        return Ok(val);
+                    }
+                }
+
                 let val = if is_explicit {
                     let stmts = self.compute_variable_array_sizes(ctx, ty.ctype)?;
                     let mut val = self.convert_expr(ctx, expr)?;
@@ -3275,6 +4087,20 @@ impl<'c> Translation<'c> {
                 if self.casting_simd_builtin_call(expr, is_explicit, kind) {
                     return Ok(val);
                 }
+                // Reading a volatile lvalue, e.g. a memory-mapped register behind a pointer
+                if kind == CastKind::LValueToRValue
+                    && source_ty.qualifiers.is_volatile
+                    && !ctx.is_static
+                    && !ctx.is_const
+                {
+                    return val.result_map(|val| match val.kind {
+                        ExprKind::Path(..)
+                        | ExprKind::Unary(ast::UnOp::Deref, _)
+                        | ExprKind::Field(..)
+                        | ExprKind::Index(..) => self.volatile_read(&val, source_ty),
+                        _ => Ok(val),
+                    });
+                }
                 self.convert_cast(ctx, source_ty, ty, val, Some(expr), Some(kind), opt_field_id)
             }
 
@@ -3283,6 +4109,12 @@ impl<'c> Translation<'c> {
             }
 
             CExprKind::Conditional(_, cond, lhs, rhs) => {
+                // A condition on types picks one arm at translation time, and the other may not
+                // even type check in Rust
+                if let Some(is_cond_true) = self.ast_context.type_condition(cond) {
+                    return self.convert_expr(ctx, if is_cond_true { lhs } else { rhs });
+                }
+
                 if ctx.is_const {
                     return Err(format_translation_err!(
                         self.ast_context.display_loc(src_loc),
@@ -3392,8 +4224,13 @@ impl<'c> Translation<'c> {
                     ));
                 }
 
+                let rhs_type = self.ast_context[*rhs]
+                    .kind
+                    .get_type()
+                    .ok_or_else(|| format_err!("rhs node bad type"))?;
                 let rhs = self.convert_expr(ctx.used(), *rhs)?;
                 rhs.and_then(|rhs| {
+                    let rhs = self.bit_int_storage(rhs_type, rhs);
                     let simple_index_array = if ctx.needs_address() {
                         // We can't necessarily index into an array if we're using
                         // that element to compute an address.
@@ -3478,6 +4315,39 @@ impl<'c> Translation<'c> {
             }
 
             CExprKind::Call(call_expr_ty, func, ref args) => {
+                // Type-generic math, e.g. `sqrt` from <tgmath.h>
+                if let Some(call) = self.convert_tgmath_call(ctx, func, args)? {
+                    return self.convert_side_effects_expr(
+                        ctx,
+                        call,
+                        "Function call expression is not supposed to be used",
+                    );
+                }
+
+                // Logging functions selected with `--log-function`
+                if let Some(call) = self.convert_log_call(ctx, func, args)? {
+                    return self.convert_side_effects_expr(
+                        ctx,
+                        call,
+                        "Function call expression is not supposed to be used",
+                    );
+                }
+
+                // pthreads primitives translated with `--translate-pthreads`
+                if let Some(call) = self.convert_pthread_call(ctx, expr_id, func, args)? {
+                    return Ok(call);
+                }
+
+                // Calls to Apple blocks
+                if let Some(call) = self.convert_block_call(ctx, func, args)? {
+                    return self.convert_side_effects_expr(
+                        ctx,
+                        call,
+                        "Function call expression is not supposed to be used",
+                    );
+                }
+
+                let callee = self.referenced_function(func);
                 let fn_ty = self.ast_context.get_pointee_qual_type(
                     self.ast_context[func].kind.get_type()
                         .ok_or_else(|| format_err!("Invalid callee expression {:?}", func))?
@@ -3486,7 +4356,24 @@ impl<'c> Translation<'c> {
                     Some(CTypeKind::Function(_, _, is_variadic, _, _)) => *is_variadic,
                     _ => false,
                 };
+                // Extern functions whose parameters or return value are passed differently
+                let ffi_sig = match self.ast_context[func].kind {
+                    CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) => {
+                        match self.ast_context[fexp].kind {
+                            CExprKind::DeclRef(_, decl_id, _) => self.ffi_safety.fns.get(&decl_id),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
                 let func = match self.ast_context[func].kind {
+                    // Checked library function of `_FORTIFY_SOURCE`
+                    CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _)
+                        if self.is_fortify_callee(fexp) =>
+                    {
+                        return self.convert_fortify_call(ctx, fexp, args)
+                    }
+
                     // Direct function call
                     CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _)
                         // Only a direct function call with pointer decay if the
@@ -3504,6 +4391,14 @@ impl<'c> Translation<'c> {
                         return self.convert_builtin(ctx, fexp, args)
                     }
 
+                    // Call through a function table translated to an array of `fn`s,
+                    // whose entries are never null
+                    CExprKind::ImplicitCast(_, elt, CastKind::LValueToRValue, _, _)
+                        if self.is_fn_table_element(elt) =>
+                    {
+                        self.convert_expr(ctx.used(), elt)?
+                    }
+
                     // Function pointer call
                     _ => {
                         let callee = self.convert_expr(ctx.used(), func)?;
@@ -3557,13 +4452,20 @@ impl<'c> Translation<'c> {
                     // We want to decay refs only when function is variadic
                     ctx.decay_ref = DecayRef::from(is_variadic);
 
-                    let args = self.convert_exprs(ctx.used(), args)?;
+                    let args = match ffi_sig {
+                        Some(sig) => self.convert_ffi_args(ctx.used(), sig, callee, args)?,
+                        None => self.convert_call_args(ctx.used(), callee, args)?,
+                    };
 
                     let res: Result<_, TranslationError> = Ok(
                         args.map(|args| mk().call_expr(func, args))
                     );
                     res
                 })?;
+                let call = match ffi_sig {
+                    Some(sig) if ctx.is_used() => self.convert_ffi_ret(sig, call),
+                    _ => call,
+                };
 
                 self.convert_side_effects_expr(
                     ctx,
@@ -3634,10 +4536,16 @@ impl<'c> Translation<'c> {
                 }
             }
 
-            CExprKind::Paren(_, val) => self.convert_expr(ctx, val),
+            CExprKind::Paren(_, val) | CExprKind::GenericSelection(_, val) => {
+                self.convert_expr(ctx, val)
+            }
 
             CExprKind::CompoundLiteral(_, val) => self.convert_expr(ctx, val),
 
+            CExprKind::Block { typ, ref params, body, ref captures } => {
+                self.convert_block_literal(ctx, typ, params, body, captures)
+            }
+
             CExprKind::InitList(ty, ref ids, opt_union_field_id, _) => {
                 self.convert_init_list(ctx, ty, ids, opt_union_field_id)
             }
@@ -3654,6 +4562,11 @@ impl<'c> Translation<'c> {
 
             CExprKind::VAArg(ty, val_id) => self.convert_vaarg(ctx, ty, val_id),
 
+            CExprKind::TypesCompatible(ty, _, _, compatible) => {
+                let val = self.mk_int_lit(ty, compatible as u64, IntBase::Dec)?;
+                Ok(WithStmts::new_val(val))
+            }
+
             CExprKind::Choose(_, _cond, lhs, rhs, is_cond_true) => {
                 let chosen_expr = if is_cond_true {
                     self.convert_expr(ctx, lhs)?
@@ -3695,6 +4608,13 @@ impl<'c> Translation<'c> {
                 .first()
             {
                 trace!("  found macro expansion: {:?}", macro_id);
+                match self.ast_context[*macro_id].kind {
+                    CDeclKind::MacroFunction { .. } if self.tcfg.translate_fn_macros => {
+                        return self.convert_fn_macro_call(ctx, expr_id, *macro_id);
+                    }
+                    CDeclKind::MacroObject { .. } if self.tcfg.translate_const_macros => {}
+                    _ => return Ok(None),
+                }
                 // Ensure that we've converted this macro and that it has a
                 // valid definition
                 let expansion = self.macro_expansions.borrow().get(macro_id).cloned();
@@ -3936,6 +4856,22 @@ impl<'c> Translation<'c> {
                     if self.ast_context.is_function_pointer(ty.ctype)
                         || self.ast_context.is_function_pointer(source_ty.ctype)
                     {
+                        if let Some(e) = expr {
+                            if let Some(thunk) = self.convert_fn_ptr_thunk(ty.ctype, e)? {
+                                return Ok(WithStmts::new_val(thunk));
+                            }
+                            if self.ast_context.is_function_pointer(ty.ctype)
+                                && self.ast_context.is_function_pointer(source_ty.ctype)
+                            {
+                                let loc = self.ast_context.display_loc(&self.ast_context[e].loc)
+                                    .map_or("Unknown location".to_string(), |l| l.to_string());
+                                warn!(
+                                    "{}: cast between incompatible function pointer types \
+                                     translated to a transmute",
+                                    loc,
+                                );
+                            }
+                        }
                         if ctx.is_static || ctx.is_const {
                             self.use_feature("const_transmute");
                         }
@@ -3969,6 +4905,13 @@ impl<'c> Translation<'c> {
                 })
             }
 
+            CastKind::IntegralToPointer if expr.map_or(false, |e| self.fixed_address_lit(e).is_some()) => {
+                // `(T *)0x40021000` keeps its literal, as in `0x40021000 as *mut T`
+                let lit = expr.and_then(|e| self.fixed_address_lit(e)).unwrap();
+                let target_ty = self.convert_type(ty.ctype)?;
+                Ok(WithStmts::new_val(mk().cast_expr(lit, target_ty)))
+            }
+
             CastKind::IntegralToPointer
             | CastKind::PointerToIntegral
             | CastKind::IntegralCast
@@ -3981,6 +4924,8 @@ impl<'c> Translation<'c> {
                 let source_ty_ctype_id = source_ty.ctype;
 
                 let source_ty = self.convert_type(source_ty_ctype_id)?;
+                // Strict `_BitInt` newtypes are converted through their storage integer
+                let val = val.map(|x| self.bit_int_storage(source_ty_ctype_id, x));
                 if let CTypeKind::LongDouble = target_ty_ctype {
                     self.use_crate(ExternCrate::F128);
 
@@ -4002,7 +4947,14 @@ impl<'c> Translation<'c> {
                             }
                             Ok(WithStmts::new_unsafe_val(transmute_expr(source_ty, target_ty, x, self.tcfg.emit_no_std)))
                         } else {
-                            Ok(WithStmts::new_val(mk().cast_expr(x, target_ty)))
+                            let target_ty = self.bit_int_storage_ty(ty.ctype).unwrap_or(target_ty);
+                            let cast = self.numeric_cast_expr(x, target_ty, ty.ctype);
+                            let fits = expr.map_or(false, |e| self.literal_fits_bit_int(ty.ctype, e));
+                            if fits {
+                                Ok(WithStmts::new_val(self.bit_int_in_range(ty.ctype, cast)))
+                            } else {
+                                Ok(WithStmts::new_val(self.wrap_bit_int(ty.ctype, cast)))
+                            }
                         }
                     })
                 }
@@ -4020,7 +4972,7 @@ impl<'c> Translation<'c> {
                 // and to be a pointer as a function argument we would get
                 // spurious casts when trying to treat it like a VaList which
                 // has reference semantics.
-                if self.ast_context.is_va_list(ty.ctype) {
+                if self.translate_valist() && self.ast_context.is_va_list(ty.ctype) {
                     return Ok(val)
                 }
 
@@ -4231,6 +5183,10 @@ impl<'c> Translation<'c> {
             _ => {}
         }
 
+        if self.is_rust_enum(enum_decl) {
+            return val.map(|x| self.rust_enum_from_int(enum_decl, x));
+        }
+
         val.map(|x| mk().cast_expr(x, target_ty))
     }
 
@@ -4245,7 +5201,8 @@ impl<'c> Translation<'c> {
         if resolved_ty.is_bool() {
             Ok(WithStmts::new_val(mk().lit_expr(mk().bool_lit(false))))
         } else if resolved_ty.is_integral_type() {
-            Ok(WithStmts::new_val(mk().lit_expr(mk().int_lit(0, LitIntType::Unsuffixed))))
+            let zero = mk().lit_expr(mk().int_lit(0, LitIntType::Unsuffixed));
+            Ok(WithStmts::new_val(self.bit_int_in_range(resolved_ty_id, zero)))
         } else if resolved_ty.is_floating_type() {
             match self.ast_context[ty_id].kind {
                 CTypeKind::LongDouble => Ok(WithStmts::new_val(mk().path_expr(vec!["f128", "f128", "ZERO"]))),
@@ -4405,6 +5362,7 @@ impl<'c> Translation<'c> {
     /// Convert a boolean expression to a boolean for use in && or || or if
     fn match_bool(&self, target: bool, ty_id: CTypeId, val: P<Expr>) -> P<Expr> {
         let ty = &self.ast_context.resolve_type(ty_id).kind;
+        let val = self.bit_int_storage(ty_id, val);
 
         if self.ast_context.is_function_pointer(ty_id) {
             if target {
@@ -4428,26 +5386,29 @@ impl<'c> Translation<'c> {
             // One simplification we can make at the cost of inspecting `val` more closely: if `val`
             // is already in the form `(x <op> y) as <ty>` where `<op>` is a Rust operator
             // that returns a boolean, we can simple output `x <op> y` or `!(x <op> y)`.
-            if let ExprKind::Cast(ref arg, _) = val.kind {
-                if let ExprKind::Binary(op, _, _) = arg.kind {
-                    match op.node {
-                        BinOpKind::Or
-                        | BinOpKind::And
-                        | BinOpKind::Eq
-                        | BinOpKind::Ne
-                        | BinOpKind::Lt
-                        | BinOpKind::Le
-                        | BinOpKind::Gt
-                        | BinOpKind::Ge => {
-                            if target {
-                                // If target == true, just return the argument
-                                return arg.clone();
-                            } else {
-                                // If target == false, return !arg
-                                return mk().unary_expr(ast::UnOp::Not, arg.clone());
+            // This is left out when boolean conditions should stay explicit.
+            if self.tcfg.simplify_bool_conditions() {
+                if let ExprKind::Cast(ref arg, _) = val.kind {
+                    if let ExprKind::Binary(op, _, _) = arg.kind {
+                        match op.node {
+                            BinOpKind::Or
+                            | BinOpKind::And
+                            | BinOpKind::Eq
+                            | BinOpKind::Ne
+                            | BinOpKind::Lt
+                            | BinOpKind::Le
+                            | BinOpKind::Gt
+                            | BinOpKind::Ge => {
+                                if target {
+                                    // If target == true, just return the argument
+                                    return arg.clone();
+                                } else {
+                                    // If target == false, return !arg
+                                    return mk().unary_expr(ast::UnOp::Not, arg.clone());
+                                }
                             }
+                            _ => {}
                         }
-                        _ => {}
                     }
                 }
             }
@@ -4490,6 +5451,7 @@ impl<'c> Translation<'c> {
 
         if self.tcfg.reorganize_definitions {
             add_src_loc_attr(&mut item.attrs, &decl.loc.as_ref().map(|x| x.begin()));
+            add_internal_linkage_attr(&mut item, decl);
             let mut item_stores = self.items.borrow_mut();
             let items = item_stores
                 .entry(decl_file_id.unwrap())
@@ -4564,6 +5526,13 @@ impl<'c> Translation<'c> {
             // libc can be accessed from anywhere as of Rust 2019 by full path
             Void | Char | SChar | UChar | Short | UShort | Int | UInt | Long | ULong | LongLong
             | ULongLong | Int128 | UInt128 | Half | Float | Double | LongDouble => {}
+            // Strict `_BitInt` newtypes are defined in the main module
+            BitInt(signed, width) => {
+                if self.tcfg.strict_bit_ints {
+                    let name = bit_int_newtype_name(signed, width);
+                    self.import_bit_int_newtype(decl_file_id, &name);
+                }
+            }
             // Bool uses the bool type, so no dependency on libc
             Bool => {}
             Paren(ctype)
@@ -4611,7 +5580,7 @@ impl<'c> Translation<'c> {
             Vector(..) => {
                 // Handled in `import_simd_typedef`
             }
-            TypeOfExpr(_) | BuiltinFn => {}
+            TypeOfExpr(_) | BuiltinFn | UnsupportedBuiltin(_) => {}
         }
     }
 
@@ -4665,6 +5634,8 @@ impl<'c> Translation<'c> {
                 }
             }
 
+            CDeclKind::MacroFunction { .. } => self.import_fn_macro_types(decl_id, decl_file_id),
+
             CDeclKind::Function { .. } => {
                 // TODO: We may need to explicitly skip SIMD functions here when getting types for
                 // a fn definition in a header since SIMD headers define functions but we're using imports
@@ -111,6 +111,11 @@ impl<'c> Translation<'c> {
                     ctx = ctx.decay_ref();
                 }
 
+                // Values of Rust enums are compared directly
+                if let Some(cmp) = self.convert_rust_enum_comparison(ctx, op, lhs, rhs)? {
+                    return Ok(cmp);
+                }
+
                 let ty = self.convert_type(type_id.ctype)?;
 
                 let lhs_type_id = self
@@ -138,7 +143,7 @@ impl<'c> Translation<'c> {
                 } else {
                     let rhs_ctx = ctx;
 
-                    // When we use methods on pointers (ie wrapping_offset_from or offset)
+                    // When we use methods on pointers (ie offset_from or offset)
                     // we must ensure we have an explicit raw ptr for the self param, as
                     // self references do not decay
                     if op == c_ast::BinOp::Subtract || op == c_ast::BinOp::Add {
@@ -340,11 +345,16 @@ impl<'c> Translation<'c> {
             _ => false,
         };
 
+        // Updates of a `_BitInt(N)` are wrapped back into N bits, which needs an explicit read
+        let is_bit_int_arith = op.underlying_assignment().is_some()
+            && qtype_kind.bit_int_storage_width().is_some();
+
         let lhs_translation = if initial_lhs_type_id.ctype != compute_lhs_type_id.ctype
             || ctx.is_used()
             || pointer_lhs.is_some()
             || is_volatile_compound_assign
             || is_unsigned_arith
+            || is_bit_int_arith
         {
             self.name_reference_write_read(ctx, lhs)?
         } else {
@@ -368,7 +378,7 @@ impl<'c> Translation<'c> {
                     }
 
                     // Anything volatile needs to be desugared into explicit reads and writes
-                    op if is_volatile || is_unsigned_arith => {
+                    op if is_volatile || is_unsigned_arith || is_bit_int_arith => {
                         let mut is_unsafe = false;
                         let op = op
                             .underlying_assignment()
@@ -389,7 +399,8 @@ impl<'c> Translation<'c> {
                         } else {
                             let lhs_type = self.convert_type(compute_type.unwrap().ctype)?;
                             let write_type = self.convert_type(qtype.ctype)?;
-                            let lhs = mk().cast_expr(read.clone(), lhs_type.clone());
+                            let read = self.bit_int_storage(initial_lhs_type_id.ctype, read.clone());
+                            let lhs = mk().cast_expr(read, lhs_type.clone());
                             let ty = self.convert_type(result_type_id.ctype)?;
                             let val = self.convert_binary_operator(
                                 ctx,
@@ -408,14 +419,17 @@ impl<'c> Translation<'c> {
                                 .kind
                                 .is_enum();
                             let result_type = self.convert_type(qtype.ctype)?;
-                            let val = if is_enum_result {
+                            if is_enum_result {
                                 is_unsafe = true;
                                 if ctx.is_const { self.use_feature("const_transmute"); }
-                                transmute_expr(lhs_type, result_type, val, self.tcfg.emit_no_std)
+                                let val = transmute_expr(lhs_type, result_type, val, self.tcfg.emit_no_std);
+                                mk().cast_expr(val, write_type)
+                            } else if let Some(storage_ty) = self.bit_int_storage_ty(qtype.ctype) {
+                                // Results go back into a `_BitInt` through its storage integer
+                                self.wrap_bit_int(qtype.ctype, mk().cast_expr(val, storage_ty))
                             } else {
-                                mk().cast_expr(val, result_type)
-                            };
-                            mk().cast_expr(val, write_type)
+                                mk().cast_expr(mk().cast_expr(val, result_type), write_type)
+                            }
                         };
 
                         let write = if is_volatile {
@@ -433,11 +447,13 @@ impl<'c> Translation<'c> {
                     // Everything else
                     c_ast::BinOp::AssignAdd if pointer_lhs.is_some() => {
                         let mul = self.compute_size_of_expr(pointer_lhs.unwrap().ctype);
+                        let rhs = self.bit_int_storage(rhs_type_id.ctype, rhs);
                         let ptr = pointer_offset(write.clone(), rhs, mul, false, false);
                         WithStmts::new_val(mk().assign_expr(&write, ptr))
                     }
                     c_ast::BinOp::AssignSubtract if pointer_lhs.is_some() => {
                         let mul = self.compute_size_of_expr(pointer_lhs.unwrap().ctype);
+                        let rhs = self.bit_int_storage(rhs_type_id.ctype, rhs);
                         let ptr = pointer_offset(write.clone(), rhs, mul, true, false);
                         WithStmts::new_val(mk().assign_expr(&write, ptr))
                     }
@@ -595,10 +611,14 @@ impl<'c> Translation<'c> {
             .index(ctype)
             .kind
             .is_unsigned_integral_type();
+        let lhs = self.bit_int_storage(lhs_type.ctype, lhs);
+        let rhs = self.bit_int_storage(rhs_type.ctype, rhs);
 
-        match op {
+        let val = match op {
             c_ast::BinOp::Add => self.convert_addition(ctx, lhs_type, rhs_type, lhs, rhs),
-            c_ast::BinOp::Subtract => self.convert_subtraction(ctx, ty, lhs_type, rhs_type, lhs, rhs),
+            c_ast::BinOp::Subtract => {
+                self.convert_subtraction(ctx, ty, lhs_type, rhs_type, lhs, rhs)
+            }
 
             c_ast::BinOp::Multiply if is_unsigned_integral_type => {
                 if ctx.is_const {
@@ -688,7 +708,18 @@ impl<'c> Translation<'c> {
             c_ast::BinOp::BitOr => Ok(mk().binary_expr(BinOpKind::BitOr, lhs, rhs)),
 
             op => unimplemented!("Translation of binary operator {:?}", op),
-        }
+        }?;
+
+        // Operations that can carry a `_BitInt(N)` out of its N bits wrap it back into range, and
+        // strict `_BitInt` results go back into their newtype
+        let wraps = match op {
+            c_ast::BinOp::Add
+            | c_ast::BinOp::Subtract
+            | c_ast::BinOp::Multiply
+            | c_ast::BinOp::ShiftLeft => true,
+            _ => self.tcfg.strict_bit_ints,
+        };
+        Ok(if wraps { self.wrap_bit_int(ctype, val) } else { val })
     }
 
     fn convert_addition(
@@ -735,15 +766,32 @@ impl<'c> Translation<'c> {
         if let &CTypeKind::Pointer(pointee) = rhs_type {
             if ctx.is_const {
                 return Err(TranslationError::generic(
-                    "Cannot use wrapping offset from in a const expression",
+                    "Cannot use offset from in a const expression",
                 ));
             }
-            // The wrapping_offset_from method is locked behind a feature gate
-            // and replaces the now deprecated offset_to (opposite argument order)
-            // wrapping_offset_from panics when the pointee is a ZST
-            self.use_feature("ptr_wrapping_offset_from");
-
-            let mut offset = mk().method_call_expr(lhs, "wrapping_offset_from", vec![rhs]);
+            // The offset_from method is locked behind a feature gate. Like C, it counts
+            // elements of the pointee type, and like C, both pointers must point into the
+            // same object. It panics when the pointee is a ZST.
+            self.use_feature("ptr_offset_from");
+
+            let lhs_const = match *lhs_type {
+                CTypeKind::Pointer(lhs_pointee) => lhs_pointee.qualifiers.is_const,
+                _ => pointee.qualifiers.is_const,
+            };
+            let (lhs, rhs) = match self.ast_context.resolve_type(pointee.ctype).kind {
+                // GNU C counts the difference of `void *`s in bytes
+                CTypeKind::Void => {
+                    let bytes_ty = || mk().ptr_ty(mk().path_ty(vec!["u8"]));
+                    (mk().cast_expr(lhs, bytes_ty()), mk().cast_expr(rhs, bytes_ty()))
+                }
+                // `const T *` and `T *` operands: make the `*mut T` to `*const T` conversion
+                // explicit instead of relying on the coercion of the argument
+                _ if lhs_const != pointee.qualifiers.is_const => {
+                    (lhs, mk().cast_expr(rhs, mk().ptr_ty(mk().infer_ty())))
+                }
+                _ => (lhs, rhs),
+            };
+            let mut offset = mk().method_call_expr(lhs, "offset_from", vec![rhs]);
 
             if let Some(sz) = self.compute_size_of_expr(pointee.ctype) {
                 let div = cast_int(sz, "isize", false);
@@ -792,6 +840,7 @@ impl<'c> Translation<'c> {
             }
             _ => mk().lit_expr(mk().int_lit(1, LitIntType::Unsuffixed)),
         };
+        let one = self.bit_int_in_range(ty.ctype, one);
         let arg_type = self.ast_context[arg]
             .kind
             .get_qual_type()
@@ -877,10 +926,13 @@ impl<'c> Translation<'c> {
                                 ));
                             }
                             let m = if up { "wrapping_add" } else { "wrapping_sub" };
-                            mk().method_call_expr(read.clone(), m, vec![one])
+                            let read = self.bit_int_storage(ty.ctype, read.clone());
+                            let val = mk().method_call_expr(read, m, vec![one]);
+                            self.wrap_bit_int(ty.ctype, val)
                         } else {
                             let k = if up { BinOpKind::Add } else { BinOpKind::Sub };
-                            mk().binary_expr(k, read.clone(), one)
+                            let read = self.bit_int_storage(ty.ctype, read.clone());
+                            self.wrap_bit_int(ty.ctype, mk().binary_expr(k, read, one))
                         }
                     };
 
@@ -1012,7 +1064,9 @@ impl<'c> Translation<'c> {
             c_ast::UnOp::Plus => self.convert_expr(ctx.used(), arg), // promotion is explicit in the clang AST
 
             c_ast::UnOp::Negate => {
-                let val = self.convert_expr(ctx.used(), arg)?;
+                let val = self
+                    .convert_expr(ctx.used(), arg)?
+                    .map(|v| self.bit_int_storage(ctype, v));
 
                 if resolved_ctype.kind.is_unsigned_integral_type() {
                     if ctx.is_const {
@@ -1020,14 +1074,17 @@ impl<'c> Translation<'c> {
                             "Cannot use wrapping negate in a const expression",
                         ));
                     }
-                    Ok(val.map(wrapping_neg_expr))
+                    Ok(val.map(|v| self.wrap_bit_int(ctype, wrapping_neg_expr(v))))
                 } else {
-                    Ok(val.map(neg_expr))
+                    Ok(val.map(|v| self.wrap_bit_int(ctype, neg_expr(v))))
                 }
             }
             c_ast::UnOp::Complement => Ok(self
                 .convert_expr(ctx.used(), arg)?
-                .map(|a| mk().unary_expr(ast::UnOp::Not, a))),
+                .map(|a| {
+                    let a = self.bit_int_storage(ctype, a);
+                    self.wrap_bit_int(ctype, mk().unary_expr(ast::UnOp::Not, a))
+                })),
 
             c_ast::UnOp::Not => {
                 let val = self.convert_condition(ctx, false, arg)?;
@@ -0,0 +1,252 @@
+//! Settings controlled by `--output-style`.
+//!
+//! `explicit` output spells everything out: full `::std::` paths, every cast, and `return`
+//! statements. `readable` output is the most idiomatic code the translator can safely produce.
+//! Every setting can also be chosen on its own, overriding the style; see the accessors on
+//! `TranspilerConfig`.
+
+use super::*;
+use std::collections::HashSet;
+use syntax::mut_visit::{self, MutVisitor};
+use syntax::visit::{self, Visitor};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputStyle {
+    Explicit,
+    Readable,
+}
+
+/// Output style settings chosen individually. `None` follows the selected `OutputStyle`.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct OutputStyleOverrides {
+    pub import_std_paths: Option<bool>,
+    pub elide_redundant_casts: Option<bool>,
+    pub trailing_return_exprs: Option<bool>,
+    pub simplify_bool_conditions: Option<bool>,
+}
+
+impl<'c> Translation<'c> {
+    /// Cast `val` to `target_ty`, the translation of `target_ctype`. With
+    /// `elide_redundant_casts`, values already of the target type are not cast again, and
+    /// integer literals that were cast to another integer type are cast directly to the target.
+    pub fn numeric_cast_expr(&self, val: P<Expr>, target_ty: P<Ty>, target_ctype: CTypeId) -> P<Expr> {
+        if self.tcfg.elide_redundant_casts() {
+            if let ExprKind::Cast(ref inner, ref ty) = val.kind {
+                if pprust::ty_to_string(ty) == pprust::ty_to_string(&target_ty) {
+                    return val;
+                }
+
+                // `1 as libc::c_int as libc::c_ulong` becomes `1 as libc::c_ulong`, as long as
+                // rustc won't reject the literal as out of range for the new type
+                let fits = int_lit_value(inner).map_or(false, |v| self.int_lit_fits(v, target_ctype));
+                if fits {
+                    return mk().cast_expr(inner.clone(), target_ty);
+                }
+            }
+        }
+        mk().cast_expr(val, target_ty)
+    }
+
+    /// Check that the value of an integer literal is in range for the integral type `ctype`.
+    fn int_lit_fits(&self, val: u128, ctype: CTypeId) -> bool {
+        let max = match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::Char | CTypeKind::SChar => i8::max_value() as u128,
+            CTypeKind::UChar => u8::max_value() as u128,
+            CTypeKind::Short => i16::max_value() as u128,
+            CTypeKind::UShort => u16::max_value() as u128,
+            // `long` is only 32 bits wide on some targets
+            CTypeKind::Int | CTypeKind::Long => i32::max_value() as u128,
+            CTypeKind::UInt | CTypeKind::ULong => u32::max_value() as u128,
+            CTypeKind::LongLong => i64::max_value() as u128,
+            CTypeKind::ULongLong => u64::max_value() as u128,
+            CTypeKind::Int128 => i128::max_value() as u128,
+            CTypeKind::UInt128 => u128::max_value(),
+            _ => return false,
+        };
+        val <= max
+    }
+}
+
+/// The value of an unsuffixed integer literal expression.
+pub fn int_lit_value(expr: &Expr) -> Option<u128> {
+    let lit = match expr.kind {
+        ExprKind::Lit(ref lit) => lit,
+        _ => return None,
+    };
+    match lit.kind {
+        LitKind::Int(val, LitIntType::Unsuffixed) => Some(val),
+        // Hex and octal literals are emitted verbatim, as unsuffixed floats
+        LitKind::FloatUnsuffixed(sym) => {
+            let digits = sym.as_str().to_string();
+            if digits.starts_with("0x") {
+                u128::from_str_radix(&digits[2..], 16).ok()
+            } else if digits.starts_with("0o") {
+                u128::from_str_radix(&digits[2..], 8).ok()
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Turn a `return x;` at the end of a function body into the trailing expression `x`, and drop
+/// a final bare `return;`.
+pub fn trailing_return_expr(block: &mut Block) {
+    let val = match block.stmts.last().map(|stmt| &stmt.kind) {
+        Some(StmtKind::Semi(expr)) | Some(StmtKind::Expr(expr)) => match expr.kind {
+            ExprKind::Ret(ref val) => val.clone(),
+            _ => return,
+        },
+        _ => return,
+    };
+    block.stmts.pop();
+
+    if let Some(val) = val {
+        // `match x { .. } as T` would parse as a statement followed by garbage
+        let val = if starts_with_block(&val) && !is_block_like(&val) {
+            mk().paren_expr(val)
+        } else {
+            val
+        };
+        block.stmts.push(mk().expr_stmt(val));
+    }
+}
+
+fn is_block_like(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::If(..)
+        | ExprKind::Match(..)
+        | ExprKind::Block(..)
+        | ExprKind::Loop(..)
+        | ExprKind::While(..)
+        | ExprKind::ForLoop(..)
+        | ExprKind::TryBlock(..) => true,
+        _ => false,
+    }
+}
+
+/// Check whether the leftmost subexpression of `expr` is block-like.
+fn starts_with_block(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Binary(_, ref lhs, _)
+        | ExprKind::Cast(ref lhs, _)
+        | ExprKind::Type(ref lhs, _)
+        | ExprKind::Field(ref lhs, _)
+        | ExprKind::Index(ref lhs, _)
+        | ExprKind::Call(ref lhs, _)
+        | ExprKind::Assign(ref lhs, _)
+        | ExprKind::AssignOp(_, ref lhs, _)
+        | ExprKind::Try(ref lhs) => starts_with_block(lhs),
+        ExprKind::MethodCall(_, ref args) => starts_with_block(&args[0]),
+        _ => is_block_like(expr),
+    }
+}
+
+/// Shorten `::std::` and `::core::` paths in `items` by importing the module containing the
+/// item they name, e.g. `::std::mem::size_of` becomes `mem::size_of` with `use std::mem;`.
+/// Submodules get imports of their own. Modules whose name is already in use are left alone.
+pub fn import_std_paths(items: Vec<P<Item>>) -> Vec<P<Item>> {
+    let mut items = items;
+    for item in &mut items {
+        if let ItemKind::Mod(ref mut module) = item.kind {
+            let inner = mem::replace(&mut module.items, vec![]);
+            module.items = import_std_paths(inner);
+        }
+    }
+
+    let mut names = DefinedNames(HashSet::new());
+    for item in &items {
+        names.visit_item(item);
+    }
+
+    let mut importer = StdPathImporter {
+        taken: names.0,
+        imports: IndexMap::new(),
+    };
+    for item in &mut items {
+        match item.kind {
+            ItemKind::Mod(..) | ItemKind::Use(..) => {}
+            _ => importer.visit_item_kind(&mut item.kind),
+        }
+    }
+
+    let mut imports = importer.imports;
+    imports.sort_keys();
+    let mut uses: Vec<P<Item>> = imports
+        .into_iter()
+        .map(|(_, path)| mk().use_simple_item(path, None as Option<Ident>))
+        .collect();
+    uses.extend(items);
+    uses
+}
+
+/// Collects the names of all items and imports, which new imports must not shadow.
+struct DefinedNames(HashSet<String>);
+
+impl<'a> Visitor<'a> for DefinedNames {
+    fn visit_item(&mut self, item: &'a Item) {
+        self.0.insert(item.ident.to_string());
+        visit::walk_item(self, item);
+    }
+
+    fn visit_foreign_item(&mut self, item: &'a ForeignItem) {
+        self.0.insert(item.ident.to_string());
+        visit::walk_foreign_item(self, item);
+    }
+
+    fn visit_use_tree(&mut self, tree: &'a UseTree, id: NodeId, _nested: bool) {
+        if let UseTreeKind::Simple(..) = tree.kind {
+            self.0.insert(tree.ident().to_string());
+        }
+        visit::walk_use_tree(self, tree, id);
+    }
+
+    fn visit_mac(&mut self, _mac: &'a Mac) {}
+}
+
+struct StdPathImporter {
+    taken: HashSet<String>,
+    /// Imported module names and the full paths they refer to
+    imports: IndexMap<String, Vec<String>>,
+}
+
+impl StdPathImporter {
+    fn shorten(&mut self, path: &mut Path) {
+        // `::std::module::item`, possibly with more modules in between
+        let len = path.segments.len();
+        if len < 4 {
+            return;
+        }
+        let names: Vec<String> = path.segments.iter().map(|seg| seg.ident.to_string()).collect();
+        let is_root = names[0].is_empty() || names[0] == "{{root}}";
+        if !is_root || (names[1] != "std" && names[1] != "core") {
+            return;
+        }
+        if path.segments[..len - 1].iter().any(|seg| seg.args.is_some()) {
+            return;
+        }
+
+        let module = &names[len - 2];
+        let module_path = names[1..len - 1].to_vec();
+        match self.imports.get(module) {
+            Some(imported) if *imported != module_path => return,
+            Some(_) => {}
+            None if self.taken.contains(module) => return,
+            None => {
+                self.imports.insert(module.clone(), module_path);
+            }
+        }
+
+        path.segments.drain(..len - 2);
+    }
+}
+
+impl MutVisitor for StdPathImporter {
+    fn visit_path(&mut self, path: &mut Path) {
+        self.shorten(path);
+        mut_visit::noop_visit_path(path, self);
+    }
+
+    fn visit_mac(&mut self, _mac: &mut Mac) {}
+}
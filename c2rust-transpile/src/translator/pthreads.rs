@@ -0,0 +1,748 @@
+//! Translation of pthreads primitives into `std::sync` and `std::thread` with
+//! `--translate-pthreads`.
+//!
+//! A `pthread_mutex_t` becomes a `Mutex<()>` when it is a global that no other file uses, and
+//! that is only initialized with the default attributes, destroyed, locked and unlocked. Since
+//! `Mutex::new` is not a `const fn`, the mutex is created by `run_static_initializers`. Each
+//! function locking it declares an `Option` of a lock guard, which locking sets and unlocking
+//! clears. This only releases the mutex where the C code does if every lock is followed by an
+//! unlock of the same mutex in the same block, and control flow can't leave or enter the
+//! statements in between other than through their start and end.
+//!
+//! A `pthread_once_t` only passed to `pthread_once` becomes a `Once`. A local `pthread_t` that
+//! is only passed to `pthread_create`, with the default attributes and a function taking a
+//! `void *`, and to `pthread_join` becomes an `Option<JoinHandle<usize>>` for a thread running
+//! that function. The argument pointer is passed to the thread as a `usize`, since raw pointers
+//! can't be sent to other threads.
+//!
+//! Everything else keeps calling the `libc` functions, including mutexes used with condition
+//! variables or shared with C code that isn't translated. A warning reports each call that
+//! could have been translated otherwise, and why it wasn't.
+
+use super::*;
+use crate::c_ast::iterators::DFNodes;
+use std::collections::HashSet;
+
+/// The crate providing the `Mutex`es that pthreads mutexes are translated into.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PthreadsBackend {
+    /// `std::sync::Mutex`
+    Std,
+    /// `parking_lot::Mutex`, whose locks can't be poisoned
+    ParkingLot,
+}
+
+/// The pthreads types that can be translated
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PthreadKind {
+    Mutex,
+    Once,
+    Thread,
+}
+
+/// The pthreads functions whose calls are translated when their first argument is translated
+const TRANSLATED_FUNCTIONS: &[&str] = &[
+    "pthread_mutex_init",
+    "pthread_mutex_destroy",
+    "pthread_mutex_lock",
+    "pthread_mutex_unlock",
+    "pthread_once",
+    "pthread_create",
+    "pthread_join",
+];
+
+/// The pthreads variables translated into `std` types, and the calls locking and unlocking the
+/// mutexes among them
+#[derive(Debug, Default)]
+pub struct PthreadObjects {
+    /// Global `pthread_mutex_t`s translated into `Mutex<()>`s
+    pub mutexes: IndexSet<CDeclId>,
+    /// `pthread_once_t`s translated into `Once`s
+    pub onces: IndexSet<CDeclId>,
+    /// Local `pthread_t`s translated into `Option<JoinHandle<usize>>`s
+    pub threads: IndexSet<CDeclId>,
+    /// Calls locking one of `mutexes`, and the mutex
+    pub locks: IndexMap<CExprId, CDeclId>,
+    /// Calls unlocking one of `mutexes`, and the mutex
+    pub unlocks: IndexMap<CExprId, CDeclId>,
+}
+
+/// Build the type `Option<ty>`.
+fn option_ty(ty: P<Ty>) -> P<Ty> {
+    mk().path_ty(vec![mk().path_segment_with_args(
+        "Option",
+        mk().angle_bracketed_args(vec![ty]),
+    )])
+}
+
+/// Build the expression `Some(expr)`.
+fn some_expr(expr: P<Expr>) -> P<Expr> {
+    mk().call_expr(mk().path_expr(vec!["Some"]), vec![expr])
+}
+
+/// Build the call `expr.method()` of a method without arguments.
+fn call_method(expr: P<Expr>, method: &str) -> P<Expr> {
+    mk().method_call_expr(expr, method, Vec::<P<Expr>>::new())
+}
+
+impl<'c> Translation<'c> {
+    /// Find the pthreads variables that can be translated into `std` types, and report the calls
+    /// that keep using `libc`.
+    pub fn find_pthread_objects(&self) -> PthreadObjects {
+        let mut params = HashSet::new();
+        for (_, decl) in self.ast_context.iter_decls() {
+            if let CDeclKind::Function { ref parameters, .. } = decl.kind {
+                params.extend(parameters.iter().cloned());
+            }
+        }
+
+        let mut kinds = IndexMap::new();
+        let mut rejected: IndexMap<CDeclId, &str> = IndexMap::new();
+        for (&decl_id, _) in self.ast_context.iter_decls() {
+            if let Some(kind) = self.pthread_kind(decl_id) {
+                kinds.insert(decl_id, kind);
+                let reason = if params.contains(&decl_id) {
+                    Some("it is a function parameter")
+                } else {
+                    self.pthread_variable_rejection(decl_id, kind)
+                };
+                if let Some(reason) = reason {
+                    rejected.insert(decl_id, reason);
+                }
+            }
+        }
+        if kinds.is_empty() {
+            return PthreadObjects::default();
+        }
+
+        // References to the variables that are arguments of calls we can translate, and the calls
+        // locking or unlocking a mutex, with whether they lock it
+        let mut supported_refs = HashSet::new();
+        let mut mutex_calls = IndexMap::new();
+        let mut calls = vec![];
+        for (&call, expr) in self.ast_context.iter_exprs() {
+            let (func, args) = match expr.kind {
+                CExprKind::Call(_, func, ref args) => (func, args),
+                _ => continue,
+            };
+            let name = match self.pthread_callee(func) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let mut object = None;
+            for (index, &arg) in args.iter().enumerate() {
+                let (decl_ref, decl_id, by_address) = match self.pthread_object(arg) {
+                    Some(found) => found,
+                    None => continue,
+                };
+                let kind = match kinds.get(&decl_id) {
+                    Some(&kind) => kind,
+                    None => continue,
+                };
+                let support = if index == 0 {
+                    object = Some(decl_id);
+                    self.pthread_call_support(name, kind, by_address, args)
+                } else if name.starts_with("pthread_cond_") {
+                    Err("it is used with a condition variable")
+                } else {
+                    Err("it is passed to a pthreads function that is not translated")
+                };
+                match support {
+                    Ok(()) => {
+                        supported_refs.insert(decl_ref);
+                        if name == "pthread_mutex_lock" || name == "pthread_mutex_unlock" {
+                            mutex_calls.insert(call, (decl_id, name == "pthread_mutex_lock"));
+                        }
+                    }
+                    Err(reason) => {
+                        rejected.entry(decl_id).or_insert(reason);
+                    }
+                }
+            }
+            calls.push((call, name, object));
+        }
+
+        // Any other reference may let C code use the variable behind our back
+        for (expr_id, expr) in self.ast_context.iter_exprs() {
+            if let CExprKind::DeclRef(_, decl_id, _) = expr.kind {
+                if kinds.contains_key(&decl_id) && !supported_refs.contains(expr_id) {
+                    rejected.entry(decl_id).or_insert("it is used outside of pthreads calls");
+                }
+            }
+        }
+
+        let (locks, unlocks) = self.pair_mutex_calls(&mutex_calls);
+        for (call, &(mutex, _)) in &mutex_calls {
+            if !locks.contains_key(call) && !unlocks.contains_key(call) {
+                rejected.entry(mutex).or_insert("its locks and unlocks are not well-nested");
+            }
+        }
+
+        for (call, name, object) in calls {
+            if !TRANSLATED_FUNCTIONS.contains(&name) {
+                continue;
+            }
+            let reason = match object {
+                Some(decl_id) => match rejected.get(&decl_id) {
+                    Some(&reason) => reason,
+                    None => continue,
+                },
+                None => "its first argument is not a variable of a pthreads type",
+            };
+            let loc = self
+                .ast_context
+                .display_loc(&self.ast_context[call].loc)
+                .map_or_else(|| "<unknown location>".to_string(), |loc| loc.to_string());
+            warn!("{}: keeping the libc call to {}, since {}", loc, name, reason);
+        }
+
+        let mut objects = PthreadObjects::default();
+        for (decl_id, kind) in kinds {
+            if rejected.contains_key(&decl_id) {
+                continue;
+            }
+            match kind {
+                PthreadKind::Mutex => objects.mutexes.insert(decl_id),
+                PthreadKind::Once => objects.onces.insert(decl_id),
+                PthreadKind::Thread => objects.threads.insert(decl_id),
+            };
+        }
+        let mutexes = &objects.mutexes;
+        let locks = locks.into_iter().filter(|(_, m)| mutexes.contains(m)).collect();
+        let unlocks = unlocks.into_iter().filter(|(_, m)| mutexes.contains(m)).collect();
+        objects.locks = locks;
+        objects.unlocks = unlocks;
+        objects
+    }
+
+    /// Get the pthreads type of the variable `decl_id`, if it has one we can translate.
+    fn pthread_kind(&self, decl_id: CDeclId) -> Option<PthreadKind> {
+        let mut ty = match self.ast_context[decl_id].kind {
+            CDeclKind::Variable { typ, .. } => typ.ctype,
+            _ => return None,
+        };
+        loop {
+            ty = match self.ast_context[ty].kind {
+                CTypeKind::Typedef(typedef_id) => match self.ast_context[typedef_id].kind {
+                    CDeclKind::Typedef { ref name, typ, .. } => match name.as_str() {
+                        "pthread_mutex_t" => return Some(PthreadKind::Mutex),
+                        "pthread_once_t" => return Some(PthreadKind::Once),
+                        "pthread_t" => return Some(PthreadKind::Thread),
+                        _ => typ.ctype,
+                    },
+                    _ => return None,
+                },
+                CTypeKind::Elaborated(ty) | CTypeKind::Paren(ty) => ty,
+                _ => return None,
+            };
+        }
+    }
+
+    /// Check whether the declaration of a pthreads variable allows translating it, or get the
+    /// reason it doesn't.
+    fn pthread_variable_rejection(
+        &self,
+        decl_id: CDeclId,
+        kind: PthreadKind,
+    ) -> Option<&'static str> {
+        let (has_static_duration, initializer) = match self.ast_context[decl_id].kind {
+            CDeclKind::Variable { is_externally_visible: true, .. }
+            | CDeclKind::Variable { is_defn: false, .. } => {
+                return Some("it is shared with C code that is not translated");
+            }
+            CDeclKind::Variable { has_thread_duration: true, .. } => {
+                return Some("it is thread-local");
+            }
+            CDeclKind::Variable { has_static_duration, initializer, .. } => {
+                (has_static_duration, initializer)
+            }
+            _ => return Some("it is not a variable"),
+        };
+
+        match kind {
+            // Function-scoped statics are not visible to `run_static_initializers`
+            PthreadKind::Mutex if !self.ast_context.c_decls_top.contains(&decl_id) => {
+                Some("it is not a global variable")
+            }
+            PthreadKind::Once if !has_static_duration => Some("it is not a static variable"),
+            PthreadKind::Thread if has_static_duration => Some("it is not a local variable"),
+            PthreadKind::Thread if initializer.is_some() => {
+                Some("it is initialized with another thread")
+            }
+            _ => None,
+        }
+    }
+
+    /// Check whether a call to the pthreads function `name`, whose first argument is a variable
+    /// of type `kind` or its address, can be translated, or get the reason it can't.
+    fn pthread_call_support(
+        &self,
+        name: &str,
+        kind: PthreadKind,
+        by_address: bool,
+        args: &[CExprId],
+    ) -> Result<(), &'static str> {
+        match (kind, name) {
+            (PthreadKind::Mutex, "pthread_mutex_lock")
+            | (PthreadKind::Mutex, "pthread_mutex_unlock")
+            | (PthreadKind::Mutex, "pthread_mutex_destroy") if by_address => Ok(()),
+            (PthreadKind::Mutex, "pthread_mutex_init") if by_address => {
+                if args.len() == 2 && self.is_null_arg(args[1]) {
+                    Ok(())
+                } else {
+                    Err("it is initialized with attributes")
+                }
+            }
+            (PthreadKind::Mutex, "pthread_mutex_trylock")
+            | (PthreadKind::Mutex, "pthread_mutex_timedlock") => {
+                Err("it is locked without blocking or with a timeout")
+            }
+            (PthreadKind::Once, "pthread_once") if by_address => {
+                if args.len() == 2 && self.direct_fn_ref(args[1]).is_some() {
+                    Ok(())
+                } else {
+                    Err("its initialization function is not named directly")
+                }
+            }
+            (PthreadKind::Thread, "pthread_create") if by_address => {
+                if args.len() != 4 || !self.is_null_arg(args[1]) {
+                    Err("it is created with attributes")
+                } else if self.direct_fn_ref(args[2]).is_none() {
+                    Err("its start function is not named directly")
+                } else {
+                    Ok(())
+                }
+            }
+            (PthreadKind::Thread, "pthread_join") if !by_address && args.len() == 2 => Ok(()),
+            (_, name) if name.starts_with("pthread_cond_") => {
+                Err("it is used with a condition variable")
+            }
+            _ => Err("it is passed to a pthreads function that is not translated"),
+        }
+    }
+
+    /// Pair each call locking a mutex with the call unlocking it, if the two are expression
+    /// statements of the same block, and control flow can't leave or enter the statements in
+    /// between other than through their start and end. Returns the paired locks and unlocks,
+    /// with the mutex they lock and unlock.
+    fn pair_mutex_calls(
+        &self,
+        mutex_calls: &IndexMap<CExprId, (CDeclId, bool)>,
+    ) -> (IndexMap<CExprId, CDeclId>, IndexMap<CExprId, CDeclId>) {
+        let mut locks = IndexMap::new();
+        let mut unlocks = IndexMap::new();
+        if mutex_calls.is_empty() {
+            return (locks, unlocks);
+        }
+
+        let mutex_call = |stmt: CStmtId| {
+            let call = self.statement_call(stmt)?;
+            mutex_calls
+                .get(&call)
+                .map(|&(mutex, is_lock)| (call, mutex, is_lock))
+        };
+        for (_, stmt) in self.ast_context.iter_stmts() {
+            let stmts = match stmt.kind {
+                CStmtKind::Compound(ref stmts) => stmts,
+                _ => continue,
+            };
+            for (i, &first) in stmts.iter().enumerate() {
+                let (lock, mutex) = match mutex_call(first) {
+                    Some((call, mutex, true)) => (call, mutex),
+                    _ => continue,
+                };
+                let rest = &stmts[i + 1..];
+                let end = rest.iter().position(|&stmt| match mutex_call(stmt) {
+                    Some((_, other, false)) => other == mutex,
+                    _ => false,
+                });
+                let end = match end {
+                    Some(end) => end,
+                    None => continue,
+                };
+                let nested = rest[..end].iter().all(|&stmt| {
+                    !self.leaves_region(stmt, false, false)
+                        && !self.uses_mutex(stmt, mutex, mutex_calls)
+                });
+                if nested {
+                    locks.insert(lock, mutex);
+                    unlocks.insert(mutex_call(rest[end]).unwrap().0, mutex);
+                }
+            }
+        }
+        (locks, unlocks)
+    }
+
+    /// Get the call that the expression statement `stmt` consists of, if it does.
+    fn statement_call(&self, stmt: CStmtId) -> Option<CExprId> {
+        let mut expr = match self.ast_context[stmt].kind {
+            CStmtKind::Expr(expr) => expr,
+            _ => return None,
+        };
+        loop {
+            match self.ast_context[expr].kind {
+                CExprKind::Paren(_, e)
+                | CExprKind::ExplicitCast(_, e, CastKind::ToVoid, _, _)
+                | CExprKind::ImplicitCast(_, e, CastKind::ToVoid, _, _) => expr = e,
+                CExprKind::Call(..) => return Some(expr),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Check if control flow can leave `stmt` other than by reaching its end, or enter it other
+    /// than at its start: with a `return`, a `goto` or a label, or with a `break`, `continue` or
+    /// `case` of a loop or `switch` outside of it.
+    fn leaves_region(&self, stmt: CStmtId, in_loop: bool, in_switch: bool) -> bool {
+        match self.ast_context[stmt].kind {
+            CStmtKind::Return(_) | CStmtKind::Goto(_) | CStmtKind::Label(_) => true,
+            CStmtKind::Asm { ref labels, .. } => !labels.is_empty(),
+            CStmtKind::Break => !in_loop && !in_switch,
+            CStmtKind::Continue => !in_loop,
+            CStmtKind::Case(_, body, _) | CStmtKind::Default(body) => {
+                !in_switch || self.leaves_region(body, in_loop, in_switch)
+            }
+            CStmtKind::Compound(ref stmts) => stmts
+                .iter()
+                .any(|&stmt| self.leaves_region(stmt, in_loop, in_switch)),
+            CStmtKind::If { true_variant, false_variant, .. } => {
+                let leaves = |stmt| self.leaves_region(stmt, in_loop, in_switch);
+                leaves(true_variant) || false_variant.map_or(false, leaves)
+            }
+            CStmtKind::Switch { body, .. } => self.leaves_region(body, in_loop, true),
+            CStmtKind::While { body, .. } | CStmtKind::DoWhile { body, .. } => {
+                self.leaves_region(body, true, in_switch)
+            }
+            CStmtKind::ForLoop { init, body, .. } => {
+                init.map_or(false, |stmt| self.leaves_region(stmt, in_loop, in_switch))
+                    || self.leaves_region(body, true, in_switch)
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if `stmt` locks or unlocks `mutex`.
+    fn uses_mutex(
+        &self,
+        stmt: CStmtId,
+        mutex: CDeclId,
+        mutex_calls: &IndexMap<CExprId, (CDeclId, bool)>,
+    ) -> bool {
+        DFNodes::new(&self.ast_context, SomeId::Stmt(stmt)).any(|id| match id {
+            SomeId::Expr(expr) => mutex_calls.get(&expr).map_or(false, |&(m, _)| m == mutex),
+            _ => false,
+        })
+    }
+
+    /// Get the name of the pthreads function that `func` refers to, if it does.
+    fn pthread_callee(&self, func: CExprId) -> Option<&str> {
+        let fexp = match self.ast_context[func].kind {
+            CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) => fexp,
+            _ => return None,
+        };
+        match self.ast_context[fexp].kind {
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Function { ref name, body: None, .. }
+                    if name.starts_with("pthread_") =>
+                {
+                    Some(name)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// If the argument `arg` is `&x` or `x` for a variable `x`, get the reference to `x`, the
+    /// variable, and whether it is passed by address.
+    fn pthread_object(&self, arg: CExprId) -> Option<(CExprId, CDeclId, bool)> {
+        let (decl_ref, by_address) = match self.ast_context[arg].kind {
+            CExprKind::Unary(_, c_ast::UnOp::AddressOf, e, _) => (e, true),
+            CExprKind::ImplicitCast(_, e, CastKind::LValueToRValue, _, _) => (e, false),
+            _ => return None,
+        };
+        match self.ast_context[decl_ref].kind {
+            CExprKind::DeclRef(_, decl_id, _) => Some((decl_ref, decl_id, by_address)),
+            _ => None,
+        }
+    }
+
+    /// If the argument `arg` names a function without casting it, get the reference to the
+    /// function.
+    fn direct_fn_ref(&self, arg: CExprId) -> Option<CExprId> {
+        match self.ast_context[arg].kind {
+            CExprKind::ImplicitCast(_, fn_ref, CastKind::FunctionToPointerDecay, _, _) => {
+                match self.ast_context[fn_ref].kind {
+                    CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                        CDeclKind::Function { .. } => Some(fn_ref),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Check if the argument `arg` is `NULL`, including `((void *)0)`.
+    fn is_null_arg(&self, arg: CExprId) -> bool {
+        match self.ast_context[arg].kind {
+            CExprKind::Paren(_, e) | CExprKind::ImplicitCast(_, e, CastKind::BitCast, _, _) => {
+                self.is_null_arg(e)
+            }
+            _ => self.ast_context.is_null_expr(arg),
+        }
+    }
+
+    /// Build the type `name<()>` from the crate providing mutexes, e.g., `::std::sync::Mutex<()>`.
+    fn mutex_ty(&self, name: &str) -> P<Ty> {
+        let mut segments = self
+            .mutex_crate_path()
+            .into_iter()
+            .map(|seg| mk().path_segment(seg))
+            .collect::<Vec<_>>();
+        let unit = mk().tuple_ty(Vec::<P<Ty>>::new());
+        segments.push(mk().path_segment_with_args(name, mk().angle_bracketed_args(vec![unit])));
+        mk().path_ty(segments)
+    }
+
+    /// The path of the module providing mutexes.
+    fn mutex_crate_path(&self) -> Vec<&'static str> {
+        match self.tcfg.translate_pthreads {
+            Some(PthreadsBackend::ParkingLot) => vec!["", "parking_lot"],
+            _ => vec!["", "std", "sync"],
+        }
+    }
+
+    /// Convert the type and initializer of a global `pthread_mutex_t` or `pthread_once_t`, if
+    /// it is translated. The `Mutex` of a mutex is created by `run_static_initializers`.
+    pub fn convert_pthread_static(
+        &self,
+        decl_id: CDeclId,
+        name: &str,
+    ) -> Option<(P<Ty>, P<Expr>)> {
+        if self.pthreads.mutexes.contains(&decl_id) {
+            if self.tcfg.translate_pthreads == Some(PthreadsBackend::ParkingLot) {
+                self.use_crate(ExternCrate::ParkingLot);
+            }
+            let mut new = self.mutex_crate_path();
+            new.extend(&["Mutex", "new"]);
+            let unit = mk().tuple_expr(Vec::<P<Expr>>::new());
+            let mutex = mk().call_expr(mk().path_expr(new), vec![unit]);
+            let init = mk().assign_expr(mk().path_expr(vec![name]), some_expr(mutex));
+            self.sectioned_static_initializers
+                .borrow_mut()
+                .push(mk().expr_stmt(init));
+
+            Some((option_ty(self.mutex_ty("Mutex")), mk().path_expr(vec!["None"])))
+        } else if self.pthreads.onces.contains(&decl_id) {
+            let ty = mk().path_ty(vec!["", "std", "sync", "Once"]);
+            let new = mk().path_expr(vec!["", "std", "sync", "Once", "new"]);
+            Some((ty, mk().call_expr(new, Vec::<P<Expr>>::new())))
+        } else {
+            None
+        }
+    }
+
+    /// Declare a local `pthread_t` holding the `JoinHandle` of its thread.
+    pub fn convert_thread_handle_local(&self, name: &str) -> cfg::DeclStmtInfo {
+        let usize_ty = mk().path_ty(vec!["usize"]);
+        let handle_ty = mk().path_ty(vec![
+            mk().path_segment(""),
+            mk().path_segment("std"),
+            mk().path_segment("thread"),
+            mk().path_segment_with_args("JoinHandle", mk().angle_bracketed_args(vec![usize_ty])),
+        ]);
+        let local = mk().local(
+            mk().mutbl().ident_pat(name),
+            Some(option_ty(handle_ty)),
+            Some(mk().path_expr(vec!["None"])),
+        );
+        let decl = vec![mk().local_stmt(P(local))];
+        cfg::DeclStmtInfo::new(decl.clone(), vec![], decl)
+    }
+
+    /// Declare a lock guard for each translated mutex that the function with body `body` locks,
+    /// and remember their names for translating its locks and unlocks.
+    pub fn pthread_guard_locals(&self, body: CStmtId) -> Vec<Stmt> {
+        let mut guards = self.pthread_guards.borrow_mut();
+        guards.clear();
+        if self.pthreads.locks.is_empty() {
+            return vec![];
+        }
+
+        let mut stmts = vec![];
+        for id in DFNodes::new(&self.ast_context, SomeId::Stmt(body)) {
+            let mutex = match id {
+                SomeId::Expr(expr) => match self.pthreads.locks.get(&expr) {
+                    Some(&mutex) => mutex,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if guards.contains_key(&mutex) {
+                continue;
+            }
+            let mutex_name = self
+                .renamer
+                .borrow()
+                .get(&mutex)
+                .expect("Globals should already be renamed");
+            let name = self
+                .renamer
+                .borrow_mut()
+                .pick_name(&format!("{}_guard", mutex_name));
+            let local = mk().local(
+                mk().mutbl().ident_pat(&name),
+                Some(option_ty(self.mutex_ty("MutexGuard"))),
+                Some(mk().path_expr(vec!["None"])),
+            );
+            stmts.push(mk().local_stmt(P(local)));
+            guards.insert(mutex, name);
+        }
+        stmts
+    }
+
+    /// Convert a call to a pthreads function using a variable translated into a `std` type.
+    /// Returns `None` for any other call, which is translated as usual.
+    pub fn convert_pthread_call(
+        &self,
+        ctx: ExprContext,
+        call: CExprId,
+        func: CExprId,
+        args: &[CExprId],
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        if self.tcfg.translate_pthreads.is_none() {
+            return Ok(None);
+        }
+        let name = match self.pthread_callee(func) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let (decl_ref, decl_id) = match args.first().and_then(|&arg| self.pthread_object(arg)) {
+            Some((decl_ref, decl_id, _)) => (decl_ref, decl_id),
+            None => return Ok(None),
+        };
+
+        let stmts = match name {
+            "pthread_mutex_lock" | "pthread_mutex_unlock" => {
+                let (mutex, is_lock) = if let Some(&mutex) = self.pthreads.locks.get(&call) {
+                    (mutex, true)
+                } else if let Some(&mutex) = self.pthreads.unlocks.get(&call) {
+                    (mutex, false)
+                } else {
+                    return Ok(None);
+                };
+                let guard = self
+                    .pthread_guards
+                    .borrow()
+                    .get(&mutex)
+                    .cloned()
+                    .ok_or_else(|| format_err!("Mutex locked outside of a function"))?;
+
+                let val = if is_lock {
+                    let mutex = self.convert_expr(ctx.used(), decl_ref)?.to_expr();
+                    let mutex = call_method(call_method(mutex, "as_ref"), "unwrap");
+                    let lock = call_method(mutex, "lock");
+                    // Only `std` mutexes are poisoned by a panic while locked
+                    if self.tcfg.translate_pthreads == Some(PthreadsBackend::Std) {
+                        some_expr(call_method(lock, "unwrap"))
+                    } else {
+                        some_expr(lock)
+                    }
+                } else {
+                    mk().path_expr(vec!["None"])
+                };
+                vec![mk().semi_stmt(mk().assign_expr(mk().ident_expr(&guard), val))]
+            }
+
+            "pthread_mutex_init" | "pthread_mutex_destroy"
+                if self.pthreads.mutexes.contains(&decl_id) =>
+            {
+                vec![]
+            }
+
+            "pthread_once" if self.pthreads.onces.contains(&decl_id) => {
+                let once = self.convert_expr(ctx.used(), decl_ref)?.to_expr();
+                let init = self
+                    .direct_fn_ref(args[1])
+                    .ok_or_else(|| format_err!("pthread_once without a function"))?;
+                let init = self.convert_expr(ctx.used(), init)?.to_expr();
+                let closure = mk().closure_expr(
+                    CaptureBy::Ref,
+                    Movability::Movable,
+                    mk().fn_decl(vec![], FunctionRetTy::Default(DUMMY_SP)),
+                    mk().call_expr(init, Vec::<P<Expr>>::new()),
+                );
+                vec![mk().semi_stmt(mk().method_call_expr(once, "call_once", vec![closure]))]
+            }
+
+            "pthread_create" if self.pthreads.threads.contains(&decl_id) => {
+                let thread = self.convert_expr(ctx.used(), decl_ref)?.to_expr();
+                let start = self
+                    .direct_fn_ref(args[2])
+                    .ok_or_else(|| format_err!("pthread_create without a function"))?;
+                let start = self.convert_expr(ctx.used(), start)?.to_expr();
+                let arg_ty = self.ast_context[args[3]]
+                    .kind
+                    .get_type()
+                    .ok_or_else(|| format_err!("bad thread argument type"))?;
+                let arg_ty = self.convert_type(arg_ty)?;
+                let (mut stmts, arg) = self.convert_expr(ctx.used(), args[3])?.discard_unsafe();
+
+                let usize_ty = mk().path_ty(vec!["usize"]);
+                let arg_name = self.renamer.borrow_mut().pick_name("thread_arg");
+                let arg_local = mk().local(
+                    mk().ident_pat(&arg_name),
+                    None as Option<P<Ty>>,
+                    Some(mk().cast_expr(arg, usize_ty.clone())),
+                );
+                stmts.push(mk().local_stmt(P(arg_local)));
+
+                let arg = mk().cast_expr(mk().ident_expr(&arg_name), arg_ty);
+                let run = mk().cast_expr(mk().call_expr(start, vec![arg]), usize_ty);
+                let closure = mk().closure_expr(
+                    CaptureBy::Value,
+                    Movability::Movable,
+                    mk().fn_decl(vec![], FunctionRetTy::Default(DUMMY_SP)),
+                    run,
+                );
+                let spawn = mk().call_expr(
+                    mk().path_expr(vec!["", "std", "thread", "spawn"]),
+                    vec![closure],
+                );
+                stmts.push(mk().semi_stmt(mk().assign_expr(thread, some_expr(spawn))));
+                stmts
+            }
+
+            "pthread_join" if self.pthreads.threads.contains(&decl_id) => {
+                let thread = self.convert_expr(ctx.used(), decl_ref)?.to_expr();
+                let handle = call_method(call_method(thread, "take"), "unwrap");
+                let result = call_method(call_method(handle, "join"), "unwrap");
+                if self.is_null_arg(args[1]) {
+                    vec![mk().semi_stmt(result)]
+                } else {
+                    let ret_ty = self.ast_context[args[1]]
+                        .kind
+                        .get_type()
+                        .and_then(|ty| self.ast_context.get_pointee_qual_type(ty))
+                        .ok_or_else(|| format_err!("bad thread result type"))?;
+                    let ret_ty = self.convert_type(ret_ty.ctype)?;
+                    let (mut stmts, ret) = self.convert_expr(ctx.used(), args[1])?.discard_unsafe();
+                    let store = mk().assign_expr(
+                        mk().unary_expr(ast::UnOp::Deref, ret),
+                        mk().cast_expr(result, ret_ty),
+                    );
+                    stmts.push(mk().semi_stmt(store));
+                    stmts
+                }
+            }
+
+            _ => return Ok(None),
+        };
+
+        // The translated calls can't fail
+        Ok(Some(WithStmts::new(stmts, mk().lit_expr(mk().int_lit(0, "")))))
+    }
+}
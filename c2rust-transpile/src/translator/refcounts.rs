@@ -0,0 +1,305 @@
+//! Detection of hand-written reference counts, for `--detect-refcounts`.
+//!
+//! C code often counts the references to an object by hand: `obj->refs++` when a reference is
+//! taken, and `if (--obj->refs == 0) free(obj);` when one is dropped. With `--detect-refcounts`,
+//! struct fields that are used for nothing but this are marked with `#[refcount_field]`, which
+//! the `refcount_to_rc` refactoring command looks for, and their increments, decrements and frees
+//! are listed in a JSON report written next to the output file.
+
+use super::*;
+use crate::c_ast::iterators::DFNodes;
+use std::collections::HashSet;
+
+/// The uses of a struct field that is a reference count
+#[derive(Debug, Default)]
+pub struct RefcountField {
+    /// Increments of the field, with the names of the functions they are in
+    pub increments: Vec<(String, CExprId)>,
+    /// Decrements of the field
+    pub decrements: Vec<(String, CExprId)>,
+    /// Calls freeing the containing object once the field reaches zero
+    pub frees: Vec<(String, CExprId)>,
+}
+
+#[derive(Serialize)]
+struct RefcountReport {
+    #[serde(rename = "struct")]
+    struct_name: String,
+    field: String,
+    increments: Vec<RefcountSite>,
+    decrements: Vec<RefcountSite>,
+    frees: Vec<RefcountSite>,
+}
+
+#[derive(Serialize)]
+struct RefcountSite {
+    function: String,
+    file: Option<String>,
+    line: u64,
+    column: u64,
+}
+
+impl<'c> Translation<'c> {
+    /// Find the struct fields that are only incremented, decremented, set to a constant, and
+    /// compared against zero to guard freeing the object that contains them.
+    pub fn find_refcount_fields(&self) -> IndexMap<CFieldId, RefcountField> {
+        let mut fields: IndexMap<CFieldId, RefcountField> = IndexMap::new();
+        let mut members: IndexMap<CFieldId, Vec<CExprId>> = IndexMap::new();
+        // Field accesses that are one of the uses above
+        let mut counted = HashSet::new();
+
+        for (_, decl) in self.ast_context.iter_decls() {
+            let (fn_name, body) = match decl.kind {
+                CDeclKind::Function { ref name, body: Some(body), .. } => (name, body),
+                _ => continue,
+            };
+            let site = |id| (fn_name.clone(), id);
+
+            for id in DFNodes::new(&self.ast_context, SomeId::Stmt(body)) {
+                match id {
+                    SomeId::Stmt(stmt_id) => {
+                        if let CStmtKind::If { scrutinee, true_variant, .. } =
+                            self.ast_context[stmt_id].kind
+                        {
+                            if let Some((member, field, var)) = self.refcount_zero_check(scrutinee) {
+                                if let Some(free) = self.find_free(true_variant, var) {
+                                    counted.insert(member);
+                                    let uses = fields.entry(field).or_insert_with(Default::default);
+                                    uses.frees.push(site(free));
+                                }
+                            }
+                        }
+                    }
+                    SomeId::Expr(expr_id) => match self.ast_context[expr_id].kind {
+                        CExprKind::Member(_, _, field, _, _) => {
+                            members.entry(field).or_insert_with(Vec::new).push(expr_id);
+                        }
+                        CExprKind::Unary(_, op, arg, _) => {
+                            let is_increment = match op {
+                                c_ast::UnOp::PreIncrement | c_ast::UnOp::PostIncrement => true,
+                                c_ast::UnOp::PreDecrement | c_ast::UnOp::PostDecrement => false,
+                                _ => continue,
+                            };
+                            if let Some((member, field)) = self.refcount_member(arg) {
+                                counted.insert(member);
+                                let uses = fields.entry(field).or_insert_with(Default::default);
+                                if is_increment {
+                                    uses.increments.push(site(expr_id));
+                                } else {
+                                    uses.decrements.push(site(expr_id));
+                                }
+                            }
+                        }
+                        CExprKind::Binary(_, op, lhs, rhs, _, _) => {
+                            let (member, field) = match self.refcount_member(lhs) {
+                                Some(member) => member,
+                                None => continue,
+                            };
+                            let uses = fields.entry(field).or_insert_with(Default::default);
+                            match (op, self.int_literal(rhs)) {
+                                (c_ast::BinOp::AssignAdd, Some(1)) => {
+                                    uses.increments.push(site(expr_id))
+                                }
+                                (c_ast::BinOp::AssignSubtract, Some(1)) => {
+                                    uses.decrements.push(site(expr_id))
+                                }
+                                // Initialization
+                                (c_ast::BinOp::Assign, Some(_)) => {}
+                                _ => continue,
+                            }
+                            counted.insert(member);
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        fields.retain(|field, uses| {
+            let is_integral = match self.ast_context[*field].kind {
+                CDeclKind::Field { typ, .. } => {
+                    self.ast_context.resolve_type(typ.ctype).kind.is_integral_type()
+                }
+                _ => false,
+            };
+            let is_struct_field = match self.ast_context.parents.get(field) {
+                Some(&record_id) => match self.ast_context[record_id].kind {
+                    CDeclKind::Struct { .. } => true,
+                    _ => false,
+                },
+                None => false,
+            };
+            let only_counted = members
+                .get(field)
+                .map_or(true, |members| members.iter().all(|member| counted.contains(member)));
+
+            is_integral
+                && is_struct_field
+                && only_counted
+                && !uses.increments.is_empty()
+                && !uses.decrements.is_empty()
+                && !uses.frees.is_empty()
+        });
+        fields
+    }
+
+    /// Get the `p->field` access `expr` is, looking through parentheses and casts, along with
+    /// its field.
+    fn refcount_member(&self, expr: CExprId) -> Option<(CExprId, CFieldId)> {
+        match self.ast_context[expr].kind {
+            CExprKind::ImplicitCast(_, e, _, _, _) | CExprKind::Paren(_, e) => {
+                self.refcount_member(e)
+            }
+            CExprKind::Member(_, _, field, MemberKind::Arrow, _) => Some((expr, field)),
+            _ => None,
+        }
+    }
+
+    /// Match a check that a reference count is zero, possibly after decrementing it:
+    /// `p->refs == 0`, `--p->refs == 0` or `!--p->refs`. Returns the field access, the field
+    /// and the variable `p`.
+    fn refcount_zero_check(&self, cond: CExprId) -> Option<(CExprId, CFieldId, CDeclId)> {
+        let counted = match *self.ast_context.resolve_expr_value(cond) {
+            CExprKind::Binary(_, c_ast::BinOp::EqualEqual, lhs, rhs, _, _) => {
+                if self.int_literal(rhs) == Some(0) {
+                    lhs
+                } else if self.int_literal(lhs) == Some(0) {
+                    rhs
+                } else {
+                    return None;
+                }
+            }
+            CExprKind::Unary(_, c_ast::UnOp::Not, arg, _) => arg,
+            _ => return None,
+        };
+        let counted = match *self.ast_context.resolve_expr_value(counted) {
+            CExprKind::Unary(_, c_ast::UnOp::PreDecrement, arg, _) => arg,
+            _ => counted,
+        };
+
+        let (member, field) = self.refcount_member(counted)?;
+        let var = match self.ast_context[member].kind {
+            CExprKind::Member(_, base, ..) => self.referenced_var(base)?,
+            _ => return None,
+        };
+        Some((member, field, var))
+    }
+
+    /// Find a call in `stmt` that frees the object `var` points to: either `free(var)` or a
+    /// call passing `var` to a function that frees that parameter.
+    fn find_free(&self, stmt: CStmtId, var: CDeclId) -> Option<CExprId> {
+        DFNodes::new(&self.ast_context, SomeId::Stmt(stmt))
+            .filter_map(|id| match id {
+                SomeId::Expr(expr_id) => Some(expr_id),
+                _ => None,
+            })
+            .find(|&expr_id| match self.ast_context[expr_id].kind {
+                CExprKind::Call(_, callee, ref args) => {
+                    let fn_id = match self.referenced_function(callee) {
+                        Some(fn_id) => fn_id,
+                        None => return false,
+                    };
+                    args.iter().enumerate().any(|(i, &arg)| {
+                        self.referenced_var(arg) == Some(var) && self.frees_param(fn_id, i, true)
+                    })
+                }
+                _ => false,
+            })
+    }
+
+    /// Check whether the function `fn_id` frees its `i`th parameter, either by being `free`
+    /// or, if `look_inside` is set, by calling `free` on it.
+    fn frees_param(&self, fn_id: CDeclId, i: usize, look_inside: bool) -> bool {
+        let (name, parameters, body) = match self.ast_context[fn_id].kind {
+            CDeclKind::Function { ref name, ref parameters, body, .. } => (name, parameters, body),
+            _ => return false,
+        };
+        if name == "free" {
+            return i == 0;
+        }
+        let (param, body) = match (parameters.get(i), body) {
+            (Some(&param), Some(body)) if look_inside => (param, body),
+            _ => return false,
+        };
+
+        DFNodes::new(&self.ast_context, SomeId::Stmt(body)).any(|id| match id {
+            SomeId::Expr(expr_id) => match self.ast_context[expr_id].kind {
+                CExprKind::Call(_, callee, ref args) if args.len() == 1 => {
+                    self.referenced_var(args[0]) == Some(param)
+                        && self
+                            .referenced_function(callee)
+                            .map_or(false, |callee| self.frees_param(callee, 0, false))
+                }
+                _ => false,
+            },
+            _ => false,
+        })
+    }
+
+    /// Get the variable `expr` refers to, looking through parentheses and casts.
+    fn referenced_var(&self, expr: CExprId) -> Option<CDeclId> {
+        match *self.ast_context.resolve_expr_value(expr) {
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Variable { .. } => Some(decl_id),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn int_literal(&self, expr: CExprId) -> Option<u64> {
+        match *self.ast_context.resolve_expr_value(expr) {
+            CExprKind::Literal(_, CLiteral::Integer(value, _)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Build the JSON report of the reference counts `find_refcount_fields` found.
+    pub fn refcount_report(&self) -> String {
+        let type_converter = self.type_converter.borrow();
+        let report = self
+            .refcount_fields
+            .iter()
+            .map(|(&field, uses)| {
+                let record_id = self.ast_context.parents[&field];
+                let sites = |sites: &[(String, CExprId)]| {
+                    sites
+                        .iter()
+                        .map(|&(ref function, expr_id)| self.refcount_site(function, expr_id))
+                        .collect()
+                };
+                RefcountReport {
+                    struct_name: type_converter
+                        .resolve_decl_name(record_id)
+                        .unwrap_or_default(),
+                    field: type_converter
+                        .resolve_field_name(Some(record_id), field)
+                        .unwrap_or_default(),
+                    increments: sites(&uses.increments),
+                    decrements: sites(&uses.decrements),
+                    frees: sites(&uses.frees),
+                }
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_string_pretty(&report).expect("Failed to serialize refcount report")
+    }
+
+    fn refcount_site(&self, function: &str, expr_id: CExprId) -> RefcountSite {
+        let expr = &self.ast_context[expr_id];
+        let file = self
+            .ast_context
+            .file_id(expr)
+            .and_then(|file_id| self.ast_context.get_file_path(file_id))
+            .map(|path| path.display().to_string());
+        let (line, column) = expr
+            .loc
+            .map_or((0, 0), |loc| (loc.begin_line, loc.begin_column));
+        RefcountSite {
+            function: function.to_string(),
+            file,
+            line,
+            column,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Splitting the definitions of a large output file across part files, see
+//! `TranspilerConfig::split_output_size`.
+//!
+//! The definitions are moved, in order, into modules declared at the end of the file, which
+//! are written to `<file>/part_<n>.rs`:
+//!
+//! ```ignore
+//! #[path = "big/part_1.rs"]
+//! mod part_1;
+//! pub use self::part_1::*;
+//! ```
+//!
+//! Each part starts with `use super::*;`, and the file reexports the definitions of each part
+//! (with a private `use` if none of them is `pub`), so that the paths of the file, its parts
+//! and other modules keep referring to the same definitions. Private definitions become
+//! `pub(super)` so that the file and the other parts can still see them. Imports, `extern`
+//! blocks, header modules and macros stay in the file.
+
+use super::*;
+use c2rust_ast_builder::Make;
+
+/// A module holding some of the definitions of an output file.
+pub struct OutputPart {
+    pub name: String,
+    pub items: Vec<P<Item>>,
+}
+
+impl OutputPart {
+    /// Path of the file of the part, relative to the directory of the output file `stem.rs`.
+    pub fn file_name(&self, stem: &str) -> String {
+        format!("{}/{}.rs", stem, self.name)
+    }
+}
+
+/// Whether `item` can move to a part without changing what the names of the file refer to.
+fn is_movable(item: &Item) -> bool {
+    match item.kind {
+        ItemKind::Use(..)
+        | ItemKind::ExternCrate(..)
+        | ItemKind::Mod(..)
+        | ItemKind::ForeignMod(..)
+        | ItemKind::GlobalAsm(..)
+        | ItemKind::Mac(..)
+        | ItemKind::MacroDef(..) => false,
+        // The entry point of a binary has to stay in the crate root
+        ItemKind::Fn(..) => &*item.ident.as_str() != "main",
+        _ => true,
+    }
+}
+
+fn widen_visibility(vis: &mut Visibility) {
+    if let VisibilityKind::Inherited = vis.node {
+        *vis = "pub(super)".make(&mk());
+    }
+}
+
+/// Make the private definitions of `item`, and its private fields and inherent methods,
+/// visible to the module of the file.
+fn widen_item_visibility(item: &mut Item) {
+    match item.kind {
+        ItemKind::Impl(_, _, _, _, ref trait_ref, _, ref mut impl_items) => {
+            if trait_ref.is_none() {
+                for impl_item in impl_items {
+                    widen_visibility(&mut impl_item.vis);
+                }
+            }
+            return;
+        }
+        ItemKind::Struct(VariantData::Struct(ref mut fields, _), _)
+        | ItemKind::Struct(VariantData::Tuple(ref mut fields, _), _)
+        | ItemKind::Union(VariantData::Struct(ref mut fields, _), _) => {
+            for field in fields {
+                widen_visibility(&mut field.vis);
+            }
+        }
+        _ => {}
+    }
+    widen_visibility(&mut item.vis);
+}
+
+/// Move the definitions of `items` into parts of at most `max_size` bytes of pretty-printed
+/// Rust each, if they take more than that, and declare the parts at the end of the file
+/// `stem.rs`. Definitions larger than `max_size` get a part of their own.
+pub fn split_items(
+    items: Vec<P<Item>>,
+    max_size: usize,
+    stem: &str,
+) -> (Vec<P<Item>>, Vec<OutputPart>) {
+    let sizes = items
+        .iter()
+        .map(|item| pprust::item_to_string(item).len())
+        .collect::<Vec<_>>();
+    if sizes.iter().sum::<usize>() <= max_size {
+        return (items, vec![]);
+    }
+
+    let taken = items
+        .iter()
+        .map(|item| item.ident.to_string())
+        .collect::<HashSet<_>>();
+    let mut kept = vec![];
+    let mut parts: Vec<OutputPart> = vec![];
+    let mut part_size = 0;
+    for (mut item, size) in items.into_iter().zip(sizes) {
+        if !is_movable(&item) {
+            kept.push(item);
+            continue;
+        }
+
+        let full = parts.last().map_or(true, |part| {
+            !part.items.is_empty() && part_size + size > max_size
+        });
+        if full {
+            let mut name = format!("part_{}", parts.len() + 1);
+            while taken.contains(&name) {
+                name.push('_');
+            }
+            parts.push(OutputPart {
+                name,
+                items: vec![],
+            });
+            part_size = 0;
+        }
+
+        widen_item_visibility(&mut item);
+        part_size += size;
+        parts.last_mut().unwrap().items.push(item);
+    }
+
+    for part in &parts {
+        let mut decl = mk().mod_(Vec::<P<Item>>::new());
+        decl.inline = false;
+        kept.push(
+            mk().str_attr("path", part.file_name(stem))
+                .mod_item(&part.name, decl),
+        );
+        // A `pub` glob has to reexport something `pub`
+        let public = part.items.iter().any(|i| match i.vis.node {
+            VisibilityKind::Public => true,
+            _ => false,
+        });
+        let reexport = if public { mk().pub_() } else { mk() };
+        kept.push(reexport.use_glob_item(vec!["self", &part.name]));
+    }
+    (kept, parts)
+}
+
+/// The items of the file of `part`, importing everything the file defines or imports.
+pub fn part_items(part: OutputPart) -> Vec<P<Item>> {
+    let mut items = vec![mk().use_glob_item(vec!["super"])];
+    items.extend(part.items);
+    items
+}
@@ -0,0 +1,393 @@
+//! Statements moved into C shim functions, for `--shim-untranslatable-stmts`.
+//!
+//! When an expression statement or an inline assembly block can't be translated, we copy its C
+//! source into a function of its own in `c2rust_shims.c` and call that function instead, so that
+//! the rest of the enclosing function still translates. The local variables the statement uses
+//! are passed by pointer, and copied into locals of the same names around the statement:
+//!
+//! ```c
+//! void c2rust_shim_file_add_0(int *c2rust_total, int *c2rust_y) {
+//!     int total = *c2rust_total;
+//!     int y = *c2rust_y;
+//!     __asm__("addl %1, %0" : "+r"(total) : "r"(y));
+//!     *c2rust_total = total;
+//!     *c2rust_y = y;
+//! }
+//! ```
+//!
+//! The shim file includes the headers that the translated file includes, so a statement can only
+//! be moved into a shim when the functions, globals and types it uses are declared in those
+//! headers. Shims are named after the output file, the function and their position in it, which
+//! keeps the shim file the same from one run to the next.
+
+use super::*;
+use crate::c_ast::iterators::DFNodes;
+use std::fs;
+
+/// A C function holding a statement that couldn't be translated
+pub struct StmtShim {
+    /// Name of the C function
+    name: String,
+    /// Name of the C function the statement was taken from
+    function: String,
+    /// C definition of the shim
+    source: String,
+}
+
+/// A local variable used by a statement moved into a shim
+struct ShimLocal {
+    decl_id: CDeclId,
+    /// A reference to the variable in the statement
+    expr_id: CExprId,
+    ident: String,
+    typ: CQualTypeId,
+}
+
+impl<'c> Translation<'c> {
+    /// Translate a statement that failed to translate into a call to a C shim holding it, with
+    /// `--shim-untranslatable-stmts`, if it can be moved into one.
+    pub fn convert_shim_stmt(&self, ctx: ExprContext, stmt_id: CStmtId) -> Option<Vec<Stmt>> {
+        if !self.tcfg.shim_untranslatable_stmts {
+            return None;
+        }
+
+        let locals = self.shim_locals(stmt_id)?;
+        let mut args = vec![];
+        for local in &locals {
+            let val = self
+                .convert_expr(ctx.used(), local.expr_id)
+                .ok()?
+                .to_pure_expr()?;
+            if local.typ.qualifiers.is_const {
+                args.push(mk().addr_of_expr(val));
+            } else {
+                args.push(mk().mutbl().addr_of_expr(val));
+            }
+        }
+
+        let existing = self
+            .stmt_shims
+            .borrow()
+            .get(&stmt_id)
+            .map(|shim| shim.name.clone());
+        let name = match existing {
+            Some(name) => name,
+            None => self.add_stmt_shim(stmt_id, &locals)?,
+        };
+        let call = mk().call_expr(mk().path_expr(vec![name]), args);
+        Some(vec![mk().semi_stmt(call)])
+    }
+
+    /// Get the C source of the shims of the translated file, to be written to
+    /// `c2rust_shims.c`, if it has any.
+    pub fn stmt_shims_source(&self) -> Option<String> {
+        let shims = self.stmt_shims.borrow();
+        if shims.is_empty() {
+            return None;
+        }
+
+        let main_path = self.ast_context.get_file_path(self.main_file);
+        let main_name = main_path
+            .and_then(|path| path.file_name())
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        let main_dir = main_path.and_then(|path| path.parent());
+
+        let mut source = format!("/* Statements of {} */\n", main_name);
+        for file in self.ast_context.get_main_file_includes() {
+            let path = match self.ast_context.get_file_path(file) {
+                Some(path) => path,
+                None => continue,
+            };
+            let path = match main_dir {
+                Some(dir) if path.is_relative() => dir.join(path),
+                _ => path.to_path_buf(),
+            };
+            // Skips clang's `<built-in>` and `<command line>` pseudo-files
+            if path.is_file() {
+                source.push_str(&format!("#include \"{}\"\n", path.display()));
+            }
+        }
+        for shim in shims.values() {
+            source.push('\n');
+            source.push_str(&shim.source);
+        }
+        Some(source)
+    }
+
+    /// Generate the shim of a statement and declare it in the current module, returning its
+    /// name.
+    fn add_stmt_shim(&self, stmt_id: CStmtId, locals: &[ShimLocal]) -> Option<String> {
+        let stmt_source = self.stmt_source(stmt_id)?;
+
+        let mut params = vec![];
+        let mut c_params = vec![];
+        let mut copy_in = String::new();
+        let mut copy_out = String::new();
+        for local in locals {
+            let ptr_name = format!("c2rust_{}", local.ident);
+            c_params.push(self.shim_c_decl(local.typ, format!("*{}", ptr_name))?);
+            let local_decl = self.shim_c_decl(local.typ, local.ident.clone())?;
+            copy_in.push_str(&format!("    {} = *{};\n", local_decl, ptr_name));
+
+            let ty = self.convert_type(local.typ.ctype).ok()?;
+            let ptr_ty = if local.typ.qualifiers.is_const {
+                mk().ptr_ty(ty)
+            } else {
+                copy_out.push_str(&format!("    *{} = {};\n", ptr_name, local.ident));
+                mk().mutbl().ptr_ty(ty)
+            };
+            params.push(mk().arg(ptr_ty, mk().ident_pat(&ptr_name)));
+        }
+        let c_params = if c_params.is_empty() {
+            "void".to_string()
+        } else {
+            c_params.join(", ")
+        };
+
+        let function = self.function_context.borrow().get_name().to_string();
+        let index = self
+            .stmt_shims
+            .borrow()
+            .values()
+            .filter(|shim| shim.function == function)
+            .count();
+        let stem: String = self
+            .data_file_stem
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let name = format!("c2rust_shim_{}_{}_{}", stem, function, index);
+
+        let source = format!(
+            "void {}({}) {{\n{}    {}\n{}}}\n",
+            name, c_params, copy_in, stmt_source, copy_out,
+        );
+
+        // Shims are declared in the module of the function using them, which needs them to be
+        // public to be accessible when definitions are reorganized
+        let visibility = if self.tcfg.reorganize_definitions {
+            "pub"
+        } else {
+            ""
+        };
+        let decl = mk().fn_decl(params, FunctionRetTy::Default(DUMMY_SP));
+        let item = mk().vis(visibility).fn_foreign_item(&name, decl);
+        self.items
+            .borrow_mut()
+            .entry(self.cur_file())
+            .or_insert_with(ItemStore::new)
+            .add_foreign_item(item);
+
+        self.stmt_shims.borrow_mut().insert(
+            stmt_id,
+            StmtShim {
+                name: name.clone(),
+                function,
+                source,
+            },
+        );
+        Some(name)
+    }
+
+    /// Get the local variables used by a statement, in the order they are first used, if the
+    /// statement can be moved into a shim.
+    fn shim_locals(&self, stmt_id: CStmtId) -> Option<Vec<ShimLocal>> {
+        let mut locals: Vec<ShimLocal> = vec![];
+        for id in DFNodes::new(&self.ast_context, SomeId::Stmt(stmt_id)) {
+            let expr_id = match id {
+                SomeId::Expr(expr_id) => expr_id,
+                SomeId::Stmt(id) if id == stmt_id => continue,
+                _ => return None,
+            };
+            match self.ast_context[expr_id].kind {
+                // Statement expressions and blocks have control flow and declarations of their
+                // own, `__func__` would name the shim, and generic selections and
+                // `__builtin_types_compatible_p` spell out types we don't check
+                CExprKind::Statements(..)
+                | CExprKind::Block { .. }
+                | CExprKind::Predefined(..)
+                | CExprKind::GenericSelection(..)
+                | CExprKind::TypesCompatible(..) => return None,
+
+                CExprKind::ExplicitCast(ty, ..)
+                | CExprKind::CompoundLiteral(ty, _)
+                | CExprKind::UnaryType(_, _, None, ty, _) => {
+                    self.shim_c_decl(ty, String::new())?;
+                }
+
+                CExprKind::DeclRef(_, decl_id, _) => {
+                    let decl = &self.ast_context[decl_id];
+                    match decl.kind {
+                        CDeclKind::Variable {
+                            has_static_duration: false,
+                            has_thread_duration: false,
+                            ref ident,
+                            typ,
+                            ..
+                        } => {
+                            if self.byref_vars.contains(&decl_id) {
+                                return None;
+                            }
+                            if locals.iter().any(|local| local.decl_id == decl_id) {
+                                continue;
+                            }
+                            self.shim_c_decl(typ, String::new())?;
+                            locals.push(ShimLocal {
+                                decl_id,
+                                expr_id,
+                                ident: ident.clone(),
+                                typ,
+                            });
+                        }
+                        // `static` locals of functions in headers aren't visible either
+                        CDeclKind::Variable { .. }
+                            if !self.ast_context.c_decls_top.contains(&decl_id) =>
+                        {
+                            return None
+                        }
+                        _ if self.is_shim_visible(decl_id) => {}
+                        _ => return None,
+                    }
+                }
+
+                _ => {}
+            }
+        }
+        Some(locals)
+    }
+
+    /// Check if the shim file can see a declaration, which it can unless the declaration is in
+    /// the translated file itself rather than in a header.
+    fn is_shim_visible(&self, decl_id: CDeclId) -> bool {
+        self.ast_context.file_id(&self.ast_context[decl_id]) != Some(self.main_file)
+    }
+
+    /// Spell out the C declaration of `declarator` with the type `qtype`, if the shim file can
+    /// name the type: builtin types, pointers to them, and types declared in headers.
+    fn shim_c_decl(&self, qtype: CQualTypeId, declarator: String) -> Option<String> {
+        let mut quals = String::new();
+        if qtype.qualifiers.is_const {
+            quals.push_str("const ");
+        }
+        if qtype.qualifiers.is_volatile {
+            quals.push_str("volatile ");
+        }
+        if qtype.qualifiers.is_restrict {
+            quals.push_str("restrict ");
+        }
+
+        let name = match self.ast_context[qtype.ctype].kind {
+            CTypeKind::Pointer(pointee) => {
+                return self.shim_c_decl(pointee, format!("*{}{}", quals, declarator));
+            }
+            CTypeKind::Elaborated(ty) | CTypeKind::Paren(ty) => {
+                return self.shim_c_decl(CQualTypeId { ctype: ty, ..qtype }, declarator);
+            }
+            CTypeKind::Attributed(ty, _) => {
+                return self.shim_c_decl(
+                    CQualTypeId {
+                        ctype: ty.ctype,
+                        ..qtype
+                    },
+                    declarator,
+                );
+            }
+
+            CTypeKind::Void => "void",
+            CTypeKind::Bool => "_Bool",
+            CTypeKind::Char => "char",
+            CTypeKind::SChar => "signed char",
+            CTypeKind::Short => "short",
+            CTypeKind::Int => "int",
+            CTypeKind::Long => "long",
+            CTypeKind::LongLong => "long long",
+            CTypeKind::UChar => "unsigned char",
+            CTypeKind::UShort => "unsigned short",
+            CTypeKind::UInt => "unsigned int",
+            CTypeKind::ULong => "unsigned long",
+            CTypeKind::ULongLong => "unsigned long long",
+            CTypeKind::Float => "float",
+            CTypeKind::Double => "double",
+            CTypeKind::LongDouble => "long double",
+            CTypeKind::Int128 => "__int128",
+            CTypeKind::UInt128 => "unsigned __int128",
+
+            CTypeKind::Typedef(decl_id) => match self.ast_context[decl_id].kind {
+                CDeclKind::Typedef {
+                    ref name,
+                    is_implicit: false,
+                    ..
+                } if self.is_shim_visible(decl_id) && !self.ast_context.is_va_list(qtype.ctype) => {
+                    name.as_str()
+                }
+                _ => return None,
+            },
+            CTypeKind::Struct(decl_id) | CTypeKind::Union(decl_id) | CTypeKind::Enum(decl_id) => {
+                let (keyword, name) = match self.ast_context[decl_id].kind {
+                    CDeclKind::Struct { ref name, .. } => ("struct", name),
+                    CDeclKind::Union { ref name, .. } => ("union", name),
+                    CDeclKind::Enum { ref name, .. } => ("enum", name),
+                    _ => return None,
+                };
+                match name {
+                    Some(name) if !name.is_empty() && self.is_shim_visible(decl_id) => {
+                        return Some(
+                            format!("{}{} {} {}", quals, keyword, name, declarator)
+                                .trim_end()
+                                .to_string(),
+                        );
+                    }
+                    _ => return None,
+                }
+            }
+
+            _ => return None,
+        };
+        Some(
+            format!("{}{} {}", quals, name, declarator)
+                .trim_end()
+                .to_string(),
+        )
+    }
+
+    /// Get the C source of a statement, up to and including its `;`.
+    fn stmt_source(&self, stmt_id: CStmtId) -> Option<String> {
+        let stmt = &self.ast_context[stmt_id];
+        let span = stmt.loc?;
+        let path = self
+            .ast_context
+            .get_file_path(self.ast_context.file_id(stmt)?)?;
+        let source = fs::read_to_string(path).ok()?;
+
+        // Lines and columns count from 1, and columns count bytes
+        let offset = |line: u64, column: u64| -> Option<usize> {
+            let line_start = match line.checked_sub(2) {
+                Some(newlines) => source.match_indices('\n').nth(newlines as usize)?.0 + 1,
+                None => 0,
+            };
+            Some(line_start + column.checked_sub(1)? as usize)
+        };
+        let begin = offset(span.begin_line, span.begin_column)?;
+        let mut end = offset(span.end_line, span.end_column)?;
+
+        // The span ends at the start of the last token. Of the tokens that can end a statement,
+        // only a string literal could contain a `;`.
+        let mut last_token = source.get(end..)?.char_indices();
+        if let Some((_, '"')) = last_token.next() {
+            let mut escaped = false;
+            for (i, c) in last_token {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    end += i + 1;
+                    break;
+                }
+            }
+        }
+        end += source.get(end..)?.find(';')? + 1;
+
+        source.get(begin..end).map(str::to_string)
+    }
+}
@@ -0,0 +1,149 @@
+//! Translation of string literals passed to functions as a writable `char *`.
+//!
+//! C string literals have type `char[N]`, so C code can pass them to functions taking a `char *`
+//! even though writing to them is undefined behavior. A literal passed as a `char *` to an
+//! extern function, whose body the translator can't see, might be written to, so it is
+//! translated to a pointer into a `static mut` array of its own rather than into a read-only
+//! byte string. A literal passed to a function defined in the same file keeps pointing to the
+//! byte string, and the cast that discards its constness is marked with a
+//! `// c2rust: literal passed as mutable` comment at the argument.
+//!
+//! With `--string-literals shared`, the default, identical literals passed to extern functions
+//! from the same module share one array, like clang merges identical literals, so comparing
+//! their addresses gives the same result as the C code compiled with clang. With
+//! `--string-literals unique-per-use`, each use gets an array of its own.
+
+use super::*;
+
+/// Whether identical string literals that are translated to arrays share one array.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StringLiterals {
+    Shared,
+    UniquePerUse,
+}
+
+impl<'c> Translation<'c> {
+    /// Convert the arguments of a call to the function `callee`, if it is known.
+    pub fn convert_call_args(
+        &self,
+        ctx: ExprContext,
+        callee: Option<CDeclId>,
+        args: &[CExprId],
+    ) -> Result<WithStmts<Vec<P<Expr>>>, TranslationError> {
+        args.iter()
+            .enumerate()
+            .map(|(index, &arg)| self.convert_call_arg(ctx, callee, index, arg))
+            .collect()
+    }
+
+    /// Convert the argument at `index` of a call to the function `callee`, if it is known,
+    /// translating a string literal passed as a `char *` as described above.
+    pub fn convert_call_arg(
+        &self,
+        ctx: ExprContext,
+        callee: Option<CDeclId>,
+        index: usize,
+        arg: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let (ptr_ty, lit, bytes, has_body) =
+            match callee.and_then(|callee| self.mutable_literal_arg(callee, index, arg)) {
+                Some(literal_arg) => literal_arg,
+                None => return self.convert_expr(ctx, arg),
+            };
+
+        if has_body {
+            let mut bytes = bytes.to_owned();
+            bytes.push(0);
+            let byte_literal = mk().lit_expr(mk().bytestr_lit(bytes));
+            let val = mk().cast_expr(byte_literal, mk().ptr_ty(mk().path_ty(vec!["u8"])));
+            let span = self
+                .comment_store
+                .borrow_mut()
+                .add_comments(&["// c2rust: literal passed as mutable".to_owned()])
+                .map(pos_to_span)
+                .unwrap_or(DUMMY_SP);
+            let val = mk().span(span).cast_expr(val, self.convert_type(ptr_ty)?);
+            Ok(WithStmts::new_val(val))
+        } else {
+            let name = self.literal_static(ctx, lit, bytes)?;
+            let array = mk().path_expr(vec![name]);
+            let ptr = mk().method_call_expr(array, "as_mut_ptr", vec![] as Vec<P<Expr>>);
+            Ok(WithStmts::new_unsafe_val(ptr))
+        }
+    }
+
+    /// If `arg` is a string literal passed to a `char *` parameter at `index` of the function
+    /// `callee`, get the pointer type it decays to, the literal, its bytes, and whether
+    /// `callee` is defined in this file.
+    fn mutable_literal_arg(
+        &self,
+        callee: CDeclId,
+        index: usize,
+        arg: CExprId,
+    ) -> Option<(CTypeId, CExprId, &[u8], bool)> {
+        let (ptr_ty, lit) = match self.ast_context[arg].kind {
+            CExprKind::ImplicitCast(ty, lit, CastKind::ArrayToPointerDecay, _, _) => (ty, lit),
+            _ => return None,
+        };
+        let bytes = match self.ast_context[lit].kind {
+            CExprKind::Literal(_, CLiteral::String(ref bytes, 1)) => bytes,
+            _ => return None,
+        };
+        if self.ast_context.get_pointee_qual_type(ptr_ty.ctype)?.qualifiers.is_const {
+            return None;
+        }
+
+        // Arguments to the variadic part of a call, or to a function without a prototype,
+        // aren't passed as a `char *`
+        let (typ, has_body) = match self.ast_context[callee].kind {
+            CDeclKind::Function { typ, body, .. } => (typ, body.is_some()),
+            _ => return None,
+        };
+        match self.ast_context.resolve_type(typ).kind {
+            CTypeKind::Function(_, ref params, _, _, true) if index < params.len() => {}
+            _ => return None,
+        }
+
+        Some((ptr_ty.ctype, lit, bytes, has_body))
+    }
+
+    /// Get the name of the `static mut` array holding a copy of the string literal `lit` for
+    /// the current module, generating it the first time it is requested.
+    fn literal_static(
+        &self,
+        ctx: ExprContext,
+        lit: CExprId,
+        bytes: &[u8],
+    ) -> Result<String, TranslationError> {
+        let cur_file = self.cur_file();
+        let use_id = match self.tcfg.string_literals {
+            StringLiterals::Shared => None,
+            StringLiterals::UniquePerUse => Some(lit),
+        };
+        let key = (cur_file, bytes.to_owned(), use_id);
+        if let Some(name) = self.literal_statics.borrow().get(&key) {
+            return Ok(name.clone());
+        }
+
+        let (lit_ty, kind) = match self.ast_context[lit].kind {
+            CExprKind::Literal(ty, ref kind) => (ty, kind),
+            _ => return Err(format_err!("{:?} is not a string literal", lit).into()),
+        };
+        let ty = self.convert_type(lit_ty.ctype)?;
+        let init = self
+            .convert_literal(ctx.static_(), lit_ty, kind)?
+            .to_pure_expr()
+            .ok_or_else(|| format_err!("Expected no side-effects in string literal"))?;
+
+        let name = self.renamer.borrow_mut().pick_name_root("string_literal");
+        let item = mk().mutbl().static_item(&name, ty, init);
+        self.items
+            .borrow_mut()
+            .entry(cur_file)
+            .or_insert_with(ItemStore::new)
+            .add_item(item);
+        self.literal_statics.borrow_mut().insert(key, name.clone());
+
+        Ok(name)
+    }
+}
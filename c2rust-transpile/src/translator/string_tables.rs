@@ -0,0 +1,157 @@
+//! Translation of tables of strings into slices of `CStr`s with `--translate-string-tables`.
+//!
+//! A name table such as `static const char *names[] = { "a", "b", NULL };` is normally
+//! translated to a `static mut` array of raw pointers, which is awkward to iterate. With
+//! `--translate-string-tables`, tables of string literals are translated to an immutable
+//! `static names: &[&CStr]`, without the trailing null sentinel, so Rust code can simply
+//! iterate over the names. Sized tables are translated the same way, as long as only their
+//! last entry is left null.
+//!
+//! Reads of an element, `names[i]`, still produce the `*const c_char` that C code expects,
+//! and reading at the index of the sentinel still produces a null pointer. Tables that are
+//! used in any way other than reading their elements keep the array of pointers.
+
+use super::*;
+
+impl<'c> Translation<'c> {
+    /// Find the static tables of strings that can be translated to slices of `CStr`s.
+    pub fn find_string_tables(&self) -> IndexSet<CDeclId> {
+        let mut tables = self
+            .ast_context
+            .iter_decls()
+            .filter(|&(&decl_id, _)| self.string_table_entries(decl_id).is_some())
+            .map(|(&decl_id, _)| decl_id)
+            .collect::<IndexSet<_>>();
+        self.remove_tables_with_other_uses(&mut tables);
+        tables
+    }
+
+    /// Get the strings a table of strings is initialized with, without the null sentinel, if
+    /// `decl_id` is a file-local static array of pointers to `const char` initialized with
+    /// string literals, optionally followed by one null pointer.
+    fn string_table_entries(&self, decl_id: CDeclId) -> Option<Vec<Vec<u8>>> {
+        let (initializer, typ) = match self.ast_context[decl_id].kind {
+            CDeclKind::Variable {
+                has_static_duration: true,
+                has_thread_duration: false,
+                is_externally_visible: false,
+                is_defn: true,
+                initializer: Some(initializer),
+                typ,
+                ..
+            } => (initializer, typ),
+            _ => return None,
+        };
+
+        let (elt, len) = match self.ast_context.resolve_type(typ.ctype).kind {
+            CTypeKind::ConstantArray(elt, len) => (elt, len),
+            _ => return None,
+        };
+        let pointee = self.ast_context.get_pointee_qual_type(elt)?;
+        if !pointee.qualifiers.is_const
+            || self.ast_context.resolve_type(pointee.ctype).kind != CTypeKind::Char
+        {
+            return None;
+        }
+
+        let mut entries = match self.ast_context[initializer].kind {
+            CExprKind::InitList(_, ref entries, _, _) => &entries[..],
+            _ => return None,
+        };
+        match entries.split_last() {
+            // An explicit sentinel
+            Some((&last, rest)) if entries.len() == len && self.ast_context.is_null_expr(last) => {
+                entries = rest;
+            }
+            // All entries are strings, or the last one is left null
+            _ if entries.len() == len || entries.len() + 1 == len => {}
+            _ => return None,
+        }
+
+        entries
+            .iter()
+            .map(|&entry| match *self.ast_context.resolve_expr_value(entry) {
+                // `CStr`s can't contain null bytes
+                CExprKind::Literal(_, CLiteral::String(ref bytes, 1)) if !bytes.contains(&0) => {
+                    Some(bytes.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Convert the type and initializer of a table of strings into a slice of `CStr`s, i.e.,
+    /// `&[&CStr]` and `&[CStr(b"a\0"), CStr(b"b\0"), ..]`.
+    pub fn convert_string_table(
+        &self,
+        decl_id: CDeclId,
+    ) -> Result<(P<Ty>, P<Expr>), TranslationError> {
+        let entries = self
+            .string_table_entries(decl_id)
+            .ok_or_else(|| format_err!("{:?} is not a table of strings", decl_id))?;
+
+        let cstr_ty = || mk().path_ty(vec!["", "std", "ffi", "CStr"]);
+        let ty = mk().ref_ty(mk().slice_ty(mk().ref_ty(cstr_ty())));
+
+        // `CStr::from_bytes_with_nul_unchecked` isn't a `const fn`, so transmute the bytes,
+        // which is what it does
+        self.use_feature("const_transmute");
+        let bytes_ty = mk().ref_ty(mk().slice_ty(mk().path_ty(vec!["u8"])));
+        let entries = entries
+            .into_iter()
+            .map(|mut bytes| {
+                bytes.push(0);
+                let bytes = mk().lit_expr(mk().bytestr_lit(bytes));
+                transmute_expr(bytes_ty.clone(), mk().ref_ty(cstr_ty()), bytes, false)
+            })
+            .collect();
+        let init = mk().block_expr(mk().unsafe_().block(vec![
+            mk().expr_stmt(mk().addr_of_expr(mk().array_expr(entries))),
+        ]));
+
+        Ok((ty, init))
+    }
+
+    /// Check if `expr` is an element of a table translated to a slice of `CStr`s.
+    pub fn is_string_table_element(&self, expr: CExprId) -> bool {
+        self.table_element(expr).map_or(false, |(table, _)| {
+            match self.ast_context[table].kind {
+                CExprKind::DeclRef(_, decl_id, _) => self.string_tables.contains(&decl_id),
+                _ => false,
+            }
+        })
+    }
+
+    /// Convert a read of an element of a table of strings into the C string pointer, i.e.,
+    /// `table.get(i as usize).map_or(ptr::null(), |s| s.as_ptr())`. Reading past the end
+    /// produces the null pointer the C table ended with.
+    pub fn convert_string_table_read(
+        &self,
+        ctx: ExprContext,
+        elt: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let (table, index) = self
+            .table_element(elt)
+            .ok_or_else(|| format_err!("{:?} is not an element of a table of strings", elt))?;
+        let table = self.convert_expr(ctx.used(), table)?;
+        let index = self.convert_expr(ctx.used(), index)?;
+
+        table.and_then(|table| {
+            Ok(index.map(|index| {
+                let index = mk().cast_expr(index, mk().path_ty(vec!["usize"]));
+                let entry = mk().method_call_expr(table, "get", vec![index]);
+                let null = mk().call_expr(mk().path_expr(vec!["", "std", "ptr", "null"]), vec![]);
+                let as_ptr = mk().closure_expr(
+                    CaptureBy::Ref,
+                    Movability::Movable,
+                    mk().fn_decl(
+                        vec![mk().arg(mk().infer_ty(), mk().ident_pat("s"))],
+                        FunctionRetTy::Default(DUMMY_SP),
+                    ),
+                    mk().method_call_expr(mk().ident_expr("s"), "as_ptr", vec![] as Vec<P<Expr>>),
+                );
+                mk().method_call_expr(entry, "map_or", vec![null, as_ptr])
+            }))
+        })
+    }
+}
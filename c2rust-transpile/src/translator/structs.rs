@@ -5,15 +5,24 @@
 use std::collections::HashSet;
 use std::ops::Index;
 
+use std::rc::Rc;
+
 use super::TranslationError;
-use crate::c_ast::{BinOp, CDeclId, CDeclKind, CExprId, CRecordId, CTypeId};
+use crate::c_ast::{
+    BinOp, CDeclId, CDeclKind, CExprId, CExprKind, CFieldId, CQualTypeId, CRecordId, CTypeId,
+    CTypeKind, OffsetOfKind,
+};
+use crate::rust_ast::item_store::ItemStore;
 use crate::translator::{ExprContext, Translation, PADDING_SUFFIX};
+use crate::ExternCrate;
 use crate::with_stmts::WithStmts;
 use c2rust_ast_builder::mk;
 use syntax::ast::{
-    self, AttrStyle, BinOpKind, Expr, ExprKind, Lit, LitIntType, LitKind, MetaItemKind,
-    NestedMetaItem, StmtKind, StrStyle, StructField, Ty, TyKind,
+    self, AttrStyle, BinOpKind, Expr, ExprKind, FunctionRetTy, Lit, LitIntType, LitKind,
+    MacDelimiter, MetaItemKind, NestedMetaItem, StmtKind, StrStyle, StructField, Ty, TyKind,
 };
+use syntax::parse::token::{self, Nonterminal};
+use syntax::tokenstream::TokenTree;
 use syntax::ptr::P;
 use syntax::source_map::symbol::Symbol;
 use syntax_pos::DUMMY_SP;
@@ -158,7 +167,13 @@ impl<'a> Translation<'a> {
                             })
                         }
 
-                        let field = mk().pub_().struct_field(field_name.clone(), ty);
+                        // Mark reference counts for the `refcount_to_rc` refactoring command
+                        let field = if self.refcount_fields.contains_key(field_id) {
+                            mk().single_attr("refcount_field")
+                        } else {
+                            mk()
+                        };
+                        let field = field.pub_().struct_field(field_name.clone(), ty);
 
                         reorganized_fields.push(FieldType::Regular {
                             name: field_name,
@@ -747,4 +762,201 @@ impl<'a> Translation<'a> {
             return Ok(WithStmts::new(stmts, val));
         })
     }
+
+    /// Recognize the `container_of` idiom,
+    /// `(struct s *)((char *)ptr - offsetof(struct s, field))`, and translate it into a call
+    /// to a generated helper which recovers a pointer to the enclosing struct. Returns `None`
+    /// when the cast does not have this shape so that the caller can fall back to a regular
+    /// pointer cast.
+    pub fn convert_container_of(
+        &self,
+        ctx: ExprContext,
+        target_ty: CQualTypeId,
+        expr: CExprId,
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        let pointee = match self.ast_context.get_pointee_qual_type(target_ty.ctype) {
+            Some(pointee) => pointee,
+            None => return Ok(None),
+        };
+        let struct_id = match self.ast_context.resolve_type(pointee.ctype).kind {
+            CTypeKind::Struct(struct_id) => struct_id,
+            _ => return Ok(None),
+        };
+        // `offset_of!` cannot be used on the fields of packed structs
+        if self.ast_context.is_packed_struct_decl(struct_id) {
+            return Ok(None);
+        }
+
+        let mut expr = expr;
+        while let CExprKind::Paren(_, subexpr) = self.ast_context[expr].kind {
+            expr = subexpr;
+        }
+
+        let (lhs, rhs) = match self.ast_context[expr].kind {
+            CExprKind::Binary(_, BinOp::Subtract, lhs, rhs, _, _) => (lhs, rhs),
+            _ => return Ok(None),
+        };
+
+        // The offset must be a constant `offsetof` so that we can find the member it names
+        let offset = match *self.ast_context.resolve_expr_value(rhs) {
+            CExprKind::OffsetOf(_, OffsetOfKind::Constant(offset))
+            | CExprKind::OffsetOf(_, OffsetOfKind::Member(offset, ..)) => offset,
+            _ => return Ok(None),
+        };
+
+        // The pointer arithmetic must be done on a byte pointer
+        let ptr = match self.ast_context[lhs].kind {
+            CExprKind::ExplicitCast(ty, ptr, _, _, _) => {
+                let is_byte_ptr = self
+                    .ast_context
+                    .get_pointee_qual_type(ty.ctype)
+                    .map_or(false, |pointee| match self.ast_context.resolve_type(pointee.ctype).kind {
+                        CTypeKind::Char | CTypeKind::SChar | CTypeKind::UChar => true,
+                        _ => false,
+                    });
+                if !is_byte_ptr {
+                    return Ok(None);
+                }
+                ptr
+            }
+            _ => return Ok(None),
+        };
+
+        let field_id = match self.ast_context.index(struct_id).kind {
+            CDeclKind::Struct {
+                fields: Some(ref fields),
+                ..
+            } => fields.iter().cloned().find(|&field_id| {
+                match self.ast_context.index(field_id).kind {
+                    CDeclKind::Field {
+                        bitfield_width: None,
+                        platform_bit_offset,
+                        ..
+                    } => platform_bit_offset == offset * 8,
+                    _ => false,
+                }
+            }),
+            _ => None,
+        };
+        let field_id = match field_id {
+            Some(field_id) => field_id,
+            None => return Ok(None),
+        };
+
+        let helper_name = self.container_of_helper(pointee.ctype, struct_id, field_id)?;
+        let struct_ty = self.convert_type(target_ty.ctype)?;
+        let void_ptr_ty = mk().mutbl().ptr_ty(mk().path_ty(vec!["libc", "c_void"]));
+
+        let mut val = self.convert_expr(ctx.used(), ptr)?.map(|ptr| {
+            let arg = mk().cast_expr(ptr, void_ptr_ty);
+            let call = mk().call_expr(mk().path_expr(vec![helper_name]), vec![arg]);
+            mk().cast_expr(call, struct_ty)
+        });
+        val.set_unsafe();
+
+        Ok(Some(val))
+    }
+
+    /// Get the name of the `container_of` helper for the given struct member, generating the
+    /// helper in the current module the first time it is requested:
+    ///
+    /// ```no_run
+    /// #[inline]
+    /// pub unsafe fn container_of_node_link(ptr: *mut libc::c_void) -> *mut node {
+    ///     (ptr as *mut libc::c_char).offset(-(offset_of!(node, link) as isize)) as *mut node
+    /// }
+    /// ```
+    fn container_of_helper(
+        &self,
+        struct_ty: CTypeId,
+        struct_id: CRecordId,
+        field_id: CFieldId,
+    ) -> Result<String, TranslationError> {
+        let cur_file = self.cur_file();
+        let key = (cur_file, struct_id, field_id);
+        if let Some(name) = self.container_of_helpers.borrow().get(&key) {
+            return Ok(name.clone());
+        }
+
+        let struct_name = self.resolve_decl_inner_name(struct_id);
+        let field_name = self
+            .type_converter
+            .borrow()
+            .resolve_field_name(Some(struct_id), field_id)
+            .ok_or_else(|| TranslationError::generic("Did not find name for container_of field"))?;
+        let fn_name = self
+            .renamer
+            .borrow_mut()
+            .pick_name_root(&format!("container_of_{}_{}", struct_name, field_name));
+
+        let struct_ptr_ty = mk().mutbl().ptr_ty(self.convert_type(struct_ty)?);
+
+        let offset = self.offset_of_member(struct_id, field_id)?;
+        let offset = mk().unary_expr(
+            ast::UnOp::Neg,
+            mk().paren_expr(mk().cast_expr(offset, mk().ident_ty("isize"))),
+        );
+
+        let byte_ptr = mk().cast_expr(
+            mk().ident_expr("ptr"),
+            mk().mutbl().ptr_ty(mk().path_ty(vec!["libc", "c_char"])),
+        );
+        let body = mk().cast_expr(
+            mk().method_call_expr(mk().paren_expr(byte_ptr), "offset", vec![offset]),
+            struct_ptr_ty.clone(),
+        );
+
+        let args = vec![mk().arg(
+            mk().mutbl().ptr_ty(mk().path_ty(vec!["libc", "c_void"])),
+            mk().ident_pat("ptr"),
+        )];
+        let decl = mk().fn_decl(args, FunctionRetTy::Ty(struct_ptr_ty));
+        let item = mk()
+            .single_attr("inline")
+            .pub_()
+            .unsafe_()
+            .fn_item(&fn_name, decl, mk().block(vec![mk().expr_stmt(body)]));
+
+        self.items
+            .borrow_mut()
+            .entry(cur_file)
+            .or_insert_with(ItemStore::new)
+            .add_item(item);
+        self.container_of_helpers
+            .borrow_mut()
+            .insert(key, fn_name.clone());
+
+        Ok(fn_name)
+    }
+
+    /// Build `offset_of!(Struct, field)` for a member of a struct, importing the struct into
+    /// the current module. The struct must not be packed.
+    pub fn offset_of_member(
+        &self,
+        struct_id: CRecordId,
+        field_id: CFieldId,
+    ) -> Result<P<Expr>, TranslationError> {
+        self.use_crate(ExternCrate::Memoffset);
+
+        let struct_name = self.resolve_decl_inner_name(struct_id);
+        self.add_import(self.cur_file(), struct_id, &struct_name);
+        let field_name = self
+            .type_converter
+            .borrow()
+            .resolve_field_name(Some(struct_id), field_id)
+            .ok_or_else(|| TranslationError::generic("Did not find name for offsetof field"))?;
+
+        let struct_ident = Nonterminal::NtIdent(mk().ident(&struct_name), false);
+        let field_ident = Nonterminal::NtIdent(mk().ident(&field_name), false);
+        let macro_body = vec![
+            TokenTree::token(token::Interpolated(Rc::new(struct_ident)), DUMMY_SP),
+            TokenTree::token(token::Comma, DUMMY_SP),
+            TokenTree::token(token::Interpolated(Rc::new(field_ident)), DUMMY_SP),
+        ];
+        Ok(mk().mac_expr(mk().mac(
+            mk().path("offset_of"),
+            macro_body,
+            MacDelimiter::Parenthesis,
+        )))
+    }
 }
@@ -0,0 +1,322 @@
+//! Declarations from system headers, for `--skip-system-headers`.
+//!
+//! Headers like `<stdio.h>` and `<sys/stat.h>` define types such as `FILE` and `struct stat`
+//! whose layout is platform-specific, so translating their definitions into every module that
+//! includes them produces code that only works on the platform it was translated on. A type
+//! declared under one of the system include directories of the compile command is instead
+//! aliased to the `libc` crate's definition when it has one, and otherwise declared as an
+//! opaque `extern` type, unless the translated code needs its layout or fields. Function and
+//! variable declarations, enums and other typedefs from system headers are still translated,
+//! since references to them need them.
+
+use super::*;
+
+/// C types from system headers with a definition in the `libc` crate, by the name of the
+/// struct, union or typedef, and the name of the `libc` type.
+const LIBC_TYPES: &[(&str, &str)] = &[
+    ("FILE", "FILE"),
+    ("_IO_FILE", "FILE"),
+    ("__sFILE", "FILE"),
+    ("fpos_t", "fpos_t"),
+    ("DIR", "DIR"),
+    ("__dirstream", "DIR"),
+    ("dirent", "dirent"),
+    ("stat", "stat"),
+    ("timespec", "timespec"),
+    ("timeval", "timeval"),
+    ("timezone", "timezone"),
+    ("tm", "tm"),
+    ("tms", "tms"),
+    ("itimerval", "itimerval"),
+    ("rlimit", "rlimit"),
+    ("rusage", "rusage"),
+    ("utsname", "utsname"),
+    ("passwd", "passwd"),
+    ("group", "group"),
+    ("termios", "termios"),
+    ("winsize", "winsize"),
+    ("pollfd", "pollfd"),
+    ("iovec", "iovec"),
+    ("fd_set", "fd_set"),
+    ("sigset_t", "sigset_t"),
+    ("__sigset_t", "sigset_t"),
+    ("sigaction", "sigaction"),
+    ("siginfo_t", "siginfo_t"),
+    ("stack_t", "stack_t"),
+    ("sockaddr", "sockaddr"),
+    ("sockaddr_in", "sockaddr_in"),
+    ("sockaddr_in6", "sockaddr_in6"),
+    ("sockaddr_un", "sockaddr_un"),
+    ("sockaddr_storage", "sockaddr_storage"),
+    ("in_addr", "in_addr"),
+    ("in6_addr", "in6_addr"),
+    ("addrinfo", "addrinfo"),
+    ("hostent", "hostent"),
+    ("msghdr", "msghdr"),
+    ("pthread_t", "pthread_t"),
+    ("pthread_attr_t", "pthread_attr_t"),
+    ("pthread_mutex_t", "pthread_mutex_t"),
+    ("pthread_mutexattr_t", "pthread_mutexattr_t"),
+    ("pthread_cond_t", "pthread_cond_t"),
+    ("pthread_condattr_t", "pthread_condattr_t"),
+    ("pthread_rwlock_t", "pthread_rwlock_t"),
+    ("pthread_rwlockattr_t", "pthread_rwlockattr_t"),
+    ("pthread_key_t", "pthread_key_t"),
+    ("sem_t", "sem_t"),
+    ("jmp_buf", "jmp_buf"),
+    ("glob_t", "glob_t"),
+    ("regex_t", "regex_t"),
+    ("regmatch_t", "regmatch_t"),
+    ("lconv", "lconv"),
+];
+
+/// How a type declared in a system header is translated
+#[derive(Copy, Clone, Debug)]
+pub enum SystemType {
+    /// An alias of the `libc` type with this name
+    Libc(&'static str),
+    /// An opaque `extern` type
+    Opaque,
+    /// Nothing, since only skipped definitions refer to it
+    Unused,
+}
+
+/// Check whether `path` is the header of a system library or of the compiler.
+fn is_system_path(path: &path::Path, system_dirs: &[PathBuf]) -> bool {
+    if system_dirs.iter().any(|dir| path.starts_with(dir)) {
+        return true;
+    }
+
+    // Headers of the compiler itself, like `stddef.h`, are in `.../lib/clang/<version>/include`
+    path.ancestors().any(|dir| {
+        dir.file_name().map_or(false, |name| name == "include")
+            && dir
+                .parent()
+                .and_then(|version| version.parent())
+                .and_then(|clang| clang.file_name())
+                .map_or(false, |name| name == "clang")
+    })
+}
+
+impl<'c> Translation<'c> {
+    /// Find the types declared in system headers that are not translated, and how to declare
+    /// them instead. Must be called after typedefs of unnamed types are collapsed.
+    pub fn find_system_types(&self) -> IndexMap<CDeclId, SystemType> {
+        let mut system_types = IndexMap::new();
+        let mut opaque = IndexSet::new();
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            let is_record = match decl.kind {
+                CDeclKind::Struct { .. } | CDeclKind::Union { .. } => true,
+                CDeclKind::Typedef { .. } => false,
+                _ => continue,
+            };
+            let in_system_header = self
+                .ast_context
+                .get_source_path(decl)
+                .map_or(false, |path| is_system_path(path, &self.system_include_dirs));
+            if !in_system_header {
+                continue;
+            }
+            let name = match self.system_type_name(decl_id) {
+                Some(name) => name,
+                None => continue,
+            };
+            if self.tcfg.system_type_allowlist.iter().any(|allowed| allowed == name) {
+                continue;
+            }
+
+            if let Some(&(_, libc_name)) = LIBC_TYPES.iter().find(|&&(c_name, _)| c_name == name) {
+                system_types.insert(decl_id, SystemType::Libc(libc_name));
+            } else if is_record {
+                match decl.kind {
+                    // Incomplete types are already opaque
+                    CDeclKind::Struct { fields: Some(_), .. }
+                    | CDeclKind::Union { fields: Some(_), .. } => {
+                        opaque.insert(decl_id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Keep the definitions of the records whose size or fields the rest of the translation
+        // uses, including through the fields of the other records it keeps
+        loop {
+            let needed = self.records_used(&system_types, &opaque, true);
+            let kept = opaque
+                .iter()
+                .filter(|id| needed.contains(*id))
+                .cloned()
+                .collect::<Vec<_>>();
+            if kept.is_empty() {
+                break;
+            }
+            for id in kept {
+                opaque.remove(&id);
+            }
+        }
+
+        // Records only referenced by the definitions that are skipped, like the internals of
+        // `FILE`, are not declared at all
+        let referenced = self.records_used(&system_types, &opaque, false);
+        for decl_id in opaque {
+            if referenced.contains(&decl_id) {
+                let name = self.system_type_name(decl_id).unwrap();
+                warn!("Declaring system type {} opaquely, since `libc` does not define it", name);
+                system_types.insert(decl_id, SystemType::Opaque);
+            } else {
+                system_types.insert(decl_id, SystemType::Unused);
+            }
+        }
+        system_types
+    }
+
+    /// The C name of a struct, union or typedef, or of the typedef naming an unnamed record.
+    fn system_type_name(&self, decl_id: CDeclId) -> Option<&str> {
+        if let Some(name) = self.ast_context[decl_id].kind.get_name() {
+            return Some(name);
+        }
+        let typedef_id = self
+            .ast_context
+            .prenamed_decls
+            .iter()
+            .find(|&(_, &record_id)| record_id == decl_id)
+            .map(|(&typedef_id, _)| typedef_id)?;
+        self.ast_context[typedef_id].kind.get_name().map(String::as_str)
+    }
+
+    /// Find the records used by the declarations and expressions that are translated, ignoring
+    /// the types in `skipped` and `opaque`. With `by_value`, only the records whose layout or
+    /// fields are used are found, and not those only used behind pointers.
+    fn records_used(
+        &self,
+        skipped: &IndexMap<CDeclId, SystemType>,
+        opaque: &IndexSet<CDeclId>,
+        by_value: bool,
+    ) -> IndexSet<CRecordId> {
+        let mut records = IndexSet::new();
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            match decl.kind {
+                CDeclKind::Function { typ, .. } => {
+                    self.collect_records(typ, skipped, by_value, &mut records);
+                }
+                CDeclKind::Variable { typ, .. } => {
+                    self.collect_records(typ.ctype, skipped, by_value, &mut records);
+                }
+                CDeclKind::Field { typ, .. } => {
+                    let parent = self.ast_context.parents.get(&decl_id);
+                    let parent_skipped = parent
+                        .map_or(false, |id| skipped.contains_key(id) || opaque.contains(id));
+                    if !parent_skipped {
+                        self.collect_records(typ.ctype, skipped, by_value, &mut records);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (_, expr) in self.ast_context.iter_exprs() {
+            match expr.kind {
+                CExprKind::Member(_, _, field, _, _) => {
+                    records.extend(self.ast_context.parents.get(&field).cloned());
+                }
+                CExprKind::UnaryType(_, _, _, arg_ty, _) => {
+                    self.collect_records(arg_ty.ctype, skipped, by_value, &mut records);
+                }
+                _ => {}
+            }
+            if let Some(ty) = expr.kind.get_type() {
+                self.collect_records(ty, skipped, by_value, &mut records);
+            }
+        }
+        records
+    }
+
+    /// Collect the records the type `ctype` refers to, or with `by_value` only those contained
+    /// in its values, not behind a pointer.
+    fn collect_records(
+        &self,
+        ctype: CTypeId,
+        skipped: &IndexMap<CDeclId, SystemType>,
+        by_value: bool,
+        records: &mut IndexSet<CRecordId>,
+    ) {
+        match self.ast_context.index(ctype).kind {
+            CTypeKind::Struct(id) | CTypeKind::Union(id) => {
+                records.insert(id);
+            }
+            CTypeKind::Typedef(id) if !skipped.contains_key(&id) => {
+                if let CDeclKind::Typedef { typ, .. } = self.ast_context[id].kind {
+                    self.collect_records(typ.ctype, skipped, by_value, records);
+                }
+            }
+            CTypeKind::Elaborated(ty)
+            | CTypeKind::Paren(ty)
+            | CTypeKind::Decayed(ty)
+            | CTypeKind::TypeOf(ty)
+            | CTypeKind::Complex(ty)
+            | CTypeKind::ConstantArray(ty, _)
+            | CTypeKind::IncompleteArray(ty)
+            | CTypeKind::VariableArray(ty, _) => {
+                self.collect_records(ty, skipped, by_value, records);
+            }
+            CTypeKind::Pointer(ty) | CTypeKind::Reference(ty) | CTypeKind::BlockPointer(ty)
+                if !by_value =>
+            {
+                self.collect_records(ty.ctype, skipped, by_value, records);
+            }
+            CTypeKind::Attributed(ty, _) | CTypeKind::Vector(ty, _) => {
+                self.collect_records(ty.ctype, skipped, by_value, records);
+            }
+            CTypeKind::Function(ret, ref params, ..) => {
+                self.collect_records(ret.ctype, skipped, by_value, records);
+                for param in params {
+                    self.collect_records(param.ctype, skipped, by_value, records);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Declare a type from a system header as an alias of the `libc` type, or as an opaque
+    /// `extern` type.
+    pub fn convert_system_type(
+        &self,
+        decl_id: CDeclId,
+        system_type: SystemType,
+    ) -> Result<ConvertedDecl, TranslationError> {
+        let s = self.get_span(SomeId::Decl(decl_id)).unwrap_or(DUMMY_SP);
+        let name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(decl_id)
+            .unwrap();
+
+        // Accesses to the fields of `libc` types use their C names
+        match self.ast_context[decl_id].kind {
+            CDeclKind::Struct { fields: Some(ref fields), .. }
+            | CDeclKind::Union { fields: Some(ref fields), .. } => {
+                for &x in fields {
+                    if let CDeclKind::Field { ref name, .. } = self.ast_context[x].kind {
+                        self.type_converter
+                            .borrow_mut()
+                            .declare_field_name(decl_id, x, name);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        match system_type {
+            SystemType::Libc(libc_name) => {
+                let ty = mk().path_ty(vec!["libc", libc_name]);
+                Ok(ConvertedDecl::Item(mk().span(s).pub_().type_item(name, ty)))
+            }
+            SystemType::Opaque => {
+                self.use_feature("extern_types");
+                Ok(ConvertedDecl::ForeignItem(mk().span(s).pub_().ty_foreign_item(name)))
+            }
+            SystemType::Unused => Ok(ConvertedDecl::NoItem),
+        }
+    }
+}
@@ -24,6 +24,42 @@ macro_rules! match_or {
 
 impl<'c> Translation<'c> {
 
+    /// Returns true iff no function in the translation unit reads a `va_list` with `va_arg` or
+    /// creates one with `va_start`, so that every `va_list` is only passed along (or `va_copy`ed)
+    /// until it reaches a C function like `vsnprintf`. Such translation units don't need the
+    /// nightly `VaList`: their `va_list` parameters can keep the C ABI type instead, e.g.
+    /// `*mut __va_list_tag` on x86_64 SysV and `*mut libc::c_char` on Windows.
+    pub fn only_forwards_va_lists(&self) -> bool {
+        for (_, decl) in self.ast_context.iter_decls() {
+            let (typ, body) = match decl.kind {
+                CDeclKind::Function { typ, body: Some(body), .. } => (typ, body),
+                _ => continue,
+            };
+            if let CTypeKind::Function(_, _, true, _, _) = self.ast_context.resolve_type(typ).kind {
+                return false;
+            }
+
+            let mut iter = DFExpr::new(&self.ast_context, body.into());
+            while let Some(s) = iter.next() {
+                if let SomeId::Expr(e) = s {
+                    if let CExprKind::VAArg(..) = self.ast_context[e].kind {
+                        return false;
+                    }
+                    if let Some(VaPart::Start(_)) = self.match_vapart(e) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns true iff `va_list`s are translated to the nightly `VaList` and `VaListImpl`
+    /// types rather than their C ABI types.
+    pub fn translate_valist(&self) -> bool {
+        self.type_converter.borrow().translate_valist
+    }
+
     /// Returns true iff `va_start`, `va_end`, or `va_copy` may be called on `decl_id`.
     pub fn is_va_decl(&self, decl_id: CDeclId) -> bool {
         let fn_ctx = self.function_context.borrow();
@@ -182,6 +218,33 @@ impl<'c> Translation<'c> {
         }
     }
 
+    /// Translate `va_copy(dst, src)` for `va_list`s kept in their C ABI representation. On
+    /// x86_64 SysV, `va_list` is an array of one `__va_list_tag` and both arguments point to
+    /// that element, which gets copied; where `va_list` is a plain pointer or struct, it is
+    /// assigned directly.
+    pub fn convert_va_copy_abi(
+        &self,
+        ctx: ExprContext,
+        dst: CExprId,
+        src: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let dst = self.convert_expr(ctx.used(), dst)?.to_expr();
+        let src = self.convert_expr(ctx.used(), src)?.to_expr();
+
+        let assign_expr = match self.ast_context.va_list_kind {
+            BuiltinVaListKind::X86_64ABIBuiltinVaList => mk().assign_expr(
+                mk().unary_expr(ast::UnOp::Deref, dst),
+                mk().unary_expr(ast::UnOp::Deref, src),
+            ),
+            _ => mk().assign_expr(dst, src),
+        };
+
+        Ok(WithStmts::new(
+            vec![mk().semi_stmt(assign_expr)],
+            self.panic_or_err("va_copy stub"),
+        ))
+    }
+
     /// Update the current function context by i) enabling the C variadics feature, ii) naming the
     /// Rust function argument that corresponds to the elipsis in the original C function, and iii)
     /// building a list of variable declarations to be translated into `VaListImpl`s. Returns the
@@ -0,0 +1,35 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+extern crate syntax;
+
+mod common;
+
+use syntax::ast::{Crate, Ident, ItemKind};
+use syntax::attr;
+
+fn add_cold_attrs(krate: &mut Crate) {
+    for item in &mut krate.module.items {
+        if let ItemKind::Fn(..) = item.kind {
+            let cold = attr::mk_word_item(Ident::from_str("cold"));
+            item.attrs.push(attr::mk_attr_outer(cold));
+        }
+    }
+}
+
+#[test]
+fn test_ast_transform() {
+    let tcfg = common::config().with_ast_transform(add_cold_attrs);
+    let output = common::translate(
+        "ast-transform",
+        tcfg,
+        "funcs.c",
+        "int add(int a, int b) { return a + b; }\n\
+         int sub(int a, int b) { return a - b; }\n",
+    );
+
+    let fns = output.lines().filter(|line| line.contains("fn ")).count();
+    let cold_attrs = output.lines().filter(|line| line.trim() == "#[cold]").count();
+    assert_eq!(fns, 2, "unexpected translation:\n{}", output);
+    assert_eq!(cold_attrs, fns, "missing #[cold] attributes:\n{}", output);
+}
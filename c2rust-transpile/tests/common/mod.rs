@@ -0,0 +1,184 @@
+//! Configuration and scratch projects shared by the transpiler tests.
+//!
+//! These tests check the text of the translation. Tests comparing the behavior of translated
+//! code against C belong in the test directories driven by `scripts/test_translator.py`.
+
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use c2rust_transpile::{RenameCollisions, ReplaceMode, StringLiterals, TranspilerConfig};
+
+/// The default translation options, failing on errors and without refactoring.  Tests
+/// override the fields they exercise with struct update syntax.
+pub fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        dump_untyped_context: false,
+        dump_typed_context: false,
+        pretty_typed_context: false,
+        dump_function_cfgs: false,
+        json_function_cfgs: false,
+        dump_cfg_liveness: false,
+        dump_structures: false,
+        verbose: false,
+        debug_ast_exporter: false,
+
+        incremental_relooper: true,
+        fail_on_multiple: false,
+        filter: None,
+        debug_relooper_labels: false,
+        cross_checks: false,
+        cross_check_backend: String::new(),
+        cross_check_configs: vec![],
+        prefix_function_names: None,
+        translate_asm: true,
+        use_c_loop_info: true,
+        use_c_multiple_info: true,
+        simplify_structures: true,
+        panic_on_translator_failure: false,
+        emit_modules: false,
+        fail_on_error: true,
+        replace_unsupported_decls: ReplaceMode::Extern,
+        shim_untranslatable_stmts: false,
+        translate_valist: true,
+        translate_prefetch: true,
+        overwrite_existing: true,
+        reduce_type_annotations: false,
+        reorganize_definitions: false,
+        enabled_warnings: HashSet::new(),
+        emit_no_std: false,
+        output_dir: None,
+        translate_const_macros: false,
+        translate_fn_macros: false,
+        inline_fn_macros: false,
+        translate_enums: false,
+        emit_repr_transparent: false,
+        strict_bit_ints: false,
+        translate_fn_tables: false,
+        translate_string_tables: false,
+        string_literals: StringLiterals::Shared,
+        translate_blocks: false,
+        rename_collisions: RenameCollisions::Number,
+        must_use_error_codes: false,
+        detect_refcounts: false,
+        embed_byte_arrays: None,
+        split_output_size: None,
+        log_functions: vec![],
+        translate_pthreads: None,
+        disable_refactoring: true,
+        log_level: log::LevelFilter::Warn,
+        header_only: false,
+        internal_visibility: None,
+        skip_system_headers: false,
+        system_type_allowlist: vec![],
+        output_style: None,
+        output_style_overrides: Default::default(),
+
+        emit_build_files: false,
+        binaries: vec![],
+
+        ast_transforms: Default::default(),
+    }
+}
+
+/// C sources in a temporary directory, which is removed when the project is dropped, including
+/// when an assertion of the test fails.
+pub struct Project {
+    dir: PathBuf,
+}
+
+impl Project {
+    /// Create an empty project for the test `name`.
+    pub fn new(name: &str) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let dir = env::temp_dir().join(format!(
+            "c2rust-{}-{}-{}",
+            name,
+            process::id(),
+            NEXT_ID.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        Project { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    /// Write `contents` to `file`, relative to the project directory.
+    pub fn write<C: AsRef<[u8]>>(&self, file: &str, contents: C) -> &Self {
+        fs::write(self.path(file), contents).unwrap();
+        self
+    }
+
+    /// Read `file`, relative to the project directory.
+    pub fn read(&self, file: &str) -> String {
+        fs::read_to_string(self.path(file))
+            .unwrap_or_else(|e| panic!("could not read {}: {}", file, e))
+    }
+
+    /// Translate the C files `units`, each compiled with `cc <args> -c <unit>`.
+    pub fn transpile(&self, tcfg: TranspilerConfig, units: &[&str], args: &[&str]) {
+        let cmds = units
+            .iter()
+            .map(|unit| {
+                let mut arguments = vec!["cc"];
+                arguments.extend(args);
+                arguments.extend(&["-c", *unit]);
+                format!(
+                    r#"{{ "directory": {:?}, "arguments": {:?}, "file": {:?} }}"#,
+                    self.dir.to_str().unwrap(),
+                    arguments,
+                    unit
+                )
+            })
+            .collect::<Vec<_>>();
+        let cc_db = self.path("compile_commands.json");
+        fs::write(&cc_db, format!("[{}]", cmds.join(",\n"))).unwrap();
+
+        c2rust_transpile::transpile(tcfg, &cc_db, &[]);
+    }
+
+    /// Translate the C file `unit` and return its translation.
+    pub fn translate(&self, tcfg: TranspilerConfig, unit: &str) -> String {
+        self.transpile(tcfg, &[unit], &[]);
+        let output = Path::new(unit).with_extension("rs");
+        self.read(output.to_str().unwrap())
+    }
+
+    /// Run `rustc --edition 2018 <args> <file>`, writing its outputs to the project directory.
+    pub fn rustc(&self, file: &str, args: &[&str]) -> Output {
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+        Command::new(rustc)
+            .args(&["--edition", "2018"])
+            .args(args)
+            .arg("--out-dir")
+            .arg(&self.dir)
+            .arg(self.path(file))
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Translate the C file `unit` with the contents `source` in a new project, and return the
+/// translation.
+pub fn translate(name: &str, tcfg: TranspilerConfig, unit: &str, source: &str) -> String {
+    let project = Project::new(name);
+    project.write(unit, source);
+    project.translate(tcfg, unit)
+}
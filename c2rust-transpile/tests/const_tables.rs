@@ -0,0 +1,43 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+fn transpile() -> String {
+    common::translate(
+        "const-tables",
+        common::config(),
+        "tables.c",
+        "#include <string.h>\n\
+         struct keyword { const char *name; int token; };\n\
+         static const struct keyword keywords[] = { { \"if\", 1 }, { \"else\", 2 } };\n\
+         static const int counts[] = { 1, 2, 3 };\n\
+         static const char *const names[] = { \"a\", \"b\" };\n\
+         static struct keyword scratch[2];\n\
+         int find(const struct keyword *table, int n, const char *word) {\n\
+             for (int i = 0; i < n; i++)\n\
+                 if (strcmp(table[i].name, word) == 0) return table[i].token;\n\
+             return 0;\n\
+         }\n\
+         int lookup(const char *word) {\n\
+             scratch[0] = keywords[0];\n\
+             return find(keywords, 2, word) + counts[2] + (names[0] != 0);\n\
+         }\n",
+    )
+}
+
+#[test]
+fn test_const_tables() {
+    let output = transpile();
+
+    // `const` tables are immutable, with the length taken from the initializer
+    assert!(output.contains("static keywords: [keyword; 2]"), "{}", output);
+    assert!(output.contains("static counts: [libc::c_int; 3]"), "{}", output);
+    assert!(output.contains("unsafe impl Sync for keyword {"), "{}", output);
+    assert!(output.contains("find(keywords.as_ptr(), 2"), "{}", output);
+
+    // Writable tables and tables of bare pointers stay mutable
+    assert!(output.contains("static mut scratch: [keyword; 2]"), "{}", output);
+    assert!(output.contains("static mut names: [*const libc::c_char; 2]"), "{}", output);
+}
@@ -0,0 +1,63 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+extern crate libc;
+
+mod common;
+
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use c2rust_transpile::TranspilerConfig;
+use common::Project;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        fail_on_error: false,
+        ..common::config()
+    }
+}
+
+/// Run `f` with the standard error of the process redirected to the file `path`, to also catch
+/// the diagnostics clang prints, and return what was written to it.
+fn capture_stderr<F: FnOnce()>(path: &Path, f: F) -> String {
+    let file = fs::File::create(path).unwrap();
+    io::stderr().flush().unwrap();
+    let saved = unsafe { libc::dup(2) };
+    assert!(saved >= 0);
+    unsafe { libc::dup2(file.as_raw_fd(), 2) };
+    f();
+    io::stderr().flush().unwrap();
+    unsafe {
+        libc::dup2(saved, 2);
+        libc::close(saved);
+    }
+    fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn test_decimal_float_diagnostic() {
+    let project = Project::new("decimal");
+    project.write(
+        "decimal.c",
+        "_Decimal64 rate;\n\
+         int twice(int x) { return 2 * x; }\n",
+    );
+
+    let stderr = capture_stderr(&project.path("stderr.txt"), || {
+        project.transpile(config(), &["decimal.c"], &[])
+    });
+    let output = project.read("decimal.rs");
+
+    assert!(
+        stderr
+            .to_lowercase()
+            .contains("unsupported decimal floating-point type `_decimal64`"),
+        "no diagnostic for _Decimal64:\n{}",
+        stderr
+    );
+    // The rest of the file is still translated
+    assert!(output.contains("fn twice("), "{}", output);
+}
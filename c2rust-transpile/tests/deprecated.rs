@@ -0,0 +1,49 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use common::Project;
+
+#[test]
+fn test_deprecated() {
+    let project = Project::new("deprecated");
+    project.write(
+        "api.c",
+        "__attribute__((deprecated(\"use new_api instead\"))) void old_api(void) {}\n\
+         void new_api(void) {}\n\
+         struct __attribute__((deprecated)) old_config { _Bool verbose; };\n\
+         __attribute__((deprecated)) _Bool old_verbose = 0;\n\
+         void caller(void) { old_api(); }\n",
+    );
+    let output = project.translate(common::config(), "api.c");
+
+    let attrs_before = |item: &str| {
+        let pos = output.find(item).unwrap_or_else(|| panic!("missing {}:\n{}", item, output));
+        let line_start = output[..pos].rfind('\n').map_or(0, |i| i + 1);
+        output[..line_start]
+            .lines()
+            .rev()
+            .take_while(|line| line.trim_start().starts_with("#["))
+            .map(|line| line.trim().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let deprecated = "#[deprecated]".to_owned();
+    let deprecated_note = "#[deprecated(note = \"use new_api instead\")]".to_owned();
+    assert!(attrs_before("fn old_api(").contains(&deprecated_note), "{}", output);
+    assert!(!attrs_before("fn new_api(").iter().any(|a| a.starts_with("#[deprecated")), "{}", output);
+    assert!(attrs_before("struct old_config ").contains(&deprecated), "{}", output);
+    assert!(attrs_before("static mut old_verbose:").contains(&deprecated), "{}", output);
+
+    // The call in `caller` triggers the lint. Only `bool` is used, so the output builds
+    // without `libc`.
+    let build = project.rustc("api.rs", &["--crate-type", "lib", "--emit", "metadata"]);
+    let stderr = String::from_utf8_lossy(&build.stderr);
+    assert!(build.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("use of deprecated item 'old_api': use new_api instead"),
+        "missing deprecation warning:\n{}",
+        stderr
+    );
+}
@@ -0,0 +1,57 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use std::fs;
+
+use c2rust_transpile::TranspilerConfig;
+use common::Project;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        embed_byte_arrays: Some(4096),
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_embed_byte_arrays() {
+    let bytes = (0..10 * 1024)
+        .map(|i| (i * 7 % 256) as u8)
+        .collect::<Vec<_>>();
+    let elems = bytes
+        .iter()
+        .map(|b| format!("0x{:02x}", b))
+        .collect::<Vec<_>>();
+    let project = Project::new("embed");
+    project.write(
+        "blob.c",
+        format!(
+            "const unsigned char blob[] = {{ {} }};\n\
+             unsigned char small[] = {{ 1, 2, 3 }};\n",
+            elems.join(", ")
+        ),
+    );
+    let output = project.translate(config(), "blob.c");
+    let data = fs::read(project.path("blob.0.bin"));
+
+    assert!(
+        output.contains(r#"*include_bytes!("blob.0.bin")"#),
+        "missing include_bytes!:\n{}",
+        output
+    );
+    assert!(
+        !output.contains("0xff"),
+        "blob was translated inline:\n{}",
+        output
+    );
+    // Arrays below the threshold are still translated inline
+    assert!(
+        output.contains("small: [libc::c_uchar; 3] = ["),
+        "small was not inline:\n{}",
+        output
+    );
+    assert_eq!(data.unwrap(), bytes);
+}
@@ -0,0 +1,41 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::{analyze_features, Feature};
+use common::Project;
+
+#[test]
+fn test_analyze_features() {
+    let project = Project::new("analyze-features");
+    project.write(
+        "sample.c",
+        "#include <stddef.h>\n\
+         int sum(int n) {\n\
+             int buf[n];\n\
+             int total = 0;\n\
+             for (int i = 0; i < n; i++) buf[i] = i;\n\
+             for (int i = 0; i < n; i++) total += buf[i];\n\
+             return total;\n\
+         }\n\
+         int add_one(int x) {\n\
+             int y;\n\
+             __asm__(\"lea 1(%1), %0\" : \"=r\"(y) : \"r\"(x));\n\
+             return y;\n\
+         }\n",
+    );
+
+    let report = analyze_features(&project.path("sample.c"), &["-DNDEBUG"]).unwrap();
+
+    assert_eq!(report.count(Feature::VariableLengthArray), 1, "{:?}", report);
+    assert_eq!(report.locations(Feature::VariableLengthArray)[0].line, 3);
+    assert_eq!(report.count(Feature::InlineAsm), 1, "{:?}", report);
+    assert_eq!(report.locations(Feature::InlineAsm)[0].line, 11);
+
+    // Nothing is reported for the rest of the file or the header it includes
+    assert!(!report.uses(Feature::Goto));
+    assert!(!report.uses(Feature::LongDouble));
+    assert_eq!(report.iter().count(), 2);
+}
@@ -0,0 +1,59 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        translate_enums: true,
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_ffi_safe_extern_decls() {
+    let output = common::translate(
+        "ffi-safety",
+        config(),
+        "ffi.c",
+        "enum color { RED, GREEN, BLUE };\n\
+         enum shape { CIRCLE, SQUARE };\n\
+         struct pixel { enum shape shape; int x; };\n\
+         struct empty {};\n\
+         enum color get_color(int idx);\n\
+         void set_color(enum color c, struct empty e, int n);\n\
+         void draw(struct pixel p);\n\
+         int paint(void) {\n\
+             struct empty e;\n\
+             set_color(get_color(0), e, 3);\n\
+             struct pixel p = { SQUARE, 1 };\n\
+             draw(p);\n\
+             return get_color(1) == BLUE;\n\
+         }\n",
+    );
+
+    let decl = |name: &str| {
+        let start = output.find(&format!("fn {}(", name)).expect(name);
+        output[start..].lines().next().unwrap()
+    };
+
+    // The enum returned by an extern function stays a Rust enum, but the declaration
+    // returns its integer representation, which is converted at the call sites
+    assert!(output.contains("pub enum color {"), "{}", output);
+    assert!(decl("get_color").contains("-> libc::c_uint;"), "{}", output);
+    assert!(output.contains("pub fn from_int(value: libc::c_uint) -> color {"), "{}", output);
+    assert_eq!(output.matches("color::from_int(").count(), 2, "{}", output);
+    assert!(!output.contains("transmute"), "{}", output);
+
+    // Enum arguments are cast, and the zero-sized argument is left out
+    assert_eq!(decl("set_color"), "fn set_color(c: libc::c_uint, n: libc::c_int);");
+    assert!(output.contains("as libc::c_uint, 3i32)"), "{}", output);
+
+    // C may store any value in the field of a struct, so its enum is kept as constants
+    assert!(!output.contains("pub enum shape"), "{}", output);
+    assert!(output.contains("pub type shape = libc::c_uint;"), "{}", output);
+    assert_eq!(decl("draw"), "fn draw(p: pixel);");
+}
@@ -0,0 +1,52 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        translate_fn_macros: true,
+        inline_fn_macros: true,
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_inline_fn_macros() {
+    let output = common::translate(
+        "inline-fn-macros",
+        config(),
+        "minmax.c",
+        "#define MIN(a, b) ((a) < (b) ? (a) : (b))\n\
+         #define MAX(a, b) ((a) > (b) ? (a) : (b))\n\
+         int clamp(int x, int lo, int hi) { return MIN(MAX(x, lo), hi); }\n",
+    );
+
+    for name in &["MIN", "MAX"] {
+        let func = output
+            .find(&format!("fn {}(", name))
+            .unwrap_or_else(|| panic!("missing function for {}:\n{}", name, output));
+        assert!(
+            output[..func]
+                .trim_end()
+                .lines()
+                .rev()
+                .take(3)
+                .any(|line| line.trim() == "#[inline(always)]"),
+            "missing #[inline(always)] on {}:\n{}",
+            name,
+            output
+        );
+    }
+
+    // Only the macro functions are marked, not the C function using them
+    assert_eq!(
+        output.matches("#[inline(always)]").count(),
+        2,
+        "unexpected #[inline(always)]:\n{}",
+        output
+    );
+}
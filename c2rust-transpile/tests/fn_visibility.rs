@@ -0,0 +1,72 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::{InternalVisibility, TranspilerConfig};
+
+fn config(internal_visibility: Option<InternalVisibility>) -> TranspilerConfig {
+    TranspilerConfig {
+        internal_visibility,
+        ..common::config()
+    }
+}
+
+const SOURCE: &str = "static int helper(int x) { return x + 1; }\n\
+                      __attribute__((visibility(\"hidden\"))) int shared(int x) { return 2 * x; }\n\
+                      int api(int x) { return helper(shared(x)); }\n";
+
+/// Translate `SOURCE` with the given visibility of internal functions
+fn translate(internal_visibility: Option<InternalVisibility>) -> String {
+    common::translate(
+        "fn-visibility",
+        config(internal_visibility),
+        "lib.c",
+        SOURCE,
+    )
+}
+
+/// Check that the function `name` is declared with the visibility `vis`
+fn assert_vis(output: &str, name: &str, vis: &str) {
+    let needle = format!("fn {}(", name);
+    let line = output
+        .lines()
+        .find(|line| line.contains(&needle))
+        .unwrap_or_else(|| panic!("no function {}:\n{}", name, output));
+    let expected = format!("{}unsafe extern \"C\" fn {}(", vis, name);
+    assert!(
+        line.trim().starts_with(&expected),
+        "{} is not `{}`:\n{}",
+        name,
+        vis,
+        output
+    );
+}
+
+#[test]
+fn test_crate_visibility() {
+    let output = translate(Some(InternalVisibility::Crate));
+
+    assert_vis(&output, "helper", "pub(crate) ");
+    assert_vis(&output, "shared", "pub(crate) ");
+    assert_vis(&output, "api", "pub ");
+}
+
+#[test]
+fn test_private_visibility() {
+    let output = translate(Some(InternalVisibility::Private));
+
+    assert_vis(&output, "helper", "");
+    assert_vis(&output, "shared", "pub(crate) ");
+    assert_vis(&output, "api", "pub ");
+}
+
+#[test]
+fn test_default_visibility() {
+    let output = translate(None);
+
+    assert_vis(&output, "helper", "");
+    assert_vis(&output, "shared", "pub ");
+    assert_vis(&output, "api", "pub ");
+}
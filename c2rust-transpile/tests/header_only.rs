@@ -0,0 +1,48 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        header_only: true,
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_header_only() {
+    let output = common::translate(
+        "header-only",
+        config(),
+        "shapes.c",
+        "struct point { int x; int y; };\n\
+         int point_count = 0;\n\
+         static int scale = 2;\n\
+         static int twice(int x) { return x * scale; }\n\
+         int manhattan(struct point p) { point_count++; return twice(p.x) + p.y; }\n",
+    );
+
+    assert!(output.contains("#[repr(C)]"), "missing struct:\n{}", output);
+    assert!(output.contains("pub struct point {"), "missing struct:\n{}", output);
+    assert!(output.contains("extern \"C\" {"), "missing extern block:\n{}", output);
+    assert!(
+        output.contains("pub static mut point_count: libc::c_int;"),
+        "missing declaration of point_count:\n{}",
+        output
+    );
+    assert!(
+        output.lines().any(|line| line.contains("fn manhattan(") && line.ends_with(';')),
+        "missing declaration of manhattan:\n{}",
+        output
+    );
+
+    // Internal definitions and function bodies are left out
+    assert!(!output.contains("scale"), "unexpected static:\n{}", output);
+    assert!(!output.contains("twice"), "unexpected function:\n{}", output);
+    assert!(!output.contains("return"), "unexpected function body:\n{}", output);
+    assert!(!output.contains("unsafe extern \"C\" fn"), "unexpected function definition:\n{}", output);
+}
@@ -0,0 +1,67 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        emit_modules: true,
+        ..common::config()
+    }
+}
+
+/// Declarations that each need something from the header: `long double` needs the `f128`
+/// crate, bit-fields the `c2rust_bitfields` derive, and `offsetof` with a variable index the
+/// `memoffset` macros.
+const DECLS: &[&str] = &[
+    "struct flags { unsigned a : 3; unsigned b : 5; };\n",
+    "struct table { int len; int entries[8]; };\n",
+    "long double scale(long double x) { return x * 2; }\n",
+    "unsigned long entry_offset(int i) { return offsetof(struct table, entries[i]); }\n",
+    "unsigned get_b(struct flags *f) { return f->b; }\n",
+    "int len(struct table *t) { return t->len; }\n",
+];
+
+/// Translate `DECLS` as a module in the order given by `order`, and return the lines of the
+/// header of the output, up to the first line that isn't a `use` or an inner attribute.
+fn translate_header(name: &str, order: &[usize]) -> Vec<String> {
+    let mut source = "#include <stddef.h>\n".to_owned();
+    for &i in order {
+        source.push_str(DECLS[i]);
+    }
+    let name = format!("header-order-{}", name);
+    let output = common::translate(&name, config(), "decls.c", &source);
+
+    output
+        .lines()
+        .take_while(|line| line.is_empty() || line.starts_with("use ") || line.starts_with("#!["))
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn test_header_is_independent_of_declaration_order() {
+    let header = translate_header("forward", &[0, 1, 2, 3, 4, 5]);
+    assert_eq!(
+        header,
+        vec!["use ::f128;", "use ::libc;"],
+        "modules should import exactly the crates their paths refer to, in sorted order"
+    );
+
+    for (name, order) in &[
+        ("reverse", [5, 4, 3, 2, 1, 0]),
+        ("shuffled", [3, 0, 5, 2, 4, 1]),
+        ("interleaved", [4, 2, 0, 5, 1, 3]),
+    ] {
+        assert_eq!(
+            translate_header(name, order),
+            header,
+            "header changed with declaration order {:?}",
+            order
+        );
+    }
+}
@@ -0,0 +1,51 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+use common::Project;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        emit_build_files: true,
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_header_inline_fn_translated_once() {
+    let project = Project::new("inline-fns");
+    project
+        .write(
+            "min.h",
+            "static inline int min(int a, int b) { return a < b ? a : b; }\n",
+        )
+        .write(
+            "first.c",
+            "#include \"min.h\"\nint first_min(int x) { return min(x, 10); }\n",
+        )
+        .write(
+            "second.c",
+            "#include \"min.h\"\nint second_min(int x) { return min(x, 20); }\n",
+        );
+    project.transpile(config(), &["first.c", "second.c"], &[]);
+
+    let first = project.read("first.rs");
+    let second = project.read("second.rs");
+
+    let defs = |output: &str| output.matches("fn min(").count();
+    assert_eq!(defs(&first), 1, "expected a definition of min:\n{}", first);
+    assert!(
+        first.contains("pub unsafe extern \"C\" fn min("),
+        "shared definition of min is not public:\n{}",
+        first
+    );
+    assert_eq!(defs(&second), 0, "unexpected definition of min:\n{}", second);
+    assert!(
+        second.contains("use crate::first::min;"),
+        "missing import of min:\n{}",
+        second
+    );
+}
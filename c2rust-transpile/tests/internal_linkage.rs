@@ -0,0 +1,82 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+use common::Project;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        reorganize_definitions: true,
+        ..common::config()
+    }
+}
+
+const HEADER: &str = "static int helper(void) { return 1; }\n\
+                      static inline int twice(int x) { return 2 * x; }\n";
+
+/// A project with two files that both include a header defining a static function.
+fn project() -> Project {
+    let project = Project::new("internal-linkage");
+    project.write("util.h", HEADER);
+    for unit in &["foo", "bar"] {
+        project.write(
+            &format!("{}.c", unit),
+            format!(
+                "#include \"util.h\"\n\
+                 static int counter;\n\
+                 int {}(void) {{ return helper() + twice(counter++); }}\n",
+                unit
+            ),
+        );
+    }
+    project
+}
+
+/// Translate both files of `project`, and return their translations.
+fn translate(project: &Project) -> Vec<String> {
+    project.transpile(config(), &["foo.c", "bar.c"], &[]);
+    vec![project.read("foo.rs"), project.read("bar.rs")]
+}
+
+#[test]
+fn test_static_definitions_have_internal_linkage() {
+    let project = project();
+    let outputs = translate(&project);
+
+    for output in &outputs {
+        let helper = output.find("fn helper(").expect(output);
+        assert!(
+            output[..helper]
+                .trim_end()
+                .lines()
+                .rev()
+                .take(3)
+                .any(|line| line.trim() == "#[internal_linkage]"),
+            "helper is not marked as internal:\n{}",
+            output
+        );
+        let counter = output.find("static mut counter:").expect(output);
+        assert!(
+            output[..counter]
+                .trim_end()
+                .lines()
+                .rev()
+                .take(3)
+                .any(|line| line.trim() == "#[internal_linkage]"),
+            "counter is not marked as internal:\n{}",
+            output
+        );
+        // Static inline functions are shared like other inline functions
+        assert_eq!(
+            output.matches("#[internal_linkage]").count(),
+            2,
+            "unexpected #[internal_linkage]:\n{}",
+            output
+        );
+    }
+
+    assert_eq!(translate(&project), outputs, "translation changed between runs");
+}
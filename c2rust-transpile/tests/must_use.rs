@@ -0,0 +1,70 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        must_use_error_codes: true,
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_must_use_error_codes() {
+    let output = common::translate(
+        "must-use",
+        config(),
+        "files.c",
+        "static int opened = 0;\n\
+         int open_file(int fd) { if (fd < 0) return -1; opened++; return 0; }\n\
+         int count_files(void) { return opened; }\n\
+         int setup(void) {\n\
+             if (open_file(0) < 0) return -1;\n\
+             int rc = open_file(1);\n\
+             if (rc != 0) return rc;\n\
+             open_file(2);\n\
+             return count_files();\n\
+         }\n",
+    );
+
+    // `open_file` is checked at two of its three call sites
+    let open_file = output.find("fn open_file(").expect("missing open_file");
+    assert!(
+        output[..open_file].trim_end().lines().rev().take(3).any(|line| line.trim() == "#[must_use]"),
+        "missing #[must_use] on open_file:\n{}",
+        output
+    );
+
+    // The results of `count_files` and `setup` are never checked
+    assert_eq!(output.matches("#[must_use]").count(), 1, "unexpected #[must_use]:\n{}", output);
+}
+
+#[test]
+fn test_must_use_hidden_error_codes() {
+    // Hidden functions are translated along with a `global_asm!` setting their visibility
+    let output = common::translate(
+        "must-use-hidden",
+        config(),
+        "hidden.c",
+        "__attribute__((visibility(\"hidden\"))) int close_file(int fd) { return fd < 0 ? -1 : 0; }\n\
+         int teardown(void) {\n\
+             if (close_file(0) < 0) return -1;\n\
+             if (close_file(1) != 0) return -1;\n\
+             close_file(2);\n\
+             return 0;\n\
+         }\n",
+    );
+    assert!(output.contains("global_asm!"), "missing global_asm!:\n{}", output);
+
+    let close_file = output.find("fn close_file(").expect("missing close_file");
+    assert!(
+        output[..close_file].trim_end().lines().rev().take(3).any(|line| line.trim() == "#[must_use]"),
+        "missing #[must_use] on close_file:\n{}",
+        output
+    );
+    assert_eq!(output.matches("#[must_use]").count(), 1, "unexpected #[must_use]:\n{}", output);
+}
@@ -0,0 +1,71 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+use common::Project;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        reorganize_definitions: true,
+        ..common::config()
+    }
+}
+
+/// `offsetof` and `container_of` on a struct defined in another header compute the offset
+/// from the struct with `offset_of!`, which needs the struct imported into the module using it.
+#[test]
+fn test_offset_of_imports_struct() {
+    let project = Project::new("offset-of");
+    project
+        .write(
+            "list.h",
+            "struct list_head { struct list_head *next; };\n\
+             struct item { int value; double weight; struct list_head link; };\n",
+        )
+        .write(
+            "walk.h",
+            "#include <stddef.h>\n\
+             #include \"list.h\"\n\
+             #define container_of(ptr, type, member) \\\n\
+             ((type *)((char *)(ptr) - offsetof(type, member)))\n\
+             static inline struct item *item_of(struct list_head *pos) {\n\
+             return container_of(pos, struct item, link);\n\
+             }\n\
+             static inline size_t weight_offset(void) { return offsetof(struct item, weight); }\n",
+        )
+        .write(
+            "walk.c",
+            "#include \"walk.h\"\n\
+             int first_value(struct list_head *head) { return item_of(head->next)->value; }\n\
+             size_t offsets(void) { return weight_offset() + offsetof(struct item, link); }\n",
+        );
+    let output = project.translate(config(), "walk.c");
+
+    for offset in &["offset_of!(item, weight)", "offset_of!(item, link)"] {
+        assert!(output.contains(offset), "missing {}:\n{}", offset, output);
+    }
+
+    // The module of `walk.h` uses the struct of `list.h`
+    assert!(
+        output.contains("use super::list_h::item;"),
+        "missing struct import:\n{}",
+        output
+    );
+    assert!(
+        output.contains("-> *mut item"),
+        "missing container_of helper:\n{}",
+        output
+    );
+
+    // The offsets aren't copied from the C layout
+    for offset in &["8 as", "16 as"] {
+        assert!(
+            !output.contains(offset),
+            "offset copied as a constant:\n{}",
+            output
+        );
+    }
+}
@@ -0,0 +1,97 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::{OutputStyle, TranspilerConfig};
+
+fn config(style: OutputStyle) -> TranspilerConfig {
+    TranspilerConfig {
+        output_style: Some(style),
+        ..common::config()
+    }
+}
+
+const SOURCE: &str = "unsigned long int_bytes(int n) {\n\
+                          if (n < 0) {\n\
+                              return 0;\n\
+                          }\n\
+                          return n * sizeof(int);\n\
+                      }\n";
+
+/// Translate `SOURCE` in the given style and return the translated `int_bytes` function along
+/// with the imports of the file.
+fn translate(style: OutputStyle) -> (String, String) {
+    let output = common::translate("output-style", config(style), "bytes.c", SOURCE);
+
+    let uses = output
+        .lines()
+        .filter(|line| line.starts_with("use "))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let start = output.find("pub unsafe extern \"C\" fn int_bytes").expect(&output);
+    let end = start + output[start..].find("\n}\n").expect(&output) + 3;
+    (uses, output[start..end].to_string())
+}
+
+#[test]
+fn test_explicit_output_style() {
+    let (uses, function) = translate(OutputStyle::Explicit);
+
+    assert_eq!(uses, "", "unexpected imports");
+    assert!(
+        function.contains("if (n < 0 as libc::c_int) as libc::c_int != 0 {"),
+        "condition is not explicit:\n{}",
+        function
+    );
+    assert!(
+        function.contains("return 0 as libc::c_int as libc::c_ulong;"),
+        "cast is not explicit:\n{}",
+        function
+    );
+    assert!(
+        function.contains("::std::mem::size_of::<libc::c_int>()"),
+        "path is not explicit:\n{}",
+        function
+    );
+    assert_eq!(
+        function.matches("return ").count(),
+        2,
+        "function does not end in a return statement:\n{}",
+        function
+    );
+}
+
+#[test]
+fn test_readable_output_style() {
+    let (uses, function) = translate(OutputStyle::Readable);
+
+    assert_eq!(uses, "use std::mem;", "unexpected imports");
+    assert!(
+        function.contains("if n < 0 as libc::c_int {"),
+        "condition is not simplified:\n{}",
+        function
+    );
+    assert!(
+        function.contains("return 0 as libc::c_ulong;"),
+        "redundant cast is kept:\n{}",
+        function
+    );
+    assert!(
+        function.contains(" mem::size_of::<libc::c_int>()") && !function.contains("::std::"),
+        "path is not imported:\n{}",
+        function
+    );
+    assert_eq!(
+        function.matches("return ").count(),
+        1,
+        "function does not end in a trailing expression:\n{}",
+        function
+    );
+    assert!(
+        !function.lines().rev().nth(1).unwrap().ends_with(';'),
+        "function does not end in a trailing expression:\n{}",
+        function
+    );
+}
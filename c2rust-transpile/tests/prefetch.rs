@@ -0,0 +1,57 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+
+fn config(translate_prefetch: bool) -> TranspilerConfig {
+    TranspilerConfig {
+        translate_prefetch,
+        ..common::config()
+    }
+}
+
+fn transpile(name: &str, translate_prefetch: bool) -> String {
+    common::translate(
+        &format!("prefetch-{}", name),
+        config(translate_prefetch),
+        "prefetch.c",
+        "static int next(int **p) { return *(*p)++; }\n\
+         int sum(int *xs, int n) {\n\
+             int total = 0;\n\
+             for (int i = 0; i < n; i++) {\n\
+                 __builtin_prefetch(&xs[i + 8]);\n\
+                 __builtin_prefetch(&xs[i + 16], 1, 0);\n\
+                 total += xs[i];\n\
+             }\n\
+             int *cursor = xs;\n\
+             __builtin_prefetch((void *)(next(&cursor), cursor));\n\
+             return total + *cursor;\n\
+         }\n",
+    )
+}
+
+#[test]
+fn test_prefetch_intrinsics() {
+    let output = transpile("intrinsics", true);
+    assert!(output.contains("#![feature(core_intrinsics)]"), "{}", output);
+
+    // The defaults are a read with high temporal locality
+    let reads = output.matches("::std::intrinsics::prefetch_read_data(").count();
+    let writes = output.matches("::std::intrinsics::prefetch_write_data(").count();
+    assert_eq!((reads, writes), (2, 1), "{}", output);
+    assert!(output.contains("3i32)"), "missing default locality:\n{}", output);
+    assert!(output.contains("0i32)"), "missing locality:\n{}", output);
+}
+
+#[test]
+fn test_prefetch_stable() {
+    let output = transpile("stable", false);
+    assert!(!output.contains("intrinsics::prefetch"), "{}", output);
+    assert!(!output.contains("core_intrinsics"), "{}", output);
+
+    // The address is still evaluated for its side effects
+    assert_eq!(output.matches("next(&mut cursor)").count(), 1, "{}", output);
+}
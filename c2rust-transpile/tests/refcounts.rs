@@ -0,0 +1,63 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+use common::Project;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        detect_refcounts: true,
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_detect_refcounts() {
+    let project = Project::new("refcounts");
+    project.write(
+        "strings.c",
+        "#include <stdlib.h>\n\
+         #include <string.h>\n\
+         struct string { int refs; size_t len; char *data; };\n\
+         struct string *string_new(const char *s) {\n\
+             struct string *str = malloc(sizeof(struct string));\n\
+             str->refs = 1;\n\
+             str->len = strlen(s);\n\
+             str->data = strdup(s);\n\
+             return str;\n\
+         }\n\
+         struct string *string_retain(struct string *str) { str->refs++; return str; }\n\
+         static void string_free(struct string *str) { free(str->data); free(str); }\n\
+         void string_release(struct string *str) {\n\
+             if (--str->refs == 0) string_free(str);\n\
+         }\n\
+         struct counter { int count; };\n\
+         int counter_bump(struct counter *c) { c->count++; return c->count; }\n",
+    );
+    let output = project.translate(config(), "strings.c");
+    let report = project.read("strings.refcounts.json");
+
+    assert!(output.contains("#![feature(custom_attribute)]"), "missing feature:\n{}", output);
+    assert!(
+        output.contains("#[refcount_field]\n    pub refs: libc::c_int,"),
+        "missing #[refcount_field] on refs:\n{}",
+        output
+    );
+    // `count` is read outside of a check guarding a free
+    assert_eq!(output.matches("#[refcount_field]").count(), 1, "unexpected marks:\n{}", output);
+
+    assert!(report.contains(r#""struct": "string""#), "bad report:\n{}", report);
+    assert!(report.contains(r#""field": "refs""#), "bad report:\n{}", report);
+    let section = |name: &str| {
+        let start = report.find(&format!("\"{}\"", name)).unwrap();
+        let end = report[start..].find(']').unwrap();
+        report[start..start + end].to_string()
+    };
+    assert!(section("increments").contains(r#""function": "string_retain""#), "{}", report);
+    assert!(section("decrements").contains(r#""function": "string_release""#), "{}", report);
+    assert!(section("frees").contains(r#""function": "string_release""#), "{}", report);
+    assert!(!report.contains("counter_bump"), "bad report:\n{}", report);
+}
@@ -0,0 +1,93 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::{RenameCollisions, TranspilerConfig};
+
+fn config(rename_collisions: RenameCollisions) -> TranspilerConfig {
+    TranspilerConfig {
+        rename_collisions,
+        ..common::config()
+    }
+}
+
+const SOURCE: &str = "struct token { int match; int type; };\n\
+                      int move(struct token *t) {\n\
+                      int ref = t->match;\n\
+                      t->type = ref;\n\
+                      return t->match + ref;\n\
+                      }\n";
+
+/// Translate `SOURCE`, renaming keywords as `rename_collisions` says.
+fn translate(rename_collisions: RenameCollisions) -> String {
+    common::translate(
+        "rename-collisions",
+        config(rename_collisions),
+        "token.c",
+        SOURCE,
+    )
+}
+
+#[test]
+fn test_number_keyword_collisions() {
+    let output = translate(RenameCollisions::Number);
+
+    assert!(
+        output.contains("pub match_0: libc::c_int,"),
+        "field is not renamed:\n{}",
+        output
+    );
+    assert!(
+        output.contains("fn move_0("),
+        "function is not renamed:\n{}",
+        output
+    );
+}
+
+#[test]
+fn test_underscore_keyword_collisions() {
+    let output = translate(RenameCollisions::Underscore);
+
+    // The field is renamed in its declaration and at every access
+    assert!(
+        output.contains("pub match_: libc::c_int,"),
+        "field is not renamed:\n{}",
+        output
+    );
+    assert_eq!(
+        output.matches("(*t).match_").count(),
+        2,
+        "field access is not renamed:\n{}",
+        output
+    );
+    assert!(
+        output.contains("pub type_: libc::c_int,"),
+        "field is not renamed:\n{}",
+        output
+    );
+    assert!(
+        output.contains("(*t).type_ = ref_;"),
+        "local is not renamed:\n{}",
+        output
+    );
+
+    // The function keeps its C symbol name
+    assert!(
+        output.contains("#[export_name = \"move\"]"),
+        "symbol is renamed:\n{}",
+        output
+    );
+    assert!(
+        output.contains("fn move_("),
+        "function is not renamed:\n{}",
+        output
+    );
+
+    assert!(
+        !output.contains("_0") && !output.contains("r#"),
+        "keyword is not renamed:\n{}",
+        output
+    );
+}
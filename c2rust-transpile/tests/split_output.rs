@@ -0,0 +1,62 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+use common::Project;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        split_output_size: Some(2048),
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_split_output_size() {
+    // Only `double` and `bool` are used, so the output builds without `libc`
+    let mut source = "struct point { double x; double y; };\n\
+                      static double total = 0;\n\
+                      static double scale(double v) { return v * 2; }\n"
+        .to_owned();
+    for i in 0..40 {
+        source.push_str(&format!(
+            "// Adds point {i}\n\
+             double add_{i}(struct point p) {{ total += scale(p.x) + p.y + {i}; return total; }}\n",
+            i = i
+        ));
+    }
+    source.push_str("_Bool check(void) { struct point p = { 1, 2 }; return add_39(p) > 0; }\n");
+    let project = Project::new("split");
+    project.write("split.c", source);
+    let output = project.translate(config(), "split.c");
+    let parts = (1..)
+        .map(|n| format!("split/part_{}.rs", n))
+        .take_while(|part| project.path(part).exists())
+        .map(|part| project.read(&part))
+        .collect::<Vec<_>>();
+
+    let build = project.rustc("split.rs", &["--crate-type", "lib", "--emit", "metadata"]);
+
+    assert!(parts.len() > 1, "output was not split:\n{}", output);
+    assert!(
+        output.contains("#[path = \"split/part_1.rs\"]\nmod part_1;"),
+        "missing part declaration:\n{}",
+        output
+    );
+    assert!(output.contains("pub use self::part_1::*;"), "missing reexport:\n{}", output);
+    assert!(!output.contains("fn add_"), "definitions left in the file:\n{}", output);
+    for part in &parts {
+        assert!(part.starts_with("use super::*;"), "{}", part);
+    }
+    let all_parts = parts.concat();
+    assert!(all_parts.contains("pub(super) static mut total: f64"), "{}", all_parts);
+    assert!(all_parts.contains("pub(super) unsafe extern \"C\" fn scale("), "{}", all_parts);
+    // Comments move with their definitions
+    assert!(all_parts.contains("// Adds point 39\n"), "{}", all_parts);
+
+    // The parts still see the file and each other
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+}
@@ -0,0 +1,73 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+use common::Project;
+
+fn config() -> TranspilerConfig {
+    TranspilerConfig {
+        strict_bit_ints: true,
+        reorganize_definitions: true,
+        ..common::config()
+    }
+}
+
+#[test]
+fn test_strict_bit_int_newtypes() {
+    let project = Project::new("strict-bit-ints");
+    project
+        .write(
+            "bits.h",
+            "typedef _BitInt(24) s24;\n\
+             static inline s24 halve(s24 x) { return x / 2; }\n",
+        )
+        .write(
+            "bits.c",
+            "#include \"bits.h\"\n\
+             unsigned _BitInt(7) low_bits(int v) { return v; }\n\
+             int halve_int(int v) { s24 s = v; return halve(s) + low_bits(v); }\n",
+        );
+    project.transpile(config(), &["bits.c"], &["-std=c2x"]);
+    let output = project.read("bits.rs");
+
+    // Each width used gets a newtype over its storage integer in the main module
+    for (newtype, storage) in &[("BitInt24", "i32"), ("UBitInt7", "u8")] {
+        let definition = format!("pub struct {}(pub {});", newtype, storage);
+        assert_eq!(
+            output.matches(&definition).count(),
+            1,
+            "missing {}:\n{}",
+            definition,
+            output
+        );
+        let new = format!("pub const fn new(value: {}) -> Self", storage);
+        assert!(output.contains(&new), "missing {}:\n{}", new, output);
+    }
+    assert!(
+        output.contains("#[repr(transparent)]"),
+        "newtypes aren't transparent:\n{}",
+        output
+    );
+
+    // The header module defining `s24` and `halve` imports it
+    assert!(
+        output.contains("use super::BitInt24;"),
+        "missing newtype import:\n{}",
+        output
+    );
+
+    // Values only go into the newtypes through `new`
+    assert!(
+        !output.contains("as BitInt24"),
+        "cast into a newtype:\n{}",
+        output
+    );
+    assert!(
+        !output.contains("as UBitInt7"),
+        "cast into a newtype:\n{}",
+        output
+    );
+}
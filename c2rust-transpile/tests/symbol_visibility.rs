@@ -0,0 +1,74 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use std::process::Command;
+
+use common::Project;
+
+#[test]
+fn test_hidden_symbols_not_exported() {
+    let project = Project::new("symbol-visibility");
+    project.write(
+        "vis.c",
+        "__attribute__((visibility(\"hidden\"))) _Bool hidden_fn(_Bool x) { return !x; }\n\
+         __attribute__((visibility(\"default\"))) _Bool default_fn(_Bool x) { return x; }\n\
+         __attribute__((visibility(\"protected\"))) _Bool protected_fn(_Bool x) { return x; }\n\
+         __attribute__((visibility(\"hidden\"))) _Bool hidden_var = 1;\n\
+         _Bool plain_var = 0;\n\
+         _Bool plain_fn(_Bool x) { return hidden_fn(x) && hidden_var; }\n",
+    );
+    project.transpile(common::config(), &["vis.c"], &[]);
+
+    // Only `bool` is used, so the output builds without `libc`
+    let build = project.rustc(
+        "vis.rs",
+        &[
+            "--crate-type",
+            "cdylib",
+            "--crate-name",
+            "vis",
+            "-C",
+            "codegen-units=16",
+        ],
+    );
+    assert!(
+        build.status.success(),
+        "{}",
+        String::from_utf8_lossy(&build.stderr)
+    );
+
+    let nm = Command::new("nm")
+        .args(&["-D", "--defined-only"])
+        .arg(project.path("libvis.so"))
+        .output()
+        .unwrap();
+    assert!(
+        nm.status.success(),
+        "{}",
+        String::from_utf8_lossy(&nm.stderr)
+    );
+    let exported = String::from_utf8_lossy(&nm.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(2).map(str::to_owned))
+        .collect::<Vec<_>>();
+
+    for symbol in &["default_fn", "protected_fn", "plain_fn", "plain_var"] {
+        assert!(
+            exported.iter().any(|s| s == symbol),
+            "{} not exported: {:?}",
+            symbol,
+            exported
+        );
+    }
+    for symbol in &["hidden_fn", "hidden_var"] {
+        assert!(
+            !exported.iter().any(|s| s == symbol),
+            "{} exported: {:?}",
+            symbol,
+            exported
+        );
+    }
+}
@@ -0,0 +1,62 @@
+#![feature(rustc_private)]
+
+extern crate c2rust_transpile;
+
+mod common;
+
+use c2rust_transpile::TranspilerConfig;
+
+fn config(skip_system_headers: bool, system_type_allowlist: Vec<String>) -> TranspilerConfig {
+    TranspilerConfig {
+        skip_system_headers,
+        system_type_allowlist,
+        ..common::config()
+    }
+}
+
+const SOURCE: &str = "#include <stdio.h>\n\
+                      #include <sys/stat.h>\n\
+                      long file_size(const char *path) {\n\
+                          struct stat st;\n\
+                          if (stat(path, &st) != 0) return -1;\n\
+                          return st.st_size;\n\
+                      }\n\
+                      int first_byte(FILE *f) { return fgetc(f); }\n";
+
+/// Translate `SOURCE` with or without skipping system headers
+fn translate(name: &str, skip_system_headers: bool, system_type_allowlist: Vec<String>) -> String {
+    common::translate(
+        &format!("system-headers-{}", name),
+        config(skip_system_headers, system_type_allowlist),
+        "lib.c",
+        SOURCE,
+    )
+}
+
+#[test]
+fn test_skip_system_headers() {
+    let output = translate("skip", true, vec![]);
+
+    assert!(output.contains("pub type FILE = libc::FILE;"), "{}", output);
+    assert!(output.contains("pub type stat = libc::stat;"), "{}", output);
+    assert!(!output.contains("pub struct _IO_FILE"), "{}", output);
+    assert!(!output.contains("pub struct stat"), "{}", output);
+    assert!(output.contains("fn file_size("), "{}", output);
+    assert!(output.contains("fn first_byte("), "{}", output);
+}
+
+#[test]
+fn test_system_type_allowlist() {
+    let output = translate("allowlist", true, vec!["stat".to_owned()]);
+
+    assert!(output.contains("pub type FILE = libc::FILE;"), "{}", output);
+    assert!(output.contains("pub struct stat {"), "{}", output);
+}
+
+#[test]
+fn test_translate_system_headers() {
+    let output = translate("translate", false, vec![]);
+
+    assert!(!output.contains("= libc::FILE;"), "{}", output);
+    assert!(output.contains("pub struct stat {"), "{}", output);
+}
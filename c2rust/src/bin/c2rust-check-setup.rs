@@ -0,0 +1,402 @@
+extern crate c2rust_ast_exporter;
+#[macro_use]
+extern crate clap;
+#[macro_use]
+extern crate serde_json;
+
+use clap::App;
+use serde_json::Value;
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// Toolchain that C2Rust must be built and run with
+const RUST_TOOLCHAIN: &str = include_str!("../../rust-toolchain");
+
+/// Components of the pinned toolchain that C2Rust needs, if the toolchain provides them
+const RUST_COMPONENTS: &[&str] = &["rustc-dev", "rustfmt"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => "error",
+        }
+    }
+}
+
+/// Result of a single environment check
+#[derive(Debug)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    message: String,
+    /// How to fix the problem, if there is one
+    remediation: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: String) -> Self {
+        Check {
+            name,
+            status: Status::Ok,
+            message,
+            remediation: None,
+        }
+    }
+
+    fn warning(name: &'static str, message: String, remediation: String) -> Self {
+        Check {
+            name,
+            status: Status::Warning,
+            message,
+            remediation: Some(remediation),
+        }
+    }
+
+    fn error(name: &'static str, message: String, remediation: String) -> Self {
+        Check {
+            name,
+            status: Status::Error,
+            message,
+            remediation: Some(remediation),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "status": self.status.as_str(),
+            "message": self.message,
+            "remediation": self.remediation,
+        })
+    }
+}
+
+fn main() {
+    let yaml = load_yaml!("../check-setup.yaml");
+    let matches = App::from_yaml(yaml).get_matches();
+
+    let mut checks = vec![check_clang(), check_toolchain(), check_exporter()];
+    if let Some(cc_db) = matches.value_of("COMPILE_COMMANDS") {
+        checks.push(check_compile_commands(Path::new(cc_db)));
+    }
+    let healthy = checks.iter().all(|check| check.status != Status::Error);
+
+    if matches.is_present("json") {
+        let report = json!({
+            "healthy": healthy,
+            "checks": checks.iter().map(Check::to_json).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        for check in &checks {
+            println!("[{}] {}: {}", check.status.as_str(), check.name, check.message);
+            if let Some(ref remediation) = check.remediation {
+                println!("    fix: {}", remediation);
+            }
+        }
+    }
+
+    if !healthy {
+        process::exit(1);
+    }
+}
+
+/// Run a command and get its standard output, if it succeeded.
+fn command_output(cmd: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(cmd).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Find the version of the installed LLVM, preferring the `llvm-config` that
+/// C2Rust is built with.
+fn installed_llvm_version() -> Option<(String, String)> {
+    let llvm_config = env::var("LLVM_CONFIG_PATH").unwrap_or_else(|_| "llvm-config".to_owned());
+    if let Some(version) = command_output(&llvm_config, &["--version"]) {
+        return Some((llvm_config, version.trim().to_owned()));
+    }
+
+    // `clang --version` prints e.g. `clang version 7.0.1-8 (tags/RELEASE_701/final)`
+    let output = command_output("clang", &["--version"])?;
+    let version = output
+        .split_whitespace()
+        .skip_while(|&word| word != "version")
+        .nth(1)?;
+    Some(("clang".to_owned(), version.to_owned()))
+}
+
+fn major_version(version: &str) -> &str {
+    version.split('.').next().unwrap_or(version)
+}
+
+fn check_clang() -> Check {
+    let name = "clang";
+    let exporter_version = c2rust_ast_exporter::get_clang_version();
+    let (tool, installed_version) = match installed_llvm_version() {
+        Some(x) => x,
+        None => {
+            return Check::error(
+                name,
+                format!(
+                    "no llvm-config or clang was found; the AST exporter was built against clang {}",
+                    exporter_version
+                ),
+                format!(
+                    "install LLVM and clang {}, or set LLVM_CONFIG_PATH to its llvm-config",
+                    major_version(exporter_version)
+                ),
+            );
+        }
+    };
+
+    if major_version(&installed_version) != major_version(exporter_version) {
+        return Check::error(
+            name,
+            format!(
+                "the AST exporter was built against clang {}, but {} reports version {}",
+                exporter_version, tool, installed_version
+            ),
+            format!(
+                "rebuild C2Rust against the installed LLVM with \
+                 `LLVM_CONFIG_PATH=/path/to/llvm-config cargo +{} install --force c2rust`, \
+                 or install LLVM {}",
+                RUST_TOOLCHAIN.trim(),
+                major_version(exporter_version)
+            ),
+        );
+    }
+    Check::ok(
+        name,
+        format!(
+            "the AST exporter was built against clang {}, and {} reports version {}",
+            exporter_version, tool, installed_version
+        ),
+    )
+}
+
+fn check_toolchain() -> Check {
+    let name = "rust-toolchain";
+    let toolchain = RUST_TOOLCHAIN.trim();
+    let components = match command_output("rustup", &["component", "list", "--toolchain", toolchain]) {
+        Some(components) => components,
+        None => {
+            return Check::error(
+                name,
+                format!("could not list the components of the {} toolchain", toolchain),
+                format!("install the toolchain with `rustup install {}`", toolchain),
+            );
+        }
+    };
+
+    // Lines look like `rustfmt-x86_64-unknown-linux-gnu (installed)`; components
+    // that aren't listed at all aren't available for the pinned toolchain
+    let missing = RUST_COMPONENTS
+        .iter()
+        .filter(|&&component| {
+            let lines = components
+                .lines()
+                .filter(|line| {
+                    let package = line.split_whitespace().next().unwrap_or("");
+                    package == component || package.starts_with(&format!("{}-", component))
+                })
+                .collect::<Vec<_>>();
+            let installed = lines
+                .iter()
+                .any(|line| line.ends_with("(installed)") || line.ends_with("(default)"));
+            !lines.is_empty() && !installed
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Check::error(
+            name,
+            format!(
+                "the {} toolchain is missing {}",
+                toolchain,
+                missing.join(", ")
+            ),
+            format!(
+                "`rustup component add --toolchain {} {}`",
+                toolchain,
+                missing.join(" ")
+            ),
+        );
+    }
+    Check::ok(name, format!("the {} toolchain is installed", toolchain))
+}
+
+/// Export the AST of a trivial C file to make sure the exporter works end-to-end.
+fn check_exporter() -> Check {
+    let name = "ast-exporter";
+    let dir = env::temp_dir().join(format!("c2rust-check-setup-{}", process::id()));
+    let result = export_test_file(&dir);
+    let _ = fs::remove_dir_all(&dir);
+    match result {
+        Ok(()) => Check::ok(name, "parsed a test C file".to_owned()),
+        Err(e) => Check::error(
+            name,
+            format!("could not parse a test C file: {}", e),
+            "make sure the clang headers are installed (e.g., the libclang-dev package) and \
+             that the installed clang matches the version C2Rust was built against"
+                .to_owned(),
+        ),
+    }
+}
+
+fn export_test_file(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let file = dir.join("check.c");
+    fs::write(&file, "int main(void) { return 0; }\n").map_err(|e| e.to_string())?;
+    let cc_db = dir.join("compile_commands.json");
+    let cmds = json!([{
+        "directory": dir,
+        "arguments": ["cc", "-c", "check.c"],
+        "file": "check.c",
+    }]);
+    fs::write(&cc_db, cmds.to_string()).map_err(|e| e.to_string())?;
+
+    c2rust_ast_exporter::get_untyped_ast(&file, &cc_db, &[], false)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Check that `cc_db` is a compilation database whose source files all exist.
+fn check_compile_commands(cc_db: &Path) -> Check {
+    let name = "compile-commands";
+    let regenerate = "regenerate it from a clean build, e.g., with \
+                      `cmake -DCMAKE_EXPORT_COMPILE_COMMANDS=1` or `intercept-build make`";
+
+    let file = match File::open(cc_db) {
+        Ok(file) => file,
+        Err(e) => {
+            return Check::error(
+                name,
+                format!("could not open {}: {}", cc_db.display(), e),
+                format!("check the path to compile_commands.json, or {}", regenerate),
+            );
+        }
+    };
+    let entries: Vec<Value> = match serde_json::from_reader(file) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Check::error(
+                name,
+                format!("{} is not a valid compilation database: {}", cc_db.display(), e),
+                regenerate.to_owned(),
+            );
+        }
+    };
+    if entries.is_empty() {
+        return Check::warning(
+            name,
+            format!("{} has no entries", cc_db.display()),
+            format!("make sure the build compiles something, then {}", regenerate),
+        );
+    }
+
+    let missing = entries
+        .iter()
+        .filter_map(|entry| {
+            let file = PathBuf::from(entry["file"].as_str()?);
+            let dir = entry["directory"].as_str().unwrap_or("");
+            let path = Path::new(dir).join(file);
+            if path.exists() {
+                None
+            } else {
+                Some(path.display().to_string())
+            }
+        })
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Check::error(
+            name,
+            format!(
+                "{} has entries for files that don't exist: {}",
+                cc_db.display(),
+                missing.join(", ")
+            ),
+            format!("the database is stale; {}", regenerate),
+        );
+    }
+    Check::ok(
+        name,
+        format!("all {} entries in {} exist", entries.len(), cc_db.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_cc_db(dir: &Path, files: &[&str]) -> PathBuf {
+        let cmds = files
+            .iter()
+            .map(|file| {
+                json!({
+                    "directory": dir,
+                    "arguments": ["cc", "-c", file],
+                    "file": file,
+                })
+            })
+            .collect::<Vec<_>>();
+        let cc_db = dir.join("compile_commands.json");
+        fs::write(&cc_db, Value::Array(cmds).to_string()).unwrap();
+        cc_db
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("c2rust-check-setup-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_missing_compile_commands() {
+        let dir = test_dir("missing");
+        let check = check_compile_commands(&dir.join("compile_commands.json"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(check.status, Status::Error);
+        assert!(check.message.contains("could not open"), "{}", check.message);
+        assert!(check.remediation.is_some());
+    }
+
+    #[test]
+    fn test_stale_compile_commands() {
+        let dir = test_dir("stale");
+        fs::write(dir.join("present.c"), "int x;\n").unwrap();
+        let cc_db = write_cc_db(&dir, &["present.c", "removed.c"]);
+        let check = check_compile_commands(&cc_db);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(check.status, Status::Error);
+        assert!(check.message.contains("removed.c"), "{}", check.message);
+        assert!(!check.message.contains("present.c"), "{}", check.message);
+        assert!(check.remediation.unwrap().contains("stale"));
+    }
+
+    #[test]
+    fn test_valid_compile_commands() {
+        let dir = test_dir("valid");
+        fs::write(dir.join("present.c"), "int x;\n").unwrap();
+        let cc_db = write_cc_db(&dir, &["present.c"]);
+        let check = check_compile_commands(&cc_db);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(check.status, Status::Ok, "{}", check.message);
+        assert!(check.remediation.is_none());
+    }
+}
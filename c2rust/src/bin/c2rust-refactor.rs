@@ -12,7 +12,9 @@ use std::io::Read;
 use std::process;
 use std::str::FromStr;
 
-use c2rust_refactor::{file_io, CargoTarget, Command, Cursor, Mark, Options, RustcArgSource};
+use c2rust_refactor::{
+    file_io, CargoTarget, Checkpoint, Command, Cursor, Mark, Options, RustcArgSource,
+};
 
 fn main() {
     let yaml = load_yaml!("../refactor.yaml");
@@ -132,6 +134,11 @@ fn parse_opts(args: &ArgMatches) -> Option<Options> {
     let plugins = args.values_of_lossy("plugin-name").unwrap_or(vec![]);
     let plugin_dirs = args.values_of_lossy("plugin-dir").unwrap_or(vec![]);
 
+    let checkpoint = args.value_of("checkpoint-cmd").map(|cmd| Checkpoint {
+        cmd: cmd.to_owned(),
+        bisect: args.is_present("bisect"),
+    });
+
     // Handle --cargo and rustc-args
     let rustc_args = match args.values_of_lossy("rustc-args") {
         Some(args) => RustcArgSource::CmdLine(args),
@@ -195,6 +202,7 @@ fn parse_opts(args: &ArgMatches) -> Option<Options> {
         rustc_args,
         cursors,
         marks,
+        checkpoint,
         plugins,
         plugin_dirs,
     })
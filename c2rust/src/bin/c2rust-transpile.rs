@@ -2,13 +2,16 @@
 extern crate clap;
 extern crate c2rust_transpile;
 
-use clap::{App, Values};
+use clap::{App, ArgMatches, Values};
 use regex::Regex;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use c2rust_transpile::{Diagnostic, ReplaceMode, TranspilerConfig};
+use c2rust_transpile::{
+    Diagnostic, InternalVisibility, OutputStyle, OutputStyleOverrides, PthreadsBackend,
+    RenameCollisions, ReplaceMode, StringLiterals, TranspilerConfig,
+};
 
 fn main() {
     let yaml = load_yaml!("../transpile.yaml");
@@ -42,6 +45,42 @@ fn main() {
         _ => panic!("Invalid log level"),
     };
 
+    let output_style = match matches.value_of("output-style") {
+        Some("explicit") => Some(OutputStyle::Explicit),
+        Some("readable") => Some(OutputStyle::Readable),
+        None => None,
+        _ => panic!("Invalid output style"),
+    };
+
+    let string_literals = match matches.value_of("string-literals") {
+        Some("shared") => StringLiterals::Shared,
+        Some("unique-per-use") => StringLiterals::UniquePerUse,
+        _ => panic!("Invalid string literal sharing"),
+    };
+
+    let rename_collisions = match matches.value_of("rename-collisions") {
+        Some("number") => RenameCollisions::Number,
+        Some("underscore") => RenameCollisions::Underscore,
+        _ => panic!("Invalid keyword renaming"),
+    };
+
+    let internal_visibility = match matches.value_of("internal-visibility") {
+        Some("crate") => Some(InternalVisibility::Crate),
+        Some("private") => Some(InternalVisibility::Private),
+        None => None,
+        _ => panic!("Invalid internal visibility"),
+    };
+
+    let translate_pthreads = if matches.is_present("translate-pthreads") {
+        match matches.value_of("translate-pthreads") {
+            None | Some("std") => Some(PthreadsBackend::Std),
+            Some("parking-lot") => Some(PthreadsBackend::ParkingLot),
+            _ => panic!("Invalid pthreads backend"),
+        }
+    } else {
+        None
+    };
+
     let mut tcfg = TranspilerConfig {
         dump_untyped_context: matches.is_present("dump-untyped-clang-ast"),
         dump_typed_context: matches.is_present("dump-typed-clang-ast"),
@@ -76,18 +115,47 @@ fn main() {
             .unwrap_or_default(),
         prefix_function_names: matches.value_of("prefix-function-names").map(String::from),
 
-        // We used to guard asm translation with a command-line
-        // option. Defaulting to enabled now, can add an option to disable if
-        // needed.
-        translate_asm: true,
+        translate_asm: !matches.is_present("no-translate-asm"),
 
         // We used to guard varargs with a command-line option before nightly
         // support landed. We may still want to disable this option to target
         // stable rust output.
         translate_valist: true,
 
+        translate_prefetch: !matches.is_present("no-prefetch-intrinsics"),
+
         translate_const_macros: matches.is_present("translate-const-macros"),
+        translate_fn_macros: matches.is_present("translate-fn-macros"),
+        inline_fn_macros: matches.is_present("inline-fn-macros"),
+        translate_enums: matches.is_present("translate-enums"),
+        emit_repr_transparent: matches.is_present("emit-repr-transparent"),
+        strict_bit_ints: matches.is_present("strict-bit-ints"),
+        translate_fn_tables: matches.is_present("translate-fn-tables"),
+        translate_string_tables: matches.is_present("translate-string-tables"),
+        string_literals,
+        translate_blocks: matches.is_present("translate-blocks"),
+        rename_collisions,
+        must_use_error_codes: matches.is_present("must-use-error-codes"),
+        detect_refcounts: matches.is_present("detect-refcounts"),
+        embed_byte_arrays: matches.value_of("embed-byte-arrays").map(|min_len| {
+            min_len.parse().expect("--embed-byte-arrays takes a number of elements")
+        }),
+        split_output_size: matches.value_of("split-output-size").map(|size| {
+            size.parse().expect("--split-output-size takes a number of bytes")
+        }),
+        log_functions: matches
+            .values_of("log-function")
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default(),
+        translate_pthreads,
         disable_refactoring: matches.is_present("disable-refactoring"),
+        header_only: matches.is_present("header-only"),
+        internal_visibility,
+        skip_system_headers: !matches.is_present("translate-system-headers"),
+        system_type_allowlist: matches
+            .values_of("system-type-allowlist")
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default(),
 
         use_c_loop_info: !matches.is_present("ignore-c-loop-info"),
         use_c_multiple_info: !matches.is_present("ignore-c-multiple-info"),
@@ -110,9 +178,18 @@ fn main() {
             }
         },
         replace_unsupported_decls: ReplaceMode::Extern,
+        shim_untranslatable_stmts: matches.is_present("shim-untranslatable-stmts"),
         emit_no_std: matches.is_present("emit-no-std"),
         enabled_warnings,
         log_level,
+        output_style,
+        output_style_overrides: OutputStyleOverrides {
+            import_std_paths: style_setting(&matches, "import-std-paths"),
+            elide_redundant_casts: style_setting(&matches, "elide-redundant-casts"),
+            trailing_return_exprs: style_setting(&matches, "trailing-return-exprs"),
+            simplify_bool_conditions: style_setting(&matches, "simplify-bool-conditions"),
+        },
+        ast_transforms: Default::default(),
     };
     // binaries imply emit-build-files
     if !tcfg.binaries.is_empty() {
@@ -125,3 +202,13 @@ fn main() {
 
     c2rust_transpile::transpile(tcfg, &cc_json_path, &extra_args);
 }
+
+/// Read an `on`/`off` setting that overrides part of `--output-style`
+fn style_setting(matches: &ArgMatches, name: &str) -> Option<bool> {
+    match matches.value_of(name) {
+        Some("on") => Some(true),
+        Some("off") => Some(false),
+        None => None,
+        _ => panic!("Invalid value for --{}", name),
+    }
+}
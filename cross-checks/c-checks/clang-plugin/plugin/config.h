@@ -23,6 +23,9 @@ enum XCheckTag : unsigned {
     XCHECK_TAG_FUNCTION_EXIT   = 2,
     XCHECK_TAG_FUNCTION_ARG    = 3,
     XCHECK_TAG_FUNCTION_RETURN = 4,
+    // Arguments selected with `args: [...]` are checked
+    // with this tag plus the index of the argument
+    XCHECK_TAG_FUNCTION_ARG_INDEX = 16,
 };
 
 enum ItemKind : unsigned {
@@ -150,6 +153,7 @@ XCheck *xcfg_scope_ret_xcheck(const ScopeConfig*);
 StringLenPtr xcfg_scope_ahasher(const ScopeConfig*);
 StringLenPtr xcfg_scope_shasher(const ScopeConfig*);
 XCheck *xcfg_scope_function_arg(const ScopeConfig*, StringLenPtr, unsigned);
+unsigned xcfg_scope_function_arg_selected(const ScopeConfig*, StringLenPtr, unsigned);
 VecLenPtr<ExtraXCheck> xcfg_scope_function_entry_extra(const ScopeConfig*);
 VecLenPtr<ExtraXCheck> xcfg_scope_function_exit_extra(const ScopeConfig*);
 StringLenPtr xcfg_scope_struct_custom_hash(const ScopeConfig*);
@@ -11,11 +11,13 @@
 #include "llvm/Option/ArgList.h"
 #include "llvm/Option/OptTable.h"
 #include "llvm/Option/Option.h"
+#include "llvm/Support/FileSystem.h"
 #include "llvm/Support/MemoryBuffer.h"
 #include "llvm/Support/YAMLTraits.h"
 
 #include <functional>
 #include <optional>
+#include <set>
 #include <string_view>
 
 #include "config.h"
@@ -315,6 +317,7 @@ CrossCheckInserter::build_xcheck(const XCheck &xcheck, config::XCheckTag tag,
 
 CrossCheckInserter::TinyStmtVec
 CrossCheckInserter::build_parameter_xcheck(ParmVarDecl *param,
+                                           unsigned param_index,
                                            llvm::StringRef func_name,
                                            const config::ScopeConfig *func_cfg,
                                            const DeclMap &param_decls,
@@ -354,12 +357,129 @@ CrossCheckInserter::build_parameter_xcheck(ParmVarDecl *param,
         };
         return generic_custom_args(ctx, param_decls, args, arg_build_fn);
     };
+    if (xcfg_scope_function_arg_selected(func_cfg, param->getName(), param_index)) {
+        // Arguments selected with `args: [...]` are hashed using
+        // the default hash function, and tagged with their index
+        unsigned tag = config::XCHECK_TAG_FUNCTION_ARG_INDEX + param_index;
+        if (tag > 0xff) {
+            report_clang_error(ctx.getDiagnostics(),
+                               "index of argument '%0' is too large for a cross-check tag",
+                               param->getName());
+            return {};
+        }
+        XCheck default_xcheck{config::XCHECK_TYPE_DEFAULT, llvm::StringRef{}};
+        return build_xcheck(default_xcheck, static_cast<config::XCheckTag>(tag), ctx,
+                            param_xcheck_default_fn,
+                            param_xcheck_custom_args_fn);
+    }
     return build_xcheck(xcfg_scope_function_arg(func_cfg, param->getName(), 1),
                         config::XCHECK_TAG_FUNCTION_ARG, ctx,
                         param_xcheck_default_fn,
                         param_xcheck_custom_args_fn);
 }
 
+void CrossCheckInserter::insert_function_symbols(FunctionDecl *fd,
+                                                 const config::ScopeConfig *func_cfg,
+                                                 ASTContext &ctx) {
+    if (symbols_file.empty())
+        return;
+
+    auto ploc = ctx.getSourceManager().getPresumedLoc(fd->getLocation());
+    if (ploc.isInvalid())
+        return;
+
+    auto add_symbol = [this, fd, &ploc] (const XCheck &xcheck) {
+        XCheckSymbol sym;
+        switch (xcheck.type) {
+        case config::XCHECK_TYPE_DEFAULT:
+            sym.name = fd->getName();
+            sym.id = djb2_hash(sym.name);
+            break;
+
+        case config::XCHECK_TYPE_DJB2:
+            sym.name = static_cast<llvm::StringRef>(xcheck.data_str);
+            sym.id = djb2_hash(sym.name);
+            break;
+
+        case config::XCHECK_TYPE_FIXED:
+            sym.id = xcheck.data_u64;
+            break;
+
+        default:
+            // No fixed id for this cross-check
+            return;
+        }
+        for (auto &old_sym : symbols)
+            if (old_sym.id == sym.id && old_sym.qualified_name == fd->getName())
+                return;
+
+        sym.qualified_name = fd->getName();
+        sym.file = ploc.getFilename();
+        sym.line = ploc.getLine();
+        symbols.push_back(std::move(sym));
+    };
+    add_symbol(xcfg_scope_entry_xcheck(func_cfg));
+    add_symbol(xcfg_scope_exit_xcheck(func_cfg));
+}
+
+void CrossCheckInserter::write_symbols(ASTContext &ctx) {
+    if (symbols_file.empty())
+        return;
+
+    // Each translation unit is compiled separately, so we merge our
+    // symbols into the ones already in the file, replacing the old
+    // entries for the source files we just compiled
+    auto &diags = ctx.getDiagnostics();
+    std::set<std::string> our_files;
+    for (auto &sym : symbols)
+        our_files.insert(sym.file);
+
+    llvm::json::Array all_symbols;
+    if (auto old_data = llvm::MemoryBuffer::getFile(symbols_file)) {
+        auto old_json = llvm::json::parse((*old_data)->getBuffer());
+        if (!old_json) {
+            llvm::consumeError(old_json.takeError());
+            report_clang_warning(diags, "ignoring invalid symbols file '%0'",
+                                 symbols_file);
+        } else if (auto *old_symbols = old_json->getAsArray()) {
+            for (auto &old_sym : *old_symbols) {
+                auto *obj = old_sym.getAsObject();
+                if (obj == nullptr)
+                    continue;
+                auto file = obj->getString("file");
+                if (file && our_files.count(file->str()) > 0)
+                    continue;
+                all_symbols.push_back(std::move(old_sym));
+            }
+        }
+    }
+
+    for (auto &sym : symbols) {
+        llvm::json::Value name = nullptr;
+        if (!sym.name.empty())
+            name = sym.name;
+        all_symbols.push_back(llvm::json::Object{
+            // JSON integers are signed, so ids above 2^63
+            // wrap around to negative values
+            {"id", static_cast<int64_t>(sym.id)},
+            {"name", std::move(name)},
+            {"qualified_name", sym.qualified_name},
+            {"file", sym.file},
+            {"line", static_cast<int64_t>(sym.line)},
+        });
+    }
+    symbols.clear();
+
+    std::error_code ec;
+    llvm::raw_fd_ostream os(symbols_file, ec, llvm::sys::fs::F_Text);
+    if (ec) {
+        report_clang_error(diags, "error writing symbols file '%0': %1",
+                           symbols_file, ec.message());
+        return;
+    }
+    os << llvm::formatv("{0:2}", llvm::json::Value(std::move(all_symbols))) << "\n";
+}
+
 bool CrossCheckInserter::HandleTopLevelDecl(DeclGroupRef dg) {
     unsigned pushed_files = 0;
     llvm::StringRef last_file;
@@ -442,6 +562,7 @@ bool CrossCheckInserter::HandleTopLevelDecl(DeclGroupRef dg) {
                                                    ctx, entry_xcheck_default_fn,
                                                    no_custom_args);
             add_body_stmts(entry_xcheck_stmts);
+            insert_function_symbols(fd, func_cfg, ctx);
 
             // Custom cross-check functions accept either function parameters
             // or global variables as their own arguments
@@ -462,9 +583,9 @@ bool CrossCheckInserter::HandleTopLevelDecl(DeclGroupRef dg) {
                 return generic_custom_args(ctx, param_decls, args, arg_build_fn);
             };
             // Add cross-checks for the function parameters
-            for (auto &param : fd->parameters()) {
+            for (unsigned i = 0; i < fd->getNumParams(); i++) {
                 auto param_xcheck_stmts =
-                    build_parameter_xcheck(param, func_name,
+                    build_parameter_xcheck(fd->getParamDecl(i), i, func_name,
                                            func_cfg, param_decls, ctx);
                 add_body_stmts(param_xcheck_stmts);
             }
@@ -714,12 +835,14 @@ class CrossCheckInsertionAction : public PluginASTAction {
 private:
     bool disable_xchecks = false;
     std::unique_ptr<const config::Config> config{config::xcfg_config_new()};
+    std::string symbols_file;
 
 protected:
     std::unique_ptr<ASTConsumer> CreateASTConsumer(CompilerInstance &ci,
                                                    llvm::StringRef) override {
         return llvm::make_unique<CrossCheckInserter>(disable_xchecks,
-                                                     std::move(config));
+                                                     std::move(config),
+                                                     symbols_file);
     }
 
     bool ParseArgs(const CompilerInstance &ci,
@@ -754,6 +877,10 @@ bool CrossCheckInsertionAction::ParseArgs(const CompilerInstance &ci,
         disable_xchecks = true;
     }
 
+    if (auto *arg = parsed_args.getLastArg(OPT_symbols_file)) {
+        symbols_file = arg->getValue();
+    }
+
     // Parse the default configuration
     std::string_view default_config_sv{CrossCheckInserter::default_config};
     auto new_config = xcfg_config_parse(config.release(), default_config_sv);
@@ -14,6 +14,7 @@
 #include "llvm/ADT/TinyPtrVector.h"
 #include "llvm/ADT/StringExtras.h"
 #include "llvm/Support/FormatVariadic.h"
+#include "llvm/Support/JSON.h"
 #include "llvm/Support/Regex.h"
 
 #include "config.h"
@@ -453,6 +454,7 @@ private:
 
     TinyStmtVec
     build_parameter_xcheck(ParmVarDecl *param,
+                           unsigned param_index,
                            llvm::StringRef func_name,
                            const config::ScopeConfig *func_cfg,
                            const DeclMap &param_decls,
@@ -478,11 +480,32 @@ private:
         ASTContext &ctx;
     };
 
+    // Entry in the map from cross-check ids to the functions
+    // that emit them, which we write to `symbols_file`
+    struct XCheckSymbol {
+        uint64_t id;
+        std::string name;
+        std::string qualified_name;
+        std::string file;
+        unsigned line;
+    };
+
+    std::string symbols_file;
+    std::vector<XCheckSymbol> symbols;
+
+    void insert_function_symbols(FunctionDecl *fd,
+                                 const config::ScopeConfig *func_cfg,
+                                 ASTContext &ctx);
+
+    void write_symbols(ASTContext &ctx);
+
 public:
     CrossCheckInserter() = delete;
-    CrossCheckInserter(bool dx, std::unique_ptr<const config::Config> cfg)
+    CrossCheckInserter(bool dx, std::unique_ptr<const config::Config> cfg,
+                       std::string sf)
             : disable_xchecks(dx), config(std::move(cfg)),
-              config_stack(config::xcfg_scope_stack_new(nullptr)) {
+              config_stack(config::xcfg_scope_stack_new(nullptr)),
+              symbols_file(std::move(sf)) {
     }
 
     void InitializeSema(Sema &S) override {
@@ -503,6 +526,7 @@ public:
             toplevel_consumer->HandleTopLevelDecl(DeclGroupRef(func));
         new_funcs.clear();
         decl_cache.clear();
+        write_symbols(ctx);
     }
 };
 
@@ -0,0 +1,23 @@
+// RUN: %clang_xcheck -O2 -o %t %s %xcheck_runtime %fakechecks
+// RUN: %t 2>&1 | FileCheck %s
+
+#include <stdio.h>
+
+#include <cross_checks.h>
+
+int foo(int x, int len) CROSS_CHECK("{ args: [1] }") {
+    return x + len;
+}
+
+int main() {
+    foo(0, 100);
+    return 0;
+}
+// CHECK: XCHECK(Ent):2090499946/0x7c9a7f6a
+// CHECK: XCHECK(Ent):193491849/0x0b887389
+// CHECK-NOT: XCHECK(Arg0)
+// CHECK: XCHECK(Arg1):8680820740569200658/0x7878787878787812
+// CHECK: XCHECK(Exi):193491849/0x0b887389
+// CHECK: XCHECK(Ret):8680820740569200658/0x7878787878787812
+// CHECK: XCHECK(Exi):2090499946/0x7c9a7f6a
+// CHECK: XCHECK(Ret):8680820740569200758/0x7878787878787876
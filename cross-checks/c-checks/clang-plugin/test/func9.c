@@ -0,0 +1,40 @@
+// RUN: rm -f %t.json
+// RUN: %clang_xcheck -O2 -o %t %s %xcheck_runtime %fakechecks -Xclang -plugin-arg-crosschecks -Xclang --symbols-file=%t.json
+// RUN: %t 2>&1 | FileCheck %s
+// RUN: FileCheck --check-prefix=SYMBOLS %s < %t.json
+
+#include <stdio.h>
+
+#include <cross_checks.h>
+
+int foo_renamed() CROSS_CHECK("{ original_name: foo }") {
+    return 1;
+}
+
+int bar() CROSS_CHECK("{ id: 0x1234 }") {
+    return 2;
+}
+
+int main() {
+    foo_renamed();
+    bar();
+    return 0;
+}
+// CHECK: XCHECK(Ent):2090499946/0x7c9a7f6a
+// CHECK: XCHECK(Ent):193491849/0x0b887389
+// CHECK: XCHECK(Exi):193491849/0x0b887389
+// CHECK: XCHECK(Ret):8680820740569200759/0x7878787878787877
+// CHECK: XCHECK(Ent):4660/0x00001234
+// CHECK: XCHECK(Exi):4660/0x00001234
+// CHECK: XCHECK(Ret):8680820740569200756/0x7878787878787874
+// CHECK: XCHECK(Exi):2090499946/0x7c9a7f6a
+// CHECK: XCHECK(Ret):8680820740569200758/0x7878787878787876
+
+// SYMBOLS-DAG: "id": 193491849,
+// SYMBOLS-DAG: "name": "foo",
+// SYMBOLS-DAG: "qualified_name": "foo_renamed"
+// SYMBOLS-DAG: "id": 4660,
+// SYMBOLS-DAG: "name": null,
+// SYMBOLS-DAG: "qualified_name": "bar"
+// SYMBOLS-DAG: "id": 2090499946,
+// SYMBOLS-DAG: "qualified_name": "main"
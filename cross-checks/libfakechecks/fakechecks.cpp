@@ -75,6 +75,8 @@ static FILE *get_fout() {
     return fout;
 }
 
+static constexpr uint8_t FUNCTION_ARG_INDEX_TAG = 16;
+
 extern "C"
 void rb_xcheck(uint8_t tag, uint64_t item) {
     static std::array<const char*, 5> tag_names = {
@@ -83,6 +85,10 @@ void rb_xcheck(uint8_t tag, uint64_t item) {
     auto *fout = get_fout();
     if (tag < tag_names.size()) {
         fprintf(fout, "XCHECK(%s):%lu/0x%08lx\n", tag_names[tag], item, item);
+    } else if (tag >= FUNCTION_ARG_INDEX_TAG) {
+        // Argument selected by index
+        fprintf(fout, "XCHECK(Arg%u):%lu/0x%08lx\n",
+                static_cast<unsigned>(tag - FUNCTION_ARG_INDEX_TAG), item, item);
     } else {
         fprintf(fout, "XCHECK(%hhu):%lu/0x%08lx\n", tag, item, item);
     }
@@ -0,0 +1,55 @@
+extern crate c2rust_xcheck_backend_trace_file;
+
+use c2rust_xcheck_backend_trace_file::{first_divergence, Divergence};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process;
+
+fn usage() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "usage: c2rust-xcheck-trace-diff <trace> <trace>",
+    )
+}
+
+fn describe_end(path: &str, trace: &str, index: u64) -> String {
+    format!("{} trace {} ends after {} checkpoints", trace, path, index)
+}
+
+pub fn main() -> Result<(), io::Error> {
+    let paths = env::args().skip(1).collect::<Vec<_>>();
+    if paths.len() != 2 {
+        return Err(usage());
+    }
+
+    let left = BufReader::new(File::open(&paths[0])?);
+    let right = BufReader::new(File::open(&paths[1])?);
+    match first_divergence(left, right)? {
+        None => {
+            println!("traces match");
+            Ok(())
+        }
+        Some(Divergence::Mismatch(l, r)) => {
+            println!("traces diverge at checkpoint {}:", l.index);
+            println!("  {}: {}", paths[0], l);
+            println!("  {}: {}", paths[1], r);
+            process::exit(1);
+        }
+        Some(Divergence::Missing { index, left, right }) => {
+            println!("traces diverge at checkpoint {}:", index);
+            match (left, right) {
+                (Some(l), None) => {
+                    println!("  {}: {}", paths[0], l);
+                    println!("  {}", describe_end(&paths[1], "right", index));
+                }
+                (None, Some(r)) => {
+                    println!("  {}", describe_end(&paths[0], "left", index));
+                    println!("  {}: {}", paths[1], r);
+                }
+                _ => unreachable!(),
+            }
+            process::exit(1);
+        }
+    }
+}
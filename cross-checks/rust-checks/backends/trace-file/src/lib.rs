@@ -0,0 +1,156 @@
+#[macro_use]
+extern crate lazy_static;
+extern crate libc;
+
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::sync::Mutex;
+
+const FUNCTION_ARG_INDEX_TAG: u8 = 16;
+const TAG_NAMES: [&str; 5] = ["Unk", "Ent", "Exi", "Arg", "Ret"];
+
+// A single cross-check, as written to the trace: its position
+// in the trace, its tag and the hash it checks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub index: u64,
+    pub tag: u8,
+    pub val: u64,
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tag = self.tag as usize;
+        if tag < TAG_NAMES.len() {
+            write!(f, "#{} XCHECK({}):0x{:016x}", self.index, TAG_NAMES[tag], self.val)
+        } else if self.tag >= FUNCTION_ARG_INDEX_TAG {
+            let arg = self.tag - FUNCTION_ARG_INDEX_TAG;
+            write!(f, "#{} XCHECK(Arg{}):0x{:016x}", self.index, arg, self.val)
+        } else {
+            write!(f, "#{} XCHECK({}):0x{:016x}", self.index, tag, self.val)
+        }
+    }
+}
+
+fn invalid_trace(line: u64, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid trace line {}: {}", line, msg),
+    )
+}
+
+impl Checkpoint {
+    // Parse a line of the trace, written as `<index> <tag> <hash in hex>`
+    fn parse(line_no: u64, line: &str) -> io::Result<Checkpoint> {
+        let mut fields = line.split_whitespace();
+        let mut next = |name| {
+            fields
+                .next()
+                .ok_or_else(|| invalid_trace(line_no, &format!("missing {}", name)))
+        };
+        let index = next("index")?
+            .parse()
+            .map_err(|_| invalid_trace(line_no, "bad index"))?;
+        let tag = next("tag")?
+            .parse()
+            .map_err(|_| invalid_trace(line_no, "bad tag"))?;
+        let val = u64::from_str_radix(next("hash")?, 16)
+            .map_err(|_| invalid_trace(line_no, "bad hash"))?;
+        Ok(Checkpoint { index, tag, val })
+    }
+}
+
+// Writes each cross-check to a trace with its index, so the traces
+// of two runs can be compared offline, e.g., on different machines
+pub struct TraceWriter<W: Write> {
+    out: W,
+    next_index: u64,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W) -> Self {
+        TraceWriter { out, next_index: 0 }
+    }
+
+    pub fn record(&mut self, tag: u8, val: u64) -> io::Result<()> {
+        writeln!(self.out, "{} {} {:016x}", self.next_index, tag, val)?;
+        self.next_index += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// Where two traces stop matching
+#[derive(Debug, PartialEq, Eq)]
+pub enum Divergence {
+    // The checkpoints at the same position differ
+    Mismatch(Checkpoint, Checkpoint),
+    // One trace ended at this position while the other went on;
+    // the checkpoint is the first one of the longer trace it lacks
+    Missing { index: u64, left: Option<Checkpoint>, right: Option<Checkpoint> },
+}
+
+fn read_checkpoint<R: BufRead>(
+    lines: &mut io::Lines<R>,
+    line_no: u64,
+) -> io::Result<Option<Checkpoint>> {
+    match lines.next() {
+        Some(line) => Checkpoint::parse(line_no, &line?).map(Some),
+        None => Ok(None),
+    }
+}
+
+// Compare two traces and find the first checkpoint they disagree on
+pub fn first_divergence<L: BufRead, R: BufRead>(
+    left: L,
+    right: R,
+) -> io::Result<Option<Divergence>> {
+    let mut left = left.lines();
+    let mut right = right.lines();
+    let mut index = 0;
+    loop {
+        let line_no = index + 1;
+        match (read_checkpoint(&mut left, line_no)?, read_checkpoint(&mut right, line_no)?) {
+            (None, None) => return Ok(None),
+            (Some(l), Some(r)) => {
+                if (l.tag, l.val) != (r.tag, r.val) {
+                    return Ok(Some(Divergence::Mismatch(l, r)));
+                }
+            }
+            (left, right) => return Ok(Some(Divergence::Missing { index, left, right })),
+        }
+        index += 1;
+    }
+}
+
+type XCheckWriter = TraceWriter<BufWriter<File>>;
+
+lazy_static! {
+    static ref RB_XCHECK_MUTEX: Mutex<Option<XCheckWriter>> = {
+        extern "C" fn cleanup() {
+            // Flush and close the file on program exit
+            let mut guard = RB_XCHECK_MUTEX.lock().unwrap();
+            let mut out = guard.take().unwrap();
+            out.flush().expect("Failed to flush cross-checks trace");
+        }
+        unsafe { libc::atexit(cleanup) };
+
+        let xchecks_file = env::var("CROSS_CHECKS_OUTPUT_FILE")
+            .expect("Expected file path in CROSS_CHECKS_OUTPUT_FILE variable");
+        let file = File::create(xchecks_file.clone())
+            .unwrap_or_else(|e| panic!("Failed to create cross-checks trace file {}: {}", xchecks_file, e));
+        Mutex::new(Some(TraceWriter::new(BufWriter::new(file))))
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn rb_xcheck(tag: u8, val: u64) {
+    let mut guard = RB_XCHECK_MUTEX.lock().unwrap();
+    let out = guard.as_mut().unwrap();
+    out.record(tag, val).expect("Failed to write cross-check");
+}
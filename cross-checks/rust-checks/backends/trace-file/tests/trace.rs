@@ -0,0 +1,76 @@
+extern crate c2rust_xcheck_backend_trace_file;
+
+use c2rust_xcheck_backend_trace_file::{first_divergence, Checkpoint, Divergence, TraceWriter};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+// The cross-checks of a run of `fib(n)`, with the last check changed by `delta`
+fn run(name: &str, n: u64, delta: u64) -> PathBuf {
+    let path = env::temp_dir().join(format!("xcheck-trace-{}-{}.log", name, std::process::id()));
+    let mut out = TraceWriter::new(BufWriter::new(File::create(&path).unwrap()));
+    out.record(1, 0x1234).unwrap();
+    out.record(3, n).unwrap();
+    let (mut a, mut b) = (0u64, 1u64);
+    for _ in 0..n {
+        out.record(16, a).unwrap();
+        let next = a + b;
+        a = b;
+        b = next;
+    }
+    out.record(4, a + delta).unwrap();
+    out.record(2, 0x1234).unwrap();
+    out.flush().unwrap();
+    path
+}
+
+fn diverge(left: &PathBuf, right: &PathBuf) -> Option<Divergence> {
+    let left = BufReader::new(File::open(left).unwrap());
+    let right = BufReader::new(File::open(right).unwrap());
+    first_divergence(left, right).unwrap()
+}
+
+#[test]
+fn test_matching_runs() {
+    let c = run("match-c", 10, 0);
+    let rust = run("match-rust", 10, 0);
+    assert_eq!(fs::read(&c).unwrap(), fs::read(&rust).unwrap());
+    assert_eq!(diverge(&c, &rust), None);
+    fs::remove_file(c).unwrap();
+    fs::remove_file(rust).unwrap();
+}
+
+#[test]
+fn test_mismatched_run() {
+    let c = run("mismatch-c", 10, 0);
+    let rust = run("mismatch-rust", 10, 1);
+    // Entry, argument, 10 iterations, then the return value
+    assert_eq!(
+        diverge(&c, &rust),
+        Some(Divergence::Mismatch(
+            Checkpoint { index: 12, tag: 4, val: 55 },
+            Checkpoint { index: 12, tag: 4, val: 56 },
+        ))
+    );
+    fs::remove_file(c).unwrap();
+    fs::remove_file(rust).unwrap();
+}
+
+#[test]
+fn test_truncated_run() {
+    let c = run("short-c", 10, 0);
+    let rust = run("short-rust", 10, 0);
+    let trace = fs::read_to_string(&rust).unwrap();
+    let truncated = trace.lines().take(5).map(|line| format!("{}\n", line)).collect::<String>();
+    fs::write(&rust, truncated).unwrap();
+
+    match diverge(&c, &rust) {
+        Some(Divergence::Missing { index: 5, left: Some(l), right: None }) => {
+            assert_eq!(l, Checkpoint { index: 5, tag: 16, val: 2 });
+        }
+        d => panic!("unexpected divergence: {:?}", d),
+    }
+    fs::remove_file(c).unwrap();
+    fs::remove_file(rust).unwrap();
+}
@@ -1,23 +1,174 @@
+extern crate serde_json;
 extern crate zstd;
 
+use std::collections::{BTreeSet, HashMap};
 use std::env;
 use std::fmt;
 use std::fs::File;
 use std::io;
-use std::io::{Read, Write};
+use std::io::{BufReader, Read, Write};
 
 const BUF_SIZE: usize = 4 * 1024 * 1024; // 4MB buffer
-const MAX_XCHECK_LEN: usize = 52;
+const MAX_XCHECK_LEN: usize = 56;
+const MAX_SYMBOL_MAPS: usize = 2;
+
+const FUNCTION_ENTRY_TAG: u8 = 1;
+const FUNCTION_EXIT_TAG: u8 = 2;
+const FUNCTION_ARG_INDEX_TAG: u8 = 16;
+
+// A function that emits a cross-check id, as written
+// to `xcheck-symbols.json` by the cross-check plugins
+struct Symbol {
+    // Name the id was derived from, if it wasn't fixed
+    name: Option<String>,
+    qualified_name: String,
+    file: String,
+    line: u64,
+}
+
+type SymbolMap = HashMap<u64, Vec<Symbol>>;
+
+fn invalid_symbols(path: &str, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid symbols file {}: {}", path, msg),
+    )
+}
+
+fn load_symbol_map(path: &str) -> io::Result<SymbolMap> {
+    let file = File::open(path)?;
+    let json: serde_json::Value = serde_json::from_reader(BufReader::new(file))?;
+    let entries = json
+        .as_array()
+        .ok_or_else(|| invalid_symbols(path, "expected an array"))?;
+
+    let mut map = SymbolMap::new();
+    for entry in entries {
+        // The clang plugin writes ids above 2^63 as negative numbers
+        let id = entry["id"]
+            .as_u64()
+            .or_else(|| entry["id"].as_i64().map(|id| id as u64))
+            .ok_or_else(|| invalid_symbols(path, "missing id"))?;
+        let qualified_name = entry["qualified_name"]
+            .as_str()
+            .ok_or_else(|| invalid_symbols(path, "missing qualified_name"))?;
+        map.entry(id).or_default().push(Symbol {
+            name: entry["name"].as_str().map(String::from),
+            qualified_name: String::from(qualified_name),
+            file: String::from(entry["file"].as_str().unwrap_or("")),
+            line: entry["line"].as_u64().unwrap_or(0),
+        });
+    }
+    Ok(map)
+}
+
+fn symbol_names(symbols: &[Symbol]) -> BTreeSet<&str> {
+    symbols.iter().filter_map(|sym| sym.name.as_ref()).map(|name| &name[..]).collect()
+}
+
+fn symbol_locations(symbols: &[Symbol]) -> String {
+    symbols
+        .iter()
+        .map(|sym| format!("{} ({}:{})", sym.qualified_name, sym.file, sym.line))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Report ids that are emitted by functions with different names,
+// either in the same build or across the two builds
+fn report_collisions(paths: &[String], maps: &[SymbolMap]) {
+    for (path, map) in paths.iter().zip(maps) {
+        for (id, symbols) in map {
+            if symbol_names(symbols).len() > 1 {
+                eprintln!(
+                    "id collision in {}: 0x{:08x} is emitted by {}",
+                    path,
+                    id,
+                    symbol_locations(symbols)
+                );
+            }
+        }
+    }
+
+    if let [map0, map1] = maps {
+        let mut ids = map0.keys().filter(|id| map1.contains_key(*id)).collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let (symbols0, symbols1) = (&map0[id], &map1[id]);
+            let (names0, names1) = (symbol_names(symbols0), symbol_names(symbols1));
+            // Functions with fixed ids have no name to compare
+            if names0.is_empty() || names1.is_empty() || !names0.is_disjoint(&names1) {
+                continue;
+            }
+            eprintln!(
+                "id collision: 0x{:08x} is emitted by {} in {}, but by {} in {}",
+                id,
+                symbol_locations(symbols0),
+                paths[0],
+                symbol_locations(symbols1),
+                paths[1]
+            );
+        }
+    }
+}
+
+// Build the symbolic name to print for each id, e.g., `foo` or
+// `foo / crate::bar` if the function was renamed in one of the builds
+fn build_labels(maps: &[SymbolMap]) -> HashMap<u64, String> {
+    let mut names: HashMap<u64, Vec<&str>> = HashMap::new();
+    for map in maps {
+        for (id, symbols) in map {
+            let id_names = names.entry(*id).or_default();
+            for sym in symbols {
+                if !id_names.contains(&&sym.qualified_name[..]) {
+                    id_names.push(&sym.qualified_name);
+                }
+            }
+        }
+    }
+    names
+        .into_iter()
+        .map(|(id, names)| (id, names.join(" / ")))
+        .collect()
+}
+
+fn usage() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "usage: c2rust-xcheck-zstd-printer [--symbols <xcheck-symbols.json>]... <log>...",
+    )
+}
 
 pub fn main() -> Result<(), std::io::Error> {
+    let mut symbols_paths = vec![];
+    let mut log_paths = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "-s" | "--symbols" => symbols_paths.push(args.next().ok_or_else(usage)?),
+            _ => log_paths.push(arg),
+        }
+    }
+    if symbols_paths.len() > MAX_SYMBOL_MAPS {
+        return Err(usage());
+    }
+
+    let symbol_maps = symbols_paths
+        .iter()
+        .map(|path| load_symbol_map(path))
+        .collect::<io::Result<Vec<_>>>()?;
+    report_collisions(&symbols_paths, &symbol_maps);
+    let labels = build_labels(&symbol_maps);
+
     let tag_names = ["Unk", "Ent", "Exi", "Arg", "Ret"]
         .iter()
         .map(ToString::to_string)
-        .chain((5..256).map(|n| n.to_string()))
+        .chain((5..FUNCTION_ARG_INDEX_TAG as usize).map(|n| n.to_string()))
+        .chain((0..256 - FUNCTION_ARG_INDEX_TAG as usize).map(|n| format!("Arg{}", n)))
         .collect::<Vec<_>>();
 
     let mut out = String::with_capacity(BUF_SIZE);
-    for arg in env::args() {
+    for arg in log_paths {
         let file = File::open(arg)?;
         let mut reader = zstd::stream::Decoder::new(file)?;
         loop {
@@ -36,10 +187,17 @@ pub fn main() -> Result<(), std::io::Error> {
             let tag_name = &tag_names[buf[0] as usize];
             fmt::write(
                 &mut out,
-                format_args!("XCHECK({0}):{1:}/0x{1:08x}\n", tag_name, val),
+                format_args!("XCHECK({0}):{1:}/0x{1:08x}", tag_name, val),
             )
             .expect("Error formatting xcheck");
-            assert!(out.len() <= old_len + MAX_XCHECK_LEN);
+            assert!(out.len() < old_len + MAX_XCHECK_LEN);
+            if buf[0] == FUNCTION_ENTRY_TAG || buf[0] == FUNCTION_EXIT_TAG {
+                if let Some(label) = labels.get(&val) {
+                    out.push(' ');
+                    out.push_str(label);
+                }
+            }
+            out.push('\n');
         }
     }
     // Flush the buffer
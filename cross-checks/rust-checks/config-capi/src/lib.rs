@@ -351,6 +351,20 @@ pub extern "C" fn xcfg_scope_function_arg<'sc>(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn xcfg_scope_function_arg_selected(
+    scope_config: Option<&xcfg::scopes::ScopeConfig>,
+    arg_name: StringLenPtr,
+    arg_index: c_uint,
+) -> c_uint {
+    scope_config.map_or(0, |sc| match sc.item {
+        xcfg::scopes::ItemConfig::Function(ref f) => {
+            f.is_selected_arg(arg_index as usize, arg_name.as_str()) as c_uint
+        }
+        _ => 0,
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn xcfg_scope_function_entry_extra(
     scope_config: Option<&xcfg::scopes::ScopeConfig>,
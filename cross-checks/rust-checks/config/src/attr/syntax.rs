@@ -1,7 +1,7 @@
 extern crate syntax;
 
 use super::{ArgList, ArgValue};
-use crate::{DefaultsConfig, FunctionConfig, ItemConfig, StructConfig, XCheckType};
+use crate::{DefaultsConfig, FieldIndex, FunctionConfig, ItemConfig, StructConfig, XCheckType};
 
 use std::convert::TryInto;
 
@@ -131,19 +131,32 @@ fn parse_function_attr_config(f: &mut FunctionConfig, mi: &ast::MetaItem) {
             "exit" => f.exit = parse_xcheck_arg(&arg, true),
             "all_args" => f.all_args = parse_xcheck_arg(&arg, true),
             "ret" => f.ret = parse_xcheck_arg(&arg, true),
+            "id" => {
+                f.id = match *arg {
+                    ArgValue::Int(id128) => Some(id128.try_into().unwrap_or_else(|_| {
+                        panic!("invalid u64 for cross_check id: {}", id128)
+                    })),
+                    _ => panic!("invalid literal for cross_check id: {:?}", arg),
+                }
+            }
+            "original_name" => f.original_name = Some(String::from(arg.as_str())),
             "args" => {
-                // Parse per-argument cross-check types
-                f.args
-                    .extend(arg.as_list().iter().filter_map(|(name, arg)| {
-                        if let ArgValue::List(ref l) = *arg {
+                // Parse per-argument cross-check types, e.g., `args(a(default))`,
+                // and arguments selected by name for indexed checks, e.g., `args(a)`
+                for (name, arg) in arg.as_list().iter() {
+                    match *arg {
+                        ArgValue::List(ref l) => {
                             let arg_xcheck = parse_xcheck_arglist(l, false).unwrap_or_else(|| {
                                 panic!("expected valid cross-check type for argument: {}", name)
                             });
-                            Some((name.to_string(), arg_xcheck))
-                        } else {
-                            None
+                            f.args.checks.insert(name.to_string(), arg_xcheck);
+                        }
+                        ArgValue::Nothing => {
+                            f.args.selected.push(FieldIndex::Str(name.to_string()));
                         }
-                    }));
+                        _ => {}
+                    }
+                }
             }
             "ahasher" => f.ahasher = Some(String::from(arg.as_str())),
             "shasher" => f.shasher = Some(String::from(arg.as_str())),
@@ -130,6 +130,12 @@ pub struct FunctionConfig {
     // Overrides for the attribute config items
     pub disable_xchecks: Option<bool>,
 
+    // Stable check id for the function: either a fixed value,
+    // or the hash of the name of the function in the C code,
+    // which lets the function be renamed in the Rust code
+    pub id: Option<u64>,
+    pub original_name: Option<String>,
+
     // How to cross-check function entry and exit
     pub entry: Option<XCheckType>,
     pub exit: Option<XCheckType>,
@@ -137,7 +143,7 @@ pub struct FunctionConfig {
     // The default check for all arguments not in "args"
     pub all_args: Option<XCheckType>,
     // How to cross-check each argument
-    pub args: HashMap<String, XCheckType>,
+    pub args: ArgsConfig,
 
     // How to cross-check the return value
     #[serde(rename = "return")]
@@ -162,6 +168,8 @@ impl FunctionConfig {
         FunctionConfig {
             name: self.name.clone(),
             disable_xchecks: self.disable_xchecks,
+            id: self.id,
+            original_name: self.original_name.clone(),
             entry: self.entry.clone(),
             exit: self.exit.clone(),
             all_args: self.all_args.clone(),
@@ -174,6 +182,50 @@ impl FunctionConfig {
             exit_extra: self.exit_extra.clone(),
         }
     }
+
+    // The cross-check that replaces the default name-based one
+    // for the function entry and exit, if the function has
+    // an explicit id or original name
+    pub fn id_xcheck(&self) -> Option<XCheckType> {
+        match (self.id, &self.original_name) {
+            (Some(id), _) => Some(XCheckType::Fixed(id)),
+            (None, Some(name)) => Some(XCheckType::Djb2(name.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+// Cross-checks for function arguments, given either as a map
+// from argument names to their cross-checks, or as a list of
+// arguments (by index or name) to hash on function entry,
+// each one emitted with a tag that holds its index
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(from = "ArgsConfigRepr")]
+pub struct ArgsConfig {
+    pub checks: HashMap<String, XCheckType>,
+    pub selected: Vec<FieldIndex>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ArgsConfigRepr {
+    Map(HashMap<String, XCheckType>),
+    List(Vec<FieldIndex>),
+}
+
+impl From<ArgsConfigRepr> for ArgsConfig {
+    fn from(repr: ArgsConfigRepr) -> ArgsConfig {
+        match repr {
+            ArgsConfigRepr::Map(checks) => ArgsConfig {
+                checks,
+                selected: vec![],
+            },
+            ArgsConfigRepr::List(selected) => ArgsConfig {
+                checks: Default::default(),
+                selected,
+            },
+        }
+    }
 }
 
 // Index of a structure/aggregate field
@@ -574,4 +626,40 @@ mod tests {
     fn test_function() {
         // TODO
     }
+
+    #[test]
+    fn test_function_args() {
+        let f = parse_test_yaml::<FunctionConfig>("{ name: foo, args: { a: none, b: default } }");
+        assert_eq!(f.args.checks.len(), 2);
+        assert_eq!(f.args.checks["a"], XCheckType::None);
+        assert!(f.args.selected.is_empty());
+
+        let f = parse_test_yaml::<FunctionConfig>("{ name: foo, args: [1, 3, len] }");
+        assert!(f.args.checks.is_empty());
+        assert_eq!(
+            f.args.selected,
+            vec![
+                FieldIndex::Int(1),
+                FieldIndex::Int(3),
+                FieldIndex::Str(String::from("len")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_id() {
+        let f = parse_test_yaml::<FunctionConfig>("{ name: foo, id: 0x1234 }");
+        assert_eq!(f.id, Some(0x1234));
+        assert_eq!(f.id_xcheck(), Some(XCheckType::Fixed(0x1234)));
+
+        let f = parse_test_yaml::<FunctionConfig>("{ name: foo_rs, original_name: foo }");
+        assert_eq!(f.id_xcheck(), Some(XCheckType::Djb2(String::from("foo"))));
+
+        // An explicit id takes precedence over the original name
+        let f = parse_test_yaml::<FunctionConfig>("{ name: foo, id: 5, original_name: bar }");
+        assert_eq!(f.id_xcheck(), Some(XCheckType::Fixed(5)));
+
+        let f = parse_test_yaml::<FunctionConfig>("{ name: foo }");
+        assert_eq!(f.id_xcheck(), None);
+    }
 }
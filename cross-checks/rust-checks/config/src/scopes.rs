@@ -34,15 +34,28 @@ impl Default for InheritedConfig {
 #[derive(Debug, Clone)]
 pub struct FunctionConfig {
     pub args: HashMap<super::FieldIndex, super::XCheckType>,
+    // Arguments selected with `args: [...]`, which get hashed
+    // on function entry with a tag holding the argument index
+    pub selected_args: Vec<super::FieldIndex>,
     pub entry_extra: Vec<super::ExtraXCheck>,
     pub exit_extra: Vec<super::ExtraXCheck>,
 }
 
+impl FunctionConfig {
+    pub fn is_selected_arg(&self, index: usize, name: &str) -> bool {
+        self.selected_args.iter().any(|arg| match *arg {
+            super::FieldIndex::Int(i) => i == index,
+            super::FieldIndex::Str(ref s) => s == name,
+        })
+    }
+}
+
 // We want all_args set to None, so we need a custom Default implementation
 impl Default for FunctionConfig {
     fn default() -> FunctionConfig {
         FunctionConfig {
             args: Default::default(),
+            selected_args: Default::default(),
             entry_extra: Default::default(),
             exit_extra: Default::default(),
         }
@@ -223,6 +236,17 @@ impl ScopeConfig {
                 parse_optional_field!(^exit,     xcfg_func, exit,     exit.clone());
                 parse_optional_field!(^all_args, xcfg_func, all_args, all_args.clone());
                 parse_optional_field!(^ret,      xcfg_func, ret,      ret.clone());
+                // An explicit id or original name replaces the default
+                // (name-based) id, unless entry or exit are overridden
+                if let Some(id_xcheck) = xcfg_func.id_xcheck() {
+                    let inherited = Rc::make_mut(&mut self.inherited);
+                    if xcfg_func.entry.is_none() {
+                        inherited.entry = id_xcheck.clone();
+                    }
+                    if xcfg_func.exit.is_none() {
+                        inherited.exit = id_xcheck;
+                    }
+                }
                 // TODO: add a way for the external config to reset these to default
                 parse_optional_field!(^ahasher, xcfg_func, ahasher, Some(ahasher.clone()));
                 parse_optional_field!(^shasher, xcfg_func, shasher, Some(shasher.clone()));
@@ -230,9 +254,13 @@ impl ScopeConfig {
                 self_func.args.extend(
                     xcfg_func
                         .args
+                        .checks
                         .iter()
                         .map(|(k, v)| (super::FieldIndex::Str(k.clone()), v.clone())),
                 );
+                self_func
+                    .selected_args
+                    .extend(xcfg_func.args.selected.iter().cloned());
                 self_func
                     .entry_extra
                     .extend(xcfg_func.entry_extra.iter().cloned());
@@ -4,6 +4,11 @@ pub const FUNCTION_EXIT_TAG: u8 = 2;
 pub const FUNCTION_ARG_TAG: u8 = 3;
 pub const FUNCTION_RETURN_TAG: u8 = 4;
 
+// Arguments selected with `args: [...]` are checked with
+// the tag `FUNCTION_ARG_INDEX_TAG + index`
+pub const FUNCTION_ARG_INDEX_TAG: u8 = 16;
+pub const MAX_FUNCTION_ARG_INDEX: usize = (u8::max_value() - FUNCTION_ARG_INDEX_TAG) as usize;
+
 #[cfg(any(feature = "xcheck-with-dlsym", feature = "xcheck-with-weak"))]
 #[inline]
 unsafe fn call_rb_xcheck_sym<T>(sym: *mut T, tag: u8, val: u64) {
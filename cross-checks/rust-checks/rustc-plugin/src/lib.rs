@@ -7,6 +7,8 @@ extern crate syntax;
 extern crate matches;
 
 extern crate serde;
+#[macro_use]
+extern crate serde_json;
 extern crate serde_yaml;
 
 // Unused: #[macro_use]
@@ -215,6 +217,40 @@ fn djb2_hash(s: &str) -> u32 {
         .fold(5381u32, |h, c| h.wrapping_mul(33).wrapping_add(c.into()))
 }
 
+// Get the id that a function entry or exit cross-check emits,
+// along with the name it was derived from (if any)
+fn function_xcheck_id(xcheck: &xcfg::XCheckType, fn_ident: ast::Ident) -> Option<(u64, Option<String>)> {
+    match *xcheck {
+        xcfg::XCheckType::Default => {
+            let name = fn_ident.name.to_string();
+            Some((djb2_hash(&name) as u64, Some(name)))
+        }
+        xcfg::XCheckType::Djb2(ref name) => Some((djb2_hash(name) as u64, Some(name.clone()))),
+        xcfg::XCheckType::Fixed(id) => Some((id, None)),
+        _ => None,
+    }
+}
+
+// Get the name of an item as it appears in the qualified names of
+// the items inside it; for `impl`s, we use the name of the type
+fn item_path_segment(item: &ast::Item) -> ast::Ident {
+    match item.kind {
+        ast::ItemKind::Impl(.., ref self_ty, _) => {
+            ast::Ident::from_str(&pprust::ty_to_string(self_ty))
+        }
+        _ => item.ident,
+    }
+}
+
+// Entry in the cross-check symbol map
+struct XCheckSymbol {
+    id: u64,
+    name: Option<String>,
+    qualified_name: String,
+    file: String,
+    line: usize,
+}
+
 trait CrossCheckBuilder {
     fn build_ident_xcheck(
         &self,
@@ -233,6 +269,16 @@ trait CrossCheckBuilder {
     ) -> ast::Stmt
     where
         F: FnOnce(P<ast::Expr>, Vec<ast::Stmt>) -> P<ast::Expr>;
+    fn build_tagged_xcheck<F>(
+        &self,
+        cx: &ExtCtxt,
+        exp: &CrossCheckExpander,
+        tag_expr: P<ast::Expr>,
+        val_ref_ident: ast::Ident,
+        f: F,
+    ) -> ast::Stmt
+    where
+        F: FnOnce(P<ast::Expr>, Vec<ast::Stmt>) -> P<ast::Expr>;
 }
 
 fn hashed_file_name(file_name: &str, arg: &str) -> FileName {
@@ -293,6 +339,21 @@ impl CrossCheckBuilder for xcfg::XCheckType {
             ],
         );
         let tag_expr = cx.expr_path(tag_path);
+        self.build_tagged_xcheck(cx, exp, tag_expr, val_ref_ident, f)
+    }
+
+    // Same as build_xcheck, but with an arbitrary expression for the tag
+    fn build_tagged_xcheck<F>(
+        &self,
+        cx: &ExtCtxt,
+        exp: &CrossCheckExpander,
+        tag_expr: P<ast::Expr>,
+        val_ref_ident: ast::Ident,
+        f: F,
+    ) -> ast::Stmt
+    where
+        F: FnOnce(P<ast::Expr>, Vec<ast::Stmt>) -> P<ast::Expr>,
+    {
         let xcheck = match *self {
             xcfg::XCheckType::Default => f(tag_expr, vec![]),
             xcfg::XCheckType::AsType(ref ty_str) => {
@@ -448,6 +509,10 @@ struct CrossChecker<'a, 'cx: 'a, 'exp> {
     // in a tuple
     field_idx_stack: Vec<usize>,
 
+    // Path of the item we're currently in, used to build
+    // the fully-qualified names in the symbol map
+    item_path: Vec<ast::Ident>,
+
     // Whether to skip calling build_new_scope() on the first scope.
     // We set this to true for #[cross_check(...)] invocations caused
     // by macro expansions, since the compiler passes the attribute to us
@@ -477,6 +542,7 @@ impl<'a, 'cx, 'exp> CrossChecker<'a, 'cx, 'exp> {
             "::c2rust_xcheck_runtime::hash::simple::SimpleHasher",
             cx.parse_sess,
         );
+        let item_path = cx.current_expansion.module.mod_path.clone();
         CrossChecker {
             expander,
             cx,
@@ -485,6 +551,7 @@ impl<'a, 'cx, 'exp> CrossChecker<'a, 'cx, 'exp> {
             default_shasher,
             pending_items: vec![],
             field_idx_stack: vec![],
+            item_path,
             skip_first_scope,
         }
     }
@@ -510,24 +577,57 @@ impl<'a, 'cx, 'exp> CrossChecker<'a, 'cx, 'exp> {
     }
 
     // Get the cross-check block for this argument
-    fn build_arg_xcheck(&self, arg: &ast::Param) -> ast::Stmt {
+    fn build_arg_xcheck(&self, index: usize, arg: &ast::Param) -> ast::Stmt {
         match arg.pat.kind {
             ast::PatKind::Ident(_, ref ident, _) => {
                 // Parameter pattern is just an identifier,
                 // so we can reference it directly by name
-                let arg_idx = xcfg::FieldIndex::Str(ident.name.to_string());
-                let arg_xcheck_cfg = self
-                    .config()
-                    .function_config()
-                    .args
-                    .get(&arg_idx)
-                    .unwrap_or(&self.config().inherited.all_args);
+                let fcfg = self.config().function_config();
+                let arg_name = ident.name.to_string();
+                let arg_idx = xcfg::FieldIndex::Str(arg_name.clone());
+                let (arg_xcheck_cfg, tag_expr) = if fcfg.is_selected_arg(index, &arg_name) {
+                    // Arguments selected by `args: [...]` are hashed with
+                    // the default hasher, and tagged with their index
+                    let index_tag_path = self.cx.path(
+                        DUMMY_SP,
+                        vec![
+                            self.cx.ident_of("c2rust_xcheck_runtime", DUMMY_SP),
+                            self.cx.ident_of("xcheck", DUMMY_SP),
+                            self.cx.ident_of("FUNCTION_ARG_INDEX_TAG", DUMMY_SP),
+                        ],
+                    );
+                    let index_lit = self.cx.expr_lit(
+                        DUMMY_SP,
+                        ast::LitKind::Int(index as u128, ast::LitIntType::Unsigned(ast::UintTy::U8)),
+                    );
+                    let tag_expr = self.cx.expr_binary(
+                        DUMMY_SP,
+                        ast::BinOpKind::Add,
+                        self.cx.expr_path(index_tag_path),
+                        index_lit,
+                    );
+                    (&xcfg::XCheckType::Default, tag_expr)
+                } else {
+                    let arg_xcheck_cfg = fcfg
+                        .args
+                        .get(&arg_idx)
+                        .unwrap_or(&self.config().inherited.all_args);
+                    let tag_path = self.cx.path(
+                        DUMMY_SP,
+                        vec![
+                            self.cx.ident_of("c2rust_xcheck_runtime", DUMMY_SP),
+                            self.cx.ident_of("xcheck", DUMMY_SP),
+                            self.cx.ident_of("FUNCTION_ARG_TAG", DUMMY_SP),
+                        ],
+                    );
+                    (arg_xcheck_cfg, self.cx.expr_path(tag_path))
+                };
                 // FIXME: no gensym()???
                 let val_ref_ident = self.cx.ident_of("__c2rust_val_ref", DUMMY_SP);
-                arg_xcheck_cfg.build_xcheck(
+                arg_xcheck_cfg.build_tagged_xcheck(
                     self.cx,
                     self.expander,
-                    "FUNCTION_ARG_TAG",
+                    tag_expr,
                     val_ref_ident,
                     |tag, pre_hash_stmts| {
                         // By default, we use cross_check_hash
@@ -670,7 +770,8 @@ impl<'a, 'cx, 'exp> CrossChecker<'a, 'cx, 'exp> {
                 fn_decl
                     .inputs
                     .iter()
-                    .map(|ref arg| self.build_arg_xcheck(arg)),
+                    .enumerate()
+                    .map(|(index, arg)| self.build_arg_xcheck(index, arg)),
             );
 
             // Insert extra entry xchecks
@@ -750,6 +851,40 @@ impl<'a, 'cx, 'exp> CrossChecker<'a, 'cx, 'exp> {
         checked_block
     }
 
+    // Add the ids of the entry and exit cross-checks of this function
+    // to the symbol map
+    fn insert_function_symbols(&self, fn_ident: ast::Ident, span: Span) {
+        let cfg = self.config();
+        if !cfg.inherited.enabled {
+            return;
+        }
+        let qualified_name = self
+            .item_path
+            .iter()
+            .chain(iter::once(&fn_ident))
+            .map(|ident| ident.name.to_string())
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>()
+            .join("::");
+        let loc = self.cx.source_map().lookup_char_pos(span.lo());
+        let ids = function_xcheck_id(&cfg.inherited.entry, fn_ident)
+            .into_iter()
+            .chain(function_xcheck_id(&cfg.inherited.exit, fn_ident))
+            .collect::<Vec<_>>();
+        for (idx, (id, name)) in ids.iter().enumerate() {
+            if ids[..idx].iter().any(|(prev_id, _)| prev_id == id) {
+                continue;
+            }
+            self.expander.insert_symbol(XCheckSymbol {
+                id: *id,
+                name: name.clone(),
+                qualified_name: qualified_name.clone(),
+                file: loc.file.name.to_string(),
+                line: loc.line,
+            });
+        }
+    }
+
     fn build_union_hash(&mut self, union_ident: ast::Ident) -> P<ast::Item> {
         let custom_hash_opt = &self.config().struct_config().custom_hash;
         let custom_hash_format = &self.config().struct_config().custom_hash_format;
@@ -841,6 +976,7 @@ impl<'a, 'cx, 'exp> CrossChecker<'a, 'cx, 'exp> {
         match item.kind {
             ast::ItemKind::Fn(ref fn_decl, _, _, ref mut block) => {
                 *block = self.build_function_xchecks(ident, fn_decl, block.clone());
+                self.insert_function_symbols(ident, span);
             }
             ast::ItemKind::Union(_, _) => {
                 let union_hash_impl = self.build_union_hash(ident);
@@ -918,7 +1054,9 @@ impl<'a, 'cx, 'exp> MutVisitor for CrossChecker<'a, 'cx, 'exp> {
             // If skip_first_scope is true, skip building a new scope
             // (see the comment for skip_first_scope in CrossChecker above)
             self.skip_first_scope = false;
+            self.item_path.push(item_path_segment(&item));
             let mut folded_items = mut_visit::noop_flat_map_item(item, self);
+            self.item_path.pop();
             for item in folded_items.iter_mut() {
                 self.internal_visit_item(item);
             }
@@ -930,7 +1068,9 @@ impl<'a, 'cx, 'exp> MutVisitor for CrossChecker<'a, 'cx, 'exp> {
                 &self.expander.external_config,
                 self.cx,
             );
+            self.item_path.push(item_path_segment(&item));
             let mut folded_items = mut_visit::noop_flat_map_item(item, self);
+            self.item_path.pop();
             for item in folded_items.iter_mut() {
                 self.internal_visit_item(item);
             }
@@ -1201,6 +1341,10 @@ struct CrossCheckExpander {
     djb2_names: RefCell<HashMap<u32, HashSet<String>>>,
     djb2_names_files: Vec<PathBuf>,
 
+    // Map from cross-check ids to the functions that emit them
+    symbols: RefCell<Vec<XCheckSymbol>>,
+    symbols_files: Vec<PathBuf>,
+
     // List of already emitted C ABI hash functions,
     // used to prevent the emission of duplicates
     #[cfg(feature = "c-hash-functions")]
@@ -1211,7 +1355,8 @@ impl CrossCheckExpander {
     fn new(args: &[ast::NestedMetaItem]) -> CrossCheckExpander {
         let mut exp = Self::default();
         exp.external_config = Self::parse_config_files(args);
-        exp.djb2_names_files = Self::parse_djb2_names_files(args);
+        exp.djb2_names_files = Self::parse_output_files(args, "djb2_names_file");
+        exp.symbols_files = Self::parse_output_files(args, "symbols_file");
         exp
     }
 
@@ -1242,15 +1387,20 @@ impl CrossCheckExpander {
             .fold(dcfg, |acc, fc| acc.merge(fc))
     }
 
-    fn parse_djb2_names_files(args: &[ast::NestedMetaItem]) -> Vec<PathBuf> {
+    // Parse arguments of the form
+    // #[plugin(c2rust_xcheck_plugin(djb2_names_file = "..."))]
+    fn parse_output_files(args: &[ast::NestedMetaItem], arg_name: &str) -> Vec<PathBuf> {
         let fl = RealFileLoader;
         args.iter()
-            .filter(|nmi| nmi.check_name(Symbol::intern("djb2_names_file")))
-            .map(|mi| mi.value_str().expect("invalid string for config_file"))
+            .filter(|nmi| nmi.check_name(Symbol::intern(arg_name)))
+            .map(|mi| {
+                mi.value_str()
+                    .unwrap_or_else(|| panic!("invalid string for {}", arg_name))
+            })
             .map(|fsym| PathBuf::from(&*fsym.as_str()))
             .map(|fp| {
                 fl.abs_path(&fp)
-                    .unwrap_or_else(|| panic!("invalid path to djb2 names file: {:?}", fp))
+                    .unwrap_or_else(|| panic!("invalid path to {}: {:?}", arg_name, fp))
             })
             .collect()
     }
@@ -1274,6 +1424,10 @@ impl CrossCheckExpander {
             .insert(name);
     }
 
+    fn insert_symbol(&self, symbol: XCheckSymbol) {
+        self.symbols.borrow_mut().push(symbol);
+    }
+
     fn write_djb2_names(&mut self) {
         let djb2_names = &*self.djb2_names.borrow();
         for fp in &self.djb2_names_files {
@@ -1286,11 +1440,38 @@ impl CrossCheckExpander {
             });
         }
     }
+
+    fn write_symbols(&mut self) {
+        let symbols = &mut *self.symbols.borrow_mut();
+        symbols.sort_by(|a, b| {
+            (a.id, &a.qualified_name, &a.file, a.line).cmp(&(b.id, &b.qualified_name, &b.file, b.line))
+        });
+        let symbols_json = symbols
+            .iter()
+            .map(|sym| {
+                json!({
+                    "id": sym.id,
+                    "name": sym.name,
+                    "qualified_name": sym.qualified_name,
+                    "file": sym.file,
+                    "line": sym.line,
+                })
+            })
+            .collect::<Vec<_>>();
+        for fp in &self.symbols_files {
+            let f = fs::File::create(fp)
+                .unwrap_or_else(|e| panic!("could not create symbols file {:?}: {}", fp, e));
+            serde_json::to_writer_pretty(f, &symbols_json).unwrap_or_else(|e| {
+                panic!("could not write JSON to symbols file {:?}: {}", fp, e)
+            });
+        }
+    }
 }
 
 impl Drop for CrossCheckExpander {
     fn drop(&mut self) {
-        self.write_djb2_names()
+        self.write_djb2_names();
+        self.write_symbols();
     }
 }
 
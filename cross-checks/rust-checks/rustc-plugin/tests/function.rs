@@ -9,7 +9,9 @@ pub use xcheck::rb_xcheck; // Export rb_xcheck for the runtime
 
 #[cross_check(none)]
 mod tests {
-    use c2rust_xcheck_runtime::xcheck::{FUNCTION_ARG_TAG, FUNCTION_ENTRY_TAG, FUNCTION_EXIT_TAG};
+    use c2rust_xcheck_runtime::xcheck::{
+        FUNCTION_ARG_INDEX_TAG, FUNCTION_ARG_TAG, FUNCTION_ENTRY_TAG, FUNCTION_EXIT_TAG,
+    };
     use super::xcheck::{expect_no_xchecks, expect_xcheck};
 
     #[test]
@@ -57,6 +59,41 @@ mod tests {
         expect_no_xchecks();
     }
 
+    #[test]
+    fn test_original_name() {
+        // Renamed from `abcd`, but still cross-checked as `abcd`
+        #[cross_check(yes, original_name = "abcd")]
+        fn abcd_renamed() {}
+
+        abcd_renamed();
+        expect_xcheck(FUNCTION_ENTRY_TAG, 0x7c93ee4f_u64);
+        expect_xcheck(FUNCTION_EXIT_TAG, 0x7c93ee4f_u64);
+        expect_no_xchecks();
+    }
+
+    #[test]
+    fn test_function_id() {
+        #[cross_check(yes, id = 0x12345678)]
+        fn abcd() {}
+
+        abcd();
+        expect_xcheck(FUNCTION_ENTRY_TAG, 0x12345678_u64);
+        expect_xcheck(FUNCTION_EXIT_TAG, 0x12345678_u64);
+        expect_no_xchecks();
+    }
+
+    #[test]
+    fn test_function_id_entry_override() {
+        // An explicit entry cross-check takes priority over the id
+        #[cross_check(yes, original_name = "abcd", entry(fixed = 0x1234))]
+        fn abcd_renamed() {}
+
+        abcd_renamed();
+        expect_xcheck(FUNCTION_ENTRY_TAG, 0x1234_u64);
+        expect_xcheck(FUNCTION_EXIT_TAG, 0x7c93ee4f_u64);
+        expect_no_xchecks();
+    }
+
     #[test]
     fn test_entry_disabled() {
         #[cross_check(yes, entry(disabled))]
@@ -137,4 +174,17 @@ mod tests {
         expect_xcheck(FUNCTION_EXIT_TAG, 0x7c93ee4f_u64);
         expect_no_xchecks();
     }
+
+    #[test]
+    fn test_selected_args() {
+        #[cross_check(yes, args(_b, _c))]
+        fn abcd(_a: u8, _b: u16, _c: u64) {}
+
+        abcd(0x7fu8, 0x1234u16, 1u64);
+        expect_xcheck(FUNCTION_ENTRY_TAG, 0x7c93ee4f_u64);
+        expect_xcheck(FUNCTION_ARG_INDEX_TAG + 1, 0x5a5a5a5a_5a5a486e_u64);
+        expect_xcheck(FUNCTION_ARG_INDEX_TAG + 2, 0x0f0f0f0f_0f0f0f0f_u64);
+        expect_xcheck(FUNCTION_EXIT_TAG, 0x7c93ee4f_u64);
+        expect_no_xchecks();
+    }
 }
@@ -1,6 +1,7 @@
 #!/usr/bin/env python3
 
 import errno
+import glob
 import os
 import sys
 import logging
@@ -71,8 +72,26 @@ class CFile:
         self.disable_incremental_relooper = "disable_incremental_relooper" in flags
         self.disallow_current_block = "disallow_current_block" in flags
         self.translate_const_macros = "translate_const_macros" in flags
+        self.translate_fn_macros = "translate_fn_macros" in flags
+        self.translate_enums = "translate_enums" in flags
+        self.emit_repr_transparent = "emit_repr_transparent" in flags
+        self.strict_bit_ints = "strict_bit_ints" in flags
+        self.shim_untranslatable_stmts = "shim_untranslatable_stmts" in flags
+        self.no_translate_asm = "no_translate_asm" in flags
+        self.translate_fn_tables = "translate_fn_tables" in flags
+        self.translate_string_tables = "translate_string_tables" in flags
+        self.string_literals_unique = "string_literals_unique" in flags
+        self.translate_blocks = "translate_blocks" in flags
+        self.translate_pthreads = "translate_pthreads" in flags
         self.reorganize_definitions = "reorganize_definitions" in flags
         self.emit_build_files = "emit_build_files" in flags
+        self.embed_byte_arrays = "embed_byte_arrays" in flags
+        self.internal_visibility = next(
+            (flag[20:] for flag in flags if flag.startswith("internal_visibility_")), None)
+        self.check_deterministic = "check_deterministic" in flags
+        # C++ headers are translated, but not compiled into the test library
+        self.is_cxx_header = path.endswith(".hpp")
+        self.log_functions = sorted(flag[13:] for flag in flags if flag.startswith("log_function_"))
 
     def translate(self, cc_db, extra_args: List[str] = []) -> RustFile:
         extensionless_file, _ = os.path.splitext(self.path)
@@ -98,10 +117,38 @@ class CFile:
             args.append("--fail-on-multiple")
         if self.translate_const_macros:
             args.append("--translate-const-macros")
+        if self.translate_fn_macros:
+            args.append("--translate-fn-macros")
+        if self.translate_enums:
+            args.append("--translate-enums")
+        if self.emit_repr_transparent:
+            args.append("--emit-repr-transparent")
+        if self.strict_bit_ints:
+            args.append("--strict-bit-ints")
+        if self.shim_untranslatable_stmts:
+            args.append("--shim-untranslatable-stmts")
+        if self.no_translate_asm:
+            args.append("--no-translate-asm")
+        if self.translate_fn_tables:
+            args.append("--translate-fn-tables")
+        if self.translate_string_tables:
+            args.append("--translate-string-tables")
+        if self.string_literals_unique:
+            args += ["--string-literals", "unique-per-use"]
+        if self.translate_blocks:
+            args.append("--translate-blocks")
+        if self.translate_pthreads:
+            args.append("--translate-pthreads")
         if self.reorganize_definitions:
             args.append("--reorganize-definitions")
         if self.emit_build_files:
             args.append("--emit-build-files")
+        if self.embed_byte_arrays:
+            args += ["--embed-byte-arrays", "1024"]
+        if self.internal_visibility:
+            args += ["--internal-visibility", self.internal_visibility]
+        for log_function in self.log_functions:
+            args += ["--log-function", log_function]
 
         if self.logLevel == 'DEBUG':
             args.append("--log-level=debug")
@@ -180,6 +227,43 @@ def build_static_library(c_files: Iterable[CFile],
     return CStaticLibrary(output_path + "/libtest.a", "test", obj_files)
 
 
+def add_to_static_library(c_path: str,
+                          static_library: CStaticLibrary) -> None:
+    """
+    Compile a C file written by the transpiler, like the statements it moved
+    into C shims, and add it to the static library
+    """
+    obj_path = os.path.splitext(c_path)[0] + ".o"
+
+    args = ["-c", "-fPIC", "-march=native", "-o", obj_path, c_path]
+    logging.debug("complication command:\n %s", str(clang[args]))
+    retcode, stdout, stderr = clang[args].run(retcode=None)
+
+    if retcode != 0:
+        raise NonZeroReturn(stderr)
+
+    args = ["-rv", static_library.path, obj_path]
+    logging.debug("combination command:\n %s", str(ar[args]))
+    retcode, stdout, stderr = ar[args].run(retcode=None)
+
+    if retcode != 0:
+        raise NonZeroReturn(stderr)
+
+    static_library.obj_files.append(obj_path)
+
+
+def read_translation(rust_path: str, shims_path: str) -> bytes:
+    """
+    Read a translated file, along with the C shims written next to it, if any
+    """
+    with open(rust_path, 'rb') as file:
+        translation = file.read()
+    if os.path.exists(shims_path):
+        with open(shims_path, 'rb') as file:
+            translation += file.read()
+    return translation
+
+
 class TestFunction:
     def __init__(self, name: str, flags: Set[str] = set()) -> None:
         self.name = name
@@ -224,7 +308,7 @@ class TestDirectory:
                 _, ext = os.path.splitext(path)
                 filename = os.path.splitext(os.path.basename(path))[0]
 
-                if ext == ".c":
+                if ext in (".c", ".hpp"):
                     c_file = self._read_c_file(path)
 
                     if c_file:
@@ -234,7 +318,8 @@ class TestDirectory:
                       files.search(filename)):
                     rs_test_file = self._read_rust_test_file(path)
 
-                    self.rs_test_files.append(rs_test_file)
+                    if rs_test_file:
+                        self.rs_test_files.append(rs_test_file)
 
     def _read_c_file(self, path: str) -> Optional[CFile]:
         file_config = None
@@ -250,9 +335,13 @@ class TestDirectory:
         if "skip_translation" in file_flags:
             return
 
+        # Tests of Apple platform features, such as blocks, only run on macOS
+        if "macos_only" in file_flags and not on_mac():
+            return
+
         return CFile(self.logLevel, path, file_flags)
 
-    def _read_rust_test_file(self, path: str) -> TestFile:
+    def _read_rust_test_file(self, path: str) -> Optional[TestFile]:
         with open(path, 'r', encoding="utf-8") as file:
             file_buffer = file.read()
 
@@ -263,6 +352,9 @@ class TestDirectory:
             flags_str = file_config.group(0)[3:]
             file_flags = {flag.strip() for flag in flags_str.split(',')}
 
+        if "macos_only" in file_flags and not on_mac():
+            return
+
         found_tests = re.findall(
             r"(//(.*))?\n\s*pub fn (test_\w+)\(\)", file_buffer)
         test_fns = []
@@ -286,18 +378,19 @@ class TestDirectory:
         if message:
             sys.stdout.write(message)
 
-    def _generate_cc_db(self, c_file_path: str) -> None:
-        directory, cfile = os.path.split(c_file_path)
+    def _generate_cc_db(self, c_file: CFile) -> None:
+        directory, cfile = os.path.split(c_file.path)
+        lang_args = '"-x", "c++", ' if c_file.is_cxx_header else ''
 
         compile_commands = """ \
         [
           {{
-            "arguments": [ "cc", "-D_FORTIFY_SOURCE=0", "-c", "{0}" ],
+            "arguments": [ "cc", "-D_FORTIFY_SOURCE=0", {2}"-c", "{0}" ],
             "directory": "{1}",
             "file": "{0}"
           }}
         ]
-        """.format(cfile, directory)
+        """.format(cfile, directory, lang_args)
 
         cc_db = os.path.join(directory, "compile_commands.json")
 
@@ -337,7 +430,9 @@ class TestDirectory:
         self.print_status(Colors.WARNING, "RUNNING", description)
 
         try:
-            static_library = build_static_library(self.c_files, self.full_path)
+            static_library = build_static_library(
+                [c_file for c_file in self.c_files if not c_file.is_cxx_header],
+                self.full_path)
         except NonZeroReturn as exception:
             self.print_status(Colors.FAIL, "FAILED", "create libtest.a")
             sys.stdout.write('\n')
@@ -371,7 +466,7 @@ class TestDirectory:
             # Run the step
             self.print_status(Colors.WARNING, "RUNNING", description)
 
-            self._generate_cc_db(c_file.path)
+            self._generate_cc_db(c_file)
 
             try:
                 translated_rust_file = c_file.translate(self.generated_files["cc_db"],
@@ -386,6 +481,59 @@ class TestDirectory:
                 continue
 
             self.generated_files["rust_src"].append(translated_rust_file)
+
+            # Statements the transpiler moved into C shims are written next to the C file
+            shims_path = os.path.join(os.path.dirname(c_file.path), "c2rust_shims.c")
+
+            # Translating the same file again must produce byte-identical output
+            if c_file.check_deterministic:
+                first_translation = read_translation(translated_rust_file.path, shims_path)
+
+                try:
+                    c_file.translate(self.generated_files["cc_db"],
+                                     extra_args=["-march=native"])
+                except NonZeroReturn as exception:
+                    self.print_status(Colors.FAIL, "FAILED", "re-translate " +
+                                      c_file_short)
+                    sys.stdout.write('\n')
+                    sys.stdout.write(str(exception))
+
+                    outcomes.append(TestOutcome.UnexpectedFailure)
+                    continue
+
+                second_translation = read_translation(translated_rust_file.path, shims_path)
+
+                if first_translation != second_translation:
+                    self.print_status(Colors.FAIL, "FAILED",
+                                      "non-deterministic translation of " +
+                                      c_file_short)
+                    sys.stdout.write('\n')
+
+                    outcomes.append(TestOutcome.UnexpectedFailure)
+                    continue
+
+            # The shims are linked from the C library
+            if c_file.shim_untranslatable_stmts:
+                if os.path.exists(shims_path):
+                    try:
+                        add_to_static_library(shims_path, static_library)
+                    except NonZeroReturn as exception:
+                        self.print_status(Colors.FAIL, "FAILED", "compile shims of " +
+                                          c_file_short)
+                        sys.stdout.write('\n')
+                        sys.stdout.write(str(exception))
+
+                        outcomes.append(TestOutcome.UnexpectedFailure)
+                        continue
+                    finally:
+                        self.generated_files["rust_src"].append(shims_path)
+                    self.generated_files["c_obj"].append(static_library.obj_files[-1])
+
+            # Byte arrays moved out of the translation are written next to it
+            if c_file.embed_byte_arrays:
+                rust_stem, _ = os.path.splitext(translated_rust_file.path)
+                self.generated_files["rust_src"].extend(glob.glob(rust_stem + ".*.bin"))
+
             if c_file.emit_build_files:
                 self.generated_files["rust_src"].append(self.full_path + "/src/Cargo.toml")
                 self.generated_files["rust_src"].append(self.full_path + "/src/build.rs")
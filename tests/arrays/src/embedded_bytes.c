@@ -0,0 +1,35 @@
+//! embed_byte_arrays
+
+#define B16 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, \
+            0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff
+#define B256 B16, B16, B16, B16, B16, B16, B16, B16, \
+             B16, B16, B16, B16, B16, B16, B16, B16
+#define B1K B256, B256, B256, B256
+
+// A 10KB blob, like one included from a generated data file, which is
+// moved into a .bin file included with include_bytes!
+const unsigned char blob[] = {
+    B1K, B1K, B1K, B1K, B1K, B1K, B1K, B1K, B1K, B1K,
+};
+
+static unsigned checksum(const unsigned char *data, unsigned len) {
+    unsigned hash = 0;
+    for (unsigned i = 0; i < len; i++) {
+        hash = hash * 31 + data[i];
+    }
+    return hash;
+}
+
+void embedded_bytes(const unsigned buffer_size, int buffer[]) {
+    // A local array of (signed) chars, which is transmuted from the bytes
+    const char text[] = { B1K, 'e', 'n', 'd' };
+
+    if (buffer_size < 6) return;
+
+    buffer[0] = sizeof(blob);
+    buffer[1] = checksum(blob, sizeof(blob));
+    buffer[2] = blob[sizeof(blob) - 1];
+    buffer[3] = sizeof(text);
+    buffer[4] = checksum((const unsigned char *)text, sizeof(text));
+    buffer[5] = text[1];
+}
@@ -0,0 +1,33 @@
+#define N 4
+
+static float trace(float m[][N], int rows) {
+    float sum = 0;
+    for (int i = 0; i < rows && i < N; i++)
+        sum += m[i][i];
+    return sum;
+}
+
+static void transpose(int dst[restrict][N], int src[restrict][N]) {
+    for (int i = 0; i < N; i++)
+        for (int j = 0; j < N; j++)
+            dst[j][i] = src[i][j];
+}
+
+void matrix_params(int buffer[]) {
+    float m[3][N] = {
+        {1.5f, 2, 3, 4},
+        {5, 6.25f, 7, 8},
+        {9, 10, 11, 12.5f},
+    };
+    buffer[0] = (int)(trace(m, 3) * 4);
+    buffer[1] = (int)(trace(m + 1, 2) * 4);
+
+    int src[N][N], dst[N][N];
+    for (int i = 0; i < N; i++)
+        for (int j = 0; j < N; j++)
+            src[i][j] = i * N + j;
+    transpose(dst, src);
+    for (int i = 0; i < N; i++)
+        for (int j = 0; j < N; j++)
+            buffer[2 + i * N + j] = dst[i][j];
+}
@@ -0,0 +1,28 @@
+void sizeof_operands(unsigned buffer[]) {
+    int arr[7];
+    int *p = arr;
+
+    // The operand of sizeof is not evaluated unless it has a VLA type
+    buffer[0] = sizeof(*p++);
+    buffer[1] = p == arr;
+    buffer[2] = sizeof(arr) / sizeof(arr[0]);
+
+    int n = 3;
+    int vla[n++];
+    buffer[3] = n;
+    buffer[4] = sizeof(vla);
+    buffer[5] = sizeof(vla) / sizeof(vla[0]);
+    buffer[6] = n;
+
+    // A VLA type name evaluates its length expression
+    buffer[7] = sizeof(int[n++]);
+    buffer[8] = n;
+
+    int m = 2;
+    int grid[m][n];
+    m = 10;
+    n = 1;
+    buffer[9] = sizeof(grid[0]) / sizeof(grid[0][0]);
+    buffer[10] = sizeof(grid) / sizeof(grid[0]);
+    buffer[11] = sizeof(grid);
+}
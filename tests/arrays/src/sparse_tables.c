@@ -0,0 +1,41 @@
+// A 64k character class table with 20 designated entries, some of which are ranges or override
+// part of an earlier range
+static unsigned char classes[65536] = {
+    ['0' ... '9'] = 1,
+    ['A' ... 'Z'] = 2,
+    ['a' ... 'z'] = 3,
+    [' '] = 4,
+    ['\t'] = 4,
+    ['\n'] = 4,
+    ['\r'] = 4,
+    ['_'] = 5,
+    ['+'] = 6,
+    ['-'] = 6,
+    ['*'] = 6,
+    ['/'] = 6,
+    ['X'] = 7,
+    [0x00c9] = 8,
+    [0x00e9] = 8,
+    [0x4e00 ... 0x4e0f] = 9,
+    [0xfeff] = 10,
+    [0xfffe] = 11,
+    ['5'] = 12,
+    [0xffff] = 13,
+};
+
+unsigned char sparse_class(unsigned c) {
+    return classes[c];
+}
+
+// A sparse local table, with values only known at run time
+void sparse_local(unsigned buffer[], int base) {
+    unsigned short local[1024] = {
+        [1 ... 3] = base,
+        [700] = base * 2,
+        [2] = 5,
+        [1023] = 9,
+    };
+    for (int i = 0; i < 1024; i++) {
+        buffer[i] = local[i];
+    }
+}
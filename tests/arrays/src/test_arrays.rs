@@ -3,7 +3,10 @@ extern crate libc;
 use arrays::rust_entry;
 use incomplete_arrays::{rust_test_sized_array,rust_entry2,rust_check_some_ints};
 use variable_arrays::{rust_variable_arrays, rust_alloca_arrays};
-use self::libc::{c_int, c_uint};
+use matrix_params::rust_matrix_params;
+use sizeof_operands::rust_sizeof_operands;
+use sparse_tables::{rust_sparse_class, rust_sparse_local};
+use self::libc::{c_int, c_uchar, c_uint};
 
 #[link(name = "test")]
 extern "C" {
@@ -24,6 +27,18 @@ extern "C" {
 
     #[no_mangle]
     fn check_some_ints() -> bool;
+
+    #[no_mangle]
+    fn matrix_params(_: *mut c_int);
+
+    #[no_mangle]
+    fn sizeof_operands(_: *mut c_uint);
+
+    #[no_mangle]
+    fn sparse_class(_: c_uint) -> c_uchar;
+
+    #[no_mangle]
+    fn sparse_local(_: *mut c_uint, _: c_int);
 }
 
 #[no_mangle]
@@ -134,3 +149,65 @@ pub fn test_alloca_arrays() {
         assert_eq!(buffer[index], rust_buffer[index], "index: {}", index);
     }
 }
+
+pub fn test_matrix_params() {
+    let mut buffer = [0; 18];
+    let mut rust_buffer = [0; 18];
+    let expected_buffer = [
+        75, 60,
+        0, 4, 8, 12,
+        1, 5, 9, 13,
+        2, 6, 10, 14,
+        3, 7, 11, 15,
+    ];
+
+    unsafe {
+        matrix_params(buffer.as_mut_ptr());
+        rust_matrix_params(rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_sizeof_operands() {
+    let mut buffer = [0; 12];
+    let mut rust_buffer = [0; 12];
+    let expected_buffer = [4, 1, 7, 4, 12, 3, 4, 16, 5, 5, 2, 40];
+
+    unsafe {
+        sizeof_operands(buffer.as_mut_ptr());
+        rust_sizeof_operands(rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_sparse_tables() {
+    for c in 0..65536 {
+        unsafe {
+            assert_eq!(sparse_class(c), rust_sparse_class(c), "index: {}", c);
+        }
+    }
+    unsafe {
+        assert_eq!(rust_sparse_class('X' as c_uint), 7);
+        assert_eq!(rust_sparse_class('5' as c_uint), 12);
+        assert_eq!(rust_sparse_class(0x4e0f), 9);
+        assert_eq!(rust_sparse_class(0x4e10), 0);
+    }
+
+    let mut buffer = [0; 1024];
+    let mut rust_buffer = [0; 1024];
+
+    unsafe {
+        sparse_local(buffer.as_mut_ptr(), 7);
+        rust_sparse_local(rust_buffer.as_mut_ptr(), 7);
+    }
+
+    for index in 0..1024 {
+        assert_eq!(buffer[index], rust_buffer[index], "index: {}", index);
+    }
+    assert_eq!(buffer[..5], [0, 7, 5, 7, 0]);
+    assert_eq!((buffer[700], buffer[1023]), (14, 9));
+}
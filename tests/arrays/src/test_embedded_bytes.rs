@@ -0,0 +1,35 @@
+extern crate libc;
+
+use embedded_bytes::{rust_blob, rust_embedded_bytes};
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn embedded_bytes(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 6;
+
+pub fn test_embedded_bytes() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [10240, -1892854784, 255, 1027, 320349915, 17];
+
+    unsafe {
+        embedded_bytes(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_embedded_bytes(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_blob_is_included() {
+    assert_eq!(rust_blob.len(), 10240);
+    assert_eq!(rust_blob[17], 0x11);
+
+    let src = include_str!("embedded_bytes.rs");
+    assert!(src.contains(r#"include_bytes!("embedded_bytes.0.bin")"#));
+    assert!(!src.contains("0xff"));
+}
@@ -0,0 +1,25 @@
+#include <stdio.h>
+#include <string.h>
+
+// With `_FORTIFY_SOURCE`, glibc's headers turn calls to these functions into the calls below,
+// passing `__builtin_object_size` of the destination. The tests are built without it, so the
+// checked variants are called directly.
+int __sprintf_chk(char *s, int flag, size_t slen, const char *format, ...);
+
+void fortify_chk(const char src[6], char *out) {
+    char buf[16];
+    char *p = buf;
+    int n;
+
+    // Known object sizes, checked inline
+    __builtin___memcpy_chk(buf, src, 6, __builtin_object_size(buf, 0));
+    n = __builtin___sprintf_chk(buf + 5, 1, sizeof(buf) - 5, "-%d", 42);
+    __builtin___strcat_chk(buf, "!", sizeof(buf));
+    __builtin___memset_chk(out, '.', 32, 32);
+
+    // Unknown object sizes, lowered to the unchecked functions
+    __builtin___memcpy_chk(out, buf, strlen(buf), __builtin_object_size(out, 0));
+    __sprintf_chk(out + 10, 1, (size_t)-1, "%d:%s", n, src);
+    __builtin___snprintf_chk(p, 8, 1, __builtin_object_size(p, 1), "%03d", n);
+    __builtin___strcpy_chk(out + 20, buf, __builtin_object_size(out, 1));
+}
@@ -2,8 +2,10 @@
 extern crate libc;
 
 use atomics::{rust_atomics_entry, rust_new_atomics};
+use fortify::rust_fortify_chk;
 use mem_x_fns::rust_mem_x;
 use math::{rust_ffs, rust_ffsl, rust_ffsll, rust_isfinite, rust_isnan, rust_isinf_sign};
+use types_compatible::rust_types_compatible;
 use self::libc::{c_int, c_uint, c_char, c_long, c_longlong, c_double};
 
 #[link(name = "test")]
@@ -15,6 +17,8 @@ extern "C" {
     #[no_mangle]
     fn mem_x(_: *const c_char, _: *mut c_char);
     #[no_mangle]
+    fn fortify_chk(_: *const c_char, _: *mut c_char);
+    #[no_mangle]
     fn ffs(_: c_int) -> c_int;
     #[no_mangle]
     fn ffsl(_: c_long) -> c_int;
@@ -26,6 +30,8 @@ extern "C" {
     fn isnan(_: c_double) -> c_int;
     #[no_mangle]
     fn isinf_sign(_: c_double) -> c_int;
+    #[no_mangle]
+    fn types_compatible(_: c_int, _: c_uint, _: c_double, _: *mut c_int);
 }
 
 const BUFFER_SIZE: usize = 1024;
@@ -76,6 +82,22 @@ pub fn test_mem_fns() {
     }
 }
 
+pub fn test_fortify_chk() {
+    let const_string = "hello\0";
+    let mut buffer = [0; 32];
+    let mut rust_buffer = [0; 32];
+    let const_str_ptr = const_string.as_ptr() as *const i8;
+
+    unsafe {
+       fortify_chk(const_str_ptr, buffer.as_mut_ptr());
+       rust_fortify_chk(const_str_ptr, rust_buffer.as_mut_ptr());
+    }
+
+    for index in 0..32 {
+        assert_eq!(buffer[index], rust_buffer[index]);
+    }
+}
+
 pub fn test_ffs() {
     for i in 0..256 {
         let ffs_ret = unsafe {
@@ -154,3 +176,17 @@ pub fn test_clang9_intrinsics() {
         assert_eq!(isinf_sign_ret, rust_isinf_sign_ret);
     }
 }
+
+pub fn test_types_compatible() {
+    let mut buffer = [0; BUFFER_SIZE2];
+    let mut rust_buffer = [0; BUFFER_SIZE2];
+
+    for &(n, u, d) in &[(7, 3, 2.5), (-9, 0, -1.25)] {
+        unsafe {
+            types_compatible(n, u, d, buffer.as_mut_ptr());
+            rust_types_compatible(n, u, d, rust_buffer.as_mut_ptr());
+        }
+
+        assert_eq!(buffer, rust_buffer);
+    }
+}
@@ -0,0 +1,28 @@
+typedef int my_int;
+
+// Halves integers with integer division and floating point values exactly,
+// picking the code path from the type of the argument
+#define HALF(x)                                                                \
+    (__builtin_types_compatible_p(__typeof__(x), double) ? (x) * 0.5 : (x) / 2)
+
+#define IS_UNSIGNED(x) __builtin_types_compatible_p(__typeof__(x), unsigned int)
+
+void types_compatible(int n, unsigned u, double d, int *out) {
+    my_int m = n;
+    const int c = n;
+    int i = 0;
+
+    out[i++] = __builtin_types_compatible_p(int, my_int);
+    out[i++] = __builtin_types_compatible_p(const int, int);
+    out[i++] = __builtin_types_compatible_p(int *, const int *);
+    out[i++] = __builtin_types_compatible_p(long, int);
+    out[i++] = IS_UNSIGNED(u) + IS_UNSIGNED(n);
+    out[i++] = HALF(n);
+    out[i++] = (int)(HALF(d) * 10);
+
+    if (__builtin_types_compatible_p(__typeof__(c), int) && !IS_UNSIGNED(m)) {
+        out[i++] = 1;
+    } else {
+        out[i++] = 2;
+    }
+}
@@ -0,0 +1,37 @@
+//! translate_enums
+
+// Only declared here, so the translation calls the C definitions in
+// ffi_enum_impl.c through an extern block
+enum color { RED, GREEN, BLUE };
+enum shape { CIRCLE, SQUARE };
+
+struct pixel {
+  enum shape shape;
+  int x;
+};
+
+struct empty {};
+
+enum color next_color(enum color c);
+int pixel_weight(struct pixel p);
+int scaled(struct empty e, int n);
+
+void entry9(const unsigned buffer_size, int buffer[]) {
+  if (buffer_size < 6) { return; }
+
+  // Enums cross the boundary as integers
+  enum color c = next_color(RED);
+  buffer[0] = c;
+  buffer[1] = next_color(c) == BLUE;
+  buffer[2] = next_color(BLUE);
+
+  // C may store any value in the field, so `shape` stays a set of constants
+  struct pixel p = { SQUARE, 3 };
+  buffer[3] = pixel_weight(p);
+  p.shape = CIRCLE;
+  buffer[4] = pixel_weight(p);
+
+  // The zero-sized argument is left out
+  struct empty e;
+  buffer[5] = scaled(e, 7);
+}
@@ -0,0 +1,22 @@
+// Definitions called through the extern declarations of ffi_enum.c
+enum color { RED, GREEN, BLUE };
+enum shape { CIRCLE, SQUARE };
+
+struct pixel {
+  enum shape shape;
+  int x;
+};
+
+struct empty {};
+
+enum color next_color(enum color c) {
+  return (c + 1) % 3;
+}
+
+int pixel_weight(struct pixel p) {
+  return p.shape == SQUARE ? p.x * p.x : p.x;
+}
+
+int scaled(struct empty e, int n) {
+  return 10 * n;
+}
@@ -0,0 +1,22 @@
+#include <stdint.h>
+
+// Enums with a fixed or packed underlying type are smaller than an `int`
+enum level : uint8_t { LOW = 1, HIGH = 200 };
+enum __attribute__((packed)) small { SMALL_A = -1, SMALL_B = 100 };
+
+struct reading {
+  enum level level;
+  enum small small;
+  uint8_t value;
+};
+
+void entry7(const unsigned buffer_size, int buffer[]) {
+  if (buffer_size < 4) { return; }
+
+  buffer[0] = sizeof(enum level);
+  buffer[1] = sizeof(enum small);
+  buffer[2] = sizeof(struct reading);
+
+  struct reading r = { HIGH, SMALL_A, 7 };
+  buffer[3] = r.level + r.small + r.value;
+}
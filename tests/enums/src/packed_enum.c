@@ -0,0 +1,27 @@
+//! translate_enums
+
+#include <stdint.h>
+
+enum mode : uint8_t { MODE_OFF, MODE_ON = 250 };
+enum __attribute__((packed)) sign { NEGATIVE = -1, ZERO, POSITIVE };
+
+static int describe(enum mode m, enum sign s) {
+  switch (m) {
+    case MODE_OFF: return s;
+    case MODE_ON: return 10 + s;
+  }
+  return -100;
+}
+
+void entry8(const unsigned buffer_size, int buffer[]) {
+  if (buffer_size < 5) { return; }
+
+  buffer[0] = sizeof(enum mode);
+  buffer[1] = sizeof(enum sign);
+
+  enum mode m = MODE_ON;
+  enum sign s = buffer[0] == 1 ? NEGATIVE : POSITIVE;
+  buffer[2] = describe(m, s);
+  buffer[3] = describe(MODE_OFF, POSITIVE);
+  buffer[4] = m;
+}
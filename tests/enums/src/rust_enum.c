@@ -0,0 +1,39 @@
+//! translate_enums
+
+enum direction { NORTH, EAST, SOUTH, WEST };
+
+// Duplicate values can't be Rust enum discriminants, so this stays a set of constants
+enum flag { FLAG_NONE = 0, FLAG_OFF = 0, FLAG_ON = 1 };
+
+// Combinations of flags aren't enumerators, so this stays a set of constants too
+enum permission { PERM_NONE, PERM_READ = 1, PERM_WRITE = 2 };
+
+static int turns(enum direction d) {
+  switch (d) {
+    case NORTH: return 0;
+    case EAST: return 1;
+    case SOUTH: return 2;
+    case WEST: return 3;
+  }
+  return -1;
+}
+
+void entry6(const unsigned buffer_size, int buffer[]) {
+  if (buffer_size < 7) { return; }
+
+  enum direction d = EAST;
+  buffer[0] = turns(NORTH);
+  buffer[1] = turns(d);
+
+  // Enumerators are converted back into the enum
+  d = buffer[1] == 1 ? SOUTH : WEST;
+  buffer[2] = turns(d);
+  buffer[3] = d == SOUTH;
+  buffer[4] = d;
+
+  enum flag f = FLAG_ON;
+  buffer[5] = f + FLAG_OFF;
+
+  enum permission p = PERM_READ | PERM_WRITE;
+  buffer[6] = p;
+}
@@ -6,6 +6,10 @@ use enum_duplicate::{e, rust_entry3};
 use top_enum::{E as otherE, rust_entry4};
 use big_enum::{E1, E2, E3, rust_entry5};
 use non_canonical_enum_def::{rust_abc, hrtimer_restart, HRTIMER_RESTART, HRTIMER_NORESTART};
+use rust_enum::{direction, rust_entry6};
+use fixed_enum::{level, small, rust_entry7};
+use packed_enum::{mode, sign, rust_entry8};
+use ffi_enum::{color, rust_entry9};
 
 use self::libc::{c_int, c_uint};
 
@@ -25,6 +29,18 @@ extern "C" {
 
     #[no_mangle]
     fn entry5(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn entry6(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn entry7(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn entry8(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn entry9(_: c_uint, _: *mut c_int);
 }
 
 const BUFFER_SIZE: usize = 10;
@@ -32,6 +48,10 @@ const BUFFER_SIZE2: usize = 7;
 const BUFFER_SIZE3: usize = 4;
 const BUFFER_SIZE4: usize = 1;
 const BUFFER_SIZE5: usize = 6;
+const BUFFER_SIZE6: usize = 7;
+const BUFFER_SIZE7: usize = 4;
+const BUFFER_SIZE8: usize = 5;
+const BUFFER_SIZE9: usize = 6;
 
 
 pub fn test_variants() {
@@ -109,3 +129,100 @@ pub fn test_buffer5() {
     assert_eq!(buffer, rust_buffer);
     assert_eq!(buffer, expected_buffer);
 }
+
+pub fn test_buffer6() {
+    let mut buffer = [0; BUFFER_SIZE6];
+    let mut rust_buffer = [0; BUFFER_SIZE6];
+    let expected_buffer = [0, 1, 2, 1, 2, 1, 3];
+
+    unsafe {
+        entry6(BUFFER_SIZE6 as u32, buffer.as_mut_ptr());
+        rust_entry6(BUFFER_SIZE6 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_rust_enum() {
+    assert_eq!(direction::SOUTH as u32, 2);
+    assert!(direction::NORTH != direction::WEST);
+
+    let src = include_str!("rust_enum.rs");
+    assert!(src.contains("#[repr(C)]\npub enum direction {"));
+    assert!(src.contains("match d {"));
+    assert!(src.contains("direction::EAST => {"));
+    // Enums with duplicate values fall back to constants
+    assert!(src.contains("pub type flag = "));
+    // So do enums given other values than their enumerators
+    assert!(src.contains("pub type permission = "));
+    assert!(!src.contains("transmute"));
+}
+
+pub fn test_buffer7() {
+    let mut buffer = [0; BUFFER_SIZE7];
+    let mut rust_buffer = [0; BUFFER_SIZE7];
+    let expected_buffer = [1, 1, 3, 206];
+
+    unsafe {
+        entry7(BUFFER_SIZE7 as u32, buffer.as_mut_ptr());
+        rust_entry7(BUFFER_SIZE7 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_fixed_enum() {
+    assert_eq!(std::mem::size_of::<level>(), 1);
+    assert_eq!(std::mem::size_of::<small>(), 1);
+}
+
+pub fn test_buffer8() {
+    let mut buffer = [0; BUFFER_SIZE8];
+    let mut rust_buffer = [0; BUFFER_SIZE8];
+    let expected_buffer = [1, 1, 9, 1, 250];
+
+    unsafe {
+        entry8(BUFFER_SIZE8 as u32, buffer.as_mut_ptr());
+        rust_entry8(BUFFER_SIZE8 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_packed_enum() {
+    assert_eq!(std::mem::size_of::<mode>(), 1);
+    assert_eq!(std::mem::size_of::<sign>(), 1);
+    assert_eq!(mode::MODE_ON as u8, 250);
+    assert_eq!(sign::NEGATIVE as i8, -1);
+
+    let src = include_str!("packed_enum.rs");
+    assert!(src.contains("#[repr(u8)]\npub enum mode {"));
+    assert!(src.contains("#[repr(i8)]\npub enum sign {"));
+}
+
+pub fn test_buffer9() {
+    let mut buffer = [0; BUFFER_SIZE9];
+    let mut rust_buffer = [0; BUFFER_SIZE9];
+    let expected_buffer = [1, 1, 0, 9, 3, 70];
+
+    unsafe {
+        entry9(BUFFER_SIZE9 as u32, buffer.as_mut_ptr());
+        rust_entry9(BUFFER_SIZE9 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_ffi_enum() {
+    assert!(color::from_int(2) == color::BLUE);
+
+    // The extern declarations only use FFI-safe types
+    let src = include_str!("ffi_enum.rs");
+    assert!(src.contains("fn next_color(c: libc::c_uint) -> libc::c_uint;"));
+    assert!(src.contains("fn scaled(n: libc::c_int) -> libc::c_int;"));
+    assert!(src.contains("pub type shape = libc::c_uint;"));
+}
@@ -0,0 +1,31 @@
+extern crate libc;
+
+use tgmath::rust_sqrt_all;
+use self::libc::{c_double, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn sqrt_all(_: c_uint, _: *mut c_double);
+}
+
+const BUFFER_SIZE: usize = 4;
+
+pub fn test_tgmath() {
+    let mut buffer = [0.; BUFFER_SIZE];
+    let mut rust_buffer = [0.; BUFFER_SIZE];
+
+    unsafe {
+        sqrt_all(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_sqrt_all(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+
+    // The _Generic selection resolves to the float width methods, not libm calls
+    let src = include_str!("tgmath.rs");
+
+    assert!(src.contains(".sqrt()"));
+    assert!(!src.contains("sqrtf("));
+    assert!(!src.contains(" sqrt("));
+}
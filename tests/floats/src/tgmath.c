@@ -0,0 +1,24 @@
+#include <math.h>
+
+// Type-generic math in the style of <tgmath.h>
+#define tg_sqrt(x) _Generic((x), float: sqrtf, long double: sqrtl, default: sqrt)(x)
+
+float sqrt_float(float x) {
+    return tg_sqrt(x);
+}
+
+double sqrt_double(double x) {
+    return tg_sqrt(x);
+}
+
+void sqrt_all(unsigned buffer_size, double buffer[const]) {
+    float f = 2.0f;
+    double d = 2.0;
+
+    if (buffer_size < 4) return;
+
+    buffer[0] = sqrt_float(f);
+    buffer[1] = sqrt_double(d);
+    buffer[2] = tg_sqrt(f * 8.0f);
+    buffer[3] = tg_sqrt(d + 7.0);
+}
@@ -0,0 +1,12 @@
+int asm_goto(int x) {
+    asm goto("cmpl $1, %0\n\t"
+             "je %l1\n\t"
+             "cmpl $2, %0\n\t"
+             "je %l2"
+             : : "r"(x) : "cc" : one, two);
+    return 0;
+one:
+    return 10;
+two:
+    return 20;
+}
@@ -0,0 +1,25 @@
+//! feature_asm
+
+extern crate libc;
+
+use asm_goto::rust_asm_goto;
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn asm_goto(_: c_int) -> c_int;
+}
+
+pub fn test_asm_goto() {
+    for x in 0..4 {
+        unsafe {
+            assert_eq!(asm_goto(x), rust_asm_goto(x));
+        }
+    }
+    unsafe {
+        assert_eq!(rust_asm_goto(0), 0);
+        assert_eq!(rust_asm_goto(1), 10);
+        assert_eq!(rust_asm_goto(2), 20);
+    }
+}
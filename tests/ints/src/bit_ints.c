@@ -0,0 +1,94 @@
+#include <stddef.h>
+
+typedef _BitInt(24) i24;
+typedef unsigned _BitInt(24) u24;
+
+struct packed24 {
+    i24 s;
+    u24 u;
+};
+
+// Each field is laid out like the integer that stores it
+struct mixed_bit_ints {
+    char c;
+    _BitInt(7) b7;
+    unsigned _BitInt(24) u24;
+    char d;
+    _BitInt(40) b40;
+    unsigned _BitInt(33) u33;
+    _BitInt(64) b64;
+};
+
+void bit_ints(const unsigned sz, int buffer[const]) {
+    int i = 0;
+
+    // Signed values wrap around at the 24-bit boundary
+    i24 s = 8388607;
+    buffer[i++] = s;
+    s += 1;
+    buffer[i++] = s;
+    s -= 1;
+    buffer[i++] = s;
+    s = s * 2;
+    buffer[i++] = s;
+    s++;
+    buffer[i++] = s;
+    buffer[i++] = (i24)0x7ffff << 4;
+    buffer[i++] = -s;
+    buffer[i++] = ~s;
+
+    // Conversions from wider types keep the low 24 bits
+    int wide = 0x12345678;
+    s = wide;
+    buffer[i++] = s;
+    s = -wide;
+    buffer[i++] = s;
+
+    // Unsigned values are reduced modulo 2^24
+    u24 u = 16777215;
+    buffer[i++] = u;
+    u += 1;
+    buffer[i++] = u;
+    u -= 1;
+    buffer[i++] = u;
+    u = u * 3;
+    buffer[i++] = u;
+    u = -u;
+    buffer[i++] = u;
+    u = ~u;
+    buffer[i++] = u;
+    u <<= 12;
+    buffer[i++] = u;
+    u = wide;
+    buffer[i++] = u;
+
+    // Comparisons see the wrapped values
+    i24 a = 8388607;
+    i24 b = a + 1;
+    buffer[i++] = b < a;
+    u24 c = 0;
+    u24 d = c - 1;
+    buffer[i++] = d > c;
+
+    // Struct fields use the layout of the containing integer
+    struct packed24 p = { -1, 16777215 };
+    p.s += 2;
+    p.u += 2;
+    buffer[i++] = p.s;
+    buffer[i++] = p.u;
+    buffer[i++] = sizeof(struct packed24);
+}
+
+void bit_int_layout(const unsigned sz, int buffer[const]) {
+    int i = 0;
+
+    buffer[i++] = sizeof(struct mixed_bit_ints);
+    buffer[i++] = _Alignof(struct mixed_bit_ints);
+    buffer[i++] = offsetof(struct mixed_bit_ints, c);
+    buffer[i++] = offsetof(struct mixed_bit_ints, b7);
+    buffer[i++] = offsetof(struct mixed_bit_ints, u24);
+    buffer[i++] = offsetof(struct mixed_bit_ints, d);
+    buffer[i++] = offsetof(struct mixed_bit_ints, b40);
+    buffer[i++] = offsetof(struct mixed_bit_ints, u33);
+    buffer[i++] = offsetof(struct mixed_bit_ints, b64);
+}
@@ -0,0 +1,80 @@
+//! strict_bit_ints
+
+typedef _BitInt(24) s24;
+typedef unsigned _BitInt(7) u7;
+
+struct strict_fields {
+    s24 s;
+    u7 u;
+};
+
+static s24 global = 8388607;
+
+static s24 twice(s24 x) {
+    return x * 2;
+}
+
+void strict_bit_ints(const unsigned sz, int buffer[const]) {
+    int i = 0;
+
+    // Signed values wrap around at the 24-bit boundary
+    s24 s = global;
+    s += 1;
+    buffer[i++] = s;
+    s++;
+    buffer[i++] = s;
+    buffer[i++] = s--;
+    buffer[i++] = s;
+    buffer[i++] = twice(8388607);
+    s++;
+    buffer[i++] = -s;
+    buffer[i++] = ~s;
+    buffer[i++] = (s24)0x7ffff << 4;
+    buffer[i++] = s / 3;
+    buffer[i++] = s % 5;
+
+    // Unsigned values are reduced modulo 2^7
+    u7 u = 127;
+    u += 1;
+    buffer[i++] = u;
+    u--;
+    buffer[i++] = u;
+    u = u * 3;
+    buffer[i++] = u;
+    u = -u;
+    buffer[i++] = u;
+    u <<= 3;
+    buffer[i++] = u;
+    u = u | 1;
+    buffer[i++] = u;
+
+    // Comparisons, conditions, switches and indexing see the values
+    s24 a = 8388607;
+    s24 b = a + 1;
+    buffer[i++] = b < a;
+    buffer[i++] = !b;
+    buffer[i++] = b ? 2 : 3;
+    switch (u) {
+    case 25:
+        buffer[i++] = 1;
+        break;
+    default:
+        buffer[i++] = 0;
+    }
+    int table[4] = { 10, 20, 30, 40 };
+    u7 idx = 2;
+    buffer[i++] = table[idx];
+    buffer[i++] = *(table + idx);
+
+    // Struct fields keep the layout of the storage integers
+    struct strict_fields f = { -1, 200 };
+    f.s -= 1;
+    f.u++;
+    buffer[i++] = f.s;
+    buffer[i++] = f.u;
+    struct strict_fields z = { 0 };
+    buffer[i++] = z.s + z.u;
+    buffer[i++] = sizeof(struct strict_fields);
+    buffer[i++] = sizeof(u7);
+    buffer[i++] = sizeof(s24) == sizeof(int);
+}
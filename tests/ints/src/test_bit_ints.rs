@@ -0,0 +1,103 @@
+//! extern_crate_memoffset
+
+extern crate libc;
+
+use bit_ints::{mixed_bit_ints, rust_bit_ints};
+use strict_bit_ints::{rust_strict_bit_ints, strict_fields, BitInt24, UBitInt7};
+
+use self::libc::{c_int, c_uint};
+use std::mem;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn bit_ints(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn bit_int_layout(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn strict_bit_ints(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 23;
+const LAYOUT_SIZE: usize = 9;
+const STRICT_BUFFER_SIZE: usize = 28;
+
+pub fn test_buffer() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [
+        8388607, -8388608, 8388607, -2, -1, 8388592, 1, 0, 3430008, -3430008,
+        16777215, 0, 16777215, 16777213, 3, 16777212, 16760832, 3430008,
+        1, 1, 1, 1, 8,
+    ];
+
+    unsafe {
+        bit_ints(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_bit_ints(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_layout() {
+    let mut buffer = [0; LAYOUT_SIZE];
+    let expected_buffer = [40, 8, 0, 1, 4, 8, 16, 24, 32];
+
+    unsafe {
+        bit_int_layout(LAYOUT_SIZE as u32, buffer.as_mut_ptr());
+    }
+
+    // The translated struct has the size, alignment and field offsets the C compiler chose
+    let s: mixed_bit_ints = unsafe { mem::zeroed() };
+    let base = &s as *const mixed_bit_ints as usize;
+    let offset = |field: usize| (field - base) as c_int;
+    let rust_layout = [
+        mem::size_of::<mixed_bit_ints>() as c_int,
+        mem::align_of::<mixed_bit_ints>() as c_int,
+        offset(&s.c as *const _ as usize),
+        offset(&s.b7 as *const _ as usize),
+        offset(&s.u24 as *const _ as usize),
+        offset(&s.d as *const _ as usize),
+        offset(&s.b40 as *const _ as usize),
+        offset(&s.u33 as *const _ as usize),
+        offset(&s.b64 as *const _ as usize),
+    ];
+
+    assert_eq!(buffer, rust_layout);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_strict_buffer() {
+    let mut buffer = [0; STRICT_BUFFER_SIZE];
+    let mut rust_buffer = [0; STRICT_BUFFER_SIZE];
+    let expected_buffer = [
+        -8388608, -8388607, -8388607, -8388608, -2, 8388607, 8388606, 8388592,
+        -2796202, -2, 0, 127, 125, 3, 24, 25,
+        1, 0, 2, 1, 30, 30,
+        -2, 73, 0, 8, 1, 1,
+    ];
+
+    unsafe {
+        strict_bit_ints(STRICT_BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_strict_bit_ints(STRICT_BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_strict_newtypes() {
+    // The newtypes wrap values into their width, and keep the layout of their storage
+    assert_eq!(BitInt24::new(8388608).0, -8388608);
+    assert_eq!(UBitInt7::new(200).0, 72);
+    assert_eq!(mem::size_of::<BitInt24>(), mem::size_of::<i32>());
+    assert_eq!(mem::size_of::<UBitInt7>(), mem::size_of::<u8>());
+
+    let f: strict_fields = unsafe { mem::zeroed() };
+    let s: BitInt24 = f.s;
+    let u: UBitInt7 = f.u;
+    assert_eq!((s, u), (BitInt24(0), UBitInt7(0)));
+}
@@ -0,0 +1,34 @@
+//! check_deterministic
+
+// A mix of types, globals and functions whose emission order must not depend
+// on hash map iteration in the translator
+#include <stdint.h>
+
+typedef struct point { int x, y; } point;
+typedef union bits { uint32_t word; uint8_t bytes[4]; } bits;
+enum color { RED, GREEN, BLUE };
+struct packed_pair { char tag; int value; } __attribute__((packed));
+
+static int counter = 3;
+const point origin = { 0, 0 };
+unsigned long long table[3] = { 1, 2, 3 };
+
+static int helper_b(int x) { return x * 2; }
+static int helper_a(int x) { return helper_b(x) + counter; }
+
+void deterministic(unsigned buffer_size, int buffer[]) {
+    point p = { 1, 2 };
+    bits b;
+    struct packed_pair pair = { 'a', 7 };
+    enum color c = BLUE;
+
+    if (buffer_size < 6) return;
+
+    b.word = 0x01020304;
+    buffer[0] = helper_a(p.x + p.y);
+    buffer[1] = b.bytes[0] + b.bytes[3];
+    buffer[2] = pair.value;
+    buffer[3] = c;
+    buffer[4] = origin.x + origin.y;
+    buffer[5] = (int)table[2];
+}
@@ -0,0 +1,19 @@
+//! internal_visibility_private
+
+// Only `internal_api` is exported from the library, so the other
+// functions aren't `pub`
+static int square(int x) { return x * x; }
+
+static int sum_squares(int n) {
+    int sum = 0;
+    for (int i = 1; i <= n; i++) {
+        sum += square(i);
+    }
+    return sum;
+}
+
+__attribute__((visibility("hidden"))) int offset_by(int x) { return x + 5; }
+
+int internal_api(int n) {
+    return offset_by(sum_squares(n));
+}
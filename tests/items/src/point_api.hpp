@@ -0,0 +1,28 @@
+#ifndef POINT_API_HPP
+#define POINT_API_HPP
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct point {
+    int x;
+    int y;
+} point;
+
+int point_dot(const point *a, const point *b);
+
+extern int point_count;
+
+#ifdef __cplusplus
+}
+
+// C++-only declarations have mangled names, so they aren't translated
+namespace geometry {
+inline int twice(int x) { return 2 * x; }
+}
+
+int point_norm1(const point *p);
+#endif
+
+#endif
@@ -0,0 +1,8 @@
+#include "point_api.hpp"
+
+int point_count = 0;
+
+int point_dot(const point *a, const point *b) {
+    point_count++;
+    return a->x * b->x + a->y * b->y;
+}
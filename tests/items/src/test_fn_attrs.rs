@@ -29,6 +29,9 @@ pub fn test_fn_attrs() {
     assert!(src.contains("#[inline]\nunsafe extern \"C\" fn rust_gnu_inline_static"));
     assert!(src.contains("#[cold]\nunsafe extern \"C\" fn rust_cold_used_attrs"));
 
+    // static void __attribute__((section(".init"))) init_section_static(void) {}
+    assert!(src.contains("#[link_section = \".init\"]\nunsafe extern \"C\" fn rust_init_section_static"));
+
     // __attribute__((__always_inline__)) void always_inline_nonstatic(void) {}
     // __attribute__((noinline)) void noinline_nonstatic(void) {}
     // void inline inline_nonstatic(void) {}
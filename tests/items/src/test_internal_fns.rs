@@ -0,0 +1,28 @@
+//! feature_global_asm
+extern crate libc;
+
+use internal_fns::rust_internal_api;
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn internal_api(_: c_int) -> c_int;
+}
+
+pub fn test_internal_fns() {
+    for n in 0..5 {
+        let (c_ret, rust_ret) = unsafe { (internal_api(n), rust_internal_api(n)) };
+
+        assert_eq!(c_ret, rust_ret);
+    }
+}
+
+pub fn test_internal_visibility() {
+    // Only the exported function is part of the API of the module
+    let src = include_str!("internal_fns.rs");
+    assert!(src.contains("pub unsafe extern \"C\" fn rust_internal_api("));
+    assert!(src.contains("\nunsafe extern \"C\" fn rust_square("));
+    assert!(src.contains("\nunsafe extern \"C\" fn rust_sum_squares("));
+    assert!(src.contains("pub(crate) unsafe extern \"C\" fn rust_offset_by("));
+}
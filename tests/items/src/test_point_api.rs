@@ -0,0 +1,33 @@
+extern crate libc;
+
+use point_impl::rust_point_dot;
+use point_api::point;
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn point_dot(_: *const point, _: *const point) -> c_int;
+}
+
+pub fn test_extern_c_header() {
+    // The struct translated from the C++ header has the same layout as in C
+    let a = point { x: 1, y: 2 };
+    let b = point { x: 3, y: 4 };
+
+    let ret = unsafe { point_dot(&a, &b) };
+    let rust_ret = unsafe {
+        rust_point_dot(&a as *const point as *const _, &b as *const point as *const _)
+    };
+
+    assert_eq!(ret, rust_ret);
+    assert_eq!(ret, 11);
+
+    let src = include_str!("point_api.rs");
+    assert!(src.contains("extern \"C\" {"));
+    assert!(src.contains("fn point_dot("));
+    assert!(src.contains("static mut point_count:"));
+    // C++-only declarations aren't translated
+    assert!(!src.contains("twice"));
+    assert!(!src.contains("point_norm1"));
+}
@@ -0,0 +1,61 @@
+//! feature_c_variadic,
+extern crate libc;
+
+use valist_forward::{rust_log_format, rust_log_format_measured};
+
+use std::ffi::{CStr, CString, VaList};
+use std::mem;
+use self::libc::{c_char, c_int, size_t};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn log_format(buf: *mut c_char, size: size_t, fmt: *const c_char, ap: VaList) -> c_int;
+
+    #[no_mangle]
+    fn log_format_measured(buf: *mut c_char, size: size_t, fmt: *const c_char, ap: VaList)
+        -> c_int;
+}
+
+const BUF_SIZE: usize = 64;
+
+// The translated shims only forward their `va_list`, so they take its C ABI type rather than
+// `VaList`; the two have the same representation.
+unsafe extern "C" fn call_c(measured: c_int, buf: *mut c_char, fmt: *const c_char, mut args: ...)
+    -> c_int {
+    if measured != 0 {
+        log_format_measured(buf, BUF_SIZE, fmt, args.as_va_list())
+    } else {
+        log_format(buf, BUF_SIZE, fmt, args.as_va_list())
+    }
+}
+
+unsafe extern "C" fn call_rust(measured: c_int, buf: *mut c_char, fmt: *const c_char, mut args: ...)
+    -> c_int {
+    if measured != 0 {
+        rust_log_format_measured(buf, BUF_SIZE as _, fmt, mem::transmute(args.as_va_list()))
+    } else {
+        rust_log_format(buf, BUF_SIZE as _, fmt, mem::transmute(args.as_va_list()))
+    }
+}
+
+pub fn test_log_format() {
+    let fmt = CString::new("%s=%d (%.1f)").unwrap();
+    let key = CString::new("answer").unwrap();
+    let long_key = CString::new("a".repeat(BUF_SIZE)).unwrap();
+
+    for &measured in &[0, 1] {
+        for key in &[&key, &long_key] {
+            let mut c_buf = [0 as c_char; BUF_SIZE];
+            let mut rust_buf = [0 as c_char; BUF_SIZE];
+            unsafe {
+                let c_ret = call_c(measured, c_buf.as_mut_ptr(), fmt.as_ptr(), key.as_ptr(), 42, 1.5);
+                let rust_ret =
+                    call_rust(measured, rust_buf.as_mut_ptr(), fmt.as_ptr(), key.as_ptr(), 42, 1.5);
+
+                assert_eq!(c_ret, rust_ret);
+                assert_eq!(CStr::from_ptr(c_buf.as_ptr()), CStr::from_ptr(rust_buf.as_ptr()));
+            }
+        }
+    }
+}
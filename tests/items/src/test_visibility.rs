@@ -0,0 +1,19 @@
+//! feature_global_asm
+extern crate libc;
+
+use visibility::rust_call_all;
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn call_all(_: c_int) -> c_int;
+}
+
+pub fn test_visibility() {
+    for x in 0..4 {
+        let (c_ret, rust_ret) = unsafe { (call_all(x), rust_call_all(x)) };
+
+        assert_eq!(c_ret, rust_ret);
+    }
+}
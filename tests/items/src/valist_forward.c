@@ -0,0 +1,19 @@
+#include <stdarg.h>
+#include <stdio.h>
+
+// A logging shim that only forwards its va_list to vsnprintf
+int log_format(char *buf, size_t size, const char *fmt, va_list ap) {
+    int prefix = snprintf(buf, size, "[log] ");
+    return prefix + vsnprintf(buf + prefix, size - prefix, fmt, ap);
+}
+
+// Measure the message on a copy of the list before formatting it
+int log_format_measured(char *buf, size_t size, const char *fmt, va_list ap) {
+    va_list aq;
+    va_copy(aq, ap);
+    int len = vsnprintf(NULL, 0, fmt, aq);
+    va_end(aq);
+    if ((size_t) len + 6 >= size)
+        return -1;
+    return log_format(buf, size, fmt, ap);
+}
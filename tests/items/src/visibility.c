@@ -0,0 +1,11 @@
+__attribute__((visibility("hidden"))) int hidden_fn(int x) { return x + 1; }
+__attribute__((visibility("default"))) int default_fn(int x) { return x + 2; }
+__attribute__((visibility("protected"))) int protected_fn(int x) { return x + 3; }
+int plain_fn(int x) { return x + 4; }
+
+__attribute__((visibility("hidden"))) int hidden_var = 1;
+int plain_var = 2;
+
+int call_all(int x) {
+    return hidden_fn(x) + default_fn(x) + protected_fn(x) + plain_fn(x) + hidden_var + plain_var;
+}
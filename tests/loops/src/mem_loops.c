@@ -0,0 +1,57 @@
+void mem_loops(unsigned buffer_size, int buffer[])
+{
+    if (buffer_size < 64)
+        return;
+
+    int i, j;
+    int *dst, *src;
+
+    for (i = 0; i < 64; i++)
+        buffer[i] = i;
+
+    // Overlapping copy to a higher address, which smears the first element
+    dst = buffer + 1;
+    src = buffer;
+    for (i = 0; i < 8; i++)
+        dst[i] = src[i];
+
+    // Overlapping copy to a lower address
+    dst = buffer + 10;
+    src = buffer + 12;
+    for (i = 0; i < 6; i++)
+        dst[i] = src[i];
+
+    // Non-overlapping copy that doesn't start at zero
+    dst = buffer + 32;
+    src = buffer + 8;
+    for (i = 2; i < 8; i++)
+        dst[i] = src[i];
+
+    // The counter is still visible after the loop
+    buffer[40] = i;
+
+    // Fill with zero
+    j = 41;
+    while (j < 46) {
+        buffer[j] = 0;
+        j++;
+    }
+
+    // Fill bytes
+    char bytes[8];
+    for (int k = 0; k < 8; ++k)
+        bytes[k] = 'a' + 1;
+    for (int k = 0; k < 8; ++k)
+        buffer[46 + k] = bytes[k];
+
+    // Copy between arrays
+    int from[8], to[8];
+    for (int k = 0; k < 8; k++) {
+        from[k] = k * 3;
+        to[k] = -1;
+    }
+    for (int k = 3; k < 8; k += 1)
+        to[k] = from[k];
+    for (int k = 0; k < 8; k++)
+        buffer[54 + k] = to[k];
+}
@@ -1,12 +1,16 @@
 extern crate libc;
 
 use break_continue::rust_entry;
+use mem_loops::rust_mem_loops;
 use self::libc::{c_int, c_uint};
 
 #[link(name = "test")]
 extern "C" {
     #[no_mangle]
     fn entry(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn mem_loops(_: c_uint, _: *mut c_int);
 }
 
 const BUFFER_SIZE: usize = 70;
@@ -34,3 +38,29 @@ pub fn test_buffer() {
         assert_eq!(buffer[index], expected_buffer[index], "index: {}", index);
     }
 }
+
+const MEM_BUFFER_SIZE: usize = 64;
+
+pub fn test_mem_loops() {
+    let mut buffer = [0; MEM_BUFFER_SIZE];
+    let mut rust_buffer = [0; MEM_BUFFER_SIZE];
+    let expected_buffer = [
+        0, 0, 0, 0, 0, 0, 0, 0, 8, 9,
+        12, 13, 14, 15, 16, 17, 16, 17, 18, 19,
+        20, 21, 22, 23, 24, 25, 26, 27, 28, 29,
+        30, 31, 32, 33, 12, 13, 14, 15, 16, 17,
+        8, 0, 0, 0, 0, 0, 98, 98, 98, 98,
+        98, 98, 98, 98, -1, -1, -1, 9, 12, 15,
+        18, 21, 62, 63,
+    ];
+
+    unsafe {
+        mem_loops(MEM_BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_mem_loops(MEM_BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    for index in 0..MEM_BUFFER_SIZE {
+        assert_eq!(buffer[index], rust_buffer[index]);
+        assert_eq!(buffer[index], expected_buffer[index], "index: {}", index);
+    }
+}
@@ -0,0 +1,20 @@
+//! translate_fn_macros
+
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+#define SQUARE(x) ((x) * (x))
+
+int max_of(int x, int y) {
+  return MAX(x, y);
+}
+
+int sum_of_squares(int n) {
+  int sum = 0;
+  for (int i = 0; i < n; i++)
+    sum += SQUARE(i);
+  return MAX(sum, SQUARE(n));
+}
+
+// Arguments with side effects keep the expansion, which evaluates them twice
+int max_inc(int *p) {
+  return MAX((*p)++, 0);
+}
@@ -3,12 +3,22 @@ extern crate libc;
 use define::{TEST_CONST1, TEST_CONST2, TEST_PARENS, rust_reference_define};
 use define::{ZSTD_WINDOWLOG_MAX_32, ZSTD_WINDOWLOG_MAX_64, rust_test_zstd};
 use define::{rust_fns, rust_stmt_expr_inc};
+use fn_macros::{MAX, rust_max_inc, rust_max_of, rust_sum_of_squares};
 use self::libc::{c_int, c_uint, c_ulong};
 
 #[link(name = "test")]
 extern "C" {
     #[no_mangle]
     fn reference_define() -> c_uint;
+
+    #[no_mangle]
+    fn max_of(_: c_int, _: c_int) -> c_int;
+
+    #[no_mangle]
+    fn sum_of_squares(_: c_int) -> c_int;
+
+    #[no_mangle]
+    fn max_inc(_: *mut c_int) -> c_int;
 }
 
 pub fn test_define() {
@@ -27,3 +37,16 @@ pub fn test_macro_stmt_expr() {
 
     assert_eq!(ret, 2);
 }
+
+pub fn test_fn_macros() {
+    unsafe {
+        assert_eq!(MAX(3, 7), 7);
+        assert_eq!(rust_max_of(-2, -5), max_of(-2, -5));
+        assert_eq!(rust_sum_of_squares(4), sum_of_squares(4));
+        assert_eq!(rust_sum_of_squares(0), sum_of_squares(0));
+
+        let (mut x, mut rust_x) = (1, 1);
+        assert_eq!(rust_max_inc(&mut rust_x), max_inc(&mut x));
+        assert_eq!(rust_x, x);
+    }
+}
@@ -0,0 +1,41 @@
+#include <stdalign.h>
+#include <stddef.h>
+#include <stdint.h>
+
+struct with_aligned_member {
+    char tag;
+    _Alignas(16) int value;
+};
+
+void alignas_local(const unsigned n, int * const buffer) {
+    if (n < 6) return;
+
+    _Alignas(32) char local_buf[24];
+    int i;
+
+    for (i = 0; i < 24; i++) {
+        local_buf[i] = (char)i;
+    }
+
+    buffer[0] = ((uintptr_t)local_buf % 32) == 0;
+    buffer[1] = local_buf[23];
+    buffer[2] = alignof(max_align_t);
+    buffer[3] = alignof(long double);
+    buffer[4] = alignof(struct with_aligned_member);
+    buffer[5] = alignof(int);
+}
+
+static const unsigned long double_align = _Alignof(double);
+
+void alignof_exprs(const unsigned n, int * const buffer) {
+    if (n < 5) return;
+
+    double d = 1.0;
+    struct with_aligned_member s = { 0 };
+
+    buffer[0] = _Alignof(double);
+    buffer[1] = double_align;
+    buffer[2] = __alignof__(d);
+    buffer[3] = __alignof__(s);
+    buffer[4] = _Alignof(d + 1);
+}
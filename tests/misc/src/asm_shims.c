@@ -0,0 +1,18 @@
+//! shim_untranslatable_stmts, no_translate_asm, check_deterministic
+
+/* Only the inline assembly is left to a C shim, the rest of the function is translated */
+static int add_asm(int x, int y) {
+    int total = x * 2;
+#if defined(__aarch64__)
+    __asm__("add %w0, %w0, %w1" : "+r"(total) : "r"(y));
+#else
+    __asm__("addl %1, %0" : "+r"(total) : "r"(y));
+#endif
+    return total + 1;
+}
+
+void asm_shims(unsigned buffer_size, int *const buffer) {
+    for (unsigned i = 0; i < buffer_size; i++) {
+        buffer[i] = add_asm(i, 10);
+    }
+}
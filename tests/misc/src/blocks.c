@@ -0,0 +1,33 @@
+//! translate_blocks, macos_only
+
+#include <Block.h>
+
+struct callbacks {
+    int (*apply)(int (^)(int), int);
+};
+
+static int apply(int (^f)(int), int x) {
+    return f(x);
+}
+
+void blocks(const unsigned n, int * const buffer) {
+    __block int calls = 0;
+    int offset = 10;
+    struct callbacks cb = { apply };
+
+    int (^add_offset)(int) = ^(int x) {
+        calls++;
+        return x + offset;
+    };
+    int (^twice)(int) = ^(int x) { return 2 * x; };
+
+    // Copies of a block share its `__block` variables
+    int (^copy)(int) = Block_copy(add_offset);
+
+    for (unsigned i = 0; i + 1 < n; i++) {
+        buffer[i] = cb.apply(add_offset, i) + cb.apply(twice, i) + copy(i);
+    }
+    Block_release(copy);
+
+    buffer[n - 1] = calls;
+}
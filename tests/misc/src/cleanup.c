@@ -0,0 +1,63 @@
+#include <stdlib.h>
+
+static int frees;
+
+static void free_buf(char **buf) {
+    if (*buf) {
+        frees++;
+        free(*buf);
+    }
+}
+
+static int fill(int early) {
+    char *buf __attribute__((cleanup(free_buf))) = malloc(16);
+    if (!buf) return -1;
+
+    buf[0] = 'x';
+    if (early) return 1;
+
+    buf[1] = 'y';
+    return 2;
+}
+
+static int loop_with_break(int n) {
+    int i, total = 0;
+    for (i = 0; i < n; i++) {
+        char *buf __attribute__((cleanup(free_buf))) = malloc(8);
+        if (i == 2) continue;
+        if (i == 4) break;
+        total += i;
+    }
+    return total;
+}
+
+static int goto_out(int fail) {
+    int ret = 0;
+    {
+        char *buf __attribute__((cleanup(free_buf))) = malloc(8);
+        if (fail) goto out;
+        ret = 1;
+    }
+    ret += 10;
+out:
+    return ret;
+}
+
+void cleanup_counts(const unsigned n, int * const buffer) {
+    if (n < 9) return;
+
+    frees = 0;
+    buffer[0] = fill(1);
+    buffer[1] = frees;
+    buffer[2] = fill(0);
+    buffer[3] = frees;
+
+    frees = 0;
+    buffer[4] = loop_with_break(10);
+    buffer[5] = frees;
+
+    frees = 0;
+    buffer[6] = goto_out(1);
+    buffer[7] = goto_out(0);
+    buffer[8] = frees;
+}
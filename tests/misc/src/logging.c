@@ -0,0 +1,18 @@
+//! log_function_LOG
+
+enum level { TRACE, DEBUG, INFO, WARN, ERROR };
+
+static int log_count = 0;
+
+void LOG(enum level level, const char *msg) {
+    log_count++;
+}
+
+void log_calls(unsigned buffer_size, int buffer[]) {
+    LOG(INFO, "msg");
+
+    for (unsigned i = 0; i < buffer_size; i++) {
+        LOG(DEBUG, "filling buffer");
+        buffer[i] = i * 2;
+    }
+}
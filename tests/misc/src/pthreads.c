@@ -0,0 +1,46 @@
+//! translate_pthreads
+
+#include <pthread.h>
+#include <stddef.h>
+
+#define ITERATIONS 1000
+
+static pthread_mutex_t counter_lock = PTHREAD_MUTEX_INITIALIZER;
+static int counter = 0;
+
+static pthread_once_t setup_once = PTHREAD_ONCE_INIT;
+static int setup_count = 0;
+
+static void setup(void) {
+    setup_count++;
+}
+
+static void *count_up(void *arg) {
+    int step = *(int *)arg;
+
+    pthread_once(&setup_once, setup);
+    for (int i = 0; i < ITERATIONS; i++) {
+        pthread_mutex_lock(&counter_lock);
+        counter += step;
+        pthread_mutex_unlock(&counter_lock);
+    }
+    return arg;
+}
+
+void pthreads_counter(unsigned buffer_size, int buffer[]) {
+    pthread_t first, second;
+    int first_step = 1, second_step = 2;
+    void *first_result;
+
+    counter = 0;
+    pthread_create(&first, NULL, count_up, &first_step);
+    pthread_create(&second, NULL, count_up, &second_step);
+    pthread_join(first, &first_result);
+    pthread_join(second, NULL);
+
+    if (buffer_size >= 3) {
+        buffer[0] = counter;
+        buffer[1] = setup_count;
+        buffer[2] = first_result == &first_step;
+    }
+}
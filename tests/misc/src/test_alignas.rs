@@ -0,0 +1,43 @@
+extern crate libc;
+
+use alignas::{rust_alignas_local, rust_alignof_exprs};
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn alignas_local(_: c_uint, _: *mut c_int);
+    #[no_mangle]
+    fn alignof_exprs(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 6;
+
+pub fn test_alignas_local() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        alignas_local(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_alignas_local(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    // The over-aligned local buffer must be 32-byte aligned in both builds
+    assert_eq!(buffer[0], 1);
+    assert_eq!(rust_buffer[0], 1);
+
+    assert_eq!(buffer, rust_buffer);
+}
+
+pub fn test_alignof_exprs() {
+    let mut buffer = [0; 5];
+    let mut rust_buffer = [0; 5];
+
+    unsafe {
+        alignof_exprs(5, buffer.as_mut_ptr());
+        rust_alignof_exprs(5, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(rust_buffer[0], std::mem::align_of::<f64>() as c_int);
+    assert_eq!(buffer, rust_buffer);
+}
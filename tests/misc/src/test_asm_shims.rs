@@ -0,0 +1,31 @@
+extern crate libc;
+
+use asm_shims::rust_asm_shims;
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn asm_shims(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 4;
+
+pub fn test_asm_shims() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        asm_shims(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_asm_shims(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(rust_buffer, [11, 13, 15, 17]);
+    assert_eq!(buffer, rust_buffer);
+
+    // The function holding the assembly is translated around a call to its shim
+    let src = include_str!("asm_shims.rs");
+    assert!(src.contains("fn c2rust_shim_asm_shims_add_asm_0("));
+    assert!(src.contains("c2rust_shim_asm_shims_add_asm_0(&mut total, &mut y)"));
+    assert!(!src.contains("asm!"));
+}
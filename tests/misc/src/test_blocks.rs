@@ -0,0 +1,28 @@
+//! macos_only
+
+extern crate libc;
+
+use blocks::rust_blocks;
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn blocks(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 5;
+
+pub fn test_blocks() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        blocks(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_blocks(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    // Both the block and its copy counted their calls in the shared `__block` variable
+    assert_eq!(rust_buffer[BUFFER_SIZE - 1], 2 * (BUFFER_SIZE as c_int - 1));
+    assert_eq!(buffer, rust_buffer);
+}
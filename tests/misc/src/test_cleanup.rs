@@ -0,0 +1,31 @@
+extern crate libc;
+
+use cleanup::rust_cleanup_counts;
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn cleanup_counts(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 9;
+
+pub fn test_cleanup_counts() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        cleanup_counts(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_cleanup_counts(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    // The buffer is freed exactly once on the early return and on the normal path
+    assert_eq!(rust_buffer[..4], [1, 1, 2, 2]);
+    // Once per iteration, including the one left by `continue` and the one left by `break`
+    assert_eq!(rust_buffer[4..6], [4, 5]);
+    // Both when jumping out of the block and when falling off its end
+    assert_eq!(rust_buffer[6..], [0, 11, 2]);
+
+    assert_eq!(buffer, rust_buffer);
+}
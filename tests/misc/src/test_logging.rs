@@ -0,0 +1,31 @@
+//! extern_crate_log
+
+extern crate libc;
+
+use logging::rust_log_calls;
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn log_calls(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 4;
+
+pub fn test_log_calls() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        log_calls(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_log_calls(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+
+    // Calls to `LOG` become `log` macros
+    let src = include_str!("logging.rs");
+    assert!(src.contains("log::info!(\"msg\")"));
+    assert!(src.contains("log::debug!(\"filling buffer\")"));
+}
@@ -0,0 +1,33 @@
+extern crate libc;
+
+use pthreads::rust_pthreads_counter;
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn pthreads_counter(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 3;
+
+pub fn test_pthreads_counter() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [3000, 1, 1];
+
+    unsafe {
+        pthreads_counter(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_pthreads_counter(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+
+    // The two threads share a `std` mutex, and run on `std` threads
+    let src = include_str!("pthreads.rs");
+    assert!(src.contains("::std::sync::Mutex<()>"));
+    assert!(src.contains("call_once"));
+    assert!(src.contains("::std::thread::spawn"));
+    assert!(src.contains(".lock().unwrap()"));
+}
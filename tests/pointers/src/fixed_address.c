@@ -0,0 +1,24 @@
+// Memory-mapped registers at fixed addresses. These are never called, since the addresses are
+// not mapped in the test process; `entry6` reads registers through a pointer instead.
+unsigned int read_status(void) {
+    return *(volatile unsigned int *)0x40021000;
+}
+
+void write_control(unsigned int val) {
+    *(volatile unsigned int *)0x40021004 = val;
+}
+
+static unsigned int read_reg(volatile unsigned int *base, int reg) {
+    return base[reg];
+}
+
+void entry6(const unsigned buffer_size, int buffer[]) {
+    if (buffer_size < 3) { return; }
+
+    unsigned int regs[3] = { 7, 11, 13 };
+    volatile unsigned int *base = regs;
+
+    buffer[0] = read_reg(base, 0);
+    buffer[1] = read_reg(base, 2);
+    buffer[2] = *base + base[1];
+}
@@ -0,0 +1,30 @@
+//! translate_fn_tables
+
+typedef int (*handler_t)(int);
+
+static int add_one(int x) { return x + 1; }
+static int twice(int x) { return x * 2; }
+static int negate(int x) { return -x; }
+
+// All entries are functions, so this becomes an array of `fn`s
+static handler_t handlers[] = { add_one, twice, &negate };
+
+// Entries that may be null keep the `Option`
+static handler_t maybe_handlers[3] = { add_one, 0, negate };
+
+void entry4(const unsigned buffer_size, int buffer[]) {
+    const unsigned num_handlers = sizeof(handlers) / sizeof(handlers[0]);
+    if (buffer_size < num_handlers + 2) { return; }
+
+    unsigned i;
+    for (i = 0; i < num_handlers; i++) {
+        buffer[i] = handlers[i](10 + i);
+    }
+
+    handler_t h = handlers[1];
+    buffer[i++] = (*h)(21);
+
+    if (maybe_handlers[1] == 0) {
+        buffer[i] = maybe_handlers[2](buffer[0]);
+    }
+}
@@ -0,0 +1,31 @@
+struct point {
+    int x;
+    int y;
+};
+
+typedef int (*callback_t)(void *, int);
+
+static int point_sum(struct point *p) { return p->x + p->y; }
+static int scaled_x(struct point *p, int scale) { return p->x * scale; }
+static int deref(int *p, int offset) { return *p + offset; }
+
+static callback_t callbacks[] = {
+    (callback_t)point_sum,
+    (callback_t)scaled_x,
+};
+
+void entry5(const unsigned buffer_size, int buffer[]) {
+    if (buffer_size < 4) { return; }
+
+    struct point p = { 3, 4 };
+    int n = 10;
+
+    buffer[0] = callbacks[0](&p, 7);
+    buffer[1] = callbacks[1](&p, 7);
+
+    callback_t cb = (callback_t)&deref;
+    buffer[2] = cb(&n, 5);
+
+    cb = (callback_t)point_sum;
+    buffer[3] = cb(&p, 0);
+}
@@ -0,0 +1,27 @@
+// Pointer differences count elements of the pointee type, whatever its size
+// and whichever operand is `const`; only `void *` differences count bytes.
+
+struct pair {
+    int key;
+    double value;
+};
+
+void entry7(const unsigned buffer_size, int buffer[]) {
+    int ints[10] = {0};
+    struct pair pairs[4];
+    const int *end = ints + 7;
+    int *start = ints + 2;
+    int i = 0;
+
+    if (buffer_size < 7) {
+        return;
+    }
+
+    buffer[i++] = end - start;
+    buffer[i++] = start - end;
+    buffer[i++] = &pairs[3] - &pairs[1];
+    buffer[i++] = (const struct pair *)pairs - &pairs[3];
+    buffer[i++] = (const char *)end - (char *)start;
+    buffer[i++] = (const void *)&pairs[3] - (void *)pairs;
+    buffer[i++] = &ints[9] - ints;
+}
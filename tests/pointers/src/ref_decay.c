@@ -71,7 +71,7 @@ void calls_all(void) {
 
     cmp_ref(i, &n);
 
-    // wrapping_offset_from requires self to be a raw pointer,
+    // offset_from requires self to be a raw pointer,
     // and self params don't ref decay. So lhs should decay,
     // but not rhs
     int o = 1;
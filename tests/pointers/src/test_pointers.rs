@@ -1,10 +1,14 @@
-//! feature_c_variadic, feature_ptr_wrapping_offset_from
+//! feature_c_variadic, feature_ptr_offset_from
 
 extern crate libc;
 
 use pointer_init::rust_entry;
 use pointer_arith::rust_entry2;
 use function_pointers::rust_entry3;
+use fn_tables::rust_entry4;
+use fn_thunks::rust_entry5;
+use fixed_address::rust_entry6;
+use pointer_diff::rust_entry7;
 use ref_decay::{rust_f, rust_bar, rust_bitcast, rust_foobar, rust_calls_all, rust_address_cast};
 use self::libc::{c_int, c_uint};
 
@@ -18,11 +22,27 @@ extern "C" {
 
     #[no_mangle]
     fn entry3(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn entry4(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn entry5(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn entry6(_: c_uint, _: *mut c_int);
+
+    #[no_mangle]
+    fn entry7(_: c_uint, _: *mut c_int);
 }
 
 const BUFFER_SIZE: usize = 5;
 const BUFFER_SIZE2: usize = 31;
 const BUFFER_SIZE3: usize = 18;
+const BUFFER_SIZE4: usize = 5;
+const BUFFER_SIZE5: usize = 4;
+const BUFFER_SIZE6: usize = 3;
+const BUFFER_SIZE7: usize = 7;
 
 pub fn test_init() {
     let mut buffer = [0; BUFFER_SIZE];
@@ -70,3 +90,78 @@ pub fn test_fn_ptrs() {
     assert_eq!(&buffer[..],      &expected_buffer[..], "c version");
     assert_eq!(&rust_buffer[..], &expected_buffer[..], "rust version");
 }
+
+pub fn test_fn_tables() {
+    let mut buffer = [0; BUFFER_SIZE4];
+    let mut rust_buffer = [0; BUFFER_SIZE4];
+    let expected_buffer = [11, 22, -12, 42, -11];
+
+    unsafe {
+        entry4(BUFFER_SIZE4 as u32, buffer.as_mut_ptr());
+        rust_entry4(BUFFER_SIZE4 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+
+    let src = include_str!("fn_tables.rs");
+    assert!(src.contains("static mut handlers: [unsafe extern \"C\" fn("));
+    assert!(src.contains("static mut maybe_handlers: [handler_t; 3]"));
+}
+
+pub fn test_fn_thunks() {
+    let mut buffer = [0; BUFFER_SIZE5];
+    let mut rust_buffer = [0; BUFFER_SIZE5];
+    let expected_buffer = [7, 21, 15, 7];
+
+    unsafe {
+        entry5(BUFFER_SIZE5 as u32, buffer.as_mut_ptr());
+        rust_entry5(BUFFER_SIZE5 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+
+    let src = include_str!("fn_thunks.rs");
+    assert!(src.contains("fn point_sum_thunk("));
+    assert!(!src.contains("transmute"));
+}
+
+pub fn test_fixed_address() {
+    let mut buffer = [0; BUFFER_SIZE6];
+    let mut rust_buffer = [0; BUFFER_SIZE6];
+    let expected_buffer = [7, 13, 18];
+
+    unsafe {
+        entry6(BUFFER_SIZE6 as u32, buffer.as_mut_ptr());
+        rust_entry6(BUFFER_SIZE6 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+
+    let src = include_str!("fixed_address.rs");
+    assert!(src.contains("0x40021000 as *mut libc::c_uint"));
+    assert!(src.contains("0x40021004 as *mut libc::c_uint"));
+    assert!(src.contains("read_volatile"));
+    assert!(src.contains("write_volatile"));
+}
+
+pub fn test_pointer_diff() {
+    let mut buffer = [0; BUFFER_SIZE7];
+    let mut rust_buffer = [0; BUFFER_SIZE7];
+    let expected_buffer = [5, -5, 2, -3, 20, 48, 9];
+
+    unsafe {
+        entry7(BUFFER_SIZE7 as u32, buffer.as_mut_ptr());
+        rust_entry7(BUFFER_SIZE7 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+
+    let src = include_str!("pointer_diff.rs");
+    assert!(src.contains(".offset_from("));
+    assert!(src.contains("as *const u8).offset_from("));
+    assert!(!src.contains("wrapping_offset_from"));
+}
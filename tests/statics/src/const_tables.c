@@ -0,0 +1,54 @@
+#include <string.h>
+
+struct keyword {
+  const char *name;
+  int token;
+};
+
+// The length of the table comes from its initializer
+const struct keyword keywords[] = {
+  { "break", 1 },
+  { "case", 2 },
+  { "else", 3 },
+  { "for", 4 },
+  { "if", 5 },
+  { "return", 6 },
+  { "while", 7 },
+};
+
+static const int keyword_count = 7;
+
+// A table referring to another table and to a global
+struct keyword_set {
+  const struct keyword *entries;
+  const int *count;
+};
+
+static const struct keyword_set keyword_sets[] = {
+  { keywords, &keyword_count },
+};
+
+static const int widths[2][3] = { { 1, 2, 3 }, { 4, 5, 6 } };
+
+int lookup_keyword(const struct keyword *table, int n, const char *word) {
+  for (int i = 0; i < n; i++) {
+    if (strcmp(table[i].name, word) == 0) {
+      return table[i].token;
+    }
+  }
+  return 0;
+}
+
+void keyword_entry(const unsigned buffer_size, int buffer[]) {
+  const char *words[] = { "if", "while", "goto", "break", "return" };
+  int i;
+
+  if (buffer_size < 8) return;
+
+  for (i = 0; i < 5; i++) {
+    buffer[i] = lookup_keyword(keywords, sizeof(keywords) / sizeof(keywords[0]), words[i]);
+  }
+  buffer[5] = lookup_keyword(keyword_sets[0].entries, *keyword_sets[0].count, "else");
+  buffer[6] = keywords[3].token + strlen(keywords[3].name);
+  buffer[7] = widths[1][2];
+}
@@ -0,0 +1,15 @@
+#include <ctype.h>
+
+static const char *last;
+
+// Whether `s` is the same string as the one passed to the previous call
+int same_as_last(char *s) {
+    int same = s == last;
+    last = s;
+    return same;
+}
+
+char *capitalize(char *s) {
+    s[0] = toupper(s[0]);
+    return s;
+}
@@ -0,0 +1,26 @@
+int same_as_last(char *s);
+char *capitalize(char *s);
+
+// Defined here, so literals passed to it stay read-only
+static int length(char *s) {
+    int n = 0;
+    while (s[n]) n++;
+    return n;
+}
+
+void literal_identity(const unsigned buffer_size, int buffer[]) {
+    unsigned i = 0;
+
+    if (buffer_size < 4) return;
+
+    same_as_last("key");
+    buffer[i++] = same_as_last("key");
+    buffer[i++] = same_as_last("other");
+    buffer[i++] = same_as_last("key");
+    buffer[i++] = length("four");
+}
+
+// Writes to a string literal, which only works once translated
+char capitalized(void) {
+    return capitalize("hello")[0];
+}
@@ -0,0 +1,31 @@
+//! translate_string_tables
+
+#include <stddef.h>
+#include <string.h>
+
+// Terminated by an explicit sentinel, which is left out of the slice
+static const char *names[] = { "alpha", "beta", "gamma", NULL };
+
+// A sized table whose last entry is left null
+static const char *colors[4] = { "red", "green", "blue" };
+
+// Written to, so this keeps the array of pointers
+static const char *scratch[] = { "one", "two", NULL };
+
+void string_table_entry(const unsigned buffer_size, int buffer[]) {
+    unsigned i = 0, n;
+
+    if (buffer_size < 8) return;
+
+    for (n = 0; names[n] != NULL; n++) {
+        buffer[i++] = strlen(names[n]);
+    }
+    buffer[i++] = n;
+
+    for (n = 0; colors[n]; n++) {}
+    buffer[i++] = n + sizeof(colors) / sizeof(colors[0]);
+    buffer[i++] = colors[2][0];
+
+    scratch[0] = names[2];
+    buffer[i++] = strlen(scratch[0]);
+}
@@ -0,0 +1,32 @@
+extern crate libc;
+
+use const_tables::{rust_keyword_entry, rust_keywords};
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn keyword_entry(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 8;
+
+pub fn test_keyword_lookup() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [5, 7, 0, 1, 6, 3, 7, 6];
+
+    unsafe {
+        keyword_entry(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_keyword_entry(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_immutable_table() {
+    // An immutable `static` with its length from the initializer, read without `unsafe`
+    assert_eq!(rust_keywords.len(), 7);
+    assert_eq!(rust_keywords[4].token, 5);
+}
@@ -0,0 +1,36 @@
+extern crate libc;
+
+use string_literals::{rust_capitalized, rust_literal_identity};
+use unique_literals::rust_unique_literal_identity;
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn literal_identity(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 4;
+
+pub fn test_shared_literals() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    // Like clang, identical literals share an address
+    let expected_buffer = [1, 0, 0, 4];
+
+    unsafe {
+        literal_identity(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_literal_identity(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_unique_literals() {
+    assert_eq!(unsafe { rust_unique_literal_identity() }, 0);
+}
+
+pub fn test_literal_written_by_callee() {
+    assert_eq!(unsafe { rust_capitalized() }, b'H' as libc::c_char);
+}
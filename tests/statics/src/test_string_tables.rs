@@ -0,0 +1,32 @@
+extern crate libc;
+
+use string_tables::{rust_names, rust_string_table_entry};
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn string_table_entry(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 8;
+
+pub fn test_string_table_reads() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [5, 4, 5, 3, 7, 98, 5, 0];
+
+    unsafe {
+        string_table_entry(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_string_table_entry(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_cstr_slice() {
+    // The sentinel is left out, so the names can be iterated without `unsafe`
+    let names: Vec<_> = rust_names.iter().map(|s| s.to_str().unwrap()).collect();
+    assert_eq!(names, ["alpha", "beta", "gamma"]);
+}
@@ -0,0 +1,8 @@
+//! string_literals_unique
+
+int same_as_last(char *s);
+
+int unique_literal_identity(void) {
+    same_as_last("key");
+    return same_as_last("key");
+}
@@ -0,0 +1,54 @@
+#include <stddef.h>
+
+#define container_of(ptr, type, member) \
+    ((type *)((char *)(ptr) - offsetof(type, member)))
+
+struct list_head {
+    struct list_head *next;
+};
+
+struct item {
+    int value;
+    double weight;
+    struct list_head link;
+};
+
+// Sums the values of an intrusive linked list by recovering each
+// enclosing item from its embedded list head.
+int sum_list(unsigned int buffer_size, int buffer[]) {
+    struct item items[4];
+    struct list_head head = { NULL };
+    struct list_head *pos;
+    int sum = 0;
+    int i;
+
+    for (i = 3; i >= 0; i--) {
+        items[i].value = i * 10 + 1;
+        items[i].weight = 0.5;
+        items[i].link.next = head.next;
+        head.next = &items[i].link;
+    }
+
+    i = 0;
+    for (pos = head.next; pos != NULL; pos = pos->next) {
+        struct item *it = container_of(pos, struct item, link);
+        if (i < buffer_size) {
+            buffer[i++] = it->value;
+        }
+        sum += it->value;
+    }
+
+    return sum;
+}
+
+// Writes the offsets of the members of an item, which are computed
+// from the translated struct rather than copied as constants.
+void item_offsets(unsigned int buffer_size, int buffer[]) {
+    if (buffer_size < 3) {
+        return;
+    }
+
+    buffer[0] = offsetof(struct item, value);
+    buffer[1] = offsetof(struct item, weight);
+    buffer[2] = offsetof(struct item, link);
+}
@@ -0,0 +1,35 @@
+#include <stddef.h>
+
+// Declared outside the push/pop region, but defined inside it
+struct forward_packed;
+
+#pragma pack(push, 2)
+
+struct packed {
+    char c;
+    int i;
+};
+
+struct forward_packed {
+    char c;
+    long l;
+};
+
+#pragma pack(pop)
+
+// Back to the default packing after the pop
+struct unpacked {
+    char c;
+    int i;
+};
+
+void pragma_pack(const unsigned int buffer_size, int buffer[const]) {
+    if (buffer_size < 6) return;
+
+    buffer[0] = sizeof(struct packed);
+    buffer[1] = offsetof(struct packed, i);
+    buffer[2] = sizeof(struct forward_packed);
+    buffer[3] = offsetof(struct forward_packed, l);
+    buffer[4] = sizeof(struct unpacked);
+    buffer[5] = offsetof(struct unpacked, i);
+}
@@ -0,0 +1,48 @@
+//! extern_crate_memoffset
+
+extern crate libc;
+
+use container_of::{item, rust_item_offsets, rust_sum_list};
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn sum_list(_: c_uint, _: *mut c_int) -> c_int;
+
+    #[no_mangle]
+    fn item_offsets(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 4;
+
+pub fn test_intrusive_list() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [1, 11, 21, 31];
+
+    let (sum, rust_sum) = unsafe {
+        (
+            sum_list(BUFFER_SIZE as c_uint, buffer.as_mut_ptr()),
+            rust_sum_list(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr()),
+        )
+    };
+
+    assert_eq!(sum, rust_sum);
+    assert_eq!(sum, 64);
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_item_offsets() {
+    let mut buffer = [0; 3];
+    let mut rust_buffer = [0; 3];
+
+    unsafe {
+        item_offsets(3, buffer.as_mut_ptr());
+        rust_item_offsets(3, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(rust_buffer[2] as usize, offset_of!(item, link));
+}
@@ -0,0 +1,40 @@
+extern crate libc;
+
+use pragma_pack::{forward_packed, packed, rust_pragma_pack, unpacked};
+use self::libc::{c_int, c_uint};
+use std::mem::{align_of, size_of};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn pragma_pack(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 6;
+
+pub fn test_pragma_pack() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [6, 2, 10, 2, 8, 4];
+
+    unsafe {
+        pragma_pack(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_pragma_pack(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+
+    // Only the structs defined between the push and the pop are packed
+    assert_eq!(size_of::<packed>(), 6);
+    assert_eq!(align_of::<packed>(), 2);
+    assert_eq!(size_of::<forward_packed>(), 10);
+    assert_eq!(align_of::<forward_packed>(), 2);
+    assert_eq!(size_of::<unpacked>(), 8);
+    assert_eq!(align_of::<unpacked>(), 4);
+
+    let src = include_str!("pragma_pack.rs");
+    assert!(src.contains("#[repr(C, packed(2))]\npub struct packed "));
+    assert!(src.contains("#[repr(C, packed(2))]\npub struct forward_packed "));
+    assert!(src.contains("#[repr(C)]\npub struct unpacked "));
+}
@@ -0,0 +1,57 @@
+extern crate libc;
+
+use self::libc::{c_double, c_int, c_uint};
+use std::mem::transmute;
+use transparent::{handle, meters, rust_deref_handle, rust_scale_meters, rust_transparent};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn transparent(_: c_uint, _: *mut c_int);
+
+    // Declared with the types the wrappers hold, as they are ABI-identical
+    #[no_mangle]
+    fn scale_meters(_: c_double, _: c_double) -> c_double;
+
+    #[no_mangle]
+    fn deref_handle(_: *mut c_int) -> c_int;
+}
+
+const BUFFER_SIZE: usize = 4;
+
+pub fn test_transparent() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [10, 7, 16, 3];
+
+    unsafe {
+        transparent(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_transparent(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+
+    let src = include_str!("transparent.rs");
+    assert!(src.contains("#[repr(transparent)]\npub struct meters "));
+    assert!(src.contains("#[repr(transparent)]\npub struct handle "));
+    assert!(src.contains("#[repr(C)]\npub struct empty "));
+    assert!(src.contains("#[repr(C)]\npub struct pair "));
+}
+
+pub fn test_transparent_ffi() {
+    let mut x = 5;
+
+    unsafe {
+        // The translated functions can be called with the inner types
+        let rust_scale_meters: unsafe extern "C" fn(c_double, c_double) -> c_double =
+            transmute(rust_scale_meters as unsafe extern "C" fn(meters, c_double) -> meters);
+        let rust_deref_handle: unsafe extern "C" fn(*mut c_int) -> c_int =
+            transmute(rust_deref_handle as unsafe extern "C" fn(handle) -> c_int);
+
+        assert_eq!(scale_meters(1.5, 2.0), 3.0);
+        assert_eq!(rust_scale_meters(1.5, 2.0), 3.0);
+        assert_eq!(deref_handle(&mut x), 5);
+        assert_eq!(rust_deref_handle(&mut x), 5);
+    }
+}
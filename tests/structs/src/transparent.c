@@ -0,0 +1,43 @@
+//! emit_repr_transparent
+
+// A newtype wrapper, which is passed like a bare double
+struct meters {
+    double value;
+};
+
+struct handle {
+    int *ptr;
+};
+
+// Not wrappers: zero-sized, or with several fields
+struct empty {
+    int none[0];
+};
+
+struct pair {
+    int a;
+    int b;
+};
+
+struct meters scale_meters(struct meters m, double k) {
+    struct meters scaled = { m.value * k };
+    return scaled;
+}
+
+int deref_handle(struct handle h) {
+    return *h.ptr;
+}
+
+void transparent(const unsigned int buffer_size, int buffer[const]) {
+    if (buffer_size < 4) return;
+
+    int x = 7;
+    struct handle h = { &x };
+    struct meters m = scale_meters((struct meters){ 2.5 }, 4.0);
+    struct pair p = { 1, 2 };
+
+    buffer[0] = (int)m.value;
+    buffer[1] = deref_handle(h);
+    buffer[2] = sizeof(struct meters) + sizeof(struct handle);
+    buffer[3] = p.a + p.b + sizeof(struct empty);
+}
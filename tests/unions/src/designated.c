@@ -0,0 +1,34 @@
+union number {
+    char tag;
+    int small;
+    double real;
+    long long big;
+};
+
+union bytes {
+    unsigned char byte;
+    unsigned int word;
+    unsigned char raw[8];
+};
+
+void designated_union(const unsigned int buffer_size, int buffer[const])
+{
+    int i = 0;
+
+    // Designators name members other than the first one
+    union number n = { .real = 1.5 };
+    union number m = { .small = -3 };
+    union bytes b = { .word = 0x01020304 };
+
+    if (buffer_size < 6)
+        return;
+
+    buffer[i++] = n.real == 1.5;
+    buffer[i++] = (int)(n.real * 4);
+    buffer[i++] = m.small;
+    buffer[i++] = b.word == 0x01020304;
+    buffer[i++] = b.raw[0] + b.raw[1] + b.raw[2] + b.raw[3];
+
+    // The bytes past the initialized member are zero
+    buffer[i++] = b.raw[4] + b.raw[5] + b.raw[6] + b.raw[7];
+}
@@ -0,0 +1,26 @@
+extern crate libc;
+
+use designated::rust_designated_union;
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn designated_union(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 6;
+
+pub fn test_designated_union() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [1, 6, -3, 1, 10, 0];
+
+    unsafe {
+        designated_union(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_designated_union(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}